@@ -0,0 +1,127 @@
+//! Syncs of aws-auth holding a few thousand entries, e.q: `cargo bench --bench aws_auth`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use iam_eks_user_mapper::kubernetes::aws_auth::AwsAuthBuilder;
+use iam_eks_user_mapper::kubernetes::{
+    ConfigMapOptions, IamArn, IamUserName, KubernetesGroupName, KubernetesRole, KubernetesService,
+    KubernetesUser, SyncedBy,
+};
+use k8s_openapi::api::core::v1::ConfigMap;
+use std::collections::{BTreeMap, BTreeSet};
+
+const ENTRIES_COUNTS: [usize; 2] = [1_000, 5_000];
+
+fn user(i: usize, groups: &[&str]) -> KubernetesUser {
+    KubernetesUser::new(
+        IamUserName::new(&format!("User-{i}")),
+        IamArn::new(&format!("arn:aws:iam::123456789012:user/ops/User-{i}")),
+        BTreeSet::from_iter(groups.iter().map(|g| KubernetesGroupName::new(g))),
+        Some(SyncedBy::IamEksUserMapper),
+    )
+}
+
+fn role(i: usize) -> KubernetesRole {
+    KubernetesRole::new(
+        IamArn::new(&format!("arn:aws:iam::123456789012:role/Role-{i}")),
+        None,
+        Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+        BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+        Some(SyncedBy::IamEksUserMapper),
+    )
+}
+
+/// Users `0..count` previously synced, a few of them hand-edited into duplicated entries, with half the roles.
+fn config_map(count: usize) -> ConfigMap {
+    let mut map_users = String::new();
+    for i in 0..count {
+        map_users.push_str(&format!(
+            "- userarn: arn:aws:iam::123456789012:user/ops/User-{i}\n  username: User-{i}\n  groups:\n  - view\n  syncedBy: iam-eks-user-mapper\n"
+        ));
+        if i % 100 == 0 {
+            map_users.push_str(&format!(
+                "- userarn: arn:aws:iam::123456789012:user/ops/user-{i}\n  username: User-{i}\n  groups:\n  - edit\n"
+            ));
+        }
+    }
+    let mut map_roles = String::new();
+    for i in 0..count / 2 {
+        map_roles.push_str(&format!(
+            "- rolearn: arn:aws:iam::123456789012:role/Role-{i}\n  username: system:node:{{{{EC2PrivateDNSName}}}}\n  groups:\n  - system:nodes\n  syncedBy: iam-eks-user-mapper\n"
+        ));
+    }
+
+    ConfigMap {
+        data: Some(BTreeMap::from_iter(vec![
+            ("mapUsers".to_string(), map_users),
+            ("mapRoles".to_string(), map_roles),
+        ])),
+        ..Default::default()
+    }
+}
+
+/// Users shifted by a tenth of `count`, some being added and others removed, every other one changing groups.
+fn desired_users(count: usize) -> BTreeSet<KubernetesUser> {
+    (count / 10..count + count / 10)
+        .map(|i| match i % 2 {
+            0 => user(i, &["view"]),
+            _ => user(i, &["edit", "view"]),
+        })
+        .collect()
+}
+
+fn build_with_report(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aws_auth_build_with_report");
+    for count in ENTRIES_COUNTS {
+        let existing = KubernetesService::parse_aws_auth(&config_map(count))
+            .expect("benchmark config map should be valid");
+        let desired = desired_users(count);
+        let roles: Vec<KubernetesRole> = (0..count).map(role).collect();
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| {
+                AwsAuthBuilder::new(
+                    existing.users.iter().cloned(),
+                    existing.roles.iter().cloned(),
+                    SyncedBy::IamEksUserMapper,
+                )
+                .new_synced_users(desired.iter().cloned())
+                .new_synced_roles(roles.iter().cloned())
+                .build_with_report()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn merge_config_map(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge_config_map_with_report");
+    for count in ENTRIES_COUNTS {
+        let config_map = config_map(count);
+        let desired = desired_users(count);
+        let roles: Vec<KubernetesRole> = (0..count).map(role).collect();
+        let options = ConfigMapOptions::default();
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter_batched(
+                || (config_map.clone(), desired.clone()),
+                |(mut config_map, desired)| {
+                    KubernetesService::merge_config_map_with_report(
+                        &mut config_map,
+                        Some(desired),
+                        None,
+                        &[],
+                        &roles,
+                        &BTreeSet::new(),
+                        &options,
+                    )
+                    .expect("benchmark config map should be merged")
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build_with_report, merge_config_map);
+criterion_main!(benches);
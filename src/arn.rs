@@ -0,0 +1,366 @@
+//! ARNs split into their parts, e.q: partition and account of `arn:aws-cn:iam::123456789012:role/ops/ci`.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Clone, Debug, Eq, PartialEq)]
+pub enum ArnError {
+    #[error("Invalid ARN `{raw_arn}`: {raw_message}, should be: `arn:<PARTITION>:<SERVICE>:<REGION>:<ACCOUNT_ID>:<RESOURCE>`")]
+    InvalidArn {
+        raw_arn: Arc<str>,
+        raw_message: Arc<str>,
+    },
+}
+
+/// ARN of any AWS resource, IAM ones having no region and a `<TYPE>/<PATH>/<NAME>` resource, e.q:
+/// `arn:aws:iam::123456789012:user/ops/alice`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Arn {
+    partition: String,
+    service: String,
+    region: String,
+    account_id: String,
+    // `None` for resources without `/`, e.q: `arn:aws:s3:::bucket`, the whole resource being the name
+    resource_type: Option<String>,
+    // `/` delimited, `/` for the root path
+    resource_path: String,
+    resource_name: String,
+}
+
+impl Arn {
+    /// Partition, e.q: `aws`, `aws-cn` or `aws-us-gov`.
+    pub fn partition(&self) -> &str {
+        &self.partition
+    }
+
+    /// Service namespace, e.q: `iam`.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Region, empty for global services such as IAM.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Account ID, e.q: `123456789012`, `aws` for AWS managed resources or empty for some services, e.q: S3.
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    /// Resource type, e.q: `user`, `role` or `instance-profile`.
+    pub fn resource_type(&self) -> Option<&str> {
+        self.resource_type.as_deref()
+    }
+
+    /// Resource path as IAM sets it, e.q: `/ops/` for `arn:aws:iam::123456789012:user/ops/alice`, `/` for the root path.
+    pub fn resource_path(&self) -> &str {
+        &self.resource_path
+    }
+
+    /// Resource name without its path, e.q: `alice` for `arn:aws:iam::123456789012:user/ops/alice`.
+    pub fn resource_name(&self) -> &str {
+        &self.resource_name
+    }
+
+    /// Same ARN at the root path, e.q: `arn:aws:iam::123456789012:user/alice` for `arn:aws:iam::123456789012:user/ops/alice`.
+    pub fn without_path(&self) -> Arn {
+        Arn {
+            resource_path: "/".to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+impl FromStr for Arn {
+    type Err = ArnError;
+
+    /// Parses `arn:<PARTITION>:<SERVICE>:<REGION>:<ACCOUNT_ID>:<RESOURCE>`, the resource possibly holding `:`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |raw_message: String| ArnError::InvalidArn {
+            raw_arn: Arc::from(s),
+            raw_message: Arc::from(raw_message),
+        };
+        let is_name_part = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+
+        let (partition, service, region, account_id, resource) =
+            match s.splitn(6, ':').collect::<Vec<_>>().as_slice() {
+                ["arn", partition, service, region, account_id, resource] => {
+                    (*partition, *service, *region, *account_id, *resource)
+                }
+                [prefix, ..] if *prefix != "arn" => {
+                    return Err(invalid("should start with `arn:`".to_string()))
+                }
+                _ => return Err(invalid("missing parts".to_string())),
+            };
+        if !partition.starts_with("aws") || !partition.chars().all(is_name_part) {
+            return Err(invalid(format!(
+                "invalid partition `{partition}`, e.q: `aws`, `aws-cn` or `aws-us-gov`"
+            )));
+        }
+        if service.is_empty() || !service.chars().all(is_name_part) {
+            return Err(invalid(format!("invalid service `{service}`")));
+        }
+        if !region.chars().all(is_name_part) {
+            return Err(invalid(format!("invalid region `{region}`")));
+        }
+        // length left to callers, e.q: IAM ARNs set in the configuration being reported as such
+        if account_id != "aws" && !account_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid(format!(
+                "invalid account ID `{account_id}`, should be a number"
+            )));
+        }
+        if resource.is_empty() {
+            return Err(invalid("empty resource".to_string()));
+        }
+
+        let (resource_type, resource_path, resource_name) = match resource.split_once('/') {
+            None => (None, String::new(), resource),
+            Some(("", _)) => return Err(invalid("empty resource type".to_string())),
+            Some((resource_type, path_and_name)) => match path_and_name.rsplit_once('/') {
+                None => (Some(resource_type), "/".to_string(), path_and_name),
+                Some((path, name)) => (Some(resource_type), format!("/{path}/"), name),
+            },
+        };
+
+        Ok(Arn {
+            partition: partition.to_string(),
+            service: service.to_string(),
+            region: region.to_string(),
+            account_id: account_id.to_string(),
+            resource_type: resource_type.map(str::to_string),
+            resource_path,
+            resource_name: resource_name.to_string(),
+        })
+    }
+}
+
+impl Display for Arn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "arn:{}:{}:{}:{}:",
+            self.partition, self.service, self.region, self.account_id
+        )?;
+        match &self.resource_type {
+            Some(resource_type) => write!(
+                f,
+                "{resource_type}{}{}",
+                self.resource_path, self.resource_name
+            ),
+            None => f.write_str(&self.resource_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arn::{Arn, ArnError};
+    use std::sync::Arc;
+
+    #[test]
+    fn arn_from_str_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: &'a str,
+            expected: (&'a str, &'a str, &'a str, Option<&'a str>, &'a str, &'a str),
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "arn:aws:iam::123456789012:user/alice",
+                expected: ("aws", "iam", "123456789012", Some("user"), "/", "alice"),
+                _description: "case 1 - user",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:user/ops/eu/alice.doe@example.com",
+                expected: ("aws", "iam", "123456789012", Some("user"), "/ops/eu/", "alice.doe@example.com"),
+                _description: "case 2 - user with path",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:role/ci",
+                expected: ("aws", "iam", "123456789012", Some("role"), "/", "ci"),
+                _description: "case 3 - role",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac",
+                expected: ("aws", "iam", "123456789012", Some("role"), "/aws-reserved/sso.amazonaws.com/eu-west-3/", "AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac"),
+                _description: "case 4 - SSO role",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:instance-profile/karpenter",
+                expected: ("aws", "iam", "123456789012", Some("instance-profile"), "/", "karpenter"),
+                _description: "case 5 - instance profile",
+            },
+            TestCase {
+                input: "arn:aws-cn:iam::123456789012:user/ops/alice",
+                expected: ("aws-cn", "iam", "123456789012", Some("user"), "/ops/", "alice"),
+                _description: "case 6 - China partition user",
+            },
+            TestCase {
+                input: "arn:aws-cn:iam::123456789012:role/nodes",
+                expected: ("aws-cn", "iam", "123456789012", Some("role"), "/", "nodes"),
+                _description: "case 7 - China partition role",
+            },
+            TestCase {
+                input: "arn:aws-cn:iam::123456789012:instance-profile/eks/karpenter",
+                expected: ("aws-cn", "iam", "123456789012", Some("instance-profile"), "/eks/", "karpenter"),
+                _description: "case 8 - China partition instance profile",
+            },
+            TestCase {
+                input: "arn:aws-us-gov:iam::123456789012:user/alice",
+                expected: ("aws-us-gov", "iam", "123456789012", Some("user"), "/", "alice"),
+                _description: "case 9 - GovCloud partition user",
+            },
+            TestCase {
+                input: "arn:aws-us-gov:iam::123456789012:role/aws-reserved/sso.amazonaws.com/us-gov-west-1/AWSReservedSSO_PowerUserAccess_1234567890abcdef",
+                expected: ("aws-us-gov", "iam", "123456789012", Some("role"), "/aws-reserved/sso.amazonaws.com/us-gov-west-1/", "AWSReservedSSO_PowerUserAccess_1234567890abcdef"),
+                _description: "case 10 - GovCloud partition SSO role",
+            },
+            TestCase {
+                input: "arn:aws-us-gov:iam::123456789012:instance-profile/karpenter",
+                expected: ("aws-us-gov", "iam", "123456789012", Some("instance-profile"), "/", "karpenter"),
+                _description: "case 11 - GovCloud partition instance profile",
+            },
+            TestCase {
+                input: "arn:aws:eks::aws:cluster-access-policy/AmazonEKSClusterAdminPolicy",
+                expected: ("aws", "eks", "aws", Some("cluster-access-policy"), "/", "AmazonEKSClusterAdminPolicy"),
+                _description: "case 12 - AWS managed resource",
+            },
+            TestCase {
+                input: "arn:aws:s3:::bucket",
+                expected: ("aws", "s3", "", None, "", "bucket"),
+                _description: "case 13 - resource without type",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let arn = tc
+                .input
+                .parse::<Arn>()
+                .unwrap_or_else(|e| panic!("{}: {e}", tc._description));
+
+            // verify:
+            assert_eq!(
+                tc.expected,
+                (
+                    arn.partition(),
+                    arn.service(),
+                    arn.account_id(),
+                    arn.resource_type(),
+                    arn.resource_path(),
+                    arn.resource_name()
+                ),
+                "{}",
+                tc._description
+            );
+            assert_eq!(tc.input, arn.to_string(), "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn arn_from_str_malformed_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: &'a str,
+            expected_message: &'a str,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "",
+                expected_message: "should start with `arn:`",
+                _description: "case 1 - empty",
+            },
+            TestCase {
+                input: "AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac",
+                expected_message: "should start with `arn:`",
+                _description: "case 2 - role name",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012",
+                expected_message: "missing parts",
+                _description: "case 3 - missing resource",
+            },
+            TestCase {
+                input: "arn:gcp:iam::123456789012:user/alice",
+                expected_message: "invalid partition `gcp`, e.q: `aws`, `aws-cn` or `aws-us-gov`",
+                _description: "case 4 - unknown partition",
+            },
+            TestCase {
+                input: "arn:AWS:iam::123456789012:user/alice",
+                expected_message: "invalid partition `AWS`, e.q: `aws`, `aws-cn` or `aws-us-gov`",
+                _description: "case 5 - uppercase partition",
+            },
+            TestCase {
+                input: "arn:aws:::123456789012:user/alice",
+                expected_message: "invalid service ``",
+                _description: "case 6 - empty service",
+            },
+            TestCase {
+                input: "arn:aws:iam:eu west:123456789012:user/alice",
+                expected_message: "invalid region `eu west`",
+                _description: "case 7 - invalid region",
+            },
+            TestCase {
+                input: "arn:aws:iam::ops:user/alice",
+                expected_message: "invalid account ID `ops`, should be a number",
+                _description: "case 8 - account alias",
+            },
+            TestCase {
+                input: "arn:aws:iam::12345678901a:user/alice",
+                expected_message: "invalid account ID `12345678901a`, should be a number",
+                _description: "case 9 - non numeric account ID",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:",
+                expected_message: "empty resource",
+                _description: "case 10 - empty resource",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:/alice",
+                expected_message: "empty resource type",
+                _description: "case 11 - empty resource type",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = tc.input.parse::<Arn>();
+
+            // verify:
+            assert_eq!(
+                Err(ArnError::InvalidArn {
+                    raw_arn: Arc::from(tc.input),
+                    raw_message: Arc::from(tc.expected_message),
+                }),
+                res,
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn arn_without_path_test() {
+        // setup:
+        let arn: Arn = "arn:aws-cn:iam::123456789012:role/aws-reserved/sso.amazonaws.com/cn-north-1/AWSReservedSSO_ViewOnlyAccess_fedcba9876543210"
+            .parse()
+            .expect("valid ARN");
+
+        // execute:
+        let res = arn.without_path();
+
+        // verify:
+        assert_eq!(
+            "arn:aws-cn:iam::123456789012:role/AWSReservedSSO_ViewOnlyAccess_fedcba9876543210",
+            res.to_string()
+        );
+        assert_eq!("/", res.resource_path());
+    }
+}
@@ -0,0 +1,370 @@
+use crate::export::{ExportedRole, ExportedUser};
+use crate::kubernetes::aws_auth::AwsAuthDiff;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Cannot serialize audit record to JSON: {raw_message}")]
+    CannotSerialize { raw_message: Arc<str> },
+    #[error("Cannot write audit record to `{path}`: {raw_message}")]
+    CannotWrite {
+        path: Arc<str>,
+        raw_message: Arc<str>,
+    },
+}
+
+/// Users and roles entries of an audit record, sorted by ARN.
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+pub struct AuditedEntries {
+    pub users: Vec<ExportedUser>,
+    pub roles: Vec<ExportedRole>,
+}
+
+impl AuditedEntries {
+    pub fn added(diff: &AwsAuthDiff) -> AuditedEntries {
+        AuditedEntries {
+            users: diff.users.added.iter().map(ExportedUser::from).collect(),
+            roles: diff.roles.added.iter().map(ExportedRole::from).collect(),
+        }
+    }
+
+    pub fn removed(diff: &AwsAuthDiff) -> AuditedEntries {
+        AuditedEntries {
+            users: diff.users.removed.iter().map(ExportedUser::from).collect(),
+            roles: diff.roles.removed.iter().map(ExportedRole::from).collect(),
+        }
+    }
+}
+
+/// Entry whose content changed, e.q: groups granted to a user.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct AuditedChange<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// Modified users and roles entries of an audit record, sorted by ARN.
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+pub struct AuditedChanges {
+    pub users: Vec<AuditedChange<ExportedUser>>,
+    pub roles: Vec<AuditedChange<ExportedRole>>,
+}
+
+impl AuditedChanges {
+    pub fn modified(diff: &AwsAuthDiff) -> AuditedChanges {
+        AuditedChanges {
+            users: diff
+                .users
+                .modified
+                .iter()
+                .map(|(before, after)| AuditedChange {
+                    before: ExportedUser::from(before),
+                    after: ExportedUser::from(after),
+                })
+                .collect(),
+            roles: diff
+                .roles
+                .modified
+                .iter()
+                .map(|(before, after)| AuditedChange {
+                    before: ExportedRole::from(before),
+                    after: ExportedRole::from(after),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Single aws-auth change, written as one JSON line of the audit log.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    #[serde(rename = "toolVersion")]
+    pub tool_version: String,
+    /// `<namespace>/<name>` of the changed config map.
+    #[serde(rename = "configMap")]
+    pub config_map: String,
+    #[serde(rename = "resourceVersionBefore")]
+    pub resource_version_before: Option<String>,
+    #[serde(rename = "resourceVersionAfter")]
+    pub resource_version_after: Option<String>,
+    pub added: AuditedEntries,
+    pub removed: AuditedEntries,
+    pub modified: AuditedChanges,
+}
+
+impl AuditRecord {
+    pub fn new(
+        config_map: &str,
+        diff: &AwsAuthDiff,
+        resource_version_before: Option<String>,
+        resource_version_after: Option<String>,
+        timestamp: SystemTime,
+    ) -> AuditRecord {
+        // diff entries are already sorted by ARN
+        AuditRecord {
+            timestamp: humantime::format_rfc3339_seconds(timestamp).to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_map: config_map.to_string(),
+            resource_version_before,
+            resource_version_after,
+            added: AuditedEntries::added(diff),
+            removed: AuditedEntries::removed(diff),
+            modified: AuditedChanges::modified(diff),
+        }
+    }
+
+    /// Single line JSON, new lines being escaped by JSON strings encoding.
+    pub fn to_json_line(&self) -> Result<String, AuditError> {
+        serde_json::to_string(self)
+            .map(|json| format!("{json}\n"))
+            .map_err(|e| AuditError::CannotSerialize {
+                raw_message: Arc::from(e.to_string()),
+            })
+    }
+}
+
+/// Append-only file recording every aws-auth change, one JSON record per line.
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    pub path: PathBuf,
+    /// Sync fails if a record cannot be written.
+    pub required: bool,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, required: bool) -> AuditLog {
+        AuditLog { path, required }
+    }
+
+    /// Appends `record` in a single write, then flushes it to disk, so the log never holds a partial line
+    /// nor loses a record on crash.
+    pub fn append(&self, record: &AuditRecord) -> Result<(), AuditError> {
+        let line = record.to_json_line()?;
+        let cannot_write = |e: std::io::Error| AuditError::CannotWrite {
+            path: Arc::from(self.path.to_string_lossy().as_ref()),
+            raw_message: Arc::from(e.to_string()),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(cannot_write)?;
+        file.write_all(line.as_bytes()).map_err(cannot_write)?;
+        file.sync_all().map_err(cannot_write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditLog, AuditRecord};
+    use crate::kubernetes::aws_auth::{AwsAuthDiff, EntriesDiff};
+    use crate::kubernetes::tests::iam_user;
+    use crate::kubernetes::{IamArn, KubernetesGroupName, KubernetesRole, SyncedBy};
+    use std::collections::BTreeSet;
+    use std::time::{Duration, SystemTime};
+
+    fn diff() -> AwsAuthDiff {
+        AwsAuthDiff {
+            users: EntriesDiff {
+                added: vec![iam_user("alice", &["system:masters"])],
+                removed: vec![iam_user("bob", &["view"])],
+                modified: vec![(
+                    iam_user("carol", &["view"]),
+                    iam_user("carol", &["edit", "view"]),
+                )],
+                untouched: vec![iam_user("dave", &["view"])],
+                unchanged: vec![],
+                suppressed: vec![],
+            },
+            roles: EntriesDiff {
+                added: vec![KubernetesRole::new(
+                    IamArn::new("arn:aws:iam::123456789012:role/karpenter"),
+                    None,
+                    Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("system:nodes"),
+                        KubernetesGroupName::new("system:bootstrappers"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )],
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn audit_record_json_schema_test() {
+        // setup:
+        let record = AuditRecord::new(
+            "kube-system/aws-auth",
+            &diff(),
+            Some("41".to_string()),
+            Some("42".to_string()),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+
+        // execute:
+        let line = record
+            .to_json_line()
+            .expect("record should be serializable");
+
+        // verify:
+        assert!(line.ends_with('\n'));
+        assert_eq!(1, line.lines().count());
+        let parsed: serde_json::Value =
+            serde_json::from_str(&line).expect("record should be valid JSON");
+        assert_eq!(
+            serde_json::json!({
+                "timestamp": "2023-11-14T22:13:20Z",
+                "toolVersion": env!("CARGO_PKG_VERSION"),
+                "configMap": "kube-system/aws-auth",
+                "resourceVersionBefore": "41",
+                "resourceVersionAfter": "42",
+                "added": {
+                    "users": [
+                        {
+                            "username": "alice",
+                            "arn": "arn:aws:iam::123456789012:user/alice",
+                            "groups": ["system:masters"],
+                            "syncedBy": "iam-eks-user-mapper",
+                        },
+                    ],
+                    "roles": [
+                        {
+                            "arn": "arn:aws:iam::123456789012:role/karpenter",
+                            "rolename": null,
+                            "username": "system:node:{{EC2PrivateDNSName}}",
+                            "groups": ["system:bootstrappers", "system:nodes"],
+                            "syncedBy": "iam-eks-user-mapper",
+                        },
+                    ],
+                },
+                "removed": {
+                    "users": [
+                        {
+                            "username": "bob",
+                            "arn": "arn:aws:iam::123456789012:user/bob",
+                            "groups": ["view"],
+                            "syncedBy": "iam-eks-user-mapper",
+                        },
+                    ],
+                    "roles": [],
+                },
+                "modified": {
+                    "users": [
+                        {
+                            "before": {
+                                "username": "carol",
+                                "arn": "arn:aws:iam::123456789012:user/carol",
+                                "groups": ["view"],
+                                "syncedBy": "iam-eks-user-mapper",
+                            },
+                            "after": {
+                                "username": "carol",
+                                "arn": "arn:aws:iam::123456789012:user/carol",
+                                "groups": ["edit", "view"],
+                                "syncedBy": "iam-eks-user-mapper",
+                            },
+                        },
+                    ],
+                    "roles": [],
+                },
+            }),
+            parsed
+        );
+    }
+
+    #[test]
+    fn audit_record_created_config_map_json_schema_test() {
+        // setup:
+        let record = AuditRecord::new(
+            "kube-system/aws-auth",
+            &AwsAuthDiff::default(),
+            None,
+            Some("1".to_string()),
+            SystemTime::UNIX_EPOCH,
+        );
+
+        // execute:
+        let line = record
+            .to_json_line()
+            .expect("record should be serializable");
+
+        // verify:
+        let parsed: serde_json::Value =
+            serde_json::from_str(&line).expect("record should be valid JSON");
+        assert_eq!(serde_json::Value::Null, parsed["resourceVersionBefore"]);
+        assert_eq!(
+            serde_json::json!({"users": [], "roles": []}),
+            parsed["added"]
+        );
+    }
+
+    #[test]
+    fn audit_log_append_test() {
+        // setup:
+        let dir =
+            std::env::temp_dir().join(format!("iam-eks-user-mapper-audit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("cannot create temp dir");
+        let audit_log = AuditLog::new(dir.join("audit.log"), true);
+        let record = |resource_version: &str| {
+            AuditRecord::new(
+                "kube-system/aws-auth",
+                &diff(),
+                None,
+                Some(resource_version.to_string()),
+                SystemTime::UNIX_EPOCH,
+            )
+        };
+
+        // execute:
+        let first = audit_log.append(&record("1"));
+        let second = audit_log.append(&record("2"));
+
+        // verify:
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        let written = std::fs::read_to_string(&audit_log.path).expect("audit log should exist");
+        assert_eq!(
+            format!(
+                "{}{}",
+                record("1").to_json_line().unwrap(),
+                record("2").to_json_line().unwrap()
+            ),
+            written
+        );
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+
+    #[test]
+    fn audit_log_append_error_test() {
+        // setup:
+        let audit_log = AuditLog::new(
+            std::env::temp_dir()
+                .join("iam-eks-user-mapper-audit-missing-dir")
+                .join("audit.log"),
+            true,
+        );
+
+        // execute:
+        let res = audit_log.append(&AuditRecord::new(
+            "kube-system/aws-auth",
+            &diff(),
+            None,
+            None,
+            SystemTime::UNIX_EPOCH,
+        ));
+
+        // verify:
+        assert!(res.is_err());
+    }
+}
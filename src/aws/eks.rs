@@ -0,0 +1,1895 @@
+use crate::arn::Arn;
+use crate::aws::{is_retryable_sdk_error, AwsSdkConfig};
+use crate::kubernetes::aws_auth::{AwsAuth, AwsAuthDiff};
+use crate::kubernetes::{
+    AuthBackend, AwsAccountId, ClusterEndpoint, ConfigMapOptions, ConfigMapUpdate,
+    ConfigMapUpdateStatus, IamArn, IamUserName, KubernetesError, KubernetesGroupName,
+    KubernetesRole, KubernetesUser, SyncedBy,
+};
+use aws_sdk_eks::types::{AccessScope, AccessScopeType};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// Tag holding the marker of the instance which created an access entry, entries without it being left untouched.
+const SYNCED_BY_TAG: &str = "iam-eks-user-mapper/synced-by";
+/// Tag set on access entries the cluster admin access policy is associated to, i.e: mapped to `system:masters`.
+const CLUSTER_ADMIN_TAG: &str = "iam-eks-user-mapper/cluster-admin";
+/// Group granted through the cluster admin access policy, EKS rejecting `system:` groups on access entries.
+const MASTERS_GROUP: &str = "system:masters";
+/// Groups granted through an `EC2_LINUX` access entry, EKS rejecting `system:` groups on access entries.
+const NODE_GROUPS: [&str; 2] = ["system:bootstrappers", "system:nodes"];
+/// Tag set on access entries created by `migrate-to-access-entries`, telling already migrated entries apart on re-runs.
+const MIGRATED_FROM_TAG: &str = "iam-eks-user-mapper/migrated-from";
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum EksError {
+    #[error("Cannot list access entries: {raw_message}")]
+    CannotListAccessEntries {
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot describe access entry `{principal_arn}`: {raw_message}")]
+    CannotDescribeAccessEntry {
+        principal_arn: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot create access entry `{principal_arn}`: {raw_message}")]
+    CannotCreateAccessEntry {
+        principal_arn: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot update access entry `{principal_arn}`: {raw_message}")]
+    CannotUpdateAccessEntry {
+        principal_arn: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot delete access entry `{principal_arn}`: {raw_message}")]
+    CannotDeleteAccessEntry {
+        principal_arn: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot describe cluster `{cluster_name}`: {raw_message}")]
+    CannotDescribeCluster {
+        cluster_name: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot list nodegroups: {raw_message}")]
+    CannotListNodegroups {
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot describe nodegroup `{nodegroup_name}`: {raw_message}")]
+    CannotDescribeNodegroup {
+        nodegroup_name: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot associate access policy `{policy_arn}` to access entry `{principal_arn}`: {raw_message}")]
+    CannotAssociateAccessPolicy {
+        principal_arn: Arc<str>,
+        policy_arn: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+}
+
+impl EksError {
+    /// Whether the failing request might succeed once retried, see [`is_retryable_error`].
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            EksError::CannotListAccessEntries { retryable, .. }
+            | EksError::CannotDescribeAccessEntry { retryable, .. }
+            | EksError::CannotCreateAccessEntry { retryable, .. }
+            | EksError::CannotUpdateAccessEntry { retryable, .. }
+            | EksError::CannotDeleteAccessEntry { retryable, .. }
+            | EksError::CannotDescribeCluster { retryable, .. }
+            | EksError::CannotListNodegroups { retryable, .. }
+            | EksError::CannotDescribeNodegroup { retryable, .. }
+            | EksError::CannotAssociateAccessPolicy { retryable, .. } => *retryable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AccessEntryType {
+    #[default]
+    Standard,
+    Ec2Linux,
+    /// Types never created by the tool, e.q: `FARGATE_LINUX` or `EC2_WINDOWS`.
+    Other,
+}
+
+impl AccessEntryType {
+    pub fn new(value: &str) -> AccessEntryType {
+        match value {
+            "STANDARD" => AccessEntryType::Standard,
+            "EC2_LINUX" => AccessEntryType::Ec2Linux,
+            _ => AccessEntryType::Other,
+        }
+    }
+
+    /// Type as named by EKS API, e.q: `EC2_LINUX`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AccessEntryType::Standard => "STANDARD",
+            AccessEntryType::Ec2Linux => "EC2_LINUX",
+            AccessEntryType::Other => "OTHER",
+        }
+    }
+}
+
+/// EKS access entry, as exchanged with EKS API.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AccessEntry {
+    pub principal_arn: String,
+    pub entry_type: AccessEntryType,
+    pub kubernetes_groups: BTreeSet<String>,
+    pub username: Option<String>,
+    pub tags: BTreeMap<String, String>,
+}
+
+impl From<&aws_sdk_eks::types::AccessEntry> for AccessEntry {
+    fn from(value: &aws_sdk_eks::types::AccessEntry) -> Self {
+        AccessEntry {
+            principal_arn: value.principal_arn().unwrap_or_default().to_string(),
+            entry_type: value.r#type().map(AccessEntryType::new).unwrap_or_default(),
+            kubernetes_groups: value.kubernetes_groups().iter().cloned().collect(),
+            username: value.username().map(str::to_string),
+            tags: value
+                .tags()
+                .map(|tags| tags.clone().into_iter().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl AccessEntry {
+    /// Access entry granting `groups` to `principal_arn` the way aws-auth does: node groups are granted through an `EC2_LINUX` entry
+    /// and `system:masters` through the cluster admin access policy, other `system:` groups cannot be granted.
+    pub fn synced(
+        principal_arn: &IamArn,
+        username: Option<String>,
+        groups: &BTreeSet<KubernetesGroupName>,
+        synced_by: &SyncedBy,
+    ) -> AccessEntry {
+        let mut access_entry = AccessEntry {
+            principal_arn: principal_arn.to_string(),
+            username,
+            tags: BTreeMap::from_iter(vec![(SYNCED_BY_TAG.to_string(), synced_by.to_string())]),
+            ..Default::default()
+        };
+
+        for group in groups.iter().map(|g| g.to_string()) {
+            match group.as_str() {
+                MASTERS_GROUP => {
+                    access_entry
+                        .tags
+                        .insert(CLUSTER_ADMIN_TAG.to_string(), "true".to_string());
+                }
+                g if NODE_GROUPS.contains(&g) => access_entry.entry_type = AccessEntryType::Ec2Linux,
+                g if g.starts_with("system:") => warn!(
+                    "Group `{g}` of `{principal_arn}` cannot be granted through an access entry, ignoring it"
+                ),
+                _ => {
+                    access_entry.kubernetes_groups.insert(group);
+                }
+            }
+        }
+
+        // nodes username is set by EKS, and they cannot be part of any other group
+        if access_entry.entry_type == AccessEntryType::Ec2Linux {
+            access_entry.username = None;
+            access_entry.kubernetes_groups.clear();
+        }
+
+        access_entry
+    }
+
+    /// Marker of the instance which created the entry, not set if it hasn't been created by the tool.
+    pub fn synced_by(&self) -> Option<SyncedBy> {
+        self.tags.get(SYNCED_BY_TAG).map(|v| SyncedBy::new(v))
+    }
+
+    pub fn is_cluster_admin(&self) -> bool {
+        self.tags
+            .get(CLUSTER_ADMIN_TAG)
+            .is_some_and(|v| v == "true")
+    }
+
+    /// Kubernetes groups granted by the entry, as they would be listed in aws-auth.
+    fn groups(&self) -> BTreeSet<KubernetesGroupName> {
+        let mut groups: BTreeSet<KubernetesGroupName> = self
+            .kubernetes_groups
+            .iter()
+            .map(|g| KubernetesGroupName::new(g))
+            .collect();
+        if self.is_cluster_admin() {
+            groups.insert(KubernetesGroupName::new(MASTERS_GROUP));
+        }
+        if self.entry_type == AccessEntryType::Ec2Linux {
+            groups.extend(NODE_GROUPS.iter().map(|g| KubernetesGroupName::new(g)));
+        }
+
+        groups
+    }
+
+    fn is_user(&self) -> bool {
+        self.principal_arn.contains(":user/")
+    }
+
+    fn to_user(&self) -> KubernetesUser {
+        let user_name = self.username.clone().unwrap_or_else(|| {
+            self.principal_arn
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+
+        KubernetesUser::new(
+            IamUserName::new(&user_name),
+            IamArn::new(&self.principal_arn),
+            self.groups(),
+            self.synced_by(),
+        )
+    }
+
+    fn to_role(&self) -> KubernetesRole {
+        KubernetesRole::new(
+            IamArn::new(&self.principal_arn),
+            None,
+            self.username.clone(),
+            self.groups(),
+            self.synced_by(),
+        )
+    }
+
+    /// Whether the entry has to be deleted and created again to match `desired`, type and tags not being updatable.
+    fn needs_recreation(&self, desired: &AccessEntry) -> bool {
+        self.entry_type != desired.entry_type
+            || self.is_cluster_admin() != desired.is_cluster_admin()
+    }
+
+    /// Whether groups or username differ from `desired`, username being defaulted by EKS if not set.
+    fn needs_update(&self, desired: &AccessEntry) -> bool {
+        self.kubernetes_groups != desired.kubernetes_groups
+            || (desired.username.is_some() && self.username != desired.username)
+    }
+}
+
+/// Access entries as aws-auth users and roles.
+fn aws_auth<'a>(access_entries: impl Iterator<Item = &'a AccessEntry>) -> AwsAuth {
+    let mut aws_auth = AwsAuth::default();
+    for access_entry in access_entries {
+        match access_entry.is_user() {
+            true => aws_auth.users.insert(access_entry.to_user()),
+            false => aws_auth.roles.insert(access_entry.to_role()),
+        };
+    }
+
+    aws_auth
+}
+
+/// Changes to make on access entries, entries being matched on their principal ARN.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct AccessEntriesPlan {
+    create: Vec<AccessEntry>,
+    /// Entries whose groups or username changed, as (existing, desired).
+    update: Vec<(AccessEntry, AccessEntry)>,
+    /// Entries whose type or access policy changed, as (existing, desired), deleted then created again.
+    recreate: Vec<(AccessEntry, AccessEntry)>,
+    delete: Vec<AccessEntry>,
+    /// Synced entries already matching the desired ones.
+    unchanged: Vec<AccessEntry>,
+    /// Entries not managed by this instance or protected, kept as is.
+    untouched: Vec<AccessEntry>,
+}
+
+impl AccessEntriesPlan {
+    fn new(
+        existing: Vec<AccessEntry>,
+        desired: Vec<AccessEntry>,
+        synced_by: &SyncedBy,
+        protected_arns: &[IamArn],
+    ) -> AccessEntriesPlan {
+        let protected_arns: HashSet<String> = protected_arns
+            .iter()
+            .map(|arn| arn.to_string().to_lowercase())
+            .collect();
+        let mut existing: BTreeMap<String, AccessEntry> = existing
+            .into_iter()
+            .map(|e| (e.principal_arn.to_lowercase(), e))
+            .collect();
+        let desired: BTreeMap<String, AccessEntry> = desired
+            .into_iter()
+            .map(|e| (e.principal_arn.to_lowercase(), e))
+            .collect();
+
+        let mut plan = AccessEntriesPlan::default();
+        for (arn, desired_entry) in desired {
+            match existing.remove(&arn) {
+                None => plan.create.push(desired_entry),
+                Some(existing_entry) if existing_entry.synced_by().as_ref() != Some(synced_by) => {
+                    warn!(
+                        "Access entry `{}` already exists and is not managed by this instance, leaving it untouched",
+                        existing_entry.principal_arn
+                    );
+                    plan.untouched.push(existing_entry)
+                }
+                Some(existing_entry) if protected_arns.contains(&arn) => {
+                    plan.untouched.push(existing_entry)
+                }
+                Some(existing_entry) if existing_entry.needs_recreation(&desired_entry) => {
+                    plan.recreate.push((existing_entry, desired_entry))
+                }
+                Some(existing_entry) if existing_entry.needs_update(&desired_entry) => {
+                    plan.update.push((existing_entry, desired_entry))
+                }
+                Some(existing_entry) => plan.unchanged.push(existing_entry),
+            }
+        }
+        for (arn, existing_entry) in existing {
+            match existing_entry.synced_by().as_ref() == Some(synced_by)
+                && !protected_arns.contains(&arn)
+            {
+                true => plan.delete.push(existing_entry),
+                false => plan.untouched.push(existing_entry),
+            }
+        }
+
+        plan
+    }
+
+    fn is_empty(&self) -> bool {
+        self.create.is_empty()
+            && self.update.is_empty()
+            && self.recreate.is_empty()
+            && self.delete.is_empty()
+    }
+
+    /// Changes as aws-auth users and roles changes.
+    fn diff(&self) -> AwsAuthDiff {
+        let mut diff = AwsAuthDiff::default();
+        for (access_entries, users, roles) in [
+            (&self.create, &mut diff.users.added, &mut diff.roles.added),
+            (
+                &self.delete,
+                &mut diff.users.removed,
+                &mut diff.roles.removed,
+            ),
+            (
+                &self.untouched,
+                &mut diff.users.untouched,
+                &mut diff.roles.untouched,
+            ),
+            (
+                &self.unchanged,
+                &mut diff.users.unchanged,
+                &mut diff.roles.unchanged,
+            ),
+        ] {
+            for access_entry in access_entries {
+                match access_entry.is_user() {
+                    true => users.push(access_entry.to_user()),
+                    false => roles.push(access_entry.to_role()),
+                }
+            }
+            users.sort_by_key(|u| u.iam_arn.to_string());
+            roles.sort_by_key(|r| r.iam_role_arn.to_string());
+        }
+        for (existing, desired) in self.update.iter().chain(self.recreate.iter()) {
+            match desired.is_user() {
+                true => diff
+                    .users
+                    .modified
+                    .push((existing.to_user(), desired.to_user())),
+                false => diff
+                    .roles
+                    .modified
+                    .push((existing.to_role(), desired.to_role())),
+            }
+        }
+
+        diff
+    }
+
+    /// Access entries once changes are made, as aws-auth users and roles.
+    fn aws_auth(&self) -> AwsAuth {
+        aws_auth(
+            self.create
+                .iter()
+                .chain(self.update.iter().map(|(_, desired)| desired))
+                .chain(self.recreate.iter().map(|(_, desired)| desired))
+                .chain(self.unchanged.iter())
+                .chain(self.untouched.iter()),
+        )
+    }
+}
+
+/// EKS access entries API of a cluster, allowing to sync access entries without relying on a concrete AWS client.
+pub trait AccessEntriesApi {
+    fn cluster_name(&self) -> &str;
+
+    /// Returns principal ARNs of all the cluster access entries.
+    async fn list_access_entries(&self) -> Result<Vec<String>, EksError>;
+
+    async fn describe_access_entry(&self, principal_arn: &str) -> Result<AccessEntry, EksError>;
+
+    async fn create_access_entry(&self, access_entry: &AccessEntry) -> Result<(), EksError>;
+
+    /// Updates access entry groups and username, type and tags cannot be updated.
+    async fn update_access_entry(&self, access_entry: &AccessEntry) -> Result<(), EksError>;
+
+    async fn delete_access_entry(&self, principal_arn: &str) -> Result<(), EksError>;
+
+    /// Associates `policy_arn` access policy to the access entry, on the whole cluster.
+    async fn associate_access_policy(
+        &self,
+        principal_arn: &str,
+        policy_arn: &str,
+    ) -> Result<(), EksError>;
+}
+
+/// EKS managed nodegroups API of a cluster.
+pub trait NodegroupsApi {
+    /// Returns names of all the cluster managed nodegroups.
+    async fn list_nodegroups(&self) -> Result<Vec<String>, EksError>;
+
+    async fn describe_nodegroup(&self, nodegroup_name: &str) -> Result<Nodegroup, EksError>;
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Nodegroup {
+    pub nodegroup_name: String,
+    /// IAM role of the nodegroup instances.
+    pub node_role: String,
+}
+
+impl From<&aws_sdk_eks::types::Nodegroup> for Nodegroup {
+    fn from(value: &aws_sdk_eks::types::Nodegroup) -> Self {
+        Nodegroup {
+            nodegroup_name: value.nodegroup_name().unwrap_or_default().to_string(),
+            node_role: value.node_role().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// EKS cluster API.
+pub trait ClusterApi {
+    async fn describe_cluster(&self) -> Result<EksCluster, EksError>;
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EksCluster {
+    pub name: String,
+    pub arn: String,
+    /// Kubernetes version, e.q: 1.30
+    pub version: String,
+    /// Kubernetes API server URL.
+    pub endpoint: String,
+    pub certificate_authority: CertificateAuthority,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CertificateAuthority {
+    /// Base64 encoded PEM certificate.
+    pub data: Option<String>,
+}
+
+impl From<&aws_sdk_eks::types::Cluster> for EksCluster {
+    fn from(value: &aws_sdk_eks::types::Cluster) -> Self {
+        EksCluster {
+            name: value.name().unwrap_or_default().to_string(),
+            arn: value.arn().unwrap_or_default().to_string(),
+            version: value.version().unwrap_or_default().to_string(),
+            endpoint: value.endpoint().unwrap_or_default().to_string(),
+            certificate_authority: CertificateAuthority {
+                data: value
+                    .certificate_authority()
+                    .and_then(|certificate| certificate.data())
+                    .map(str::to_string),
+            },
+        }
+    }
+}
+
+impl EksCluster {
+    /// DER certificates of the cluster CA, empty if it cannot be decoded.
+    fn ca_certificates(&self) -> Vec<Vec<u8>> {
+        self.certificate_authority
+            .data
+            .as_ref()
+            .and_then(|data| BASE64_STANDARD.decode(data.trim()).ok())
+            .and_then(|pem| pem::parse_many(pem).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|certificate| certificate.into_contents())
+            .collect()
+    }
+
+    /// Whether a client configured with `endpoint` reaches this cluster, either its URL or its CA being the cluster ones.
+    /// In-cluster clients use the `kubernetes` service instead of the cluster endpoint, their CA matching though.
+    pub fn is_reached_by(&self, endpoint: &ClusterEndpoint) -> bool {
+        let normalized = |url: &str| url.trim_end_matches('/').to_lowercase();
+        normalized(&self.endpoint) == normalized(&endpoint.url)
+            || self
+                .ca_certificates()
+                .iter()
+                .any(|certificate| endpoint.root_certs.contains(certificate))
+    }
+}
+
+/// Roles of the cluster managed nodegroups, mapped the way EKS does so their nodes can join the cluster.
+/// Nodegroups sharing a role get a single entry.
+pub async fn discover_node_roles(
+    api: &impl NodegroupsApi,
+) -> Result<Vec<KubernetesRole>, EksError> {
+    let mut node_role_arns = BTreeSet::new();
+    for nodegroup_name in api.list_nodegroups().await? {
+        node_role_arns.insert(api.describe_nodegroup(&nodegroup_name).await?.node_role);
+    }
+
+    Ok(node_role_arns
+        .into_iter()
+        .map(|arn| {
+            KubernetesRole::new(
+                IamArn::new(&arn),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                BTreeSet::from_iter(NODE_GROUPS.iter().map(|g| KubernetesGroupName::new(g))),
+                Some(SyncedBy::IamEksUserMapper), // <- managed by the tool
+            )
+        })
+        .collect())
+}
+
+/// EKS API client.
+pub struct EksService {
+    client: aws_sdk_eks::Client,
+    cluster_name: String,
+}
+
+impl EksService {
+    pub fn new(config: &AwsSdkConfig, cluster_name: &str) -> EksService {
+        Self::with_client(aws_sdk_eks::Client::new(&config.config), cluster_name)
+    }
+
+    /// Service calling EKS through `client`, e.q: a client replaying canned responses.
+    pub fn with_client(client: aws_sdk_eks::Client, cluster_name: &str) -> EksService {
+        EksService {
+            client,
+            cluster_name: cluster_name.to_string(),
+        }
+    }
+}
+
+impl AccessEntriesApi for EksService {
+    fn cluster_name(&self) -> &str {
+        &self.cluster_name
+    }
+
+    async fn list_access_entries(&self) -> Result<Vec<String>, EksError> {
+        let mut principal_arns = Vec::new();
+        let mut next_token = None;
+        loop {
+            let output = self
+                .client
+                .list_access_entries()
+                .cluster_name(&self.cluster_name)
+                .max_results(100)
+                .set_next_token(next_token)
+                .send()
+                .await
+                .map_err(|e| EksError::CannotListAccessEntries {
+                    raw_message: Arc::from(e.to_string()),
+                    retryable: is_retryable_sdk_error(&e),
+                })?;
+
+            principal_arns.extend(output.access_entries().iter().cloned());
+            match output.next_token() {
+                Some(token) => next_token = Some(token.to_string()),
+                None => return Ok(principal_arns),
+            }
+        }
+    }
+
+    async fn describe_access_entry(&self, principal_arn: &str) -> Result<AccessEntry, EksError> {
+        let output = self
+            .client
+            .describe_access_entry()
+            .cluster_name(&self.cluster_name)
+            .principal_arn(principal_arn)
+            .send()
+            .await
+            .map_err(|e| EksError::CannotDescribeAccessEntry {
+                principal_arn: Arc::from(principal_arn),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })?;
+
+        output.access_entry().map(AccessEntry::from).ok_or_else(|| {
+            EksError::CannotDescribeAccessEntry {
+                principal_arn: Arc::from(principal_arn),
+                raw_message: Arc::from("response holds no access entry"),
+                retryable: false,
+            }
+        })
+    }
+
+    async fn create_access_entry(&self, access_entry: &AccessEntry) -> Result<(), EksError> {
+        self.client
+            .create_access_entry()
+            .cluster_name(&self.cluster_name)
+            .principal_arn(&access_entry.principal_arn)
+            .r#type(access_entry.entry_type.as_str())
+            .set_kubernetes_groups(
+                (!access_entry.kubernetes_groups.is_empty())
+                    .then(|| access_entry.kubernetes_groups.iter().cloned().collect()),
+            )
+            .set_username(access_entry.username.clone())
+            .set_tags(Some(access_entry.tags.clone().into_iter().collect()))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| EksError::CannotCreateAccessEntry {
+                principal_arn: Arc::from(access_entry.principal_arn.as_str()),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })
+    }
+
+    async fn update_access_entry(&self, access_entry: &AccessEntry) -> Result<(), EksError> {
+        self.client
+            .update_access_entry()
+            .cluster_name(&self.cluster_name)
+            .principal_arn(&access_entry.principal_arn)
+            .set_kubernetes_groups(Some(
+                access_entry.kubernetes_groups.iter().cloned().collect(),
+            ))
+            .set_username(access_entry.username.clone())
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| EksError::CannotUpdateAccessEntry {
+                principal_arn: Arc::from(access_entry.principal_arn.as_str()),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })
+    }
+
+    async fn delete_access_entry(&self, principal_arn: &str) -> Result<(), EksError> {
+        self.client
+            .delete_access_entry()
+            .cluster_name(&self.cluster_name)
+            .principal_arn(principal_arn)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| EksError::CannotDeleteAccessEntry {
+                principal_arn: Arc::from(principal_arn),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })
+    }
+
+    async fn associate_access_policy(
+        &self,
+        principal_arn: &str,
+        policy_arn: &str,
+    ) -> Result<(), EksError> {
+        self.client
+            .associate_access_policy()
+            .cluster_name(&self.cluster_name)
+            .principal_arn(principal_arn)
+            .policy_arn(policy_arn)
+            .access_scope(
+                AccessScope::builder()
+                    .r#type(AccessScopeType::Cluster)
+                    .build(),
+            )
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| EksError::CannotAssociateAccessPolicy {
+                principal_arn: Arc::from(principal_arn),
+                policy_arn: Arc::from(policy_arn),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })
+    }
+}
+
+impl ClusterApi for EksService {
+    async fn describe_cluster(&self) -> Result<EksCluster, EksError> {
+        let output = self
+            .client
+            .describe_cluster()
+            .name(&self.cluster_name)
+            .send()
+            .await
+            .map_err(|e| EksError::CannotDescribeCluster {
+                cluster_name: Arc::from(self.cluster_name.as_str()),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })?;
+
+        output
+            .cluster()
+            .map(EksCluster::from)
+            .ok_or_else(|| EksError::CannotDescribeCluster {
+                cluster_name: Arc::from(self.cluster_name.as_str()),
+                raw_message: Arc::from("response holds no cluster"),
+                retryable: false,
+            })
+    }
+}
+
+impl NodegroupsApi for EksService {
+    async fn list_nodegroups(&self) -> Result<Vec<String>, EksError> {
+        let mut nodegroup_names = Vec::new();
+        let mut next_token = None;
+        loop {
+            let output = self
+                .client
+                .list_nodegroups()
+                .cluster_name(&self.cluster_name)
+                .max_results(100)
+                .set_next_token(next_token)
+                .send()
+                .await
+                .map_err(|e| EksError::CannotListNodegroups {
+                    raw_message: Arc::from(e.to_string()),
+                    retryable: is_retryable_sdk_error(&e),
+                })?;
+
+            nodegroup_names.extend(output.nodegroups().iter().cloned());
+            match output.next_token() {
+                Some(token) => next_token = Some(token.to_string()),
+                None => return Ok(nodegroup_names),
+            }
+        }
+    }
+
+    async fn describe_nodegroup(&self, nodegroup_name: &str) -> Result<Nodegroup, EksError> {
+        let output = self
+            .client
+            .describe_nodegroup()
+            .cluster_name(&self.cluster_name)
+            .nodegroup_name(nodegroup_name)
+            .send()
+            .await
+            .map_err(|e| EksError::CannotDescribeNodegroup {
+                nodegroup_name: Arc::from(nodegroup_name),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })?;
+
+        output
+            .nodegroup()
+            .map(Nodegroup::from)
+            .ok_or_else(|| EksError::CannotDescribeNodegroup {
+                nodegroup_name: Arc::from(nodegroup_name),
+                raw_message: Arc::from("response holds no nodegroup"),
+                retryable: false,
+            })
+    }
+}
+
+/// Cluster admin access policy ARN, in the partition of `principal_arn`, e.q: `aws-cn`.
+fn cluster_admin_policy_arn(principal_arn: &str) -> String {
+    let partition = principal_arn
+        .parse::<Arn>()
+        .map(|arn| arn.partition().to_string())
+        .unwrap_or_else(|_| "aws".to_string());
+    format!("arn:{partition}:eks::aws:cluster-access-policy/AmazonEKSClusterAdminPolicy")
+}
+
+/// Outcome of an aws-auth entry migration to access entries.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MigrationStatus {
+    Created,
+    /// Dry run, entry would have been created.
+    WouldBeCreated,
+    /// Entry has been created by a previous migration.
+    AlreadyMigrated,
+    /// An access entry not created by a migration already exists for this principal, it's left as is.
+    AlreadyExists,
+}
+
+impl Display for MigrationStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MigrationStatus::Created => "created",
+            MigrationStatus::WouldBeCreated => "would be created (dry run)",
+            MigrationStatus::AlreadyMigrated => "already migrated, skipped",
+            MigrationStatus::AlreadyExists => "access entry already exists, skipped",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MigratedEntry {
+    pub principal_arn: IamArn,
+    pub status: MigrationStatus,
+}
+
+impl MigratedEntry {
+    /// Whether the aws-auth entry can be removed, access being granted by an access entry the migration created.
+    pub fn can_remove_source(&self) -> bool {
+        self.status != MigrationStatus::AlreadyExists
+    }
+}
+
+/// Syncs users and roles into EKS access entries instead of the aws-auth config map.
+/// Only entries tagged with this instance marker are modified or deleted.
+pub struct AccessEntriesBackend<A: AccessEntriesApi> {
+    api: A,
+    options: ConfigMapOptions,
+}
+
+impl<A: AccessEntriesApi> AccessEntriesBackend<A> {
+    pub fn new(api: A, options: ConfigMapOptions) -> AccessEntriesBackend<A> {
+        AccessEntriesBackend { api, options }
+    }
+
+    async fn existing_access_entries(&self) -> Result<Vec<AccessEntry>, EksError> {
+        let mut access_entries = Vec::new();
+        for principal_arn in self.api.list_access_entries().await? {
+            access_entries.push(self.api.describe_access_entry(&principal_arn).await?);
+        }
+
+        Ok(access_entries)
+    }
+
+    async fn create(&self, access_entry: &AccessEntry) -> Result<(), EksError> {
+        self.api.create_access_entry(access_entry).await?;
+        if access_entry.is_cluster_admin() {
+            self.api
+                .associate_access_policy(
+                    &access_entry.principal_arn,
+                    &cluster_admin_policy_arn(&access_entry.principal_arn),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn apply(&self, plan: &AccessEntriesPlan) -> Result<(), EksError> {
+        for access_entry in &plan.create {
+            self.create(access_entry).await?;
+        }
+        for (_, access_entry) in &plan.update {
+            self.api.update_access_entry(access_entry).await?;
+        }
+        for (existing, desired) in &plan.recreate {
+            // associated access policies are deleted along with the entry
+            self.api
+                .delete_access_entry(&existing.principal_arn)
+                .await?;
+            self.create(desired).await?;
+        }
+        for access_entry in &plan.delete {
+            self.api
+                .delete_access_entry(&access_entry.principal_arn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates access entries equivalent to `aws_auth` entries synced by this instance, or to all of them if `include_unmanaged` is set.
+    /// Entries migrated from unmanaged ones aren't tagged as synced, sync never deleting them. Nothing is created in dry run.
+    pub async fn migrate(
+        &self,
+        aws_auth: &AwsAuth,
+        include_unmanaged: bool,
+    ) -> Result<Vec<MigratedEntry>, EksError> {
+        let synced_by = &self.options.synced_by;
+        let candidates = aws_auth
+            .users
+            .iter()
+            .filter(|u| include_unmanaged || u.synced_by.as_ref() == Some(synced_by))
+            .map(|u| {
+                (
+                    u.synced_by.as_ref() == Some(synced_by),
+                    AccessEntry::synced(
+                        &u.iam_arn,
+                        Some(u.iam_user_name.to_string()),
+                        &u.roles,
+                        synced_by,
+                    ),
+                )
+            })
+            .chain(
+                aws_auth
+                    .roles
+                    .iter()
+                    .filter(|r| include_unmanaged || r.synced_by.as_ref() == Some(synced_by))
+                    .map(|r| {
+                        (
+                            r.synced_by.as_ref() == Some(synced_by),
+                            AccessEntry::synced(
+                                &r.iam_role_arn,
+                                r.user_name.clone(),
+                                &r.groups,
+                                synced_by,
+                            ),
+                        )
+                    }),
+            );
+
+        let mut existing: BTreeMap<String, AccessEntry> = self
+            .existing_access_entries()
+            .await?
+            .into_iter()
+            .map(|e| (e.principal_arn.to_lowercase(), e))
+            .collect();
+        let mut migrated_entries = Vec::new();
+        for (managed, mut access_entry) in candidates {
+            if !managed {
+                access_entry.tags.remove(SYNCED_BY_TAG);
+            }
+            access_entry
+                .tags
+                .insert(MIGRATED_FROM_TAG.to_string(), "aws-auth".to_string());
+
+            let key = access_entry.principal_arn.to_lowercase();
+            let status = match existing.get(&key) {
+                Some(e) if e.tags.contains_key(MIGRATED_FROM_TAG) => {
+                    MigrationStatus::AlreadyMigrated
+                }
+                Some(_) => MigrationStatus::AlreadyExists,
+                None if self.options.dry_run => MigrationStatus::WouldBeCreated,
+                None => {
+                    self.create(&access_entry).await?;
+                    MigrationStatus::Created
+                }
+            };
+            migrated_entries.push(MigratedEntry {
+                principal_arn: IamArn::new(&access_entry.principal_arn),
+                status,
+            });
+            // the same principal can be listed several times in aws-auth
+            existing.entry(key).or_insert(access_entry);
+        }
+
+        migrated_entries.sort_by_key(|e| e.principal_arn.to_string());
+        Ok(migrated_entries)
+    }
+
+    fn sync_error(&self, e: EksError) -> KubernetesError {
+        KubernetesError::AccessEntriesCannotBeSynced {
+            cluster_name: Arc::from(self.api.cluster_name()),
+            raw_message: Arc::from(e.to_string()),
+            retryable: e.is_retryable(),
+        }
+    }
+}
+
+impl<A: AccessEntriesApi> AuthBackend for AccessEntriesBackend<A> {
+    async fn get_config_map_resource_version(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError> {
+        // access entries have no version, each sync goes through the whole reconciliation
+        Ok(None)
+    }
+
+    async fn read_current(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        let existing = self
+            .existing_access_entries()
+            .await
+            .map_err(|e| self.sync_error(e))?;
+        Ok(Some(aws_auth(existing.iter())))
+    }
+
+    async fn update_user_and_role_config_map(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError> {
+        if !aws_accounts_to_be_added.is_empty() {
+            warn!(
+                "AWS accounts cannot be mapped through access entries, ignoring {} account(s)",
+                aws_accounts_to_be_added.len()
+            );
+        }
+
+        let synced_by = &self.options.synced_by;
+        let desired = kubernetes_users_to_be_added
+            .unwrap_or_default()
+            .iter()
+            .map(|u| {
+                AccessEntry::synced(
+                    &u.iam_arn,
+                    Some(u.iam_user_name.to_string()),
+                    &u.roles,
+                    synced_by,
+                )
+            })
+            .chain(
+                kubernetes_sso_role_to_be_added
+                    .iter()
+                    .chain(karpenter_roles_to_be_added.iter())
+                    .chain(other_roles_to_be_added)
+                    .map(|r| {
+                        AccessEntry::synced(
+                            &r.iam_role_arn,
+                            r.user_name.clone(),
+                            &r.groups,
+                            synced_by,
+                        )
+                    }),
+            )
+            .collect();
+        let existing = self
+            .existing_access_entries()
+            .await
+            .map_err(|e| self.sync_error(e))?;
+        let existing_aws_auth = aws_auth(existing.iter());
+
+        let plan =
+            AccessEntriesPlan::new(existing, desired, synced_by, &self.options.protected_arns);
+        let diff = plan.diff();
+        self.options
+            .removal_guard
+            .check(&existing_aws_auth, &diff, synced_by)?;
+
+        let status = match (
+            plan.is_empty(),
+            self.options.dry_run || self.options.server_dry_run,
+        ) {
+            (true, _) => ConfigMapUpdateStatus::Unchanged,
+            // EKS has no server side dry run, nothing is sent in both cases
+            (false, true) => ConfigMapUpdateStatus::DryRun,
+            (false, false) => {
+                self.apply(&plan).await.map_err(|e| self.sync_error(e))?;
+                info!(
+                    "Access entries of EKS cluster `{}` updated",
+                    self.api.cluster_name()
+                );
+                ConfigMapUpdateStatus::Updated
+            }
+        };
+
+        let aws_auth = plan.aws_auth();
+        Ok(ConfigMapUpdate {
+            next_expiry: self.options.next_expiry([&existing_aws_auth, &aws_auth]),
+            aws_auth,
+            previous_resource_version: None,
+            resource_version: None,
+            status,
+            diff,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::eks::{
+        discover_node_roles, AccessEntriesApi, AccessEntriesBackend, AccessEntriesPlan,
+        AccessEntry, AccessEntryType, CertificateAuthority, EksCluster, EksError, EksService,
+        MigratedEntry, MigrationStatus, CLUSTER_ADMIN_TAG, SYNCED_BY_TAG,
+    };
+    use crate::aws::fake::{FakeAccessEntriesApi, FakeNodegroupsApi};
+    use crate::kubernetes::aws_auth::{AwsAuth, RemovalGuard};
+    use crate::kubernetes::tests::iam_user;
+    use crate::kubernetes::{
+        AuthBackend, ClusterEndpoint, ConfigMapOptions, ConfigMapUpdateStatus, IamArn,
+        KubernetesError, KubernetesGroupName, KubernetesRole, SyncedBy,
+    };
+    use aws_sdk_eks::config::http::{HttpRequest, HttpResponse};
+    use aws_sdk_eks::config::retry::RetryConfig;
+    use aws_sdk_eks::config::{BehaviorVersion, Credentials, Region};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// EKS service replaying `responses`, as HTTP status and body, in order, and the client recording its requests.
+    fn replaying_eks_service(responses: &[(u16, String)]) -> (EksService, StaticReplayClient) {
+        let http_client = StaticReplayClient::new(
+            responses
+                .iter()
+                .map(|(status, body)| {
+                    ReplayEvent::new(
+                        HttpRequest::new(SdkBody::empty()),
+                        HttpResponse::new(
+                            (*status).try_into().expect("status should be valid"),
+                            SdkBody::from(body.as_str()),
+                        ),
+                    )
+                })
+                .collect(),
+        );
+        let config = aws_sdk_eks::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("AKID", "SECRET", None, None, "test"))
+            // throttling errors are retried by the sync loop, not the SDK
+            .retry_config(RetryConfig::disabled())
+            .http_client(http_client.clone())
+            .build();
+
+        (
+            EksService::with_client(aws_sdk_eks::Client::from_conf(config), "my-cluster"),
+            http_client,
+        )
+    }
+
+    /// ListAccessEntries response body holding `principal_arns`, `next_token` being set when more entries are to be fetched.
+    fn list_access_entries_response(principal_arns: &[&str], next_token: Option<&str>) -> String {
+        let principal_arns = principal_arns
+            .iter()
+            .map(|arn| format!("\"{arn}\""))
+            .collect::<Vec<_>>()
+            .join(",");
+        match next_token {
+            Some(token) => {
+                format!(r#"{{"accessEntries":[{principal_arns}],"nextToken":"{token}"}}"#)
+            }
+            None => format!(r#"{{"accessEntries":[{principal_arns}]}}"#),
+        }
+    }
+
+    fn groups(groups: &[&str]) -> BTreeSet<KubernetesGroupName> {
+        groups.iter().map(|g| KubernetesGroupName::new(g)).collect()
+    }
+
+    fn access_entry(
+        principal_arn: &str,
+        kubernetes_groups: &[&str],
+        synced_by: Option<&str>,
+    ) -> AccessEntry {
+        AccessEntry {
+            principal_arn: principal_arn.to_string(),
+            kubernetes_groups: kubernetes_groups.iter().map(|g| g.to_string()).collect(),
+            tags: synced_by
+                .map(|s| BTreeMap::from_iter(vec![(SYNCED_BY_TAG.to_string(), s.to_string())]))
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn access_entry_synced_test() {
+        // setup:
+        struct TestCase<'a> {
+            groups: BTreeSet<KubernetesGroupName>,
+            username: Option<String>,
+            expected_output: AccessEntry,
+            _description: &'a str,
+        }
+
+        let synced_by_tag = (SYNCED_BY_TAG.to_string(), "iam-eks-user-mapper".to_string());
+        let test_cases = vec![
+            TestCase {
+                groups: groups(&["developers", "viewers"]),
+                username: Some("user_1".to_string()),
+                expected_output: AccessEntry {
+                    principal_arn: "arn:aws:iam::123456789012:user/user_1".to_string(),
+                    entry_type: AccessEntryType::Standard,
+                    kubernetes_groups: BTreeSet::from_iter(vec![
+                        "developers".to_string(),
+                        "viewers".to_string(),
+                    ]),
+                    username: Some("user_1".to_string()),
+                    tags: BTreeMap::from_iter(vec![synced_by_tag.clone()]),
+                },
+                _description: "case 1: groups are mapped to kubernetes groups",
+            },
+            TestCase {
+                groups: groups(&["system:masters", "developers"]),
+                username: Some("user_1".to_string()),
+                expected_output: AccessEntry {
+                    principal_arn: "arn:aws:iam::123456789012:user/user_1".to_string(),
+                    entry_type: AccessEntryType::Standard,
+                    kubernetes_groups: BTreeSet::from_iter(vec!["developers".to_string()]),
+                    username: Some("user_1".to_string()),
+                    tags: BTreeMap::from_iter(vec![
+                        synced_by_tag.clone(),
+                        (CLUSTER_ADMIN_TAG.to_string(), "true".to_string()),
+                    ]),
+                },
+                _description: "case 2: system:masters is granted through cluster admin policy",
+            },
+            TestCase {
+                groups: groups(&["system:bootstrappers", "system:nodes"]),
+                username: Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                expected_output: AccessEntry {
+                    principal_arn: "arn:aws:iam::123456789012:user/user_1".to_string(),
+                    entry_type: AccessEntryType::Ec2Linux,
+                    kubernetes_groups: BTreeSet::new(),
+                    username: None,
+                    tags: BTreeMap::from_iter(vec![synced_by_tag.clone()]),
+                },
+                _description: "case 3: node groups are granted through an EC2_LINUX entry",
+            },
+            TestCase {
+                groups: groups(&["system:authenticated", "developers"]),
+                username: None,
+                expected_output: AccessEntry {
+                    principal_arn: "arn:aws:iam::123456789012:user/user_1".to_string(),
+                    entry_type: AccessEntryType::Standard,
+                    kubernetes_groups: BTreeSet::from_iter(vec!["developers".to_string()]),
+                    username: None,
+                    tags: BTreeMap::from_iter(vec![synced_by_tag.clone()]),
+                },
+                _description: "case 4: other system groups are ignored",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = AccessEntry::synced(
+                &IamArn::new("arn:aws:iam::123456789012:user/user_1"),
+                tc.username,
+                &tc.groups,
+                &SyncedBy::IamEksUserMapper,
+            );
+
+            // verify:
+            assert_eq!(tc.expected_output, res, "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn access_entries_plan_test() {
+        // setup:
+        struct TestCase<'a> {
+            existing: Vec<AccessEntry>,
+            desired: Vec<AccessEntry>,
+            protected_arns: Vec<IamArn>,
+            expected_output: AccessEntriesPlan,
+            _description: &'a str,
+        }
+
+        let synced =
+            |arn: &str, groups: &[&str]| access_entry(arn, groups, Some("iam-eks-user-mapper"));
+        let admin = |arn: &str| {
+            let mut access_entry = synced(arn, &[]);
+            access_entry
+                .tags
+                .insert(CLUSTER_ADMIN_TAG.to_string(), "true".to_string());
+            access_entry
+        };
+
+        let test_cases = vec![
+            TestCase {
+                existing: vec![synced("arn:test:user_1", &["viewers"])],
+                desired: vec![synced("arn:test:user_2", &["viewers"])],
+                protected_arns: vec![],
+                expected_output: AccessEntriesPlan {
+                    create: vec![synced("arn:test:user_2", &["viewers"])],
+                    delete: vec![synced("arn:test:user_1", &["viewers"])],
+                    ..Default::default()
+                },
+                _description:
+                    "case 1: missing entry is created, synced entry no longer desired is deleted",
+            },
+            TestCase {
+                existing: vec![
+                    access_entry("arn:test:creator", &[], None),
+                    access_entry("arn:test:user_1", &["viewers"], Some("other-instance")),
+                ],
+                desired: vec![synced("arn:test:user_1", &["developers"])],
+                protected_arns: vec![],
+                expected_output: AccessEntriesPlan {
+                    untouched: vec![
+                        access_entry("arn:test:user_1", &["viewers"], Some("other-instance")),
+                        access_entry("arn:test:creator", &[], None),
+                    ],
+                    ..Default::default()
+                },
+                _description:
+                    "case 2: entries not created by this instance are never modified nor deleted",
+            },
+            TestCase {
+                existing: vec![
+                    synced("arn:test:user_1", &["viewers"]),
+                    synced("arn:test:user_2", &["viewers"]),
+                ],
+                desired: vec![synced("arn:test:user_1", &["developers"])],
+                protected_arns: vec![
+                    IamArn::new("arn:test:USER_1"),
+                    IamArn::new("arn:test:user_2"),
+                ],
+                expected_output: AccessEntriesPlan {
+                    untouched: vec![
+                        synced("arn:test:user_1", &["viewers"]),
+                        synced("arn:test:user_2", &["viewers"]),
+                    ],
+                    ..Default::default()
+                },
+                _description: "case 3: protected entries are kept as is",
+            },
+            TestCase {
+                existing: vec![
+                    synced("arn:test:user_1", &["viewers"]),
+                    synced("arn:test:user_2", &["viewers"]),
+                    synced("arn:test:user_3", &["viewers"]),
+                ],
+                desired: vec![
+                    synced("arn:test:user_1", &["developers"]),
+                    admin("arn:test:user_2"),
+                    synced("arn:test:user_3", &["viewers"]),
+                ],
+                protected_arns: vec![],
+                expected_output: AccessEntriesPlan {
+                    update: vec![(
+                        synced("arn:test:user_1", &["viewers"]),
+                        synced("arn:test:user_1", &["developers"]),
+                    )],
+                    recreate: vec![(
+                        synced("arn:test:user_2", &["viewers"]),
+                        admin("arn:test:user_2"),
+                    )],
+                    unchanged: vec![synced("arn:test:user_3", &["viewers"])],
+                    ..Default::default()
+                },
+                _description:
+                    "case 4: changed groups are updated, changed access policy is recreated",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = AccessEntriesPlan::new(
+                tc.existing,
+                tc.desired,
+                &SyncedBy::IamEksUserMapper,
+                &tc.protected_arns,
+            );
+
+            // verify:
+            assert_eq!(tc.expected_output, res, "{}", tc._description);
+        }
+    }
+
+    #[tokio::test]
+    async fn update_access_entries_test() {
+        // setup:
+        let api = FakeAccessEntriesApi::new(vec![
+            access_entry("arn:aws:iam::123456789012:role/creator", &[], None),
+            access_entry(
+                "arn:aws:iam::123456789012:user/former",
+                &["developers"],
+                Some("iam-eks-user-mapper"),
+            ),
+            access_entry(
+                "arn:aws:iam::123456789012:user/dev",
+                &["viewers"],
+                Some("iam-eks-user-mapper"),
+            ),
+        ]);
+        let backend = AccessEntriesBackend::new(api, ConfigMapOptions::default());
+        let karpenter_role = KubernetesRole::new(
+            IamArn::new("arn:aws:iam::123456789012:role/karpenter"),
+            None,
+            Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+            groups(&["system:bootstrappers", "system:nodes"]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        let users = BTreeSet::from_iter(vec![
+            iam_user("dev", &["developers"]),
+            iam_user("lead", &["system:masters"]),
+        ]);
+
+        // execute:
+        let res = backend
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(users.clone()),
+                None,
+                std::slice::from_ref(&karpenter_role),
+                &[],
+                &BTreeSet::new(),
+            )
+            .await
+            .expect("access entries should be synced");
+
+        // verify:
+        assert_eq!(ConfigMapUpdateStatus::Updated, res.status);
+        assert_eq!(None, res.resource_version);
+        assert_eq!(1, res.diff.users.added.len());
+        assert_eq!(1, res.diff.users.removed.len());
+        assert_eq!(1, res.diff.users.modified.len());
+        assert_eq!(1, res.diff.roles.added.len());
+        assert_eq!(1, res.diff.roles.untouched.len());
+        assert_eq!(
+            vec![
+                "create arn:aws:iam::123456789012:role/karpenter",
+                "create arn:aws:iam::123456789012:user/lead",
+                "associate arn:aws:iam::123456789012:user/lead",
+                "update arn:aws:iam::123456789012:user/dev",
+                "delete arn:aws:iam::123456789012:user/former",
+            ],
+            backend.api.writes()
+        );
+        let access_entries = backend.api.access_entries();
+        assert_eq!(
+            vec![
+                "arn:aws:iam::123456789012:role/creator",
+                "arn:aws:iam::123456789012:role/karpenter",
+                "arn:aws:iam::123456789012:user/dev",
+                "arn:aws:iam::123456789012:user/lead",
+            ],
+            access_entries.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            AccessEntryType::Ec2Linux,
+            access_entries["arn:aws:iam::123456789012:role/karpenter"].entry_type
+        );
+        assert_eq!(
+            BTreeSet::from_iter(vec!["developers".to_string()]),
+            access_entries["arn:aws:iam::123456789012:user/dev"].kubernetes_groups
+        );
+        assert_eq!(
+            BTreeMap::from_iter(vec![(
+                "arn:aws:iam::123456789012:user/lead".to_string(),
+                BTreeSet::from_iter(vec![
+                    "arn:aws:eks::aws:cluster-access-policy/AmazonEKSClusterAdminPolicy"
+                        .to_string()
+                ])
+            )]),
+            backend.api.access_policies()
+        );
+        assert!(res
+            .aws_auth
+            .users
+            .contains(&iam_user("lead", &["system:masters"])));
+
+        // execute:
+        let res = backend
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(users),
+                None,
+                &[karpenter_role],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await
+            .expect("access entries should be synced");
+
+        // verify:
+        assert_eq!(ConfigMapUpdateStatus::Unchanged, res.status);
+        assert!(res.diff.is_empty());
+        assert_eq!(5, backend.api.writes().len(), "nothing is written");
+    }
+
+    #[tokio::test]
+    async fn update_access_entries_without_writes_test() {
+        // setup:
+        struct TestCase<'a> {
+            options: ConfigMapOptions,
+            expected_status: Result<ConfigMapUpdateStatus, ()>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                options: ConfigMapOptions {
+                    dry_run: true,
+                    ..Default::default()
+                },
+                expected_status: Ok(ConfigMapUpdateStatus::DryRun),
+                _description: "case 1: dry run",
+            },
+            TestCase {
+                options: ConfigMapOptions {
+                    removal_guard: RemovalGuard {
+                        max_removals: Some(0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_status: Err(()),
+                _description: "case 2: too many removals",
+            },
+        ];
+
+        for tc in test_cases {
+            let api = FakeAccessEntriesApi::new(vec![access_entry(
+                "arn:aws:iam::123456789012:user/former",
+                &["developers"],
+                Some("iam-eks-user-mapper"),
+            )]);
+            let backend = AccessEntriesBackend::new(api, tc.options);
+
+            // execute:
+            let res = backend
+                .update_user_and_role_config_map(
+                    "kube-system",
+                    "aws-auth",
+                    Some(BTreeSet::from_iter(vec![iam_user("new", &["developers"])])),
+                    None,
+                    &[],
+                    &[],
+                    &BTreeSet::new(),
+                )
+                .await;
+
+            // verify:
+            match tc.expected_status {
+                Ok(status) => assert_eq!(
+                    Some(status),
+                    res.as_ref().ok().map(|r| r.status),
+                    "{}",
+                    tc._description
+                ),
+                Err(()) => assert!(
+                    matches!(res, Err(KubernetesError::TooManyRemovals { .. })),
+                    "{}",
+                    tc._description
+                ),
+            }
+            assert!(backend.api.writes().is_empty(), "{}", tc._description);
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_test() {
+        // setup:
+        struct TestCase<'a> {
+            include_unmanaged: bool,
+            dry_run: bool,
+            expected_statuses: Vec<(&'a str, MigrationStatus)>,
+            expected_writes: Vec<String>,
+            _description: &'a str,
+        }
+
+        let alice = "arn:aws:iam::123456789012:user/alice";
+        let bob = "arn:aws:iam::123456789012:user/bob";
+        let ops = "arn:aws:iam::123456789012:role/ops";
+        let aws_auth = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                iam_user("alice", &["system:masters"]),
+                iam_user("bob", &[]),
+            ]),
+            roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                IamArn::new(ops),
+                None,
+                Some("ops".to_string()),
+                groups(&["operators"]),
+                None,
+            )]),
+        };
+
+        let test_cases = vec![
+            TestCase {
+                include_unmanaged: false,
+                dry_run: false,
+                expected_statuses: vec![
+                    (alice, MigrationStatus::Created),
+                    (bob, MigrationStatus::AlreadyExists),
+                ],
+                expected_writes: vec![format!("create {alice}"), format!("associate {alice}")],
+                _description: "case 1: entries synced by the tool only",
+            },
+            TestCase {
+                include_unmanaged: true,
+                dry_run: false,
+                expected_statuses: vec![
+                    (ops, MigrationStatus::Created),
+                    (alice, MigrationStatus::Created),
+                    (bob, MigrationStatus::AlreadyExists),
+                ],
+                expected_writes: vec![
+                    format!("create {alice}"),
+                    format!("associate {alice}"),
+                    format!("create {ops}"),
+                ],
+                _description: "case 2: unmanaged entries included",
+            },
+            TestCase {
+                include_unmanaged: true,
+                dry_run: true,
+                expected_statuses: vec![
+                    (ops, MigrationStatus::WouldBeCreated),
+                    (alice, MigrationStatus::WouldBeCreated),
+                    (bob, MigrationStatus::AlreadyExists),
+                ],
+                expected_writes: vec![],
+                _description: "case 3: dry run",
+            },
+        ];
+
+        for tc in test_cases {
+            let api = FakeAccessEntriesApi::new(vec![access_entry(
+                bob,
+                &[],
+                Some("iam-eks-user-mapper"),
+            )]);
+            let backend = AccessEntriesBackend::new(
+                api,
+                ConfigMapOptions {
+                    dry_run: tc.dry_run,
+                    ..Default::default()
+                },
+            );
+
+            // execute:
+            let res = backend.migrate(&aws_auth, tc.include_unmanaged).await;
+
+            // verify:
+            assert_eq!(
+                Ok(tc
+                    .expected_statuses
+                    .iter()
+                    .map(|(arn, status)| MigratedEntry {
+                        principal_arn: IamArn::new(arn),
+                        status: *status,
+                    })
+                    .collect::<Vec<_>>()),
+                res,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_writes,
+                backend.api.writes(),
+                "{}",
+                tc._description
+            );
+            if let Some(migrated_ops) = backend.api.access_entries().get(ops) {
+                // unmanaged entries stay unmanaged, sync never deleting them
+                assert_eq!(None, migrated_ops.synced_by(), "{}", tc._description);
+            }
+
+            // re-running skips already migrated entries
+            if !tc.dry_run {
+                let res = backend
+                    .migrate(&aws_auth, tc.include_unmanaged)
+                    .await
+                    .expect("migration should succeed");
+                assert!(
+                    res.iter().all(|e| e.status != MigrationStatus::Created),
+                    "{}",
+                    tc._description
+                );
+                assert_eq!(
+                    tc.expected_writes,
+                    backend.api.writes(),
+                    "{}",
+                    tc._description
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn eks_cluster_is_reached_by_test() {
+        // setup:
+        let cluster_ca = vec![1, 2, 3];
+        let cluster = |ca_data: Option<String>| EksCluster {
+            name: "my-cluster".to_string(),
+            arn: "arn:aws:eks:eu-west-3:123456789012:cluster/my-cluster".to_string(),
+            version: "1.30".to_string(),
+            endpoint: "https://ABCDEF.gr7.eu-west-3.eks.amazonaws.com".to_string(),
+            certificate_authority: CertificateAuthority { data: ca_data },
+        };
+        let ca_data = BASE64_STANDARD.encode(pem::encode(&pem::Pem::new(
+            "CERTIFICATE",
+            cluster_ca.clone(),
+        )));
+
+        struct TestCase<'a> {
+            cluster: EksCluster,
+            endpoint: ClusterEndpoint,
+            expected: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                cluster: cluster(Some(ca_data.clone())),
+                endpoint: ClusterEndpoint {
+                    url: "https://abcdef.gr7.eu-west-3.eks.amazonaws.com/".to_string(),
+                    root_certs: vec![],
+                },
+                expected: true,
+                _description: "case 1 - same endpoint",
+            },
+            TestCase {
+                cluster: cluster(Some(ca_data.clone())),
+                endpoint: ClusterEndpoint {
+                    url: "https://kubernetes.default.svc/".to_string(),
+                    root_certs: vec![vec![9], cluster_ca.clone()],
+                },
+                expected: true,
+                _description: "case 2 - in-cluster endpoint with the cluster CA",
+            },
+            TestCase {
+                cluster: cluster(Some(ca_data.clone())),
+                endpoint: ClusterEndpoint {
+                    url: "https://GHIJKL.gr7.eu-west-3.eks.amazonaws.com".to_string(),
+                    root_certs: vec![vec![9]],
+                },
+                expected: false,
+                _description: "case 3 - another cluster",
+            },
+            TestCase {
+                cluster: cluster(Some("not base64".to_string())),
+                endpoint: ClusterEndpoint {
+                    url: "https://kubernetes.default.svc/".to_string(),
+                    root_certs: vec![cluster_ca.clone()],
+                },
+                expected: false,
+                _description: "case 4 - undecodable CA",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = tc.cluster.is_reached_by(&tc.endpoint);
+
+            // verify:
+            assert_eq!(tc.expected, res, "{}", tc._description);
+        }
+    }
+
+    #[tokio::test]
+    async fn discover_node_roles_test() {
+        // setup:
+        let nodegroups = FakeNodegroupsApi::new(vec![
+            ("default", "arn:aws:iam::123:role/nodes"),
+            ("gpu", "arn:aws:iam::123:role/gpu-nodes"),
+            ("spot", "arn:aws:iam::123:role/nodes"),
+        ]);
+
+        // execute:
+        let res = discover_node_roles(&nodegroups).await;
+
+        // verify:
+        let node_role = |arn: &str| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                groups(&["system:bootstrappers", "system:nodes"]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        assert_eq!(
+            Ok(vec![
+                node_role("arn:aws:iam::123:role/gpu-nodes"),
+                node_role("arn:aws:iam::123:role/nodes"),
+            ]),
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn eks_service_list_access_entries_test() {
+        struct TestCase<'a> {
+            responses: Vec<(u16, String)>,
+            expected: Result<Vec<&'a str>, bool>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                responses: vec![(200, list_access_entries_response(&[], None))],
+                expected: Ok(vec![]),
+                _description: "case 1 - no access entries",
+            },
+            TestCase {
+                responses: vec![
+                    (
+                        200,
+                        list_access_entries_response(
+                            &["arn:aws:iam::123:user/alice", "arn:aws:iam::123:role/admin"],
+                            Some("page-2"),
+                        ),
+                    ),
+                    (
+                        200,
+                        list_access_entries_response(&["arn:aws:iam::123:user/bob"], None),
+                    ),
+                ],
+                expected: Ok(vec![
+                    "arn:aws:iam::123:user/alice",
+                    "arn:aws:iam::123:role/admin",
+                    "arn:aws:iam::123:user/bob",
+                ]),
+                _description: "case 2 - access entries spread over two pages",
+            },
+            TestCase {
+                responses: vec![(
+                    400,
+                    r#"{"__type":"ThrottlingException","message":"Rate exceeded"}"#.to_string(),
+                )],
+                expected: Err(true),
+                _description: "case 3 - throttled request is retryable",
+            },
+            TestCase {
+                responses: vec![(
+                    403,
+                    r#"{"__type":"AccessDeniedException","message":"not authorized"}"#.to_string(),
+                )],
+                expected: Err(false),
+                _description: "case 4 - access denied is fatal",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let (eks, http_client) = replaying_eks_service(&tc.responses);
+
+            // execute:
+            let res = eks.list_access_entries().await;
+
+            // verify:
+            match (tc.expected, res) {
+                (Ok(expected), Ok(principal_arns)) => {
+                    assert_eq!(expected, principal_arns, "{}", tc._description)
+                }
+                (Err(expected_retryable), Err(e @ EksError::CannotListAccessEntries { .. })) => {
+                    assert_eq!(expected_retryable, e.is_retryable(), "{}", tc._description)
+                }
+                (expected, res) => {
+                    panic!("{}: expected {expected:?}, got {res:?}", tc._description)
+                }
+            }
+            assert_eq!(
+                tc.responses.len(),
+                http_client.actual_requests().count(),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn eks_service_describe_access_entry_test() {
+        // setup:
+        let (eks, _) = replaying_eks_service(&[(
+            200,
+            r#"{"accessEntry":{"clusterName":"my-cluster","principalArn":"arn:aws:iam::123:role/node","type":"EC2_LINUX","kubernetesGroups":["system:nodes","system:bootstrappers"],"username":"system:node:{{EC2PrivateDNSName}}","tags":{"iam-eks-user-mapper/synced-by":"instance-a"}}}"#
+                .to_string(),
+        )]);
+
+        // execute:
+        let res = eks
+            .describe_access_entry("arn:aws:iam::123:role/node")
+            .await;
+
+        // verify:
+        assert_eq!(
+            AccessEntry {
+                principal_arn: "arn:aws:iam::123:role/node".to_string(),
+                entry_type: AccessEntryType::Ec2Linux,
+                kubernetes_groups: BTreeSet::from([
+                    "system:bootstrappers".to_string(),
+                    "system:nodes".to_string()
+                ]),
+                username: Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                tags: BTreeMap::from([(SYNCED_BY_TAG.to_string(), "instance-a".to_string())]),
+            },
+            res.expect("access entry should be described")
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::aws::eks::{
+    AccessEntriesApi, AccessEntry, ClusterApi, EksCluster, EksError, Nodegroup, NodegroupsApi,
+};
+use crate::aws::iam::{
+    Arn, AwsUser, IamError, IamGroup, IamRoleSource, IamUserSource, User, SSO_ROLES_PATH,
+};
+use crate::aws::{AwsError, CallerIdentity, CallerIdentitySource};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// In-memory IAM groups, used to test the sync logic without any AWS account.
+#[derive(Default)]
+pub struct FakeIamUserSource {
+    /// Users (name, ARN) per IAM group.
+    pub groups: HashMap<IamGroup, Vec<(String, String)>>,
+    /// ARNs of existing IAM roles.
+    pub roles: Vec<String>,
+    /// Role ARN per instance profile name.
+    pub instance_profiles: HashMap<String, String>,
+}
+
+impl FakeIamUserSource {
+    pub fn new(groups: Vec<(&str, Vec<(&str, &str)>)>) -> FakeIamUserSource {
+        FakeIamUserSource {
+            groups: groups
+                .into_iter()
+                .map(|(group, users)| {
+                    (
+                        IamGroup::new(group),
+                        users
+                            .into_iter()
+                            .map(|(name, arn)| (name.to_string(), arn.to_string()))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            roles: Vec::new(),
+            instance_profiles: HashMap::new(),
+        }
+    }
+
+    pub fn with_roles(self, roles: &[&str]) -> FakeIamUserSource {
+        FakeIamUserSource {
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            ..self
+        }
+    }
+
+    pub fn with_instance_profiles(self, instance_profiles: &[(&str, &str)]) -> FakeIamUserSource {
+        FakeIamUserSource {
+            instance_profiles: instance_profiles
+                .iter()
+                .map(|(name, role_arn)| (name.to_string(), role_arn.to_string()))
+                .collect(),
+            ..self
+        }
+    }
+}
+
+impl IamRoleSource for FakeIamUserSource {
+    async fn get_role_arn(&self, role_name: &str) -> Result<Arn, IamError> {
+        self.roles
+            .iter()
+            .find(|arn| {
+                arn.rsplit_once('/')
+                    .is_some_and(|(_, name)| name == role_name)
+            })
+            .map(|arn| Arn::new(arn))
+            .ok_or_else(|| IamError::CannotGetRole {
+                role_name: Arc::from(role_name),
+                raw_message: Arc::from("NoSuchEntity"),
+                retryable: false,
+            })
+    }
+
+    async fn get_instance_profile_role_arn(
+        &self,
+        instance_profile_name: &str,
+    ) -> Result<Arn, IamError> {
+        self.instance_profiles
+            .get(instance_profile_name)
+            .map(|role_arn| Arn::new(role_arn))
+            .ok_or_else(|| IamError::CannotGetInstanceProfileRole {
+                instance_profile_name: Arc::from(instance_profile_name),
+                raw_message: Arc::from("NoSuchEntity"),
+                retryable: false,
+            })
+    }
+
+    async fn list_sso_role_arns(&self) -> Result<Vec<Arn>, IamError> {
+        Ok(self
+            .roles
+            .iter()
+            .filter(|arn| arn.contains(&format!(":role{SSO_ROLES_PATH}")))
+            .map(|arn| Arn::new(arn))
+            .collect())
+    }
+}
+
+impl IamUserSource for FakeIamUserSource {
+    async fn get_users_from_groups(
+        &self,
+        iam_groups: BTreeSet<IamGroup>,
+    ) -> Result<HashSet<AwsUser>, IamError> {
+        // merging groups of users belonging to several IAM groups
+        let mut users: HashMap<(String, String), BTreeSet<IamGroup>> = HashMap::new();
+        for iam_group in iam_groups {
+            let group_users = match self.groups.get(&iam_group) {
+                Some(group_users) if !group_users.is_empty() => group_users,
+                _ => return Err(IamError::NoUsersFoundInIamGroup { group: iam_group }),
+            };
+            for user in group_users {
+                users
+                    .entry(user.clone())
+                    .or_default()
+                    .insert(iam_group.clone());
+            }
+        }
+
+        Ok(users
+            .into_iter()
+            .map(|((name, arn), groups)| AwsUser {
+                arn: Arn::new(&arn),
+                user_name: User::new(&name),
+                groups,
+            })
+            .collect())
+    }
+}
+
+/// Identity of fake AWS credentials, `None` simulating credentials which cannot be resolved.
+pub struct FakeCallerIdentity {
+    pub identity: Option<CallerIdentity>,
+}
+
+impl FakeCallerIdentity {
+    pub fn new(arn: &str) -> FakeCallerIdentity {
+        FakeCallerIdentity {
+            identity: Some(CallerIdentity {
+                account: arn.split(':').nth(4).unwrap_or_default().to_string(),
+                arn: arn.to_string(),
+            }),
+        }
+    }
+}
+
+impl CallerIdentitySource for FakeCallerIdentity {
+    async fn get_caller_identity(&self) -> Result<CallerIdentity, AwsError> {
+        self.identity
+            .clone()
+            .ok_or_else(|| AwsError::CannotGetCallerIdentity {
+                raw_message: Arc::from("InvalidClientTokenId"),
+                retryable: false,
+            })
+    }
+}
+
+/// In-memory EKS cluster access entries, used to test access entries sync without any AWS account.
+#[derive(Default)]
+pub struct FakeAccessEntriesApi {
+    pub access_entries: Mutex<BTreeMap<String, AccessEntry>>,
+    /// Access policies ARNs associated to each principal ARN.
+    pub access_policies: Mutex<BTreeMap<String, BTreeSet<String>>>,
+    /// Write requests received, e.q: `delete arn:test:user_1`.
+    pub writes: Mutex<Vec<String>>,
+}
+
+impl FakeAccessEntriesApi {
+    pub fn new(access_entries: Vec<AccessEntry>) -> FakeAccessEntriesApi {
+        FakeAccessEntriesApi {
+            access_entries: Mutex::new(
+                access_entries
+                    .into_iter()
+                    .map(|e| (e.principal_arn.clone(), e))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    pub fn access_entries(&self) -> BTreeMap<String, AccessEntry> {
+        self.access_entries
+            .lock()
+            .expect("cannot lock fake access entries")
+            .clone()
+    }
+
+    pub fn access_policies(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.access_policies
+            .lock()
+            .expect("cannot lock fake access policies")
+            .clone()
+    }
+
+    pub fn writes(&self) -> Vec<String> {
+        self.writes.lock().expect("cannot lock fake writes").clone()
+    }
+
+    fn record_write(&self, write: &str, principal_arn: &str) {
+        self.writes
+            .lock()
+            .expect("cannot lock fake writes")
+            .push(format!("{write} {principal_arn}"));
+    }
+}
+
+impl AccessEntriesApi for FakeAccessEntriesApi {
+    fn cluster_name(&self) -> &str {
+        "fake-cluster"
+    }
+
+    async fn list_access_entries(&self) -> Result<Vec<String>, EksError> {
+        Ok(self.access_entries().into_keys().collect())
+    }
+
+    async fn describe_access_entry(&self, principal_arn: &str) -> Result<AccessEntry, EksError> {
+        self.access_entries().remove(principal_arn).ok_or_else(|| {
+            EksError::CannotDescribeAccessEntry {
+                principal_arn: Arc::from(principal_arn),
+                raw_message: Arc::from("ResourceNotFoundException"),
+                retryable: false,
+            }
+        })
+    }
+
+    async fn create_access_entry(&self, access_entry: &AccessEntry) -> Result<(), EksError> {
+        self.record_write("create", &access_entry.principal_arn);
+        let mut access_entries = self
+            .access_entries
+            .lock()
+            .expect("cannot lock fake access entries");
+        if access_entries.contains_key(&access_entry.principal_arn) {
+            return Err(EksError::CannotCreateAccessEntry {
+                principal_arn: Arc::from(access_entry.principal_arn.as_str()),
+                raw_message: Arc::from("ResourceInUseException"),
+                retryable: true,
+            });
+        }
+        access_entries.insert(access_entry.principal_arn.clone(), access_entry.clone());
+
+        Ok(())
+    }
+
+    async fn update_access_entry(&self, access_entry: &AccessEntry) -> Result<(), EksError> {
+        self.record_write("update", &access_entry.principal_arn);
+        match self
+            .access_entries
+            .lock()
+            .expect("cannot lock fake access entries")
+            .get_mut(&access_entry.principal_arn)
+        {
+            Some(existing) => {
+                existing.kubernetes_groups = access_entry.kubernetes_groups.clone();
+                if access_entry.username.is_some() {
+                    existing.username = access_entry.username.clone();
+                }
+                Ok(())
+            }
+            None => Err(EksError::CannotUpdateAccessEntry {
+                principal_arn: Arc::from(access_entry.principal_arn.as_str()),
+                raw_message: Arc::from("ResourceNotFoundException"),
+                retryable: false,
+            }),
+        }
+    }
+
+    async fn delete_access_entry(&self, principal_arn: &str) -> Result<(), EksError> {
+        self.record_write("delete", principal_arn);
+        self.access_policies
+            .lock()
+            .expect("cannot lock fake access policies")
+            .remove(principal_arn);
+        self.access_entries
+            .lock()
+            .expect("cannot lock fake access entries")
+            .remove(principal_arn)
+            .map(|_| ())
+            .ok_or_else(|| EksError::CannotDeleteAccessEntry {
+                principal_arn: Arc::from(principal_arn),
+                raw_message: Arc::from("ResourceNotFoundException"),
+                retryable: false,
+            })
+    }
+
+    async fn associate_access_policy(
+        &self,
+        principal_arn: &str,
+        policy_arn: &str,
+    ) -> Result<(), EksError> {
+        self.record_write("associate", principal_arn);
+        self.access_policies
+            .lock()
+            .expect("cannot lock fake access policies")
+            .entry(principal_arn.to_string())
+            .or_default()
+            .insert(policy_arn.to_string());
+
+        Ok(())
+    }
+}
+
+/// In-memory EKS cluster managed nodegroups, as (name, node role ARN).
+#[derive(Default)]
+pub struct FakeNodegroupsApi {
+    pub nodegroups: Vec<(String, String)>,
+}
+
+impl FakeNodegroupsApi {
+    pub fn new(nodegroups: Vec<(&str, &str)>) -> FakeNodegroupsApi {
+        FakeNodegroupsApi {
+            nodegroups: nodegroups
+                .into_iter()
+                .map(|(name, node_role)| (name.to_string(), node_role.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl NodegroupsApi for FakeNodegroupsApi {
+    async fn list_nodegroups(&self) -> Result<Vec<String>, EksError> {
+        Ok(self
+            .nodegroups
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    async fn describe_nodegroup(&self, nodegroup_name: &str) -> Result<Nodegroup, EksError> {
+        self.nodegroups
+            .iter()
+            .find(|(name, _)| name == nodegroup_name)
+            .map(|(name, node_role)| Nodegroup {
+                nodegroup_name: name.clone(),
+                node_role: node_role.clone(),
+            })
+            .ok_or_else(|| EksError::CannotDescribeNodegroup {
+                nodegroup_name: Arc::from(nodegroup_name),
+                raw_message: Arc::from("ResourceNotFoundException"),
+                retryable: false,
+            })
+    }
+}
+
+/// In-memory EKS cluster description, `None` simulating a describe failure.
+pub struct FakeClusterApi {
+    pub cluster: Option<EksCluster>,
+}
+
+impl ClusterApi for FakeClusterApi {
+    async fn describe_cluster(&self) -> Result<EksCluster, EksError> {
+        self.cluster
+            .clone()
+            .ok_or_else(|| EksError::CannotDescribeCluster {
+                cluster_name: Arc::from("my-cluster"),
+                raw_message: Arc::from("ResourceNotFoundException"),
+                retryable: false,
+            })
+    }
+}
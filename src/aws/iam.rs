@@ -1,19 +1,65 @@
-use crate::aws::AwsSdkConfig;
-use std::collections::HashSet;
+use crate::aws::{is_retryable_sdk_error, AwsSdkConfig};
+use crate::config::sso_role_permission_set;
+use crate::kubernetes::{IamArn, KubernetesRole};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use thiserror::Error;
+use tracing::{field, info, info_span, warn, Instrument};
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum IamError {
     #[error("Cannot get users from IAM group `{group}`, error: {raw_message}")]
     CannotGetUserFromIamGroup {
         group: IamGroup,
         raw_message: Arc<str>,
+        /// Whether IAM failed in a way retrying might fix, e.q: throttling, and not for a missing group.
+        retryable: bool,
     },
     #[error("No users found in IAM group `{group}`")]
     NoUsersFoundInIamGroup { group: IamGroup },
+    #[error("Cannot get IAM role `{role_name}`, error: {raw_message}")]
+    CannotGetRole {
+        role_name: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error(
+        "Cannot get role of IAM instance profile `{instance_profile_name}`, error: {raw_message}"
+    )]
+    CannotGetInstanceProfileRole {
+        instance_profile_name: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot list IAM Identity Center roles, error: {raw_message}")]
+    CannotListSSORoles {
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+    #[error("Cannot find role of SSO permission set `{permission_set}`, error: {raw_message}")]
+    CannotFindSSORole {
+        permission_set: Arc<str>,
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+}
+
+impl IamError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            IamError::CannotGetUserFromIamGroup { retryable, .. }
+            | IamError::CannotGetRole { retryable, .. }
+            | IamError::CannotGetInstanceProfileRole { retryable, .. }
+            | IamError::CannotListSSORoles { retryable, .. }
+            | IamError::CannotFindSSORole { retryable, .. } => *retryable,
+            // users may be added to the group at any time
+            IamError::NoUsersFoundInIamGroup { .. } => true,
+        }
+    }
 }
 
 #[derive(Eq, PartialEq)]
@@ -31,7 +77,7 @@ impl Display for Arn {
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub struct IamGroup(String);
 
 impl IamGroup {
@@ -65,7 +111,8 @@ impl Display for User {
 pub struct AwsUser {
     pub arn: Arn,
     pub user_name: User,
-    pub groups: HashSet<IamGroup>,
+    /// IAM groups the user has been found in, only among the ones users were fetched from, e.q: mapped IAM groups.
+    pub groups: BTreeSet<IamGroup>,
 }
 
 impl Hash for AwsUser {
@@ -75,6 +122,32 @@ impl Hash for AwsUser {
     }
 }
 
+/// Source of IAM users, allowing to sync users without relying on a concrete AWS client.
+pub trait IamUserSource {
+    async fn get_users_from_groups(
+        &self,
+        iam_groups: BTreeSet<IamGroup>,
+    ) -> Result<HashSet<AwsUser>, IamError>;
+}
+
+/// Source of IAM roles, allowing to check configured roles exist without relying on a concrete AWS client.
+pub trait IamRoleSource {
+    /// ARN of the role named `role_name` in the account of the credentials.
+    async fn get_role_arn(&self, role_name: &str) -> Result<Arn, IamError>;
+
+    /// ARN of the role held by the instance profile named `instance_profile_name`, an instance profile holding one role at most.
+    async fn get_instance_profile_role_arn(
+        &self,
+        instance_profile_name: &str,
+    ) -> Result<Arn, IamError>;
+
+    /// ARNs of the roles IAM Identity Center provisioned in the account of the credentials, under `SSO_ROLES_PATH`.
+    async fn list_sso_role_arns(&self) -> Result<Vec<Arn>, IamError>;
+}
+
+/// IAM path of the roles IAM Identity Center provisions, followed by their region for recent ones.
+pub const SSO_ROLES_PATH: &str = "/aws-reserved/sso.amazonaws.com/";
+
 pub struct IamService {
     client: aws_sdk_iam::Client,
     _verbose: bool,
@@ -82,73 +155,593 @@ pub struct IamService {
 
 impl IamService {
     pub fn new(config: &AwsSdkConfig, verbose: bool) -> Self {
+        Self::with_client(aws_sdk_iam::Client::new(&config.config), verbose)
+    }
+
+    /// Service calling IAM through `client`, e.q: a client replaying canned responses.
+    pub fn with_client(client: aws_sdk_iam::Client, verbose: bool) -> Self {
         IamService {
-            client: aws_sdk_iam::Client::new(&config.config),
+            client,
             _verbose: verbose,
         }
     }
 
-    pub async fn get_users_from_groups(
+    pub async fn get_users_from_group(
         &self,
-        iam_groups: HashSet<IamGroup>,
+        iam_group: &IamGroup,
     ) -> Result<HashSet<AwsUser>, IamError> {
-        let mut all_users = HashSet::new();
-
-        for iam_group in iam_groups {
-            match self.get_users_from_group(&iam_group).await {
-                Ok(users) => all_users.extend(users),
-                Err(e) => return Err(e),
+        let mut users: HashSet<AwsUser> = HashSet::new();
+        let mut marker = None;
+        loop {
+            let output = self
+                .client
+                .get_group()
+                .group_name(iam_group.to_string())
+                .max_items(1000)
+                .set_marker(marker)
+                .send()
+                .await
+                .map_err(|e| IamError::CannotGetUserFromIamGroup {
+                    group: iam_group.clone(),
+                    raw_message: Arc::from(e.to_string()),
+                    retryable: is_retryable_sdk_error(&e),
+                })?;
+            users.extend(output.users().iter().map(|user| AwsUser {
+                arn: Arn::new(user.arn()),
+                user_name: User::new(user.user_name()),
+                groups: BTreeSet::from_iter(vec![iam_group.clone()]),
+            }));
+            match (output.is_truncated(), output.marker()) {
+                (true, Some(next_marker)) => marker = Some(next_marker.to_string()),
+                _ => break,
             }
         }
 
-        Ok(all_users)
+        if users.is_empty() {
+            return Err(IamError::NoUsersFoundInIamGroup {
+                group: iam_group.clone(),
+            });
+        }
+
+        Ok(users)
     }
+}
 
-    pub async fn get_users_from_group(
-        &self,
-        iam_group: &IamGroup,
-    ) -> Result<HashSet<AwsUser>, IamError> {
-        let mut users: HashSet<AwsUser> = HashSet::new();
+impl IamRoleSource for IamService {
+    async fn get_role_arn(&self, role_name: &str) -> Result<Arn, IamError> {
+        self.client
+            .get_role()
+            .role_name(role_name)
+            .send()
+            .await
+            .map(|output| Arn::new(output.role().map(|r| r.arn()).unwrap_or_default()))
+            .map_err(|e| IamError::CannotGetRole {
+                role_name: Arc::from(role_name),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })
+    }
 
-        match self
+    async fn get_instance_profile_role_arn(
+        &self,
+        instance_profile_name: &str,
+    ) -> Result<Arn, IamError> {
+        let output = self
             .client
-            .get_group()
-            .group_name(iam_group.to_string())
-            .max_items(1000)
+            .get_instance_profile()
+            .instance_profile_name(instance_profile_name)
             .send()
             .await
+            .map_err(|e| IamError::CannotGetInstanceProfileRole {
+                instance_profile_name: Arc::from(instance_profile_name),
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })?;
+
+        match output
+            .instance_profile()
+            .and_then(|instance_profile| instance_profile.roles().first())
         {
-            Ok(group) => {
-                let group_users = group.users();
+            Some(role) => Ok(Arn::new(role.arn())),
+            // a role may be added to the instance profile at any time
+            None => Err(IamError::CannotGetInstanceProfileRole {
+                instance_profile_name: Arc::from(instance_profile_name),
+                raw_message: Arc::from("instance profile holds no role"),
+                retryable: true,
+            }),
+        }
+    }
 
-                if group_users.is_empty() {
-                    return Err(IamError::NoUsersFoundInIamGroup {
-                        group: iam_group.clone(),
-                    });
-                }
+    async fn list_sso_role_arns(&self) -> Result<Vec<Arn>, IamError> {
+        let mut role_arns = Vec::new();
+        let mut marker = None;
+        loop {
+            let output = self
+                .client
+                .list_roles()
+                .path_prefix(SSO_ROLES_PATH)
+                .set_marker(marker)
+                .send()
+                .await
+                .map_err(|e| IamError::CannotListSSORoles {
+                    raw_message: Arc::from(e.to_string()),
+                    retryable: is_retryable_sdk_error(&e),
+                })?;
+            role_arns.extend(output.roles().iter().map(|role| Arn::new(role.arn())));
+            match (output.is_truncated(), output.marker()) {
+                (true, Some(next_marker)) => marker = Some(next_marker.to_string()),
+                _ => return Ok(role_arns),
+            }
+        }
+    }
+}
+
+/// ARN of the `AWSReservedSSO_<PERMISSION_SET>_<SUFFIX>` role of `permission_set`, failing unless a single one exists.
+pub async fn find_sso_role_arn(
+    iam: &impl IamRoleSource,
+    permission_set: &str,
+) -> Result<Arn, IamError> {
+    let mut role_arns = iam
+        .list_sso_role_arns()
+        .await?
+        .into_iter()
+        .filter(|arn| sso_role_permission_set(&arn.to_string()) == Some(permission_set))
+        .collect::<Vec<_>>();
 
-                for user in group_users {
-                    let mut aws_user = AwsUser {
-                        arn: Arn::new(user.arn()),
-                        user_name: User::new(user.user_name()),
-                        groups: HashSet::from_iter(vec![iam_group.clone()]),
-                    };
+    match role_arns.len() {
+        1 => Ok(role_arns.remove(0)),
+        // the permission set may be provisioned at any time
+        0 => Err(IamError::CannotFindSSORole {
+            permission_set: Arc::from(permission_set),
+            raw_message: Arc::from(format!(
+                "no role `AWSReservedSSO_{permission_set}_*` under `{SSO_ROLES_PATH}`"
+            )),
+            retryable: true,
+        }),
+        _ => Err(IamError::CannotFindSSORole {
+            permission_set: Arc::from(permission_set),
+            raw_message: Arc::from(format!(
+                "several roles found, set `iam_sso_role_arn` instead: {}",
+                role_arns
+                    .iter()
+                    .map(|arn| format!("`{arn}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            retryable: false,
+        }),
+    }
+}
+
+/// Roles with instance profile ARNs replaced by the ARN of the role they hold, others being kept as is.
+/// Roles ending up with an ARN already mapped get a single entry.
+pub async fn resolve_instance_profiles(
+    iam: &impl IamRoleSource,
+    roles: &[KubernetesRole],
+) -> Result<Vec<KubernetesRole>, IamError> {
+    let mut resolved_roles: Vec<KubernetesRole> = Vec::with_capacity(roles.len());
+    for role in roles {
+        let mut role = role.clone();
+        if role.iam_role_arn.is_instance_profile() {
+            let role_arn = iam
+                .get_instance_profile_role_arn(role.iam_role_arn.name())
+                .await?;
+            info!(
+                "Instance profile `{}` resolved to role `{role_arn}`",
+                role.iam_role_arn
+            );
+            role.iam_role_arn = IamArn::new(&role_arn.to_string());
+        }
+        if resolved_roles
+            .iter()
+            .any(|r| r.iam_role_arn == role.iam_role_arn)
+        {
+            warn!(
+                "Role ARN `{}` is set more than once, mapping it once",
+                role.iam_role_arn
+            );
+            continue;
+        }
+        resolved_roles.push(role);
+    }
+
+    Ok(resolved_roles)
+}
+
+impl IamUserSource for IamService {
+    async fn get_users_from_groups(
+        &self,
+        iam_groups: BTreeSet<IamGroup>,
+    ) -> Result<HashSet<AwsUser>, IamError> {
+        // merging groups of users belonging to several IAM groups
+        let mut all_users: HashMap<(String, String), AwsUser> = HashMap::new();
 
-                    if let Some(u) = users.get(&aws_user) {
-                        aws_user.groups.extend(u.groups.clone());
+        for iam_group in iam_groups {
+            let span = info_span!("iam_group_fetch", iam_group = %iam_group, users = field::Empty);
+            match self
+                .get_users_from_group(&iam_group)
+                .instrument(span.clone())
+                .await
+            {
+                Ok(users) => {
+                    span.record("users", users.len());
+                    for user in users {
+                        match all_users.entry((user.user_name.to_string(), user.arn.to_string())) {
+                            Entry::Occupied(mut known_user) => {
+                                known_user.get_mut().groups.extend(user.groups)
+                            }
+                            Entry::Vacant(entry) => {
+                                entry.insert(user);
+                            }
+                        }
                     }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(all_users.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::fake::FakeIamUserSource;
+    use crate::aws::iam::{
+        find_sso_role_arn, resolve_instance_profiles, Arn, IamError, IamGroup, IamService,
+        IamUserSource,
+    };
+    use crate::kubernetes::{IamArn, KubernetesGroupName, KubernetesRole};
+    use aws_sdk_iam::config::http::{HttpRequest, HttpResponse};
+    use aws_sdk_iam::config::retry::RetryConfig;
+    use aws_sdk_iam::config::{BehaviorVersion, Credentials, Region};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use std::collections::BTreeSet;
+
+    /// IAM service replaying `responses`, as HTTP status and body, in order, and the client recording its requests.
+    fn replaying_iam_service(responses: &[(u16, String)]) -> (IamService, StaticReplayClient) {
+        let http_client = StaticReplayClient::new(
+            responses
+                .iter()
+                .map(|(status, body)| {
+                    ReplayEvent::new(
+                        HttpRequest::new(SdkBody::empty()),
+                        HttpResponse::new(
+                            (*status).try_into().expect("status should be valid"),
+                            SdkBody::from(body.as_str()),
+                        ),
+                    )
+                })
+                .collect(),
+        );
+        let config = aws_sdk_iam::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("AKID", "SECRET", None, None, "test"))
+            // throttling errors are retried by the sync loop, not the SDK
+            .retry_config(RetryConfig::disabled())
+            .http_client(http_client.clone())
+            .build();
+
+        (
+            IamService::with_client(aws_sdk_iam::Client::from_conf(config), false),
+            http_client,
+        )
+    }
+
+    /// GetGroup response body listing `users` of `group`, `marker` being set when more users are to be fetched.
+    fn get_group_response(group: &str, users: &[&str], marker: Option<&str>) -> String {
+        let users = users
+            .iter()
+            .map(|user| {
+                format!(
+                    "<member><Path>/</Path><UserName>{user}</UserName><UserId>AIDA{user}</UserId>\
+                     <Arn>arn:aws:iam::123:user/{user}</Arn><CreateDate>2024-01-01T00:00:00Z</CreateDate></member>"
+                )
+            })
+            .collect::<String>();
+        let pagination = match marker {
+            Some(marker) => format!("<IsTruncated>true</IsTruncated><Marker>{marker}</Marker>"),
+            None => "<IsTruncated>false</IsTruncated>".to_string(),
+        };
+
+        format!(
+            "<GetGroupResponse xmlns=\"https://iam.amazonaws.com/doc/2010-05-08/\"><GetGroupResult>\
+             <Group><Path>/</Path><GroupName>{group}</GroupName><GroupId>AGPA{group}</GroupId>\
+             <Arn>arn:aws:iam::123:group/{group}</Arn><CreateDate>2024-01-01T00:00:00Z</CreateDate></Group>\
+             <Users>{users}</Users>{pagination}</GetGroupResult>\
+             <ResponseMetadata><RequestId>request-id</RequestId></ResponseMetadata></GetGroupResponse>"
+        )
+    }
 
-                    users.insert(aws_user);
+    /// IAM error response body with `code`.
+    fn error_response(code: &str) -> String {
+        format!(
+            "<ErrorResponse xmlns=\"https://iam.amazonaws.com/doc/2010-05-08/\"><Error><Type>Sender</Type>\
+             <Code>{code}</Code><Message>{code} message</Message></Error><RequestId>request-id</RequestId></ErrorResponse>"
+        )
+    }
+
+    #[tokio::test]
+    async fn get_users_from_group_test() {
+        // setup:
+        struct TestCase<'a> {
+            responses: Vec<(u16, String)>,
+            expected: Result<Vec<&'a str>, (&'a str, bool)>,
+            expected_markers: Vec<Option<&'a str>>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                responses: vec![(200, get_group_response("Admins", &["alice", "bob"], None))],
+                expected: Ok(vec!["alice", "bob"]),
+                expected_markers: vec![None],
+                _description: "case 1 - single page",
+            },
+            TestCase {
+                responses: vec![
+                    (
+                        200,
+                        get_group_response("Admins", &["alice"], Some("page-2")),
+                    ),
+                    (200, get_group_response("Admins", &["bob"], Some("page-3"))),
+                    (200, get_group_response("Admins", &["carol"], None)),
+                ],
+                expected: Ok(vec!["alice", "bob", "carol"]),
+                expected_markers: vec![None, Some("page-2"), Some("page-3")],
+                _description: "case 2 - truncated pages fetched from their marker",
+            },
+            TestCase {
+                responses: vec![(200, get_group_response("Admins", &[], None))],
+                expected: Err(("NoUsersFoundInIamGroup", false)),
+                expected_markers: vec![None],
+                _description: "case 3 - empty group",
+            },
+            TestCase {
+                responses: vec![(404, error_response("NoSuchEntity"))],
+                expected: Err(("CannotGetUserFromIamGroup", false)),
+                expected_markers: vec![None],
+                _description: "case 4 - missing group",
+            },
+            TestCase {
+                responses: vec![(400, error_response("Throttling"))],
+                expected: Err(("CannotGetUserFromIamGroup", true)),
+                expected_markers: vec![None],
+                _description: "case 5 - throttled",
+            },
+            TestCase {
+                responses: vec![
+                    (
+                        200,
+                        get_group_response("Admins", &["alice"], Some("page-2")),
+                    ),
+                    (400, error_response("Throttling")),
+                ],
+                expected: Err(("CannotGetUserFromIamGroup", true)),
+                expected_markers: vec![None, Some("page-2")],
+                _description: "case 6 - throttled on a later page",
+            },
+        ];
+
+        for tc in test_cases {
+            let (iam, http_client) = replaying_iam_service(&tc.responses);
+
+            // execute:
+            let res = iam.get_users_from_group(&IamGroup::new("Admins")).await;
+
+            // verify:
+            match (tc.expected, res) {
+                (Ok(expected_users), Ok(users)) => {
+                    let mut user_names = users
+                        .iter()
+                        .map(|user| {
+                            assert_eq!(
+                                format!("arn:aws:iam::123:user/{}", user.user_name),
+                                user.arn.to_string(),
+                                "{}",
+                                tc._description
+                            );
+                            assert_eq!(
+                                BTreeSet::from_iter(vec![IamGroup::new("Admins")]),
+                                user.groups,
+                                "{}",
+                                tc._description
+                            );
+                            user.user_name.to_string()
+                        })
+                        .collect::<Vec<_>>();
+                    user_names.sort();
+                    assert_eq!(expected_users, user_names, "{}", tc._description);
+                }
+                (
+                    Err(("NoUsersFoundInIamGroup", _)),
+                    Err(IamError::NoUsersFoundInIamGroup { group }),
+                ) => {
+                    assert_eq!(IamGroup::new("Admins"), group, "{}", tc._description)
+                }
+                (
+                    Err(("CannotGetUserFromIamGroup", expected_retryable)),
+                    Err(IamError::CannotGetUserFromIamGroup {
+                        group, retryable, ..
+                    }),
+                ) => {
+                    assert_eq!(IamGroup::new("Admins"), group, "{}", tc._description);
+                    assert_eq!(expected_retryable, retryable, "{}", tc._description);
                 }
+                (expected, res) => panic!(
+                    "{}: expected {expected:?}, got {:?}",
+                    tc._description,
+                    res.map(|users| users.len())
+                ),
             }
-            Err(e) => {
-                return Err(IamError::CannotGetUserFromIamGroup {
-                    group: iam_group.clone(),
-                    raw_message: Arc::from(e.to_string()),
+            let markers = http_client
+                .actual_requests()
+                .map(|request| {
+                    let body = std::str::from_utf8(request.body().bytes().unwrap_or_default())
+                        .expect("request body should be UTF-8")
+                        .to_string();
+                    assert!(body.contains("GroupName=Admins"), "{}", tc._description);
+                    body.split('&')
+                        .find_map(|param| param.strip_prefix("Marker="))
+                        .map(str::to_string)
                 })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                tc.expected_markers,
+                markers.iter().map(Option::as_deref).collect::<Vec<_>>(),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_users_from_groups_test() {
+        // setup:
+        // responses are replayed in order whatever the group asked for, alice being in both groups anyway
+        let (iam, _) = replaying_iam_service(&[
+            (200, get_group_response("Admins", &["alice"], None)),
+            (200, get_group_response("Devops", &["alice", "bob"], None)),
+        ]);
+        let res = iam
+            .get_users_from_groups(BTreeSet::from_iter(vec![
+                IamGroup::new("Admins"),
+                IamGroup::new("Devops"),
+            ]))
+            .await;
+
+        // verify:
+        let users = res.expect("users should be fetched");
+        assert_eq!(2, users.len());
+        let alice = users
+            .iter()
+            .find(|user| user.user_name.to_string() == "alice")
+            .expect("alice should be found");
+        assert_eq!(2, alice.groups.len());
+        let bob = users
+            .iter()
+            .find(|user| user.user_name.to_string() == "bob")
+            .expect("bob should be found");
+        assert_eq!(1, bob.groups.len());
+    }
+
+    #[tokio::test]
+    async fn find_sso_role_arn_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![]).with_roles(&[
+            "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac",
+            "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_Administrator_Access_0a1b2c3d4e5f6a7b",
+            "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/AWSReservedSSO_ReadOnly_1111111111111111",
+            "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_ReadOnly_2222222222222222",
+            // not provisioned by IAM Identity Center
+            "arn:aws:iam::123456789012:role/AWSReservedSSO_Billing_3333333333333333",
+        ]);
+
+        // execute & verify:
+        assert!(matches!(
+            find_sso_role_arn(&iam, "AdministratorAccess").await,
+            Ok(arn) if arn == Arn::new("arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac")
+        ));
+        // permission set names may contain `_`
+        assert!(matches!(
+            find_sso_role_arn(&iam, "Administrator_Access").await,
+            Ok(arn) if arn == Arn::new("arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_Administrator_Access_0a1b2c3d4e5f6a7b")
+        ));
+        assert!(matches!(
+            find_sso_role_arn(&iam, "Administrator").await,
+            Err(IamError::CannotFindSSORole {
+                retryable: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            find_sso_role_arn(&iam, "Billing").await,
+            Err(IamError::CannotFindSSORole {
+                retryable: true,
+                ..
+            })
+        ));
+        match find_sso_role_arn(&iam, "ReadOnly").await {
+            Err(IamError::CannotFindSSORole {
+                permission_set,
+                raw_message,
+                retryable: false,
+            }) => {
+                assert_eq!("ReadOnly", permission_set.as_ref());
+                assert!(raw_message.contains("AWSReservedSSO_ReadOnly_1111111111111111"));
+                assert!(raw_message.contains("AWSReservedSSO_ReadOnly_2222222222222222"));
             }
+            _ => panic!("several roles should be an error"),
         }
+    }
 
-        Ok(users)
+    #[tokio::test]
+    async fn resolve_instance_profiles_test() {
+        // setup:
+        let role = |arn: &str| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+                None,
+            )
+        };
+        let iam = FakeIamUserSource::new(vec![]).with_instance_profiles(&[
+            ("karpenter", "arn:aws:iam::123456789012:role/karpenter-node"),
+            ("nodes", "arn:aws:iam::123456789012:role/nodes"),
+        ]);
+
+        // execute:
+        let resolved = resolve_instance_profiles(
+            &iam,
+            &[
+                role("arn:aws:iam::123456789012:instance-profile/karpenter"),
+                role("arn:aws:iam::123456789012:role/nodes"),
+                // resolved to a role already mapped
+                role("arn:aws:iam::123456789012:instance-profile/nodes"),
+            ],
+        )
+        .await;
+
+        // verify:
+        assert_eq!(
+            vec![
+                "arn:aws:iam::123456789012:role/karpenter-node",
+                "arn:aws:iam::123456789012:role/nodes",
+            ],
+            resolved
+                .unwrap()
+                .iter()
+                .map(|r| r.iam_role_arn.to_string())
+                .collect::<Vec<_>>()
+        );
+        // groups and username are kept
+        let resolved = resolve_instance_profiles(
+            &iam,
+            &[role("arn:aws:iam::123456789012:instance-profile/karpenter")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            role("arn:aws:iam::123456789012:role/karpenter-node").groups,
+            resolved[0].groups
+        );
+        assert_eq!(
+            Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+            resolved[0].user_name
+        );
+        // unknown instance profile
+        assert!(matches!(
+            resolve_instance_profiles(
+                &iam,
+                &[role("arn:aws:iam::123456789012:instance-profile/unknown")],
+            )
+            .await,
+            Err(IamError::CannotGetInstanceProfileRole { instance_profile_name, retryable: false, .. })
+                if instance_profile_name.as_ref() == "unknown"
+        ));
     }
 }
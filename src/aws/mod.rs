@@ -1,17 +1,105 @@
+use crate::aws::eks::EksError;
 use crate::aws::iam::IamError;
 use aws_config::meta::region::RegionProviderChain;
 use aws_config::{BehaviorVersion, SdkConfig};
+use aws_sdk_iam::config::http::HttpResponse;
 use aws_sdk_iam::config::Region;
+use aws_sdk_iam::error::{ProvideErrorMetadata, SdkError};
 use aws_sdk_sts::Client;
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::{error, info};
 
+pub mod eks;
+pub mod fake;
 pub mod iam;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum AwsError {
     #[error("AWS error: error with IAM: {underlying_error}")]
     IamError { underlying_error: IamError },
+    #[error("AWS error: error with EKS: {underlying_error}")]
+    EksError { underlying_error: EksError },
+    #[error("AWS error: cannot get caller identity: {raw_message}")]
+    CannotGetCallerIdentity {
+        raw_message: Arc<str>,
+        retryable: bool,
+    },
+}
+
+impl AwsError {
+    /// Whether the failing call might succeed once retried, see [`is_retryable_error`].
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AwsError::IamError { underlying_error } => underlying_error.is_retryable(),
+            AwsError::EksError { underlying_error } => underlying_error.is_retryable(),
+            AwsError::CannotGetCallerIdentity { retryable, .. } => *retryable,
+        }
+    }
+}
+
+/// AWS error codes retrying won't fix, e.q: missing permissions, missing entities or invalid requests.
+const FATAL_ERROR_CODES: &[&str] = &[
+    "AccessDenied",
+    "AccessDeniedException",
+    "UnauthorizedOperation",
+    "UnrecognizedClientException",
+    "InvalidClientTokenId",
+    "SignatureDoesNotMatch",
+    "NoSuchEntity",
+    "ResourceNotFoundException",
+    "InvalidInput",
+    "InvalidParameterException",
+    "InvalidRequestException",
+    "ValidationError",
+    "ValidationException",
+];
+
+/// AWS error codes of throttled or conflicting calls, answered with a 4xx status but worth retrying.
+const RETRYABLE_ERROR_CODES: &[&str] = &[
+    "Throttling",
+    "ThrottlingException",
+    "RequestLimitExceeded",
+    "TooManyRequestsException",
+    "RequestThrottled",
+    "RequestThrottledException",
+    "ConcurrentModification",
+    "ResourceInUseException",
+];
+
+/// Whether an AWS call failing with error `code` and HTTP `status` might succeed once retried. Unknown codes
+/// fall back on the status: throttling (429), conflicts (409) and server errors (5xx) are retryable, other
+/// client errors are not. No status at all means the call didn't get any response, e.q: on a timeout.
+pub fn is_retryable_error(code: Option<&str>, status: Option<u16>) -> bool {
+    if let Some(code) = code {
+        if FATAL_ERROR_CODES.contains(&code) {
+            return false;
+        }
+        if RETRYABLE_ERROR_CODES.contains(&code) {
+            return true;
+        }
+    }
+
+    match status {
+        Some(409) | Some(429) => true,
+        Some(status) => !(400..500).contains(&status),
+        None => true,
+    }
+}
+
+/// Whether an AWS SDK call failing with `e` might succeed once retried, see [`is_retryable_error`].
+pub fn is_retryable_sdk_error<E: ProvideErrorMetadata>(e: &SdkError<E, HttpResponse>) -> bool {
+    match e {
+        // request cannot even be built, e.q: invalid input or missing credentials
+        SdkError::ConstructionFailure(_) => false,
+        SdkError::ServiceError(e) => {
+            is_retryable_error(e.err().code(), Some(e.raw().status().as_u16()))
+        }
+        SdkError::ResponseError(e) => is_retryable_error(None, Some(e.raw().status().as_u16())),
+        // timeouts, connection errors and whatever the SDK may add
+        _ => true,
+    }
 }
 
 impl From<IamError> for AwsError {
@@ -22,6 +110,14 @@ impl From<IamError> for AwsError {
     }
 }
 
+impl From<EksError> for AwsError {
+    fn from(e: EksError) -> Self {
+        AwsError::EksError {
+            underlying_error: e,
+        }
+    }
+}
+
 pub struct AwsSdkConfig {
     config: SdkConfig,
     _verbose: bool,
@@ -61,6 +157,35 @@ impl AwsSdkConfig {
     }
 }
 
+/// Identity AWS credentials resolve into.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallerIdentity {
+    pub account: String,
+    pub arn: String,
+}
+
+/// Resolves AWS credentials into the identity calls are made as, e.q: to check credentials before syncing.
+pub trait CallerIdentitySource {
+    async fn get_caller_identity(&self) -> Result<CallerIdentity, AwsError>;
+}
+
+impl CallerIdentitySource for AwsSdkConfig {
+    async fn get_caller_identity(&self) -> Result<CallerIdentity, AwsError> {
+        Client::new(&self.config)
+            .get_caller_identity()
+            .send()
+            .await
+            .map(|identity| CallerIdentity {
+                account: identity.account().unwrap_or_default().to_string(),
+                arn: identity.arn().unwrap_or_default().to_string(),
+            })
+            .map_err(|e| AwsError::CannotGetCallerIdentity {
+                raw_message: Arc::from(e.to_string()),
+                retryable: is_retryable_sdk_error(&e),
+            })
+    }
+}
+
 impl From<SdkConfig> for AwsSdkConfig {
     fn from(value: SdkConfig) -> Self {
         AwsSdkConfig {
@@ -69,3 +194,159 @@ impl From<SdkConfig> for AwsSdkConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::{is_retryable_error, is_retryable_sdk_error};
+    use aws_sdk_iam::config::http::HttpResponse;
+    use aws_sdk_iam::error::{ConnectorError, ErrorMetadata, SdkError};
+    use aws_sdk_iam::operation::get_group::GetGroupError;
+
+    #[test]
+    fn is_retryable_error_test() {
+        struct TestCase<'a> {
+            code: Option<&'a str>,
+            status: Option<u16>,
+            expected: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                code: Some("AccessDenied"),
+                status: Some(403),
+                expected: false,
+                _description: "case 1 - access denied",
+            },
+            TestCase {
+                code: Some("NoSuchEntity"),
+                status: Some(404),
+                expected: false,
+                _description: "case 2 - missing IAM entity",
+            },
+            TestCase {
+                code: Some("ValidationError"),
+                status: Some(400),
+                expected: false,
+                _description: "case 3 - invalid request",
+            },
+            TestCase {
+                code: Some("Throttling"),
+                status: Some(400),
+                expected: true,
+                _description: "case 4 - throttled with a client error status",
+            },
+            TestCase {
+                code: Some("ResourceInUseException"),
+                status: Some(409),
+                expected: true,
+                _description: "case 5 - conflict",
+            },
+            TestCase {
+                code: Some("ServiceFailure"),
+                status: Some(500),
+                expected: true,
+                _description: "case 6 - server error",
+            },
+            TestCase {
+                code: Some("AccessDeniedException"),
+                status: Some(500),
+                expected: false,
+                _description: "case 7 - code prevails over status",
+            },
+            TestCase {
+                code: None,
+                status: Some(503),
+                expected: true,
+                _description: "case 8 - unavailable, no code",
+            },
+            TestCase {
+                code: None,
+                status: Some(429),
+                expected: true,
+                _description: "case 9 - too many requests, no code",
+            },
+            TestCase {
+                code: Some("SomethingUnexpected"),
+                status: Some(400),
+                expected: false,
+                _description: "case 10 - unknown code with a client error status",
+            },
+            TestCase {
+                code: None,
+                status: None,
+                expected: true,
+                _description: "case 11 - no response at all",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = is_retryable_error(tc.code, tc.status);
+
+            // verify:
+            assert_eq!(tc.expected, res, "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn is_retryable_sdk_error_test() {
+        struct TestCase<'a> {
+            error: SdkError<GetGroupError, HttpResponse>,
+            expected: bool,
+            _description: &'a str,
+        }
+
+        let service_error = |code: &str, status: u16| {
+            SdkError::service_error(
+                GetGroupError::generic(ErrorMetadata::builder().code(code).build()),
+                HttpResponse::new(status.try_into().unwrap(), "".into()),
+            )
+        };
+        let test_cases = vec![
+            TestCase {
+                error: service_error("NoSuchEntity", 404),
+                expected: false,
+                _description: "case 1 - missing IAM group",
+            },
+            TestCase {
+                error: service_error("AccessDenied", 403),
+                expected: false,
+                _description: "case 2 - access denied",
+            },
+            TestCase {
+                error: service_error("Throttling", 400),
+                expected: true,
+                _description: "case 3 - throttled",
+            },
+            TestCase {
+                error: service_error("ServiceFailure", 500),
+                expected: true,
+                _description: "case 4 - server error",
+            },
+            TestCase {
+                error: SdkError::timeout_error("timed out"),
+                expected: true,
+                _description: "case 5 - timeout",
+            },
+            TestCase {
+                error: SdkError::dispatch_failure(ConnectorError::io("connection reset".into())),
+                expected: true,
+                _description: "case 6 - connection error",
+            },
+            TestCase {
+                error: SdkError::construction_failure("missing group name"),
+                expected: false,
+                _description: "case 7 - request cannot be built",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = is_retryable_sdk_error(&tc.error);
+
+            // verify:
+            assert_eq!(tc.expected, res, "{}", tc._description);
+        }
+    }
+}
@@ -0,0 +1,3364 @@
+use crate::audit::AuditLog;
+use crate::aws::eks::{discover_node_roles, AccessEntriesBackend, ClusterApi, EksService};
+use crate::aws::iam::{
+    resolve_instance_profiles, IamGroup, IamRoleSource, IamService, IamUserSource,
+};
+use crate::aws::AwsSdkConfig;
+use crate::config::loader::ArgsLoader;
+use crate::config::{
+    parse_config_map_target, Config, ConfigOptions, ConfigurationError, Credentials,
+    MissedTickBehavior, RedactedConfig, MIN_REFRESH_INTERVAL, REDACTED,
+};
+use crate::config_file::watch_config_file;
+use crate::errors::Error;
+use crate::export::{ExportTarget, MappingExport};
+use crate::health::{HealthServer, SharedSyncState};
+use crate::heartbeat::check_heartbeat;
+use crate::kubernetes::aws_auth::{AwsAuth, AwsAuthDiff, RemovalGuard};
+use crate::kubernetes::events::{EventRecorder, SyncEvent};
+use crate::kubernetes::group_mapping::IamGroupMapping;
+use crate::kubernetes::identity_mapping::IdentityMappingsBackend;
+use crate::kubernetes::leadership::LeaseOptions;
+use crate::kubernetes::status::SyncStatus;
+use crate::kubernetes::{
+    AuthBackend, AwsAccountId, ClusterAccess, ClusterEndpoint, ConfigMapOptions, ConfigMapUpdate,
+    IamArn, KubernetesError, KubernetesRole, KubernetesService, KubernetesUser, SyncedBy,
+    DEFAULT_ERROR_SNIPPET_MAX_LENGTH,
+};
+use crate::notification::slack::SlackSink;
+use crate::notification::webhook::WebhookSink;
+use crate::notification::{Notification, NotificationError, NotificationSink, Notifier};
+use crate::preflight::{run_checks, CheckResult, PreflightCheck, PreflightTargets};
+use crate::sync::{
+    permission_set_sso_role, render_config_map, ClusterTarget, ConfigMapTarget, ConfigMapTargets,
+    SyncHooks, SyncLoop, SyncReporter, SyncSettings, SyncTargets, TargetsOutcomes,
+};
+use crate::telemetry::OtlpTracing;
+use crate::trigger::{sync_trigger, SyncTrigger};
+use crate::{config, errors};
+use clap::builder::NonEmptyStringValueParser;
+use clap::{ArgAction, ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::Shell;
+use futures::FutureExt;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::ResourceExt;
+use regex::Regex;
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tokio::task;
+use tracing::Subscriber;
+use tracing::{debug, error, info, span, warn, Level};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{prelude::*, EnvFilter, Layer};
+
+/// Delay between two checks of the config file modification time.
+const CONFIG_FILE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = errors::EXIT_CODES_HELP)]
+#[command(group(
+    ArgGroup::new("aws_credentials")
+        .args(&["aws_role_arn", "aws_access_key_id"])
+        .required(true)
+))]
+#[command(subcommand_negates_reqs = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// YAML config file whose keys are the arguments names, e.q: /etc/iam-eks-user-mapper/config.yaml
+    ///
+    /// Env vars and flags take precedence over the file values, unknown keys are rejected
+    #[clap(long, env = "CONFIG_FILE", value_hint = ValueHint::FilePath, required = false, global = true)]
+    pub config: Option<PathBuf>,
+    /// File of `KEY=VALUE` lines read as env vars, e.q: /etc/iam-eks-user-mapper/mapper.env
+    ///
+    /// Process env vars take precedence over its entries, which take precedence over the config file. Only arguments env vars are accepted
+    #[clap(long, env = "ENV_FILE", value_hint = ValueHint::FilePath, required = false, global = true)]
+    pub env_file: Option<PathBuf>,
+    /// Service account name to be used, e.q: my-service-account
+    #[arg(short = 's', long, env, global = true)]
+    pub service_account_name: Option<String>,
+    /// AWS role ARN to be used, e.q: arn:aws:iam::12345678910:role/my-role
+    #[arg(short = 'R', long, env, conflicts_with_all = &["aws_access_key_id", "aws_secret_access_key"], global = true)]
+    pub aws_role_arn: Option<String>,
+    /// AWS access key ID to be used
+    #[arg(
+        short = 'a',
+        long,
+        env,
+        requires = "aws_secret_access_key",
+        global = true
+    )]
+    pub aws_access_key_id: Option<String>,
+    /// AWS secret access key to be used
+    #[arg(short = 'k', long, env, requires = "aws_access_key_id", global = true)]
+    pub aws_secret_access_key: Option<String>,
+    /// AWS default region to be used, e.q: eu-west-3
+    #[arg(short = 'r', long, env, global = true)]
+    pub aws_default_region: Option<String>,
+    /// Refresh interval between two user synchronization, at least 5s, e.q: 30s, 5m or 1h
+    #[clap(long, env, default_value = "60s", value_parser = parse_refresh_interval, required = false, global = true)]
+    pub refresh_interval: Duration,
+    /// Deprecated, use `refresh_interval` instead. Refresh interval in seconds between two user synchronization, at least 5, e.q: 30
+    #[arg(
+        short = 'i',
+        long,
+        env,
+        value_parser = parse_refresh_interval_seconds,
+        conflicts_with = "refresh_interval",
+        global = true
+    )]
+    pub refresh_interval_seconds: Option<u64>,
+    /// Sync once and exit, with a non-zero code if the sync failed, e.q: from a CronJob or a CI pipeline
+    #[clap(
+        long,
+        env,
+        default_value_t = false,
+        required = false,
+        conflicts_with_all = ["refresh_interval", "refresh_interval_seconds", "watch_config_map", "enable_leader_election"],
+        global = true,
+    )]
+    pub once: bool,
+    /// Print the effective configuration, secrets redacted, and exit without syncing
+    #[clap(long, env, default_value_t = false, global = true)]
+    pub show_config: bool,
+    /// Format of `--show-config` output, JSON being machine-readable, e.q: to be attached to an issue
+    #[clap(long, env = "SHOW_CONFIG_OUTPUT", value_enum, default_value_t = ShowConfigOutput::Yaml, required = false, global = true)]
+    pub output: ShowConfigOutput,
+    /// Maximum random offset in seconds added to or removed from each refresh interval, not for several mappers to sync at the same time, e.q: 10
+    #[clap(long, env, default_value_t = 0, required = false, global = true)]
+    pub refresh_interval_jitter_seconds: u64,
+    /// Seed of the refresh interval jitter, making it reproducible, e.q: 42
+    #[clap(long, env, required = false, global = true)]
+    pub refresh_interval_jitter_seed: Option<u64>,
+    /// What happens to refresh ticks missed while a sync overran the refresh interval: `skip` them, `delay` the schedule or `burst` syncs back to back to catch up
+    #[clap(long, env, value_enum, default_value_t = MissedTickBehavior::Skip, required = false, global = true)]
+    pub missed_tick_behavior: MissedTickBehavior,
+    /// Maximum delay in seconds between two attempts after consecutive sync failures, the refresh interval being doubled on each failure, e.q: 900
+    #[clap(long, env, default_value_t = 900, required = false, global = true)]
+    pub max_backoff_seconds: u64,
+    /// Exit with a non-zero code after this number of consecutive sync failures, for Kubernetes to restart the pod, e.q: 10
+    ///
+    /// Syncs failing with retryable errors keep on being attempted forever if not set, other errors exiting right away
+    #[clap(long, env, value_parser = clap::value_parser!(u32).range(1..), required = false, global = true)]
+    pub max_consecutive_failures: Option<u32>,
+    /// Keep on retrying syncs failing with errors retrying won't fix, e.q: a missing IAM permission or a wrong config map name, instead of exiting
+    ///
+    /// The first sync being attempted at startup, such errors otherwise fail the rollout instead of leaving a running pod not syncing anything
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub no_fail_fast: bool,
+    /// Maximum duration in seconds of a sync before writing the config map, e.q: 300
+    ///
+    /// Timed out syncs count as failures, config map writes being bounded by `kube_request_timeout_seconds` instead not to be left half done
+    #[clap(long, env, default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..), required = false, global = true)]
+    pub sync_timeout_seconds: u64,
+    /// Address health probes are served on, `/healthz` answering 200 while syncing and `/readyz` once a sync succeeded, e.q: 0.0.0.0:8080
+    #[clap(
+        long,
+        env,
+        default_value = "0.0.0.0:8080",
+        required = false,
+        global = true
+    )]
+    pub health_listen_addr: SocketAddr,
+    /// Age of the last successful sync after which `/readyz` fails, 3 times the refresh interval by default, e.q: 10m
+    #[clap(long, env, value_parser = humantime::parse_duration, required = false, global = true)]
+    pub readiness_max_staleness: Option<Duration>,
+    /// Serve `POST /sync` on `health_listen_addr`, syncing right away and answering the sync summary as JSON, e.q: after changing IAM groups membership
+    ///
+    /// Syncs can be requested with `SIGUSR1` as well, whether set or not
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub enable_sync_endpoint: bool,
+    /// File the time of the last successful sync is written into after each sync, checked by the `healthcheck` subcommand, e.q: /tmp/last-sync
+    #[clap(long, env, value_hint = ValueHint::FilePath, required = false, global = true)]
+    pub heartbeat_file: Option<PathBuf>,
+    /// OTLP gRPC endpoint sync traces are exported to, other `OTEL_*` env vars being honoured, e.q: http://otel-collector:4317
+    ///
+    /// Traces are not exported if not set
+    #[clap(
+        long,
+        env = "OTEL_EXPORTER_OTLP_ENDPOINT",
+        required = false,
+        global = true
+    )]
+    pub otlp_endpoint: Option<String>,
+    /// Activate group user sync (requires `iam_k8s_groups` or `crd_mode` to be set)
+    #[clap(long, env, required = false, default_value_t = false, global = true)]
+    pub enable_group_user_sync: bool,
+    /// IAM groups to be mapped into Kubernetes, e.q: Admins->system:masters
+    ///
+    /// Several mappings can be provided using comma separator, e.q: Admins->system:masters,Devops->system:devops
+    ///
+    /// Syntax is <IAM_GROUP>-><KUBERNETES_GROUP>,<IAM_GROUP_2>-><KUBERNETES_GROUP_2>, (requires `enable_group_user_sync` to be set)
+    ///
+    /// Names containing `->` or `|` are double quoted, e.q: Admins->"legacy->admins"
+    ///
+    /// `|username=<TEMPLATE>` overrides `username_template` for the IAM group users, e.q: TenantA-Devs->tenant-a-group|username=tenant-a:{name}
+    #[clap(short = 'g', long, env, value_parser, num_args = 1.., value_delimiter = ',', required = false, requires = "enable_group_user_sync", global = true)]
+    pub iam_k8s_groups: Vec<String>,
+    /// Read IAM groups mappings from `IamGroupMapping` resources instead of `iam_k8s_groups`, syncing right away when they change
+    ///
+    /// Group user sync is enabled whatever `enable_group_user_sync`, the CRD has to be installed beforehand
+    #[clap(
+        long,
+        env,
+        default_value_t = false,
+        required = false,
+        conflicts_with = "iam_k8s_groups",
+        global = true
+    )]
+    pub crd_mode: bool,
+    /// Reject `system:` Kubernetes groups unknown to the authenticator, e.q: `system:master`, instead of warning about them
+    #[clap(long, env, default_value_t = false, global = true)]
+    pub strict_group_validation: bool,
+    /// Namespace watched for `IamGroupMapping` resources in CRD mode
+    #[clap(
+        long,
+        env,
+        default_value = "kube-system",
+        required = false,
+        global = true
+    )]
+    pub crd_namespace: String,
+    /// Activate SSO on the cluster (requires `iam_sso_role_arn` or `iam_sso_permission_set` to be set)
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub enable_sso: bool,
+    /// IAM SSO role arn
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub iam_sso_role_arn: Option<String>,
+    /// Role name of the SSO role entry, `cluster-admin-sso` if not set
+    #[clap(long, env, value_parser = NonEmptyStringValueParser::new(), required = false, global = true)]
+    pub iam_sso_role_name: Option<String>,
+    /// IAM Identity Center permission set of the SSO role, instead of its ARN, e.q: AdministratorAccess
+    ///
+    /// Its `AWSReservedSSO_<NAME>_<SUFFIX>` role is looked up in the account of the credentials before each sync, following suffix changes when the permission set is provisioned again
+    #[clap(long, env, value_parser = NonEmptyStringValueParser::new(), conflicts_with = "iam_sso_role_arn", required = false, global = true)]
+    pub iam_sso_permission_set: Option<String>,
+    /// Enable Karpenter by defining its role ARN, e.q: arn:aws:iam::12345678910:role/karpenter-node
+    ///
+    /// Several Karpenter roles, e.g. one per node pool, can be provided using comma separator or repeating the flag
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub karpenter_role_arn: Vec<String>,
+    /// Kubernetes username of Karpenter roles entries
+    #[clap(long, env, default_value = config::DEFAULT_KARPENTER_ROLE_USERNAME, value_parser = NonEmptyStringValueParser::new(), required = false, global = true)]
+    pub karpenter_role_username: String,
+    /// Kubernetes groups of Karpenter roles entries, e.q: system:bootstrappers,system:nodes,eks:kube-proxy-windows for Windows nodes
+    #[clap(long, env, value_delimiter = ',', default_value = config::DEFAULT_KARPENTER_ROLE_GROUPS, required = false, global = true)]
+    pub karpenter_role_groups: Vec<String>,
+    /// Accept instance profile ARNs as Karpenter role ARNs, e.q: arn:aws:iam::12345678910:instance-profile/karpenter-node
+    ///
+    /// Each instance profile is resolved to the ARN of the role it holds through IAM `GetInstanceProfile`, at startup and on config reloads, syncs being skipped while it cannot be
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub resolve_instance_profile: bool,
+    /// Node roles of self-managed nodegroups, mapped the way EKS does, e.q: arn:aws:iam::12345678910:role/nodes
+    ///
+    /// Several roles can be provided using comma separator or repeating the flag. A role also set as Karpenter role gets a single entry, with groups of both
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub node_role_arns: Vec<String>,
+    /// AWS account IDs to be added to aws-auth `mapAccounts`, e.q: 111122223333,444455556666
+    ///
+    /// Accounts already present in `mapAccounts` and not added by the tool are kept
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub map_aws_accounts: Vec<String>,
+    /// Roles to be added to aws-auth `mapRoles` and kept in sync, e.q: arn:aws:iam::123456789012:role/ci|username=ci-deployer|groups=ci,deployers
+    ///
+    /// Several mappings can be provided using semicolon separator, syntax is <ROLE_ARN>|username=<USERNAME>|groups=<GROUP_1>,<GROUP_2>;<ROLE_ARN_2>|...
+    #[clap(long, env, value_delimiter = ';', required = false, global = true)]
+    pub map_roles: Vec<String>,
+    /// Users to be added to aws-auth `mapUsers` and kept in sync without IAM group, e.q: arn:aws:iam::123456789012:user/alice|username=alice|groups=view
+    ///
+    /// Several mappings can be provided using semicolon separator, syntax is <USER_ARN>|username=<USERNAME>|groups=<GROUP_1>,<GROUP_2>;<USER_ARN_2>|...
+    #[clap(long, env, value_delimiter = ';', required = false, global = true)]
+    pub map_users: Vec<String>,
+    /// ARNs of IAM users granted `system:masters`, whatever their IAM groups, e.q: arn:aws:iam::123456789012:user/alice
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub admin_iam_user_arns: Vec<String>,
+    /// Kubernetes groups nothing can be mapped into, whether through IAM groups mappings, SSO, Karpenter or static mappings, e.q: system:masters,cluster-admins
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub denied_k8s_groups: Vec<String>,
+    /// Allow mapping into `system:masters`, set to false to deny it as if it was part of `denied_k8s_groups`
+    #[clap(long, env, default_value_t = true, action = ArgAction::Set, global = true)]
+    pub allow_system_masters: bool,
+    /// Kubernetes username of IAM groups and admins users, e.q: `iam:{name}`, placeholders being `{name}`, `{account}` and `{path}`
+    ///
+    /// Templates of `IamGroupMapping` resources take precedence in CRD mode
+    #[clap(long, env, default_value = config::DEFAULT_USERNAME_TEMPLATE, required = false, global = true)]
+    pub username_template: String,
+    /// Lowercase usernames of synced users, e.q: `Alice` being mapped as `alice`, ARNs being kept as is
+    #[clap(long, env, default_value_t = false, global = true)]
+    pub lowercase_usernames: bool,
+    /// Kubeconfig file used to reach the cluster instead of the in-cluster config, e.q: ~/.kube/my-cluster
+    ///
+    /// `KUBECONFIG` is not read for this flag as it can hold several paths, it is already used by default when running outside of a cluster
+    #[clap(long, value_hint = ValueHint::FilePath, required = false, global = true)]
+    pub kubeconfig: Option<PathBuf>,
+    /// Kubeconfig context used to reach the cluster instead of the current one, e.q: my-cluster
+    #[clap(long, env, required = false, global = true)]
+    pub kube_context: Option<String>,
+    /// Kubeconfig contexts of several clusters to be synced with the same users and roles instead of a single one, e.q: cluster-a,cluster-b
+    ///
+    /// Clusters are synced independently, one being unreachable not preventing others from being synced
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        required = false,
+        conflicts_with_all = ["kube_context", "watch_config_map", "enable_leader_election", "emit_kubernetes_events", "export_json", "crd_mode", "discover_nodegroup_roles"],
+        global = true,
+    )]
+    pub kube_contexts: Vec<String>,
+    /// Timeout in seconds of each Kubernetes API request, e.q: 10
+    ///
+    /// Requests failing with transient errors, timeouts included, are retried a few times before giving up on the sync
+    #[clap(long, env, value_parser = clap::value_parser!(u64).range(1..), required = false, global = true)]
+    pub kube_request_timeout_seconds: Option<u64>,
+    /// ARNs of mapUsers / mapRoles entries which must never be removed nor modified, e.q: arn:aws:iam::12345678910:role/my-node-role
+    ///
+    /// Existing entries are kept as is even if synced by the tool, new entries can still be added for those ARNs
+    #[clap(long, env, value_delimiter = ',', required = false, global = true)]
+    pub protected_arns: Vec<String>,
+    /// Maximum fraction of synced entries which can be removed in a single sync, from 0 to 1, e.q: 0.5
+    ///
+    /// Protects against IAM outages or bad mapping changes removing all users at once, refused syncs are retried on next refresh.
+    /// The allowed count is rounded up, e.q: removing 1 of 1 synced entry is allowed at 0.5
+    #[clap(long, env, default_value_t = 0.5, value_parser = parse_fraction, required = false, global = true)]
+    pub max_removal_fraction: f64,
+    /// Maximum number of synced entries which can be removed in a single sync, e.q: 10
+    #[clap(long, env, required = false, global = true)]
+    pub max_removals: Option<usize>,
+    /// Bypass `max_removal_fraction` and `max_removals` limits, e.q: to apply an intended large cleanup
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub force_removals: bool,
+    /// Marker written in `syncedBy` field of entries managed by this instance, e.q: iam-eks-user-mapper-ci
+    ///
+    /// Several instances can target the same cluster using different markers, each one leaving others entries untouched
+    #[clap(long, env, default_value = "iam-eks-user-mapper", value_parser = NonEmptyStringValueParser::new(), required = false, global = true)]
+    pub synced_by_value: String,
+    /// Duration after which entries synced by the tool are removed, even if still granted access, e.q: 30d
+    ///
+    /// Forces re-attestation: expired entries are added back with a fresh `syncedAt` on next sync only if still granted access, their access being interrupted until then. Syncs are not skipped once an entry expired, even if IAM state is unchanged
+    #[clap(long, env, value_parser = humantime::parse_duration, required = false, global = true)]
+    pub synced_entry_ttl: Option<Duration>,
+    /// Adopt unmanaged entries having the ARN of a synced entry: they are replaced by it instead of being kept alongside, e.q: when onboarding a cluster with hand-written entries
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub adopt_existing_entries: bool,
+    /// Adopt unmanaged entries whose ARN matches this regex even if not synced, e.q: ^arn:aws:iam::123456789012:user/
+    ///
+    /// Matching entries are marked as synced by the tool, next syncs removing them unless synced. Protected ARNs are never adopted
+    #[clap(long, env, value_parser = Regex::new, required = false, global = true)]
+    pub adopt_by_arn_pattern: Option<Regex>,
+    /// Only remove synced entries no longer granted access, never adding nor modifying any, e.q: when additions go through a manual approval
+    ///
+    /// Entries which would have been added are logged as suppressed additions. Not supported with access entries backend
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub removals_only: bool,
+    /// Where users and roles are synced: the aws-auth config map, EKS access entries or `IAMIdentityMapping` resources
+    ///
+    /// Access entries require the cluster authentication mode to be `API` or `API_AND_CONFIG_MAP`, `IAMIdentityMapping` resources require
+    /// aws-iam-authenticator CRD backend, `mapAccounts` having no equivalent in both cases
+    #[clap(long, env, value_enum, default_value_t = AuthBackendKind::Configmap, required = false, global = true)]
+    pub auth_backend: AuthBackendKind,
+    /// Name of the EKS cluster whose access entries are synced or nodegroups roles discovered, e.q: my-cluster
+    ///
+    /// When set, the tool refuses to start if the Kubernetes client doesn't reach this cluster, requires `eks:DescribeCluster`
+    #[clap(
+        long,
+        env,
+        required_if_eq("auth_backend", "access-entries"),
+        global = true
+    )]
+    pub eks_cluster_name: Option<String>,
+    /// Skip checking the Kubernetes client reaches `eks_cluster_name`, for proxied API servers
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub skip_cluster_identity_check: bool,
+    /// Discover roles of `eks_cluster_name` managed nodegroups and make sure they are mapped so their nodes can join the cluster
+    ///
+    /// Discovered roles are synced like the Karpenter one, syncs being skipped while nodegroups cannot be read not to remove them
+    #[clap(
+        long,
+        env,
+        default_value_t = false,
+        required = false,
+        requires = "eks_cluster_name",
+        global = true
+    )]
+    pub discover_nodegroup_roles: bool,
+    /// Namespace of the config map to be synced
+    #[clap(
+        long,
+        env,
+        default_value = config::DEFAULT_CONFIG_MAP_NAMESPACE,
+        required = false,
+        global = true
+    )]
+    pub config_map_namespace: String,
+    /// Name of the config map to be synced, e.q: aws-auth-test to try the mapper on a scratch config map
+    #[clap(long, env, default_value = config::DEFAULT_CONFIG_MAP_NAME, required = false, global = true)]
+    pub config_map_name: String,
+    /// Config maps to be synced with the same users and roles, e.q: kube-system/aws-auth,preview/aws-auth-shadow
+    ///
+    /// Each config map keeps its own entries not synced by the tool and is synced independently, its failure not preventing others from being synced but failing the sync, retried with backoff.
+    /// Notifications and events are about each config map, the status only being successful once all of them synced. The first one is the config map watched. Only supported with the `configmap` auth backend
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        required = false,
+        conflicts_with_all = ["config_map_namespace", "config_map_name", "kube_contexts"],
+        global = true,
+    )]
+    pub config_map_targets: Vec<String>,
+    /// Name of the config map, in the same namespace, where the previous content is backed up before each modification
+    #[clap(
+        long,
+        env,
+        default_value = "aws-auth-backup-iam-eks-user-mapper",
+        required = false,
+        global = true
+    )]
+    pub backup_config_map_name: String,
+    /// Number of backups to keep, 0 disables backups
+    #[clap(long, env, default_value_t = 5, required = false, global = true)]
+    pub backup_history_count: usize,
+    /// Don't annotate the config map with last sync time, version and content hash, e.q: for clusters with strict admission policies
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub no_annotations: bool,
+    /// Send config map writes as server side dry runs (`dryRun=All`): requests go through admission webhooks but nothing is persisted
+    ///
+    /// Requires a cluster supporting dry runs, unlike a local dry run no request is skipped
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub server_dry_run: bool,
+    /// Fetch IAM users and read the config map, but stop before any write, logging the changes which would have been made instead
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub dry_run: bool,
+    /// Maximum number of characters of the offending `mapUsers` / `mapRoles` entry shown when the config map cannot be parsed, 0 showing none
+    #[clap(long, env, default_value_t = DEFAULT_ERROR_SNIPPET_MAX_LENGTH, required = false, global = true)]
+    pub error_snippet_max_length: usize,
+    /// Watch the config map and sync right away when it's modified by someone else, without waiting for the next refresh
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub watch_config_map: bool,
+    /// Minimum delay in seconds between two syncs triggered by config map modifications, e.q: 10
+    #[clap(long, env, default_value_t = 10, required = false, global = true)]
+    pub watch_debounce_seconds: u64,
+    /// Elect a leader through a Kubernetes lease so only one of several replicas syncs the config map
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub enable_leader_election: bool,
+    /// Name of the lease used for leader election
+    #[clap(
+        long,
+        env,
+        default_value = "iam-eks-user-mapper",
+        required = false,
+        global = true
+    )]
+    pub lease_name: String,
+    /// Namespace of the lease used for leader election
+    #[clap(
+        long,
+        env,
+        default_value = "kube-system",
+        required = false,
+        global = true
+    )]
+    pub lease_namespace: String,
+    /// Duration in seconds after which a lease not renewed by the leader can be taken over by another replica, e.q: 15
+    #[clap(long, env, default_value_t = 15, required = false, global = true)]
+    pub lease_duration_seconds: u64,
+    /// Publish a Kubernetes event attached to the config map after each sync, succeeded or failed
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub emit_kubernetes_events: bool,
+    /// Create the aws-auth config map if it doesn't exist yet instead of failing
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub create_config_map_if_missing: bool,
+    /// Export the computed IAM to Kubernetes mapping as JSON after each sync, e.q: /tmp/mapping.json
+    ///
+    /// Use `-` to write the export to stdout
+    #[clap(long, env, value_hint = ValueHint::FilePath, required = false, global = true)]
+    pub export_json: Option<String>,
+    /// Append a JSON line recording added, removed and modified entries to this file each time aws-auth is changed, e.q: /var/log/iam-eks-user-mapper/audit.log
+    ///
+    /// Records are appended in a single write and flushed to disk, the file is never truncated
+    #[clap(long, env, value_hint = ValueHint::FilePath, required = false, global = true)]
+    pub audit_log_path: Option<PathBuf>,
+    /// Fail the sync when a record cannot be written to the audit log instead of only logging an error
+    #[clap(
+        long,
+        env,
+        default_value_t = false,
+        required = false,
+        requires = "audit_log_path",
+        global = true
+    )]
+    pub audit_log_required: bool,
+    /// POST a JSON payload listing added, removed and modified entries to this URL after each sync changing them, e.q: https://hooks.example.com/iam
+    ///
+    /// Notifications are never sent in dry run modes, their failures are only logged
+    #[clap(long, env, required = false, global = true)]
+    pub notify_webhook_url: Option<String>,
+    /// Post a message listing added, removed and modified entries to this Slack incoming webhook after each sync changing them
+    ///
+    /// Notifications are never sent in dry run modes, their failures are only logged, at most once every 15 minutes
+    #[clap(long, env, required = false, global = true)]
+    pub slack_webhook_url: Option<String>,
+    /// Maximum duration of a single notification attempt, up to 3 attempts being made
+    #[clap(long, env, default_value_t = 5, required = false, global = true)]
+    pub notify_webhook_timeout_seconds: u64,
+    /// Notify sync failures as well, e.q: IAM or Kubernetes API being unreachable
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub notify_on_failure: bool,
+    /// Write the outcome of each sync attempt into a dedicated status config map
+    #[clap(long, env, default_value_t = false, required = false, global = true)]
+    pub status_config_map: bool,
+    /// Name of the status config map
+    #[clap(
+        long,
+        env,
+        default_value = "iam-eks-user-mapper-status",
+        required = false,
+        global = true
+    )]
+    pub status_config_map_name: String,
+    /// Namespace of the status config map
+    #[clap(
+        long,
+        env,
+        default_value = "kube-system",
+        required = false,
+        global = true
+    )]
+    pub status_config_map_namespace: String,
+    /// Activate verbose mode, the tool logging at debug level while AWS SDK stays at `log_level`
+    #[clap(short = 'v', long, env, default_value_t = false, global = true)]
+    pub verbose: bool,
+    /// Log warnings and errors only, at most, the startup configuration summary being still logged once
+    #[clap(
+        short = 'q',
+        long,
+        env,
+        default_value_t = false,
+        conflicts_with = "verbose",
+        global = true
+    )]
+    pub quiet: bool,
+    /// Minimum level of logs, `RUST_LOG` directives overriding it per module, e.q: RUST_LOG=kube=debug
+    #[clap(long, env, value_enum, default_value_t = LogLevel::Info, required = false, global = true)]
+    pub log_level: LogLevel,
+    /// Format of logs: `text`, fields being written as `field: value`, or `json`, one object per line with fields flattened and without colours
+    #[clap(long, env, value_enum, default_value_t = LogFormat::Text, required = false, global = true)]
+    pub log_format: LogFormat,
+    /// Don't colour text logs, colours being used only when logs are written to a terminal otherwise
+    #[clap(long, env, default_value_t = false, global = true)]
+    pub no_ansi: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Sync IAM users and roles into the cluster on each refresh, what's done when no command is given
+    Sync,
+    /// Check the configuration, AWS and the cluster before a rollout, printing a PASS or FAIL line per check, then exit without writing anything
+    ///
+    /// Exits with a non-zero code if any check failed, all of them being run anyway
+    Validate {
+        /// Checks not to be run, e.q: iam-roles,config-map-rbac
+        #[arg(long, value_enum, value_delimiter = ',')]
+        skip_check: Vec<PreflightCheck>,
+    },
+    /// Remove all entries synced by the tool, i.e. marked with `synced_by_value`, from the config map, print them, then exit
+    ///
+    /// Other entries are kept as written, `dry_run` printing what would be removed. Meant for uninstalling the tool,
+    /// the removal guard doesn't apply
+    Cleanup {
+        /// Confirm entries are to be removed, required unless `dry_run` is set
+        #[arg(long, default_value_t = false)]
+        yes: bool,
+    },
+    /// Restore a backup of the config map content taken before a modification
+    Restore {
+        /// Backup to be restored, e.q: backup-2024-01-01T00-00-00Z, the most recent one is restored if not set
+        #[arg(long)]
+        backup: Option<String>,
+        /// List available backups instead of restoring one
+        #[arg(long, default_value_t = false)]
+        list: bool,
+    },
+    /// Create EKS access entries equivalent to the config map entries synced by the tool
+    ///
+    /// Requires `eks_cluster_name` and `aws_default_region`, entries already migrated are skipped so it can be re-run safely
+    MigrateToAccessEntries {
+        /// Migrate entries not synced by the tool as well, their access entries are never modified by sync
+        #[arg(long, default_value_t = false)]
+        include_unmanaged: bool,
+        /// Remove migrated entries from the config map once their access entry exists
+        #[arg(long, default_value_t = false)]
+        delete_source: bool,
+        /// Print what would be migrated without creating nor removing anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Print the aws-auth config map the sync would write, computed from IAM without any cluster access
+    Render {
+        /// aws-auth config map manifest computed entries are merged into, e.q: output of `kubectl get configmap aws-auth -n kube-system -o yaml`
+        ///
+        /// Entries not synced by the tool are kept the way the sync keeps them, an empty config map being assumed if not set
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        existing_aws_auth: Option<PathBuf>,
+    },
+    /// Exit with a non-zero code if the last successful sync written into `heartbeat_file` is too old, e.q: as a Docker HEALTHCHECK or an exec liveness probe
+    Healthcheck {
+        /// Maximum age in seconds of the last successful sync, e.q: 180
+        #[arg(long, default_value_t = 180)]
+        max_age_seconds: u64,
+    },
+    /// Print shell completions to stdout, e.q: iam-eks-user-mapper completions bash > /etc/bash_completion.d/iam-eks-user-mapper
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page to stdout as roff, e.q: iam-eks-user-mapper manpage > iam-eks-user-mapper.1
+    Manpage,
+}
+
+impl Command {
+    /// Name of the command on the command line, e.q: in errors.
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Sync => "sync",
+            Command::Validate { .. } => "validate",
+            Command::Cleanup { .. } => "cleanup",
+            Command::Restore { .. } => "restore",
+            Command::MigrateToAccessEntries { .. } => "migrate-to-access-entries",
+            Command::Render { .. } => "render",
+            Command::Healthcheck { .. } => "healthcheck",
+            Command::Completions { .. } => "completions",
+            Command::Manpage => "manpage",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum AuthBackendKind {
+    /// aws-auth config map
+    Configmap,
+    /// EKS access entries
+    AccessEntries,
+    /// aws-iam-authenticator `IAMIdentityMapping` resources
+    #[value(name = "iamidentitymapping")]
+    IamIdentityMapping,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn directive(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ShowConfigOutput {
+    Yaml,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, e.q: for Loki, with RFC 3339 timestamps.
+    Json,
+}
+
+/// Target of the startup configuration summary, logged even when quiet.
+const STARTUP_LOG_TARGET: &str = concat!(env!("CARGO_CRATE_NAME"), "::startup");
+
+/// Sink configured through `notify_*` arguments.
+enum SelectedNotificationSink {
+    Webhook(WebhookSink),
+    Slack(SlackSink),
+}
+
+impl NotificationSink for SelectedNotificationSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+        match self {
+            SelectedNotificationSink::Webhook(s) => s.notify(notification).await,
+            SelectedNotificationSink::Slack(s) => s.notify(notification).await,
+        }
+    }
+
+    fn failure_log_interval(&self) -> Duration {
+        match self {
+            SelectedNotificationSink::Webhook(s) => s.failure_log_interval(),
+            SelectedNotificationSink::Slack(s) => s.failure_log_interval(),
+        }
+    }
+}
+
+/// Sinks configured in `args`, none being returned in dry run modes as nothing is actually changed.
+fn notification_sinks(args: &Args) -> Result<Vec<SelectedNotificationSink>, NotificationError> {
+    let timeout = Duration::from_secs(args.notify_webhook_timeout_seconds);
+    let mut sinks = Vec::new();
+    if let Some(url) = &args.notify_webhook_url {
+        sinks.push(SelectedNotificationSink::Webhook(WebhookSink::new(
+            url, timeout,
+        )?));
+    }
+    if let Some(url) = &args.slack_webhook_url {
+        sinks.push(SelectedNotificationSink::Slack(SlackSink::new(
+            url, timeout,
+        )?));
+    }
+    if !sinks.is_empty() && (args.dry_run || args.server_dry_run) {
+        info!("Dry run mode, notifications won't be sent");
+        sinks.clear();
+    }
+    Ok(sinks)
+}
+
+/// Backend selected through `auth_backend`.
+enum SelectedAuthBackend {
+    ConfigMap(KubernetesService),
+    AccessEntries(AccessEntriesBackend<EksService>),
+    IamIdentityMapping(IdentityMappingsBackend<KubernetesService>),
+}
+
+impl AuthBackend for SelectedAuthBackend {
+    async fn get_config_map_resource_version(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError> {
+        match self {
+            SelectedAuthBackend::ConfigMap(b) => {
+                b.get_config_map_resource_version(config_map_namespace, config_map_name)
+                    .await
+            }
+            SelectedAuthBackend::AccessEntries(b) => {
+                b.get_config_map_resource_version(config_map_namespace, config_map_name)
+                    .await
+            }
+            SelectedAuthBackend::IamIdentityMapping(b) => {
+                b.get_config_map_resource_version(config_map_namespace, config_map_name)
+                    .await
+            }
+        }
+    }
+
+    async fn read_current(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        match self {
+            SelectedAuthBackend::ConfigMap(b) => {
+                b.read_current(config_map_namespace, config_map_name).await
+            }
+            SelectedAuthBackend::AccessEntries(b) => {
+                b.read_current(config_map_namespace, config_map_name).await
+            }
+            SelectedAuthBackend::IamIdentityMapping(b) => {
+                b.read_current(config_map_namespace, config_map_name).await
+            }
+        }
+    }
+
+    async fn update_user_and_role_config_map(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError> {
+        match self {
+            SelectedAuthBackend::ConfigMap(b) => {
+                b.update_user_and_role_config_map(
+                    config_map_namespace,
+                    config_map_name,
+                    kubernetes_users_to_be_added,
+                    kubernetes_sso_role_to_be_added,
+                    karpenter_roles_to_be_added,
+                    other_roles_to_be_added,
+                    aws_accounts_to_be_added,
+                )
+                .await
+            }
+            SelectedAuthBackend::AccessEntries(b) => {
+                b.update_user_and_role_config_map(
+                    config_map_namespace,
+                    config_map_name,
+                    kubernetes_users_to_be_added,
+                    kubernetes_sso_role_to_be_added,
+                    karpenter_roles_to_be_added,
+                    other_roles_to_be_added,
+                    aws_accounts_to_be_added,
+                )
+                .await
+            }
+            SelectedAuthBackend::IamIdentityMapping(b) => {
+                b.update_user_and_role_config_map(
+                    config_map_namespace,
+                    config_map_name,
+                    kubernetes_users_to_be_added,
+                    kubernetes_sso_role_to_be_added,
+                    karpenter_roles_to_be_added,
+                    other_roles_to_be_added,
+                    aws_accounts_to_be_added,
+                )
+                .await
+            }
+        }
+    }
+}
+
+impl Args {
+    /// `refresh_interval`, unless set in seconds with the deprecated `refresh_interval_seconds`.
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(self.refresh_interval)
+    }
+}
+
+fn parse_refresh_interval(raw: &str) -> Result<Duration, String> {
+    let refresh_interval = humantime::parse_duration(raw)
+        .map_err(|e| format!("`{raw}` should be a duration, e.q: 30s, 5m or 1h: {e}"))?;
+    match refresh_interval >= MIN_REFRESH_INTERVAL {
+        true => Ok(refresh_interval),
+        false => Err(format!(
+            "`{raw}` should be at least {}, not to hammer IAM",
+            humantime::format_duration(MIN_REFRESH_INTERVAL)
+        )),
+    }
+}
+
+/// Deprecated `refresh_interval_seconds`, checked against the same minimum as `refresh_interval`.
+fn parse_refresh_interval_seconds(raw: &str) -> Result<u64, String> {
+    let refresh_interval_seconds = raw
+        .parse::<u64>()
+        .map_err(|e| format!("`{raw}` should be a number of seconds, e.q: 30: {e}"))?;
+    match Duration::from_secs(refresh_interval_seconds) >= MIN_REFRESH_INTERVAL {
+        true => Ok(refresh_interval_seconds),
+        false => Err(format!(
+            "`{raw}` should be at least {}, not to hammer IAM",
+            MIN_REFRESH_INTERVAL.as_secs()
+        )),
+    }
+}
+
+fn parse_fraction(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("`{raw}` should be a number between 0 and 1")),
+    }
+}
+
+/// Reports the outcome of a sync in the status of each of `group_mappings`, failures being only logged.
+async fn update_group_mappings_status(
+    kubernetes_client: &KubernetesService,
+    group_mappings: &[IamGroupMapping],
+    sync_result: Result<&HashMap<IamGroup, usize>, &Error>,
+) {
+    let now = SystemTime::now();
+    for group_mapping in group_mappings {
+        let status = match (group_mapping.validate(), sync_result) {
+            (Err(e), _) => group_mapping.failed_status(&e, now),
+            (Ok(()), Err(e)) => group_mapping.failed_status(&e.to_string(), now),
+            (Ok(()), Ok(users_per_iam_group)) => group_mapping.synced_status(
+                users_per_iam_group
+                    .get(&IamGroup::new(group_mapping.spec.iam_group.trim()))
+                    .copied()
+                    .unwrap_or_default(),
+                now,
+            ),
+        };
+        if let Err(e) = kubernetes_client
+            .update_group_mapping_status(
+                &group_mapping.namespace().unwrap_or_default(),
+                &group_mapping.name_any(),
+                &status,
+            )
+            .await
+        {
+            warn!("Error while updating IAM group mapping status: {e}");
+        }
+    }
+}
+
+/// Makes sure a Kubernetes client configured with `endpoint` reaches the cluster described by `api`.
+async fn check_cluster_identity(
+    api: &impl ClusterApi,
+    endpoint: &ClusterEndpoint,
+    verbose: bool,
+) -> Result<(), Error> {
+    let cluster = api.describe_cluster().await.map_err(|e| Error::Aws {
+        underlying_error: e.into(),
+    })?;
+    if verbose {
+        info!(
+            "EKS cluster `{}` running Kubernetes {}",
+            cluster.arn, cluster.version
+        );
+    }
+
+    match cluster.is_reached_by(endpoint) {
+        true => Ok(()),
+        false => Err(Error::ClusterIdentityMismatch {
+            cluster_name: Arc::from(cluster.name),
+            cluster_endpoint: Arc::from(cluster.endpoint),
+            kube_endpoint: Arc::from(endpoint.url.as_str()),
+        }),
+    }
+}
+
+/// Config map holding the outcome of the last sync attempt.
+#[derive(Clone, Debug)]
+struct StatusConfigMap {
+    namespace: String,
+    name: String,
+}
+
+/// Writes `status` into `status_config_map` if set, failures being only logged.
+async fn write_sync_status(
+    kubernetes_client: &KubernetesService,
+    status_config_map: &Option<StatusConfigMap>,
+    status: SyncStatus,
+) {
+    if let Some(status_config_map) = status_config_map {
+        if let Err(e) = kubernetes_client
+            .write_sync_status(
+                &status_config_map.namespace,
+                &status_config_map.name,
+                &status,
+            )
+            .await
+        {
+            warn!("Error while writing sync status: {e}");
+        }
+    }
+}
+
+/// Reports syncs of the config maps of the cluster `kubernetes_client` reaches: notifications and Kubernetes events
+/// of each config map, IAM group mappings status, status config map and mapping export of all of them.
+struct ConfigMapsReporter {
+    kubernetes_client: KubernetesService,
+    status_config_map: Option<StatusConfigMap>,
+    synced_by: SyncedBy,
+    export_target: Option<ExportTarget>,
+    notifier: Notifier<SelectedNotificationSink>,
+    notified_cluster: String,
+    /// Synced config maps by `<namespace>/<name>`, in targets order, along the recorder of their sync events.
+    config_maps: Vec<(String, Option<EventRecorder>)>,
+}
+
+impl SyncReporter for ConfigMapsReporter {
+    async fn report(
+        &mut self,
+        outcomes: &Result<TargetsOutcomes, Error>,
+        settings: &SyncSettings,
+        group_mappings: &[IamGroupMapping],
+    ) {
+        // every config map fails when IAM users cannot be fetched
+        let config_maps_outcomes = match outcomes {
+            Ok(outcomes) => outcomes
+                .iter()
+                .map(|(_, outcome)| outcome.as_ref())
+                .collect::<Vec<_>>(),
+            Err(e) => self.config_maps.iter().map(|_| Err(e)).collect(),
+        };
+        let several_config_maps = self.config_maps.len() > 1;
+        // outcomes are in targets order
+        for (outcome, (target, event_recorder)) in
+            config_maps_outcomes.iter().zip(&mut self.config_maps)
+        {
+            let notified = match several_config_maps {
+                true => format!("{} ({target})", self.notified_cluster),
+                false => self.notified_cluster.clone(),
+            };
+            let sync_event = match outcome {
+                Ok(outcome) => {
+                    self.notifier.sync_succeeded(&notified, &outcome.diff).await;
+                    SyncEvent::succeeded(&outcome.diff)
+                }
+                Err(e) => {
+                    self.notifier.sync_failed(&notified, e).await;
+                    SyncEvent::failed(e)
+                }
+            };
+            if let Some(event_recorder) = event_recorder {
+                if let Err(e) = event_recorder.record(&sync_event).await {
+                    warn!("Error while publishing sync event of config map `{target}`: {e}");
+                }
+            }
+        }
+
+        let first_error = config_maps_outcomes
+            .iter()
+            .find_map(|outcome| outcome.err());
+        match (first_error, config_maps_outcomes.first()) {
+            (None, Some(Ok(outcome))) => {
+                update_group_mappings_status(
+                    &self.kubernetes_client,
+                    group_mappings,
+                    Ok(&outcome.users_per_iam_group),
+                )
+                .await;
+                write_sync_status(
+                    &self.kubernetes_client,
+                    &self.status_config_map,
+                    SyncStatus::succeeded(&outcome.aws_auth, &self.synced_by, SystemTime::now()),
+                )
+                .await;
+                if let Some(export_target) = &self.export_target {
+                    let export = MappingExport::new(
+                        &outcome.aws_auth,
+                        &settings
+                            .groups_mappings
+                            .as_ref()
+                            .map(|gm| gm.iam_groups())
+                            .unwrap_or_default(),
+                        SystemTime::now(),
+                    );
+                    if let Err(e) = export.write_to(export_target) {
+                        error!("Error while exporting IAM EKS mapping: {e}");
+                    }
+                }
+            }
+            (Some(e), _) => {
+                update_group_mappings_status(&self.kubernetes_client, group_mappings, Err(e)).await;
+                write_sync_status(
+                    &self.kubernetes_client,
+                    &self.status_config_map,
+                    SyncStatus::failed(e, SystemTime::now()),
+                )
+                .await;
+            }
+            // no config map synced
+            (None, _) => {}
+        }
+    }
+}
+
+/// Reports syncs of each cluster, by context: status config map and notifications.
+struct ClustersReporter {
+    clusters: Vec<(String, KubernetesService)>,
+    status_config_map: Option<StatusConfigMap>,
+    synced_by: SyncedBy,
+    notifier: Notifier<SelectedNotificationSink>,
+}
+
+impl SyncReporter for ClustersReporter {
+    async fn report(
+        &mut self,
+        outcomes: &Result<TargetsOutcomes, Error>,
+        _settings: &SyncSettings,
+        _group_mappings: &[IamGroupMapping],
+    ) {
+        match outcomes {
+            // outcomes are in clusters order
+            Ok(outcomes) => {
+                for ((context, outcome), (_, backend)) in outcomes.iter().zip(&self.clusters) {
+                    let status = match outcome {
+                        Ok(outcome) => {
+                            self.notifier.sync_succeeded(context, &outcome.diff).await;
+                            SyncStatus::succeeded(
+                                &outcome.aws_auth,
+                                &self.synced_by,
+                                SystemTime::now(),
+                            )
+                        }
+                        Err(e) => {
+                            self.notifier.sync_failed(context, e).await;
+                            SyncStatus::failed(e, SystemTime::now())
+                        }
+                    };
+                    write_sync_status(backend, &self.status_config_map, status).await;
+                }
+            }
+            Err(e) => {
+                for (context, backend) in &self.clusters {
+                    self.notifier.sync_failed(context, e).await;
+                    write_sync_status(
+                        backend,
+                        &self.status_config_map,
+                        SyncStatus::failed(e, SystemTime::now()),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+}
+
+/// Reads a config map manifest, e.q: `kubectl get configmap -o yaml` output.
+fn read_config_map_file(path: &Path) -> Result<ConfigMap, ConfigurationError> {
+    let raw = std::fs::read_to_string(path).map_err(|e| {
+        ConfigurationError::CannotReadExistingAwsAuth {
+            path: Arc::from(path.to_string_lossy().as_ref()),
+            raw_message: Arc::from(e.to_string()),
+        }
+    })?;
+
+    serde_yaml::from_str(&raw).map_err(|e| ConfigurationError::InvalidExistingAwsAuth {
+        path: Arc::from(path.to_string_lossy().as_ref()),
+        raw_message: Arc::from(e.to_string()),
+    })
+}
+
+async fn restore_config_map_backup(
+    kubernetes_client: &KubernetesService,
+    config_map_namespace: &str,
+    config_map_name: &str,
+    backup_name: Option<&str>,
+    list: bool,
+) -> Result<(), errors::Error> {
+    if list {
+        let backup_names = kubernetes_client
+            .list_config_map_backups(config_map_namespace)
+            .await
+            .map_err(|e| Error::Kubernetes {
+                underlying_error: e,
+            })?;
+        info!(
+            "{} backup(s) available for config map `{config_map_namespace}/{config_map_name}`",
+            backup_names.len()
+        );
+        for backup_name in backup_names {
+            println!("{backup_name}");
+        }
+
+        return Ok(());
+    }
+
+    let restored_backup_name = kubernetes_client
+        .restore_config_map_backup(config_map_namespace, config_map_name, backup_name)
+        .await
+        .map_err(|e| Error::Kubernetes {
+            underlying_error: e,
+        })?;
+    info!("Backup `{restored_backup_name}` restored into config map `{config_map_namespace}/{config_map_name}`");
+
+    Ok(())
+}
+
+/// What `settings` make the sync read and write, checked by the `validate` command.
+fn preflight_targets(settings: &SyncSettings, create_if_missing: bool) -> PreflightTargets {
+    PreflightTargets {
+        iam_groups: settings
+            .groups_mappings
+            .as_ref()
+            .map(|gm| BTreeSet::from_iter(gm.iam_groups()))
+            .unwrap_or_default(),
+        roles: settings
+            .sso_role
+            .iter()
+            .map(|r| ("SSO role", r.iam_role_arn.clone()))
+            .chain(
+                settings
+                    .karpenter_roles
+                    .iter()
+                    .map(|r| ("Karpenter role", r.iam_role_arn.clone())),
+            )
+            .collect(),
+        config_map_namespace: settings.config_map_namespace.clone(),
+        config_map_name: settings.config_map_name.clone(),
+        create_if_missing,
+    }
+}
+
+/// Prints a line per check result to stdout, failing if any check failed.
+fn report_checks(results: &[CheckResult]) -> Result<(), errors::Error> {
+    for result in results {
+        println!("{result}");
+    }
+
+    match results.iter().filter(|r| r.failed()).count() {
+        0 => Ok(()),
+        failed_checks => Err(Error::ValidationFailed { failed_checks }),
+    }
+}
+
+/// Removes all entries synced by the tool from the config map, printing removed ones to stdout.
+async fn cleanup_synced_entries(
+    kubernetes_client: &KubernetesService,
+    config_map_namespace: &str,
+    config_map_name: &str,
+    dry_run: bool,
+) -> Result<AwsAuthDiff, errors::Error> {
+    let diff = kubernetes_client
+        .remove_synced_entries(config_map_namespace, config_map_name)
+        .await
+        .map_err(|e| Error::Kubernetes {
+            underlying_error: e,
+        })?;
+
+    let summary = diff.summary();
+    match (summary.users.removed + summary.roles.removed, dry_run) {
+        (0, _) => info!(
+            "No entries synced by the tool in config map `{config_map_namespace}/{config_map_name}`, nothing written"
+        ),
+        (_, true) => info!(
+            "Dry run, entries synced by the tool to be removed from config map `{config_map_namespace}/{config_map_name}`: {summary}"
+        ),
+        (_, false) => info!(
+            "Entries synced by the tool removed from config map `{config_map_namespace}/{config_map_name}`: {summary}"
+        ),
+    }
+    print!("{diff}");
+
+    Ok(diff)
+}
+
+async fn migrate_to_access_entries(
+    kubernetes_client: &KubernetesService,
+    access_entries_backend: &AccessEntriesBackend<EksService>,
+    config_map_namespace: &str,
+    config_map_name: &str,
+    include_unmanaged: bool,
+    delete_source: bool,
+) -> Result<(), errors::Error> {
+    let aws_auth = kubernetes_client
+        .get_aws_auth(config_map_namespace, config_map_name)
+        .await
+        .map_err(|e| Error::Kubernetes {
+            underlying_error: e,
+        })?;
+
+    let migrated_entries = access_entries_backend
+        .migrate(&aws_auth, include_unmanaged)
+        .await
+        .map_err(|e| Error::Aws {
+            underlying_error: e.into(),
+        })?;
+    for migrated_entry in &migrated_entries {
+        println!(
+            "{}: {}",
+            migrated_entry.principal_arn, migrated_entry.status
+        );
+    }
+    info!(
+        "{} entries of config map `{config_map_namespace}/{config_map_name}` considered for migration to access entries",
+        migrated_entries.len()
+    );
+
+    if delete_source {
+        let migrated_arns: Vec<IamArn> = migrated_entries
+            .iter()
+            .filter(|e| e.can_remove_source())
+            .map(|e| e.principal_arn.clone())
+            .collect();
+        let diff = kubernetes_client
+            .remove_aws_auth_entries(config_map_namespace, config_map_name, &migrated_arns)
+            .await
+            .map_err(|e| Error::Kubernetes {
+                underlying_error: e,
+            })?;
+        info!(
+            "{} migrated entries removed from config map `{config_map_namespace}/{config_map_name}`",
+            diff.users.removed.len() + diff.roles.removed.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// `config` with secrets redacted, along protected ARNs, webhooks and features set through `args`.
+fn redacted_config(args: &Args, config: &Config) -> RedactedConfig {
+    let mut summary = config.redacted();
+    summary.protected_arns = args
+        .protected_arns
+        .iter()
+        .map(|arn| arn.trim())
+        .filter(|arn| !arn.is_empty())
+        .map(str::to_string)
+        .collect();
+    summary.notify_webhook_url = args
+        .notify_webhook_url
+        .as_ref()
+        .map(|_| REDACTED.to_string());
+    summary.slack_webhook_url = args
+        .slack_webhook_url
+        .as_ref()
+        .map(|_| REDACTED.to_string());
+    summary.features = BTreeMap::from([
+        ("once", args.once),
+        ("dry_run", args.dry_run),
+        ("server_dry_run", args.server_dry_run),
+        ("crd_mode", args.crd_mode),
+        ("watch_config_map", args.watch_config_map),
+        ("enable_leader_election", args.enable_leader_election),
+        ("enable_sync_endpoint", args.enable_sync_endpoint),
+        ("discover_nodegroup_roles", args.discover_nodegroup_roles),
+        ("resolve_instance_profile", args.resolve_instance_profile),
+        (
+            "create_config_map_if_missing",
+            args.create_config_map_if_missing,
+        ),
+        ("adopt_existing_entries", args.adopt_existing_entries),
+        ("removals_only", args.removals_only),
+        ("force_removals", args.force_removals),
+        ("no_annotations", args.no_annotations),
+        ("emit_kubernetes_events", args.emit_kubernetes_events),
+        ("status_config_map", args.status_config_map),
+        (
+            "skip_cluster_identity_check",
+            args.skip_cluster_identity_check,
+        ),
+        ("no_fail_fast", args.no_fail_fast),
+    ]);
+    summary
+}
+
+/// Builds the sync config out of `args`.
+pub fn config_from_args(args: &Args) -> Result<Config, ConfigurationError> {
+    // global arguments cannot be required by clap, commands not needing them
+    let required = |value: &Option<String>, argument: &str| {
+        value
+            .clone()
+            .filter(|v| !v.trim().is_empty())
+            .ok_or_else(|| ConfigurationError::MissingArgumentForCommand {
+                argument: Arc::from(argument),
+                command: Arc::from(args.command.as_ref().map(Command::name).unwrap_or("sync")),
+            })
+    };
+    let credentials = Credentials::try_from_args(
+        required(&args.aws_default_region, "aws_default_region")?,
+        required(&args.service_account_name, "service_account_name")?,
+        args.aws_role_arn.as_deref(),
+        args.aws_access_key_id.as_deref(),
+        args.aws_secret_access_key.as_deref(),
+    )?;
+    // arguments groups aren't propagated to commands, checked here for all of them
+    if args.notify_on_failure
+        && args.notify_webhook_url.is_none()
+        && args.slack_webhook_url.is_none()
+    {
+        return Err(ConfigurationError::NotifyOnFailureWithoutSink);
+    }
+    // access entries aren't synced through the aws-auth builder suppressing additions
+    if args.removals_only && args.auth_backend == AuthBackendKind::AccessEntries {
+        return Err(ConfigurationError::IncompatibleArguments {
+            argument: Arc::from("removals_only"),
+            other_argument: Arc::from("auth_backend"),
+        });
+    }
+
+    // first target is the primary one, watched
+    let (config_map_namespace, config_map_name) = match args.config_map_targets.first() {
+        Some(config_map_target) => parse_config_map_target(config_map_target)?,
+        None => (
+            args.config_map_namespace.clone(),
+            args.config_map_name.clone(),
+        ),
+    };
+
+    let config = config::Config::new(
+        credentials,
+        ConfigOptions {
+            refresh_interval: args.refresh_interval(),
+            refresh_interval_jitter: Duration::from_secs(args.refresh_interval_jitter_seconds),
+            refresh_interval_jitter_seed: args.refresh_interval_jitter_seed,
+            missed_tick_behavior: args.missed_tick_behavior,
+            sync_timeout: Duration::from_secs(args.sync_timeout_seconds),
+            max_backoff: Duration::from_secs(args.max_backoff_seconds),
+            max_consecutive_failures: args.max_consecutive_failures,
+            fail_fast: !args.no_fail_fast,
+            // mappings are read from the cluster in CRD mode, before each sync
+            enable_group_sync: args.enable_group_user_sync && !args.crd_mode,
+            iam_k8s_groups: args.iam_k8s_groups.clone(),
+            enable_sso: args.enable_sso,
+            iam_sso_role_arn: args.iam_sso_role_arn.clone(),
+            iam_sso_role_name: args.iam_sso_role_name.clone(),
+            iam_sso_permission_set: args.iam_sso_permission_set.clone(),
+            karpenter_role_arns: args.karpenter_role_arn.clone(),
+            resolve_instance_profile: args.resolve_instance_profile,
+            karpenter_role_username: args.karpenter_role_username.clone(),
+            karpenter_role_groups: args.karpenter_role_groups.clone(),
+            node_role_arns: args.node_role_arns.clone(),
+            map_aws_accounts: args.map_aws_accounts.clone(),
+            map_roles: args.map_roles.clone(),
+            map_users: args.map_users.clone(),
+            admins_iam_users: args.admin_iam_user_arns.clone(),
+            denied_k8s_groups: args
+                .denied_k8s_groups
+                .iter()
+                .cloned()
+                .chain((!args.allow_system_masters).then(|| "system:masters".to_string()))
+                .collect(),
+            username_template: args.username_template.clone(),
+            lowercase_usernames: args.lowercase_usernames,
+            config_map_namespace,
+            config_map_name,
+            verbose: args.verbose,
+        },
+    )?;
+    config.check_system_groups(args.strict_group_validation)?;
+
+    Ok(config)
+}
+
+/// Logs filter at `log_level`, the tool logging at debug level at least when `verbose` and at warn level at
+/// most when `quiet`, the startup summary aside. `rust_log` directives come last to override all of them,
+/// e.q: `kube=debug` or `info` to silence verbose mode.
+fn log_filter(
+    log_level: LogLevel,
+    verbose: bool,
+    quiet: bool,
+    rust_log: Option<&str>,
+) -> EnvFilter {
+    let log_level = match quiet {
+        true => log_level.min(LogLevel::Warn),
+        false => log_level,
+    };
+    let mut directives = vec![log_level.directive().to_string()];
+    if verbose && log_level < LogLevel::Debug {
+        directives.push(format!("{}=debug", env!("CARGO_CRATE_NAME")));
+    }
+    if quiet {
+        directives.push(format!("{STARTUP_LOG_TARGET}=info"));
+    }
+    directives.extend(
+        rust_log
+            .map(str::trim)
+            .filter(|rust_log| !rust_log.is_empty())
+            .map(str::to_string),
+    );
+
+    EnvFilter::new(directives.join(","))
+}
+
+/// Whether logs are coloured, only text ones written to a terminal being by default.
+fn log_ansi(log_format: LogFormat, no_ansi: bool, is_terminal: bool) -> bool {
+    log_format == LogFormat::Text && !no_ansi && is_terminal
+}
+
+/// Layer writing logs to `writer` in `log_format`, coloured if `ansi` for text ones.
+fn fmt_layer<S>(
+    log_format: LogFormat,
+    ansi: bool,
+    writer: BoxMakeWriter,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    match log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .fmt_fields(
+                tracing_subscriber::fmt::format::debug_fn(|writer, field, value| {
+                    write!(writer, "{field}: {value:?}")
+                })
+                .delimited(", "),
+            )
+            .with_ansi(ansi)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_ansi(false)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+/// Parses arguments again with the current content of the config and env files, errors being returned instead of exiting.
+fn reload_args<I, T>(argv: I) -> Result<Args, ConfigurationError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let argv: Vec<std::ffi::OsString> = argv.into_iter().map(Into::into).collect();
+    let loader = ArgsLoader::from_argv(argv.clone())?;
+
+    loader
+        .load(argv)
+        .map_err(|e| ConfigurationError::InvalidConfigFile {
+            path: Arc::from(
+                loader
+                    .config_path()
+                    .map(|path| path.to_string_lossy())
+                    .unwrap_or_default()
+                    .as_ref(),
+            ),
+            raw_message: Arc::from(e.to_string().trim()),
+        })
+}
+
+/// Shell completions generated from the arguments definition, for them never to drift from it.
+fn completions(shell: Shell) -> String {
+    let mut buffer = Vec::new();
+    clap_complete::generate(
+        shell,
+        &mut Args::command(),
+        env!("CARGO_PKG_NAME"),
+        &mut buffer,
+    );
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+/// Roff man page generated from the arguments definition, for it never to drift from it.
+fn manpage() -> String {
+    let mut buffer = Vec::new();
+    // writing into memory cannot fail
+    let _ = clap_mangen::Man::new(Args::command()).render(&mut buffer);
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+/// Parses arguments from flags, env vars (`--env-file` included) and the `--config` file if any, in decreasing precedence.
+pub fn parse_args() -> Result<Args, ConfigurationError> {
+    Ok(ArgsLoader::from_argv(std::env::args_os())?
+        .load(std::env::args_os())
+        .unwrap_or_else(|e| e.exit()))
+}
+
+/// Runs the command of `args`, syncing until terminated unless another one is set, after setting up tracing.
+pub async fn execute(args: Args) -> Result<(), Error> {
+    // only the arguments definition is needed, nothing is set up
+    match &args.command {
+        Some(Command::Completions { shell }) => {
+            print!("{}", completions(*shell));
+            return Ok(());
+        }
+        Some(Command::Manpage) => {
+            print!("{}", manpage());
+            return Ok(());
+        }
+        _ => {}
+    }
+    let otlp_tracing = args
+        .otlp_endpoint
+        .as_deref()
+        .map(OtlpTracing::new)
+        .transpose()
+        .map_err(|e| Error::Telemetry {
+            underlying_error: e,
+        })?;
+
+    // Init tracing subscriber
+    // rendered config map and shown config have to be the only things written to stdout, to be piped
+    let (writer, is_terminal) = match (&args.command, args.show_config) {
+        (Some(Command::Render { .. }), _) | (_, true) => (
+            BoxMakeWriter::new(std::io::stderr),
+            std::io::stderr().is_terminal(),
+        ),
+        _ => (
+            BoxMakeWriter::new(std::io::stdout),
+            std::io::stdout().is_terminal(),
+        ),
+    };
+    let subscriber =
+        tracing_subscriber::registry()
+            .with(log_filter(
+                args.log_level,
+                args.verbose,
+                args.quiet,
+                std::env::var(EnvFilter::DEFAULT_ENV).ok().as_deref(),
+            ))
+            .with(fmt_layer(
+                args.log_format,
+                log_ansi(args.log_format, args.no_ansi, is_terminal),
+                writer,
+            ))
+            .with(otlp_tracing.as_ref().map(|otlp_tracing| {
+                tracing_opentelemetry::layer().with_tracer(otlp_tracing.tracer())
+            }));
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| {
+        Error::InitializationErrorCannotSetupTracing {
+            underlying_error: e,
+        }
+    })?;
+
+    let Some(otlp_tracing) = otlp_tracing else {
+        return run(args).await;
+    };
+    let res = tokio::select! {
+        res = run(args) => res,
+        _ = shutdown_signal() => {
+            info!("Shutting down");
+            Ok(())
+        }
+    };
+    // spans are exported in batches, the last ones would be lost without flushing them before exiting
+    match task::spawn_blocking(move || otlp_tracing.shutdown()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("Error while exporting traces: {e}"),
+        Err(e) => warn!("Error while exporting traces: {e}"),
+    }
+
+    res
+}
+
+/// Resolves on SIGTERM, e.q: when the pod is deleted, or on Ctrl-C.
+async fn shutdown_signal() {
+    let sigterm = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("Cannot listen for SIGTERM, traces won't be exported on termination: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = sigterm => {}
+        Ok(()) = tokio::signal::ctrl_c() => {}
+    }
+}
+
+async fn run(args: Args) -> Result<(), Error> {
+    let span = span!(Level::INFO, "main_span");
+    let _enter = span.enter();
+
+    if args.refresh_interval_seconds.is_some() {
+        warn!("`refresh_interval_seconds` is deprecated, use `refresh_interval` instead, e.q: --refresh-interval 60s");
+    }
+
+    if let Some(Command::Healthcheck { max_age_seconds }) = &args.command {
+        let heartbeat_file =
+            args.heartbeat_file
+                .as_deref()
+                .ok_or_else(|| Error::Configuration {
+                    underlying_error: ConfigurationError::MissingArgumentForCommand {
+                        argument: Arc::from("heartbeat_file"),
+                        command: Arc::from("healthcheck"),
+                    },
+                })?;
+        let age = check_heartbeat(
+            heartbeat_file,
+            Duration::from_secs(*max_age_seconds),
+            SystemTime::now(),
+        )
+        .map_err(|e| Error::Heartbeat {
+            underlying_error: e,
+        })?;
+        info!("Last successful sync is {}s old", age.as_secs());
+        return Ok(());
+    }
+
+    let config_map_options = ConfigMapOptions {
+        create_if_missing: args.create_config_map_if_missing,
+        backup_config_map_name: args.backup_config_map_name.clone(),
+        backup_history_count: args.backup_history_count,
+        skip_annotations: args.no_annotations,
+        server_dry_run: args.server_dry_run,
+        dry_run: args.dry_run,
+        protected_arns: args
+            .protected_arns
+            .iter()
+            .map(|arn| arn.trim())
+            .filter(|arn| !arn.is_empty())
+            .map(IamArn::new)
+            .collect(),
+        removal_guard: RemovalGuard {
+            max_removal_fraction: args.max_removal_fraction,
+            max_removals: args.max_removals,
+            force: args.force_removals,
+        },
+        synced_by: SyncedBy::new(args.synced_by_value.trim()),
+        synced_entry_ttl: args.synced_entry_ttl,
+        adopt_existing_entries: args.adopt_existing_entries,
+        adopt_arn_pattern: args.adopt_by_arn_pattern.clone(),
+        removals_only: args.removals_only,
+        error_snippet_max_length: args.error_snippet_max_length,
+    };
+    let cluster_access = ClusterAccess {
+        kubeconfig_path: args.kubeconfig.clone(),
+        context: args.kube_context.clone(),
+        request_timeout: args.kube_request_timeout_seconds.map(Duration::from_secs),
+    };
+
+    if let Some(Command::Restore { backup, list }) = &args.command {
+        let kubernetes_client = KubernetesService::new(&cluster_access, config_map_options)
+            .await
+            .map_err(|e| Error::Kubernetes {
+                underlying_error: e,
+            })?;
+
+        return restore_config_map_backup(
+            &kubernetes_client,
+            &args.config_map_namespace,
+            &args.config_map_name,
+            backup.as_deref(),
+            *list,
+        )
+        .await;
+    }
+
+    if let Some(Command::Cleanup { yes }) = &args.command {
+        // other backends don't sync config maps
+        if args.auth_backend != AuthBackendKind::Configmap {
+            return Err(Error::Configuration {
+                underlying_error: ConfigurationError::IncompatibleArguments {
+                    argument: Arc::from("cleanup"),
+                    other_argument: Arc::from("auth_backend"),
+                },
+            });
+        }
+        // removed entries can only be brought back from backups
+        if !yes && !args.dry_run {
+            return Err(Error::Configuration {
+                underlying_error: ConfigurationError::MissingArgumentForCommand {
+                    argument: Arc::from("yes"),
+                    command: Arc::from("cleanup"),
+                },
+            });
+        }
+        let config_map_targets = match args.config_map_targets.is_empty() {
+            true => vec![(
+                args.config_map_namespace.clone(),
+                args.config_map_name.clone(),
+            )],
+            false => args
+                .config_map_targets
+                .iter()
+                .map(|target| parse_config_map_target(target))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Error::Configuration {
+                    underlying_error: e,
+                })?,
+        };
+        let dry_run = config_map_options.dry_run || config_map_options.server_dry_run;
+        let kubernetes_client = KubernetesService::new(&cluster_access, config_map_options)
+            .await
+            .map_err(|e| Error::Kubernetes {
+                underlying_error: e,
+            })?;
+
+        for (namespace, name) in config_map_targets {
+            cleanup_synced_entries(&kubernetes_client, &namespace, &name, dry_run).await?;
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::MigrateToAccessEntries {
+        include_unmanaged,
+        delete_source,
+        dry_run,
+    }) = &args.command
+    {
+        let missing_argument = |argument: &str| Error::Configuration {
+            underlying_error: ConfigurationError::MissingArgumentForCommand {
+                argument: Arc::from(argument),
+                command: Arc::from("migrate-to-access-entries"),
+            },
+        };
+        let eks_cluster_name = args
+            .eks_cluster_name
+            .as_deref()
+            .ok_or_else(|| missing_argument("eks_cluster_name"))?;
+        let aws_default_region = args
+            .aws_default_region
+            .clone()
+            .ok_or_else(|| missing_argument("aws_default_region"))?;
+        let config_map_options = ConfigMapOptions {
+            dry_run: *dry_run || config_map_options.dry_run,
+            ..config_map_options
+        };
+
+        let kubernetes_client = KubernetesService::new(&cluster_access, config_map_options.clone())
+            .await
+            .map_err(|e| Error::Kubernetes {
+                underlying_error: e,
+            })?;
+        let aws_config = AwsSdkConfig::new(aws_default_region, args.verbose)
+            .await
+            .map_err(|e| Error::Aws {
+                underlying_error: e,
+            })?;
+        let eks_client = EksService::new(&aws_config, eks_cluster_name);
+
+        return migrate_to_access_entries(
+            &kubernetes_client,
+            &AccessEntriesBackend::new(eks_client, config_map_options),
+            &args.config_map_namespace,
+            &args.config_map_name,
+            *include_unmanaged,
+            *delete_source,
+        )
+        .await;
+    }
+
+    let config = config_from_args(&args);
+    if let Some(Command::Validate { .. }) = &args.command {
+        println!(
+            "{}",
+            CheckResult::new(
+                "config",
+                config
+                    .as_ref()
+                    .map(|_| "configuration is valid".to_string())
+                    .map_err(|e| e.to_string())
+            )
+        );
+    }
+    let config = config.map_err(|e| Error::Configuration {
+        underlying_error: e,
+    })?;
+    let config_summary = redacted_config(&args, &config);
+
+    // neither AWS nor Kubernetes are reached
+    if args.show_config {
+        match args.output {
+            ShowConfigOutput::Yaml => print!(
+                "{}",
+                serde_yaml::to_string(&config_summary).unwrap_or_default()
+            ),
+            ShowConfigOutput::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&config_summary).unwrap_or_default()
+            ),
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Validate { skip_check }) = &args.command {
+        let aws_config = AwsSdkConfig::new(config.credentials.region.clone(), config.verbose)
+            .await
+            .map_err(|e| Error::Aws {
+                underlying_error: e,
+            })?;
+        let iam_client = IamService::new(&aws_config, config.verbose);
+        let mut settings = SyncSettings::new(&config);
+        settings.karpenter_roles =
+            resolve_instance_profiles(&iam_client, &settings.karpenter_roles)
+                .await
+                .map_err(|e| Error::Aws {
+                    underlying_error: e.into(),
+                })?;
+        if let Some(permission_set) = &settings.sso_permission_set {
+            settings.sso_role = Some(permission_set_sso_role(&iam_client, permission_set).await?);
+        }
+        let targets = preflight_targets(&settings, config_map_options.create_if_missing);
+        // a client which cannot be built fails cluster checks, not the command
+        let kubernetes_client = KubernetesService::new(&cluster_access, config_map_options).await;
+
+        let results = run_checks(
+            &aws_config,
+            &iam_client,
+            kubernetes_client.as_ref(),
+            &targets,
+            &BTreeSet::from_iter(skip_check.iter().copied()),
+        )
+        .await;
+        return report_checks(&results);
+    }
+
+    if let Some(Command::Render { existing_aws_auth }) = &args.command {
+        let existing_config_map = existing_aws_auth
+            .as_deref()
+            .map(read_config_map_file)
+            .transpose()
+            .map_err(|e| Error::Configuration {
+                underlying_error: e,
+            })?;
+        let aws_config = AwsSdkConfig::new(config.credentials.region.clone(), config.verbose)
+            .await
+            .map_err(|e| Error::Aws {
+                underlying_error: e,
+            })?;
+        let mut settings = SyncSettings::new(&config);
+        if args.discover_nodegroup_roles {
+            // always set when discovering nodegroups roles
+            let nodegroups_client = EksService::new(
+                &aws_config,
+                args.eks_cluster_name.as_deref().unwrap_or_default(),
+            );
+            settings.node_roles =
+                discover_node_roles(&nodegroups_client)
+                    .await
+                    .map_err(|e| Error::Aws {
+                        underlying_error: e.into(),
+                    })?;
+        }
+        let iam_client = IamService::new(&aws_config, config.verbose);
+        settings.karpenter_roles =
+            resolve_instance_profiles(&iam_client, &settings.karpenter_roles)
+                .await
+                .map_err(|e| Error::Aws {
+                    underlying_error: e.into(),
+                })?;
+        if let Some(permission_set) = &settings.sso_permission_set {
+            settings.sso_role = Some(permission_set_sso_role(&iam_client, permission_set).await?);
+        }
+
+        let rendered = render_config_map(
+            &iam_client,
+            &settings,
+            &config_map_options,
+            existing_config_map,
+        )
+        .await?;
+        print!("{rendered}");
+        return Ok(());
+    }
+
+    let notifier = Notifier::new(
+        notification_sinks(&args).map_err(|e| Error::Notification {
+            underlying_error: e,
+        })?,
+        args.notify_on_failure,
+    );
+
+    let config_maps = match args.config_map_targets.is_empty() {
+        true => format!("{}/{}", args.config_map_namespace, args.config_map_name),
+        false => args.config_map_targets.join(","),
+    };
+    info!(
+        target: STARTUP_LOG_TARGET,
+        version = env!("CARGO_PKG_VERSION"),
+        auth_backend = ?args.auth_backend,
+        config_maps = %config_maps,
+        clusters = args.kube_contexts.len().max(1),
+        config = %serde_json::to_string(&config_summary).unwrap_or_default(),
+        "Starting iam-eks-user-mapper"
+    );
+
+    let audit_log = args
+        .audit_log_path
+        .clone()
+        .map(|path| AuditLog::new(path, args.audit_log_required));
+
+    let config_map_targets = match args.config_map_targets.is_empty() {
+        true => vec![(
+            config.config_map_namespace.clone(),
+            config.config_map_name.clone(),
+        )],
+        false => {
+            // other backends don't sync config maps
+            if args.auth_backend != AuthBackendKind::Configmap {
+                return Err(Error::Configuration {
+                    underlying_error: ConfigurationError::IncompatibleArguments {
+                        argument: Arc::from("config_map_targets"),
+                        other_argument: Arc::from("auth_backend"),
+                    },
+                });
+            }
+            args.config_map_targets
+                .iter()
+                .map(|target| parse_config_map_target(target))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Error::Configuration {
+                    underlying_error: e,
+                })?
+        }
+    }
+    .into_iter()
+    .map(|(namespace, name)| ConfigMapTarget {
+        namespace,
+        name,
+        last_sync: None,
+    })
+    .collect::<Vec<_>>();
+
+    let aws_config = AwsSdkConfig::new(config.credentials.region.clone(), config.verbose)
+        .await
+        .map_err(|e| Error::Aws {
+            underlying_error: e,
+        })?;
+
+    let iam_client = IamService::new(&aws_config, config.verbose);
+
+    let status_config_map = match args.status_config_map {
+        true => Some(StatusConfigMap {
+            namespace: args.status_config_map_namespace.clone(),
+            name: args.status_config_map_name.clone(),
+        }),
+        false => None,
+    };
+    let synced_by = config_map_options.synced_by.clone();
+
+    let sync_state = SharedSyncState::default();
+    let (sync_trigger, triggered_syncs) = sync_trigger();
+    // nobody to probe a single sync
+    if !args.once {
+        let mut health_server = HealthServer::bind(
+            args.health_listen_addr,
+            sync_state.clone(),
+            args.readiness_max_staleness
+                .unwrap_or(config.refresh_interval * 3),
+        )
+        .await
+        .map_err(|e| Error::Health {
+            underlying_error: e,
+        })?;
+        if args.enable_sync_endpoint {
+            health_server = health_server.with_sync_trigger(sync_trigger.clone());
+        }
+        info!(
+            "Serving health probes on `{}`",
+            health_server
+                .local_addr()
+                .unwrap_or(args.health_listen_addr)
+        );
+        task::spawn(health_server.serve());
+        task::spawn(trigger_on_sigusr1(sync_trigger));
+    }
+
+    if !args.kube_contexts.is_empty() {
+        // other backends are not cluster scoped, or need a cluster name per context
+        if args.auth_backend != AuthBackendKind::Configmap {
+            return Err(Error::Configuration {
+                underlying_error: ConfigurationError::IncompatibleArguments {
+                    argument: Arc::from("kube_contexts"),
+                    other_argument: Arc::from("auth_backend"),
+                },
+            });
+        }
+        let mut clusters = Vec::with_capacity(args.kube_contexts.len());
+        for context in &args.kube_contexts {
+            let cluster_access = ClusterAccess {
+                context: Some(context.clone()),
+                ..cluster_access.clone()
+            };
+            clusters.push(ClusterTarget {
+                context: context.clone(),
+                backend: KubernetesService::new(&cluster_access, config_map_options.clone())
+                    .await
+                    .map_err(|e| Error::Kubernetes {
+                        underlying_error: e,
+                    })?,
+                last_sync: None,
+            });
+        }
+        let reporter = ClustersReporter {
+            clusters: clusters
+                .iter()
+                .map(|cluster| (cluster.context.clone(), cluster.backend.clone()))
+                .collect(),
+            status_config_map,
+            synced_by,
+            notifier,
+        };
+        let sync_loop = SyncLoop::new(&config, &iam_client, clusters, reporter)
+            .with_audit_log(audit_log)
+            .with_hooks(SyncHooks {
+                sync_state,
+                triggered_syncs,
+                heartbeat_file: args.heartbeat_file.clone(),
+                ..SyncHooks::default()
+            });
+        return run_sync_loop(args.once, sync_loop).await;
+    }
+
+    let mut kubernetes_client = KubernetesService::new(&cluster_access, config_map_options.clone())
+        .await
+        .map_err(|e| Error::Kubernetes {
+            underlying_error: e,
+        })?;
+
+    if let Some(eks_cluster_name) = &args.eks_cluster_name {
+        match args.skip_cluster_identity_check {
+            true => {
+                warn!("Not checking Kubernetes client reaches EKS cluster `{eks_cluster_name}`")
+            }
+            false => {
+                let eks_client = EksService::new(&aws_config, eks_cluster_name);
+                check_cluster_identity(&eks_client, kubernetes_client.endpoint(), config.verbose)
+                    .await?;
+            }
+        }
+    }
+
+    // pod name when running in a cluster
+    let instance_identity = std::env::var("HOSTNAME")
+        .unwrap_or_else(|_| format!("iam-eks-user-mapper-{}", std::process::id()));
+
+    let leadership = match args.enable_leader_election {
+        true => {
+            let leader_elector = kubernetes_client.leader_elector(LeaseOptions {
+                lease_namespace: args.lease_namespace.clone(),
+                lease_name: args.lease_name.clone(),
+                lease_duration: Duration::from_secs(args.lease_duration_seconds),
+                holder_identity: instance_identity.clone(),
+            });
+            let leadership = leader_elector.leadership();
+            kubernetes_client = kubernetes_client.with_leadership(leadership.clone());
+            task::spawn(leader_elector.run());
+            Some(leadership)
+        }
+        false => None,
+    };
+
+    let nodegroups_client = match args.discover_nodegroup_roles {
+        true => Some(
+            // always set when discovering nodegroups roles
+            EksService::new(
+                &aws_config,
+                args.eks_cluster_name.as_deref().unwrap_or_default(),
+            ),
+        ),
+        false => None,
+    };
+
+    let auth_backend = match args.auth_backend {
+        AuthBackendKind::Configmap => SelectedAuthBackend::ConfigMap(kubernetes_client.clone()),
+        AuthBackendKind::AccessEntries => {
+            let eks_client = EksService::new(
+                &aws_config,
+                // always set with access entries backend
+                args.eks_cluster_name.as_deref().unwrap_or_default(),
+            );
+            SelectedAuthBackend::AccessEntries(AccessEntriesBackend::new(
+                eks_client,
+                config_map_options,
+            ))
+        }
+        AuthBackendKind::IamIdentityMapping => SelectedAuthBackend::IamIdentityMapping(
+            IdentityMappingsBackend::new(kubernetes_client.clone(), config_map_options),
+        ),
+    };
+
+    let export_target = args.export_json.as_deref().map(ExportTarget::from);
+    let notified_cluster = args
+        .eks_cluster_name
+        .clone()
+        .unwrap_or_else(|| kubernetes_client.endpoint().url.clone());
+
+    // events of each config map are published onto it
+    let reported_config_maps = config_map_targets
+        .iter()
+        .map(|target| {
+            (
+                format!("{}/{}", target.namespace, target.name),
+                args.emit_kubernetes_events.then(|| {
+                    kubernetes_client.event_recorder(
+                        &target.namespace,
+                        &target.name,
+                        &instance_identity,
+                    )
+                }),
+            )
+        })
+        .collect();
+
+    // sender is dropped right away when watch is disabled, never triggering any sync
+    let (config_map_changes_sender, config_map_changes) = mpsc::channel(1);
+    if args.watch_config_map {
+        let kubernetes_client = kubernetes_client.clone();
+        let (config_map_namespace, config_map_name) = (
+            config.config_map_namespace.clone(),
+            config.config_map_name.clone(),
+        );
+        task::spawn(async move {
+            kubernetes_client
+                .watch_config_map(
+                    &config_map_namespace,
+                    &config_map_name,
+                    config_map_changes_sender,
+                )
+                .await
+        });
+    }
+    // same as config map changes, sender is dropped right away outside of CRD mode
+    let (group_mappings_changes_sender, group_mappings_changes) = mpsc::channel(1);
+    if args.crd_mode {
+        let kubernetes_client = kubernetes_client.clone();
+        let crd_namespace = args.crd_namespace.clone();
+        task::spawn(async move {
+            kubernetes_client
+                .watch_group_mappings(&crd_namespace, group_mappings_changes_sender)
+                .await
+        });
+    }
+    let watch_debounce = Duration::from_secs(args.watch_debounce_seconds);
+    // same as config map changes, sender is dropped right away without config file
+    let (config_reloads_sender, config_reloads) = mpsc::channel(1);
+    if let Some(config_path) = &args.config {
+        let (config_changes_sender, mut config_changes) = mpsc::channel(1);
+        task::spawn(watch_config_file(
+            config_path.clone(),
+            CONFIG_FILE_POLL_INTERVAL,
+            config_changes_sender,
+        ));
+        task::spawn(async move {
+            while config_changes.recv().await.is_some() {
+                match reload_args(std::env::args_os()).and_then(|args| config_from_args(&args)) {
+                    Ok(reloaded_config) => {
+                        if config_reloads_sender.send(reloaded_config).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => error!("Error while reloading config, keeping the current one: {e}"),
+                }
+            }
+        });
+    }
+
+    let reporter = ConfigMapsReporter {
+        kubernetes_client: kubernetes_client.clone(),
+        status_config_map,
+        synced_by,
+        export_target,
+        notifier,
+        notified_cluster,
+        config_maps: reported_config_maps,
+    };
+    let group_mappings_source = args
+        .crd_mode
+        .then(|| (kubernetes_client.clone(), args.crd_namespace.clone()));
+    let config_maps = ConfigMapTargets::new(&auth_backend, config_map_targets).map_err(|e| {
+        Error::Configuration {
+            underlying_error: e,
+        }
+    })?;
+    let sync_loop = SyncLoop::new(&config, &iam_client, config_maps, reporter)
+        .with_audit_log(audit_log)
+        .with_hooks(SyncHooks {
+            sync_state,
+            triggered_syncs,
+            config_map_changes,
+            group_mappings_changes,
+            watch_debounce,
+            config_reloads,
+            leadership,
+            nodegroups_client,
+            group_mappings_source,
+            heartbeat_file: args.heartbeat_file.clone(),
+        });
+    run_sync_loop(args.once, sync_loop).await
+}
+
+/// Syncs once with `once`, forever otherwise.
+async fn run_sync_loop<I, T, R>(
+    once: bool,
+    mut sync_loop: SyncLoop<'_, I, T, R>,
+) -> Result<(), Error>
+where
+    I: IamUserSource + IamRoleSource,
+    T: SyncTargets,
+    R: SyncReporter,
+{
+    if once {
+        let res = sync_loop.sync_once().await;
+        flush_output();
+        return res.map(|_| ());
+    }
+    let res = AssertUnwindSafe(sync_loop.run()).catch_unwind().await;
+    flush_output();
+    Err(sync_loop_exit(res))
+}
+
+/// Error the sync loop stopped with, a panicking one failing instead of exiting successfully.
+fn sync_loop_exit(res: Result<Result<Infallible, Error>, Box<dyn Any + Send>>) -> Error {
+    match res {
+        Ok(Err(e)) => e,
+        Ok(Ok(never)) => match never {},
+        Err(panic) => Error::SyncLoopAborted {
+            raw_message: Arc::from(format!(
+                "panicked with message {:?}",
+                panic
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or_default()
+            )),
+        },
+    }
+}
+
+/// Requests a sync on each `SIGUSR1`, e.q: `kill -USR1 <pid>` after changing IAM groups membership.
+async fn trigger_on_sigusr1(sync_trigger: SyncTrigger) {
+    match signal(SignalKind::user_defined1()) {
+        Ok(mut sigusr1) => {
+            while sigusr1.recv().await.is_some() {
+                debug!("SIGUSR1 received, requesting a sync");
+                sync_trigger.request();
+            }
+        }
+        Err(e) => warn!("Cannot listen for SIGUSR1, syncs cannot be requested by signal: {e}"),
+    }
+}
+
+/// Flushes logs written so far, before exiting.
+fn flush_output() {
+    use std::io::Write;
+
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::eks::EksCluster;
+    use crate::aws::fake::FakeClusterApi;
+    use crate::aws::iam::IamGroup;
+    use crate::cli::{
+        check_cluster_identity, completions, config_from_args, fmt_layer, log_ansi, log_filter,
+        manpage, parse_refresh_interval, preflight_targets, read_config_map_file, redacted_config,
+        reload_args, report_checks, sync_loop_exit, Args, ClusterEndpoint, Command, LogFormat,
+        LogLevel, ShowConfigOutput, STARTUP_LOG_TARGET,
+    };
+    use crate::config::{ConfigurationError, KarpenterRoleConfig, REDACTED};
+    use crate::errors::Error;
+    use crate::kubernetes::{IamArn, KubernetesGroupName};
+    use crate::preflight::{CheckResult, PreflightCheck};
+    use crate::sync::SyncSettings;
+    use clap::Parser;
+    use clap_complete::Shell;
+    use futures::FutureExt;
+    use std::collections::BTreeSet;
+    use std::convert::Infallible;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tracing::callsite::{Callsite, Identifier};
+    use tracing::field::FieldSet;
+    use tracing::metadata::Kind;
+    use tracing::subscriber::Interest;
+    use tracing::{Level, Metadata, Subscriber};
+    use tracing_subscriber::fmt::writer::BoxMakeWriter;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn read_config_map_file_error_test() {
+        // setup:
+        let dir = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-render-invalid-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).expect("cannot create temp dir");
+        let invalid = dir.join("aws-auth.yaml");
+        std::fs::write(&invalid, "data: [not, a, map]\n").expect("cannot write file");
+
+        // execute & verify:
+        assert!(read_config_map_file(&dir.join("missing.yaml")).is_err());
+        assert!(read_config_map_file(&invalid).is_err());
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+
+    #[tokio::test]
+    async fn check_cluster_identity_test() {
+        // setup:
+        let cluster = EksCluster {
+            name: "my-cluster".to_string(),
+            arn: "arn:aws:eks:eu-west-3:123456789012:cluster/my-cluster".to_string(),
+            version: "1.30".to_string(),
+            endpoint: "https://abcdef.gr7.eu-west-3.eks.amazonaws.com".to_string(),
+            certificate_authority: Default::default(),
+        };
+        let endpoint = |url: &str| ClusterEndpoint {
+            url: url.to_string(),
+            root_certs: vec![],
+        };
+
+        // execute:
+        let matching = check_cluster_identity(
+            &FakeClusterApi {
+                cluster: Some(cluster.clone()),
+            },
+            &endpoint("https://abcdef.gr7.eu-west-3.eks.amazonaws.com"),
+            true,
+        )
+        .await;
+        let mismatching = check_cluster_identity(
+            &FakeClusterApi {
+                cluster: Some(cluster),
+            },
+            &endpoint("https://ghijkl.gr7.eu-west-3.eks.amazonaws.com"),
+            false,
+        )
+        .await;
+        let undescribable = check_cluster_identity(
+            &FakeClusterApi { cluster: None },
+            &endpoint("https://abcdef.gr7.eu-west-3.eks.amazonaws.com"),
+            false,
+        )
+        .await;
+
+        // verify:
+        assert!(matching.is_ok());
+        assert!(matches!(
+            mismatching,
+            Err(Error::ClusterIdentityMismatch { kube_endpoint, .. })
+                if kube_endpoint.as_ref() == "https://ghijkl.gr7.eu-west-3.eks.amazonaws.com"
+        ));
+        assert!(matches!(undescribable, Err(Error::Aws { .. })));
+    }
+
+    #[test]
+    fn reload_args_test() {
+        // setup:
+        let path = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-reloaded-config-{}.yaml",
+            std::process::id()
+        ));
+        let argv = [
+            "iam-eks-user-mapper",
+            "--config",
+            path.to_str().unwrap(),
+            "--refresh-interval-seconds",
+            "15",
+        ];
+        let base = "service_account_name: iam-eks-user-mapper\naws_role_arn: arn:aws:iam::123456789012:role/mapper\naws_default_region: eu-west-3\n";
+
+        struct TestCase<'a> {
+            input: String,
+            expected_valid: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: format!("{base}enable_group_user_sync: true\niam_k8s_groups: [Admins->system:masters]\nkarpenter_role_arn: [arn:aws:iam::123456789012:role/karpenter]\nrefresh_interval_seconds: 30\n"),
+                expected_valid: true,
+                _description: "case 1 - valid config",
+            },
+            TestCase {
+                input: format!("{base}enable_group_user_sync: true\niam_k8s_groups: [Admins]\n"),
+                expected_valid: false,
+                _description: "case 2 - invalid group mapping",
+            },
+            TestCase {
+                input: format!("{base}max_removal_fraction: 2\n"),
+                expected_valid: false,
+                _description: "case 3 - value rejected by its flag parser",
+            },
+            TestCase {
+                input: format!("{base}iam_k8s_group: [Admins->system:masters]\n"),
+                expected_valid: false,
+                _description: "case 4 - unknown key",
+            },
+        ];
+
+        for tc in test_cases {
+            std::fs::write(&path, &tc.input).unwrap();
+
+            // execute:
+            let res = reload_args(argv).and_then(|args| config_from_args(&args));
+
+            // verify:
+            assert_eq!(tc.expected_valid, res.is_ok(), "{}", tc._description);
+            if let Ok(config) = res {
+                // flags still take precedence over the reloaded file
+                assert_eq!(Duration::from_secs(15), config.refresh_interval);
+                assert!(matches!(
+                    config.karpenter_config,
+                    KarpenterRoleConfig::Enabled { .. }
+                ));
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn once_args_test() {
+        // setup:
+        let args = |extra_args: &[&str]| {
+            Args::try_parse_from(
+                [
+                    "iam-eks-user-mapper",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+        };
+
+        // execute & verify:
+        assert!(args(&["--once"]).is_ok_and(|a| a.once));
+        assert!(args(&[]).is_ok_and(|a| !a.once));
+        assert!(args(&["--once", "--refresh-interval-seconds", "30"]).is_err());
+        assert!(args(&["--once", "--watch-config-map"]).is_err());
+        assert!(args(&["--once", "--enable-leader-election"]).is_err());
+    }
+
+    #[test]
+    fn log_args_test() {
+        // setup:
+        let args = |extra_args: &[&str]| {
+            Args::try_parse_from(
+                [
+                    "iam-eks-user-mapper",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+        };
+
+        // execute & verify:
+        assert!(args(&[]).is_ok_and(|a| !a.quiet && !a.no_ansi));
+        assert!(args(&["-q", "--no-ansi"]).is_ok_and(|a| a.quiet && a.no_ansi));
+        assert!(args(&["--quiet", "--log-level", "debug"]).is_ok());
+        assert!(args(&["--quiet", "--verbose"]).is_err());
+    }
+
+    #[test]
+    fn group_user_sync_args_test() {
+        // setup:
+        let args = |extra_args: &[&str]| {
+            Args::try_parse_from(
+                [
+                    "iam-eks-user-mapper",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+        };
+
+        // execute & verify:
+        assert!(args(&["--enable-group-user-sync", "-g", "Admins->system:masters"]).is_ok());
+        assert!(args(&["--enable-group-user-sync", "--crd-mode"]).is_ok());
+        assert!(args(&["--crd-mode"]).is_ok());
+        assert_eq!(
+            Err(ConfigurationError::GroupUserSyncWithoutMappings),
+            config_from_args(&args(&["--enable-group-user-sync"]).unwrap()).map(|_| ())
+        );
+        assert!(args(&["-g", "Admins->system:masters"]).is_err());
+    }
+
+    #[test]
+    fn denied_k8s_groups_args_test() {
+        // setup:
+        let config = |extra_args: &[&str]| {
+            let args = Args::try_parse_from(
+                [
+                    "iam-eks-user-mapper",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                    "--enable-sso",
+                    "--iam-sso-role-arn",
+                    "arn:aws:iam::123456789012:role/sso-admin",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+            .unwrap();
+            config_from_args(&args)
+        };
+
+        // execute & verify:
+        // system:masters allowed by default, SSO role getting it
+        assert!(config(&[]).is_ok());
+        assert!(config(&["--allow-system-masters", "true"]).is_ok());
+        assert_eq!(
+            Err(ConfigurationError::DeniedKubernetesGroup {
+                group_name: Arc::from("system:masters"),
+                mapping: Arc::from("iam_sso_role_arn `arn:aws:iam::123456789012:role/sso-admin`"),
+            }),
+            config(&["--allow-system-masters", "false"]).map(|_| ())
+        );
+        assert_eq!(
+            Ok(BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("cluster-admins"),
+                KubernetesGroupName::new("view"),
+            ])),
+            config(&["--denied-k8s-groups", "cluster-admins,view"]).map(|c| c.denied_groups)
+        );
+    }
+
+    #[test]
+    fn removals_only_args_test() {
+        // setup:
+        let config = |extra_args: &[&str]| {
+            let args = Args::try_parse_from(
+                [
+                    "iam-eks-user-mapper",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                    "--removals-only",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+            .unwrap();
+            config_from_args(&args).map(|_| ())
+        };
+
+        // execute & verify:
+        assert_eq!(Ok(()), config(&[]));
+        assert_eq!(Ok(()), config(&["--auth-backend", "iamidentitymapping"]));
+        assert_eq!(
+            Err(ConfigurationError::IncompatibleArguments {
+                argument: Arc::from("removals_only"),
+                other_argument: Arc::from("auth_backend"),
+            }),
+            config(&[
+                "--auth-backend",
+                "access-entries",
+                "--eks-cluster-name",
+                "my-cluster"
+            ])
+        );
+    }
+
+    #[test]
+    fn redacted_config_test() {
+        // setup:
+        let args = Args::try_parse_from([
+            "iam-eks-user-mapper",
+            "--service-account-name",
+            "iam-eks-user-mapper",
+            "--aws-role-arn",
+            "arn:aws:iam::123456789012:role/mapper",
+            "--aws-default-region",
+            "eu-west-3",
+            "--protected-arns",
+            "arn:aws:iam::123456789012:role/break-glass, ",
+            "--slack-webhook-url",
+            "https://hooks.slack.com/services/T000/B000/secret-token",
+            "--show-config",
+            "--output",
+            "json",
+        ])
+        .unwrap();
+        let config = config_from_args(&args).unwrap();
+
+        // execute:
+        let redacted = redacted_config(&args, &config);
+        let json = serde_json::to_string(&redacted).unwrap();
+
+        // verify:
+        assert_eq!(ShowConfigOutput::Json, args.output);
+        assert_eq!(
+            vec!["arn:aws:iam::123456789012:role/break-glass".to_string()],
+            redacted.protected_arns
+        );
+        assert_eq!(None, redacted.notify_webhook_url);
+        assert_eq!(Some(REDACTED.to_string()), redacted.slack_webhook_url);
+        assert!(!json.contains("secret-token"));
+    }
+
+    #[test]
+    fn completions_test() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            // execute:
+            let completions = completions(shell);
+
+            // verify:
+            for expected in [
+                "aws-role-arn",
+                "iam-k8s-groups",
+                "refresh-interval",
+                "existing-aws-auth",
+                "migrate-to-access-entries",
+            ] {
+                assert!(
+                    completions.contains(expected),
+                    "{shell} completions should contain `{expected}`"
+                );
+            }
+        }
+        // file paths and enum values are completed
+        let zsh_completions = completions(Shell::Zsh);
+        assert!(zsh_completions.contains(":CONFIG:_files"));
+        assert!(zsh_completions.contains(":AUTH_BACKEND:((configmap"));
+        let fish_completions = completions(Shell::Fish);
+        assert!(fish_completions
+            .lines()
+            .any(|line| line.contains("-l env-file") && line.ends_with("-r -F")));
+        assert!(fish_completions
+            .lines()
+            .any(|line| line.contains("-l log-format") && line.contains("json")));
+    }
+
+    #[test]
+    fn manpage_test() {
+        // execute:
+        let manpage = manpage();
+
+        // verify:
+        assert!(manpage.contains(".TH iam-eks-user-mapper 1"));
+        for expected in [
+            r"\-\-aws\-role\-arn",
+            r"\-\-refresh\-interval",
+            r"iam\-eks\-user\-mapper\-completions(1)",
+            r"iam\-eks\-user\-mapper\-manpage(1)",
+        ] {
+            assert!(
+                manpage.contains(expected),
+                "man page should contain `{expected}`"
+            );
+        }
+    }
+
+    #[test]
+    fn subcommands_args_test() {
+        // setup:
+        let args = |args: &[&str]| {
+            Args::try_parse_from(std::iter::once(&"iam-eks-user-mapper").chain(args))
+        };
+        let global_args = [
+            "--service-account-name",
+            "iam-eks-user-mapper",
+            "--aws-role-arn",
+            "arn:aws:iam::123456789012:role/mapper",
+            "--aws-default-region",
+            "eu-west-3",
+        ];
+        let with_global_args =
+            |subcommand_args: &[&str]| args(&[&global_args[..], subcommand_args].concat());
+
+        // execute & verify:
+        // no subcommand syncs
+        assert!(with_global_args(&[]).is_ok_and(|a| a.command.is_none()));
+        assert!(with_global_args(&["sync"]).is_ok_and(|a| matches!(a.command, Some(Command::Sync))));
+        assert!(with_global_args(&["validate"]).is_ok_and(
+            |a| matches!(a.command, Some(Command::Validate { skip_check }) if skip_check.is_empty())
+        ));
+        assert!(
+            with_global_args(&["validate", "--skip-check", "iam-roles,config-map-rbac"]).is_ok_and(
+                |a| matches!(
+                    a.command,
+                    Some(Command::Validate { skip_check })
+                        if skip_check == vec![PreflightCheck::IamRoles, PreflightCheck::ConfigMapRbac]
+                )
+            )
+        );
+        assert!(with_global_args(&["validate", "--skip-check", "config"]).is_err());
+        assert!(with_global_args(&["cleanup"])
+            .is_ok_and(|a| matches!(a.command, Some(Command::Cleanup { yes: false }))));
+        assert!(with_global_args(&["cleanup", "--yes"])
+            .is_ok_and(|a| matches!(a.command, Some(Command::Cleanup { yes: true }))));
+        assert!(with_global_args(&["cleanup", "--dry-run"])
+            .is_ok_and(|a| matches!(a.command, Some(Command::Cleanup { .. })) && a.dry_run));
+        assert!(args(&["render", "--existing-aws-auth", "aws-auth.yaml"]).is_ok_and(|a| matches!(
+            a.command,
+            Some(Command::Render { existing_aws_auth: Some(path) }) if path.to_str() == Some("aws-auth.yaml")
+        )));
+        assert!(args(&["restore", "--list"]).is_ok_and(|a| matches!(
+            a.command,
+            Some(Command::Restore {
+                backup: None,
+                list: true
+            })
+        )));
+        assert!(
+            args(&["migrate-to-access-entries", "--dry-run"]).is_ok_and(|a| matches!(
+                a.command,
+                Some(Command::MigrateToAccessEntries {
+                    include_unmanaged: false,
+                    delete_source: false,
+                    dry_run: true
+                })
+            ))
+        );
+        assert!(
+            args(&["healthcheck", "--max-age-seconds", "60"]).is_ok_and(|a| matches!(
+                a.command,
+                Some(Command::Healthcheck {
+                    max_age_seconds: 60
+                })
+            ))
+        );
+        assert!(args(&["completions", "zsh"])
+            .is_ok_and(|a| matches!(a.command, Some(Command::Completions { shell: Shell::Zsh }))));
+        assert!(args(&["completions"]).is_err());
+        assert!(args(&["manpage"]).is_ok_and(|a| matches!(a.command, Some(Command::Manpage))));
+        assert!(args(&["unknown"]).is_err());
+        assert!(args(&["validate", "--existing-aws-auth", "aws-auth.yaml"]).is_err());
+
+        // global args can be set after the subcommand as well
+        assert!(
+            args(&[&["validate"], &global_args[..]].concat()).is_ok_and(|a| {
+                matches!(a.command, Some(Command::Validate { .. }))
+                    && a.aws_default_region.as_deref() == Some("eu-west-3")
+                    && a.service_account_name.as_deref() == Some("iam-eks-user-mapper")
+            })
+        );
+        assert!(args(&[
+            "render",
+            "--aws-default-region",
+            "eu-west-3",
+            "-g",
+            "Admins->system:masters",
+            "--enable-group-user-sync"
+        ])
+        .is_ok_and(|a| a.aws_default_region.as_deref() == Some("eu-west-3")));
+
+        // arguments required to sync, checked once parsed
+        assert_eq!(
+            Err(ConfigurationError::MissingArgumentForCommand {
+                argument: Arc::from("aws_default_region"),
+                command: Arc::from("validate"),
+            }),
+            config_from_args(
+                &args(&[
+                    "validate",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                ])
+                .unwrap()
+            )
+            .map(|_| ())
+        );
+        assert_eq!(
+            Err(ConfigurationError::MissingArgumentForCommand {
+                argument: Arc::from("service_account_name"),
+                command: Arc::from("sync"),
+            }),
+            config_from_args(
+                &args(&[
+                    "--aws-role-arn",
+                    "arn:aws:iam::123456789012:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                ])
+                .unwrap()
+            )
+            .map(|_| ())
+        );
+        assert!(config_from_args(&with_global_args(&["sync"]).unwrap()).is_ok());
+        assert_eq!(
+            Err(ConfigurationError::NotifyOnFailureWithoutSink),
+            config_from_args(&with_global_args(&["sync", "--notify-on-failure"]).unwrap())
+                .map(|_| ())
+        );
+        assert!(config_from_args(
+            &with_global_args(&[
+                "sync",
+                "--notify-on-failure",
+                "--slack-webhook-url",
+                "https://hooks.slack.com/services/T/B/X"
+            ])
+            .unwrap()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn preflight_targets_test() {
+        // setup:
+        let args = Args::try_parse_from([
+            "iam-eks-user-mapper",
+            "--service-account-name=iam-eks-user-mapper",
+            "--aws-role-arn=arn:aws:iam::123456789012:role/iam-eks-user-mapper",
+            "--aws-default-region=eu-west-3",
+            "--enable-group-user-sync",
+            "--iam-k8s-groups=Admins->system:masters,Devops->devops",
+            "--enable-sso",
+            "--iam-sso-role-arn=arn:aws:iam::123456789012:role/sso-admin",
+            "--karpenter-role-arn=arn:aws:iam::123456789012:role/karpenter",
+        ])
+        .unwrap();
+        let settings = SyncSettings::new(&config_from_args(&args).unwrap());
+
+        // execute:
+        let targets = preflight_targets(&settings, true);
+
+        // verify:
+        assert_eq!(
+            BTreeSet::from_iter(vec![IamGroup::new("Admins"), IamGroup::new("Devops")]),
+            targets.iam_groups
+        );
+        assert_eq!(
+            vec![
+                (
+                    "SSO role",
+                    IamArn::new("arn:aws:iam::123456789012:role/sso-admin")
+                ),
+                (
+                    "Karpenter role",
+                    IamArn::new("arn:aws:iam::123456789012:role/karpenter")
+                ),
+            ],
+            targets.roles
+        );
+        assert_eq!(
+            ("kube-system", "aws-auth", true),
+            (
+                targets.config_map_namespace.as_str(),
+                targets.config_map_name.as_str(),
+                targets.create_if_missing
+            )
+        );
+    }
+
+    #[test]
+    fn report_checks_test() {
+        // setup:
+        let passed = CheckResult::new("kubernetes", Ok("API server reached".to_string()));
+        let failed = CheckResult::new("iam-roles", Err("SSO role not found".to_string()));
+
+        // execute & verify:
+        assert!(report_checks(std::slice::from_ref(&passed)).is_ok());
+        assert!(matches!(
+            report_checks(&[passed, failed.clone(), failed]),
+            Err(Error::ValidationFailed { failed_checks: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn sync_loop_exit_test() {
+        // setup:
+        let panicking = async { panic!("boom") };
+
+        // execute:
+        let res: Result<Result<Infallible, Error>, _> =
+            AssertUnwindSafe(panicking).catch_unwind().await;
+
+        // verify:
+        assert!(matches!(
+            sync_loop_exit(res),
+            Error::SyncLoopAborted { raw_message } if raw_message.contains("boom")
+        ));
+        assert!(matches!(
+            sync_loop_exit(Ok(Err(Error::ValidationFailed { failed_checks: 1 }))),
+            Error::ValidationFailed { failed_checks: 1 }
+        ));
+    }
+
+    #[test]
+    fn parse_refresh_interval_test() {
+        struct TestCase<'a> {
+            input: &'a str,
+            expected: Option<Duration>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "30s",
+                expected: Some(Duration::from_secs(30)),
+                _description: "seconds",
+            },
+            TestCase {
+                input: "5m",
+                expected: Some(Duration::from_secs(300)),
+                _description: "minutes",
+            },
+            TestCase {
+                input: "1h",
+                expected: Some(Duration::from_secs(3600)),
+                _description: "hours",
+            },
+            TestCase {
+                input: "1d",
+                expected: Some(Duration::from_secs(86400)),
+                _description: "days",
+            },
+            TestCase {
+                input: "1m 30s",
+                expected: Some(Duration::from_secs(90)),
+                _description: "several units",
+            },
+            TestCase {
+                input: "5s",
+                expected: Some(Duration::from_secs(5)),
+                _description: "minimum interval",
+            },
+            TestCase {
+                input: "4s",
+                expected: None,
+                _description: "under minimum interval",
+            },
+            TestCase {
+                input: "500ms",
+                expected: None,
+                _description: "sub-second interval",
+            },
+            TestCase {
+                input: "60",
+                expected: None,
+                _description: "missing unit",
+            },
+            TestCase {
+                input: "soon",
+                expected: None,
+                _description: "not a duration",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = parse_refresh_interval(tc.input);
+
+            // verify:
+            assert_eq!(tc.expected, res.ok(), "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn refresh_interval_args_test() {
+        // setup:
+        let args = |extra_args: &[&str]| {
+            Args::try_parse_from(
+                [
+                    "iam-eks-user-mapper",
+                    "--service-account-name",
+                    "iam-eks-user-mapper",
+                    "--aws-role-arn",
+                    "arn:aws:iam::12345678910:role/mapper",
+                    "--aws-default-region",
+                    "eu-west-3",
+                ]
+                .iter()
+                .chain(extra_args),
+            )
+        };
+
+        // execute & verify:
+        assert_eq!(
+            Duration::from_secs(60),
+            args(&[]).unwrap().refresh_interval()
+        );
+        assert_eq!(
+            Duration::from_secs(300),
+            args(&["--refresh-interval", "5m"])
+                .unwrap()
+                .refresh_interval()
+        );
+        // deprecated flag still accepted
+        assert_eq!(
+            Duration::from_secs(120),
+            args(&["--refresh-interval-seconds", "120"])
+                .unwrap()
+                .refresh_interval()
+        );
+        assert!(args(&["--refresh-interval", "1s"]).is_err());
+        assert!(args(&["--refresh-interval-seconds", "0"]).is_err());
+        assert!(args(&["--refresh-interval-seconds", "4"]).is_err());
+        assert!(args(&["--refresh-interval-seconds", "soon"]).is_err());
+        assert!(args(&[
+            "--refresh-interval",
+            "5m",
+            "--refresh-interval-seconds",
+            "120"
+        ])
+        .is_err());
+    }
+
+    /// Metadata of an event logged by `target` at `level`, `tracing` macros requiring constant ones.
+    fn event_metadata(target: &str, level: Level) -> Metadata<'_> {
+        struct TestCallsite;
+        impl Callsite for TestCallsite {
+            fn set_interest(&self, _: Interest) {}
+            fn metadata(&self) -> &Metadata<'_> {
+                unreachable!("not registered")
+            }
+        }
+        static CALLSITE: TestCallsite = TestCallsite;
+
+        Metadata::new(
+            "event",
+            target,
+            level,
+            None,
+            None,
+            None,
+            FieldSet::new(&[], Identifier(&CALLSITE)),
+            Kind::EVENT,
+        )
+    }
+
+    #[test]
+    fn log_filter_test() {
+        struct TestCase<'a> {
+            log_level: LogLevel,
+            verbose: bool,
+            quiet: bool,
+            rust_log: Option<&'a str>,
+            expected_enabled: Vec<(&'a str, Level)>,
+            expected_disabled: Vec<(&'a str, Level)>,
+            _description: &'a str,
+        }
+
+        let own = "iam_eks_user_mapper::kubernetes";
+        let test_cases = vec![
+            TestCase {
+                log_level: LogLevel::Info,
+                verbose: false,
+                quiet: false,
+                rust_log: None,
+                expected_enabled: vec![(own, Level::INFO), ("aws_config", Level::INFO)],
+                expected_disabled: vec![(own, Level::DEBUG), ("aws_config", Level::DEBUG)],
+                _description: "case 1 - default, sync logs shown without RUST_LOG",
+            },
+            TestCase {
+                log_level: LogLevel::Warn,
+                verbose: false,
+                quiet: false,
+                rust_log: Some(""),
+                expected_enabled: vec![(own, Level::WARN)],
+                expected_disabled: vec![(own, Level::INFO)],
+                _description: "case 2 - warn level, empty RUST_LOG ignored",
+            },
+            TestCase {
+                log_level: LogLevel::Info,
+                verbose: true,
+                quiet: false,
+                rust_log: None,
+                expected_enabled: vec![(own, Level::DEBUG), ("aws_config", Level::INFO)],
+                expected_disabled: vec![(own, Level::TRACE), ("aws_config", Level::DEBUG)],
+                _description: "case 3 - verbose, AWS SDK kept at info",
+            },
+            TestCase {
+                log_level: LogLevel::Trace,
+                verbose: true,
+                quiet: false,
+                rust_log: None,
+                expected_enabled: vec![(own, Level::TRACE), ("aws_config", Level::TRACE)],
+                expected_disabled: vec![],
+                _description: "case 4 - verbose doesn't lower a more verbose level",
+            },
+            TestCase {
+                log_level: LogLevel::Info,
+                verbose: false,
+                quiet: false,
+                rust_log: Some("kube=debug"),
+                expected_enabled: vec![("kube::client", Level::DEBUG), (own, Level::INFO)],
+                expected_disabled: vec![(own, Level::DEBUG), ("aws_config", Level::DEBUG)],
+                _description: "case 5 - RUST_LOG adding a per module directive",
+            },
+            TestCase {
+                log_level: LogLevel::Debug,
+                verbose: true,
+                quiet: false,
+                rust_log: Some("error,iam_eks_user_mapper=warn"),
+                expected_enabled: vec![(own, Level::WARN), ("aws_config", Level::ERROR)],
+                expected_disabled: vec![(own, Level::INFO), ("aws_config", Level::WARN)],
+                _description: "case 6 - RUST_LOG overriding both log level and verbose",
+            },
+            TestCase {
+                log_level: LogLevel::Info,
+                verbose: false,
+                quiet: true,
+                rust_log: None,
+                expected_enabled: vec![(own, Level::WARN), (STARTUP_LOG_TARGET, Level::INFO)],
+                expected_disabled: vec![(own, Level::INFO), ("aws_config", Level::INFO)],
+                _description: "case 7 - quiet, startup summary still logged",
+            },
+            TestCase {
+                log_level: LogLevel::Error,
+                verbose: false,
+                quiet: true,
+                rust_log: None,
+                expected_enabled: vec![(own, Level::ERROR), (STARTUP_LOG_TARGET, Level::INFO)],
+                expected_disabled: vec![(own, Level::WARN), ("aws_config", Level::WARN)],
+                _description: "case 8 - quiet doesn't raise a less verbose level",
+            },
+            TestCase {
+                log_level: LogLevel::Debug,
+                verbose: false,
+                quiet: true,
+                rust_log: Some("iam_eks_user_mapper=debug"),
+                expected_enabled: vec![(own, Level::DEBUG), ("aws_config", Level::WARN)],
+                expected_disabled: vec![(own, Level::TRACE), ("aws_config", Level::INFO)],
+                _description: "case 9 - RUST_LOG overriding quiet",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let filter = log_filter(tc.log_level, tc.verbose, tc.quiet, tc.rust_log);
+            let subscriber = tracing_subscriber::registry().with(filter);
+
+            // execute & verify:
+            for (target, level) in tc.expected_enabled {
+                assert!(
+                    subscriber.enabled(&event_metadata(target, level)),
+                    "{}: `{target}` {level} should be enabled",
+                    tc._description
+                );
+            }
+            for (target, level) in tc.expected_disabled {
+                assert!(
+                    !subscriber.enabled(&event_metadata(target, level)),
+                    "{}: `{target}` {level} should be disabled",
+                    tc._description
+                );
+            }
+        }
+    }
+
+    /// Logs written so far, shared with the writer of the layer under test.
+    #[derive(Clone, Default)]
+    struct LogsBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogsBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fmt_layer_test() {
+        struct TestCase<'a> {
+            log_format: LogFormat,
+            ansi: bool,
+            expected_content: Vec<&'a str>,
+            expected_coloured: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                log_format: LogFormat::Text,
+                ansi: false,
+                expected_content: vec![
+                    "sync{cluster: \"prod\"}: iam_eks_user_mapper::cli::tests: message: Synced, added: 2",
+                ],
+                expected_coloured: false,
+                _description: "case 1 - text, fields written as `field: value`",
+            },
+            TestCase {
+                log_format: LogFormat::Text,
+                ansi: true,
+                expected_content: vec!["\x1b[32m INFO\x1b[0m", "message: Synced, added: 2"],
+                expected_coloured: true,
+                _description: "case 2 - coloured text",
+            },
+            TestCase {
+                log_format: LogFormat::Json,
+                ansi: true,
+                expected_content: vec![
+                    r#""level":"INFO","message":"Synced","added":2,"target":"iam_eks_user_mapper::cli::tests","span":{"cluster":"prod","name":"sync"},"spans":[{"cluster":"prod","name":"sync"}]}"#,
+                ],
+                expected_coloured: false,
+                _description: "case 3 - json, fields flattened along the message and never coloured",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let logs = LogsBuffer::default();
+            let writer = logs.clone();
+            let subscriber = tracing_subscriber::registry().with(fmt_layer(
+                tc.log_format,
+                tc.ansi,
+                BoxMakeWriter::new(move || writer.clone()),
+            ));
+
+            // execute:
+            tracing::subscriber::with_default(subscriber, || {
+                let _span = tracing::info_span!("sync", cluster = "prod").entered();
+                tracing::info!(added = 2, "Synced");
+            });
+
+            // verify:
+            let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+            assert_eq!(1, logs.lines().count(), "{}: {logs}", tc._description);
+            for expected in tc.expected_content {
+                assert!(
+                    logs.contains(expected),
+                    "{}: `{logs}` should contain `{expected}`",
+                    tc._description
+                );
+            }
+            assert_eq!(
+                tc.expected_coloured,
+                logs.contains('\x1b'),
+                "{}: `{logs}`",
+                tc._description
+            );
+            if tc.log_format == LogFormat::Json {
+                let json: serde_json::Value = serde_json::from_str(&logs).unwrap();
+                // RFC 3339 timestamp, e.q: for Loki to parse it
+                assert!(
+                    humantime::parse_rfc3339_weak(json["timestamp"].as_str().unwrap()).is_ok(),
+                    "{}: `{logs}`",
+                    tc._description
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn log_ansi_test() {
+        struct TestCase<'a> {
+            log_format: LogFormat,
+            no_ansi: bool,
+            is_terminal: bool,
+            expected: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                log_format: LogFormat::Text,
+                no_ansi: false,
+                is_terminal: true,
+                expected: true,
+                _description: "case 1 - text written to a terminal",
+            },
+            TestCase {
+                log_format: LogFormat::Text,
+                no_ansi: false,
+                is_terminal: false,
+                expected: false,
+                _description: "case 2 - text written to a pipe, e.q: a log aggregator",
+            },
+            TestCase {
+                log_format: LogFormat::Text,
+                no_ansi: true,
+                is_terminal: true,
+                expected: false,
+                _description: "case 3 - colours disabled on a terminal",
+            },
+            TestCase {
+                log_format: LogFormat::Json,
+                no_ansi: false,
+                is_terminal: true,
+                expected: false,
+                _description: "case 4 - json never coloured",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute & verify:
+            assert_eq!(
+                tc.expected,
+                log_ansi(tc.log_format, tc.no_ansi, tc.is_terminal),
+                "{}",
+                tc._description
+            );
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use crate::cli::Args;
+use crate::config::ConfigurationError;
+use crate::config_file::ConfigFile;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command, CommandFactory, FromArgMatches};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Layer an argument value is read from, in increasing precedence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum ArgSource {
+    /// Nothing set, argument default being used.
+    Default,
+    /// `--config` file.
+    ConfigFile,
+    /// Env vars, `--env-file` entries included.
+    Environment,
+    /// Flags.
+    CommandLine,
+}
+
+/// Reads arguments from the `--config` file, then env vars, then flags, each layer taking precedence over the previous one.
+///
+/// `--env-file` entries are read as env vars, the process ones taking precedence over them.
+#[derive(Clone, Debug, Default)]
+pub struct ArgsLoader {
+    config_file: ConfigFile,
+    config_path: Option<PathBuf>,
+    /// Entries of the env file, by env var name.
+    env_file: BTreeMap<String, String>,
+}
+
+impl ArgsLoader {
+    #[cfg(test)]
+    pub fn new(config_file: ConfigFile, env_file: BTreeMap<String, String>) -> ArgsLoader {
+        ArgsLoader {
+            config_file,
+            config_path: None,
+            env_file,
+        }
+    }
+
+    /// Reads the `--config` and `--env-file` files set through `argv` or env vars, if any.
+    pub fn from_argv<I, T>(argv: I) -> Result<ArgsLoader, ConfigurationError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        // files have to be known before parsing arguments they can hold required ones of
+        let matches = Args::command()
+            .ignore_errors(true)
+            .try_get_matches_from(argv)
+            .ok();
+        let path = |id: &str| {
+            matches
+                .as_ref()
+                .and_then(|m| m.get_one::<PathBuf>(id).cloned())
+        };
+
+        let env_file = match path("env_file") {
+            Some(env_file_path) => read_env_file(&env_file_path)?,
+            None => BTreeMap::new(),
+        };
+        // the config file can be set in the env file as well
+        let config_path = path("config").or_else(|| {
+            env_name(&Args::command(), "config")
+                .and_then(|name| env_file.get(&name))
+                .map(PathBuf::from)
+        });
+        let config_file = match &config_path {
+            Some(config_path) => ConfigFile::load(config_path)?,
+            None => ConfigFile::default(),
+        };
+
+        Ok(ArgsLoader {
+            config_file,
+            config_path,
+            env_file,
+        })
+    }
+
+    /// Config file the arguments are read from, if any.
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// Parses `argv` and env vars over the files values.
+    pub fn load<I, T>(&self, argv: I) -> Result<Args, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let layers = self.layers();
+        let mut command = Args::command();
+        // required arguments are satisfied by the files
+        if layers
+            .keys()
+            .any(|id| id == "aws_role_arn" || id == "aws_access_key_id")
+        {
+            command = command.mut_group("aws_credentials", |g| g.required(false));
+        }
+        for (id, (_, values)) in &layers {
+            command = command.mut_arg(id, |a| a.default_values(values).required(false));
+        }
+
+        let matches = command.try_get_matches_from(argv)?;
+        let mut args = Args::from_arg_matches(&matches)?;
+        let source = |id: &str| Self::source(&matches, &layers, id);
+
+        // credentials are set as a whole: the mode of the highest layer replaces the other one
+        let keys_source = source("aws_access_key_id").max(source("aws_secret_access_key"));
+        match source("aws_role_arn").cmp(&keys_source) {
+            Ordering::Greater => {
+                args.aws_access_key_id = None;
+                args.aws_secret_access_key = None;
+            }
+            Ordering::Less => args.aws_role_arn = None,
+            Ordering::Equal => {}
+        }
+
+        Ok(args)
+    }
+
+    /// Layer the value of argument `id` comes from, files values being set as arguments defaults.
+    fn source(
+        matches: &ArgMatches,
+        layers: &BTreeMap<String, (ArgSource, Vec<String>)>,
+        id: &str,
+    ) -> ArgSource {
+        match matches.value_source(id) {
+            Some(ValueSource::CommandLine) => ArgSource::CommandLine,
+            Some(ValueSource::EnvVariable) => ArgSource::Environment,
+            _ => layers
+                .get(id)
+                .map(|(source, _)| *source)
+                .unwrap_or(ArgSource::Default),
+        }
+    }
+
+    /// Files values by argument id, env file entries replacing config file ones.
+    fn layers(&self) -> BTreeMap<String, (ArgSource, Vec<String>)> {
+        let mut layers: BTreeMap<String, (ArgSource, Vec<String>)> = self
+            .config_file
+            .values()
+            .into_iter()
+            .map(|(id, values)| (id.to_string(), (ArgSource::ConfigFile, values)))
+            .collect();
+
+        let command = Args::command();
+        for arg in command.get_arguments() {
+            let Some(value) = arg
+                .get_env()
+                .and_then(|name| self.env_file.get(name.to_string_lossy().as_ref()))
+            else {
+                continue;
+            };
+            // split as clap splits env vars
+            let values = match arg.get_value_delimiter() {
+                Some(delimiter) => value.split(delimiter).map(str::to_string).collect(),
+                None => vec![value.clone()],
+            };
+            layers.insert(arg.get_id().to_string(), (ArgSource::Environment, values));
+        }
+
+        layers
+    }
+}
+
+/// Env var argument `id` is read from, if any.
+fn env_name(command: &Command, id: &str) -> Option<String> {
+    command
+        .get_arguments()
+        .find(|a| a.get_id() == id)
+        .and_then(|a| a.get_env())
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+/// Reads `KEY=VALUE` lines of the env file at `path`, blank lines and `#` comments being skipped.
+pub fn read_env_file(path: &Path) -> Result<BTreeMap<String, String>, ConfigurationError> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| ConfigurationError::CannotReadEnvFile {
+            path: Arc::from(path.to_string_lossy().as_ref()),
+            raw_message: Arc::from(e.to_string()),
+        })?;
+
+    parse_env_file(&content).map_err(|(line, raw_message)| ConfigurationError::InvalidEnvFile {
+        path: Arc::from(path.to_string_lossy().as_ref()),
+        line,
+        raw_message: Arc::from(raw_message),
+    })
+}
+
+/// Entries of env file `content`, or the number of the invalid line along the reason.
+fn parse_env_file(content: &str) -> Result<BTreeMap<String, String>, (usize, String)> {
+    let command = Args::command();
+    let mut entries = BTreeMap::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            return Err((number + 1, "should be `KEY=VALUE`".to_string()));
+        };
+        let name = name.trim();
+        // entries are only read as arguments env vars, others would silently have no effect
+        if !command
+            .get_arguments()
+            .any(|a| a.get_env().is_some_and(|env| env == name))
+        {
+            return Err((number + 1, format!("unknown env var `{name}`")));
+        }
+        let value = value.trim();
+        let value = match (value.chars().next(), value.chars().last()) {
+            (Some(first @ ('"' | '\'')), Some(last)) if value.len() > 1 && first == last => {
+                &value[1..value.len() - 1]
+            }
+            _ => value,
+        };
+        entries.insert(name.to_string(), value.to_string());
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::loader::{parse_env_file, ArgsLoader};
+    use crate::config_file::ConfigFile;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    const REQUIRED_ARGS: &str =
+        "service_account_name: iam-eks-user-mapper\naws_default_region: eu-west-3\n";
+
+    fn loader(config_file: &str, env_file: &str) -> ArgsLoader {
+        ArgsLoader::new(
+            ConfigFile::parse(&format!("{REQUIRED_ARGS}{config_file}")).unwrap(),
+            parse_env_file(env_file).unwrap(),
+        )
+    }
+
+    fn argv<'a>(flags: &'a [&'a str]) -> impl Iterator<Item = &'a str> + Clone {
+        std::iter::once("iam-eks-user-mapper").chain(flags.iter().copied())
+    }
+
+    #[test]
+    fn args_loader_interval_precedence_test() {
+        // setup:
+        struct TestCase<'a> {
+            config_file: &'a str,
+            env_file: &'a str,
+            flags: &'a [&'a str],
+            expected: Duration,
+            _description: &'a str,
+        }
+
+        let credentials = "aws_role_arn: arn:aws:iam::123456789012:role/mapper\n";
+        let with_interval = format!("{credentials}refresh_interval: 2m\n");
+        let test_cases = vec![
+            TestCase {
+                config_file: credentials,
+                env_file: "",
+                flags: &[],
+                expected: Duration::from_secs(60),
+                _description: "case 1 - default",
+            },
+            TestCase {
+                config_file: &with_interval,
+                env_file: "",
+                flags: &[],
+                expected: Duration::from_secs(120),
+                _description: "case 2 - config file over default",
+            },
+            TestCase {
+                config_file: &with_interval,
+                env_file: "REFRESH_INTERVAL=3m\n",
+                flags: &[],
+                expected: Duration::from_secs(180),
+                _description: "case 3 - environment over config file",
+            },
+            TestCase {
+                config_file: &with_interval,
+                env_file: "REFRESH_INTERVAL=3m\n",
+                flags: &["--refresh-interval", "4m"],
+                expected: Duration::from_secs(240),
+                _description: "case 4 - flag over environment",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let args = loader(tc.config_file, tc.env_file)
+                .load(argv(tc.flags))
+                .unwrap();
+
+            // verify:
+            assert_eq!(tc.expected, args.refresh_interval(), "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn args_loader_mappings_precedence_test() {
+        // setup:
+        let loader = loader(
+            "aws_role_arn: arn:aws:iam::123456789012:role/mapper\niam_k8s_groups: [FromFile->view]\nmap_users: [arn:aws:iam::123456789012:user/file|username=file|groups=view]\n",
+            "IAM_K8S_GROUPS=FromEnv->view,Admins->system:masters\n",
+        );
+
+        // execute:
+        let from_layers = loader.load(argv(&[])).unwrap();
+        let from_flags = loader
+            .load(argv(&[
+                "--enable-group-user-sync",
+                "--iam-k8s-groups",
+                "FromFlag->edit",
+            ]))
+            .unwrap();
+
+        // verify:
+        assert_eq!(
+            vec![
+                "FromEnv->view".to_string(),
+                "Admins->system:masters".to_string()
+            ],
+            from_layers.iam_k8s_groups,
+            "environment replaces config file list as a whole"
+        );
+        assert_eq!(
+            vec!["arn:aws:iam::123456789012:user/file|username=file|groups=view".to_string()],
+            from_layers.map_users,
+            "config file list kept when not set elsewhere"
+        );
+        assert_eq!(
+            vec!["FromFlag->edit".to_string()],
+            from_flags.iam_k8s_groups
+        );
+    }
+
+    #[test]
+    fn args_loader_credentials_precedence_test() {
+        // setup:
+        struct TestCase<'a> {
+            config_file: &'a str,
+            env_file: &'a str,
+            flags: &'a [&'a str],
+            expected_role_arn: Option<&'a str>,
+            expected_access_key_id: Option<&'a str>,
+            _description: &'a str,
+        }
+
+        let role = "aws_role_arn: arn:aws:iam::123456789012:role/mapper\n";
+        let keys = "aws_access_key_id: file-key\naws_secret_access_key: file-secret\n";
+        let test_cases = vec![
+            TestCase {
+                config_file: role,
+                env_file: "AWS_ACCESS_KEY_ID=env-key\nAWS_SECRET_ACCESS_KEY=env-secret\n",
+                flags: &[],
+                expected_role_arn: None,
+                expected_access_key_id: Some("env-key"),
+                _description: "case 1 - environment keys replace config file role",
+            },
+            TestCase {
+                config_file: keys,
+                env_file: "AWS_ROLE_ARN=arn:aws:iam::123456789012:role/env\n",
+                flags: &[],
+                expected_role_arn: Some("arn:aws:iam::123456789012:role/env"),
+                expected_access_key_id: None,
+                _description: "case 2 - environment role replaces config file keys",
+            },
+            TestCase {
+                config_file: role,
+                env_file: "AWS_ACCESS_KEY_ID=env-key\nAWS_SECRET_ACCESS_KEY=env-secret\n",
+                flags: &["--aws-role-arn", "arn:aws:iam::123456789012:role/flag"],
+                expected_role_arn: Some("arn:aws:iam::123456789012:role/flag"),
+                expected_access_key_id: None,
+                _description: "case 3 - flag role replaces environment keys",
+            },
+            TestCase {
+                config_file: keys,
+                env_file: "AWS_SECRET_ACCESS_KEY=env-secret\n",
+                flags: &[],
+                expected_role_arn: None,
+                expected_access_key_id: Some("file-key"),
+                _description: "case 4 - same mode values merged across layers",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let args = loader(tc.config_file, tc.env_file)
+                .load(argv(tc.flags))
+                .unwrap();
+
+            // verify:
+            assert_eq!(
+                tc.expected_role_arn.map(str::to_string),
+                args.aws_role_arn,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_access_key_id.map(str::to_string),
+                args.aws_access_key_id,
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn parse_env_file_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: &'a str,
+            expected: Result<Vec<(&'a str, &'a str)>, (usize, &'a str)>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "# mapper settings\n\nREFRESH_INTERVAL=30s\nexport LOG_LEVEL=debug\nIAM_SSO_ROLE_ARN = \"arn:aws:iam::123456789012:role/sso\"\nUSERNAME_TEMPLATE='{name}=x'\n",
+                expected: Ok(vec![
+                    ("IAM_SSO_ROLE_ARN", "arn:aws:iam::123456789012:role/sso"),
+                    ("LOG_LEVEL", "debug"),
+                    ("REFRESH_INTERVAL", "30s"),
+                    ("USERNAME_TEMPLATE", "{name}=x"),
+                ]),
+                _description: "case 1 - entries, comments, export prefix and quotes",
+            },
+            TestCase {
+                input: "REFRESH_INTERVAL=30s\nLOG_LEVEL\n",
+                expected: Err((2, "should be `KEY=VALUE`")),
+                _description: "case 2 - missing value",
+            },
+            TestCase {
+                input: "AWS_PROFILE=default\n",
+                expected: Err((1, "unknown env var `AWS_PROFILE`")),
+                _description: "case 3 - not an argument env var",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = parse_env_file(tc.input);
+
+            // verify:
+            assert_eq!(
+                tc.expected
+                    .map(|entries| entries
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect::<BTreeMap<_, _>>())
+                    .map_err(|(line, message)| (line, message.to_string())),
+                res,
+                "{}",
+                tc._description
+            );
+        }
+    }
+}
@@ -0,0 +1,811 @@
+use crate::config::ConfigurationError;
+use serde::de::{self, Visitor};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time;
+use tracing::{info, warn};
+
+/// Arguments read from a YAML config file given with `--config`, keys being the arguments names.
+///
+/// File values are the lowest layer arguments are read from, overridden by env vars and flags, see `ArgsLoader`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub service_account_name: Option<String>,
+    pub aws_role_arn: Option<String>,
+    pub aws_access_key_id: Option<String>,
+    pub aws_secret_access_key: Option<String>,
+    pub aws_default_region: Option<String>,
+    pub refresh_interval: Option<String>,
+    pub refresh_interval_seconds: Option<u64>,
+    pub refresh_interval_jitter_seconds: Option<u64>,
+    pub refresh_interval_jitter_seed: Option<u64>,
+    pub missed_tick_behavior: Option<String>,
+    pub once: Option<bool>,
+    pub show_config: Option<bool>,
+    pub output: Option<String>,
+    pub max_backoff_seconds: Option<u64>,
+    pub max_consecutive_failures: Option<u32>,
+    pub no_fail_fast: Option<bool>,
+    pub sync_timeout_seconds: Option<u64>,
+    pub health_listen_addr: Option<String>,
+    pub readiness_max_staleness: Option<String>,
+    pub enable_sync_endpoint: Option<bool>,
+    pub heartbeat_file: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub enable_group_user_sync: Option<bool>,
+    pub iam_k8s_groups: Option<Vec<String>>,
+    pub crd_mode: Option<bool>,
+    pub strict_group_validation: Option<bool>,
+    pub crd_namespace: Option<String>,
+    pub enable_sso: Option<bool>,
+    pub iam_sso_role_arn: Option<String>,
+    pub iam_sso_role_name: Option<String>,
+    pub iam_sso_permission_set: Option<String>,
+    pub karpenter_role_arn: Option<Vec<String>>,
+    pub karpenter_role_username: Option<String>,
+    pub karpenter_role_groups: Option<Vec<String>>,
+    pub resolve_instance_profile: Option<bool>,
+    pub node_role_arns: Option<Vec<String>>,
+    pub map_aws_accounts: Option<Vec<String>>,
+    pub map_roles: Option<Vec<String>>,
+    pub map_users: Option<Vec<String>>,
+    pub admin_iam_user_arns: Option<Vec<String>>,
+    pub denied_k8s_groups: Option<Vec<String>>,
+    pub allow_system_masters: Option<bool>,
+    pub username_template: Option<String>,
+    pub lowercase_usernames: Option<bool>,
+    pub kubeconfig: Option<PathBuf>,
+    pub kube_context: Option<String>,
+    pub kube_contexts: Option<Vec<String>>,
+    pub kube_request_timeout_seconds: Option<u64>,
+    pub protected_arns: Option<Vec<String>>,
+    pub max_removal_fraction: Option<f64>,
+    pub max_removals: Option<usize>,
+    pub force_removals: Option<bool>,
+    pub synced_by_value: Option<String>,
+    /// Humantime duration, e.q: 1h
+    pub synced_entry_ttl: Option<String>,
+    pub adopt_existing_entries: Option<bool>,
+    pub adopt_by_arn_pattern: Option<String>,
+    pub removals_only: Option<bool>,
+    pub auth_backend: Option<String>,
+    pub eks_cluster_name: Option<String>,
+    pub skip_cluster_identity_check: Option<bool>,
+    pub discover_nodegroup_roles: Option<bool>,
+    pub config_map_namespace: Option<String>,
+    pub config_map_name: Option<String>,
+    pub config_map_targets: Option<Vec<String>>,
+    pub backup_config_map_name: Option<String>,
+    pub backup_history_count: Option<usize>,
+    pub no_annotations: Option<bool>,
+    pub server_dry_run: Option<bool>,
+    pub dry_run: Option<bool>,
+    pub watch_config_map: Option<bool>,
+    pub watch_debounce_seconds: Option<u64>,
+    pub enable_leader_election: Option<bool>,
+    pub lease_name: Option<String>,
+    pub lease_namespace: Option<String>,
+    pub lease_duration_seconds: Option<u64>,
+    pub emit_kubernetes_events: Option<bool>,
+    pub create_config_map_if_missing: Option<bool>,
+    pub export_json: Option<String>,
+    pub audit_log_path: Option<PathBuf>,
+    pub audit_log_required: Option<bool>,
+    pub notify_webhook_url: Option<String>,
+    pub slack_webhook_url: Option<String>,
+    pub notify_webhook_timeout_seconds: Option<u64>,
+    pub notify_on_failure: Option<bool>,
+    pub status_config_map: Option<bool>,
+    pub status_config_map_name: Option<String>,
+    pub status_config_map_namespace: Option<String>,
+    pub verbose: Option<bool>,
+    pub quiet: Option<bool>,
+    pub log_level: Option<String>,
+    pub log_format: Option<String>,
+    pub no_ansi: Option<bool>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<ConfigFile, ConfigurationError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ConfigurationError::CannotReadConfigFile {
+                path: Arc::from(path.to_string_lossy().as_ref()),
+                raw_message: Arc::from(e.to_string()),
+            }
+        })?;
+        Self::parse(&content).map_err(|e| match e {
+            ConfigurationError::InvalidConfigFile { raw_message, .. } => {
+                ConfigurationError::InvalidConfigFile {
+                    path: Arc::from(path.to_string_lossy().as_ref()),
+                    raw_message,
+                }
+            }
+            e => e,
+        })
+    }
+
+    pub fn parse(content: &str) -> Result<ConfigFile, ConfigurationError> {
+        // an empty file is a valid, empty, config
+        if content.trim().is_empty() {
+            return Ok(ConfigFile::default());
+        }
+        // all unknown keys are reported at once, serde stopping at the first one
+        if let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(content) {
+            let keys = config_file_keys();
+            let unknown_keys: Vec<String> = mapping
+                .keys()
+                .filter_map(serde_yaml::Value::as_str)
+                .filter(|key| !keys.contains(key))
+                .map(|key| match closest_key(key, keys) {
+                    Some(closest_key) => {
+                        format!("unknown field `{key}`, did you mean `{closest_key}`?")
+                    }
+                    None => format!("unknown field `{key}`"),
+                })
+                .collect();
+            if !unknown_keys.is_empty() {
+                return Err(ConfigurationError::InvalidConfigFile {
+                    path: Arc::from(""),
+                    raw_message: Arc::from(unknown_keys.join("; ")),
+                });
+            }
+        }
+        let config_file: ConfigFile =
+            serde_yaml::from_str(content).map_err(|e| ConfigurationError::InvalidConfigFile {
+                path: Arc::from(""),
+                raw_message: Arc::from(e.to_string()),
+            })?;
+        if config_file.aws_role_arn.is_some() && config_file.aws_access_key_id.is_some() {
+            return Err(ConfigurationError::IncompatibleArguments {
+                argument: Arc::from("aws_role_arn"),
+                other_argument: Arc::from("aws_access_key_id"),
+            });
+        }
+
+        Ok(config_file)
+    }
+
+    /// Values of keys set in the file, by argument id.
+    pub(crate) fn values(&self) -> Vec<(&'static str, Vec<String>)> {
+        fn single<T: ToString>(
+            id: &'static str,
+            value: &Option<T>,
+        ) -> Option<(&'static str, Vec<String>)> {
+            value.as_ref().map(|v| (id, vec![v.to_string()]))
+        }
+        fn multiple(
+            id: &'static str,
+            values: &Option<Vec<String>>,
+        ) -> Option<(&'static str, Vec<String>)> {
+            values.as_ref().map(|v| (id, v.clone()))
+        }
+
+        vec![
+            single("service_account_name", &self.service_account_name),
+            single("aws_role_arn", &self.aws_role_arn),
+            single("aws_access_key_id", &self.aws_access_key_id),
+            single("aws_secret_access_key", &self.aws_secret_access_key),
+            single("aws_default_region", &self.aws_default_region),
+            single("refresh_interval", &self.refresh_interval),
+            single("refresh_interval_seconds", &self.refresh_interval_seconds),
+            single(
+                "refresh_interval_jitter_seconds",
+                &self.refresh_interval_jitter_seconds,
+            ),
+            single(
+                "refresh_interval_jitter_seed",
+                &self.refresh_interval_jitter_seed,
+            ),
+            single("missed_tick_behavior", &self.missed_tick_behavior),
+            single("once", &self.once),
+            single("show_config", &self.show_config),
+            single("output", &self.output),
+            single("max_backoff_seconds", &self.max_backoff_seconds),
+            single("max_consecutive_failures", &self.max_consecutive_failures),
+            single("no_fail_fast", &self.no_fail_fast),
+            single("sync_timeout_seconds", &self.sync_timeout_seconds),
+            single("health_listen_addr", &self.health_listen_addr),
+            single("readiness_max_staleness", &self.readiness_max_staleness),
+            single("enable_sync_endpoint", &self.enable_sync_endpoint),
+            single("heartbeat_file", &self.heartbeat_file),
+            single("otlp_endpoint", &self.otlp_endpoint),
+            single("enable_group_user_sync", &self.enable_group_user_sync),
+            multiple("iam_k8s_groups", &self.iam_k8s_groups),
+            single("crd_mode", &self.crd_mode),
+            single("strict_group_validation", &self.strict_group_validation),
+            single("crd_namespace", &self.crd_namespace),
+            single("enable_sso", &self.enable_sso),
+            single("iam_sso_role_arn", &self.iam_sso_role_arn),
+            single("iam_sso_role_name", &self.iam_sso_role_name),
+            single("iam_sso_permission_set", &self.iam_sso_permission_set),
+            multiple("karpenter_role_arn", &self.karpenter_role_arn),
+            single("karpenter_role_username", &self.karpenter_role_username),
+            multiple("karpenter_role_groups", &self.karpenter_role_groups),
+            single("resolve_instance_profile", &self.resolve_instance_profile),
+            multiple("node_role_arns", &self.node_role_arns),
+            multiple("map_aws_accounts", &self.map_aws_accounts),
+            multiple("map_roles", &self.map_roles),
+            multiple("map_users", &self.map_users),
+            multiple("admin_iam_user_arns", &self.admin_iam_user_arns),
+            multiple("denied_k8s_groups", &self.denied_k8s_groups),
+            single("allow_system_masters", &self.allow_system_masters),
+            single("username_template", &self.username_template),
+            single("lowercase_usernames", &self.lowercase_usernames),
+            single(
+                "kubeconfig",
+                &self.kubeconfig.as_ref().map(|p| p.to_string_lossy()),
+            ),
+            single("kube_context", &self.kube_context),
+            multiple("kube_contexts", &self.kube_contexts),
+            single(
+                "kube_request_timeout_seconds",
+                &self.kube_request_timeout_seconds,
+            ),
+            multiple("protected_arns", &self.protected_arns),
+            single("max_removal_fraction", &self.max_removal_fraction),
+            single("max_removals", &self.max_removals),
+            single("force_removals", &self.force_removals),
+            single("synced_by_value", &self.synced_by_value),
+            single("synced_entry_ttl", &self.synced_entry_ttl),
+            single("adopt_existing_entries", &self.adopt_existing_entries),
+            single("adopt_by_arn_pattern", &self.adopt_by_arn_pattern),
+            single("removals_only", &self.removals_only),
+            single("auth_backend", &self.auth_backend),
+            single("eks_cluster_name", &self.eks_cluster_name),
+            single(
+                "skip_cluster_identity_check",
+                &self.skip_cluster_identity_check,
+            ),
+            single("discover_nodegroup_roles", &self.discover_nodegroup_roles),
+            single("config_map_namespace", &self.config_map_namespace),
+            single("config_map_name", &self.config_map_name),
+            multiple("config_map_targets", &self.config_map_targets),
+            single("backup_config_map_name", &self.backup_config_map_name),
+            single("backup_history_count", &self.backup_history_count),
+            single("no_annotations", &self.no_annotations),
+            single("server_dry_run", &self.server_dry_run),
+            single("dry_run", &self.dry_run),
+            single("watch_config_map", &self.watch_config_map),
+            single("watch_debounce_seconds", &self.watch_debounce_seconds),
+            single("enable_leader_election", &self.enable_leader_election),
+            single("lease_name", &self.lease_name),
+            single("lease_namespace", &self.lease_namespace),
+            single("lease_duration_seconds", &self.lease_duration_seconds),
+            single("emit_kubernetes_events", &self.emit_kubernetes_events),
+            single(
+                "create_config_map_if_missing",
+                &self.create_config_map_if_missing,
+            ),
+            single("export_json", &self.export_json),
+            single(
+                "audit_log_path",
+                &self.audit_log_path.as_ref().map(|p| p.to_string_lossy()),
+            ),
+            single("audit_log_required", &self.audit_log_required),
+            single("notify_webhook_url", &self.notify_webhook_url),
+            single("slack_webhook_url", &self.slack_webhook_url),
+            single(
+                "notify_webhook_timeout_seconds",
+                &self.notify_webhook_timeout_seconds,
+            ),
+            single("notify_on_failure", &self.notify_on_failure),
+            single("status_config_map", &self.status_config_map),
+            single("status_config_map_name", &self.status_config_map_name),
+            single(
+                "status_config_map_namespace",
+                &self.status_config_map_namespace,
+            ),
+            single("verbose", &self.verbose),
+            single("quiet", &self.quiet),
+            single("log_level", &self.log_level),
+            single("log_format", &self.log_format),
+            single("no_ansi", &self.no_ansi),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Deserializer only recording the fields names of the struct deserialized with it.
+struct StructFields<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> de::Deserializer<'de> for StructFields<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("fields recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Keys accepted in the file, taken from `ConfigFile` for them never to drift from it.
+fn config_file_keys() -> &'static [&'static str] {
+    let mut keys: &'static [&'static str] = &[];
+    let _ = ConfigFile::deserialize(StructFields(&mut keys));
+    keys
+}
+
+/// Known key closest to an unknown one, if close enough to be a typo, e.q: `refresh_interval_seconds` for
+/// `refreshIntervalSecond`. Case, `_` and `-` are ignored.
+fn closest_key(unknown_key: &str, keys: &[&'static str]) -> Option<&'static str> {
+    let normalize = |key: &str| -> Vec<char> {
+        key.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let unknown_key = normalize(unknown_key);
+
+    keys.iter()
+        .map(|key| (normalize(key), *key))
+        .map(|(normalized_key, key)| {
+            (
+                edit_distance(&unknown_key, &normalized_key),
+                normalized_key.len(),
+                key,
+            )
+        })
+        .filter(|(distance, len, _)| *distance <= (len / 3).max(1))
+        .min_by_key(|(distance, _, _)| *distance)
+        .map(|(_, _, key)| key)
+}
+
+/// Levenshtein distance, i.e. number of characters insertions, deletions and substitutions between `a` and `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    // following symlinks, mounted config maps files being swapped through them
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Notifies `sender` when the config file at `path` has to be reloaded: on SIGHUP, or when its
+/// modification time changed, checked every `poll_interval`.
+pub async fn watch_config_file(path: PathBuf, poll_interval: Duration, sender: mpsc::Sender<()>) {
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => Some(sighup),
+        Err(e) => {
+            warn!("Cannot listen to SIGHUP, config file is only reloaded on modification: {e}");
+            None
+        }
+    };
+    let mut last_modified = modified_time(&path);
+    let mut poll_interval = time::interval(poll_interval);
+
+    loop {
+        tokio::select! {
+            _ = poll_interval.tick() => {
+                let modified = modified_time(&path);
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                info!("Config file `{}` modified, reloading it", path.display());
+            }
+            Some(()) = async {
+                match &mut sighup {
+                    Some(sighup) => sighup.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                info!("SIGHUP received, reloading config file `{}`", path.display());
+            }
+        }
+        // a reload already pending will read the latest content
+        if let Err(TrySendError::Closed(())) = sender.try_send(()) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::Args;
+    use crate::config::loader::ArgsLoader;
+    use crate::config::ConfigurationError;
+    use crate::config_file::{edit_distance, watch_config_file, ConfigFile};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time;
+
+    fn parse_args(config_file: &ConfigFile, argv: &[&str]) -> Result<Args, clap::Error> {
+        ArgsLoader::new(config_file.clone(), BTreeMap::new())
+            .load(std::iter::once("iam-eks-user-mapper").chain(argv.iter().copied()))
+    }
+
+    #[test]
+    fn config_file_parse_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: &'a str,
+            expected: Result<ConfigFile, ConfigurationError>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "",
+                expected: Ok(ConfigFile::default()),
+                _description: "case 1 - empty file",
+            },
+            TestCase {
+                input: r#"
+service_account_name: iam-eks-user-mapper
+aws_role_arn: arn:aws:iam::123456789012:role/mapper
+aws_default_region: eu-west-3
+refresh_interval_seconds: 30
+enable_group_user_sync: true
+iam_k8s_groups:
+  - Admins->system:masters
+  - Devops->system:devops
+enable_sso: true
+iam_sso_role_arn: arn:aws:iam::123456789012:role/sso
+karpenter_role_arn:
+  - arn:aws:iam::123456789012:role/karpenter
+karpenter_role_groups:
+  - system:bootstrappers
+  - system:nodes
+  - eks:kube-proxy-windows
+map_roles:
+  - arn:aws:iam::123456789012:role/ci|username=ci|groups=ci,deployers
+max_removal_fraction: 0.25
+synced_entry_ttl: 1h
+"#,
+                expected: Ok(ConfigFile {
+                    service_account_name: Some("iam-eks-user-mapper".to_string()),
+                    aws_role_arn: Some("arn:aws:iam::123456789012:role/mapper".to_string()),
+                    aws_default_region: Some("eu-west-3".to_string()),
+                    refresh_interval_seconds: Some(30),
+                    enable_group_user_sync: Some(true),
+                    iam_k8s_groups: Some(vec![
+                        "Admins->system:masters".to_string(),
+                        "Devops->system:devops".to_string(),
+                    ]),
+                    enable_sso: Some(true),
+                    iam_sso_role_arn: Some("arn:aws:iam::123456789012:role/sso".to_string()),
+                    karpenter_role_arn: Some(vec![
+                        "arn:aws:iam::123456789012:role/karpenter".to_string()
+                    ]),
+                    karpenter_role_groups: Some(vec![
+                        "system:bootstrappers".to_string(),
+                        "system:nodes".to_string(),
+                        "eks:kube-proxy-windows".to_string(),
+                    ]),
+                    map_roles: Some(vec![
+                        "arn:aws:iam::123456789012:role/ci|username=ci|groups=ci,deployers"
+                            .to_string(),
+                    ]),
+                    max_removal_fraction: Some(0.25),
+                    synced_entry_ttl: Some("1h".to_string()),
+                    ..Default::default()
+                }),
+                _description: "case 2 - credentials, mappings, SSO, Karpenter and intervals",
+            },
+            TestCase {
+                input: "refresh_interval_seconds: soon",
+                expected: Err(ConfigurationError::InvalidConfigFile {
+                    path: Arc::from(""),
+                    raw_message: Arc::from("refresh_interval_seconds: invalid type: string \"soon\", expected u64 at line 1 column 27"),
+                }),
+                _description: "case 3 - invalid value type",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = ConfigFile::parse(tc.input);
+
+            // verify:
+            assert_eq!(tc.expected, res, "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn config_file_unknown_key_test() {
+        // setup:
+        let content = "aws_default_region: eu-west-3\nrefresh_intervals: 30s\n";
+
+        // execute:
+        let res = ConfigFile::parse(content);
+
+        // verify:
+        match res {
+            Err(ConfigurationError::InvalidConfigFile { raw_message, .. }) => {
+                assert!(
+                    raw_message.contains("unknown field `refresh_intervals`"),
+                    "{raw_message}"
+                );
+            }
+            res => panic!("expected an invalid config file error, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn config_file_unknown_keys_suggestions_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: &'a str,
+            expected: &'a str,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "aws_default_region: eu-west-3\nrefreshIntervalSecond: 30\n",
+                expected: "unknown field `refreshIntervalSecond`, did you mean `refresh_interval_seconds`?",
+                _description: "case 1 - camel case typo",
+            },
+            TestCase {
+                input: r#"
+aws-role-arn: arn:aws:iam::123456789012:role/mapper
+enable_group_user_sync: true
+iam_k8s_group:
+  - Admins->system:masters
+dry_rn: true
+"#,
+                expected: "unknown field `aws-role-arn`, did you mean `aws_role_arn`?; unknown field `iam_k8s_group`, did you mean `iam_k8s_groups`?; unknown field `dry_rn`, did you mean `dry_run`?",
+                _description: "case 2 - several typos reported at once, in file order",
+            },
+            TestCase {
+                input: "clusters: [production]\nlog_levl: debug\n",
+                expected: "unknown field `clusters`; unknown field `log_levl`, did you mean `log_level`?",
+                _description: "case 3 - no suggestion for a key not close to any known one",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = ConfigFile::parse(tc.input);
+
+            // verify:
+            assert_eq!(
+                Err(ConfigurationError::InvalidConfigFile {
+                    path: Arc::from(""),
+                    raw_message: Arc::from(tc.expected),
+                }),
+                res,
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn edit_distance_test() {
+        // setup:
+        let chars = |s: &str| s.chars().collect::<Vec<char>>();
+
+        // execute & verify:
+        assert_eq!(0, edit_distance(&chars("dry_run"), &chars("dry_run")));
+        assert_eq!(1, edit_distance(&chars("dry_rn"), &chars("dry_run")));
+        assert_eq!(2, edit_distance(&chars("log_lveel"), &chars("log_level")));
+        assert_eq!(3, edit_distance(&chars("kitten"), &chars("sitting")));
+        assert_eq!(7, edit_distance(&chars(""), &chars("dry_run")));
+    }
+
+    #[test]
+    fn config_file_load_test() {
+        // setup:
+        let path = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-config-{}.yaml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "karpenter: arn:aws:iam::123456789012:role/karpenter\n",
+        )
+        .unwrap();
+        let missing_path = path.with_extension("missing");
+
+        // execute:
+        let res = ConfigFile::load(&path);
+        let missing_res = ConfigFile::load(&missing_path);
+        std::fs::remove_file(&path).unwrap();
+
+        // verify:
+        match res {
+            Err(ConfigurationError::InvalidConfigFile {
+                path: error_path,
+                raw_message,
+            }) => {
+                assert_eq!(path.to_string_lossy(), error_path.as_ref());
+                assert!(raw_message.contains("unknown field `karpenter`"));
+            }
+            res => panic!("expected an invalid config file error, got {res:?}"),
+        }
+        assert!(matches!(
+            missing_res,
+            Err(ConfigurationError::CannotReadConfigFile { .. })
+        ));
+    }
+
+    #[test]
+    fn config_file_args_test() {
+        // setup:
+        let config_file = ConfigFile::parse(
+            r#"
+service_account_name: iam-eks-user-mapper
+aws_role_arn: arn:aws:iam::123456789012:role/mapper
+aws_default_region: eu-west-3
+refresh_interval_seconds: 30
+enable_sso: true
+iam_k8s_groups: [Admins->system:masters, Devops->system:devops]
+map_roles:
+  - arn:aws:iam::123456789012:role/ci|username=ci|groups=ci,deployers
+synced_entry_ttl: 1h
+"#,
+        )
+        .unwrap();
+
+        // execute:
+        let args = parse_args(&config_file, &[]).unwrap();
+
+        // verify:
+        assert_eq!(
+            Some("iam-eks-user-mapper".to_string()),
+            args.service_account_name
+        );
+        assert_eq!(
+            Some("arn:aws:iam::123456789012:role/mapper".to_string()),
+            args.aws_role_arn
+        );
+        assert_eq!(Some("eu-west-3".to_string()), args.aws_default_region);
+        assert_eq!(Duration::from_secs(30), args.refresh_interval());
+        assert!(args.enable_sso);
+        assert_eq!(
+            vec![
+                "Admins->system:masters".to_string(),
+                "Devops->system:devops".to_string()
+            ],
+            args.iam_k8s_groups
+        );
+        assert_eq!(
+            vec!["arn:aws:iam::123456789012:role/ci|username=ci|groups=ci,deployers".to_string()],
+            args.map_roles
+        );
+        assert_eq!(Some(Duration::from_secs(3600)), args.synced_entry_ttl);
+        // not set in the file
+        assert_eq!("aws-auth", args.config_map_name);
+        // file values are validated as flags are
+        let invalid_config_file = ConfigFile {
+            auth_backend: Some("unknown".to_string()),
+            ..config_file
+        };
+        assert!(parse_args(&invalid_config_file, &[]).is_err());
+    }
+
+    #[test]
+    fn config_file_precedence_test() {
+        // setup:
+        let config_file = ConfigFile {
+            service_account_name: Some("iam-eks-user-mapper".to_string()),
+            aws_role_arn: Some("arn:aws:iam::123456789012:role/mapper".to_string()),
+            aws_default_region: Some("eu-west-3".to_string()),
+            lease_name: Some("from-file".to_string()),
+            lease_namespace: Some("from-file".to_string()),
+            status_config_map_name: Some("from-file".to_string()),
+            ..Default::default()
+        };
+        // only test reading those env vars
+        std::env::set_var("LEASE_NAMESPACE", "from-env");
+        std::env::set_var("STATUS_CONFIG_MAP_NAME", "from-env");
+
+        // execute:
+        let res = parse_args(&config_file, &["--status-config-map-name", "from-flag"]);
+        std::env::remove_var("LEASE_NAMESPACE");
+        std::env::remove_var("STATUS_CONFIG_MAP_NAME");
+
+        // verify:
+        let args = res.unwrap();
+        assert_eq!("from-file", args.lease_name, "file over default");
+        assert_eq!("from-env", args.lease_namespace, "env over file");
+        assert_eq!("from-flag", args.status_config_map_name, "flag over env");
+        assert_eq!(
+            Some("arn:aws:iam::123456789012:role/mapper".to_string()),
+            args.aws_role_arn
+        );
+    }
+
+    #[test]
+    fn config_file_credentials_precedence_test() {
+        // setup:
+        let config_file = ConfigFile {
+            service_account_name: Some("iam-eks-user-mapper".to_string()),
+            aws_role_arn: Some("arn:aws:iam::123456789012:role/mapper".to_string()),
+            aws_default_region: Some("eu-west-3".to_string()),
+            ..Default::default()
+        };
+
+        // execute:
+        let res = parse_args(
+            &config_file,
+            &[
+                "--aws-access-key-id",
+                "key",
+                "--aws-secret-access-key",
+                "secret",
+            ],
+        );
+
+        // verify:
+        let args = res.unwrap();
+        assert_eq!(None, args.aws_role_arn);
+        assert_eq!(Some("key".to_string()), args.aws_access_key_id);
+        assert_eq!(
+            Err(ConfigurationError::IncompatibleArguments {
+                argument: Arc::from("aws_role_arn"),
+                other_argument: Arc::from("aws_access_key_id"),
+            }),
+            ConfigFile::parse(
+                "aws_role_arn: arn:aws:iam::123456789012:role/mapper\naws_access_key_id: key\n"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_config_file_test() {
+        // setup:
+        let path = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-watched-config-{}.yaml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "refresh_interval_seconds: 60\n").unwrap();
+        let (sender, mut receiver) = mpsc::channel(1);
+        let watcher = tokio::spawn(watch_config_file(
+            path.clone(),
+            Duration::from_millis(10),
+            sender,
+        ));
+
+        // execute:
+        time::sleep(Duration::from_millis(50)).await;
+        let unmodified = receiver.try_recv();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        let modified = time::timeout(Duration::from_secs(5), receiver.recv()).await;
+        watcher.abort();
+        std::fs::remove_file(&path).unwrap();
+
+        // verify:
+        assert!(unmodified.is_err(), "no reload without modification");
+        assert_eq!(Ok(Some(())), modified);
+    }
+}
@@ -1,10 +1,31 @@
+use crate::audit::AuditError;
 use crate::aws::AwsError;
 use crate::config::ConfigurationError;
+use crate::health::HealthError;
+use crate::heartbeat::HeartbeatError;
 use crate::kubernetes::KubernetesError;
+use crate::notification::NotificationError;
+use crate::telemetry::TelemetryError;
+use std::process::ExitCode;
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::subscriber::SetGlobalDefaultError;
 
+/// Exit codes of `Error::exit_code`, shown in `--help`.
+pub const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  other error, e.q: audit log, notification or health probes error, or the sync loop panicking
+  2  configuration error, including invalid arguments and a Kubernetes client not reaching the EKS cluster, not to be retried
+  3  AWS error, e.q: IAM or EKS unreachable
+  4  Kubernetes error, e.q: cluster unreachable or aws-auth write conflict
+  5  tracing or telemetry initialization error
+  6  sync timed out
+  7  healthcheck failed, last successful sync being too old or missing
+  8  validate failed, at least one check not passing";
+
+/// Error of a sync or of the binary, see [`Error::exit_code`] for its category.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Initialization error, cannot setup tracing: {underlying_error}")]
     InitializationErrorCannotSetupTracing {
@@ -18,4 +39,332 @@ pub enum Error {
     Aws { underlying_error: AwsError },
     #[error("Kubernetes error: {underlying_error}")]
     Kubernetes { underlying_error: KubernetesError },
+    #[error("Audit log error: {underlying_error}")]
+    Audit { underlying_error: AuditError },
+    #[error("Notification error: {underlying_error}")]
+    Notification { underlying_error: NotificationError },
+    #[error("Health probes error: {underlying_error}")]
+    Health { underlying_error: HealthError },
+    #[error("Heartbeat error: {underlying_error}")]
+    Heartbeat { underlying_error: HeartbeatError },
+    #[error("Telemetry error: {underlying_error}")]
+    Telemetry { underlying_error: TelemetryError },
+    #[error("IAM user `{iam_user}` belongs to IAM group `{iam_group}` which has no Kubernetes groups mapping, skipping sync")]
+    UnmappedIamGroup {
+        iam_group: Arc<str>,
+        iam_user: Arc<str>,
+    },
+    #[error("Sync timed out after {timeout_seconds}s while {phase}, nothing written")]
+    SyncTimeout {
+        phase: Arc<str>,
+        timeout_seconds: u64,
+    },
+    #[error("Sync loop stopped unexpectedly: {raw_message}")]
+    SyncLoopAborted { raw_message: Arc<str> },
+    #[error("{failed_checks} validation check(s) failed")]
+    ValidationFailed { failed_checks: usize },
+    #[error("Kubernetes client endpoint `{kube_endpoint}` is not EKS cluster `{cluster_name}` one `{cluster_endpoint}` and its CA doesn't match either, check the kubeconfig and AWS credentials target the same cluster")]
+    ClusterIdentityMismatch {
+        cluster_name: Arc<str>,
+        cluster_endpoint: Arc<str>,
+        kube_endpoint: Arc<str>,
+    },
+}
+
+impl Error {
+    /// Process exit code of the error category, stable for automation to tell errors worth retrying apart.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Configuration { .. } | Error::ClusterIdentityMismatch { .. } => 2,
+            Error::Aws { .. } => 3,
+            Error::Kubernetes { .. } => 4,
+            Error::InitializationErrorCannotSetupTracing { .. } | Error::Telemetry { .. } => 5,
+            Error::SyncTimeout { .. } => 6,
+            Error::Heartbeat { .. } => 7,
+            Error::ValidationFailed { .. } => 8,
+            Error::Audit { .. }
+            | Error::Notification { .. }
+            | Error::Health { .. }
+            | Error::UnmappedIamGroup { .. }
+            | Error::SyncLoopAborted { .. } => 1,
+        }
+    }
+
+    /// Whether a sync failing with the error might succeed once retried, the sync loop exiting right away
+    /// otherwise instead of retrying forever, e.q: on missing permissions or a malformed configuration.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Aws { underlying_error } => underlying_error.is_retryable(),
+            Error::Kubernetes { underlying_error } => underlying_error.is_retryable(),
+            Error::SyncTimeout { .. } | Error::Audit { .. } => true,
+            Error::InitializationErrorCannotSetupTracing { .. }
+            | Error::Configuration { .. }
+            | Error::Notification { .. }
+            | Error::Health { .. }
+            | Error::Heartbeat { .. }
+            | Error::Telemetry { .. }
+            | Error::UnmappedIamGroup { .. }
+            | Error::SyncLoopAborted { .. }
+            | Error::ValidationFailed { .. }
+            | Error::ClusterIdentityMismatch { .. } => false,
+        }
+    }
+}
+
+impl From<&Error> for ExitCode {
+    fn from(error: &Error) -> ExitCode {
+        ExitCode::from(error.exit_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::eks::EksError;
+    use crate::aws::iam::{IamError, IamGroup};
+    use crate::aws::AwsError;
+    use crate::config::ConfigurationError;
+    use crate::errors::{Error, EXIT_CODES_HELP};
+    use crate::heartbeat::HeartbeatError;
+    use crate::kubernetes::{EntryErrorLocation, KubernetesError};
+    use crate::notification::NotificationError;
+    use crate::telemetry::TelemetryError;
+    use std::sync::Arc;
+
+    #[test]
+    fn error_exit_code_test() {
+        struct TestCase<'a> {
+            error: Error,
+            expected: u8,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                error: Error::Configuration {
+                    underlying_error: ConfigurationError::EmptyConfigMapName,
+                },
+                expected: 2,
+                _description: "case 1 - configuration error",
+            },
+            TestCase {
+                error: Error::ClusterIdentityMismatch {
+                    cluster_name: Arc::from("my-cluster"),
+                    cluster_endpoint: Arc::from("https://my-cluster.eks.amazonaws.com"),
+                    kube_endpoint: Arc::from("https://other.eks.amazonaws.com"),
+                },
+                expected: 2,
+                _description: "case 2 - kubeconfig targeting another cluster",
+            },
+            TestCase {
+                error: Error::Aws {
+                    underlying_error: AwsError::from(IamError::NoUsersFoundInIamGroup {
+                        group: IamGroup::new("Admins"),
+                    }),
+                },
+                expected: 3,
+                _description: "case 3 - AWS error",
+            },
+            TestCase {
+                error: Error::Kubernetes {
+                    underlying_error: KubernetesError::ClusterUnreachable {
+                        raw_message: Arc::from("connection refused"),
+                    },
+                },
+                expected: 4,
+                _description: "case 4 - Kubernetes error",
+            },
+            TestCase {
+                error: Error::Telemetry {
+                    underlying_error: TelemetryError::CannotFlush {
+                        raw_message: Arc::from("timed out"),
+                    },
+                },
+                expected: 5,
+                _description: "case 5 - telemetry error",
+            },
+            TestCase {
+                error: Error::SyncTimeout {
+                    phase: Arc::from("fetching IAM users"),
+                    timeout_seconds: 300,
+                },
+                expected: 6,
+                _description: "case 6 - sync timeout",
+            },
+            TestCase {
+                error: Error::Heartbeat {
+                    underlying_error: HeartbeatError::TooOld {
+                        age_seconds: 600,
+                        max_age_seconds: 180,
+                    },
+                },
+                expected: 7,
+                _description: "case 7 - healthcheck failed",
+            },
+            TestCase {
+                error: Error::Notification {
+                    underlying_error: NotificationError::InvalidConfiguration {
+                        raw_message: Arc::from("invalid URL"),
+                    },
+                },
+                expected: 1,
+                _description: "case 8 - other error",
+            },
+            TestCase {
+                error: Error::ValidationFailed { failed_checks: 2 },
+                expected: 8,
+                _description: "case 9 - validate failed",
+            },
+            TestCase {
+                error: Error::SyncLoopAborted {
+                    raw_message: Arc::from("task 12 panicked with message \"boom\""),
+                },
+                expected: 1,
+                _description: "case 10 - sync loop panicked",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let exit_code = tc.error.exit_code();
+
+            // verify:
+            assert_eq!(tc.expected, exit_code, "{}", tc._description);
+            assert!(
+                EXIT_CODES_HELP.contains(&format!("\n  {exit_code}  ")),
+                "{}: exit code {exit_code} should be documented",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn error_is_retryable_test() {
+        struct TestCase<'a> {
+            error: Error,
+            expected: bool,
+            _description: &'a str,
+        }
+
+        let kubernetes = |underlying_error: KubernetesError| Error::Kubernetes { underlying_error };
+        let test_cases = vec![
+            TestCase {
+                error: Error::Aws {
+                    underlying_error: AwsError::from(IamError::CannotGetUserFromIamGroup {
+                        group: IamGroup::new("Admins"),
+                        raw_message: Arc::from("NoSuchEntity"),
+                        retryable: false,
+                    }),
+                },
+                expected: false,
+                _description: "case 1 - missing IAM group",
+            },
+            TestCase {
+                error: Error::Aws {
+                    underlying_error: AwsError::from(IamError::CannotGetUserFromIamGroup {
+                        group: IamGroup::new("Admins"),
+                        raw_message: Arc::from("Throttling"),
+                        retryable: true,
+                    }),
+                },
+                expected: true,
+                _description: "case 2 - throttled by IAM",
+            },
+            TestCase {
+                error: Error::Aws {
+                    underlying_error: AwsError::from(EksError::CannotListAccessEntries {
+                        raw_message: Arc::from("500 Internal Server Error"),
+                        retryable: true,
+                    }),
+                },
+                expected: true,
+                _description: "case 3 - EKS server error",
+            },
+            TestCase {
+                error: Error::Aws {
+                    underlying_error: AwsError::from(EksError::CannotDescribeCluster {
+                        cluster_name: Arc::from("my-cluster"),
+                        raw_message: Arc::from("ResourceNotFoundException"),
+                        retryable: false,
+                    }),
+                },
+                expected: false,
+                _description: "case 4 - missing EKS cluster",
+            },
+            TestCase {
+                error: kubernetes(KubernetesError::ClusterUnreachable {
+                    raw_message: Arc::from("connection refused"),
+                }),
+                expected: true,
+                _description: "case 5 - cluster unreachable",
+            },
+            TestCase {
+                error: kubernetes(KubernetesError::ConfigMapCannotBePatched {
+                    config_map_name: Arc::from("aws-auth"),
+                    config_map_namespace: Arc::from("kube-system"),
+                    attempts: 3,
+                    raw_message: Arc::from("409 Conflict"),
+                }),
+                expected: true,
+                _description: "case 6 - write conflicts",
+            },
+            TestCase {
+                error: kubernetes(KubernetesError::CannotDeserializeUsersMap {
+                    location: EntryErrorLocation::default(),
+                    snippet: Arc::from("invalid YAML"),
+                    underlying_error: Arc::from("invalid YAML"),
+                }),
+                expected: false,
+                _description: "case 7 - malformed aws-auth config map",
+            },
+            TestCase {
+                error: kubernetes(KubernetesError::AccessEntriesCannotBeSynced {
+                    cluster_name: Arc::from("my-cluster"),
+                    raw_message: Arc::from("403 Forbidden"),
+                    retryable: false,
+                }),
+                expected: false,
+                _description: "case 8 - access entries sync denied",
+            },
+            TestCase {
+                error: Error::Configuration {
+                    underlying_error: ConfigurationError::EmptyConfigMapName,
+                },
+                expected: false,
+                _description: "case 9 - configuration error",
+            },
+            TestCase {
+                error: Error::UnmappedIamGroup {
+                    iam_group: Arc::from("Admins"),
+                    iam_user: Arc::from("jane"),
+                },
+                expected: false,
+                _description: "case 10 - unmapped IAM group",
+            },
+            TestCase {
+                error: Error::SyncTimeout {
+                    phase: Arc::from("fetching IAM users"),
+                    timeout_seconds: 300,
+                },
+                expected: true,
+                _description: "case 11 - sync timeout",
+            },
+            TestCase {
+                error: kubernetes(KubernetesError::BackupCannotBeRead {
+                    backup_config_map_name: Arc::from("aws-auth-backup"),
+                    config_map_namespace: Arc::from("kube-system"),
+                    raw_message: Arc::from("403 Forbidden"),
+                }),
+                expected: true,
+                _description: "case 12 - backup config map cannot be read",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = tc.error.is_retryable();
+
+            // verify:
+            assert_eq!(tc.expected, res, "{}", tc._description);
+        }
+    }
 }
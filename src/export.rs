@@ -0,0 +1,313 @@
+use crate::aws::iam::IamGroup;
+use crate::kubernetes::aws_auth::AwsAuth;
+use crate::kubernetes::{KubernetesRole, KubernetesUser, SyncedBy};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Cannot serialize mapping export to JSON: {raw_message}")]
+    CannotSerialize { raw_message: Arc<str> },
+    #[error("Cannot write mapping export to `{path}`: {raw_message}")]
+    CannotWrite {
+        path: Arc<str>,
+        raw_message: Arc<str>,
+    },
+}
+
+/// Where the computed mapping should be exported to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExportTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+impl From<&str> for ExportTarget {
+    fn from(value: &str) -> Self {
+        match value {
+            "-" => ExportTarget::Stdout,
+            path => ExportTarget::File(PathBuf::from(path)),
+        }
+    }
+}
+
+impl Display for ExportTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportTarget::Stdout => f.write_str("-"),
+            ExportTarget::File(path) => f.write_str(&path.to_string_lossy()),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct ExportedUser {
+    pub username: String,
+    pub arn: String,
+    pub groups: Vec<String>,
+    #[serde(rename = "syncedBy")]
+    pub synced_by: Option<SyncedBy>,
+}
+
+impl From<&KubernetesUser> for ExportedUser {
+    fn from(value: &KubernetesUser) -> Self {
+        ExportedUser {
+            username: value.iam_user_name.to_string(),
+            arn: value.iam_arn.to_string(),
+            groups: value.roles.iter().map(|g| g.to_string()).collect(),
+            synced_by: value.synced_by.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct ExportedRole {
+    pub arn: String,
+    pub rolename: Option<String>,
+    pub username: Option<String>,
+    pub groups: Vec<String>,
+    #[serde(rename = "syncedBy")]
+    pub synced_by: Option<SyncedBy>,
+}
+
+impl From<&KubernetesRole> for ExportedRole {
+    fn from(value: &KubernetesRole) -> Self {
+        ExportedRole {
+            arn: value.iam_role_arn.to_string(),
+            rolename: value.role_name.clone(),
+            username: value.user_name.clone(),
+            groups: value.groups.iter().map(|g| g.to_string()).collect(),
+            synced_by: value.synced_by.clone(),
+        }
+    }
+}
+
+/// Machine readable snapshot of the computed IAM -> Kubernetes mapping.
+/// Entries are sorted so two exports of the same state are identical.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct MappingExport {
+    #[serde(rename = "generatedAt")]
+    pub generated_at: String,
+    #[serde(rename = "sourceIamGroups")]
+    pub source_iam_groups: Vec<String>,
+    pub users: Vec<ExportedUser>,
+    pub roles: Vec<ExportedRole>,
+}
+
+impl MappingExport {
+    pub fn new(
+        aws_auth: &AwsAuth,
+        source_iam_groups: &BTreeSet<IamGroup>,
+        generated_at: SystemTime,
+    ) -> MappingExport {
+        let source_iam_groups: Vec<String> =
+            source_iam_groups.iter().map(|g| g.to_string()).collect();
+
+        let mut users: Vec<ExportedUser> = aws_auth.users.iter().map(ExportedUser::from).collect();
+        users.sort_by(|a, b| a.arn.cmp(&b.arn).then(a.username.cmp(&b.username)));
+
+        let mut roles: Vec<ExportedRole> = aws_auth.roles.iter().map(ExportedRole::from).collect();
+        roles.sort_by(|a, b| a.arn.cmp(&b.arn).then(a.rolename.cmp(&b.rolename)));
+
+        MappingExport {
+            generated_at: humantime::format_rfc3339_seconds(generated_at).to_string(),
+            source_iam_groups,
+            users,
+            roles,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, ExportError> {
+        serde_json::to_string_pretty(self).map_err(|e| ExportError::CannotSerialize {
+            raw_message: Arc::from(e.to_string()),
+        })
+    }
+
+    pub fn write_to(&self, target: &ExportTarget) -> Result<(), ExportError> {
+        let json = self.to_json()?;
+
+        match target {
+            ExportTarget::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                writeln!(stdout, "{json}").map_err(|e| ExportError::CannotWrite {
+                    path: Arc::from(target.to_string()),
+                    raw_message: Arc::from(e.to_string()),
+                })
+            }
+            ExportTarget::File(path) => {
+                write_atomically(path, json.as_bytes()).map_err(|e| ExportError::CannotWrite {
+                    path: Arc::from(target.to_string()),
+                    raw_message: Arc::from(e.to_string()),
+                })
+            }
+        }
+    }
+}
+
+/// Writes `content` to a temporary file next to `path` then renames it, so readers never see a partial file.
+pub fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file path"))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.tmp-{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let mut tmp_file = fs::File::create(&tmp_path)?;
+    tmp_file.write_all(content)?;
+    tmp_file.sync_all()?;
+    drop(tmp_file);
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::iam::IamGroup;
+    use crate::export::{ExportTarget, MappingExport};
+    use crate::kubernetes::aws_auth::AwsAuth;
+    use crate::kubernetes::{
+        IamArn, IamUserName, KubernetesGroupName, KubernetesRole, KubernetesUser, SyncedBy,
+    };
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn aws_auth() -> AwsAuth {
+        AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                KubernetesUser::new(
+                    IamUserName::new("user_2"),
+                    IamArn::new("arn:aws:iam::123456789012:user/user_2"),
+                    BTreeSet::from_iter(vec![KubernetesGroupName::new("group_2")]),
+                    None,
+                ),
+                KubernetesUser::new(
+                    IamUserName::new("user_1"),
+                    IamArn::new("arn:aws:iam::123456789012:user/user_1"),
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_1"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                ),
+            ]),
+            roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                IamArn::new("arn:aws:iam::123456789012:role/karpenter"),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("system:nodes"),
+                    KubernetesGroupName::new("system:bootstrappers"),
+                ]),
+                Some(SyncedBy::IamEksUserMapper),
+            )]),
+        }
+    }
+
+    #[test]
+    fn export_target_from_str_test() {
+        assert_eq!(ExportTarget::Stdout, ExportTarget::from("-"));
+        assert_eq!(
+            ExportTarget::File(PathBuf::from("/tmp/mapping.json")),
+            ExportTarget::from("/tmp/mapping.json")
+        );
+    }
+
+    #[test]
+    fn mapping_export_json_schema_test() {
+        // setup:
+        let export = MappingExport::new(
+            &aws_auth(),
+            &BTreeSet::from_iter(vec![IamGroup::new("Devops"), IamGroup::new("Admins")]),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        );
+
+        // execute:
+        let json = export.to_json().expect("export should be serializable");
+
+        // verify:
+        let parsed: serde_json::Value =
+            serde_json::from_str(&json).expect("export should be valid JSON");
+        assert_eq!(
+            serde_json::json!({
+                "generatedAt": "2023-11-14T22:13:20Z",
+                "sourceIamGroups": ["Admins", "Devops"],
+                "users": [
+                    {
+                        "username": "user_1",
+                        "arn": "arn:aws:iam::123456789012:user/user_1",
+                        "groups": ["group_1", "group_2"],
+                        "syncedBy": "iam-eks-user-mapper",
+                    },
+                    {
+                        "username": "user_2",
+                        "arn": "arn:aws:iam::123456789012:user/user_2",
+                        "groups": ["group_2"],
+                        "syncedBy": null,
+                    },
+                ],
+                "roles": [
+                    {
+                        "arn": "arn:aws:iam::123456789012:role/karpenter",
+                        "rolename": null,
+                        "username": "system:node:{{EC2PrivateDNSName}}",
+                        "groups": ["system:bootstrappers", "system:nodes"],
+                        "syncedBy": "iam-eks-user-mapper",
+                    },
+                ],
+            }),
+            parsed
+        );
+    }
+
+    #[test]
+    fn mapping_export_is_stable_test() {
+        // setup:
+        let generated_at = SystemTime::UNIX_EPOCH;
+        let source_iam_groups = BTreeSet::from_iter(vec![IamGroup::new("Admins")]);
+
+        // execute:
+        let first = MappingExport::new(&aws_auth(), &source_iam_groups, generated_at).to_json();
+        let second = MappingExport::new(&aws_auth(), &source_iam_groups, generated_at).to_json();
+
+        // verify:
+        assert_eq!(first.unwrap(), second.unwrap());
+    }
+
+    #[test]
+    fn mapping_export_write_to_file_test() {
+        // setup:
+        let dir =
+            std::env::temp_dir().join(format!("iam-eks-user-mapper-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("cannot create temp dir");
+        let path = dir.join("mapping.json");
+        let export = MappingExport::new(&aws_auth(), &BTreeSet::default(), SystemTime::UNIX_EPOCH);
+
+        // execute:
+        let res = export.write_to(&ExportTarget::File(path.clone()));
+
+        // verify:
+        assert!(res.is_ok());
+        let written = std::fs::read_to_string(&path).expect("export file should exist");
+        assert_eq!(export.to_json().unwrap(), written);
+        // no temporary file should be left behind
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+}
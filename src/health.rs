@@ -0,0 +1,484 @@
+use crate::trigger::{SyncReport, SyncTrigger};
+use bytes::Bytes;
+use http::{header, Method, Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum HealthError {
+    #[error("Cannot listen for health probes on `{addr}`: {raw_message}")]
+    CannotListen {
+        addr: Arc<str>,
+        raw_message: Arc<str>,
+    },
+}
+
+/// What the sync loop publishes about itself for health and readiness probes.
+#[derive(Debug, Default)]
+pub struct SyncState {
+    /// Whether the sync loop task is running, unset when it stops, panics included.
+    pub loop_alive: bool,
+    /// Whether the loop skips syncs as a leader election standby, ready as long as alive.
+    pub standby: bool,
+    pub last_success: Option<Instant>,
+    pub last_failure: Option<Instant>,
+}
+
+/// `SyncState` shared between the sync loop and the health server.
+pub type SharedSyncState = Arc<RwLock<SyncState>>;
+
+impl SyncState {
+    pub fn succeeded(&mut self, at: Instant) {
+        self.standby = false;
+        self.last_success = Some(at);
+    }
+
+    pub fn failed(&mut self, at: Instant) {
+        self.standby = false;
+        self.last_failure = Some(at);
+    }
+
+    /// Why the loop isn't ready at `now`, if not: no successful sync yet or last one older than `max_staleness`.
+    fn not_ready_reason(&self, max_staleness: Duration, now: Instant) -> Option<String> {
+        if !self.loop_alive {
+            return Some("sync loop is not running".to_string());
+        }
+        if self.standby {
+            return None;
+        }
+        match self.last_success {
+            None => Some("no successful sync yet".to_string()),
+            Some(last_success) if now.saturating_duration_since(last_success) > max_staleness => {
+                Some(format!(
+                    "last successful sync is {}s old, more than {}s",
+                    now.saturating_duration_since(last_success).as_secs(),
+                    max_staleness.as_secs()
+                ))
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+/// Updates `state` with `update`, a lock poisoned by a panicking loop still holding the state.
+pub fn publish(state: &SharedSyncState, update: impl FnOnce(&mut SyncState)) {
+    update(&mut state.write().unwrap_or_else(|e| e.into_inner()));
+}
+
+/// Marks the sync loop as alive while held, e.q: for the whole loop task, not alive anymore once dropped.
+pub struct LoopAliveGuard {
+    state: SharedSyncState,
+}
+
+impl LoopAliveGuard {
+    pub fn new(state: SharedSyncState) -> LoopAliveGuard {
+        publish(&state, |state| state.loop_alive = true);
+        LoopAliveGuard { state }
+    }
+}
+
+impl Drop for LoopAliveGuard {
+    fn drop(&mut self) {
+        publish(&self.state, |state| state.loop_alive = false);
+    }
+}
+
+/// Serves `/healthz`, answering 200 while the sync loop is alive, and `/readyz`, answering 200 once a sync
+/// succeeded and as long as the last successful one isn't older than `max_staleness`.
+pub struct HealthServer {
+    listener: TcpListener,
+    state: SharedSyncState,
+    max_staleness: Duration,
+    /// Serves `POST /sync` as well if set, see `with_sync_trigger`.
+    sync_trigger: Option<SyncTrigger>,
+}
+
+impl HealthServer {
+    pub async fn bind(
+        addr: SocketAddr,
+        state: SharedSyncState,
+        max_staleness: Duration,
+    ) -> Result<HealthServer, HealthError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| HealthError::CannotListen {
+                addr: Arc::from(addr.to_string()),
+                raw_message: Arc::from(e.to_string()),
+            })?;
+
+        Ok(HealthServer {
+            listener,
+            state,
+            max_staleness,
+            sync_trigger: None,
+        })
+    }
+
+    /// Serves `POST /sync` as well, syncing right away and answering the sync report as JSON once done.
+    pub fn with_sync_trigger(self, sync_trigger: SyncTrigger) -> HealthServer {
+        HealthServer {
+            sync_trigger: Some(sync_trigger),
+            ..self
+        }
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    /// Answers probes forever, each connection being served concurrently.
+    pub async fn serve(self) {
+        loop {
+            let stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Error while accepting health probe connection: {e}");
+                    continue;
+                }
+            };
+            let state = self.state.clone();
+            let max_staleness = self.max_staleness;
+            let sync_trigger = self.sync_trigger.clone();
+            let service = service_fn(move |request: Request<Incoming>| {
+                let state = state.clone();
+                let sync_trigger = sync_trigger.clone();
+                async move {
+                    let response = match (&sync_trigger, request.method(), request.uri().path()) {
+                        (Some(sync_trigger), &Method::POST, "/sync") => {
+                            sync_response(sync_trigger).await
+                        }
+                        _ => probe_response(&request, &state, max_staleness),
+                    };
+                    Ok::<_, hyper::Error>(response)
+                }
+            });
+            tokio::spawn(async move {
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    debug!("Error while serving health probe: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// Requests a sync, answering its report with 200 if it succeeded.
+async fn sync_response(sync_trigger: &SyncTrigger) -> Response<Full<Bytes>> {
+    let (status, body) = match sync_trigger.sync().await {
+        Some(report) => {
+            let status = match &report {
+                SyncReport::Succeeded { .. } => StatusCode::OK,
+                SyncReport::Failed { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+                SyncReport::Skipped { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (status, serde_json::to_string(&report).unwrap_or_default())
+        }
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({ "status": "failed", "error": "sync loop is not running" })
+                .to_string(),
+        ),
+    };
+
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn probe_response(
+    request: &Request<Incoming>,
+    state: &SharedSyncState,
+    max_staleness: Duration,
+) -> Response<Full<Bytes>> {
+    let response = |status: StatusCode, body: String| {
+        let mut response = Response::new(Full::new(Bytes::from(body)));
+        *response.status_mut() = status;
+        response
+    };
+    if request.method() != Method::GET {
+        return response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method not allowed".to_string(),
+        );
+    }
+
+    let state = state.read().unwrap_or_else(|e| e.into_inner());
+    match request.uri().path() {
+        "/healthz" => match state.loop_alive {
+            true => response(StatusCode::OK, "ok".to_string()),
+            false => response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "sync loop is not running".to_string(),
+            ),
+        },
+        "/readyz" => match state.not_ready_reason(max_staleness, Instant::now()) {
+            None => response(StatusCode::OK, "ok".to_string()),
+            Some(reason) => response(StatusCode::SERVICE_UNAVAILABLE, reason),
+        },
+        _ => response(StatusCode::NOT_FOUND, "not found".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::{HealthServer, LoopAliveGuard, SharedSyncState, SyncState};
+    use crate::kubernetes::aws_auth::AwsAuthDiff;
+    use crate::trigger::{sync_trigger, SyncReport};
+    use bytes::Bytes;
+    use http::{Method, Request, StatusCode};
+    use http_body_util::{BodyExt, Empty};
+    use hyper_util::client::legacy::Client;
+    use hyper_util::rt::TokioExecutor;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    /// Starts a health server on a random local port, returning its base URL.
+    async fn start_server(state: SharedSyncState, max_staleness: Duration) -> String {
+        let server =
+            HealthServer::bind(SocketAddr::from(([127, 0, 0, 1], 0)), state, max_staleness)
+                .await
+                .expect("cannot bind health server");
+        let url = format!("http://{}", server.local_addr().unwrap());
+        tokio::spawn(server.serve());
+
+        url
+    }
+
+    async fn get(url: &str) -> (StatusCode, String) {
+        request(Method::GET, url).await
+    }
+
+    async fn request(method: Method, url: &str) -> (StatusCode, String) {
+        let client = Client::builder(TokioExecutor::new()).build_http::<Empty<Bytes>>();
+        let response = client
+            .request(
+                Request::builder()
+                    .method(method)
+                    .uri(url)
+                    .body(Empty::new())
+                    .unwrap(),
+            )
+            .await
+            .expect("health server should answer");
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[tokio::test]
+    async fn health_server_probes_test() {
+        struct TestCase<'a> {
+            state: SyncState,
+            expected_healthz: StatusCode,
+            expected_readyz: StatusCode,
+            _description: &'a str,
+        }
+
+        let max_staleness = Duration::from_secs(180);
+        let now = Instant::now();
+        let test_cases = vec![
+            TestCase {
+                state: SyncState {
+                    loop_alive: true,
+                    ..Default::default()
+                },
+                expected_healthz: StatusCode::OK,
+                expected_readyz: StatusCode::SERVICE_UNAVAILABLE,
+                _description: "case 1 - no sync yet",
+            },
+            TestCase {
+                state: SyncState {
+                    loop_alive: true,
+                    last_success: Some(now),
+                    ..Default::default()
+                },
+                expected_healthz: StatusCode::OK,
+                expected_readyz: StatusCode::OK,
+                _description: "case 2 - recent successful sync",
+            },
+            TestCase {
+                state: SyncState {
+                    loop_alive: true,
+                    last_success: Some(now - Duration::from_secs(120)),
+                    last_failure: Some(now),
+                    ..Default::default()
+                },
+                expected_healthz: StatusCode::OK,
+                expected_readyz: StatusCode::OK,
+                _description: "case 3 - failure after a still fresh successful sync",
+            },
+            TestCase {
+                state: SyncState {
+                    loop_alive: true,
+                    last_success: Some(now - Duration::from_secs(600)),
+                    ..Default::default()
+                },
+                expected_healthz: StatusCode::OK,
+                expected_readyz: StatusCode::SERVICE_UNAVAILABLE,
+                _description: "case 4 - stale successful sync",
+            },
+            TestCase {
+                state: SyncState {
+                    loop_alive: true,
+                    standby: true,
+                    ..Default::default()
+                },
+                expected_healthz: StatusCode::OK,
+                expected_readyz: StatusCode::OK,
+                _description: "case 5 - leader election standby",
+            },
+            TestCase {
+                state: SyncState {
+                    loop_alive: false,
+                    last_success: Some(now),
+                    ..Default::default()
+                },
+                expected_healthz: StatusCode::SERVICE_UNAVAILABLE,
+                expected_readyz: StatusCode::SERVICE_UNAVAILABLE,
+                _description: "case 6 - sync loop stopped",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let url = start_server(SharedSyncState::new(tc.state.into()), max_staleness).await;
+
+            // execute:
+            let (healthz, _) = get(&format!("{url}/healthz")).await;
+            let (readyz, readyz_body) = get(&format!("{url}/readyz")).await;
+
+            // verify:
+            assert_eq!(tc.expected_healthz, healthz, "{}", tc._description);
+            assert_eq!(
+                tc.expected_readyz, readyz,
+                "{}: {readyz_body}",
+                tc._description
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn health_server_unknown_path_test() {
+        // setup:
+        let url = start_server(SharedSyncState::default(), Duration::from_secs(180)).await;
+
+        // execute:
+        let (status, _) = get(&format!("{url}/metrics")).await;
+
+        // verify:
+        assert_eq!(StatusCode::NOT_FOUND, status);
+    }
+
+    #[tokio::test]
+    async fn loop_alive_guard_test() {
+        // setup:
+        let state = SharedSyncState::default();
+        let url = start_server(state.clone(), Duration::from_secs(180)).await;
+
+        // execute & verify:
+        let guard = LoopAliveGuard::new(state.clone());
+        assert_eq!(StatusCode::OK, get(&format!("{url}/healthz")).await.0);
+        state.write().unwrap().succeeded(Instant::now());
+        assert_eq!(StatusCode::OK, get(&format!("{url}/readyz")).await.0);
+
+        // loop task panicking drops the guard as well
+        let task_state = state.clone();
+        let res = tokio::spawn(async move {
+            let _guard = guard;
+            task_state.write().unwrap().failed(Instant::now());
+            panic!("sync loop panicked");
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            get(&format!("{url}/healthz")).await.0
+        );
+    }
+
+    #[tokio::test]
+    async fn health_server_sync_test() {
+        // setup:
+        let (sync_trigger, mut triggered_syncs) = sync_trigger();
+        let server = HealthServer::bind(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            SharedSyncState::default(),
+            Duration::from_secs(180),
+        )
+        .await
+        .expect("cannot bind health server")
+        .with_sync_trigger(sync_trigger);
+        let url = format!("http://{}", server.local_addr().unwrap());
+        tokio::spawn(server.serve());
+        // sync loop answering a single request
+        let sync_loop = tokio::spawn(async move {
+            triggered_syncs.requested().await;
+            let number = triggered_syncs.started();
+            triggered_syncs.finished(
+                number,
+                SyncReport::Failed {
+                    error: "access denied".to_string(),
+                },
+            );
+            triggered_syncs.requested().await;
+            let number = triggered_syncs.started();
+            triggered_syncs.finished(
+                number,
+                SyncReport::succeeded(AwsAuthDiff::default(), Duration::from_secs(1)),
+            );
+        });
+
+        // execute:
+        let failed = request(Method::POST, &format!("{url}/sync")).await;
+        let succeeded = request(Method::POST, &format!("{url}/sync")).await;
+        sync_loop.await.unwrap();
+        let stopped = request(Method::POST, &format!("{url}/sync")).await;
+        let not_posted = get(&format!("{url}/sync")).await;
+
+        // verify:
+        assert_eq!(
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                r#"{"status":"failed","error":"access denied"}"#.to_string()
+            ),
+            failed
+        );
+        assert_eq!(StatusCode::OK, succeeded.0);
+        assert!(succeeded
+            .1
+            .starts_with(r#"{"status":"succeeded","summary":"#));
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, stopped.0);
+        // only probes answer GET requests
+        assert_eq!(StatusCode::NOT_FOUND, not_posted.0);
+    }
+
+    #[tokio::test]
+    async fn health_server_sync_disabled_test() {
+        // setup:
+        let url = start_server(SharedSyncState::default(), Duration::from_secs(180)).await;
+
+        // execute:
+        let (status, _) = request(Method::POST, &format!("{url}/sync")).await;
+
+        // verify:
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, status);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::export::write_atomically;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum HeartbeatError {
+    #[error("Cannot write heartbeat file `{path}`: {raw_message}")]
+    CannotWrite {
+        path: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot read heartbeat file `{path}`, no sync succeeded yet: {raw_message}")]
+    CannotRead {
+        path: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Invalid heartbeat file `{path}`, expected an RFC 3339 timestamp: {raw_message}")]
+    InvalidHeartbeat {
+        path: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Last successful sync is {age_seconds}s old, more than {max_age_seconds}s")]
+    TooOld {
+        age_seconds: u64,
+        max_age_seconds: u64,
+    },
+}
+
+/// Writes `synced_at` as the last successful sync time into `path`, atomically not for a concurrent
+/// `healthcheck` to read a partial file.
+pub fn write_heartbeat(path: &Path, synced_at: SystemTime) -> Result<(), HeartbeatError> {
+    let content = format!("{}\n", humantime::format_rfc3339_seconds(synced_at));
+    write_atomically(path, content.as_bytes()).map_err(|e| HeartbeatError::CannotWrite {
+        path: Arc::from(path.to_string_lossy().as_ref()),
+        raw_message: Arc::from(e.to_string()),
+    })
+}
+
+/// Age at `now` of the last successful sync written into `path`, failing if older than `max_age`.
+pub fn check_heartbeat(
+    path: &Path,
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<Duration, HeartbeatError> {
+    let path_str = || Arc::from(path.to_string_lossy().as_ref());
+    let content = std::fs::read_to_string(path).map_err(|e| HeartbeatError::CannotRead {
+        path: path_str(),
+        raw_message: Arc::from(e.to_string()),
+    })?;
+    let synced_at = humantime::parse_rfc3339_weak(content.trim()).map_err(|e| {
+        HeartbeatError::InvalidHeartbeat {
+            path: path_str(),
+            raw_message: Arc::from(e.to_string()),
+        }
+    })?;
+
+    // a heartbeat from the future, e.q: after a clock adjustment, is as fresh as it gets
+    let age = now.duration_since(synced_at).unwrap_or_default();
+    match age > max_age {
+        true => Err(HeartbeatError::TooOld {
+            age_seconds: age.as_secs(),
+            max_age_seconds: max_age.as_secs(),
+        }),
+        false => Ok(age),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heartbeat::{check_heartbeat, write_heartbeat, HeartbeatError};
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-heartbeat-{name}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).expect("cannot create temp dir");
+        dir
+    }
+
+    #[test]
+    fn write_heartbeat_test() {
+        // setup:
+        let dir = temp_dir("write");
+        let path = dir.join("last-sync");
+        let first = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let second = first + Duration::from_secs(60);
+
+        // execute:
+        let first_res = write_heartbeat(&path, first);
+        let second_res = write_heartbeat(&path, second);
+
+        // verify:
+        assert!(first_res.is_ok());
+        assert!(second_res.is_ok());
+        // previous heartbeat is replaced, not appended to
+        assert_eq!(
+            "2023-11-14T22:14:20Z\n",
+            std::fs::read_to_string(&path).unwrap()
+        );
+        // no temporary file should be left behind
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+
+    #[test]
+    fn write_heartbeat_missing_dir_test() {
+        // setup:
+        let dir = temp_dir("missing");
+        let path = dir.join("not-a-dir").join("last-sync");
+
+        // execute:
+        let res = write_heartbeat(&path, SystemTime::now());
+
+        // verify:
+        assert!(matches!(res, Err(HeartbeatError::CannotWrite { .. })));
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+
+    #[test]
+    fn check_heartbeat_test() {
+        struct TestCase<'a> {
+            content: Option<&'a str>,
+            expected: Result<Duration, &'a str>,
+            _description: &'a str,
+        }
+
+        let dir = temp_dir("check");
+        let path = dir.join("last-sync");
+        let max_age = Duration::from_secs(180);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let test_cases = vec![
+            TestCase {
+                content: Some("2023-11-14T22:12:20Z\n"),
+                expected: Ok(Duration::from_secs(60)),
+                _description: "case 1 - recent heartbeat",
+            },
+            TestCase {
+                content: Some("2023-11-14T22:10:20Z"),
+                expected: Ok(Duration::from_secs(180)),
+                _description: "case 2 - heartbeat exactly max age old",
+            },
+            TestCase {
+                content: Some("2023-11-14T22:10:19Z"),
+                expected: Err("TooOld"),
+                _description: "case 3 - heartbeat older than max age",
+            },
+            TestCase {
+                content: Some("2023-11-14T22:20:00Z"),
+                expected: Ok(Duration::ZERO),
+                _description: "case 4 - heartbeat in the future",
+            },
+            TestCase {
+                content: Some("yesterday"),
+                expected: Err("InvalidHeartbeat"),
+                _description: "case 5 - invalid content",
+            },
+            TestCase {
+                content: None,
+                expected: Err("CannotRead"),
+                _description: "case 6 - missing file, no sync succeeded yet",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            match tc.content {
+                Some(content) => std::fs::write(&path, content).unwrap(),
+                None => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+
+            // execute:
+            let res = check_heartbeat(&path, max_age, now);
+
+            // verify:
+            match (tc.expected, res) {
+                (Ok(expected_age), Ok(age)) => {
+                    assert_eq!(expected_age, age, "{}", tc._description)
+                }
+                (Err("TooOld"), Err(HeartbeatError::TooOld { .. }))
+                | (Err("InvalidHeartbeat"), Err(HeartbeatError::InvalidHeartbeat { .. }))
+                | (Err("CannotRead"), Err(HeartbeatError::CannotRead { .. })) => {}
+                (expected, res) => {
+                    panic!("{}: expected {expected:?}, got {res:?}", tc._description)
+                }
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+}
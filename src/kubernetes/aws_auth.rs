@@ -1,144 +1,1147 @@
-use crate::kubernetes::{KubernetesRole, KubernetesUser, SyncedBy};
-use std::collections::HashSet;
+use crate::kubernetes::{
+    IamArn, KubernetesError, KubernetesGroupName, KubernetesRole, KubernetesUser, SyncedBy,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Add;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
 
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct AwsAuth {
-    pub users: HashSet<KubernetesUser>,
-    pub roles: HashSet<KubernetesRole>,
+    pub users: BTreeSet<KubernetesUser>,
+    pub roles: BTreeSet<KubernetesRole>,
+}
+
+impl AwsAuth {
+    /// Hash of users and roles content, independent from sets iteration order.
+    pub fn content_hash(&self) -> u64 {
+        #[allow(clippy::type_complexity)]
+        let mut users: Vec<(
+            String,
+            String,
+            Vec<String>,
+            Option<SyncedBy>,
+            Option<SystemTime>,
+        )> = self
+            .users
+            .iter()
+            .map(|u| {
+                (
+                    u.iam_arn.to_string(),
+                    u.iam_user_name.to_string(),
+                    u.roles.iter().map(|g| g.to_string()).collect(),
+                    u.synced_by.clone(),
+                    u.synced_at,
+                )
+            })
+            .collect();
+        users.sort();
+
+        #[allow(clippy::type_complexity)]
+        let mut roles: Vec<(
+            String,
+            Option<String>,
+            Option<String>,
+            Vec<String>,
+            Option<SyncedBy>,
+            Option<SystemTime>,
+        )> = self
+            .roles
+            .iter()
+            .map(|r| {
+                (
+                    r.iam_role_arn.to_string(),
+                    r.role_name.clone(),
+                    r.user_name.clone(),
+                    r.groups.iter().map(|g| g.to_string()).collect(),
+                    r.synced_by.clone(),
+                    r.synced_at,
+                )
+            })
+            .collect();
+        roles.sort();
+
+        let mut hasher = DefaultHasher::new();
+        users.hash(&mut hasher);
+        roles.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Computes changes needed to go from this aws-auth to the `desired` one, entries not marked with `synced_by` being untouched.
+    pub fn diff(&self, desired: &AwsAuth, synced_by: &SyncedBy) -> AwsAuthDiff {
+        AwsAuthDiff {
+            users: EntriesDiff::new(&self.users, &desired.users, synced_by),
+            roles: EntriesDiff::new(&self.roles, &desired.roles, synced_by),
+        }
+    }
+
+    /// Earliest time an entry marked with `synced_by` expires, `None` without TTL nor timestamped synced entry.
+    pub fn next_expiry(&self, synced_by: &SyncedBy, ttl: Option<Duration>) -> Option<SystemTime> {
+        let synced_at = self
+            .users
+            .iter()
+            .filter(|u| u.is_synced_by(synced_by))
+            .filter_map(|u| u.synced_at)
+            .chain(
+                self.roles
+                    .iter()
+                    .filter(|r| r.is_synced_by(synced_by))
+                    .filter_map(|r| r.synced_at),
+            )
+            .min()?;
+
+        synced_at.checked_add(ttl?)
+    }
+}
+
+/// Identity of an aws-auth entry: lowercase ARN and optional names, matching entries `Hash` implementation. Borrowed
+/// from the entry, as entries are compared by identity on each ordered set operation.
+type EntryKey<'a> = (&'a str, Option<Cow<'a, str>>, Option<Cow<'a, str>>);
+
+/// `name` lowercased, borrowed if it already is, as names mostly are.
+fn lowercase(name: &str) -> Cow<'_, str> {
+    match name.chars().any(char::is_uppercase) {
+        true => Cow::Owned(name.to_lowercase()),
+        false => Cow::Borrowed(name),
+    }
+}
+
+/// Behaviour shared by `mapUsers` and `mapRoles` entries.
+pub trait AwsAuthEntry: Clone + Ord + Hash {
+    /// Entry kind, used in logs.
+    const KIND: &'static str;
+
+    fn key(&self) -> EntryKey<'_>;
+    /// Whether `other` is an entry of the same IAM identity, whatever its groups, e.q: a user whose groups changed.
+    /// Equality tells entries of the same identity having different groups apart.
+    fn same_identity(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+    /// Whether the entry is written as `other`, names case included, which equality may ignore.
+    fn is_written_as(&self, other: &Self) -> bool;
+    fn arn(&self) -> &IamArn;
+    fn groups_mut(&mut self) -> &mut BTreeSet<KubernetesGroupName>;
+    fn is_synced_by(&self, synced_by: &SyncedBy) -> bool;
+    /// Whether the entry is marked by another mapper instance than `synced_by`, such entries being never touched.
+    fn is_synced_by_other(&self, synced_by: &SyncedBy) -> bool;
+    fn synced_by(&self) -> Option<&SyncedBy>;
+    fn set_synced_by(&mut self, synced_by: SyncedBy);
+    fn synced_at(&self) -> Option<SystemTime>;
+    fn set_synced_at(&mut self, synced_at: SystemTime);
+    /// Whether the entry is read back as `other` once serialized, names case, marker and sync time included.
+    fn is_read_back_as(&self, other: &Self) -> bool {
+        self.is_written_as(other)
+            && self.synced_by() == other.synced_by()
+            && self.synced_at() == other.synced_at()
+    }
+}
+
+impl AwsAuthEntry for KubernetesUser {
+    const KIND: &'static str = "user";
+
+    fn key(&self) -> EntryKey<'_> {
+        (
+            self.iam_arn.lowercase(),
+            Some(Cow::Borrowed(self.iam_user_name.lowercase())),
+            None,
+        )
+    }
+
+    fn is_written_as(&self, other: &Self) -> bool {
+        self == other && self.iam_arn == other.iam_arn && self.iam_user_name == other.iam_user_name
+    }
+
+    fn arn(&self) -> &IamArn {
+        &self.iam_arn
+    }
+
+    fn groups_mut(&mut self) -> &mut BTreeSet<KubernetesGroupName> {
+        &mut self.roles
+    }
+
+    fn is_synced_by(&self, synced_by: &SyncedBy) -> bool {
+        self.synced_by.as_ref() == Some(synced_by)
+    }
+
+    fn is_synced_by_other(&self, synced_by: &SyncedBy) -> bool {
+        self.synced_by.as_ref().is_some_and(|s| s != synced_by)
+    }
+
+    fn synced_by(&self) -> Option<&SyncedBy> {
+        self.synced_by.as_ref()
+    }
+
+    fn set_synced_by(&mut self, synced_by: SyncedBy) {
+        self.synced_by = Some(synced_by);
+    }
+
+    fn synced_at(&self) -> Option<SystemTime> {
+        self.synced_at
+    }
+
+    fn set_synced_at(&mut self, synced_at: SystemTime) {
+        self.synced_at = Some(synced_at);
+    }
+}
+
+impl AwsAuthEntry for KubernetesRole {
+    const KIND: &'static str = "role";
+
+    fn key(&self) -> EntryKey<'_> {
+        (
+            self.iam_role_arn.lowercase(),
+            self.role_name.as_deref().map(lowercase),
+            self.user_name.as_deref().map(lowercase),
+        )
+    }
+
+    fn is_written_as(&self, other: &Self) -> bool {
+        self == other
+            && self.iam_role_arn == other.iam_role_arn
+            && self.role_name == other.role_name
+            && self.user_name == other.user_name
+    }
+
+    fn arn(&self) -> &IamArn {
+        &self.iam_role_arn
+    }
+
+    fn groups_mut(&mut self) -> &mut BTreeSet<KubernetesGroupName> {
+        &mut self.groups
+    }
+
+    fn is_synced_by(&self, synced_by: &SyncedBy) -> bool {
+        self.synced_by.as_ref() == Some(synced_by)
+    }
+
+    fn is_synced_by_other(&self, synced_by: &SyncedBy) -> bool {
+        self.synced_by.as_ref().is_some_and(|s| s != synced_by)
+    }
+
+    fn synced_by(&self) -> Option<&SyncedBy> {
+        self.synced_by.as_ref()
+    }
+
+    fn set_synced_by(&mut self, synced_by: SyncedBy) {
+        self.synced_by = Some(synced_by);
+    }
+
+    fn synced_at(&self) -> Option<SystemTime> {
+        self.synced_at
+    }
+
+    fn set_synced_at(&mut self, synced_at: SystemTime) {
+        self.synced_at = Some(synced_at);
+    }
+}
+
+/// Changes of a set of aws-auth entries, entries being matched on their identity and sorted by ARN.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntriesDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    /// Entries whose content changed, as (existing, desired).
+    pub modified: Vec<(T, T)>,
+    /// Entries not managed by the tool, kept as is.
+    pub untouched: Vec<T>,
+    /// Entries managed by the tool kept as is.
+    pub unchanged: Vec<T>,
+    /// Entries which would have been added, left out since only removals are synced.
+    pub suppressed: Vec<T>,
+}
+
+impl<T> Default for EntriesDiff<T> {
+    fn default() -> Self {
+        EntriesDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+            untouched: Vec::new(),
+            unchanged: Vec::new(),
+            suppressed: Vec::new(),
+        }
+    }
+}
+
+/// Diffs of several sets of entries put together, e.q: of all synced clusters.
+impl<T> Add for EntriesDiff<T> {
+    type Output = EntriesDiff<T>;
+
+    fn add(mut self, other: EntriesDiff<T>) -> EntriesDiff<T> {
+        self.added.extend(other.added);
+        self.removed.extend(other.removed);
+        self.modified.extend(other.modified);
+        self.untouched.extend(other.untouched);
+        self.unchanged.extend(other.unchanged);
+        self.suppressed.extend(other.suppressed);
+
+        self
+    }
+}
+
+impl<T: AwsAuthEntry> EntriesDiff<T> {
+    fn new(existing: &BTreeSet<T>, desired: &BTreeSet<T>, synced_by: &SyncedBy) -> Self {
+        let existing: BTreeMap<EntryKey, &T> = existing.iter().map(|e| (e.key(), e)).collect();
+        let desired: BTreeMap<EntryKey, &T> = desired.iter().map(|e| (e.key(), e)).collect();
+
+        let mut diff = EntriesDiff::default();
+        for (k, desired_entry) in &desired {
+            match existing.get(k) {
+                None => diff.added.push((*desired_entry).clone()),
+                Some(existing_entry) if !existing_entry.is_written_as(desired_entry) => diff
+                    .modified
+                    .push(((*existing_entry).clone(), (*desired_entry).clone())),
+                Some(_) if !desired_entry.is_synced_by(synced_by) => {
+                    diff.untouched.push((*desired_entry).clone())
+                }
+                Some(_) => diff.unchanged.push((*desired_entry).clone()),
+            }
+        }
+        for (k, existing_entry) in &existing {
+            if !desired.contains_key(k) {
+                diff.removed.push((*existing_entry).clone());
+            }
+        }
+
+        diff
+    }
+
+    fn with_suppressed(mut self, suppressed: &BTreeSet<T>) -> Self {
+        let suppressed: BTreeMap<EntryKey, &T> = suppressed.iter().map(|e| (e.key(), e)).collect();
+        self.suppressed = suppressed.into_values().cloned().collect();
+
+        self
+    }
+}
+
+impl<T> EntriesDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    pub fn counts(&self) -> EntriesCounts {
+        EntriesCounts {
+            added: self.added.len(),
+            removed: self.removed.len(),
+            modified: self.modified.len(),
+            unchanged: self.unchanged.len() + self.untouched.len(),
+        }
+    }
+
+    /// Entries kept, modified ones with their new content, e.q: a user whose groups changed.
+    pub fn kept(&self) -> Vec<T>
+    where
+        T: Clone + Ord,
+    {
+        let mut kept: Vec<T> = self
+            .unchanged
+            .iter()
+            .chain(self.untouched.iter())
+            .chain(self.modified.iter().map(|(_, desired)| desired))
+            .cloned()
+            .collect();
+        kept.sort();
+        kept
+    }
+}
+
+/// Number of entries changed by a sync, displayed as `+added -removed ~modified =unchanged`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct EntriesCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    /// Entries kept as is, not managed by the tool ones included.
+    pub unchanged: usize,
+}
+
+impl Add for EntriesCounts {
+    type Output = EntriesCounts;
+
+    fn add(self, other: EntriesCounts) -> EntriesCounts {
+        EntriesCounts {
+            added: self.added + other.added,
+            removed: self.removed + other.removed,
+            modified: self.modified + other.modified,
+            unchanged: self.unchanged + other.unchanged,
+        }
+    }
+}
+
+impl Display for EntriesCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "+{} -{} ~{} ={}",
+            self.added, self.removed, self.modified, self.unchanged
+        )
+    }
+}
+
+/// Users and roles counts of an `AwsAuthDiff`, displayed as `users: +3 -1 ~0 =42, roles: +0 -0 ~0 =5`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct AwsAuthDiffSummary {
+    pub users: EntriesCounts,
+    pub roles: EntriesCounts,
+}
+
+/// Summaries of several aws-auth added up, e.q: of all synced clusters.
+impl Add for AwsAuthDiffSummary {
+    type Output = AwsAuthDiffSummary;
+
+    fn add(self, other: AwsAuthDiffSummary) -> AwsAuthDiffSummary {
+        AwsAuthDiffSummary {
+            users: self.users + other.users,
+            roles: self.roles + other.roles,
+        }
+    }
+}
+
+impl Display for AwsAuthDiffSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "users: {}, roles: {}", self.users, self.roles)
+    }
+}
+
+/// Users and roles changes between two aws-auth.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AwsAuthDiff {
+    pub users: EntriesDiff<KubernetesUser>,
+    pub roles: EntriesDiff<KubernetesRole>,
+}
+
+/// Diffs of several aws-auth put together, e.q: of all synced clusters.
+impl Add for AwsAuthDiff {
+    type Output = AwsAuthDiff;
+
+    fn add(self, other: AwsAuthDiff) -> AwsAuthDiff {
+        AwsAuthDiff {
+            users: self.users + other.users,
+            roles: self.roles + other.roles,
+        }
+    }
+}
+
+impl AwsAuthDiff {
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty() && self.roles.is_empty()
+    }
+
+    pub fn summary(&self) -> AwsAuthDiffSummary {
+        AwsAuthDiffSummary {
+            users: self.users.counts(),
+            roles: self.roles.counts(),
+        }
+    }
+
+    /// Records `suppressed` entries as additions left out, only removals being synced.
+    pub fn with_suppressed_additions(self, suppressed: &AwsAuth) -> AwsAuthDiff {
+        AwsAuthDiff {
+            users: self.users.with_suppressed(&suppressed.users),
+            roles: self.roles.with_suppressed(&suppressed.roles),
+        }
+    }
+}
+
+/// Lists changes, one per line, followed by suppressed additions. Alternate format (`{:#}`) lists untouched entries as well.
+impl Display for AwsAuthDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn groups(groups: &BTreeSet<KubernetesGroupName>) -> String {
+            groups
+                .iter()
+                .map(|g| g.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        fn user(u: &KubernetesUser) -> String {
+            format!(
+                "(username: {}, groups: [{}])",
+                u.iam_user_name,
+                groups(&u.roles)
+            )
+        }
+        fn role(r: &KubernetesRole) -> String {
+            let mut names = String::new();
+            if let Some(role_name) = &r.role_name {
+                names.push_str(&format!("rolename: {role_name}, "));
+            }
+            if let Some(user_name) = &r.user_name {
+                names.push_str(&format!("username: {user_name}, "));
+            }
+            format!("({names}groups: [{}])", groups(&r.groups))
+        }
+
+        for (sign, users) in [("+", &self.users.added), ("-", &self.users.removed)] {
+            for u in users {
+                writeln!(f, "{sign} user `{}` {}", u.iam_arn, user(u))?;
+            }
+        }
+        for (existing, desired) in &self.users.modified {
+            writeln!(
+                f,
+                "~ user `{}` {} -> {}",
+                desired.iam_arn,
+                user(existing),
+                user(desired)
+            )?;
+        }
+        for (sign, roles) in [("+", &self.roles.added), ("-", &self.roles.removed)] {
+            for r in roles {
+                writeln!(f, "{sign} role `{}` {}", r.iam_role_arn, role(r))?;
+            }
+        }
+        for (existing, desired) in &self.roles.modified {
+            writeln!(
+                f,
+                "~ role `{}` {} -> {}",
+                desired.iam_role_arn,
+                role(existing),
+                role(desired)
+            )?;
+        }
+        for u in &self.users.suppressed {
+            writeln!(
+                f,
+                "! user `{}` {} (addition suppressed, removals only)",
+                u.iam_arn,
+                user(u)
+            )?;
+        }
+        for r in &self.roles.suppressed {
+            writeln!(
+                f,
+                "! role `{}` {} (addition suppressed, removals only)",
+                r.iam_role_arn,
+                role(r)
+            )?;
+        }
+
+        if f.alternate() {
+            for u in &self.users.untouched {
+                writeln!(
+                    f,
+                    "= user `{}` {} (not managed by the tool)",
+                    u.iam_arn,
+                    user(u)
+                )?;
+            }
+            for r in &self.roles.untouched {
+                writeln!(
+                    f,
+                    "= role `{}` {} (not managed by the tool)",
+                    r.iam_role_arn,
+                    role(r)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Refuses changes removing too many entries synced by the tool at once, e.q: on IAM outage returning empty groups.
+#[derive(Clone, Debug)]
+pub struct RemovalGuard {
+    /// Maximum fraction of currently synced entries which can be removed at once, from 0 to 1.
+    pub max_removal_fraction: f64,
+    /// Maximum number of entries which can be removed at once.
+    pub max_removals: Option<usize>,
+    /// Bypasses the guard, allowing any removal.
+    pub force: bool,
+}
+
+impl Default for RemovalGuard {
+    fn default() -> Self {
+        RemovalGuard {
+            max_removal_fraction: 1.0,
+            max_removals: None,
+            force: false,
+        }
+    }
+}
+
+impl RemovalGuard {
+    /// Fails if going from `existing` aws-auth with `diff` changes removes more entries marked with `synced_by` than allowed.
+    pub fn check(
+        &self,
+        existing: &AwsAuth,
+        diff: &AwsAuthDiff,
+        synced_by: &SyncedBy,
+    ) -> Result<(), KubernetesError> {
+        if self.force {
+            return Ok(());
+        }
+
+        let synced_entries = existing
+            .users
+            .iter()
+            .filter(|u| u.is_synced_by(synced_by))
+            .count()
+            + existing
+                .roles
+                .iter()
+                .filter(|r| r.is_synced_by(synced_by))
+                .count();
+        let removals = diff.users.removed.len() + diff.roles.removed.len();
+        if removals == 0 {
+            return Ok(());
+        }
+
+        // rounded up, for the fraction not to refuse any removal out of a few synced entries, e.q: 1 of 1 at 0.5
+        let max_fraction_removals = (synced_entries as f64 * self.max_removal_fraction).ceil();
+        let too_many_removals = self.max_removals.is_some_and(|max| removals > max)
+            || removals as f64 > max_fraction_removals;
+        match too_many_removals {
+            false => Ok(()),
+            true => Err(KubernetesError::TooManyRemovals {
+                removals,
+                synced_entries,
+                removed_entries: Arc::from(
+                    AwsAuthDiff {
+                        users: EntriesDiff {
+                            removed: diff.users.removed.clone(),
+                            ..Default::default()
+                        },
+                        roles: EntriesDiff {
+                            removed: diff.roles.removed.clone(),
+                            ..Default::default()
+                        },
+                    }
+                    .to_string(),
+                ),
+            }),
+        }
+    }
 }
 
 pub struct AwsAuthBuilder {
-    users: HashSet<KubernetesUser>,
-    roles: HashSet<KubernetesRole>,
+    users: BTreeSet<KubernetesUser>,
+    roles: BTreeSet<KubernetesRole>,
+    // marker of entries managed by this instance
+    synced_by: SyncedBy,
+    // entries previously synced by this instance, only kept if protected
+    previously_synced_users: BTreeSet<KubernetesUser>,
+    previously_synced_roles: BTreeSet<KubernetesRole>,
+    // lowercase ARNs
+    protected_arns: BTreeSet<String>,
+    // synced entries older than this are removed
+    synced_entry_ttl: Option<Duration>,
+    sync_time: SystemTime,
+    // unmanaged entries having the ARN of a synced one are replaced by it
+    adopt_existing_entries: bool,
+    // unmanaged entries matching it are adopted even if not synced
+    adopt_arn_pattern: Option<Regex>,
+    // only previously synced entries no longer synced are removed, nothing being added, modified nor adopted
+    removals_only: bool,
+    // existing entries, synced ones included, are left as they are
+    keep_users: bool,
+    keep_roles: bool,
 
-    new_synced_users: HashSet<KubernetesUser>,
-    new_synced_roles: HashSet<KubernetesRole>,
+    new_synced_users: BTreeSet<KubernetesUser>,
+    new_synced_roles: BTreeSet<KubernetesRole>,
 }
 
 impl AwsAuthBuilder {
-    pub fn new(users: HashSet<KubernetesUser>, roles: HashSet<KubernetesRole>) -> AwsAuthBuilder {
+    /// Entries marked with `synced_by` are managed by this instance, others (third-party or other instances ones) are kept.
+    pub fn new(
+        users: impl IntoIterator<Item = KubernetesUser>,
+        roles: impl IntoIterator<Item = KubernetesRole>,
+        synced_by: SyncedBy,
+    ) -> AwsAuthBuilder {
+        // removing all users and roles managed by this instance (allowing to delete previously synced ones)
+        let (previously_synced_users, users) =
+            users.into_iter().partition(|u| u.is_synced_by(&synced_by));
+        let (previously_synced_roles, roles) =
+            roles.into_iter().partition(|r| r.is_synced_by(&synced_by));
+
         AwsAuthBuilder {
-            users: users
-                .into_iter()
-                .filter(|u| match u.synced_by {
-                    // removing all users managed by the tool (allowing to delete previously synced users)
-                    Some(SyncedBy::IamEksUserMapper) => false,
-                    _ => true,
-                })
-                .collect(),
-            roles: roles
-                .into_iter()
-                .filter(|r| match r.synced_by {
-                    // removing all roles managed by the tool (allowing to delete previously synced users)
-                    Some(SyncedBy::IamEksUserMapper) => false,
-                    _ => true,
-                })
-                .collect(),
+            users,
+            roles,
+            synced_by,
+            previously_synced_users,
+            previously_synced_roles,
+            protected_arns: BTreeSet::default(),
+            synced_entry_ttl: None,
+            sync_time: seconds_precision(SystemTime::now()),
+            adopt_existing_entries: false,
+            adopt_arn_pattern: None,
+            removals_only: false,
+            keep_users: false,
+            keep_roles: false,
 
-            new_synced_users: HashSet::default(),
-            new_synced_roles: HashSet::default(),
+            new_synced_users: BTreeSet::default(),
+            new_synced_roles: BTreeSet::default(),
         }
     }
 
-    pub fn new_synced_users(&mut self, u: HashSet<KubernetesUser>) -> &mut Self {
-        self.new_synced_users = u
+    /// Users sharing an ARN, e.q: found by group sync and set in `--map-users`, are merged into one holding all their groups.
+    pub fn new_synced_users(&mut self, u: impl IntoIterator<Item = KubernetesUser>) -> &mut Self {
+        self.new_synced_users = Self::merge_same_arn_entries(u)
             .into_iter()
-            .map(|u| KubernetesUser::new_synced_from(u, SyncedBy::IamEksUserMapper))
+            .map(|u| KubernetesUser::new_synced_from(u, self.synced_by.clone()))
             .collect(); // make sure those users are set to synced
 
         self
     }
 
-    pub fn new_synced_roles(&mut self, r: HashSet<KubernetesRole>) -> &mut Self {
-        self.new_synced_roles = r
+    /// Roles sharing an ARN are merged into one holding all their groups.
+    pub fn new_synced_roles(&mut self, r: impl IntoIterator<Item = KubernetesRole>) -> &mut Self {
+        self.new_synced_roles = Self::merge_same_arn_entries(r)
             .into_iter()
-            .map(|r| KubernetesRole::new_synced_from(r, SyncedBy::IamEksUserMapper))
+            .map(|r| KubernetesRole::new_synced_from(r, self.synced_by.clone()))
+            .collect();
+
+        self
+    }
+
+    /// Existing entries having one of those ARNs are never removed nor modified, even if synced by the tool.
+    pub fn protected_arns(&mut self, arns: &[IamArn]) -> &mut Self {
+        self.protected_arns = arns
+            .iter()
+            .map(|arn| arn.to_string().to_lowercase())
             .collect();
 
         self
     }
 
+    /// Synced entries first synced more than `ttl` ago are removed, even if still granted access.
+    pub fn synced_entry_ttl(&mut self, ttl: Option<Duration>) -> &mut Self {
+        self.synced_entry_ttl = ttl;
+
+        self
+    }
+
+    /// Unmanaged entries having the ARN of a synced entry are adopted: replaced by the synced entry instead of being kept alongside it.
+    pub fn adopt_existing_entries(&mut self, adopt: bool) -> &mut Self {
+        self.adopt_existing_entries = adopt;
+
+        self
+    }
+
+    /// Unmanaged entries whose ARN matches `pattern` are adopted even if not synced, i.e: marked as synced so next syncs remove them.
+    pub fn adopt_arn_pattern(&mut self, pattern: Option<Regex>) -> &mut Self {
+        self.adopt_arn_pattern = pattern;
+
+        self
+    }
+
+    /// Only removes previously synced entries no longer synced, new entries being suppressed and synced ones kept as written,
+    /// e.q: when additions go through a manual approval process.
+    pub fn removals_only(&mut self, removals_only: bool) -> &mut Self {
+        self.removals_only = removals_only;
+
+        self
+    }
+
+    /// Existing users, synced ones included, are left as they are, nothing being added, removed nor adopted,
+    /// e.q: when group users sync is disabled.
+    pub fn keep_users(&mut self, keep_users: bool) -> &mut Self {
+        self.keep_users = keep_users;
+
+        self
+    }
+
+    /// Existing roles, synced ones included, are left as they are, nothing being added, removed nor adopted.
+    pub fn keep_roles(&mut self, keep_roles: bool) -> &mut Self {
+        self.keep_roles = keep_roles;
+
+        self
+    }
+
+    /// Time set on newly synced entries and against which their TTL is checked, defaults to builder creation time.
+    #[cfg(test)]
+    pub fn sync_time(&mut self, sync_time: SystemTime) -> &mut Self {
+        self.sync_time = seconds_precision(sync_time);
+
+        self
+    }
+
     pub fn build(&self) -> AwsAuth {
+        let new_synced_users = self.removals_only_entries(
+            self.timestamp_entries(&self.new_synced_users, &self.previously_synced_users),
+            &self.previously_synced_users,
+        );
+        let new_synced_roles = self.removals_only_entries(
+            self.timestamp_entries(&self.new_synced_roles, &self.previously_synced_roles),
+            &self.previously_synced_roles,
+        );
+
         // computing users
-        let mut kubernetes_users: HashSet<KubernetesUser> = HashSet::from_iter(
-            self.users
-                .clone()
+        let mut kubernetes_users: BTreeSet<KubernetesUser> = BTreeSet::from_iter(
+            self.adopt_entries(&self.users, &new_synced_users)
                 .into_iter()
-                // remove users already there but not flagged as synced since those will be added
-                .filter(|u| !self.new_synced_users.contains(u)),
+                // remove users already there but not flagged as synced since those will be added, same identity ones
+                // having other groups being kept alongside unless adopted, other instances' ones being always kept
+                // so that instances syncing the same entry do not overwrite each other's marker
+                .filter(|u| u.is_synced_by_other(&self.synced_by) || !new_synced_users.contains(u)),
         );
         // adding new synced users
-        kubernetes_users.extend(self.new_synced_users.clone());
+        kubernetes_users.extend(new_synced_users.clone());
 
         // computing roles
-        let mut kubernetes_roles: HashSet<KubernetesRole> = HashSet::from_iter(
-            self.roles
-                .clone()
+        let mut kubernetes_roles: BTreeSet<KubernetesRole> = BTreeSet::from_iter(
+            self.adopt_entries(&self.roles, &new_synced_roles)
                 .into_iter()
-                // remove roles already there but not flagged as synced since those will be added
-                .filter(|r| !self.new_synced_roles.contains(r)),
+                // remove roles already there but not flagged as synced since those will be added, same identity ones
+                // having other groups being kept alongside unless adopted, other instances' ones being always kept
+                .filter(|r| r.is_synced_by_other(&self.synced_by) || !new_synced_roles.contains(r)),
         );
         // adding new synced roles
-        kubernetes_roles.extend(self.new_synced_roles.clone());
+        kubernetes_roles.extend(new_synced_roles.clone());
 
+        let initial_aws_auth = self.initial_aws_auth();
         AwsAuth {
-            users: kubernetes_users,
-            roles: kubernetes_roles,
+            users: match self.keep_users {
+                true => initial_aws_auth.users,
+                false => self.keep_protected_entries(
+                    kubernetes_users,
+                    self.users.iter().chain(&self.previously_synced_users),
+                    &new_synced_users,
+                ),
+            },
+            roles: match self.keep_roles {
+                true => initial_aws_auth.roles,
+                false => self.keep_protected_entries(
+                    kubernetes_roles,
+                    self.roles.iter().chain(&self.previously_synced_roles),
+                    &new_synced_roles,
+                ),
+            },
         }
     }
-}
 
-impl From<AwsAuth> for AwsAuthBuilder {
-    fn from(value: AwsAuth) -> Self {
-        AwsAuthBuilder {
-            users: value.users,
-            roles: value.roles,
+    /// Built aws-auth and its changes from the entries the builder was created with, suppressed additions included.
+    pub fn build_with_report(&self) -> (AwsAuth, AwsAuthDiff) {
+        let initial_aws_auth = self.initial_aws_auth();
+        let aws_auth = self.build();
+        let diff = initial_aws_auth
+            .diff(&aws_auth, &self.synced_by)
+            .with_suppressed_additions(&self.suppressed_additions());
+
+        (aws_auth, diff)
+    }
 
-            new_synced_users: HashSet::default(),
-            new_synced_roles: HashSet::default(),
+    /// Entries the builder was created with.
+    fn initial_aws_auth(&self) -> AwsAuth {
+        AwsAuth {
+            users: self
+                .users
+                .iter()
+                .chain(&self.previously_synced_users)
+                .cloned()
+                .collect(),
+            roles: self
+                .roles
+                .iter()
+                .chain(&self.previously_synced_roles)
+                .cloned()
+                .collect(),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::kubernetes::aws_auth::AwsAuthBuilder;
-    use crate::kubernetes::{
-        IamArn, IamUserName, KubernetesGroupName, KubernetesRole, KubernetesUser, SyncedBy,
-    };
-    use std::collections::HashSet;
+    /// Entries `build` leaves out in removals only mode: new synced entries not in aws-auth yet, empty otherwise.
+    pub fn suppressed_additions(&self) -> AwsAuth {
+        if !self.removals_only {
+            return AwsAuth::default();
+        }
 
-    #[test]
-    fn aws_auth_build_users_test() {
-        // setup:
-        struct TestCase<'a> {
-            existing_users: HashSet<KubernetesUser>,
-            new_users_to_be_added: HashSet<KubernetesUser>,
-            expected_users: HashSet<KubernetesUser>,
-            _description: &'a str,
+        AwsAuth {
+            users: Self::new_entries(
+                &self.new_synced_users,
+                self.users.iter().chain(&self.previously_synced_users),
+            ),
+            roles: Self::new_entries(
+                &self.new_synced_roles,
+                self.roles.iter().chain(&self.previously_synced_roles),
+            ),
         }
+    }
 
-        let test_cases = vec![
-            TestCase {
-                existing_users: HashSet::default(),
-                new_users_to_be_added: HashSet::default(),
-                expected_users: HashSet::default(),
-                _description: "case 1: no existing users, no new users",
-            },
-            TestCase {
-                existing_users: HashSet::default(),
-                new_users_to_be_added: HashSet::from_iter(vec![KubernetesUser::new(
-                    IamUserName::new("user_1"),
-                    IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    Some(SyncedBy::IamEksUserMapper),
-                )]),
-                expected_users: HashSet::from_iter(vec![KubernetesUser::new(
-                    IamUserName::new("user_1"),
+    /// `entries` with the ones sharing an ARN merged into the first of them by identity, holding the groups of all of them,
+    /// aws-iam-authenticator using a single entry per ARN.
+    fn merge_same_arn_entries<T: AwsAuthEntry>(
+        entries: impl IntoIterator<Item = T>,
+    ) -> BTreeSet<T> {
+        let mut merged_entries: Vec<T> = Vec::new();
+
+        // entries being ordered by identity, the ones sharing an ARN are next to each other
+        for mut entry in BTreeSet::from_iter(entries) {
+            match merged_entries
+                .last_mut()
+                .filter(|e| e.arn().lowercase() == entry.arn().lowercase())
+            {
+                Some(merged_entry) => {
+                    if !merged_entry.same_identity(&entry) {
+                        warn!(
+                            "Synced {} `{}` is set several times with different names, keeping the first one",
+                            T::KIND,
+                            entry.arn()
+                        );
+                    }
+                    let groups = std::mem::take(entry.groups_mut());
+                    merged_entry.groups_mut().extend(groups);
+                }
+                None => merged_entries.push(entry),
+            }
+        }
+
+        merged_entries.into_iter().collect()
+    }
+
+    /// `new_synced_entries` having no entry with the same identity in `existing_entries`.
+    fn new_entries<'a, T: AwsAuthEntry + 'a>(
+        new_synced_entries: &BTreeSet<T>,
+        existing_entries: impl Iterator<Item = &'a T>,
+    ) -> BTreeSet<T> {
+        let existing_keys: HashSet<EntryKey> = existing_entries.map(|e| e.key()).collect();
+        new_synced_entries
+            .iter()
+            .filter(|e| !existing_keys.contains(&e.key()))
+            .cloned()
+            .collect()
+    }
+
+    /// In removals only mode, previously synced entries still synced, as written, instead of `new_synced_entries`.
+    fn removals_only_entries<T: AwsAuthEntry>(
+        &self,
+        new_synced_entries: BTreeSet<T>,
+        previously_synced_entries: &BTreeSet<T>,
+    ) -> BTreeSet<T> {
+        if !self.removals_only {
+            return new_synced_entries;
+        }
+
+        let synced_keys: HashSet<EntryKey> = new_synced_entries.iter().map(|e| e.key()).collect();
+        previously_synced_entries
+            .iter()
+            .filter(|e| synced_keys.contains(&e.key()))
+            .cloned()
+            .collect()
+    }
+
+    /// Unmanaged `existing_entries` left once adopted ones are handled: the ones having the ARN of one of `new_synced_entries`
+    /// are dropped, being replaced by it, others matching the adoption pattern are marked as synced.
+    /// Protected entries are never adopted.
+    fn adopt_entries<T: AwsAuthEntry>(
+        &self,
+        existing_entries: &BTreeSet<T>,
+        new_synced_entries: &BTreeSet<T>,
+    ) -> BTreeSet<T> {
+        // adopting would modify unmanaged entries
+        if self.removals_only {
+            return existing_entries.clone();
+        }
+
+        let synced_arns: HashSet<&str> = new_synced_entries
+            .iter()
+            .map(|e| e.arn().lowercase())
+            .collect();
+
+        existing_entries
+            .iter()
+            .cloned()
+            .filter_map(|mut entry| {
+                let arn = entry.arn().to_string();
+                if self.protected_arns.contains(entry.arn().lowercase())
+                    || entry.is_synced_by_other(&self.synced_by)
+                {
+                    return Some(entry);
+                }
+                let matches_pattern = self
+                    .adopt_arn_pattern
+                    .as_ref()
+                    .is_some_and(|p| p.is_match(&arn));
+                let is_synced = synced_arns.contains(entry.arn().lowercase());
+                match (is_synced, self.adopt_existing_entries, matches_pattern) {
+                    (true, true, _) | (true, _, true) => {
+                        info!(
+                            "Adopting unmanaged {} `{arn}`, replacing it with the synced one",
+                            T::KIND
+                        );
+                        None
+                    }
+                    (false, _, true) => {
+                        info!(
+                            "Adopting unmanaged {} `{arn}` matching adoption pattern, it will be removed by next sync unless synced",
+                            T::KIND
+                        );
+                        entry.set_synced_by(self.synced_by.clone());
+                        entry.set_synced_at(self.sync_time);
+                        Some(entry)
+                    }
+                    _ => Some(entry),
+                }
+            })
+            .collect()
+    }
+
+    /// Sets `synced_at` of `new_synced_entries`, keeping the one of the matching previously synced entry if any.
+    /// Entries older than the TTL are dropped.
+    fn timestamp_entries<T: AwsAuthEntry>(
+        &self,
+        new_synced_entries: &BTreeSet<T>,
+        previously_synced_entries: &BTreeSet<T>,
+    ) -> BTreeSet<T> {
+        let previously_synced_at: BTreeMap<EntryKey, Option<SystemTime>> =
+            previously_synced_entries
+                .iter()
+                .map(|e| (e.key(), e.synced_at()))
+                .collect();
+
+        new_synced_entries
+            .iter()
+            .cloned()
+            .filter_map(|mut entry| {
+                let synced_at = previously_synced_at
+                    .get(&entry.key())
+                    .copied()
+                    .flatten()
+                    .unwrap_or(self.sync_time);
+                entry.set_synced_at(synced_at);
+
+                match self.synced_entry_ttl {
+                    Some(ttl)
+                        if synced_at
+                            .checked_add(ttl)
+                            .is_some_and(|expires_at| expires_at <= self.sync_time) =>
+                    {
+                        info!(
+                            "Synced {} `{}` is older than {}, removing it",
+                            T::KIND,
+                            entry.arn(),
+                            humantime::format_duration(ttl)
+                        );
+                        None
+                    }
+                    _ => Some(entry),
+                }
+            })
+            .collect()
+    }
+
+    /// Puts back `existing_entries` having a protected ARN into `entries`, replacing new entries with the same identity.
+    fn keep_protected_entries<'a, T: AwsAuthEntry + 'a>(
+        &self,
+        mut entries: BTreeSet<T>,
+        existing_entries: impl Iterator<Item = &'a T>,
+        new_synced_entries: &BTreeSet<T>,
+    ) -> BTreeSet<T> {
+        for protected_entry in
+            existing_entries.filter(|e| self.protected_arns.contains(e.arn().lowercase()))
+        {
+            match new_synced_entries
+                .iter()
+                .find(|e| e.same_identity(protected_entry))
+            {
+                None if protected_entry.is_synced_by(&self.synced_by) => warn!(
+                    "Protected {} `{}` would have been removed, keeping it",
+                    T::KIND,
+                    protected_entry.arn()
+                ),
+                Some(new_entry) if !new_entry.is_written_as(protected_entry) => warn!(
+                    "Protected {} `{}` would have been modified, keeping it as is",
+                    T::KIND,
+                    protected_entry.arn()
+                ),
+                _ => {}
+            }
+            entries.retain(|e| !e.same_identity(protected_entry));
+            entries.insert(protected_entry.clone());
+        }
+
+        entries
+    }
+}
+
+/// Truncates `time` to the second, `syncedAt` being written with this precision in the config map.
+fn seconds_precision(time: SystemTime) -> SystemTime {
+    SystemTime::UNIX_EPOCH
+        + Duration::from_secs(
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        )
+}
+
+impl From<AwsAuth> for AwsAuthBuilder {
+    fn from(value: AwsAuth) -> Self {
+        AwsAuthBuilder {
+            users: value.users,
+            roles: value.roles,
+            synced_by: SyncedBy::default(),
+            previously_synced_users: BTreeSet::default(),
+            previously_synced_roles: BTreeSet::default(),
+            protected_arns: BTreeSet::default(),
+            synced_entry_ttl: None,
+            sync_time: seconds_precision(SystemTime::now()),
+            adopt_existing_entries: false,
+            adopt_arn_pattern: None,
+            removals_only: false,
+            keep_users: false,
+            keep_roles: false,
+
+            new_synced_users: BTreeSet::default(),
+            new_synced_roles: BTreeSet::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::{
+        AwsAuth, AwsAuthBuilder, AwsAuthDiff, AwsAuthEntry, EntriesCounts, EntriesDiff,
+        RemovalGuard,
+    };
+    use crate::kubernetes::{
+        IamArn, IamUserName, KubernetesError, KubernetesGroupName, KubernetesRole, KubernetesUser,
+        SyncedBy,
+    };
+    use regex::Regex;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeSet;
+    use std::hash::{Hash, Hasher};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn aws_auth_build_users_test() {
+        // setup:
+        struct TestCase<'a> {
+            existing_users: BTreeSet<KubernetesUser>,
+            new_users_to_be_added: BTreeSet<KubernetesUser>,
+            expected_users: BTreeSet<KubernetesUser>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                existing_users: BTreeSet::default(),
+                new_users_to_be_added: BTreeSet::default(),
+                expected_users: BTreeSet::default(),
+                _description: "case 1: no existing users, no new users",
+            },
+            TestCase {
+                existing_users: BTreeSet::default(),
+                new_users_to_be_added: BTreeSet::from_iter(vec![KubernetesUser::new(
+                    IamUserName::new("user_1"),
+                    IamArn::new("arn::user_1"),
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )]),
+                expected_users: BTreeSet::from_iter(vec![KubernetesUser::new(
+                    IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
@@ -147,20 +1150,20 @@ mod tests {
                 _description: "case 2: no existing users, some new users",
             },
             TestCase {
-                existing_users: HashSet::from_iter(vec![KubernetesUser::new(
+                existing_users: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
                     None,
                 )]),
-                new_users_to_be_added: HashSet::default(),
-                expected_users: HashSet::from_iter(vec![KubernetesUser::new(
+                new_users_to_be_added: BTreeSet::default(),
+                expected_users: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
@@ -169,29 +1172,29 @@ mod tests {
                 _description: "case 3: existing users, no new users",
             },
             TestCase {
-                existing_users: HashSet::from_iter(vec![KubernetesUser::new(
+                existing_users: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
                     None,
                 )]),
-                new_users_to_be_added: HashSet::from_iter(vec![KubernetesUser::new(
+                new_users_to_be_added: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_2"),
                     IamArn::new("arn::user_2"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_2"),
                         KubernetesGroupName::new("group_3"),
                     ]),
                     Some(SyncedBy::IamEksUserMapper),
                 )]),
-                expected_users: HashSet::from_iter(vec![
+                expected_users: BTreeSet::from_iter(vec![
                     KubernetesUser::new(
                         IamUserName::new("user_1"),
                         IamArn::new("arn::user_1"),
-                        HashSet::from_iter(vec![
+                        BTreeSet::from_iter(vec![
                             KubernetesGroupName::new("group_1"),
                             KubernetesGroupName::new("group_2"),
                         ]),
@@ -200,7 +1203,7 @@ mod tests {
                     KubernetesUser::new(
                         IamUserName::new("user_2"),
                         IamArn::new("arn::user_2"),
-                        HashSet::from_iter(vec![
+                        BTreeSet::from_iter(vec![
                             KubernetesGroupName::new("group_2"),
                             KubernetesGroupName::new("group_3"),
                         ]),
@@ -210,28 +1213,28 @@ mod tests {
                 _description: "case 4: existing users, some new users",
             },
             TestCase {
-                existing_users: HashSet::from_iter(vec![KubernetesUser::new(
+                existing_users: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
                     None,
                 )]),
-                new_users_to_be_added: HashSet::from_iter(vec![KubernetesUser::new(
+                new_users_to_be_added: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
                     Some(SyncedBy::IamEksUserMapper),
                 )]),
-                expected_users: HashSet::from_iter(vec![KubernetesUser::new(
+                expected_users: BTreeSet::from_iter(vec![KubernetesUser::new(
                     IamUserName::new("user_1"),
                     IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
+                    BTreeSet::from_iter(vec![
                         KubernetesGroupName::new("group_1"),
                         KubernetesGroupName::new("group_2"),
                     ]),
@@ -243,294 +1246,1988 @@ mod tests {
 
         for tc in test_cases {
             // execute:
-            let result = AwsAuthBuilder::new(tc.existing_users, HashSet::default())
-                .new_synced_users(tc.new_users_to_be_added)
-                .build();
+            let result = AwsAuthBuilder::new(
+                tc.existing_users,
+                BTreeSet::default(),
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_users(tc.new_users_to_be_added)
+            .build();
+
+            // verify:
+            assert_eq!(tc.expected_users, result.users);
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_add_new_synced_users_should_add_synced_field_test() {
+        // setup:
+        let test_cases: Vec<BTreeSet<KubernetesUser>> = vec![
+            BTreeSet::default(),
+            BTreeSet::from_iter(vec![
+                KubernetesUser::new(
+                    IamUserName::new("user_1"),
+                    IamArn::new("arn::user_1"),
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    None,
+                ),
+                KubernetesUser::new(
+                    IamUserName::new("user_2"),
+                    IamArn::new("arn::user_2"),
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_3"),
+                    ]),
+                    None,
+                ),
+            ]),
+            BTreeSet::from_iter(vec![KubernetesUser::new(
+                IamUserName::new("user_2"),
+                IamArn::new("arn::user_2"),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("group_2"),
+                    KubernetesGroupName::new("group_3"),
+                ]),
+                Some(SyncedBy::Other("unknown".to_string())),
+            )]),
+            BTreeSet::from_iter(vec![KubernetesUser::new(
+                IamUserName::new("user_2"),
+                IamArn::new("arn::user_2"),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("group_2"),
+                    KubernetesGroupName::new("group_3"),
+                ]),
+                Some(SyncedBy::IamEksUserMapper),
+            )]),
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                BTreeSet::default(),
+                BTreeSet::default(),
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_users(tc.clone())
+            .build();
+
+            // verify:
+            assert_eq!(tc.len(), result.users.iter().len());
+            assert!(result
+                .users
+                .iter()
+                .all(|u| u.synced_by == Some(SyncedBy::IamEksUserMapper)));
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_merges_same_arn_entries_test() {
+        // setup:
+        let groups = |groups: &[&str]| -> BTreeSet<KubernetesGroupName> {
+            groups.iter().map(|g| KubernetesGroupName::new(g)).collect()
+        };
+        let user = |name: &str, arn: &str, user_groups: &[&str]| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(arn),
+                groups(user_groups),
+                None,
+            )
+        };
+        let role = |arn: &str, role_groups: &[&str]| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some("role".to_string()),
+                groups(role_groups),
+                None,
+            )
+        };
+
+        struct TestCase<'a> {
+            new_users_to_be_added: BTreeSet<KubernetesUser>,
+            new_roles_to_be_added: BTreeSet<KubernetesRole>,
+            expected_users: Vec<(&'a str, &'a str, BTreeSet<KubernetesGroupName>)>,
+            expected_roles: Vec<(&'a str, BTreeSet<KubernetesGroupName>)>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                new_users_to_be_added: BTreeSet::from_iter(vec![
+                    user("user_1", "arn::user_1", &["group_1"]),
+                    user("user_1", "arn::user_1", &["group_2"]),
+                ]),
+                new_roles_to_be_added: BTreeSet::from_iter(vec![
+                    role("arn::role_1", &["group_1"]),
+                    role("arn::role_1", &["group_2"]),
+                ]),
+                expected_users: vec![("user_1", "arn::user_1", groups(&["group_1", "group_2"]))],
+                expected_roles: vec![("arn::role_1", groups(&["group_1", "group_2"]))],
+                _description: "case 1 - same ARN with disjoint groups",
+            },
+            TestCase {
+                new_users_to_be_added: BTreeSet::from_iter(vec![
+                    user("user_1", "arn::user_1", &["group_1", "group_2"]),
+                    user("user_1", "arn::user_1", &["group_2", "group_3"]),
+                    user("user_2", "arn::user_2", &["group_1"]),
+                ]),
+                new_roles_to_be_added: BTreeSet::from_iter(vec![
+                    role("arn::role_1", &["group_1", "group_2"]),
+                    role("arn::role_1", &["group_2", "group_3"]),
+                    role("arn::role_2", &["group_1"]),
+                ]),
+                expected_users: vec![
+                    (
+                        "user_1",
+                        "arn::user_1",
+                        groups(&["group_1", "group_2", "group_3"]),
+                    ),
+                    ("user_2", "arn::user_2", groups(&["group_1"])),
+                ],
+                expected_roles: vec![
+                    ("arn::role_1", groups(&["group_1", "group_2", "group_3"])),
+                    ("arn::role_2", groups(&["group_1"])),
+                ],
+                _description: "case 2 - same ARN with overlapping groups",
+            },
+            TestCase {
+                new_users_to_be_added: BTreeSet::from_iter(vec![
+                    user("user_1", "arn::user_1", &["group_1"]),
+                    user("User_1_Alias", "ARN::USER_1", &["group_2"]),
+                ]),
+                new_roles_to_be_added: BTreeSet::new(),
+                expected_users: vec![("user_1", "arn::user_1", groups(&["group_1", "group_2"]))],
+                expected_roles: vec![],
+                _description: "case 3 - same ARN in another case with another username, first one by identity kept",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                BTreeSet::default(),
+                BTreeSet::default(),
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_users(tc.new_users_to_be_added)
+            .new_synced_roles(tc.new_roles_to_be_added)
+            .build();
+
+            // verify:
+            let mut users = result
+                .users
+                .iter()
+                .map(|u| {
+                    assert_eq!(
+                        Some(SyncedBy::IamEksUserMapper),
+                        u.synced_by,
+                        "{}",
+                        tc._description
+                    );
+                    (
+                        u.iam_user_name.to_string(),
+                        u.iam_arn.to_string(),
+                        u.roles.clone(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            users.sort_by(|a, b| a.1.cmp(&b.1));
+            assert_eq!(
+                tc.expected_users
+                    .into_iter()
+                    .map(|(name, arn, groups)| (name.to_string(), arn.to_string(), groups))
+                    .collect::<Vec<_>>(),
+                users,
+                "{}",
+                tc._description
+            );
+            let mut roles = result
+                .roles
+                .iter()
+                .map(|r| {
+                    assert_eq!(
+                        Some(SyncedBy::IamEksUserMapper),
+                        r.synced_by,
+                        "{}",
+                        tc._description
+                    );
+                    (r.iam_role_arn.to_string(), r.groups.clone())
+                })
+                .collect::<Vec<_>>();
+            roles.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                tc.expected_roles
+                    .into_iter()
+                    .map(|(arn, groups)| (arn.to_string(), groups))
+                    .collect::<Vec<_>>(),
+                roles,
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_roles_test() {
+        // setup:
+        struct TestCase<'a> {
+            existing_roles: BTreeSet<KubernetesRole>,
+            new_roles_to_be_added: BTreeSet<KubernetesRole>,
+            expected_roles: BTreeSet<KubernetesRole>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                existing_roles: BTreeSet::default(),
+                new_roles_to_be_added: BTreeSet::default(),
+                expected_roles: BTreeSet::default(),
+                _description: "case 1: no existing roles, no new roles",
+            },
+            TestCase {
+                existing_roles: BTreeSet::default(),
+                new_roles_to_be_added: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )]),
+                expected_roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )]),
+                _description: "case 2: no existing roles, some new roles",
+            },
+            TestCase {
+                existing_roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    None,
+                )]),
+                new_roles_to_be_added: BTreeSet::default(),
+                expected_roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    None,
+                )]),
+                _description: "case 3: existing roles, no new roles",
+            },
+            TestCase {
+                existing_roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    None,
+                )]),
+                new_roles_to_be_added: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_2"),
+                    Some("role_2".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_3"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )]),
+                expected_roles: BTreeSet::from_iter(vec![
+                    KubernetesRole::new(
+                        IamArn::new("arn::role_1"),
+                        Some("role_1".to_string()),
+                        None,
+                        BTreeSet::from_iter(vec![
+                            KubernetesGroupName::new("group_1"),
+                            KubernetesGroupName::new("group_2"),
+                        ]),
+                        None,
+                    ),
+                    KubernetesRole::new(
+                        IamArn::new("arn::role_2"),
+                        Some("role_2".to_string()),
+                        None,
+                        BTreeSet::from_iter(vec![
+                            KubernetesGroupName::new("group_2"),
+                            KubernetesGroupName::new("group_3"),
+                        ]),
+                        Some(SyncedBy::IamEksUserMapper),
+                    ),
+                ]),
+                _description: "case 4: existing roles, some new roles",
+            },
+            TestCase {
+                existing_roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    None,
+                )]),
+                new_roles_to_be_added: BTreeSet::from_iter(vec![KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )]),
+                expected_roles: BTreeSet::from_iter(vec![
+                    KubernetesRole::new(
+                        IamArn::new("arn::role_1"),
+                        Some("role_1".to_string()),
+                        None,
+                        BTreeSet::from_iter(vec![
+                            KubernetesGroupName::new("group_1"),
+                            KubernetesGroupName::new("group_2"),
+                        ]),
+                        Some(SyncedBy::IamEksUserMapper),
+                    ),
+                ]),
+                _description: "case 5: existing role without synced by flag, same new role with new synced by field",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                BTreeSet::default(),
+                tc.existing_roles,
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_roles(tc.new_roles_to_be_added)
+            .build();
+
+            // verify:
+            assert_eq!(tc.expected_roles, result.roles);
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_add_new_synced_roles_should_add_synced_field_test() {
+        // setup:
+        let test_cases: Vec<BTreeSet<KubernetesRole>> = vec![
+            BTreeSet::default(),
+            BTreeSet::from_iter(vec![
+                KubernetesRole::new(
+                    IamArn::new("arn::role_1"),
+                    Some("role_1".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    None,
+                ),
+                KubernetesRole::new(
+                    IamArn::new("arn::role_2"),
+                    Some("role_2".to_string()),
+                    None,
+                    BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_3"),
+                    ]),
+                    None,
+                ),
+            ]),
+            BTreeSet::from_iter(vec![KubernetesRole::new(
+                IamArn::new("arn::role_2"),
+                Some("role_2".to_string()),
+                None,
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("group_2"),
+                    KubernetesGroupName::new("group_3"),
+                ]),
+                Some(SyncedBy::Other("unknown".to_string())),
+            )]),
+            BTreeSet::from_iter(vec![KubernetesRole::new(
+                IamArn::new("arn::role_2"),
+                Some("role_2".to_string()),
+                None,
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("group_2"),
+                    KubernetesGroupName::new("group_3"),
+                ]),
+                Some(SyncedBy::IamEksUserMapper),
+            )]),
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                BTreeSet::default(),
+                BTreeSet::default(),
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_roles(tc.clone())
+            .build();
+
+            // verify:
+            assert_eq!(tc.len(), result.roles.iter().len());
+            assert!(result
+                .roles
+                .iter()
+                .all(|u| u.synced_by == Some(SyncedBy::IamEksUserMapper)));
+        }
+    }
+
+    #[test]
+    fn aws_auth_entries_identity_test() {
+        // setup:
+        fn hash(entry: &impl Hash) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            entry.hash(&mut hasher);
+            hasher.finish()
+        }
+        let user = |name: &str, arn: &str, groups: &[&str]| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(arn),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                None,
+            )
+        };
+        let role = |arn: &str, user_name: &str, groups: &[&str]| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some(user_name.to_string()),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                None,
+            )
+        };
+        let alice = user("alice", "arn:aws:iam::123:user/alice", &["view"]);
+        let admin = role("arn:aws:iam::123:role/admin", "admin", &["view"]);
+
+        // execute & verify:
+        // equal entries, names case aside, hash the same
+        let alice_other_case = user("Alice", "arn:aws:iam::123:user/Alice", &["view"]);
+        assert_eq!(alice, alice_other_case);
+        assert_eq!(hash(&alice), hash(&alice_other_case));
+        assert!(!alice.is_written_as(&alice_other_case));
+        let admin_other_case = role("arn:aws:iam::123:role/Admin", "Admin", &["view"]);
+        assert_eq!(admin, admin_other_case);
+        assert_eq!(hash(&admin), hash(&admin_other_case));
+        assert!(!admin.is_written_as(&admin_other_case));
+        // ordering is consistent with equality
+        assert_eq!(Ordering::Equal, alice.cmp(&alice_other_case));
+        assert_eq!(Ordering::Equal, admin.cmp(&admin_other_case));
+        // entries whose groups changed are the same identity, but not equal
+        let alice_regrouped = user("alice", "arn:aws:iam::123:user/alice", &["edit"]);
+        assert_ne!(alice, alice_regrouped);
+        assert_ne!(Ordering::Equal, alice.cmp(&alice_regrouped));
+        assert!(alice.same_identity(&alice_regrouped));
+        let admin_regrouped = role("arn:aws:iam::123:role/Admin", "admin", &["edit"]);
+        assert_ne!(admin, admin_regrouped);
+        assert!(admin.same_identity(&admin_regrouped));
+        // other identities
+        assert!(!alice.same_identity(&user("bob", "arn:aws:iam::123:user/alice", &["view"])));
+        assert!(!admin.same_identity(&role("arn:aws:iam::123:role/admin", "root", &["view"])));
+    }
+
+    #[test]
+    fn aws_auth_build_iteration_order_test() {
+        // setup:
+        let user = |name: &str, arn: &str| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(arn),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("view")]),
+                None,
+            )
+        };
+        let role = |arn: &str, role_name: &str| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                Some(role_name.to_string()),
+                None,
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("view")]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let existing_users = vec![
+            user("zoe", "arn:aws:iam::123:user/zoe"),
+            user("Bob", "arn:aws:iam::123:user/Bob"),
+        ];
+        let new_synced_users = vec![
+            user("carol", "arn:aws:iam::123:user/carol"),
+            user("alice", "arn:aws:iam::123:user/alice"),
+        ];
+        let new_synced_roles = vec![
+            role("arn:aws:iam::123:role/nodes", "nodes"),
+            role("arn:aws:iam::123:role/Admin", "Admin"),
+            role("arn:aws:iam::123:role/karpenter", "karpenter"),
+        ];
+
+        // execute:
+        let result =
+            AwsAuthBuilder::new(existing_users, BTreeSet::new(), SyncedBy::IamEksUserMapper)
+                .new_synced_users(new_synced_users)
+                .new_synced_roles(new_synced_roles)
+                .build();
+
+        // verify:
+        // entries iterate by case insensitive ARN, whatever the insertion order
+        assert_eq!(
+            vec!["alice", "Bob", "carol", "zoe"],
+            result
+                .users
+                .iter()
+                .map(|u| u.iam_user_name.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                "arn:aws:iam::123:role/Admin",
+                "arn:aws:iam::123:role/karpenter",
+                "arn:aws:iam::123:role/nodes",
+            ],
+            result
+                .roles
+                .iter()
+                .map(|r| r.iam_role_arn.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn aws_auth_build_groups_changed_test() {
+        // setup:
+        let synced = Some(SyncedBy::IamEksUserMapper);
+        let user = |arn_name: &str, groups: &[&str], synced_by: Option<SyncedBy>| {
+            KubernetesUser::new(
+                IamUserName::new("alice"),
+                IamArn::new(&format!("arn:aws:iam::123:user/{arn_name}")),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                synced_by,
+            )
+        };
+        let role = |arn_name: &str, groups: &[&str], synced_by: Option<SyncedBy>| {
+            KubernetesRole::new(
+                IamArn::new(&format!("arn:aws:iam::123:role/{arn_name}")),
+                None,
+                Some("admin".to_string()),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                synced_by,
+            )
+        };
+
+        struct TestCase<'a> {
+            existing: AwsAuth,
+            expected_user_groups: Vec<&'a str>,
+            expected_role_groups: Vec<&'a str>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                existing: AwsAuth {
+                    users: BTreeSet::from_iter(vec![user("alice", &["view"], synced.clone())]),
+                    roles: BTreeSet::from_iter(vec![role("admin", &["view"], synced.clone())]),
+                },
+                expected_user_groups: vec!["edit", "view"],
+                expected_role_groups: vec!["edit", "view"],
+                _description: "case 1: synced entries whose groups changed",
+            },
+            TestCase {
+                existing: AwsAuth {
+                    users: BTreeSet::from_iter(vec![user("Alice", &["view"], synced.clone())]),
+                    roles: BTreeSet::from_iter(vec![role("Admin", &["view"], synced.clone())]),
+                },
+                expected_user_groups: vec!["edit", "view"],
+                expected_role_groups: vec!["edit", "view"],
+                _description: "case 2: synced entries whose groups and ARN case changed",
+            },
+            TestCase {
+                existing: AwsAuth {
+                    users: BTreeSet::from_iter(vec![user("Alice", &["edit", "view"], None)]),
+                    roles: BTreeSet::from_iter(vec![role("Admin", &["edit", "view"], None)]),
+                },
+                expected_user_groups: vec!["edit", "view"],
+                expected_role_groups: vec!["edit", "view"],
+                _description: "case 3: unmanaged entries with the synced groups and ARN in another case replaced",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                tc.existing.users,
+                tc.existing.roles,
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_users(BTreeSet::from_iter(vec![user(
+                "alice",
+                &["edit", "view"],
+                None,
+            )]))
+            .new_synced_roles(BTreeSet::from_iter(vec![role(
+                "admin",
+                &["edit", "view"],
+                None,
+            )]))
+            .build();
+
+            // verify:
+            let groups = |groups: &BTreeSet<KubernetesGroupName>| {
+                let mut groups: Vec<String> = groups.iter().map(|g| g.to_string()).collect();
+                groups.sort();
+                groups
+            };
+            assert_eq!(1, result.users.len(), "{}", tc._description);
+            let synced_user = result.users.iter().next().unwrap();
+            assert_eq!(
+                "arn:aws:iam::123:user/alice",
+                synced_user.iam_arn.to_string(),
+                "{}",
+                tc._description
+            );
+            assert_eq!(synced, synced_user.synced_by, "{}", tc._description);
+            assert_eq!(
+                tc.expected_user_groups,
+                groups(&synced_user.roles),
+                "{}",
+                tc._description
+            );
+            assert_eq!(1, result.roles.len(), "{}", tc._description);
+            let synced_role = result.roles.iter().next().unwrap();
+            assert_eq!(
+                "arn:aws:iam::123:role/admin",
+                synced_role.iam_role_arn.to_string(),
+                "{}",
+                tc._description
+            );
+            assert_eq!(synced, synced_role.synced_by, "{}", tc._description);
+            assert_eq!(
+                tc.expected_role_groups,
+                groups(&synced_role.groups),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_adopt_entries_test() {
+        // setup:
+        type Entries<'a> = Vec<(&'a str, &'a str, Vec<&'a str>, Option<SyncedBy>)>;
+        struct TestCase<'a> {
+            adopt_existing_entries: bool,
+            adopt_arn_pattern: Option<&'a str>,
+            expected_users: Entries<'a>,
+            _description: &'a str,
+        }
+
+        let user = |name: &str, groups: &[&str], synced_by: Option<SyncedBy>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:aws:iam::123:user/{name}")),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                synced_by,
+            )
+        };
+        let existing_users = BTreeSet::from_iter(vec![
+            // hand-written entry of a user synced from IAM, with outdated groups
+            user("alice", &["view"], None),
+            // hand-written entry of a user not in IAM anymore
+            user("bob", &["view"], None),
+            // hand-written entry not matching the adoption pattern
+            user("ci", &["deployer"], None),
+            // protected hand-written entry
+            user("break-glass", &["system:masters"], None),
+        ]);
+        let new_synced_users = BTreeSet::from_iter(vec![
+            user("alice", &["devops"], Some(SyncedBy::IamEksUserMapper)),
+            user("break-glass", &["view"], Some(SyncedBy::IamEksUserMapper)),
+        ]);
+
+        let test_cases = vec![
+            TestCase {
+                adopt_existing_entries: false,
+                adopt_arn_pattern: None,
+                expected_users: vec![
+                    (
+                        "alice",
+                        "alice",
+                        vec!["devops"],
+                        Some(SyncedBy::IamEksUserMapper),
+                    ),
+                    ("alice", "alice", vec!["view"], None),
+                    ("bob", "bob", vec!["view"], None),
+                    ("break-glass", "break-glass", vec!["system:masters"], None),
+                    ("ci", "ci", vec!["deployer"], None),
+                ],
+                _description:
+                    "case 1: no adoption, hand-written entries kept alongside synced ones",
+            },
+            TestCase {
+                adopt_existing_entries: true,
+                adopt_arn_pattern: None,
+                expected_users: vec![
+                    (
+                        "alice",
+                        "alice",
+                        vec!["devops"],
+                        Some(SyncedBy::IamEksUserMapper),
+                    ),
+                    ("bob", "bob", vec!["view"], None),
+                    ("break-glass", "break-glass", vec!["system:masters"], None),
+                    ("ci", "ci", vec!["deployer"], None),
+                ],
+                _description:
+                    "case 2: hand-written entries of synced users replaced by synced ones",
+            },
+            TestCase {
+                adopt_existing_entries: false,
+                adopt_arn_pattern: Some("user/(alice|bob|break-glass)$"),
+                expected_users: vec![
+                    (
+                        "alice",
+                        "alice",
+                        vec!["devops"],
+                        Some(SyncedBy::IamEksUserMapper),
+                    ),
+                    ("bob", "bob", vec!["view"], Some(SyncedBy::IamEksUserMapper)),
+                    ("break-glass", "break-glass", vec!["system:masters"], None),
+                    ("ci", "ci", vec!["deployer"], None),
+                ],
+                _description:
+                    "case 3: hand-written entries matching pattern adopted, even if not synced",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                existing_users.clone(),
+                BTreeSet::default(),
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_users(new_synced_users.clone())
+            .protected_arns(&[IamArn::new("arn:aws:iam::123:user/break-glass")])
+            .adopt_existing_entries(tc.adopt_existing_entries)
+            .adopt_arn_pattern(tc.adopt_arn_pattern.map(|p| Regex::new(p).unwrap()))
+            .build();
+
+            // verify:
+            let mut users: Vec<_> = result
+                .users
+                .iter()
+                .map(|u| {
+                    let mut groups: Vec<String> = u.roles.iter().map(|g| g.to_string()).collect();
+                    groups.sort();
+                    (
+                        u.iam_user_name.to_string(),
+                        u.iam_arn.to_string(),
+                        groups,
+                        u.synced_by.clone(),
+                    )
+                })
+                .collect();
+            users.sort();
+            let expected_users: Vec<_> = tc
+                .expected_users
+                .into_iter()
+                .map(|(name, arn_name, groups, synced_by)| {
+                    (
+                        name.to_string(),
+                        format!("arn:aws:iam::123:user/{arn_name}"),
+                        groups
+                            .into_iter()
+                            .map(|g| g.to_string())
+                            .collect::<Vec<_>>(),
+                        synced_by,
+                    )
+                })
+                .collect();
+            assert_eq!(expected_users, users, "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_node_roles_test() {
+        // setup:
+        let node_role = |arn: &str, groups: &[&str], synced_by: Option<SyncedBy>| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                synced_by,
+            )
+        };
+        let existing_roles = BTreeSet::from_iter(vec![
+            // manual entry of a node role
+            node_role(
+                "arn::role/nodes",
+                &["system:bootstrappers", "system:nodes"],
+                None,
+            ),
+            // node role previously synced, no longer set
+            node_role(
+                "arn::role/old-nodes",
+                &["system:bootstrappers", "system:nodes"],
+                Some(SyncedBy::IamEksUserMapper),
+            ),
+            // Karpenter role previously synced without node groups
+            node_role(
+                "arn::role/karpenter",
+                &["eks:kube-proxy-windows"],
+                Some(SyncedBy::IamEksUserMapper),
+            ),
+        ]);
+        let new_synced_roles = BTreeSet::from_iter(vec![
+            node_role(
+                "arn::role/nodes",
+                &["system:bootstrappers", "system:nodes"],
+                None,
+            ),
+            // Karpenter role also set as node role, merged into a single entry
+            node_role(
+                "arn::role/karpenter",
+                &[
+                    "eks:kube-proxy-windows",
+                    "system:bootstrappers",
+                    "system:nodes",
+                ],
+                None,
+            ),
+        ]);
+
+        // execute:
+        let result = AwsAuthBuilder::new(
+            BTreeSet::default(),
+            existing_roles,
+            SyncedBy::IamEksUserMapper,
+        )
+        .new_synced_roles(new_synced_roles)
+        .build();
+
+        // verify:
+        assert_eq!(
+            BTreeSet::from_iter(vec![
+                node_role(
+                    "arn::role/nodes",
+                    &["system:bootstrappers", "system:nodes"],
+                    Some(SyncedBy::IamEksUserMapper),
+                ),
+                node_role(
+                    "arn::role/karpenter",
+                    &[
+                        "eks:kube-proxy-windows",
+                        "system:bootstrappers",
+                        "system:nodes",
+                    ],
+                    Some(SyncedBy::IamEksUserMapper),
+                ),
+            ]),
+            result.roles
+        );
+        assert!(result
+            .roles
+            .iter()
+            .all(|r| r.synced_by == Some(SyncedBy::IamEksUserMapper)));
+    }
+
+    #[test]
+    fn aws_auth_content_hash_test() {
+        // setup:
+        let user_1 = KubernetesUser::new(
+            IamUserName::new("user_1"),
+            IamArn::new("arn::user_1"),
+            BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("group_1"),
+                KubernetesGroupName::new("group_2"),
+            ]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        let user_1_reordered_groups = KubernetesUser::new(
+            IamUserName::new("user_1"),
+            IamArn::new("arn::user_1"),
+            BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("group_2"),
+                KubernetesGroupName::new("group_1"),
+            ]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        let user_1_other_groups = KubernetesUser::new(
+            IamUserName::new("user_1"),
+            IamArn::new("arn::user_1"),
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        let users: Vec<KubernetesUser> = (2..20)
+            .map(|i| {
+                KubernetesUser::new(
+                    IamUserName::new(&format!("user_{i}")),
+                    IamArn::new(&format!("arn::user_{i}")),
+                    BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                    None,
+                )
+            })
+            .collect();
+        let role = KubernetesRole::new(
+            IamArn::new("arn::role_1"),
+            Some("role_1".to_string()),
+            None,
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+
+        let aws_auth = AwsAuth {
+            users: BTreeSet::from_iter(users.iter().cloned().chain(vec![user_1.clone()])),
+            roles: BTreeSet::from_iter(vec![role.clone()]),
+        };
+        let aws_auth_reordered = AwsAuth {
+            users: BTreeSet::from_iter(
+                vec![user_1_reordered_groups]
+                    .into_iter()
+                    .chain(users.iter().rev().cloned()),
+            ),
+            roles: BTreeSet::from_iter(vec![role.clone()]),
+        };
+        let aws_auth_modified = AwsAuth {
+            users: BTreeSet::from_iter(users.iter().cloned().chain(vec![user_1_other_groups])),
+            roles: BTreeSet::from_iter(vec![role]),
+        };
+        let aws_auth_without_roles = AwsAuth {
+            users: BTreeSet::from_iter(users.iter().cloned().chain(vec![user_1])),
+            roles: BTreeSet::default(),
+        };
+
+        // verify:
+        assert_eq!(aws_auth, aws_auth_reordered);
+        assert_eq!(aws_auth.content_hash(), aws_auth_reordered.content_hash());
+        assert_ne!(aws_auth, aws_auth_modified);
+        assert_ne!(aws_auth.content_hash(), aws_auth_modified.content_hash());
+        assert_ne!(
+            aws_auth.content_hash(),
+            aws_auth_without_roles.content_hash()
+        );
+        assert_eq!(
+            AwsAuth::default().content_hash(),
+            AwsAuth::default().content_hash()
+        );
+    }
+
+    #[test]
+    fn aws_auth_build_protected_arns_test() {
+        // setup:
+        struct TestCase<'a> {
+            existing_users: BTreeSet<KubernetesUser>,
+            existing_roles: BTreeSet<KubernetesRole>,
+            new_users_to_be_added: BTreeSet<KubernetesUser>,
+            new_roles_to_be_added: BTreeSet<KubernetesRole>,
+            expected_output: AwsAuth,
+            _description: &'a str,
+        }
+
+        let user = |name: &str, groups: Vec<&str>, synced_by: Option<SyncedBy>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                synced_by,
+            )
+        };
+        let role = |name: &str, groups: Vec<&str>, synced_by: Option<SyncedBy>| {
+            KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                None,
+                Some(name.to_string()),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                synced_by,
+            )
+        };
+        let synced = Some(SyncedBy::IamEksUserMapper);
+
+        let test_cases = vec![
+            TestCase {
+                existing_users: BTreeSet::from_iter(vec![
+                    user("break_glass", vec!["system:masters"], synced.clone()),
+                    user("former", vec!["group_1"], synced.clone()),
+                ]),
+                existing_roles: BTreeSet::from_iter(vec![role(
+                    "node",
+                    vec!["system:nodes"],
+                    synced.clone(),
+                )]),
+                new_users_to_be_added: BTreeSet::new(),
+                new_roles_to_be_added: BTreeSet::new(),
+                expected_output: AwsAuth {
+                    users: BTreeSet::from_iter(vec![user(
+                        "break_glass",
+                        vec!["system:masters"],
+                        synced.clone(),
+                    )]),
+                    roles: BTreeSet::from_iter(vec![role(
+                        "node",
+                        vec!["system:nodes"],
+                        synced.clone(),
+                    )]),
+                },
+                _description: "case 1: protected synced entries disappearing from IAM are kept",
+            },
+            TestCase {
+                existing_users: BTreeSet::from_iter(vec![user(
+                    "break_glass",
+                    vec!["system:masters"],
+                    synced.clone(),
+                )]),
+                existing_roles: BTreeSet::from_iter(vec![role("node", vec!["system:nodes"], None)]),
+                new_users_to_be_added: BTreeSet::from_iter(vec![user(
+                    "break_glass",
+                    vec!["view"],
+                    None,
+                )]),
+                new_roles_to_be_added: BTreeSet::from_iter(vec![role("node", vec![], None)]),
+                expected_output: AwsAuth {
+                    users: BTreeSet::from_iter(vec![user(
+                        "break_glass",
+                        vec!["system:masters"],
+                        synced.clone(),
+                    )]),
+                    roles: BTreeSet::from_iter(vec![role("node", vec!["system:nodes"], None)]),
+                },
+                _description: "case 2: protected entries groups are not modified",
+            },
+            TestCase {
+                existing_users: BTreeSet::new(),
+                existing_roles: BTreeSet::new(),
+                new_users_to_be_added: BTreeSet::from_iter(vec![user(
+                    "break_glass",
+                    vec!["system:masters"],
+                    None,
+                )]),
+                new_roles_to_be_added: BTreeSet::new(),
+                expected_output: AwsAuth {
+                    users: BTreeSet::from_iter(vec![user(
+                        "break_glass",
+                        vec!["system:masters"],
+                        synced.clone(),
+                    )]),
+                    roles: BTreeSet::new(),
+                },
+                _description: "case 3: protected entries can be added",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = AwsAuthBuilder::new(
+                tc.existing_users,
+                tc.existing_roles,
+                SyncedBy::IamEksUserMapper,
+            )
+            .new_synced_users(tc.new_users_to_be_added)
+            .new_synced_roles(tc.new_roles_to_be_added)
+            .protected_arns(&[
+                IamArn::new("arn:test:break_glass"),
+                // protection is case insensitive, as entries identity
+                IamArn::new("ARN:TEST:NODE"),
+            ])
+            .build();
+
+            // verify:
+            assert_eq!(tc.expected_output, result, "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn aws_auth_build_leaves_other_instances_entries_test() {
+        // setup:
+        let instance_a = SyncedBy::IamEksUserMapper;
+        let instance_b = SyncedBy::new("iam-eks-user-mapper-ci");
+        let user = |name: &str, synced_by: &SyncedBy| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                Some(synced_by.clone()),
+            )
+        };
+        let role = |name: &str, synced_by: &SyncedBy| {
+            KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                None,
+                Some(name.to_string()),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                Some(synced_by.clone()),
+            )
+        };
+        let existing = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                user("a_removed", &instance_a),
+                user("b_user", &instance_b),
+                user("shared", &instance_b),
+            ]),
+            roles: BTreeSet::from_iter(vec![
+                role("b_role", &instance_b),
+                role("shared_role", &instance_b),
+            ]),
+        };
+        let build_a = |aws_auth: &AwsAuth| {
+            AwsAuthBuilder::new(
+                aws_auth.users.clone(),
+                aws_auth.roles.clone(),
+                instance_a.clone(),
+            )
+            .new_synced_users(BTreeSet::from_iter(vec![
+                user("a_added", &instance_a),
+                user("shared", &instance_a),
+            ]))
+            .new_synced_roles(BTreeSet::from_iter(vec![role("shared_role", &instance_a)]))
+            .build()
+        };
+        let build_b = |aws_auth: &AwsAuth| {
+            AwsAuthBuilder::new(
+                aws_auth.users.clone(),
+                aws_auth.roles.clone(),
+                instance_b.clone(),
+            )
+            .new_synced_users(BTreeSet::from_iter(vec![user("shared", &instance_b)]))
+            .new_synced_roles(BTreeSet::from_iter(vec![
+                role("b_role", &instance_b),
+                role("shared_role", &instance_b),
+            ]))
+            .build()
+        };
+
+        // execute:
+        let aws_auth_a = build_a(&existing);
+        let aws_auth_b = build_b(&aws_auth_a);
+        let aws_auth_a_again = build_a(&aws_auth_b);
+
+        // verify:
+        assert_eq!(
+            AwsAuth {
+                users: BTreeSet::from_iter(vec![
+                    user("a_added", &instance_a),
+                    user("b_user", &instance_b),
+                    user("shared", &instance_b),
+                ]),
+                roles: BTreeSet::from_iter(vec![
+                    role("b_role", &instance_b),
+                    role("shared_role", &instance_b),
+                ]),
+            },
+            aws_auth_a,
+            "instance A only replaces its own entries"
+        );
+        assert_eq!(
+            AwsAuth {
+                users: BTreeSet::from_iter(vec![
+                    user("a_added", &instance_a),
+                    user("shared", &instance_b),
+                ]),
+                roles: BTreeSet::from_iter(vec![
+                    role("b_role", &instance_b),
+                    role("shared_role", &instance_b),
+                ]),
+            },
+            aws_auth_b,
+            "instance B only replaces its own entries"
+        );
+        for aws_auth in [&aws_auth_a, &aws_auth_b, &aws_auth_a_again] {
+            assert_eq!(
+                Some(Some(&instance_b)),
+                aws_auth
+                    .users
+                    .get(&user("shared", &instance_b))
+                    .map(|u| u.synced_by.as_ref()),
+                "user synced by both instances keeps instance B marker"
+            );
+            assert_eq!(
+                Some(Some(&instance_b)),
+                aws_auth
+                    .roles
+                    .get(&role("shared_role", &instance_b))
+                    .map(|r| r.synced_by.as_ref()),
+                "role synced by both instances keeps instance B marker"
+            );
+        }
+        let markers = |aws_auth: &AwsAuth| {
+            (
+                aws_auth
+                    .users
+                    .iter()
+                    .map(|u| (u.clone(), u.synced_by.clone(), u.synced_at))
+                    .collect::<Vec<_>>(),
+                aws_auth
+                    .roles
+                    .iter()
+                    .map(|r| (r.clone(), r.synced_by.clone(), r.synced_at))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(
+            markers(&aws_auth_b),
+            markers(&aws_auth_a_again),
+            "instance A does not rewrite entries once instance B synced"
+        );
+        let diff = existing.diff(&aws_auth_a, &instance_a);
+        assert_eq!(
+            vec![user("b_user", &instance_b), user("shared", &instance_b)],
+            diff.users.untouched,
+            "instance B entries are untouched from instance A point of view"
+        );
+    }
+
+    #[test]
+    fn aws_auth_build_keep_entries_test() {
+        // setup:
+        let user = |name: &str, synced_by: Option<SyncedBy>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                synced_by,
+            )
+        };
+        let role = |name: &str, synced_by: Option<SyncedBy>| {
+            KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                Some(name.to_string()),
+                None,
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                synced_by,
+            )
+        };
+        let synced = Some(SyncedBy::IamEksUserMapper);
+        let existing = AwsAuth {
+            users: BTreeSet::from_iter(vec![user("manual", None), user("synced", synced.clone())]),
+            roles: BTreeSet::from_iter(vec![
+                role("manual_role", None),
+                role("synced_role", synced.clone()),
+            ]),
+        };
+
+        // execute:
+        let (aws_auth, diff) = AwsAuthBuilder::new(
+            existing.users.clone(),
+            existing.roles.clone(),
+            SyncedBy::IamEksUserMapper,
+        )
+        .new_synced_users(BTreeSet::new())
+        .new_synced_roles(BTreeSet::new())
+        // would adopt unmanaged entries if not kept
+        .adopt_arn_pattern(Some(Regex::new("manual").unwrap()))
+        .keep_users(true)
+        .keep_roles(true)
+        .build_with_report();
+
+        // verify:
+        // synced entries aren't removed, unmanaged ones aren't adopted
+        assert_eq!(existing, aws_auth);
+        assert!(aws_auth
+            .users
+            .iter()
+            .any(|u| u.iam_arn == IamArn::new("arn:test:manual") && u.synced_by.is_none()));
+        assert!(aws_auth.roles.iter().any(|r| r.iam_role_arn
+            == IamArn::new("arn:test:manual_role")
+            && r.synced_by.is_none()));
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn aws_auth_build_removals_only_test() {
+        // setup:
+        let sync_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let user = |name: &str, groups: Vec<&str>, synced_by: Option<SyncedBy>| KubernetesUser {
+            synced_at: synced_by.as_ref().map(|_| sync_time),
+            ..KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                synced_by,
+            )
+        };
+        let synced = Some(SyncedBy::IamEksUserMapper);
+        let role = |name: &str| KubernetesRole {
+            synced_at: Some(sync_time),
+            ..KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                Some(name.to_string()),
+                None,
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let existing = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                user("manual", vec!["group_1"], None),
+                user("kept", vec!["group_1"], synced.clone()),
+                user("stale", vec!["group_1"], synced.clone()),
+                user("regrouped", vec!["group_1"], synced.clone()),
+            ]),
+            roles: BTreeSet::from_iter(vec![role("stale_role")]),
+        };
+
+        // execute:
+        let mut builder = AwsAuthBuilder::new(
+            existing.users.clone(),
+            existing.roles.clone(),
+            SyncedBy::IamEksUserMapper,
+        );
+        builder
+            .new_synced_users(BTreeSet::from_iter(vec![
+                user("kept", vec!["group_1"], synced.clone()),
+                user("regrouped", vec!["group_1", "group_2"], synced.clone()),
+                user("added", vec!["group_1"], synced.clone()),
+                // unmanaged entry having a synced ARN is neither adopted nor added alongside
+                user("manual", vec!["group_2"], synced.clone()),
+            ]))
+            .new_synced_roles(BTreeSet::from_iter(vec![role("added_role")]))
+            .adopt_existing_entries(true)
+            .sync_time(sync_time)
+            .removals_only(true);
+        let (aws_auth, diff) = builder.build_with_report();
+
+        // verify:
+        assert_eq!(
+            AwsAuth {
+                users: BTreeSet::from_iter(vec![
+                    user("manual", vec!["group_1"], None),
+                    user("kept", vec!["group_1"], synced.clone()),
+                    // still synced entries are kept as written
+                    user("regrouped", vec!["group_1"], synced.clone()),
+                ]),
+                roles: BTreeSet::new(),
+            },
+            aws_auth
+        );
+        assert!(diff.users.added.is_empty() && diff.roles.added.is_empty());
+        assert!(diff.users.modified.is_empty() && diff.roles.modified.is_empty());
+        assert_eq!(
+            vec![user("stale", vec!["group_1"], synced.clone())],
+            diff.users.removed
+        );
+        assert_eq!(vec![role("stale_role")], diff.roles.removed);
+        assert_eq!(
+            vec![user("added", vec!["group_1"], synced.clone())],
+            diff.users.suppressed
+        );
+        assert_eq!(vec![role("added_role")], diff.roles.suppressed);
+        assert_eq!(
+            "- user `arn:test:stale` (username: stale, groups: [group_1])\n\
+             - role `arn:test:stale_role` (rolename: stale_role, groups: [group_1])\n\
+             ! user `arn:test:added` (username: added, groups: [group_1]) (addition suppressed, removals only)\n\
+             ! role `arn:test:added_role` (rolename: added_role, groups: [group_1]) (addition suppressed, removals only)\n",
+            diff.to_string()
+        );
 
-            // verify:
-            assert_eq!(tc.expected_users, result.users);
-        }
+        // nothing suppressed when additions are synced
+        builder.removals_only(false);
+        assert_eq!(AwsAuth::default(), builder.suppressed_additions());
+        assert!(builder
+            .build()
+            .users
+            .contains(&user("added", vec!["group_1"], synced.clone())));
     }
 
     #[test]
-    fn aws_auth_build_add_new_synced_users_should_add_synced_field_test() {
+    fn aws_auth_build_with_report_test() {
         // setup:
-        let test_cases: Vec<HashSet<KubernetesUser>> = vec![
-            HashSet::default(),
-            HashSet::from_iter(vec![
-                KubernetesUser::new(
-                    IamUserName::new("user_1"),
-                    IamArn::new("arn::user_1"),
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    None,
-                ),
-                KubernetesUser::new(
-                    IamUserName::new("user_2"),
-                    IamArn::new("arn::user_2"),
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_2"),
-                        KubernetesGroupName::new("group_3"),
-                    ]),
-                    None,
-                ),
+        let sync_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let synced = Some(SyncedBy::IamEksUserMapper);
+        let user = |name: &str, groups: Vec<&str>, synced_by: Option<SyncedBy>| KubernetesUser {
+            synced_at: synced_by.as_ref().map(|_| sync_time),
+            ..KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                synced_by,
+            )
+        };
+        let role = |name: &str, groups: Vec<&str>, synced_by: Option<SyncedBy>| KubernetesRole {
+            synced_at: synced_by.as_ref().map(|_| sync_time),
+            ..KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                None,
+                Some(name.to_string()),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                synced_by,
+            )
+        };
+        let existing = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                user("manual", vec!["group_1"], None),
+                user("kept", vec!["group_1"], synced.clone()),
+                user("removed", vec!["group_1"], synced.clone()),
+                user("regrouped", vec!["group_1"], synced.clone()),
             ]),
-            HashSet::from_iter(vec![KubernetesUser::new(
-                IamUserName::new("user_2"),
-                IamArn::new("arn::user_2"),
-                HashSet::from_iter(vec![
-                    KubernetesGroupName::new("group_2"),
-                    KubernetesGroupName::new("group_3"),
+            roles: BTreeSet::from_iter(vec![
+                role("manual_role", vec!["group_1"], None),
+                role("removed_role", vec!["group_1"], synced.clone()),
+            ]),
+        };
+
+        // execute:
+        let (aws_auth, diff) = AwsAuthBuilder::new(
+            existing.users.clone(),
+            existing.roles.clone(),
+            SyncedBy::IamEksUserMapper,
+        )
+        .new_synced_users(BTreeSet::from_iter(vec![
+            user("kept", vec!["group_1"], None),
+            user("regrouped", vec!["group_1", "group_2"], None),
+            user("added", vec!["group_1"], None),
+        ]))
+        .new_synced_roles(BTreeSet::from_iter(vec![role(
+            "added_role",
+            vec!["group_1"],
+            None,
+        )]))
+        .sync_time(sync_time)
+        .build_with_report();
+
+        // verify:
+        assert_eq!(
+            AwsAuth {
+                users: BTreeSet::from_iter(vec![
+                    user("manual", vec!["group_1"], None),
+                    user("kept", vec!["group_1"], synced.clone()),
+                    user("regrouped", vec!["group_1", "group_2"], synced.clone()),
+                    user("added", vec!["group_1"], synced.clone()),
                 ]),
-                Some(SyncedBy::Unknown),
-            )]),
-            HashSet::from_iter(vec![KubernetesUser::new(
-                IamUserName::new("user_2"),
-                IamArn::new("arn::user_2"),
-                HashSet::from_iter(vec![
-                    KubernetesGroupName::new("group_2"),
-                    KubernetesGroupName::new("group_3"),
+                roles: BTreeSet::from_iter(vec![
+                    role("manual_role", vec!["group_1"], None),
+                    role("added_role", vec!["group_1"], synced.clone()),
                 ]),
+            },
+            aws_auth
+        );
+        assert_eq!(
+            EntriesDiff {
+                added: vec![user("added", vec!["group_1"], synced.clone())],
+                removed: vec![user("removed", vec!["group_1"], synced.clone())],
+                modified: vec![(
+                    user("regrouped", vec!["group_1"], synced.clone()),
+                    user("regrouped", vec!["group_1", "group_2"], synced.clone()),
+                )],
+                untouched: vec![user("manual", vec!["group_1"], None)],
+                unchanged: vec![user("kept", vec!["group_1"], synced.clone())],
+                suppressed: vec![],
+            },
+            diff.users
+        );
+        assert_eq!(
+            EntriesDiff {
+                added: vec![role("added_role", vec!["group_1"], synced.clone())],
+                removed: vec![role("removed_role", vec!["group_1"], synced.clone())],
+                modified: vec![],
+                untouched: vec![role("manual_role", vec!["group_1"], None)],
+                unchanged: vec![],
+                suppressed: vec![],
+            },
+            diff.roles
+        );
+        assert_eq!(existing.diff(&aws_auth, &SyncedBy::IamEksUserMapper), diff);
+    }
+
+    #[test]
+    fn aws_auth_build_synced_at_test() {
+        // setup:
+        struct TestCase<'a> {
+            existing_synced_at: Option<Option<SystemTime>>,
+            synced_entry_ttl: Option<Duration>,
+            protected: bool,
+            expected_synced_at: Option<SystemTime>,
+            _description: &'a str,
+        }
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        let sync_time = SystemTime::UNIX_EPOCH + 100 * day;
+        let user = |synced_at: Option<SystemTime>| KubernetesUser {
+            synced_at,
+            ..KubernetesUser::new(
+                IamUserName::new("user_1"),
+                IamArn::new("arn:test:user_1"),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
                 Some(SyncedBy::IamEksUserMapper),
-            )]),
+            )
+        };
+        let role = |synced_at: Option<SystemTime>| KubernetesRole {
+            synced_at,
+            ..KubernetesRole::new(
+                IamArn::new("arn:test:role_1"),
+                Some("role_1".to_string()),
+                None,
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+
+        let test_cases = vec![
+            TestCase {
+                existing_synced_at: None,
+                synced_entry_ttl: None,
+                protected: false,
+                expected_synced_at: Some(sync_time),
+                _description: "case 1: new entry gets sync time",
+            },
+            TestCase {
+                existing_synced_at: Some(Some(sync_time - 10 * day)),
+                synced_entry_ttl: None,
+                protected: false,
+                expected_synced_at: Some(sync_time - 10 * day),
+                _description: "case 2: refreshed entry keeps its original timestamp",
+            },
+            TestCase {
+                existing_synced_at: Some(None),
+                synced_entry_ttl: None,
+                protected: false,
+                expected_synced_at: Some(sync_time),
+                _description: "case 3: entry synced before timestamps existed gets sync time",
+            },
+            TestCase {
+                existing_synced_at: Some(Some(sync_time - 10 * day)),
+                synced_entry_ttl: Some(30 * day),
+                protected: false,
+                expected_synced_at: Some(sync_time - 10 * day),
+                _description: "case 4: entry younger than TTL is kept",
+            },
+            TestCase {
+                existing_synced_at: Some(Some(sync_time - 30 * day)),
+                synced_entry_ttl: Some(30 * day),
+                protected: false,
+                expected_synced_at: None,
+                _description: "case 5: entry older than TTL is removed even if still granted",
+            },
+            TestCase {
+                existing_synced_at: Some(Some(sync_time - 30 * day)),
+                synced_entry_ttl: Some(30 * day),
+                protected: true,
+                expected_synced_at: Some(sync_time - 30 * day),
+                _description: "case 6: protected entry older than TTL is kept",
+            },
         ];
 
         for tc in test_cases {
             // execute:
-            let result = AwsAuthBuilder::new(HashSet::default(), HashSet::default())
-                .new_synced_users(tc.clone())
-                .build();
+            let existing = AwsAuth {
+                users: tc.existing_synced_at.map(user).into_iter().collect(),
+                roles: tc.existing_synced_at.map(role).into_iter().collect(),
+            };
+            let protected_arns = match tc.protected {
+                true => vec![
+                    IamArn::new("arn:test:user_1"),
+                    IamArn::new("arn:test:role_1"),
+                ],
+                false => vec![],
+            };
+            let result =
+                AwsAuthBuilder::new(existing.users, existing.roles, SyncedBy::IamEksUserMapper)
+                    .new_synced_users(BTreeSet::from_iter(vec![user(None)]))
+                    .new_synced_roles(BTreeSet::from_iter(vec![role(None)]))
+                    .protected_arns(&protected_arns)
+                    .synced_entry_ttl(tc.synced_entry_ttl)
+                    .sync_time(sync_time)
+                    .build();
 
             // verify:
-            assert_eq!(tc.len(), result.users.iter().len());
-            assert!(result
-                .users
-                .iter()
-                .all(|u| u.synced_by == Some(SyncedBy::IamEksUserMapper)));
+            assert_eq!(
+                tc.expected_synced_at,
+                result.users.iter().next().and_then(|u| u.synced_at),
+                "users {}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_synced_at,
+                result.roles.iter().next().and_then(|r| r.synced_at),
+                "roles {}",
+                tc._description
+            );
         }
     }
 
     #[test]
-    fn aws_auth_build_roles_test() {
+    fn removal_guard_check_test() {
         // setup:
         struct TestCase<'a> {
-            existing_roles: HashSet<KubernetesRole>,
-            new_roles_to_be_added: HashSet<KubernetesRole>,
-            expected_roles: HashSet<KubernetesRole>,
+            guard: RemovalGuard,
+            existing_synced_users: usize,
+            desired_synced_users: usize,
+            expected_removals_refused: bool,
             _description: &'a str,
         }
 
+        let user = |i: usize, synced_by: Option<SyncedBy>| {
+            KubernetesUser::new(
+                IamUserName::new(&format!("user_{i}")),
+                IamArn::new(&format!("arn:test:user_{i}")),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                synced_by,
+            )
+        };
+        // synced users and a third party one, which is not taken into account
+        let aws_auth = |synced_users: usize| AwsAuth {
+            users: (0..synced_users)
+                .map(|i| user(i, Some(SyncedBy::IamEksUserMapper)))
+                .chain(vec![user(100, None)])
+                .collect(),
+            roles: BTreeSet::new(),
+        };
+        let fraction_guard = |max_removal_fraction: f64| RemovalGuard {
+            max_removal_fraction,
+            ..Default::default()
+        };
+
         let test_cases = vec![
             TestCase {
-                existing_roles: HashSet::default(),
-                new_roles_to_be_added: HashSet::default(),
-                expected_roles: HashSet::default(),
-                _description: "case 1: no existing roles, no new roles",
+                guard: fraction_guard(0.5),
+                existing_synced_users: 10,
+                desired_synced_users: 5,
+                expected_removals_refused: false,
+                _description: "case 1: removing exactly the max fraction",
             },
             TestCase {
-                existing_roles: HashSet::default(),
-                new_roles_to_be_added: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    Some(SyncedBy::IamEksUserMapper),
-                )]),
-                expected_roles: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    Some(SyncedBy::IamEksUserMapper),
-                )]),
-                _description: "case 2: no existing roles, some new roles",
+                guard: fraction_guard(0.5),
+                existing_synced_users: 10,
+                desired_synced_users: 4,
+                expected_removals_refused: true,
+                _description: "case 2: removing just above the max fraction",
             },
             TestCase {
-                existing_roles: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    None,
-                )]),
-                new_roles_to_be_added: HashSet::default(),
-                expected_roles: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    None,
-                )]),
-                _description: "case 3: existing roles, no new roles",
+                guard: fraction_guard(0.0),
+                existing_synced_users: 10,
+                desired_synced_users: 10,
+                expected_removals_refused: false,
+                _description: "case 3: no removals",
             },
             TestCase {
-                existing_roles: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    None,
-                )]),
-                new_roles_to_be_added: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_2"),
-                    Some("role_2".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_2"),
-                        KubernetesGroupName::new("group_3"),
-                    ]),
-                    Some(SyncedBy::IamEksUserMapper),
-                )]),
-                expected_roles: HashSet::from_iter(vec![
-                    KubernetesRole::new(
-                        IamArn::new("arn::role_1"),
-                        Some("role_1".to_string()),
-                        None,
-                        HashSet::from_iter(vec![
-                            KubernetesGroupName::new("group_1"),
-                            KubernetesGroupName::new("group_2"),
-                        ]),
-                        None,
-                    ),
-                    KubernetesRole::new(
-                        IamArn::new("arn::role_2"),
-                        Some("role_2".to_string()),
-                        None,
-                        HashSet::from_iter(vec![
-                            KubernetesGroupName::new("group_2"),
-                            KubernetesGroupName::new("group_3"),
-                        ]),
-                        Some(SyncedBy::IamEksUserMapper),
-                    ),
-                ]),
-                _description: "case 4: existing roles, some new roles",
+                guard: RemovalGuard {
+                    max_removals: Some(3),
+                    ..Default::default()
+                },
+                existing_synced_users: 10,
+                desired_synced_users: 7,
+                expected_removals_refused: false,
+                _description: "case 4: removing exactly max removals",
             },
             TestCase {
-                existing_roles: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    None,
-                )]),
-                new_roles_to_be_added: HashSet::from_iter(vec![KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    Some(SyncedBy::IamEksUserMapper),
-                )]),
-                expected_roles: HashSet::from_iter(vec![
-                    KubernetesRole::new(
-                        IamArn::new("arn::role_1"),
-                        Some("role_1".to_string()),
-                        None,
-                        HashSet::from_iter(vec![
-                            KubernetesGroupName::new("group_1"),
-                            KubernetesGroupName::new("group_2"),
-                        ]),
-                        Some(SyncedBy::IamEksUserMapper),
-                    ),
-                ]),
-                _description: "case 5: existing role without synced by flag, same new role with new synced by field",
+                guard: RemovalGuard {
+                    max_removals: Some(3),
+                    ..Default::default()
+                },
+                existing_synced_users: 10,
+                desired_synced_users: 6,
+                expected_removals_refused: true,
+                _description: "case 5: removing just above max removals",
+            },
+            TestCase {
+                guard: RemovalGuard {
+                    max_removal_fraction: 0.1,
+                    max_removals: Some(1),
+                    force: true,
+                },
+                existing_synced_users: 10,
+                desired_synced_users: 0,
+                expected_removals_refused: false,
+                _description: "case 6: forced removals",
+            },
+            TestCase {
+                guard: RemovalGuard::default(),
+                existing_synced_users: 10,
+                desired_synced_users: 0,
+                expected_removals_refused: false,
+                _description: "case 7: default guard allows everything",
+            },
+            TestCase {
+                guard: fraction_guard(0.5),
+                existing_synced_users: 1,
+                desired_synced_users: 0,
+                expected_removals_refused: false,
+                _description: "case 8: removing the only synced entry, max fraction rounded up",
+            },
+            TestCase {
+                guard: fraction_guard(0.5),
+                existing_synced_users: 3,
+                desired_synced_users: 1,
+                expected_removals_refused: false,
+                _description: "case 9: removing 2 of 3 synced entries, max fraction rounded up",
+            },
+            TestCase {
+                guard: fraction_guard(0.5),
+                existing_synced_users: 3,
+                desired_synced_users: 0,
+                expected_removals_refused: true,
+                _description: "case 10: removing 3 of 3 synced entries",
             },
         ];
 
         for tc in test_cases {
+            let existing = aws_auth(tc.existing_synced_users);
+            let desired = aws_auth(tc.desired_synced_users);
+
             // execute:
-            let result = AwsAuthBuilder::new(HashSet::default(), tc.existing_roles)
-                .new_synced_roles(tc.new_roles_to_be_added)
-                .build();
+            let res = tc.guard.check(
+                &existing,
+                &existing.diff(&desired, &SyncedBy::IamEksUserMapper),
+                &SyncedBy::IamEksUserMapper,
+            );
 
             // verify:
-            assert_eq!(tc.expected_roles, result.roles);
+            match tc.expected_removals_refused {
+                false => assert_eq!(Ok(()), res, "{}", tc._description),
+                true => match res {
+                    Err(KubernetesError::TooManyRemovals {
+                        removals,
+                        synced_entries,
+                        removed_entries,
+                    }) => {
+                        assert_eq!(
+                            tc.existing_synced_users - tc.desired_synced_users,
+                            removals,
+                            "{}",
+                            tc._description
+                        );
+                        assert_eq!(
+                            tc.existing_synced_users, synced_entries,
+                            "{}",
+                            tc._description
+                        );
+                        assert_eq!(removals, removed_entries.lines().count());
+                        assert!(removed_entries.starts_with("- user `arn:test:user_"));
+                    }
+                    res => panic!("{}: unexpected result {res:?}", tc._description),
+                },
+            }
         }
     }
 
     #[test]
-    fn aws_auth_build_add_new_synced_roles_should_add_synced_field_test() {
+    fn aws_auth_diff_test() {
         // setup:
-        let test_cases: Vec<HashSet<KubernetesRole>> = vec![
-            HashSet::default(),
-            HashSet::from_iter(vec![
-                KubernetesRole::new(
-                    IamArn::new("arn::role_1"),
-                    Some("role_1".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    None,
-                ),
-                KubernetesRole::new(
-                    IamArn::new("arn::role_2"),
-                    Some("role_2".to_string()),
-                    None,
-                    HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_2"),
-                        KubernetesGroupName::new("group_3"),
-                    ]),
-                    None,
-                ),
-            ]),
-            HashSet::from_iter(vec![KubernetesRole::new(
-                IamArn::new("arn::role_2"),
-                Some("role_2".to_string()),
+        let user = |name: &str, groups: Vec<&str>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
                 None,
-                HashSet::from_iter(vec![
-                    KubernetesGroupName::new("group_2"),
-                    KubernetesGroupName::new("group_3"),
-                ]),
-                Some(SyncedBy::Unknown),
-            )]),
-            HashSet::from_iter(vec![KubernetesRole::new(
-                IamArn::new("arn::role_2"),
-                Some("role_2".to_string()),
+            )
+        };
+        let synced_user = |name: &str, groups: Vec<&str>| KubernetesUser {
+            synced_by: Some(SyncedBy::IamEksUserMapper),
+            ..user(name, groups)
+        };
+        let role = |name: &str, groups: Vec<&str>| {
+            KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                Some(name.to_string()),
                 None,
-                HashSet::from_iter(vec![
-                    KubernetesGroupName::new("group_2"),
-                    KubernetesGroupName::new("group_3"),
-                ]),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
                 Some(SyncedBy::IamEksUserMapper),
-            )]),
+            )
+        };
+        let existing = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                user("third_party", vec!["group_1"]),
+                synced_user("kept", vec!["group_1"]),
+                synced_user("removed", vec!["group_1"]),
+                synced_user("regrouped", vec!["group_1"]),
+            ]),
+            roles: BTreeSet::from_iter(vec![
+                role("role_removed", vec!["group_1"]),
+                role("role_regrouped", vec!["group_1"]),
+            ]),
+        };
+        let desired = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                user("third_party", vec!["group_1"]),
+                synced_user("kept", vec!["group_1"]),
+                synced_user("added", vec!["group_1"]),
+                synced_user("regrouped", vec!["group_1", "group_2"]),
+            ]),
+            roles: BTreeSet::from_iter(vec![
+                role("role_added", vec!["group_1"]),
+                role("role_regrouped", vec!["group_2"]),
+            ]),
+        };
+
+        // execute:
+        let diff = existing.diff(&desired, &SyncedBy::IamEksUserMapper);
+
+        // verify:
+        assert_eq!(
+            AwsAuthDiff {
+                users: EntriesDiff {
+                    added: vec![synced_user("added", vec!["group_1"])],
+                    removed: vec![synced_user("removed", vec!["group_1"])],
+                    modified: vec![(
+                        synced_user("regrouped", vec!["group_1"]),
+                        synced_user("regrouped", vec!["group_1", "group_2"])
+                    )],
+                    untouched: vec![user("third_party", vec!["group_1"])],
+                    unchanged: vec![synced_user("kept", vec!["group_1"])],
+                    suppressed: vec![],
+                },
+                roles: EntriesDiff {
+                    added: vec![role("role_added", vec!["group_1"])],
+                    removed: vec![role("role_removed", vec!["group_1"])],
+                    modified: vec![(
+                        role("role_regrouped", vec!["group_1"]),
+                        role("role_regrouped", vec!["group_2"])
+                    )],
+                    untouched: vec![],
+                    unchanged: vec![],
+                    suppressed: vec![],
+                },
+            },
+            diff
+        );
+        assert_eq!(
+            "+ user `arn:test:added` (username: added, groups: [group_1])\n\
+             - user `arn:test:removed` (username: removed, groups: [group_1])\n\
+             ~ user `arn:test:regrouped` (username: regrouped, groups: [group_1]) -> (username: regrouped, groups: [group_1, group_2])\n\
+             + role `arn:test:role_added` (rolename: role_added, groups: [group_1])\n\
+             - role `arn:test:role_removed` (rolename: role_removed, groups: [group_1])\n\
+             ~ role `arn:test:role_regrouped` (rolename: role_regrouped, groups: [group_1]) -> (rolename: role_regrouped, groups: [group_2])\n",
+            diff.to_string()
+        );
+        assert!(format!("{diff:#}").ends_with(
+            "= user `arn:test:third_party` (username: third_party, groups: [group_1]) (not managed by the tool)\n"
+        ));
+        assert!(existing
+            .diff(&existing, &SyncedBy::IamEksUserMapper)
+            .is_empty());
+    }
+
+    #[test]
+    fn aws_auth_diff_summary_test() {
+        struct TestCase<'a> {
+            existing: Vec<KubernetesUser>,
+            desired: Vec<KubernetesUser>,
+            expected: EntriesCounts,
+            expected_display: &'a str,
+            _description: &'a str,
+        }
+
+        let user = |name: &str, groups: Vec<&str>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let third_party = KubernetesUser {
+            synced_by: None,
+            ..user("third_party", vec!["group_1"])
+        };
+
+        let test_cases = vec![
+            TestCase {
+                existing: vec![user("kept", vec!["group_1"])],
+                desired: vec![
+                    user("kept", vec!["group_1"]),
+                    user("added", vec!["group_1"]),
+                ],
+                expected: EntriesCounts {
+                    added: 1,
+                    unchanged: 1,
+                    ..Default::default()
+                },
+                expected_display: "users: +1 -0 ~0 =1, roles: +0 -0 ~0 =0",
+                _description: "case 1 - user added",
+            },
+            TestCase {
+                existing: vec![
+                    user("kept", vec!["group_1"]),
+                    user("removed", vec!["group_1"]),
+                ],
+                desired: vec![user("kept", vec!["group_1"])],
+                expected: EntriesCounts {
+                    removed: 1,
+                    unchanged: 1,
+                    ..Default::default()
+                },
+                expected_display: "users: +0 -1 ~0 =1, roles: +0 -0 ~0 =0",
+                _description: "case 2 - user removed",
+            },
+            TestCase {
+                existing: vec![user("regrouped", vec!["group_1"])],
+                desired: vec![user("regrouped", vec!["group_1", "group_2"])],
+                expected: EntriesCounts {
+                    modified: 1,
+                    ..Default::default()
+                },
+                expected_display: "users: +0 -0 ~1 =0, roles: +0 -0 ~0 =0",
+                _description: "case 3 - user groups changed",
+            },
+            TestCase {
+                existing: vec![user("kept", vec!["group_1"]), third_party.clone()],
+                desired: vec![user("kept", vec!["group_1"]), third_party.clone()],
+                expected: EntriesCounts {
+                    unchanged: 2,
+                    ..Default::default()
+                },
+                expected_display: "users: +0 -0 ~0 =2, roles: +0 -0 ~0 =0",
+                _description:
+                    "case 4 - nothing changed, entries not managed by the tool counted as unchanged",
+            },
         ];
 
         for tc in test_cases {
+            // setup:
+            let existing = AwsAuth {
+                users: BTreeSet::from_iter(tc.existing),
+                roles: BTreeSet::new(),
+            };
+            let desired = AwsAuth {
+                users: BTreeSet::from_iter(tc.desired),
+                roles: BTreeSet::new(),
+            };
+
             // execute:
-            let result = AwsAuthBuilder::new(HashSet::default(), HashSet::default())
-                .new_synced_roles(tc.clone())
-                .build();
+            let summary = existing
+                .diff(&desired, &SyncedBy::IamEksUserMapper)
+                .summary();
 
             // verify:
-            assert_eq!(tc.len(), result.roles.iter().len());
-            assert!(result
-                .roles
-                .iter()
-                .all(|u| u.synced_by == Some(SyncedBy::IamEksUserMapper)));
+            assert_eq!(tc.expected, summary.users, "{}", tc._description);
+            assert_eq!(
+                EntriesCounts::default(),
+                summary.roles,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_display,
+                summary.to_string(),
+                "{}",
+                tc._description
+            );
         }
     }
 }
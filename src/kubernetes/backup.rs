@@ -0,0 +1,241 @@
+use crate::kubernetes::KubernetesError;
+use k8s_openapi::api::core::v1::ConfigMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Prefix of backup config map data keys, followed by the backup date, e.q: `backup-2024-01-01T00-00-00Z`.
+const BACKUP_KEY_PREFIX: &str = "backup-";
+
+/// Content of aws-auth config map managed by the tool, as it was before a modification.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ConfigMapBackup {
+    #[serde(rename = "mapUsers")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub map_users: Option<String>,
+    #[serde(rename = "mapRoles")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub map_roles: Option<String>,
+    #[serde(rename = "mapAccounts")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub map_accounts: Option<String>,
+}
+
+impl ConfigMapBackup {
+    pub fn from_config_map(config_map: &ConfigMap) -> ConfigMapBackup {
+        let data = config_map.data.clone().unwrap_or_default();
+
+        ConfigMapBackup {
+            map_users: data.get("mapUsers").cloned(),
+            map_roles: data.get("mapRoles").cloned(),
+            map_accounts: data.get("mapAccounts").cloned(),
+        }
+    }
+
+    /// Writes backup content into `config_map`, keys absent from the backup are removed.
+    pub fn apply_to(&self, config_map: &mut ConfigMap) {
+        let data = config_map.data.get_or_insert_with(BTreeMap::new);
+        for (key, value) in [
+            ("mapUsers", &self.map_users),
+            ("mapRoles", &self.map_roles),
+            ("mapAccounts", &self.map_accounts),
+        ] {
+            match value {
+                Some(value) => data.insert(key.to_string(), value.clone()),
+                None => data.remove(key),
+            };
+        }
+    }
+}
+
+/// Returns backups names from the oldest to the most recent one.
+pub fn backup_names(backups: &BTreeMap<String, String>) -> Vec<String> {
+    // keys embed an RFC 3339 date, lexicographic order is the chronological one
+    backups
+        .keys()
+        .filter(|k| k.starts_with(BACKUP_KEY_PREFIX))
+        .cloned()
+        .collect()
+}
+
+/// Adds `backup` to `backups`, keeping the `history_count` most recent ones.
+/// Nothing is added if the most recent backup already holds the same content, returns whether it was added.
+pub fn add_backup(
+    backups: &mut BTreeMap<String, String>,
+    backup: &ConfigMapBackup,
+    taken_at: SystemTime,
+    history_count: usize,
+) -> Result<bool, KubernetesError> {
+    if let Some(latest_backup_name) = backup_names(backups).last() {
+        if get_backup(backups, Some(latest_backup_name))
+            .ok()
+            .map(|(_, b)| b)
+            == Some(backup.clone())
+        {
+            return Ok(false);
+        }
+    }
+
+    backups.insert(
+        format!(
+            "{BACKUP_KEY_PREFIX}{}",
+            humantime::format_rfc3339_seconds(taken_at)
+                .to_string()
+                .replace(':', "-")
+        ),
+        serde_yaml::to_string(backup).map_err(|e| KubernetesError::CannotSerializeBackup {
+            raw_message: Arc::from(e.to_string()),
+        })?,
+    );
+
+    let backup_names = backup_names(backups);
+    for expired_backup_name in backup_names
+        .iter()
+        .take(backup_names.len().saturating_sub(history_count))
+    {
+        backups.remove(expired_backup_name);
+    }
+
+    Ok(true)
+}
+
+/// Returns backup named `backup_name`, or the most recent one if not set.
+pub fn get_backup(
+    backups: &BTreeMap<String, String>,
+    backup_name: Option<&str>,
+) -> Result<(String, ConfigMapBackup), KubernetesError> {
+    let backup_name = match backup_name {
+        Some(backup_name) => backup_name.to_string(),
+        None => backup_names(backups)
+            .pop()
+            .ok_or(KubernetesError::BackupNotFound {
+                backup_name: Arc::from("latest"),
+            })?,
+    };
+
+    let raw_backup = backups
+        .get(&backup_name)
+        .ok_or_else(|| KubernetesError::BackupNotFound {
+            backup_name: Arc::from(backup_name.as_str()),
+        })?;
+
+    let backup = serde_yaml::from_str::<ConfigMapBackup>(raw_backup).map_err(|e| {
+        KubernetesError::CannotDeserializeBackup {
+            backup_name: Arc::from(backup_name.as_str()),
+            underlying_error: Arc::from(e.to_string()),
+        }
+    })?;
+
+    Ok((backup_name, backup))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::backup::{add_backup, backup_names, get_backup, ConfigMapBackup};
+    use crate::kubernetes::KubernetesError;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    fn backup(map_users: &str) -> ConfigMapBackup {
+        ConfigMapBackup {
+            map_users: Some(map_users.to_string()),
+            map_roles: Some("[]\n".to_string()),
+            map_accounts: None,
+        }
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + seconds)
+    }
+
+    #[test]
+    fn add_backup_keeps_history_count_test() {
+        // setup:
+        let mut backups = BTreeMap::new();
+
+        // execute:
+        for i in 0..4 {
+            add_backup(&mut backups, &backup(&format!("v{i}")), at(i), 2)
+                .expect("backup should be added");
+        }
+
+        // verify:
+        assert_eq!(
+            vec![
+                "backup-2023-11-14T22-13-22Z".to_string(),
+                "backup-2023-11-14T22-13-23Z".to_string()
+            ],
+            backup_names(&backups)
+        );
+        assert_eq!(
+            Ok(("backup-2023-11-14T22-13-23Z".to_string(), backup("v3"))),
+            get_backup(&backups, None)
+        );
+        assert_eq!(
+            Ok(("backup-2023-11-14T22-13-22Z".to_string(), backup("v2"))),
+            get_backup(&backups, Some("backup-2023-11-14T22-13-22Z"))
+        );
+    }
+
+    #[test]
+    fn add_backup_skips_unchanged_content_test() {
+        // setup:
+        let mut backups = BTreeMap::new();
+        add_backup(&mut backups, &backup("v1"), at(0), 5).expect("backup should be added");
+
+        // execute:
+        let res = add_backup(&mut backups, &backup("v1"), at(1), 5);
+
+        // verify:
+        assert_eq!(Ok(false), res);
+        assert_eq!(1, backup_names(&backups).len());
+    }
+
+    #[test]
+    fn get_backup_not_found_test() {
+        assert_eq!(
+            Err(KubernetesError::BackupNotFound {
+                backup_name: Arc::from("latest")
+            }),
+            get_backup(&BTreeMap::new(), None)
+        );
+        assert_eq!(
+            Err(KubernetesError::BackupNotFound {
+                backup_name: Arc::from("backup-whatever")
+            }),
+            get_backup(&BTreeMap::new(), Some("backup-whatever"))
+        );
+    }
+
+    #[test]
+    fn config_map_backup_apply_to_test() {
+        // setup:
+        let mut config_map = ConfigMap {
+            data: Some(BTreeMap::from_iter(vec![
+                ("mapUsers".to_string(), "- new".to_string()),
+                ("mapAccounts".to_string(), "- \"111122223333\"".to_string()),
+                ("someOtherKey".to_string(), "whatever".to_string()),
+            ])),
+            ..Default::default()
+        };
+
+        // execute:
+        backup("- old").apply_to(&mut config_map);
+
+        // verify:
+        assert_eq!(
+            Some(BTreeMap::from_iter(vec![
+                ("mapUsers".to_string(), "- old".to_string()),
+                ("mapRoles".to_string(), "[]\n".to_string()),
+                ("someOtherKey".to_string(), "whatever".to_string()),
+            ])),
+            config_map.data
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use crate::kubernetes::aws_auth::AwsAuthDiff;
+use crate::kubernetes::{KubernetesError, KubernetesService};
+use k8s_openapi::api::core::v1::ObjectReference;
+use k8s_openapi::api::events::v1::{Event, EventSeries};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta, Time};
+use k8s_openapi::chrono::{DateTime, TimeDelta, Utc};
+use kube::api::PostParams;
+use kube::Api;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Component emitting events, shown as their source.
+const REPORTING_CONTROLLER: &str = "iam-eks-user-mapper";
+/// Identical events occurring within this delay are aggregated into the previous one, as client-go does.
+const EVENT_AGGREGATION_WINDOW: TimeDelta = TimeDelta::minutes(10);
+/// Maximum event note size accepted by the API server.
+const MAX_NOTE_LENGTH: usize = 1024;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncEventType {
+    Normal,
+    Warning,
+}
+
+impl SyncEventType {
+    fn as_str(&self) -> &str {
+        match self {
+            SyncEventType::Normal => "Normal",
+            SyncEventType::Warning => "Warning",
+        }
+    }
+}
+
+/// Outcome of a sync, published as an event attached to the aws-auth config map.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyncEvent {
+    pub event_type: SyncEventType,
+    pub reason: &'static str,
+    pub note: String,
+}
+
+impl SyncEvent {
+    pub fn succeeded(diff: &AwsAuthDiff) -> SyncEvent {
+        SyncEvent {
+            event_type: SyncEventType::Normal,
+            reason: "SyncSucceeded",
+            note: format!(
+                "Users: {} added, {} removed, {} modified. Roles: {} added, {} removed, {} modified",
+                diff.users.added.len(),
+                diff.users.removed.len(),
+                diff.users.modified.len(),
+                diff.roles.added.len(),
+                diff.roles.removed.len(),
+                diff.roles.modified.len(),
+            ),
+        }
+    }
+
+    pub fn failed(error: &impl Display) -> SyncEvent {
+        let mut note = error.to_string();
+        if note.len() > MAX_NOTE_LENGTH {
+            let mut end = MAX_NOTE_LENGTH - "...".len();
+            while !note.is_char_boundary(end) {
+                end -= 1;
+            }
+            note.truncate(end);
+            note.push_str("...");
+        }
+
+        SyncEvent {
+            event_type: SyncEventType::Warning,
+            reason: "SyncFailed",
+            note,
+        }
+    }
+}
+
+/// Publishes sync events through the events.k8s.io API, aggregating identical consecutive ones.
+pub struct EventRecorder {
+    events_api: Api<Event>,
+    regarding: ObjectReference,
+    /// Identity of this replica, e.q: its pod name.
+    reporting_instance: String,
+    /// Last published event, updated instead of creating a new one when the same event occurs again.
+    last_event: Option<Event>,
+}
+
+impl KubernetesService {
+    pub fn event_recorder(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        reporting_instance: &str,
+    ) -> EventRecorder {
+        EventRecorder {
+            events_api: Api::namespaced(self.client.clone(), config_map_namespace),
+            regarding: ObjectReference {
+                api_version: Some("v1".to_string()),
+                kind: Some("ConfigMap".to_string()),
+                namespace: Some(config_map_namespace.to_string()),
+                name: Some(config_map_name.to_string()),
+                ..Default::default()
+            },
+            reporting_instance: reporting_instance.to_string(),
+            last_event: None,
+        }
+    }
+}
+
+impl EventRecorder {
+    /// Publishes `sync_event`, bumping the previous event series if it's the same one.
+    pub async fn record(&mut self, sync_event: &SyncEvent) -> Result<(), KubernetesError> {
+        let event = self.next_event(sync_event, Utc::now());
+        let result = match (&event.metadata.name, &event.metadata.resource_version) {
+            (Some(name), Some(_)) => {
+                self.events_api
+                    .replace(name, &PostParams::default(), &event)
+                    .await
+            }
+            _ => self.events_api.create(&PostParams::default(), &event).await,
+        };
+
+        match result {
+            Ok(event) => {
+                self.last_event = Some(event);
+                Ok(())
+            }
+            Err(e) => {
+                // previous event may have been garbage collected, starting a new one next time
+                self.last_event = None;
+                Err(KubernetesError::EventCannotBeRecorded {
+                    reason: Arc::from(sync_event.reason),
+                    raw_message: Arc::from(e.to_string()),
+                })
+            }
+        }
+    }
+
+    /// Returns the event to publish for `sync_event` occurring at `now`: the last event with its series bumped if identical
+    /// and recent enough, a new event otherwise.
+    fn next_event(&self, sync_event: &SyncEvent, now: DateTime<Utc>) -> Event {
+        match &self.last_event {
+            Some(last_event) if Self::aggregates(last_event, sync_event, now) => {
+                let mut event = last_event.clone();
+                let count = event.series.as_ref().map(|s| s.count).unwrap_or(1) + 1;
+                event.series = Some(EventSeries {
+                    count,
+                    last_observed_time: MicroTime(now),
+                });
+                event.deprecated_count = Some(count);
+                event.deprecated_last_timestamp = Some(Time(now));
+
+                event
+            }
+            _ => Event {
+                metadata: ObjectMeta {
+                    // same naming as client-go: regarding object name followed by a unique suffix
+                    name: Some(format!(
+                        "{}.{:x}",
+                        self.regarding.name.as_deref().unwrap_or_default(),
+                        now.timestamp_nanos_opt().unwrap_or_default()
+                    )),
+                    namespace: self.regarding.namespace.clone(),
+                    ..Default::default()
+                },
+                event_time: Some(MicroTime(now)),
+                action: Some("Sync".to_string()),
+                reason: Some(sync_event.reason.to_string()),
+                note: Some(sync_event.note.clone()),
+                type_: Some(sync_event.event_type.as_str().to_string()),
+                regarding: Some(self.regarding.clone()),
+                reporting_controller: Some(REPORTING_CONTROLLER.to_string()),
+                reporting_instance: Some(self.reporting_instance.clone()),
+                deprecated_count: Some(1),
+                deprecated_first_timestamp: Some(Time(now)),
+                deprecated_last_timestamp: Some(Time(now)),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Whether `sync_event` is the same as `last_event`, last observed less than aggregation window ago.
+    fn aggregates(last_event: &Event, sync_event: &SyncEvent, now: DateTime<Utc>) -> bool {
+        let last_observed_time = last_event
+            .series
+            .as_ref()
+            .map(|s| s.last_observed_time.0)
+            .or(last_event.event_time.as_ref().map(|t| t.0));
+
+        last_event.type_.as_deref() == Some(sync_event.event_type.as_str())
+            && last_event.reason.as_deref() == Some(sync_event.reason)
+            && last_event.note.as_deref() == Some(sync_event.note.as_str())
+            && last_observed_time.is_some_and(|t| now - t < EVENT_AGGREGATION_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::{AwsAuthDiff, EntriesDiff};
+    use crate::kubernetes::events::{SyncEvent, SyncEventType, MAX_NOTE_LENGTH};
+    use crate::kubernetes::tests::status_response;
+    use crate::kubernetes::{
+        ConfigMapOptions, IamArn, IamUserName, KubernetesError, KubernetesService, KubernetesUser,
+    };
+    use http::{Method, Request, Response};
+    use k8s_openapi::api::events::v1::Event;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::MicroTime;
+    use k8s_openapi::chrono::{TimeDelta, Utc};
+    use kube::client::Body;
+    use kube::Client;
+    use std::collections::BTreeSet;
+
+    type ApiServerHandle = tower_test::mock::Handle<Request<Body>, Response<Body>>;
+
+    fn mock_kubernetes_service() -> (KubernetesService, ApiServerHandle) {
+        let (mock_service, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        (
+            KubernetesService::with_client(
+                Client::new(mock_service, "default"),
+                ConfigMapOptions::default(),
+            ),
+            handle,
+        )
+    }
+
+    fn user(name: &str) -> KubernetesUser {
+        KubernetesUser::new(
+            IamUserName::new(name),
+            IamArn::new(&format!("arn:test:{name}")),
+            BTreeSet::new(),
+            None,
+        )
+    }
+
+    async fn request_body_event(request: Request<Body>) -> Event {
+        let body = request
+            .into_body()
+            .collect_bytes()
+            .await
+            .expect("cannot read request body");
+        serde_json::from_slice(&body).expect("request body should be an event")
+    }
+
+    fn event_response(event: &Event, resource_version: &str) -> Response<Body> {
+        let mut event = event.clone();
+        event.metadata.resource_version = Some(resource_version.to_string());
+        Response::builder()
+            .status(200)
+            .body(Body::from(serde_json::to_vec(&event).unwrap()))
+            .unwrap()
+    }
+
+    #[test]
+    fn sync_event_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: SyncEvent,
+            expected_output: SyncEvent,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: SyncEvent::succeeded(&AwsAuthDiff {
+                    users: EntriesDiff {
+                        added: vec![user("user_1"), user("user_2")],
+                        removed: vec![user("user_3")],
+                        modified: vec![(user("user_4"), user("user_4"))],
+                        untouched: vec![user("user_5")],
+                        unchanged: vec![],
+                        suppressed: vec![],
+                    },
+                    roles: EntriesDiff::default(),
+                }),
+                expected_output: SyncEvent {
+                    event_type: SyncEventType::Normal,
+                    reason: "SyncSucceeded",
+                    note: "Users: 2 added, 1 removed, 1 modified. Roles: 0 added, 0 removed, 0 modified".to_string(),
+                },
+                _description: "case 1: succeeded sync with changes counts",
+            },
+            TestCase {
+                input: SyncEvent::failed(&KubernetesError::ClusterUnreachable {
+                    raw_message: "connection refused".into(),
+                }),
+                expected_output: SyncEvent {
+                    event_type: SyncEventType::Warning,
+                    reason: "SyncFailed",
+                    note: "Cluster not reachable: connection refused".to_string(),
+                },
+                _description: "case 2: failed sync with error summary",
+            },
+            TestCase {
+                input: SyncEvent::failed(&"é".repeat(MAX_NOTE_LENGTH)),
+                expected_output: SyncEvent {
+                    event_type: SyncEventType::Warning,
+                    reason: "SyncFailed",
+                    note: format!("{}...", "é".repeat((MAX_NOTE_LENGTH - 4) / 2)),
+                },
+                _description: "case 3: too long error summary is truncated on a char boundary",
+            },
+        ];
+
+        for tc in test_cases {
+            // verify:
+            assert_eq!(tc.expected_output, tc.input, "{}", tc._description);
+            assert!(tc.input.note.len() <= MAX_NOTE_LENGTH);
+        }
+    }
+
+    #[tokio::test]
+    async fn next_event_test() {
+        // setup:
+        let (service, _handle) = mock_kubernetes_service();
+        let mut recorder = service.event_recorder("kube-system", "aws-auth", "replica-1");
+        let now = Utc::now();
+        let failed = SyncEvent::failed(&"IAM unreachable");
+
+        // execute & verify:
+        let first = recorder.next_event(&failed, now);
+        assert_eq!(Some("kube-system"), first.metadata.namespace.as_deref());
+        assert!(first
+            .metadata
+            .name
+            .as_deref()
+            .is_some_and(|n| n.starts_with("aws-auth.")));
+        assert_eq!(Some("Warning"), first.type_.as_deref());
+        assert_eq!(Some("SyncFailed"), first.reason.as_deref());
+        assert_eq!(Some("IAM unreachable"), first.note.as_deref());
+        assert_eq!(
+            Some("aws-auth"),
+            first.regarding.as_ref().and_then(|r| r.name.as_deref())
+        );
+        assert_eq!(
+            Some("iam-eks-user-mapper"),
+            first.reporting_controller.as_deref()
+        );
+        assert_eq!(Some("replica-1"), first.reporting_instance.as_deref());
+        assert_eq!(None, first.series);
+        assert_eq!(Some(1), first.deprecated_count);
+
+        recorder.last_event = Some(first.clone());
+        let second = recorder.next_event(&failed, now + TimeDelta::minutes(1));
+        assert_eq!(
+            first.metadata.name, second.metadata.name,
+            "same event is aggregated"
+        );
+        assert_eq!(Some(2), second.series.as_ref().map(|s| s.count));
+        assert_eq!(
+            Some(MicroTime(now + TimeDelta::minutes(1))),
+            second.series.as_ref().map(|s| s.last_observed_time.clone())
+        );
+        assert_eq!(Some(2), second.deprecated_count);
+        assert_eq!(first.event_time, second.event_time);
+
+        recorder.last_event = Some(second.clone());
+        let third = recorder.next_event(&failed, now + TimeDelta::minutes(10));
+        assert_eq!(
+            Some(3),
+            third.series.as_ref().map(|s| s.count),
+            "window starts from last observation"
+        );
+
+        recorder.last_event = Some(third);
+        let other = recorder.next_event(
+            &SyncEvent::failed(&"cluster unreachable"),
+            now + TimeDelta::minutes(11),
+        );
+        assert_eq!(None, other.series, "different event is not aggregated");
+        assert_ne!(first.metadata.name, other.metadata.name);
+
+        let late = recorder.next_event(&failed, now + TimeDelta::minutes(21));
+        assert_eq!(
+            None, late.series,
+            "event past aggregation window is not aggregated"
+        );
+    }
+
+    #[tokio::test]
+    async fn record_aggregates_identical_events_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let mut recorder = service.event_recorder("kube-system", "aws-auth", "replica-1");
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("POST expected");
+            assert_eq!(Method::POST, request.method());
+            assert_eq!(
+                "/apis/events.k8s.io/v1/namespaces/kube-system/events?",
+                request.uri().to_string()
+            );
+            let created = request_body_event(request).await;
+            send.send_response(event_response(&created, "1"));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let replaced = request_body_event(request).await;
+            assert_eq!(created.metadata.name, replaced.metadata.name);
+            assert_eq!(Some(2), replaced.series.as_ref().map(|s| s.count));
+            send.send_response(status_response(404, "NotFound"));
+
+            let (request, send) = handle.next_request().await.expect("POST expected");
+            assert_eq!(Method::POST, request.method());
+            let created = request_body_event(request).await;
+            assert_eq!(None, created.series);
+            send.send_response(event_response(&created, "1"));
+        });
+
+        // execute:
+        let failed = SyncEvent::failed(&"IAM unreachable");
+        let first = recorder.record(&failed).await;
+        let second = recorder.record(&failed).await;
+        let third = recorder.record(&failed).await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok(()), first);
+        assert!(matches!(
+            second,
+            Err(KubernetesError::EventCannotBeRecorded { .. })
+        ));
+        assert_eq!(
+            Ok(()),
+            third,
+            "a new event is created once the previous one is gone"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use crate::kubernetes::aws_auth::AwsAuth;
+use crate::kubernetes::identity_mapping::{IdentityMapping, IdentityMappingsApi};
+use crate::kubernetes::{
+    AuthBackend, AwsAccountId, ClusterPreflight, ConfigMapOptions, ConfigMapUpdate,
+    ConfigMapUpdateStatus, KubernetesError, KubernetesRole, KubernetesService, KubernetesUser,
+};
+use k8s_openapi::api::core::v1::ConfigMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+/// In-memory aws-auth config map, used to test the sync logic without any cluster.
+#[derive(Default)]
+pub struct FakeAuthBackend {
+    pub config_map: Mutex<ConfigMap>,
+    pub resource_version: Mutex<u64>,
+    pub updates_count: Mutex<usize>,
+    /// Fails every request as an unreachable cluster would.
+    pub unreachable: bool,
+    /// RBAC denying any write to the config map.
+    pub read_only: bool,
+    /// Options the config map is merged with, as the real backend would.
+    pub options: ConfigMapOptions,
+}
+
+impl FakeAuthBackend {
+    pub fn new(data: BTreeMap<String, String>) -> FakeAuthBackend {
+        FakeAuthBackend {
+            config_map: Mutex::new(ConfigMap {
+                data: Some(data),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn unreachable() -> FakeAuthBackend {
+        FakeAuthBackend {
+            unreachable: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_options(self, options: ConfigMapOptions) -> FakeAuthBackend {
+        FakeAuthBackend { options, ..self }
+    }
+
+    fn check_reachable(&self) -> Result<(), KubernetesError> {
+        match self.unreachable {
+            true => Err(KubernetesError::ClusterUnreachable {
+                raw_message: Arc::from("connection refused"),
+            }),
+            false => Ok(()),
+        }
+    }
+
+    pub fn config_map(&self) -> ConfigMap {
+        self.config_map
+            .lock()
+            .expect("cannot lock fake config map")
+            .clone()
+    }
+
+    pub fn data(&self) -> BTreeMap<String, String> {
+        self.config_map().data.unwrap_or_default()
+    }
+
+    /// Simulates someone else modifying the config map.
+    pub fn set_config_map(&self, config_map: ConfigMap) {
+        *self.config_map.lock().expect("cannot lock fake config map") = config_map;
+        *self
+            .resource_version
+            .lock()
+            .expect("cannot lock fake resource version") += 1;
+    }
+
+    /// Simulates someone else modifying the config map data.
+    pub fn set_data(&self, data: BTreeMap<String, String>) {
+        let mut config_map = self.config_map();
+        config_map.data = Some(data);
+        self.set_config_map(config_map);
+    }
+
+    pub fn updates_count(&self) -> usize {
+        *self
+            .updates_count
+            .lock()
+            .expect("cannot lock fake updates count")
+    }
+}
+
+impl AuthBackend for FakeAuthBackend {
+    async fn get_config_map_resource_version(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError> {
+        self.check_reachable()?;
+        Ok(Some(
+            self.resource_version
+                .lock()
+                .expect("cannot lock fake resource version")
+                .to_string(),
+        ))
+    }
+
+    async fn read_current(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        self.check_reachable()?;
+        KubernetesService::parse_aws_auth(&self.config_map()).map(Some)
+    }
+
+    async fn update_user_and_role_config_map(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError> {
+        self.check_reachable()?;
+        let previous_resource_version = self
+            .resource_version
+            .lock()
+            .expect("cannot lock fake resource version")
+            .to_string();
+        let original_config_map = self.config_map();
+        let mut config_map = original_config_map.clone();
+        let (aws_auth, diff) = KubernetesService::merge_config_map_with_report(
+            &mut config_map,
+            kubernetes_users_to_be_added,
+            kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            aws_accounts_to_be_added,
+            &self.options,
+        )?;
+        let status = match KubernetesService::config_map_content_changed(
+            &original_config_map,
+            &config_map,
+        ) {
+            true => {
+                self.set_config_map(config_map);
+                *self
+                    .updates_count
+                    .lock()
+                    .expect("cannot lock fake updates count") += 1;
+                ConfigMapUpdateStatus::Updated
+            }
+            false => ConfigMapUpdateStatus::Unchanged,
+        };
+
+        Ok(ConfigMapUpdate {
+            diff,
+            next_expiry: self.options.next_expiry([
+                &KubernetesService::parse_aws_auth(&original_config_map)?,
+                &aws_auth,
+            ]),
+            aws_auth,
+            previous_resource_version: Some(previous_resource_version),
+            resource_version: Some(
+                self.resource_version
+                    .lock()
+                    .expect("cannot lock fake resource version")
+                    .to_string(),
+            ),
+            status,
+        })
+    }
+}
+
+impl ClusterPreflight for FakeAuthBackend {
+    async fn server_version(&self) -> Result<String, KubernetesError> {
+        self.check_reachable()?;
+        Ok("v1.30.0".to_string())
+    }
+
+    async fn read_aws_auth(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        self.read_current(config_map_namespace, config_map_name)
+            .await
+    }
+
+    async fn can_i(
+        &self,
+        verb: &str,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<bool, KubernetesError> {
+        self.check_reachable()?;
+        Ok(verb == "get" || !self.read_only)
+    }
+}
+
+/// In-memory aws-auth like config maps, keyed by `<namespace>/<name>`, requests to a missing one failing.
+#[derive(Default)]
+pub struct FakeConfigMapsBackend {
+    pub config_maps: BTreeMap<String, FakeAuthBackend>,
+}
+
+impl FakeConfigMapsBackend {
+    pub fn new(config_maps: Vec<(&str, FakeAuthBackend)>) -> FakeConfigMapsBackend {
+        FakeConfigMapsBackend {
+            config_maps: config_maps
+                .into_iter()
+                .map(|(target, backend)| (target.to_string(), backend))
+                .collect(),
+        }
+    }
+
+    pub fn config_map(&self, target: &str) -> &FakeAuthBackend {
+        self.config_maps
+            .get(target)
+            .expect("fake config map should exist")
+    }
+
+    fn backend(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<&FakeAuthBackend, KubernetesError> {
+        self.config_maps
+            .get(&format!("{config_map_namespace}/{config_map_name}"))
+            .ok_or_else(|| KubernetesError::ConfigMapNotFound {
+                config_map_name: Arc::from(config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from("NotFound"),
+            })
+    }
+}
+
+impl ClusterPreflight for FakeConfigMapsBackend {
+    async fn server_version(&self) -> Result<String, KubernetesError> {
+        Ok("v1.30.0".to_string())
+    }
+
+    async fn read_aws_auth(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        match self.backend(config_map_namespace, config_map_name) {
+            Ok(backend) => {
+                backend
+                    .read_aws_auth(config_map_namespace, config_map_name)
+                    .await
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn can_i(
+        &self,
+        _verb: &str,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<bool, KubernetesError> {
+        Ok(true)
+    }
+}
+
+impl AuthBackend for FakeConfigMapsBackend {
+    async fn get_config_map_resource_version(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError> {
+        self.backend(config_map_namespace, config_map_name)?
+            .get_config_map_resource_version(config_map_namespace, config_map_name)
+            .await
+    }
+
+    async fn read_current(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        self.backend(config_map_namespace, config_map_name)?
+            .read_current(config_map_namespace, config_map_name)
+            .await
+    }
+
+    async fn update_user_and_role_config_map(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError> {
+        self.backend(config_map_namespace, config_map_name)?
+            .update_user_and_role_config_map(
+                config_map_namespace,
+                config_map_name,
+                kubernetes_users_to_be_added,
+                kubernetes_sso_role_to_be_added,
+                karpenter_roles_to_be_added,
+                other_roles_to_be_added,
+                aws_accounts_to_be_added,
+            )
+            .await
+    }
+}
+
+/// In-memory `IAMIdentityMapping` resources, keyed by name.
+#[derive(Default)]
+pub struct FakeIdentityMappingsApi {
+    pub identity_mappings: Mutex<BTreeMap<String, IdentityMapping>>,
+    /// Write requests received, e.q: `delete user-1`.
+    pub writes: Mutex<Vec<String>>,
+}
+
+impl FakeIdentityMappingsApi {
+    pub fn new(identity_mappings: Vec<IdentityMapping>) -> FakeIdentityMappingsApi {
+        FakeIdentityMappingsApi {
+            identity_mappings: Mutex::new(
+                identity_mappings
+                    .into_iter()
+                    .map(|m| (m.name.clone(), m))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    pub fn identity_mappings(&self) -> BTreeMap<String, IdentityMapping> {
+        self.identity_mappings
+            .lock()
+            .expect("cannot lock fake identity mappings")
+            .clone()
+    }
+
+    pub fn writes(&self) -> Vec<String> {
+        self.writes.lock().expect("cannot lock fake writes").clone()
+    }
+
+    fn write(&self, write: &str, mapping: IdentityMapping) -> Result<(), KubernetesError> {
+        self.writes
+            .lock()
+            .expect("cannot lock fake writes")
+            .push(format!("{write} {}", mapping.name));
+        let mut identity_mappings = self
+            .identity_mappings
+            .lock()
+            .expect("cannot lock fake identity mappings");
+        let exists = identity_mappings.contains_key(&mapping.name);
+        if exists == (write == "create") {
+            return Err(KubernetesError::IdentityMappingCannotBeWritten {
+                name: Arc::from(mapping.name.as_str()),
+                raw_message: Arc::from(match exists {
+                    true => "AlreadyExists",
+                    false => "NotFound",
+                }),
+            });
+        }
+        match write {
+            "delete" => identity_mappings.remove(&mapping.name),
+            _ => identity_mappings.insert(mapping.name.clone(), mapping),
+        };
+
+        Ok(())
+    }
+}
+
+impl IdentityMappingsApi for FakeIdentityMappingsApi {
+    async fn list_identity_mappings(&self) -> Result<Vec<IdentityMapping>, KubernetesError> {
+        Ok(self.identity_mappings().into_values().collect())
+    }
+
+    async fn create_identity_mapping(
+        &self,
+        mapping: &IdentityMapping,
+    ) -> Result<(), KubernetesError> {
+        self.write("create", mapping.clone())
+    }
+
+    async fn replace_identity_mapping(
+        &self,
+        mapping: &IdentityMapping,
+    ) -> Result<(), KubernetesError> {
+        self.write("replace", mapping.clone())
+    }
+
+    async fn delete_identity_mapping(&self, name: &str) -> Result<(), KubernetesError> {
+        self.write(
+            "delete",
+            IdentityMapping {
+                name: name.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+}
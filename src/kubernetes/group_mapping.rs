@@ -0,0 +1,369 @@
+use crate::kubernetes::{
+    retry_on_transient_error, KubernetesError, KubernetesGroupName, KubernetesService, RequestError,
+};
+use futures::StreamExt;
+use kube::api::{ListParams, Patch, PatchParams};
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::{Api, CustomResource, ResourceExt};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Placeholder of `usernameTemplate` replaced by the IAM user name.
+pub const USERNAME_PLACEHOLDER: &str = "{{username}}";
+
+/// Maps an IAM group to Kubernetes groups, read in CRD mode instead of `iam_k8s_groups`.
+#[derive(
+    CustomResource, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema,
+)]
+#[kube(
+    group = "iam-eks-user-mapper.qovery.com",
+    version = "v1alpha1",
+    kind = "IamGroupMapping",
+    namespaced,
+    status = "IamGroupMappingStatus",
+    shortname = "igm",
+    printcolumn = r#"{"name":"IAM group","type":"string","jsonPath":".spec.iamGroup"}"#,
+    printcolumn = r#"{"name":"Users","type":"integer","jsonPath":".status.userCount"}"#,
+    printcolumn = r#"{"name":"Error","type":"string","jsonPath":".status.error"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct IamGroupMappingSpec {
+    /// IAM group whose users are synced.
+    pub iam_group: String,
+    /// Kubernetes groups granted to the IAM group users.
+    pub kubernetes_groups: Vec<String>,
+    /// Kubernetes username of the IAM group users, `{{username}}` being replaced by their IAM user name. IAM user name if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_template: Option<String>,
+}
+
+/// Outcome of the last sync of a mapping.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IamGroupMappingStatus {
+    /// Last sync attempt, e.q: `2024-01-01T00:00:00Z`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_sync_time: Option<String>,
+    /// Number of IAM group users synced, kept from the last successful sync on errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_count: Option<usize>,
+    /// Error of the last sync, always serialized for a merge patch to clear it once sync succeeds.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Mapping generation the status has been computed from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+}
+
+impl IamGroupMapping {
+    /// Fails with the reason the mapping cannot be synced, such mappings being ignored.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.spec.iam_group.trim().is_empty() {
+            return Err("`iamGroup` cannot be empty".to_string());
+        }
+        if self.spec.kubernetes_groups.is_empty()
+            || self
+                .spec
+                .kubernetes_groups
+                .iter()
+                .any(|g| g.trim().is_empty())
+        {
+            return Err("`kubernetesGroups` cannot be empty nor contain empty groups".to_string());
+        }
+        for kubernetes_group in &self.spec.kubernetes_groups {
+            KubernetesGroupName::parse(kubernetes_group).map_err(|e| e.to_string())?;
+        }
+        if let Some(username_template) = &self.spec.username_template {
+            if username_template.trim().is_empty() {
+                return Err("`usernameTemplate` cannot be empty if set".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn synced_status(&self, user_count: usize, sync_time: SystemTime) -> IamGroupMappingStatus {
+        IamGroupMappingStatus {
+            last_sync_time: Some(humantime::format_rfc3339_seconds(sync_time).to_string()),
+            user_count: Some(user_count),
+            error: None,
+            observed_generation: self.metadata.generation,
+        }
+    }
+
+    pub fn failed_status(&self, error: &str, sync_time: SystemTime) -> IamGroupMappingStatus {
+        IamGroupMappingStatus {
+            last_sync_time: Some(humantime::format_rfc3339_seconds(sync_time).to_string()),
+            user_count: None,
+            error: Some(error.to_string()),
+            observed_generation: self.metadata.generation,
+        }
+    }
+}
+
+/// Tells apart spec changes from status updates, which don't bump mappings generation.
+#[derive(Default)]
+struct GenerationTracker {
+    generations: HashMap<String, Option<i64>>,
+}
+
+impl GenerationTracker {
+    /// Whether `event` changes the mappings to be synced.
+    fn changed(&mut self, event: &watcher::Event<IamGroupMapping>) -> bool {
+        match event {
+            watcher::Event::Apply(m) | watcher::Event::InitApply(m) => {
+                self.generations.insert(m.name_any(), m.metadata.generation)
+                    != Some(m.metadata.generation)
+            }
+            watcher::Event::Delete(m) => {
+                self.generations.remove(&m.name_any());
+                true
+            }
+            watcher::Event::Init | watcher::Event::InitDone => false,
+        }
+    }
+}
+
+impl KubernetesService {
+    pub async fn list_group_mappings(
+        &self,
+        namespace: &str,
+    ) -> Result<Vec<IamGroupMapping>, KubernetesError> {
+        let (api, list_params): (Api<IamGroupMapping>, _) = (
+            Api::namespaced(self.client.clone(), namespace),
+            ListParams::default(),
+        );
+
+        retry_on_transient_error(
+            &format!("list IAM group mappings in namespace `{namespace}`"),
+            || api.list(&list_params),
+        )
+        .await
+        .map(|mappings| mappings.items)
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::GroupMappingsCannotBeListed {
+                namespace: Arc::from(namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })
+    }
+
+    /// Writes `status` of mapping `namespace/name`, nothing being written in dry run.
+    pub async fn update_group_mapping_status(
+        &self,
+        namespace: &str,
+        name: &str,
+        status: &IamGroupMappingStatus,
+    ) -> Result<(), KubernetesError> {
+        if self.options.dry_run {
+            debug!("Dry run, not writing IAM group mapping `{namespace}/{name}` status");
+            return Ok(());
+        }
+
+        let api: Api<IamGroupMapping> = Api::namespaced(self.client.clone(), namespace);
+        let (patch_params, patch) = (
+            PatchParams::default(),
+            Patch::Merge(serde_json::json!({ "status": status })),
+        );
+        retry_on_transient_error(
+            &format!("update IAM group mapping `{namespace}/{name}` status"),
+            || api.patch_status(name, &patch_params, &patch),
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::GroupMappingStatusCannotBeUpdated {
+                name: Arc::from(name),
+                namespace: Arc::from(namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })
+    }
+
+    /// Watches mappings of `namespace`, notifying `changes` each time one is created, deleted or its spec modified.
+    /// API server disconnections are retried with backoff, returns only once `changes` receiver is dropped.
+    pub async fn watch_group_mappings(&self, namespace: &str, changes: mpsc::Sender<()>) {
+        let api: Api<IamGroupMapping> = Api::namespaced(self.client.clone(), namespace);
+        let mut watcher_config = watcher::Config::default();
+        if let Some(request_timeout) = self.request_timeout {
+            watcher_config = watcher_config.timeout(Self::watch_timeout_seconds(request_timeout));
+        }
+        let mut events = watcher(api, watcher_config).default_backoff().boxed();
+        let mut generation_tracker = GenerationTracker::default();
+
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(event) if generation_tracker.changed(&event) => {
+                    info!("IAM group mappings of namespace `{namespace}` have been modified");
+                    // a full channel means a sync is already pending, changes are coalesced
+                    if let Err(mpsc::error::TrySendError::Closed(_)) = changes.try_send(()) {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "Error while watching IAM group mappings of namespace `{namespace}`, retrying: {e}"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::group_mapping::{
+        GenerationTracker, IamGroupMapping, IamGroupMappingSpec, IamGroupMappingStatus,
+    };
+    use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+    use kube::runtime::watcher;
+    use kube::CustomResourceExt;
+    use std::time::SystemTime;
+
+    fn group_mapping(name: &str, generation: i64, spec: IamGroupMappingSpec) -> IamGroupMapping {
+        let mut group_mapping = IamGroupMapping::new(name, spec);
+        group_mapping.metadata.generation = Some(generation);
+        group_mapping
+    }
+
+    #[test]
+    fn crd_manifest_test() {
+        // setup:
+        let chart_crd: CustomResourceDefinition = serde_yaml::from_str(include_str!(
+            "../../charts/iam-eks-user-mapper/crds/iamgroupmappings.yaml"
+        ))
+        .expect("chart CRD should be valid");
+
+        // execute:
+        let crd = IamGroupMapping::crd();
+
+        // verify:
+        assert!(
+            crd == chart_crd,
+            "chart CRD is outdated, it should be:\n{}",
+            serde_yaml::to_string(&crd).unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn validate_test() {
+        // setup:
+        struct TestCase<'a> {
+            spec: IamGroupMappingSpec,
+            expected_valid: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                spec: IamGroupMappingSpec {
+                    iam_group: "Devops".to_string(),
+                    kubernetes_groups: vec!["devops".to_string()],
+                    username_template: Some("iam-{{username}}".to_string()),
+                },
+                expected_valid: true,
+                _description: "case 1: valid mapping",
+            },
+            TestCase {
+                spec: IamGroupMappingSpec {
+                    iam_group: " ".to_string(),
+                    kubernetes_groups: vec!["devops".to_string()],
+                    username_template: None,
+                },
+                expected_valid: false,
+                _description: "case 2: empty IAM group",
+            },
+            TestCase {
+                spec: IamGroupMappingSpec {
+                    iam_group: "Devops".to_string(),
+                    kubernetes_groups: vec![],
+                    username_template: None,
+                },
+                expected_valid: false,
+                _description: "case 3: no Kubernetes groups",
+            },
+            TestCase {
+                spec: IamGroupMappingSpec {
+                    iam_group: "Devops".to_string(),
+                    kubernetes_groups: vec!["devops".to_string()],
+                    username_template: Some("".to_string()),
+                },
+                expected_valid: false,
+                _description: "case 4: empty username template",
+            },
+            TestCase {
+                spec: IamGroupMappingSpec {
+                    iam_group: "Devops".to_string(),
+                    kubernetes_groups: vec!["devops".to_string(), "dev ops".to_string()],
+                    username_template: None,
+                },
+                expected_valid: false,
+                _description: "case 5: invalid Kubernetes group name",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = group_mapping("devops", 1, tc.spec).validate();
+
+            // verify:
+            assert_eq!(tc.expected_valid, res.is_ok(), "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn status_test() {
+        // setup:
+        let group_mapping = group_mapping("devops", 3, IamGroupMappingSpec::default());
+
+        // execute:
+        let synced = group_mapping.synced_status(2, SystemTime::UNIX_EPOCH);
+        let failed = group_mapping.failed_status("boom", SystemTime::UNIX_EPOCH);
+
+        // verify:
+        assert_eq!(
+            IamGroupMappingStatus {
+                last_sync_time: Some("1970-01-01T00:00:00Z".to_string()),
+                user_count: Some(2),
+                error: None,
+                observed_generation: Some(3),
+            },
+            synced
+        );
+        // error is cleared by merge patches, user count is kept
+        assert_eq!(
+            serde_json::json!({"lastSyncTime": "1970-01-01T00:00:00Z", "userCount": 2, "error": null, "observedGeneration": 3}),
+            serde_json::to_value(&synced).expect("status should be serializable")
+        );
+        assert_eq!(
+            serde_json::json!({"lastSyncTime": "1970-01-01T00:00:00Z", "error": "boom", "observedGeneration": 3}),
+            serde_json::to_value(&failed).expect("status should be serializable")
+        );
+    }
+
+    #[test]
+    fn generation_tracker_test() {
+        // setup:
+        let mut tracker = GenerationTracker::default();
+        let v1 = group_mapping("devops", 1, IamGroupMappingSpec::default());
+        let v2 = group_mapping("devops", 2, IamGroupMappingSpec::default());
+
+        // execute & verify:
+        assert!(!tracker.changed(&watcher::Event::Init));
+        assert!(tracker.changed(&watcher::Event::InitApply(v1.clone())));
+        assert!(!tracker.changed(&watcher::Event::InitDone));
+        // status updates don't bump generation
+        assert!(!tracker.changed(&watcher::Event::Apply(v1.clone())));
+        assert!(tracker.changed(&watcher::Event::Apply(v2.clone())));
+        // re-listing after a disconnection
+        assert!(!tracker.changed(&watcher::Event::InitApply(v2.clone())));
+        assert!(tracker.changed(&watcher::Event::Delete(v2)));
+        assert!(tracker.changed(&watcher::Event::Apply(v1)));
+    }
+}
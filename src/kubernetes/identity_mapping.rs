@@ -0,0 +1,769 @@
+use crate::kubernetes::aws_auth::{AwsAuth, AwsAuthEntry};
+use crate::kubernetes::{
+    retry_on_transient_error, AuthBackend, AwsAccountId, ConfigMapOptions, ConfigMapUpdate,
+    ConfigMapUpdateStatus, IamArn, IamUserName, KubernetesError, KubernetesGroupName,
+    KubernetesRole, KubernetesService, KubernetesUser, RequestError, SyncedBy,
+};
+use kube::api::{ApiResource, DeleteParams, DynamicObject, GroupVersionKind, ListParams};
+use kube::Api;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// aws-iam-authenticator CRD, cluster scoped.
+const IDENTITY_MAPPING_GROUP: &str = "iamauthenticator.k8s.aws";
+const IDENTITY_MAPPING_VERSION: &str = "v1alpha1";
+const IDENTITY_MAPPING_KIND: &str = "IAMIdentityMapping";
+/// Label holding the marker of the instance which created a mapping, mappings without it being left untouched.
+const MANAGED_BY_LABEL: &str = "managed-by";
+/// Annotation holding when the entry was first synced, as `syncedAt` does in aws-auth.
+const SYNCED_AT_ANNOTATION: &str = "iam-eks-user-mapper/synced-at";
+/// Longest ARN part kept in generated mapping names, leaving room for the hash suffix.
+const MAX_NAME_PREFIX_LENGTH: usize = 48;
+
+/// `IAMIdentityMapping` spec, as read by aws-iam-authenticator.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct IdentityMappingSpec {
+    pub arn: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub groups: BTreeSet<String>,
+}
+
+/// `IAMIdentityMapping` resource, one per aws-auth entry.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct IdentityMapping {
+    pub name: String,
+    pub spec: IdentityMappingSpec,
+    /// Marker of the instance which created the mapping, not set if it hasn't been created by the tool.
+    pub synced_by: Option<SyncedBy>,
+    pub synced_at: Option<SystemTime>,
+    pub resource_version: Option<String>,
+}
+
+impl IdentityMapping {
+    pub fn from_user(user: &KubernetesUser) -> IdentityMapping {
+        Self::synced(
+            &user.iam_arn,
+            Some(user.iam_user_name.to_string()),
+            &user.roles,
+            user.synced_by.clone(),
+            user.synced_at,
+        )
+    }
+
+    pub fn from_role(role: &KubernetesRole) -> IdentityMapping {
+        Self::synced(
+            &role.iam_role_arn,
+            role.user_name.clone(),
+            &role.groups,
+            role.synced_by.clone(),
+            role.synced_at,
+        )
+    }
+
+    fn synced(
+        arn: &IamArn,
+        username: Option<String>,
+        groups: &BTreeSet<KubernetesGroupName>,
+        synced_by: Option<SyncedBy>,
+        synced_at: Option<SystemTime>,
+    ) -> IdentityMapping {
+        IdentityMapping {
+            name: Self::name_for(arn),
+            spec: IdentityMappingSpec {
+                arn: arn.to_string(),
+                username,
+                groups: groups.iter().map(|g| g.to_string()).collect(),
+            },
+            synced_by,
+            synced_at,
+            resource_version: None,
+        }
+    }
+
+    /// Resource name derived from `arn`: its last part, sanitized to be a valid name, and a hash of the whole ARN
+    /// telling apart principals sharing the same name in different accounts or paths.
+    fn name_for(arn: &IamArn) -> String {
+        let arn = arn.to_string().to_lowercase();
+        let mut hasher = DefaultHasher::new();
+        arn.hash(&mut hasher);
+
+        let prefix: String = arn
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c,
+                false => '-',
+            })
+            .take(MAX_NAME_PREFIX_LENGTH)
+            .collect();
+        match prefix.trim_matches('-') {
+            "" => format!("mapping-{:08x}", hasher.finish() as u32),
+            prefix => format!("{prefix}-{:08x}", hasher.finish() as u32),
+        }
+    }
+
+    fn is_user(&self) -> bool {
+        self.spec.arn.contains(":user/")
+    }
+
+    fn to_user(&self) -> KubernetesUser {
+        let mut user = KubernetesUser::new(
+            IamUserName::new(self.spec.username.as_deref().unwrap_or_default()),
+            IamArn::new(&self.spec.arn),
+            self.spec
+                .groups
+                .iter()
+                .map(|g| KubernetesGroupName::new(g))
+                .collect(),
+            self.synced_by.clone(),
+        );
+        user.synced_at = self.synced_at;
+
+        user
+    }
+
+    fn to_role(&self) -> KubernetesRole {
+        let mut role = KubernetesRole::new(
+            IamArn::new(&self.spec.arn),
+            None,
+            self.spec.username.clone(),
+            self.spec
+                .groups
+                .iter()
+                .map(|g| KubernetesGroupName::new(g))
+                .collect(),
+            self.synced_by.clone(),
+        );
+        role.synced_at = self.synced_at;
+
+        role
+    }
+
+    fn api_resource() -> ApiResource {
+        ApiResource::from_gvk(&GroupVersionKind::gvk(
+            IDENTITY_MAPPING_GROUP,
+            IDENTITY_MAPPING_VERSION,
+            IDENTITY_MAPPING_KIND,
+        ))
+    }
+
+    fn from_dynamic_object(object: DynamicObject) -> Result<IdentityMapping, KubernetesError> {
+        let name = object.metadata.name.unwrap_or_default();
+        let invalid = |raw_message: String| KubernetesError::InvalidIdentityMapping {
+            name: Arc::from(name.as_str()),
+            raw_message: Arc::from(raw_message),
+        };
+        let spec = serde_json::from_value(object.data.get("spec").cloned().unwrap_or_default())
+            .map_err(|e| invalid(e.to_string()))?;
+        let synced_at = object
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(SYNCED_AT_ANNOTATION))
+            .map(|raw| humantime::parse_rfc3339_weak(raw).map_err(|e| invalid(e.to_string())))
+            .transpose()?;
+
+        Ok(IdentityMapping {
+            spec,
+            synced_by: object
+                .metadata
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get(MANAGED_BY_LABEL))
+                .map(|v| SyncedBy::new(v)),
+            synced_at,
+            resource_version: object.metadata.resource_version,
+            name,
+        })
+    }
+
+    fn to_dynamic_object(&self) -> DynamicObject {
+        let mut object = DynamicObject::new(&self.name, &Self::api_resource())
+            .data(serde_json::json!({ "spec": self.spec }));
+        object.metadata.resource_version = self.resource_version.clone();
+        if let Some(synced_by) = &self.synced_by {
+            object.metadata.labels = Some(BTreeMap::from_iter(vec![(
+                MANAGED_BY_LABEL.to_string(),
+                synced_by.to_string(),
+            )]));
+        }
+        if let Some(synced_at) = self.synced_at {
+            object.metadata.annotations = Some(BTreeMap::from_iter(vec![(
+                SYNCED_AT_ANNOTATION.to_string(),
+                humantime::format_rfc3339_seconds(synced_at).to_string(),
+            )]));
+        }
+
+        object
+    }
+}
+
+/// Users and roles granted by `mappings`, as they would be listed in aws-auth.
+fn aws_auth(mappings: &[IdentityMapping]) -> AwsAuth {
+    let (users, roles): (Vec<&IdentityMapping>, Vec<&IdentityMapping>) =
+        mappings.iter().partition(|m| m.is_user());
+
+    AwsAuth {
+        users: users.into_iter().map(|m| m.to_user()).collect(),
+        roles: roles.into_iter().map(|m| m.to_role()).collect(),
+    }
+}
+
+/// Writes needed to go from existing mappings to the desired ones, only mappings synced by this instance being touched.
+#[derive(Debug, Default)]
+struct IdentityMappingsPlan {
+    create: Vec<IdentityMapping>,
+    update: Vec<IdentityMapping>,
+    delete: Vec<IdentityMapping>,
+}
+
+impl IdentityMappingsPlan {
+    fn new(
+        existing: &[IdentityMapping],
+        desired: &AwsAuth,
+        synced_by: &SyncedBy,
+    ) -> IdentityMappingsPlan {
+        let mut existing: HashMap<String, &IdentityMapping> = existing
+            .iter()
+            .filter(|m| m.synced_by.as_ref() == Some(synced_by))
+            .map(|m| (m.spec.arn.to_lowercase(), m))
+            .collect();
+        let desired = desired
+            .users
+            .iter()
+            .filter(|u| u.is_synced_by(synced_by))
+            .map(IdentityMapping::from_user)
+            .chain(
+                desired
+                    .roles
+                    .iter()
+                    .filter(|r| r.is_synced_by(synced_by))
+                    .map(IdentityMapping::from_role),
+            );
+
+        let mut plan = IdentityMappingsPlan::default();
+        for mut mapping in desired {
+            match existing.remove(&mapping.spec.arn.to_lowercase()) {
+                None => plan.create.push(mapping),
+                Some(e) if e.spec != mapping.spec || e.synced_at != mapping.synced_at => {
+                    mapping.name = e.name.clone();
+                    mapping.resource_version = e.resource_version.clone();
+                    plan.update.push(mapping);
+                }
+                Some(_) => {}
+            }
+        }
+        plan.delete = existing.into_values().cloned().collect();
+
+        plan.create.sort_by(|a, b| a.name.cmp(&b.name));
+        plan.update.sort_by(|a, b| a.name.cmp(&b.name));
+        plan.delete.sort_by(|a, b| a.name.cmp(&b.name));
+        plan
+    }
+
+    fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+}
+
+/// `IAMIdentityMapping` resources operations, abstracted to test reconciliation without any cluster.
+pub trait IdentityMappingsApi {
+    async fn list_identity_mappings(&self) -> Result<Vec<IdentityMapping>, KubernetesError>;
+    async fn create_identity_mapping(
+        &self,
+        mapping: &IdentityMapping,
+    ) -> Result<(), KubernetesError>;
+    async fn replace_identity_mapping(
+        &self,
+        mapping: &IdentityMapping,
+    ) -> Result<(), KubernetesError>;
+    async fn delete_identity_mapping(&self, name: &str) -> Result<(), KubernetesError>;
+}
+
+impl KubernetesService {
+    fn identity_mappings_api(&self) -> Api<DynamicObject> {
+        Api::all_with(self.client.clone(), &IdentityMapping::api_resource())
+    }
+
+    /// Fails if leadership is not held anymore, another replica possibly writing already.
+    fn ensure_identity_mapping_leadership(&self, name: &str) -> Result<(), KubernetesError> {
+        match &self.leadership {
+            Some(leadership) if !leadership.is_leader() => {
+                Err(KubernetesError::IdentityMappingCannotBeWritten {
+                    name: Arc::from(name),
+                    raw_message: Arc::from("leadership has been lost"),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn identity_mapping_write_error(name: &str, e: RequestError) -> KubernetesError {
+        match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::IdentityMappingCannotBeWritten {
+                name: Arc::from(name),
+                raw_message: Arc::from(e.to_string()),
+            },
+        }
+    }
+}
+
+impl IdentityMappingsApi for KubernetesService {
+    async fn list_identity_mappings(&self) -> Result<Vec<IdentityMapping>, KubernetesError> {
+        let (api, list_params) = (self.identity_mappings_api(), ListParams::default());
+        let objects =
+            retry_on_transient_error("list IAM identity mappings", || api.list(&list_params))
+                .await
+                .map_err(|e| match e {
+                    RequestError::Unreachable(e) => e,
+                    RequestError::Failed(e) => KubernetesError::IdentityMappingsCannotBeListed {
+                        raw_message: Arc::from(e.to_string()),
+                    },
+                })?;
+
+        objects
+            .items
+            .into_iter()
+            .map(IdentityMapping::from_dynamic_object)
+            .collect()
+    }
+
+    async fn create_identity_mapping(
+        &self,
+        mapping: &IdentityMapping,
+    ) -> Result<(), KubernetesError> {
+        self.ensure_identity_mapping_leadership(&mapping.name)?;
+        let api = self.identity_mappings_api();
+        let (post_params, object) = (self.post_params(), mapping.to_dynamic_object());
+        retry_on_transient_error(
+            &format!("create IAM identity mapping `{}`", mapping.name),
+            || api.create(&post_params, &object),
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| Self::identity_mapping_write_error(&mapping.name, e))
+    }
+
+    async fn replace_identity_mapping(
+        &self,
+        mapping: &IdentityMapping,
+    ) -> Result<(), KubernetesError> {
+        self.ensure_identity_mapping_leadership(&mapping.name)?;
+        let api = self.identity_mappings_api();
+        let (post_params, object) = (self.post_params(), mapping.to_dynamic_object());
+        retry_on_transient_error(
+            &format!("replace IAM identity mapping `{}`", mapping.name),
+            || api.replace(&mapping.name, &post_params, &object),
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| Self::identity_mapping_write_error(&mapping.name, e))
+    }
+
+    async fn delete_identity_mapping(&self, name: &str) -> Result<(), KubernetesError> {
+        self.ensure_identity_mapping_leadership(name)?;
+        let api = self.identity_mappings_api();
+        let delete_params = DeleteParams {
+            dry_run: self.options.server_dry_run,
+            ..Default::default()
+        };
+        retry_on_transient_error(&format!("delete IAM identity mapping `{name}`"), || {
+            api.delete(name, &delete_params)
+        })
+        .await
+        .map(|_| ())
+        .map_err(|e| Self::identity_mapping_write_error(name, e))
+    }
+}
+
+/// Syncs users and roles into aws-iam-authenticator `IAMIdentityMapping` resources instead of the aws-auth config map.
+/// Only mappings labelled with this instance marker are modified or deleted.
+pub struct IdentityMappingsBackend<A: IdentityMappingsApi> {
+    api: A,
+    options: ConfigMapOptions,
+}
+
+impl<A: IdentityMappingsApi> IdentityMappingsBackend<A> {
+    pub fn new(api: A, options: ConfigMapOptions) -> IdentityMappingsBackend<A> {
+        IdentityMappingsBackend { api, options }
+    }
+
+    async fn apply(&self, plan: &IdentityMappingsPlan) -> Result<(), KubernetesError> {
+        for mapping in &plan.create {
+            self.api.create_identity_mapping(mapping).await?;
+        }
+        for mapping in &plan.update {
+            self.api.replace_identity_mapping(mapping).await?;
+        }
+        for mapping in &plan.delete {
+            self.api.delete_identity_mapping(&mapping.name).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<A: IdentityMappingsApi> AuthBackend for IdentityMappingsBackend<A> {
+    async fn get_config_map_resource_version(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError> {
+        // mappings are separate resources without a common version, each sync goes through the whole reconciliation
+        Ok(None)
+    }
+
+    async fn read_current(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        Ok(Some(aws_auth(&self.api.list_identity_mappings().await?)))
+    }
+
+    async fn update_user_and_role_config_map(
+        &self,
+        _config_map_namespace: &str,
+        _config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError> {
+        if !aws_accounts_to_be_added.is_empty() {
+            warn!(
+                "AWS accounts cannot be mapped through IAM identity mappings, ignoring {} account(s)",
+                aws_accounts_to_be_added.len()
+            );
+        }
+
+        let synced_by = &self.options.synced_by;
+        let existing = self.api.list_identity_mappings().await?;
+        let existing_aws_auth = aws_auth(&existing);
+        let (aws_auth, diff) = KubernetesService::merge_aws_auth(
+            existing_aws_auth.clone(),
+            kubernetes_users_to_be_added,
+            kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            &self.options,
+        );
+
+        self.options
+            .removal_guard
+            .check(&existing_aws_auth, &diff, synced_by)?;
+
+        let plan = IdentityMappingsPlan::new(&existing, &aws_auth, synced_by);
+        let status = match (plan.is_empty(), self.options.dry_run) {
+            (true, _) => ConfigMapUpdateStatus::Unchanged,
+            (false, true) => ConfigMapUpdateStatus::DryRun,
+            (false, false) => {
+                self.apply(&plan).await?;
+                info!("IAM identity mappings updated");
+                match self.options.server_dry_run {
+                    true => ConfigMapUpdateStatus::ServerDryRun,
+                    false => ConfigMapUpdateStatus::Updated,
+                }
+            }
+        };
+
+        Ok(ConfigMapUpdate {
+            next_expiry: self.options.next_expiry([&existing_aws_auth, &aws_auth]),
+            aws_auth,
+            previous_resource_version: None,
+            resource_version: None,
+            status,
+            diff,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::RemovalGuard;
+    use crate::kubernetes::fake::FakeIdentityMappingsApi;
+    use crate::kubernetes::identity_mapping::{
+        IdentityMapping, IdentityMappingSpec, IdentityMappingsBackend, MANAGED_BY_LABEL,
+    };
+    use crate::kubernetes::tests::iam_user;
+    use crate::kubernetes::{
+        AuthBackend, ConfigMapOptions, ConfigMapUpdateStatus, IamArn, KubernetesError,
+        KubernetesGroupName, KubernetesRole, SyncedBy,
+    };
+    use std::collections::BTreeSet;
+    use std::time::{Duration, SystemTime};
+
+    fn mapping(name: &str, groups: &[&str], synced_by: Option<&str>) -> IdentityMapping {
+        IdentityMapping {
+            name: name.to_string(),
+            spec: IdentityMappingSpec {
+                arn: format!("arn:aws:iam::123456789012:user/{name}"),
+                username: Some(name.to_string()),
+                groups: groups.iter().map(|g| g.to_string()).collect(),
+            },
+            synced_by: synced_by.map(SyncedBy::new),
+            synced_at: Some(SystemTime::UNIX_EPOCH),
+            resource_version: Some("1".to_string()),
+        }
+    }
+
+    #[test]
+    fn name_for_test() {
+        // setup:
+        struct TestCase<'a> {
+            arn: &'a str,
+            expected_prefix: &'a str,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                arn: "arn:aws:iam::123456789012:user/Alice.Smith",
+                expected_prefix: "alice-smith-",
+                _description: "case 1: user name is sanitized",
+            },
+            TestCase {
+                arn: "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/AWSReservedSSO_Admin_0123",
+                expected_prefix: "awsreservedsso-admin-0123-",
+                _description: "case 2: only the last path part is kept",
+            },
+            TestCase {
+                arn: "arn:aws:iam::123456789012:user/_",
+                expected_prefix: "mapping-",
+                _description: "case 3: nothing left after sanitizing",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = IdentityMapping::name_for(&IamArn::new(tc.arn));
+
+            // verify:
+            assert!(
+                res.starts_with(tc.expected_prefix),
+                "{}: {res}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_prefix.len() + 8,
+                res.len(),
+                "{}",
+                tc._description
+            );
+        }
+        assert_ne!(
+            IdentityMapping::name_for(&IamArn::new("arn:aws:iam::123456789012:user/alice")),
+            IdentityMapping::name_for(&IamArn::new("arn:aws:iam::444455556666:user/alice")),
+        );
+    }
+
+    #[test]
+    fn dynamic_object_round_trip_test() {
+        // setup:
+        let mapping = mapping("alice", &["developers"], Some("iam-eks-user-mapper"));
+
+        // execute:
+        let object = mapping.to_dynamic_object();
+        let res = IdentityMapping::from_dynamic_object(object.clone());
+
+        // verify:
+        assert_eq!(Ok(mapping), res);
+        assert_eq!(
+            Some("iam-eks-user-mapper"),
+            object
+                .metadata
+                .labels
+                .as_ref()
+                .and_then(|l| l.get(MANAGED_BY_LABEL))
+                .map(|v| v.as_str())
+        );
+        assert_eq!(
+            Some("iamauthenticator.k8s.aws/v1alpha1"),
+            object.types.as_ref().map(|t| t.api_version.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn update_identity_mappings_test() {
+        // setup:
+        let api = FakeIdentityMappingsApi::new(vec![
+            mapping("former", &["developers"], Some("iam-eks-user-mapper")),
+            mapping("promoted", &["developers"], Some("iam-eks-user-mapper")),
+            mapping("kept", &["developers"], Some("iam-eks-user-mapper")),
+            mapping("third-party", &["developers"], None),
+            mapping("other-instance", &["developers"], Some("other-mapper")),
+        ]);
+        let backend = IdentityMappingsBackend::new(api, ConfigMapOptions::default());
+        let users = BTreeSet::from_iter(vec![
+            iam_user("new", &["developers"]),
+            iam_user("promoted", &["admins"]),
+            iam_user("kept", &["developers"]),
+        ]);
+        let karpenter_role = KubernetesRole::new(
+            IamArn::new("arn:aws:iam::123456789012:role/karpenter"),
+            None,
+            Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+            BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("system:bootstrappers"),
+                KubernetesGroupName::new("system:nodes"),
+            ]),
+            None,
+        );
+
+        // execute:
+        let res = backend
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(users.clone()),
+                None,
+                std::slice::from_ref(&karpenter_role),
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        let update = res.expect("update should succeed");
+        assert_eq!(ConfigMapUpdateStatus::Updated, update.status);
+        assert_eq!(
+            2,
+            update.diff.users.added.len() + update.diff.roles.added.len()
+        );
+        assert_eq!(1, update.diff.users.removed.len());
+        assert_eq!(1, update.diff.users.modified.len());
+        let writes = backend.api.writes();
+        assert_eq!(4, writes.len(), "{writes:?}");
+        assert!(writes[0].starts_with("create karpenter-"));
+        assert!(writes[1].starts_with("create new-"));
+        assert_eq!("replace promoted", writes[2]);
+        assert_eq!("delete former", writes[3]);
+
+        let mappings = backend.api.identity_mappings();
+        let new = mappings
+            .values()
+            .find(|m| m.spec.arn.ends_with("user/new"))
+            .expect("new user mapping should be created");
+        assert_eq!(Some(SyncedBy::IamEksUserMapper), new.synced_by);
+        assert!(new.synced_at.is_some());
+        assert_eq!(
+            BTreeSet::from_iter(vec!["admins".to_string()]),
+            mappings["promoted"].spec.groups
+        );
+        // entries not synced by this instance are untouched
+        assert_eq!(
+            mapping("third-party", &["developers"], None),
+            mappings["third-party"]
+        );
+        assert!(mappings.contains_key("other-instance"));
+
+        // a second sync has nothing to write
+        let res = backend
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(users),
+                None,
+                &[karpenter_role],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+        assert_eq!(
+            Some(ConfigMapUpdateStatus::Unchanged),
+            res.ok().map(|u| u.status)
+        );
+        assert_eq!(4, backend.api.writes().len());
+    }
+
+    #[tokio::test]
+    async fn update_identity_mappings_without_writes_test() {
+        // setup:
+        struct TestCase<'a> {
+            options: ConfigMapOptions,
+            expected_status: Result<ConfigMapUpdateStatus, ()>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                options: ConfigMapOptions {
+                    dry_run: true,
+                    ..Default::default()
+                },
+                expected_status: Ok(ConfigMapUpdateStatus::DryRun),
+                _description: "case 1: dry run",
+            },
+            TestCase {
+                options: ConfigMapOptions {
+                    removal_guard: RemovalGuard {
+                        max_removals: Some(0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                expected_status: Err(()),
+                _description: "case 2: too many removals",
+            },
+            TestCase {
+                options: ConfigMapOptions {
+                    synced_entry_ttl: Some(Duration::from_secs(3600)),
+                    protected_arns: vec![IamArn::new("arn:aws:iam::123456789012:user/former")],
+                    ..Default::default()
+                },
+                expected_status: Ok(ConfigMapUpdateStatus::Unchanged),
+                _description: "case 3: protected entry is kept, even if expired",
+            },
+        ];
+
+        for tc in test_cases {
+            let api = FakeIdentityMappingsApi::new(vec![mapping(
+                "former",
+                &["developers"],
+                Some("iam-eks-user-mapper"),
+            )]);
+            let backend = IdentityMappingsBackend::new(api, tc.options);
+
+            // execute:
+            let res = backend
+                .update_user_and_role_config_map(
+                    "kube-system",
+                    "aws-auth",
+                    Some(BTreeSet::new()),
+                    None,
+                    &[],
+                    &[],
+                    &BTreeSet::new(),
+                )
+                .await;
+
+            // verify:
+            match tc.expected_status {
+                Ok(status) => assert_eq!(
+                    Some(status),
+                    res.as_ref().ok().map(|r| r.status),
+                    "{}",
+                    tc._description
+                ),
+                Err(()) => assert!(
+                    matches!(res, Err(KubernetesError::TooManyRemovals { .. })),
+                    "{}",
+                    tc._description
+                ),
+            }
+            assert!(backend.api.writes().is_empty(), "{}", tc._description);
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use crate::kubernetes::{KubernetesError, KubernetesService};
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use k8s_openapi::chrono::{TimeDelta, Utc};
+use kube::api::PostParams;
+use kube::Api;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tracing::{debug, info, warn};
+
+/// Lease used to elect the replica allowed to sync aws-auth.
+#[derive(Clone, Debug)]
+pub struct LeaseOptions {
+    pub lease_namespace: String,
+    pub lease_name: String,
+    /// Duration after which a lease not renewed can be taken over by another replica.
+    pub lease_duration: Duration,
+    /// Identity of this replica, e.q: its pod name.
+    pub holder_identity: String,
+}
+
+impl LeaseOptions {
+    /// Delay between two lease acquisition or renewal attempts.
+    fn retry_period(&self) -> Duration {
+        self.lease_duration / 3
+    }
+
+    /// Delay during which leadership is considered held after a successful renewal.
+    /// Shorter than lease duration so the leader steps down before another replica can take over.
+    fn renew_deadline(&self) -> Duration {
+        self.lease_duration * 2 / 3
+    }
+}
+
+/// Leadership state shared between the leader elector and the tasks it guards.
+#[derive(Clone, Debug, Default)]
+pub struct Leadership {
+    held_until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Leadership {
+    pub fn is_leader(&self) -> bool {
+        self.held_until
+            .lock()
+            .map(|held_until| held_until.is_some_and(|held_until| Instant::now() < held_until))
+            .unwrap_or(false)
+    }
+
+    fn hold_until(&self, until: Instant) {
+        if let Ok(mut held_until) = self.held_until.lock() {
+            *held_until = Some(until);
+        }
+    }
+
+    fn release(&self) {
+        if let Ok(mut held_until) = self.held_until.lock() {
+            *held_until = None;
+        }
+    }
+}
+
+/// Acquires and renews the lease, updating the shared leadership accordingly.
+pub struct LeaderElector {
+    leases_api: Api<Lease>,
+    options: LeaseOptions,
+    leadership: Leadership,
+}
+
+impl KubernetesService {
+    pub fn leader_elector(&self, options: LeaseOptions) -> LeaderElector {
+        LeaderElector {
+            leases_api: Api::namespaced(self.client.clone(), &options.lease_namespace),
+            options,
+            leadership: Leadership::default(),
+        }
+    }
+}
+
+impl LeaderElector {
+    pub fn leadership(&self) -> Leadership {
+        self.leadership.clone()
+    }
+
+    /// Tries to acquire or renew the lease forever, leadership being lost if it cannot be renewed in time.
+    pub async fn run(self) {
+        let mut retry_interval = time::interval(self.options.retry_period());
+
+        loop {
+            retry_interval.tick().await;
+            let was_leader = self.leadership.is_leader();
+            match self.try_acquire_or_renew().await {
+                Ok(true) if !was_leader => info!(
+                    "Lease `{}/{}` acquired, `{}` is now the leader",
+                    self.options.lease_namespace,
+                    self.options.lease_name,
+                    self.options.holder_identity
+                ),
+                Ok(false) if was_leader => warn!(
+                    "Lease `{}/{}` lost, `{}` is not the leader anymore",
+                    self.options.lease_namespace,
+                    self.options.lease_name,
+                    self.options.holder_identity
+                ),
+                Ok(_) => {}
+                // leadership expires on its own if errors last longer than the renew deadline
+                Err(e) => warn!("Error while acquiring or renewing lease: {e}"),
+            }
+        }
+    }
+
+    /// Acquires the lease if it's free or expired, renews it if already held.
+    /// Returns whether this replica is the leader.
+    pub async fn try_acquire_or_renew(&self) -> Result<bool, KubernetesError> {
+        let attempt_started_at = Instant::now();
+        let now = MicroTime(Utc::now());
+        let lease_name = self.options.lease_name.as_str();
+
+        let acquired = match self
+            .leases_api
+            .get_opt(lease_name)
+            .await
+            .map_err(|e| self.lease_error(e))?
+        {
+            None => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(lease_name.to_string()),
+                        namespace: Some(self.options.lease_namespace.clone()),
+                        ..Default::default()
+                    },
+                    spec: Some(LeaseSpec {
+                        holder_identity: Some(self.options.holder_identity.clone()),
+                        lease_duration_seconds: Some(self.lease_duration_seconds()),
+                        acquire_time: Some(now.clone()),
+                        renew_time: Some(now),
+                        lease_transitions: Some(0),
+                    }),
+                };
+                self.write_lease(self.leases_api.create(&PostParams::default(), &lease).await)?
+            }
+            Some(mut lease) => {
+                let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+                let held =
+                    spec.holder_identity.as_deref() == Some(self.options.holder_identity.as_str());
+
+                if !held && !Self::lease_expired(spec, &now) {
+                    debug!(
+                        "Lease `{}/{lease_name}` is held by `{}`",
+                        self.options.lease_namespace,
+                        spec.holder_identity.as_deref().unwrap_or_default()
+                    );
+                    false
+                } else {
+                    if !held {
+                        spec.holder_identity = Some(self.options.holder_identity.clone());
+                        spec.acquire_time = Some(now.clone());
+                        spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+                    }
+                    spec.lease_duration_seconds = Some(self.lease_duration_seconds());
+                    spec.renew_time = Some(now);
+
+                    // lease resource version makes the replace fail if someone else wrote it in the meantime
+                    self.write_lease(
+                        self.leases_api
+                            .replace(lease_name, &PostParams::default(), &lease)
+                            .await,
+                    )?
+                }
+            }
+        };
+
+        match acquired {
+            true => self
+                .leadership
+                .hold_until(attempt_started_at + self.options.renew_deadline()),
+            false => self.leadership.release(),
+        }
+
+        Ok(acquired)
+    }
+
+    fn lease_expired(spec: &LeaseSpec, now: &MicroTime) -> bool {
+        match (&spec.renew_time, spec.lease_duration_seconds) {
+            (Some(renew_time), Some(lease_duration_seconds)) => {
+                renew_time.0 + TimeDelta::seconds(i64::from(lease_duration_seconds)) < now.0
+            }
+            _ => true,
+        }
+    }
+
+    fn lease_duration_seconds(&self) -> i32 {
+        i32::try_from(self.options.lease_duration.as_secs()).unwrap_or(i32::MAX)
+    }
+
+    /// Returns whether lease write succeeded, a conflict meaning another replica won the race.
+    fn write_lease(&self, result: Result<Lease, kube::Error>) -> Result<bool, KubernetesError> {
+        match result {
+            Ok(_) => Ok(true),
+            Err(kube::Error::Api(e)) if e.code == 409 => Ok(false),
+            Err(e) => Err(self.lease_error(e)),
+        }
+    }
+
+    fn lease_error(&self, e: kube::Error) -> KubernetesError {
+        KubernetesError::LeaseCannotBeAcquired {
+            lease_name: Arc::from(self.options.lease_name.as_str()),
+            lease_namespace: Arc::from(self.options.lease_namespace.as_str()),
+            raw_message: Arc::from(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::leadership::{Leadership, LeaseOptions};
+    use crate::kubernetes::tests::status_response;
+    use crate::kubernetes::{ConfigMapOptions, KubernetesError, KubernetesService};
+    use http::{Method, Request, Response};
+    use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+    use k8s_openapi::chrono::{TimeDelta, Utc};
+    use kube::client::Body;
+    use kube::Client;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    type ApiServerHandle = tower_test::mock::Handle<Request<Body>, Response<Body>>;
+
+    fn mock_kubernetes_service() -> (KubernetesService, ApiServerHandle) {
+        let (mock_service, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        (
+            KubernetesService::with_client(
+                Client::new(mock_service, "default"),
+                ConfigMapOptions::default(),
+            ),
+            handle,
+        )
+    }
+
+    fn lease_options() -> LeaseOptions {
+        LeaseOptions {
+            lease_namespace: "kube-system".to_string(),
+            lease_name: "iam-eks-user-mapper".to_string(),
+            lease_duration: Duration::from_secs(15),
+            holder_identity: "replica-1".to_string(),
+        }
+    }
+
+    fn lease(holder_identity: &str, renewed_seconds_ago: i64, lease_transitions: i32) -> Lease {
+        Lease {
+            metadata: ObjectMeta {
+                name: Some("iam-eks-user-mapper".to_string()),
+                namespace: Some("kube-system".to_string()),
+                resource_version: Some("42".to_string()),
+                ..Default::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: Some(holder_identity.to_string()),
+                lease_duration_seconds: Some(15),
+                acquire_time: Some(MicroTime(Utc::now() - TimeDelta::seconds(3600))),
+                renew_time: Some(MicroTime(
+                    Utc::now() - TimeDelta::seconds(renewed_seconds_ago),
+                )),
+                lease_transitions: Some(lease_transitions),
+            }),
+        }
+    }
+
+    fn lease_response(lease: &Lease) -> Response<Body> {
+        Response::builder()
+            .status(200)
+            .body(Body::from(serde_json::to_vec(lease).unwrap()))
+            .unwrap()
+    }
+
+    async fn request_body_lease(request: Request<Body>) -> Lease {
+        let body = request
+            .into_body()
+            .collect_bytes()
+            .await
+            .expect("cannot read request body");
+        serde_json::from_slice(&body).expect("request body should be a lease")
+    }
+
+    #[tokio::test]
+    async fn try_acquire_or_renew_creates_missing_lease_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let elector = service.leader_elector(lease_options());
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(status_response(404, "NotFound"));
+
+            let (request, send) = handle.next_request().await.expect("POST expected");
+            assert_eq!(Method::POST, request.method());
+            let created = request_body_lease(request).await;
+            let spec = created.spec.clone().expect("lease spec should be set");
+            assert_eq!(Some("replica-1".to_string()), spec.holder_identity);
+            assert_eq!(Some(15), spec.lease_duration_seconds);
+            assert_eq!(Some(0), spec.lease_transitions);
+            send.send_response(lease_response(&created));
+        });
+
+        // execute:
+        let res = elector.try_acquire_or_renew().await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok(true), res);
+        assert!(elector.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn try_acquire_or_renew_follows_valid_lease_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let elector = service.leader_elector(lease_options());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(lease_response(&lease("replica-2", 5, 3)));
+            // no write expected, mock handle being dropped would make it fail
+        });
+
+        // execute:
+        let res = elector.try_acquire_or_renew().await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok(false), res);
+        assert!(!elector.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn try_acquire_or_renew_takes_over_expired_lease_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let elector = service.leader_elector(lease_options());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(lease_response(&lease("replica-2", 30, 3)));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let replaced = request_body_lease(request).await;
+            assert_eq!(Some("42".to_string()), replaced.metadata.resource_version);
+            let spec = replaced.spec.clone().expect("lease spec should be set");
+            assert_eq!(Some("replica-1".to_string()), spec.holder_identity);
+            assert_eq!(Some(4), spec.lease_transitions);
+            assert_eq!(spec.acquire_time, spec.renew_time);
+            send.send_response(lease_response(&replaced));
+        });
+
+        // execute:
+        let res = elector.try_acquire_or_renew().await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok(true), res);
+        assert!(elector.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn try_acquire_or_renew_renews_held_lease_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let elector = service.leader_elector(lease_options());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(lease_response(&lease("replica-1", 5, 3)));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let replaced = request_body_lease(request).await;
+            let spec = replaced.spec.clone().expect("lease spec should be set");
+            assert_eq!(Some("replica-1".to_string()), spec.holder_identity);
+            assert_eq!(Some(3), spec.lease_transitions);
+            assert_ne!(spec.acquire_time, spec.renew_time);
+            send.send_response(lease_response(&replaced));
+        });
+
+        // execute:
+        let res = elector.try_acquire_or_renew().await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok(true), res);
+        assert!(elector.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn try_acquire_or_renew_loses_race_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let elector = service.leader_elector(lease_options());
+        elector
+            .leadership()
+            .hold_until(Instant::now() + Duration::from_secs(10));
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(lease_response(&lease("replica-1", 5, 3)));
+
+            let (_, send) = handle.next_request().await.expect("PUT expected");
+            send.send_response(status_response(409, "Conflict"));
+        });
+
+        // execute:
+        let res = elector.try_acquire_or_renew().await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok(false), res);
+        assert!(!elector.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn try_acquire_or_renew_error_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service();
+        let elector = service.leader_elector(lease_options());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(status_response(403, "Forbidden"));
+        });
+
+        // execute:
+        let res = elector.try_acquire_or_renew().await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(matches!(
+            res,
+            Err(KubernetesError::LeaseCannotBeAcquired { lease_name, .. }) if lease_name == Arc::from("iam-eks-user-mapper")
+        ));
+    }
+
+    #[test]
+    fn leadership_expires_test() {
+        // setup:
+        let leadership = Leadership::default();
+
+        // execute & verify:
+        assert!(!leadership.is_leader());
+        leadership.hold_until(Instant::now() + Duration::from_secs(10));
+        assert!(leadership.is_leader());
+        leadership.hold_until(Instant::now() - Duration::from_secs(1));
+        assert!(!leadership.is_leader());
+    }
+}
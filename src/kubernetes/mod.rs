@@ -1,15 +1,58 @@
-mod aws_auth;
+pub mod aws_auth;
+pub mod backup;
+pub mod events;
+pub mod fake;
+pub mod group_mapping;
+pub mod identity_mapping;
+pub mod leadership;
+pub mod status;
+pub mod watch;
+pub mod yaml;
 
-use crate::kubernetes::aws_auth::AwsAuthBuilder;
+use crate::arn::Arn;
+use crate::config::ConfigurationError;
+use crate::kubernetes::aws_auth::{
+    AwsAuth, AwsAuthBuilder, AwsAuthDiff, AwsAuthEntry, RemovalGuard,
+};
+use crate::kubernetes::backup::ConfigMapBackup;
+use crate::kubernetes::leadership::Leadership;
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
 use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use kube::api::PostParams;
-use kube::{Api, Client};
-use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::{Api, Client, Config};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::{hash_map, BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
+use std::future::Future;
 use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
+use tokio::time::{self, Instant};
+use tracing::{info, info_span, warn, Instrument};
+
+/// Maximum number of read-modify-write attempts when the config map is concurrently modified.
+const CONFIG_MAP_UPDATE_MAX_ATTEMPTS: u32 = 3;
+/// Maximum number of attempts of a single API request failing with a transient error, e.q: API server briefly unavailable.
+const TRANSIENT_ERROR_MAX_ATTEMPTS: u32 = 3;
+/// Delay before retrying a request failing with a transient error, doubled on each attempt.
+const TRANSIENT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+/// Annotation listing `mapAccounts` entries added by the tool.
+const MANAGED_ACCOUNTS_ANNOTATION: &str = "iam-eks-user-mapper/managed-accounts";
+/// Annotations describing the last write done by the tool.
+const LAST_SYNC_TIME_ANNOTATION: &str = "iam-eks-user-mapper/last-sync-time";
+const VERSION_ANNOTATION: &str = "iam-eks-user-mapper/version";
+const CONTENT_HASH_ANNOTATION: &str = "iam-eks-user-mapper/content-hash";
+/// Maximum number of characters of the offending entry shown in deserialization errors.
+pub const DEFAULT_ERROR_SNIPPET_MAX_LENGTH: usize = 200;
 
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum KubernetesError {
@@ -17,15 +60,26 @@ pub enum KubernetesError {
     ClusterUnreachable { raw_message: Arc<str> },
     #[error("Error while trying to serialize users map to YAML: {raw_message}")]
     CannotSerializeUsersMap { raw_message: Arc<str> },
-    #[error("Error while trying to deserialize users map from YAML: {raw_message}")]
+    #[error("Error while trying to deserialize users map from YAML at {location}: {underlying_error}, near {snippet:?}")]
     CannotDeserializeUsersMap {
-        raw_message: Arc<str>,
+        location: EntryErrorLocation,
+        snippet: Arc<str>,
         underlying_error: Arc<str>,
     },
     #[error("Error while trying to serialize roles map to YAML: {raw_message}")]
     CannotSerializeRolesMap { raw_message: Arc<str> },
-    #[error("Error while trying to deserialize roles map from YAML: {raw_message}")]
+    #[error("Error while trying to deserialize roles map from YAML at {location}: {underlying_error}, near {snippet:?}")]
     CannotDeserializeRolesMap {
+        location: EntryErrorLocation,
+        snippet: Arc<str>,
+        underlying_error: Arc<str>,
+    },
+    #[error("Error while trying to serialize accounts map to YAML: {raw_message}")]
+    CannotSerializeAccountsMap { raw_message: Arc<str> },
+    #[error("Error while trying to serialize config map to YAML: {raw_message}")]
+    CannotSerializeConfigMap { raw_message: Arc<str> },
+    #[error("Error while trying to deserialize accounts map from YAML: {raw_message}")]
+    CannotDeserializeAccountsMap {
         raw_message: Arc<str>,
         underlying_error: Arc<str>,
     },
@@ -35,69 +89,367 @@ pub enum KubernetesError {
         config_map_namespace: Arc<str>,
         raw_message: Arc<str>,
     },
-    #[error("Cannot patch config map `{config_map_name}` in namespace `{config_map_namespace}`: {raw_message}")]
+    #[error("Cannot create config map `{config_map_name}` in namespace `{config_map_namespace}`: {raw_message}")]
+    ConfigMapCannotBeCreated {
+        config_map_name: Arc<str>,
+        config_map_namespace: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot patch config map `{config_map_name}` in namespace `{config_map_namespace}` after {attempts} attempt(s): {raw_message}")]
     ConfigMapCannotBePatched {
         config_map_name: Arc<str>,
         config_map_namespace: Arc<str>,
+        attempts: u32,
+        raw_message: Arc<str>,
+    },
+    #[error("Error while trying to serialize config map backup to YAML: {raw_message}")]
+    CannotSerializeBackup { raw_message: Arc<str> },
+    #[error("Error while trying to deserialize config map backup `{backup_name}` from YAML: {underlying_error}")]
+    CannotDeserializeBackup {
+        backup_name: Arc<str>,
+        underlying_error: Arc<str>,
+    },
+    #[error("Cannot find config map backup `{backup_name}`")]
+    BackupNotFound { backup_name: Arc<str> },
+    #[error("Cannot read backup config map `{backup_config_map_name}` in namespace `{config_map_namespace}`: {raw_message}")]
+    BackupCannotBeRead {
+        backup_config_map_name: Arc<str>,
+        config_map_namespace: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot read kubeconfig `{kubeconfig_path}`: {raw_message}")]
+    KubeconfigCannotBeRead {
+        kubeconfig_path: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot find context `{context}` in kubeconfig `{kubeconfig_path}`")]
+    KubeContextNotFound {
+        context: Arc<str>,
+        kubeconfig_path: Arc<str>,
+    },
+    #[error("Cannot acquire lease `{lease_name}` in namespace `{lease_namespace}`: {raw_message}")]
+    LeaseCannotBeAcquired {
+        lease_name: Arc<str>,
+        lease_namespace: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot record `{reason}` event: {raw_message}")]
+    EventCannotBeRecorded {
+        reason: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot sync access entries of EKS cluster `{cluster_name}`: {raw_message}")]
+    AccessEntriesCannotBeSynced {
+        cluster_name: Arc<str>,
+        raw_message: Arc<str>,
+        /// Whether EKS failed in a way retrying might fix, see `EksError::is_retryable`.
+        retryable: bool,
+    },
+    #[error("Cannot list IAM group mappings in namespace `{namespace}`: {raw_message}")]
+    GroupMappingsCannotBeListed {
+        namespace: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot update status of IAM group mapping `{name}` in namespace `{namespace}`: {raw_message}")]
+    GroupMappingStatusCannotBeUpdated {
+        name: Arc<str>,
+        namespace: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot list IAM identity mappings: {raw_message}")]
+    IdentityMappingsCannotBeListed { raw_message: Arc<str> },
+    #[error("Invalid IAM identity mapping `{name}`: {raw_message}")]
+    InvalidIdentityMapping {
+        name: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot write IAM identity mapping `{name}`: {raw_message}")]
+    IdentityMappingCannotBeWritten {
+        name: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot review `{verb}` access to config map `{config_map_name}` in namespace `{config_map_namespace}`: {raw_message}")]
+    AccessCannotBeReviewed {
+        verb: Arc<str>,
+        config_map_name: Arc<str>,
+        config_map_namespace: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Config map `{config_map_name}` in namespace `{config_map_namespace}` not written, leadership has been lost")]
+    LeadershipLost {
+        config_map_name: Arc<str>,
+        config_map_namespace: Arc<str>,
+    },
+    #[error("Refusing to remove {removals} of {synced_entries} synced entries at once, exceeding removal limits:\n{removed_entries}")]
+    TooManyRemovals {
+        removals: usize,
+        synced_entries: usize,
+        removed_entries: Arc<str>,
+    },
+    #[error(
+        "Generated `{key}` content cannot be read back as intended, not writing it: {raw_message}"
+    )]
+    GeneratedContentInvalid {
+        key: Arc<str>,
+        generated_content: Arc<str>,
+        intended_content: Arc<str>,
         raw_message: Arc<str>,
     },
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+impl KubernetesError {
+    /// Whether the failing sync might succeed once retried. Transient request errors being already retried and
+    /// reported as `ClusterUnreachable`, errors retrying won't fix are the ones about malformed or missing
+    /// content and configuration, e.q: an aws-auth config map which cannot be parsed or a missing kubeconfig.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            KubernetesError::CannotSerializeUsersMap { .. }
+            | KubernetesError::CannotDeserializeUsersMap { .. }
+            | KubernetesError::CannotSerializeRolesMap { .. }
+            | KubernetesError::CannotDeserializeRolesMap { .. }
+            | KubernetesError::CannotSerializeAccountsMap { .. }
+            | KubernetesError::CannotSerializeConfigMap { .. }
+            | KubernetesError::CannotDeserializeAccountsMap { .. }
+            | KubernetesError::CannotSerializeBackup { .. }
+            | KubernetesError::CannotDeserializeBackup { .. }
+            | KubernetesError::BackupNotFound { .. }
+            | KubernetesError::ConfigMapNotFound { .. }
+            | KubernetesError::KubeconfigCannotBeRead { .. }
+            | KubernetesError::KubeContextNotFound { .. }
+            | KubernetesError::InvalidIdentityMapping { .. }
+            | KubernetesError::GeneratedContentInvalid { .. } => false,
+            KubernetesError::AccessEntriesCannotBeSynced { retryable, .. } => *retryable,
+            // conflicts, lost leadership and removals exceeding limits may be resolved by the next sync
+            KubernetesError::ClusterUnreachable { .. }
+            | KubernetesError::ConfigMapCannotBeCreated { .. }
+            | KubernetesError::ConfigMapCannotBePatched { .. }
+            | KubernetesError::BackupCannotBeRead { .. }
+            | KubernetesError::LeaseCannotBeAcquired { .. }
+            | KubernetesError::EventCannotBeRecorded { .. }
+            | KubernetesError::GroupMappingsCannotBeListed { .. }
+            | KubernetesError::GroupMappingStatusCannotBeUpdated { .. }
+            | KubernetesError::IdentityMappingsCannotBeListed { .. }
+            | KubernetesError::IdentityMappingCannotBeWritten { .. }
+            | KubernetesError::AccessCannotBeReviewed { .. }
+            | KubernetesError::LeadershipLost { .. }
+            | KubernetesError::TooManyRemovals { .. } => true,
+        }
+    }
+}
+
+/// Marker of entries managed by a mapper instance, written as a plain string in `syncedBy` field.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum SyncedBy {
-    #[serde(rename = "iam-eks-user-mapper")]
+    /// Default marker: `iam-eks-user-mapper`.
+    #[default]
     IamEksUserMapper,
-    #[serde(rename = "unknown")]
-    #[serde(other)]
-    Unknown,
+    /// Any other marker, e.q: set by another mapper instance.
+    Other(String),
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct IamUserName(String);
+impl SyncedBy {
+    const DEFAULT_VALUE: &'static str = "iam-eks-user-mapper";
 
-impl IamUserName {
-    pub fn new(iam_user_name: &str) -> IamUserName {
-        IamUserName(iam_user_name.to_string())
+    pub fn new(value: &str) -> SyncedBy {
+        match value {
+            Self::DEFAULT_VALUE => SyncedBy::IamEksUserMapper,
+            value => SyncedBy::Other(value.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SyncedBy::IamEksUserMapper => Self::DEFAULT_VALUE,
+            SyncedBy::Other(value) => value,
+        }
     }
 }
 
-impl Display for IamUserName {
+impl Display for SyncedBy {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.0.as_str())
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for SyncedBy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SyncedBy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|value| SyncedBy::new(&value))
     }
 }
 
-#[derive(Eq, PartialEq)]
-pub struct IamRoleName(String);
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub struct IamUserName {
+    name: String,
+    /// Lowercased once, entries being identified case insensitively on each comparison.
+    lowercase: String,
+}
+
+impl IamUserName {
+    pub fn new(iam_user_name: &str) -> IamUserName {
+        IamUserName {
+            name: iam_user_name.to_string(),
+            lowercase: iam_user_name.to_lowercase(),
+        }
+    }
+
+    pub(crate) fn lowercase(&self) -> &str {
+        &self.lowercase
+    }
+}
 
-impl Display for IamRoleName {
+impl Display for IamUserName {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.0.as_str())
+        f.write_str(self.name.as_str())
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub struct IamArn(String);
+#[derive(Debug, Eq, PartialEq, Clone, Hash, PartialOrd, Ord)]
+pub struct IamArn {
+    arn: String,
+    /// Lowercased once, entries being identified case insensitively on each comparison.
+    lowercase: String,
+}
 
 impl IamArn {
+    /// ARN as is, for values read back from the cluster which may hold entries not set by the tool.
     pub fn new(iam_arn: &str) -> IamArn {
-        IamArn(iam_arn.to_string())
+        IamArn {
+            arn: iam_arn.to_string(),
+            lowercase: iam_arn.to_lowercase(),
+        }
+    }
+
+    pub(crate) fn lowercase(&self) -> &str {
+        &self.lowercase
+    }
+
+    /// Parses an IAM ARN of `resource_type` (`user` or `role`) set in the configuration, trimmed,
+    /// e.q: `arn:aws:iam::123456789012:role/ci` or `arn:aws-cn:iam::123456789012:user/ops/alice`.
+    pub fn parse(raw_arn: &str, resource_type: &str) -> Result<IamArn, ConfigurationError> {
+        let raw_arn = raw_arn.trim();
+        let valid = raw_arn.parse::<Arn>().is_ok_and(|arn| {
+            arn.service() == "iam"
+                && arn.region().is_empty()
+                && arn.account_id().len() == 12
+                && arn.resource_type() == Some(resource_type)
+                && !arn.resource_name().is_empty()
+                // names allowed by IAM, paths being `/` delimited
+                && arn
+                    .resource_path()
+                    .chars()
+                    .chain(arn.resource_name().chars())
+                    .all(|c| c.is_ascii_alphanumeric() || "+=,.@_-/".contains(c))
+        });
+
+        match valid {
+            true => Ok(IamArn::new(raw_arn)),
+            false => Err(ConfigurationError::InvalidArn {
+                raw_arn: Arc::from(raw_arn),
+                resource_type: Arc::from(resource_type),
+            }),
+        }
+    }
+
+    /// Account ID, e.q: `123456789012`, empty if the ARN is malformed.
+    pub fn account_id(&self) -> &str {
+        self.arn.split(':').nth(4).unwrap_or_default()
+    }
+
+    /// IAM path without its leading and trailing `/`, e.q: `ops` for `arn:aws:iam::123456789012:user/ops/alice`,
+    /// empty for the root path.
+    pub fn path(&self) -> &str {
+        self.arn
+            .splitn(6, ':')
+            .nth(5)
+            .and_then(|resource| resource.split_once('/'))
+            .and_then(|(_, path_and_name)| path_and_name.rsplit_once('/'))
+            .map(|(path, _)| path)
+            .unwrap_or_default()
+    }
+
+    /// Whether the ARN is an instance profile one, e.q: `arn:aws:iam::123456789012:instance-profile/karpenter`.
+    pub fn is_instance_profile(&self) -> bool {
+        self.arn
+            .parse::<Arn>()
+            .is_ok_and(|arn| arn.resource_type() == Some("instance-profile"))
+    }
+
+    /// Name without its path, e.q: `alice` for `arn:aws:iam::123456789012:user/ops/alice`.
+    pub fn name(&self) -> &str {
+        self.arn
+            .rsplit_once('/')
+            .map(|(_, name)| name)
+            .unwrap_or_default()
     }
 }
 
 impl Display for IamArn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.arn.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct AwsAccountId(String);
+
+impl AwsAccountId {
+    pub fn new(aws_account_id: &str) -> AwsAccountId {
+        AwsAccountId(aws_account_id.to_string())
+    }
+}
+
+impl Display for AwsAccountId {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.0.as_str())
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+/// `system:` groups known by the authenticator.
+const WELL_KNOWN_SYSTEM_GROUPS: [&str; 4] = [
+    "system:masters",
+    "system:bootstrappers",
+    "system:nodes",
+    "system:node-proxier",
+];
+/// Longest group name accepted, as for Kubernetes resources names.
+const MAX_GROUP_NAME_LENGTH: usize = 253;
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct KubernetesGroupName(String);
 
 impl KubernetesGroupName {
+    /// Group as is, for values read back from the cluster which may hold entries not set by the tool.
     pub fn new(kubernetes_role: &str) -> KubernetesGroupName {
         KubernetesGroupName(kubernetes_role.to_string())
     }
+
+    /// Parses a group name set in the configuration, trimmed, made of printable ASCII characters but spaces,
+    /// quotes, commas and backslashes, which the authenticator or the mappings syntax wouldn't cope with.
+    pub fn parse(raw_group_name: &str) -> Result<KubernetesGroupName, ConfigurationError> {
+        let group_name = raw_group_name.trim();
+        match !group_name.is_empty()
+            && group_name.len() <= MAX_GROUP_NAME_LENGTH
+            && group_name
+                .chars()
+                .all(|c| c.is_ascii_graphic() && !"\"',\\".contains(c))
+        {
+            true => Ok(KubernetesGroupName::new(group_name)),
+            false => Err(ConfigurationError::InvalidKubernetesGroupName {
+                raw_group_name: Arc::from(raw_group_name),
+            }),
+        }
+    }
+
+    /// Whether it's a `system:` group unknown to the authenticator, e.q: `system:master`, granting nothing.
+    pub fn is_unknown_system_group(&self) -> bool {
+        self.0.starts_with("system:") && !WELL_KNOWN_SYSTEM_GROUPS.contains(&self.0.as_str())
+    }
 }
 
 impl Display for KubernetesGroupName {
@@ -110,15 +462,19 @@ impl Display for KubernetesGroupName {
 pub struct KubernetesUser {
     pub iam_user_name: IamUserName,
     pub iam_arn: IamArn,
-    pub roles: HashSet<KubernetesGroupName>,
+    pub roles: BTreeSet<KubernetesGroupName>,
     pub synced_by: Option<SyncedBy>,
+    /// When the entry was first synced by the tool.
+    pub synced_at: Option<SystemTime>,
+    /// Entry keys not modeled by the tool, kept as is.
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl KubernetesUser {
     pub fn new(
         iam_user_name: IamUserName,
         iam_arn: IamArn,
-        roles: HashSet<KubernetesGroupName>,
+        roles: BTreeSet<KubernetesGroupName>,
         synced_by: Option<SyncedBy>,
     ) -> KubernetesUser {
         KubernetesUser {
@@ -126,6 +482,8 @@ impl KubernetesUser {
             iam_arn,
             roles,
             synced_by,
+            synced_at: None,
+            extra: BTreeMap::new(),
         }
     }
 
@@ -140,38 +498,58 @@ impl KubernetesUser {
 impl From<MapUserConfig> for KubernetesUser {
     fn from(value: MapUserConfig) -> Self {
         KubernetesUser {
-            iam_user_name: IamUserName(value.username),
-            iam_arn: IamArn(value.user_arn),
-            roles: HashSet::from_iter(value.groups.into_iter().map(KubernetesGroupName)),
+            iam_user_name: IamUserName::new(&value.username),
+            iam_arn: IamArn::new(&value.user_arn),
+            roles: BTreeSet::from_iter(value.groups.into_iter().map(KubernetesGroupName)),
             synced_by: value.synced_by,
+            synced_at: value.synced_at,
+            extra: value.extra,
         }
     }
 }
 
 impl Hash for KubernetesUser {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.iam_user_name.to_string().to_lowercase().hash(state);
-        self.iam_arn.to_string().to_lowercase().hash(state);
+        self.key().hash(state);
     }
 }
 
 impl PartialEq for KubernetesUser {
+    /// ARN and username are compared case insensitively as they are hashed, `Alice` and `alice` being the same user.
     fn eq(&self, other: &Self) -> bool {
-        self.roles == other.roles
-            && self.iam_arn == other.iam_arn
-            && self.iam_user_name == other.iam_user_name
+        self.roles == other.roles && self.key() == other.key() && self.extra == other.extra
     }
 }
 
 impl Eq for KubernetesUser {}
 
+impl Ord for KubernetesUser {
+    /// Entries are ordered by identity, entries of the same identity by groups, consistently with equality.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key()
+            .cmp(&other.key())
+            .then_with(|| self.roles.cmp(&other.roles))
+            .then_with(|| cmp_extra(&self.extra, &other.extra))
+    }
+}
+
+impl PartialOrd for KubernetesUser {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct KubernetesRole {
     pub iam_role_arn: IamArn,
     pub role_name: Option<String>,
     pub user_name: Option<String>,
-    pub groups: HashSet<KubernetesGroupName>,
+    pub groups: BTreeSet<KubernetesGroupName>,
     pub synced_by: Option<SyncedBy>,
+    /// When the entry was first synced by the tool.
+    pub synced_at: Option<SystemTime>,
+    /// Entry keys not modeled by the tool, kept as is.
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl KubernetesRole {
@@ -179,7 +557,7 @@ impl KubernetesRole {
         iam_role_arn: IamArn,
         role_name: Option<String>,
         user_name: Option<String>,
-        groups: HashSet<KubernetesGroupName>,
+        groups: BTreeSet<KubernetesGroupName>,
         synced_by: Option<SyncedBy>,
     ) -> Self {
         Self {
@@ -188,6 +566,8 @@ impl KubernetesRole {
             user_name,
             groups,
             synced_by,
+            synced_at: None,
+            extra: BTreeMap::new(),
         }
     }
     pub fn new_synced_from(r: KubernetesRole, synced_by: SyncedBy) -> KubernetesRole {
@@ -200,27 +580,48 @@ impl KubernetesRole {
 
 impl Hash for KubernetesRole {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.iam_role_arn.to_string().to_lowercase().hash(state);
-        if let Some(role_name) = &self.role_name {
-            role_name.to_string().to_lowercase().hash(state);
-        }
-        if let Some(user_name) = &self.user_name {
-            user_name.to_string().to_lowercase().hash(state);
-        }
+        self.key().hash(state);
     }
 }
 
 impl PartialEq for KubernetesRole {
+    /// ARN and names are compared case insensitively as they are hashed, IAM role names being case insensitive.
     fn eq(&self, other: &Self) -> bool {
-        self.groups == other.groups
-            && self.iam_role_arn == other.iam_role_arn
-            && self.user_name == other.user_name
-            && self.role_name == other.role_name
+        self.groups == other.groups && self.key() == other.key() && self.extra == other.extra
     }
 }
 
 impl Eq for KubernetesRole {}
 
+impl Ord for KubernetesRole {
+    /// Entries are ordered by identity, entries of the same identity by groups, consistently with equality.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key()
+            .cmp(&other.key())
+            .then_with(|| self.groups.cmp(&other.groups))
+            .then_with(|| cmp_extra(&self.extra, &other.extra))
+    }
+}
+
+impl PartialOrd for KubernetesRole {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Total order of entry keys not modeled by the tool, YAML values being only partially ordered, e.q: `.nan`.
+fn cmp_extra(
+    extra: &BTreeMap<String, serde_yaml::Value>,
+    other_extra: &BTreeMap<String, serde_yaml::Value>,
+) -> Ordering {
+    match extra.partial_cmp(other_extra) {
+        Some(ordering) if ordering != Ordering::Equal || extra == other_extra => ordering,
+        _ => serde_yaml::to_string(extra)
+            .unwrap_or_default()
+            .cmp(&serde_yaml::to_string(other_extra).unwrap_or_default()),
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 struct MapUserConfig {
     #[serde(rename = "userarn")]
@@ -228,11 +629,18 @@ struct MapUserConfig {
     #[serde(rename = "username")]
     username: String,
     #[serde(rename = "groups")]
-    groups: HashSet<String>,
+    groups: BTreeSet<String>,
     #[serde(rename = "syncedBy")]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     synced_by: Option<SyncedBy>,
+    #[serde(rename = "syncedAt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, with = "rfc3339_seconds")]
+    synced_at: Option<SystemTime>,
+    /// Keys added by other tools, e.q: `email`, re-emitted untouched.
+    #[serde(flatten)]
+    extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl From<KubernetesUser> for MapUserConfig {
@@ -242,6 +650,8 @@ impl From<KubernetesUser> for MapUserConfig {
             username: value.iam_user_name.to_string(),
             groups: value.roles.iter().map(|r| r.to_string()).collect(),
             synced_by: value.synced_by,
+            synced_at: value.synced_at,
+            extra: value.extra,
         }
     }
 }
@@ -266,11 +676,18 @@ struct MapRoleConfig {
     #[serde(default)]
     username: Option<String>,
     #[serde(rename = "groups")]
-    groups: HashSet<String>,
+    groups: BTreeSet<String>,
     #[serde(rename = "syncedBy")]
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     synced_by: Option<SyncedBy>,
+    #[serde(rename = "syncedAt")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, with = "rfc3339_seconds")]
+    synced_at: Option<SystemTime>,
+    /// Keys added by other tools, re-emitted untouched.
+    #[serde(flatten)]
+    extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl From<KubernetesRole> for MapRoleConfig {
@@ -281,6 +698,8 @@ impl From<KubernetesRole> for MapRoleConfig {
             username: value.user_name,
             groups: value.groups.iter().map(|g| g.to_string()).collect(),
             synced_by: value.synced_by,
+            synced_at: value.synced_at,
+            extra: value.extra,
         }
     }
 }
@@ -297,31 +716,429 @@ impl Hash for MapRoleConfig {
     }
 }
 
+/// (De)serializes optional timestamps as RFC 3339 dates, e.q: `2024-01-01T00:00:00Z`.
+mod rfc3339_seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => {
+                serializer.serialize_str(&humantime::format_rfc3339_seconds(*value).to_string())
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|raw| humantime::parse_rfc3339_weak(&raw).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Where a `mapUsers` or `mapRoles` entry failed to be deserialized.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EntryErrorLocation {
+    /// Index of the offending entry, unknown if the error is outside any entry, e.q: content not being a list.
+    pub entry_index: Option<usize>,
+    /// ARN of the offending entry, if it could be extracted.
+    pub arn: Option<Arc<str>>,
+    /// Line and column of the error in the raw YAML, both starting at 1.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl Display for EntryErrorLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.entry_index {
+            Some(entry_index) => write!(f, "entry {entry_index}")?,
+            None => f.write_str("unknown entry")?,
+        }
+        if let Some(arn) = &self.arn {
+            write!(f, " (`{arn}`)")?;
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ", line {line}, column {column}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Locates `error` raised while deserializing `raw` entries, returned along the offending entry truncated to
+/// `snippet_max_length` characters: the whole content is never put into errors as it lists every ARN of the cluster.
+fn locate_entry_error(
+    raw: &str,
+    error: &serde_yaml::Error,
+    arn_key: &str,
+    snippet_max_length: usize,
+) -> (EntryErrorLocation, Arc<str>) {
+    let (line, column) = match error.location() {
+        Some(location) => (Some(location.line()), Some(location.column())),
+        None => (None, None),
+    };
+    let lines: Vec<&str> = raw.lines().collect();
+    let is_dash = |line: &str| line == "-" || line.starts_with("- ");
+    // entries start with a dash at the indentation of the first entry
+    let entries_indent = lines.iter().find_map(|l| {
+        let trimmed = l.trim_start();
+        is_dash(trimmed).then_some(l.len() - trimmed.len())
+    });
+    let entry_starts: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| {
+            let trimmed = l.trim_start();
+            is_dash(trimmed) && Some(l.len() - trimmed.len()) == entries_indent
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let entry_index = line.and_then(|line| entry_starts.iter().rposition(|start| *start < line));
+
+    let offending_text = match entry_index {
+        Some(entry_index) => {
+            let end = entry_starts
+                .get(entry_index + 1)
+                .copied()
+                .unwrap_or(lines.len());
+            lines[entry_starts[entry_index]..end].join("\n")
+        }
+        None => line
+            .and_then(|line| lines.get(line.saturating_sub(1)))
+            .map(|line| line.to_string())
+            .unwrap_or_default(),
+    };
+    let arn = entry_index
+        .and_then(|_| serde_yaml::from_str::<Vec<serde_yaml::Value>>(&offending_text).ok())
+        .and_then(|entries| Some(Arc::from(entries.first()?.get(arn_key)?.as_str()?)));
+
+    let offending_text = offending_text.trim();
+    let snippet = match offending_text.char_indices().nth(snippet_max_length) {
+        _ if snippet_max_length == 0 => Arc::from(""),
+        Some((end, _)) => Arc::from(format!("{}...", &offending_text[..end])),
+        None => Arc::from(offending_text),
+    };
+
+    (
+        EntryErrorLocation {
+            entry_index,
+            arn,
+            line,
+            column,
+        },
+        snippet,
+    )
+}
+
+/// Merges entries sharing the same ARN into the first one, hand-edited aws-auth may contain the same ARN
+/// several times with different groups.
+fn merge_duplicated_entries<T>(
+    key: &str,
+    entries: Vec<T>,
+    arn: impl Fn(&T) -> &String,
+    groups: impl Fn(&mut T) -> &mut BTreeSet<String>,
+) -> Vec<T> {
+    let mut merged_entries: Vec<T> = Vec::with_capacity(entries.len());
+    // index in `merged_entries` of each lowercased ARN, not to go through all merged entries for each one
+    let mut merged_indexes: HashMap<String, usize> = HashMap::with_capacity(entries.len());
+    let mut duplicated_arns = BTreeSet::new();
+
+    for mut entry in entries {
+        match merged_indexes.entry(arn(&entry).to_lowercase()) {
+            hash_map::Entry::Occupied(index) => {
+                duplicated_arns.insert(arn(&entry).to_string());
+                groups(&mut merged_entries[*index.get()]).append(groups(&mut entry));
+            }
+            hash_map::Entry::Vacant(index) => {
+                index.insert(merged_entries.len());
+                merged_entries.push(entry);
+            }
+        }
+    }
+
+    if !duplicated_arns.is_empty() {
+        warn!(
+            "Duplicated `{key}` entries found for {}, merging their groups",
+            duplicated_arns
+                .iter()
+                .map(|a| format!("`{a}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    merged_entries
+}
+
+/// Backend persisting the computed users and roles, e.q: aws-auth config map.
+pub trait AuthBackend {
+    async fn get_config_map_resource_version(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError>;
+
+    /// Users and roles currently synced by the backend, `None` if nothing has been synced yet.
+    async fn read_current(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn update_user_and_role_config_map(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError>;
+}
+
+/// Read-only checks of the cluster, e.q: done by the `validate` command before any sync.
+pub trait ClusterPreflight {
+    /// Version of the API server, e.q: `v1.30.4-eks-a737599`, failing if it cannot be reached.
+    async fn server_version(&self) -> Result<String, KubernetesError>;
+
+    /// Users and roles of the config map, `None` if it doesn't exist.
+    async fn read_aws_auth(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError>;
+
+    /// Whether RBAC allows the client to `verb` the config map, e.q: `patch`.
+    async fn can_i(
+        &self,
+        verb: &str,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<bool, KubernetesError>;
+}
+
+/// Result of a config map update.
+pub struct ConfigMapUpdate {
+    pub aws_auth: AwsAuth,
+    /// Resource version before the update, `None` if the config map has been created or the backend has none.
+    pub previous_resource_version: Option<String>,
+    pub resource_version: Option<String>,
+    pub status: ConfigMapUpdateStatus,
+    /// Users and roles changes compared to the previous config map content.
+    pub diff: AwsAuthDiff,
+    /// Earliest time a synced entry expires, `None` without TTL, next syncs having to go through the update by then.
+    pub next_expiry: Option<SystemTime>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigMapUpdateStatus {
+    /// Config map content has been written.
+    Updated,
+    /// Config map already had the expected content, nothing has been written.
+    Unchanged,
+    /// Config map would have been written, but dry run is enabled, nothing has been sent.
+    DryRun,
+    /// Config map would have been written, but it went through a server side dry run, nothing has been persisted.
+    ServerDryRun,
+}
+
+/// Options tuning how the aws-auth config map is updated.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigMapOptions {
+    /// Create the config map when it doesn't exist instead of failing.
+    pub create_if_missing: bool,
+    /// Config map, in the same namespace, where the previous content is backed up before each modification.
+    pub backup_config_map_name: String,
+    /// Number of backups to keep, backups are disabled if 0.
+    pub backup_history_count: usize,
+    /// Don't write sync metadata annotations, e.q: for clusters with strict admission policies.
+    pub skip_annotations: bool,
+    /// Send writes as server side dry runs, going through admission without persisting anything.
+    pub server_dry_run: bool,
+    /// Stop before any write, only computing changes which would have been made.
+    pub dry_run: bool,
+    /// Entries with those ARNs are never removed nor modified, e.q: node instance role or break-glass user.
+    pub protected_arns: Vec<IamArn>,
+    /// Limits entries removed in a single update.
+    pub removal_guard: RemovalGuard,
+    /// Marker of entries managed by this instance, entries with other markers being left untouched.
+    pub synced_by: SyncedBy,
+    /// Synced entries older than this are removed, even if still granted access.
+    pub synced_entry_ttl: Option<Duration>,
+    /// Unmanaged entries having the ARN of a synced entry are replaced by it instead of being kept alongside.
+    pub adopt_existing_entries: bool,
+    /// Unmanaged entries whose ARN matches it are marked as synced, getting removed once not synced.
+    pub adopt_arn_pattern: Option<Regex>,
+    /// Only remove synced entries no longer granted access, additions being suppressed, e.q: when they go through a manual approval.
+    pub removals_only: bool,
+    /// Maximum number of characters of the offending entry shown in config map deserialization errors, 0 showing none.
+    pub error_snippet_max_length: usize,
+}
+
+impl ConfigMapOptions {
+    /// Earliest time an entry synced by this instance expires in any of `aws_auths`, previous content included so
+    /// that expired entries still granted access are added back by next sync.
+    pub fn next_expiry<'a>(
+        &self,
+        aws_auths: impl IntoIterator<Item = &'a AwsAuth>,
+    ) -> Option<SystemTime> {
+        aws_auths
+            .into_iter()
+            .filter_map(|aws_auth| aws_auth.next_expiry(&self.synced_by, self.synced_entry_ttl))
+            .min()
+    }
+}
+
+/// Kubeconfig used to reach the cluster, in-cluster config or default kubeconfig being inferred if nothing is set.
+#[derive(Clone, Debug, Default)]
+pub struct ClusterAccess {
+    /// Kubeconfig file to read instead of `KUBECONFIG` or `~/.kube/config`.
+    pub kubeconfig_path: Option<PathBuf>,
+    /// Kubeconfig context to use instead of the current one.
+    pub context: Option<String>,
+    /// Timeout of each API request, kube defaults being used if not set.
+    pub request_timeout: Option<Duration>,
+}
+
+/// API server URL and CA certificates a client is configured with.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClusterEndpoint {
+    pub url: String,
+    /// DER encoded certificates.
+    pub root_certs: Vec<Vec<u8>>,
+}
+
+#[derive(Clone)]
 pub struct KubernetesService {
     client: Client,
+    /// Endpoint the client is configured with, empty when built from an existing client.
+    endpoint: ClusterEndpoint,
+    options: ConfigMapOptions,
+    /// Set when leader election is enabled, writes being aborted when leadership is lost.
+    leadership: Option<Leadership>,
+    /// Timeout of each API request, long running ones like watches have to end before it.
+    request_timeout: Option<Duration>,
 }
 
 impl KubernetesService {
-    pub async fn new() -> Result<KubernetesService, KubernetesError> {
+    pub async fn new(
+        cluster_access: &ClusterAccess,
+        options: ConfigMapOptions,
+    ) -> Result<KubernetesService, KubernetesError> {
+        let mut config = match (&cluster_access.kubeconfig_path, &cluster_access.context) {
+            (None, None) => {
+                Config::infer()
+                    .await
+                    .map_err(|e| KubernetesError::ClusterUnreachable {
+                        raw_message: Arc::from(e.to_string()),
+                    })?
+            }
+            _ => Self::kube_config(cluster_access).await?,
+        };
+        if let Some(request_timeout) = cluster_access.request_timeout {
+            config.connect_timeout = Some(request_timeout);
+            config.read_timeout = Some(request_timeout);
+            config.write_timeout = Some(request_timeout);
+        }
+        let endpoint = ClusterEndpoint {
+            url: config.cluster_url.to_string(),
+            root_certs: config.root_cert.clone().unwrap_or_default(),
+        };
         let kube_client =
-            Client::try_default()
-                .await
-                .map_err(|e| KubernetesError::ClusterUnreachable {
-                    raw_message: Arc::from(e.to_string()),
-                })?;
+            Client::try_from(config).map_err(|e| KubernetesError::ClusterUnreachable {
+                raw_message: Arc::from(e.to_string()),
+            })?;
 
         Ok(KubernetesService {
-            client: kube_client,
+            endpoint,
+            request_timeout: cluster_access.request_timeout,
+            ..Self::with_client(kube_client, options)
+        })
+    }
+
+    pub fn with_client(client: Client, options: ConfigMapOptions) -> KubernetesService {
+        KubernetesService {
+            client,
+            endpoint: ClusterEndpoint::default(),
+            options,
+            leadership: None,
+            request_timeout: None,
+        }
+    }
+
+    pub fn endpoint(&self) -> &ClusterEndpoint {
+        &self.endpoint
+    }
+
+    pub fn with_leadership(self, leadership: Leadership) -> KubernetesService {
+        KubernetesService {
+            leadership: Some(leadership),
+            ..self
+        }
+    }
+
+    /// Builds client config from the kubeconfig file and context selected in `cluster_access`.
+    async fn kube_config(cluster_access: &ClusterAccess) -> Result<Config, KubernetesError> {
+        let kubeconfig_path = cluster_access
+            .kubeconfig_path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "default".to_string());
+        let kubeconfig = match &cluster_access.kubeconfig_path {
+            Some(path) => Kubeconfig::read_from(path),
+            None => Kubeconfig::read(),
+        }
+        .map_err(|e| KubernetesError::KubeconfigCannotBeRead {
+            kubeconfig_path: Arc::from(kubeconfig_path.as_str()),
+            raw_message: Arc::from(e.to_string()),
+        })?;
+
+        if let Some(context) = &cluster_access.context {
+            if !kubeconfig.contexts.iter().any(|c| &c.name == context) {
+                return Err(KubernetesError::KubeContextNotFound {
+                    context: Arc::from(context.as_str()),
+                    kubeconfig_path: Arc::from(kubeconfig_path.as_str()),
+                });
+            }
+        }
+
+        Config::from_custom_kubeconfig(
+            kubeconfig,
+            &KubeConfigOptions {
+                context: cluster_access.context.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .map_err(|e| KubernetesError::KubeconfigCannotBeRead {
+            kubeconfig_path: Arc::from(kubeconfig_path.as_str()),
+            raw_message: Arc::from(e.to_string()),
         })
     }
 
     fn generate_users_config_map_yaml_string(
-        kubernetes_users: HashSet<KubernetesUser>,
+        kubernetes_users: BTreeSet<KubernetesUser>,
     ) -> Result<String, KubernetesError> {
-        let user_config_map: HashSet<MapUserConfig> =
-            HashSet::from_iter(kubernetes_users.into_iter().map(MapUserConfig::from));
+        // entries are sorted so the config map content is stable across syncs
+        let mut user_config_map: Vec<MapUserConfig> = kubernetes_users
+            .into_iter()
+            .map(MapUserConfig::from)
+            .collect();
+        user_config_map.sort_by(|a, b| {
+            a.user_arn
+                .cmp(&b.user_arn)
+                .then_with(|| a.username.cmp(&b.username))
+        });
 
-        match serde_yaml::to_string(&user_config_map) {
+        match yaml::to_string(&user_config_map) {
             Ok(s) => Ok(s),
             Err(e) => Err(KubernetesError::CannotSerializeUsersMap {
                 raw_message: Arc::from(e.to_string()),
@@ -330,12 +1147,21 @@ impl KubernetesService {
     }
 
     fn generate_roles_config_map_yaml_string(
-        kubernetes_roles: HashSet<KubernetesRole>,
+        kubernetes_roles: BTreeSet<KubernetesRole>,
     ) -> Result<String, KubernetesError> {
-        let role_config_map: HashSet<MapRoleConfig> =
-            HashSet::from_iter(kubernetes_roles.into_iter().map(MapRoleConfig::from));
+        // entries are sorted so the config map content is stable across syncs
+        let mut role_config_map: Vec<MapRoleConfig> = kubernetes_roles
+            .into_iter()
+            .map(MapRoleConfig::from)
+            .collect();
+        role_config_map.sort_by(|a, b| {
+            a.role_arn
+                .cmp(&b.role_arn)
+                .then_with(|| a.rolename.cmp(&b.rolename))
+                .then_with(|| a.username.cmp(&b.username))
+        });
 
-        match serde_yaml::to_string(&role_config_map) {
+        match yaml::to_string(&role_config_map) {
             Ok(s) => Ok(s),
             Err(e) => Err(KubernetesError::CannotSerializeRolesMap {
                 raw_message: Arc::from(e.to_string()),
@@ -343,377 +1169,4829 @@ impl KubernetesService {
         }
     }
 
-    pub async fn update_user_and_role_config_map(
-        &self,
-        config_map_namespace: &str,
-        config_map_name: &str,
-        kubernetes_users_to_be_added: Option<HashSet<KubernetesUser>>,
-        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
-        karpenter_role_to_be_added: Option<KubernetesRole>,
-    ) -> Result<(), KubernetesError> {
-        let config_maps_api: Api<ConfigMap> =
-            Api::namespaced(self.client.clone(), config_map_namespace); // TODO(benjaminch): avoid clone()
-
-        // get config map
-        let mut users_config_map = config_maps_api.get(config_map_name).await.map_err(|e| {
-            KubernetesError::ConfigMapNotFound {
-                config_map_name: Arc::from(config_map_name),
-                config_map_namespace: Arc::from(config_map_namespace),
-                raw_message: Arc::from(e.to_string()),
-            }
-        })?;
-
-        // update config map
-        let mut default_config_map_data = BTreeMap::new();
-        let config_map_data = users_config_map
-            .data
-            .as_mut()
-            .unwrap_or(&mut default_config_map_data);
+    /// Parses users and roles currently set in aws-auth config map, duplicated entries being merged.
+    pub fn parse_aws_auth(config_map: &ConfigMap) -> Result<AwsAuth, KubernetesError> {
+        Self::parse_aws_auth_with_error_snippet(config_map, DEFAULT_ERROR_SNIPPET_MAX_LENGTH)
+    }
 
-        let aws_auth = AwsAuthBuilder::new(
+    /// Same as [`Self::parse_aws_auth`], errors showing at most `error_snippet_max_length` characters of the offending entry.
+    pub fn parse_aws_auth_with_error_snippet(
+        config_map: &ConfigMap,
+        error_snippet_max_length: usize,
+    ) -> Result<AwsAuth, KubernetesError> {
+        let config_map_data = config_map.data.clone().unwrap_or_default();
+        Ok(AwsAuth {
             // get existing users from configmap
-            match config_map_data.get("mapUsers") {
-                None => HashSet::with_capacity(0),
-                Some(kubernetes_existing_users_raw_yaml) => HashSet::from_iter(
-                    serde_yaml::from_str::<HashSet<MapUserConfig>>(
-                        kubernetes_existing_users_raw_yaml,
+            users: match config_map_data.get("mapUsers") {
+                None => BTreeSet::new(),
+                Some(kubernetes_existing_users_raw_yaml) => BTreeSet::from_iter(
+                    merge_duplicated_entries(
+                        "mapUsers",
+                        serde_yaml::from_str::<Vec<MapUserConfig>>(
+                            kubernetes_existing_users_raw_yaml,
+                        )
+                        .map_err(|e| {
+                            let (location, snippet) = locate_entry_error(
+                                kubernetes_existing_users_raw_yaml,
+                                &e,
+                                "userarn",
+                                error_snippet_max_length,
+                            );
+                            KubernetesError::CannotDeserializeUsersMap {
+                                location,
+                                snippet,
+                                underlying_error: Arc::from(e.to_string().as_str()),
+                            }
+                        })?,
+                        |u| &u.user_arn,
+                        |u| &mut u.groups,
                     )
-                    .map_err(|e| KubernetesError::CannotDeserializeUsersMap {
-                        raw_message: Arc::from(kubernetes_existing_users_raw_yaml.as_str()),
-                        underlying_error: Arc::from(e.to_string().as_str()),
-                    })?
                     .into_iter()
                     .map(KubernetesUser::from)
                     .collect::<Vec<_>>(),
                 ),
             },
             // get existing roles from configmap
-            match config_map_data.get("mapRoles") {
-                None => HashSet::with_capacity(0),
-                Some(kubernetes_existing_roles_raw_yaml) => HashSet::from_iter(
-                    serde_yaml::from_str::<HashSet<MapRoleConfig>>(
-                        kubernetes_existing_roles_raw_yaml,
+            roles: match config_map_data.get("mapRoles") {
+                None => BTreeSet::new(),
+                Some(kubernetes_existing_roles_raw_yaml) => BTreeSet::from_iter(
+                    merge_duplicated_entries(
+                        "mapRoles",
+                        serde_yaml::from_str::<Vec<MapRoleConfig>>(
+                            kubernetes_existing_roles_raw_yaml,
+                        )
+                        .map_err(|e| {
+                            let (location, snippet) = locate_entry_error(
+                                kubernetes_existing_roles_raw_yaml,
+                                &e,
+                                "rolearn",
+                                error_snippet_max_length,
+                            );
+                            KubernetesError::CannotDeserializeRolesMap {
+                                location,
+                                snippet,
+                                underlying_error: Arc::from(e.to_string().as_str()),
+                            }
+                        })?,
+                        |r| &r.role_arn,
+                        |r| &mut r.groups,
                     )
-                    .map_err(|e| KubernetesError::CannotDeserializeRolesMap {
-                        raw_message: Arc::from(kubernetes_existing_roles_raw_yaml.as_str()),
-                        underlying_error: Arc::from(e.to_string().as_str()),
-                    })?
                     .into_iter()
                     .map(|r| KubernetesRole {
                         role_name: r.rolename.clone(),
                         user_name: r.username.clone(),
-                        iam_role_arn: IamArn(r.role_arn.to_string()),
+                        iam_role_arn: IamArn::new(&r.role_arn.to_string()),
                         groups: r
                             .groups
                             .iter()
                             .map(|g| KubernetesGroupName(g.to_string()))
                             .collect(),
                         synced_by: r.synced_by.clone(),
+                        synced_at: r.synced_at,
+                        extra: r.extra,
                     })
                     .collect::<Vec<_>>(),
                 ),
             },
-        )
-        .new_synced_users(kubernetes_users_to_be_added.unwrap_or_default())
-        .new_synced_roles({
-            let mut roles = Vec::new();
-            if let Some(sso_role) = kubernetes_sso_role_to_be_added {
-                roles.append(&mut vec![sso_role])
-            };
-            if let Some(karpenter_role) = karpenter_role_to_be_added {
-                roles.append(&mut vec![karpenter_role])
-            };
-            HashSet::from_iter(roles)
         })
-        .build();
+    }
 
-        // adding users
-        config_map_data.insert(
-            "mapUsers".to_string(),
-            Self::generate_users_config_map_yaml_string(aws_auth.users)?,
-        );
+    /// Replaces entries synced by this instance in `existing_aws_auth` with users and roles to be synced, other entries being kept.
+    /// Changes made to `existing_aws_auth` are returned along, additions left out, only removals being synced, included.
+    pub fn merge_aws_auth(
+        existing_aws_auth: AwsAuth,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        options: &ConfigMapOptions,
+    ) -> (AwsAuth, AwsAuthDiff) {
+        Self::aws_auth_builder(
+            existing_aws_auth,
+            kubernetes_users_to_be_added,
+            kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            options,
+        )
+        .build_with_report()
+    }
 
-        // adding sso roles
-        config_map_data.insert(
-            "mapRoles".to_string(),
-            Self::generate_roles_config_map_yaml_string(aws_auth.roles)?,
+    fn aws_auth_builder(
+        existing_aws_auth: AwsAuth,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        options: &ConfigMapOptions,
+    ) -> AwsAuthBuilder {
+        let keep_users = kubernetes_users_to_be_added.is_none();
+        let keep_roles = Self::roles_untouched(
+            &existing_aws_auth,
+            &kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            options,
         );
+        let mut builder = AwsAuthBuilder::new(
+            existing_aws_auth.users,
+            existing_aws_auth.roles,
+            options.synced_by.clone(),
+        );
+        builder
+            .new_synced_users(kubernetes_users_to_be_added.unwrap_or_default())
+            .new_synced_roles({
+                let mut roles = Vec::new();
+                if let Some(sso_role) = kubernetes_sso_role_to_be_added {
+                    roles.append(&mut vec![sso_role])
+                };
+                roles.extend_from_slice(karpenter_roles_to_be_added);
+                roles.extend_from_slice(other_roles_to_be_added);
+                roles
+            })
+            .protected_arns(&options.protected_arns)
+            .synced_entry_ttl(options.synced_entry_ttl)
+            .adopt_existing_entries(options.adopt_existing_entries)
+            .adopt_arn_pattern(options.adopt_arn_pattern.clone())
+            .removals_only(options.removals_only)
+            .keep_users(keep_users)
+            .keep_roles(keep_roles);
 
-        match config_maps_api
-            .replace(config_map_name, &PostParams::default(), &users_config_map)
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(e) => Err(KubernetesError::ConfigMapCannotBePatched {
-                config_map_name: Arc::from(config_map_name),
-                config_map_namespace: Arc::from(config_map_namespace),
+        builder
+    }
+
+    /// Whether existing roles are left as they are: no role to be synced, e.q: neither SSO nor Karpenter configured,
+    /// and none previously synced to be removed.
+    fn roles_untouched(
+        existing_aws_auth: &AwsAuth,
+        kubernetes_sso_role_to_be_added: &Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        options: &ConfigMapOptions,
+    ) -> bool {
+        kubernetes_sso_role_to_be_added.is_none()
+            && karpenter_roles_to_be_added.is_empty()
+            && other_roles_to_be_added.is_empty()
+            && !existing_aws_auth
+                .roles
+                .iter()
+                .any(|r| r.is_synced_by(&options.synced_by))
+    }
+
+    /// Merges users, roles and accounts to be synced into aws-auth config map, returning the resulting aws-auth.
+    /// Other config map data keys are left untouched.
+    pub fn merge_config_map(
+        config_map: &mut ConfigMap,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+        options: &ConfigMapOptions,
+    ) -> Result<AwsAuth, KubernetesError> {
+        Self::merge_config_map_with_report(
+            config_map,
+            kubernetes_users_to_be_added,
+            kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            aws_accounts_to_be_added,
+            options,
+        )
+        .map(|(aws_auth, _)| aws_auth)
+    }
+
+    /// Same as `merge_config_map`, users and roles changes made to the config map being returned along.
+    /// `mapUsers` is left as written if there are no users to be synced, `mapRoles` if roles are left untouched.
+    pub fn merge_config_map_with_report(
+        config_map: &mut ConfigMap,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+        options: &ConfigMapOptions,
+    ) -> Result<(AwsAuth, AwsAuthDiff), KubernetesError> {
+        let existing_aws_auth =
+            Self::parse_aws_auth_with_error_snippet(config_map, options.error_snippet_max_length)?;
+        let keep_users = kubernetes_users_to_be_added.is_none();
+        let keep_roles = Self::roles_untouched(
+            &existing_aws_auth,
+            &kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            options,
+        );
+        let config_map_data = config_map.data.get_or_insert_with(BTreeMap::new);
+        let (aws_auth, diff) = Self::merge_aws_auth(
+            existing_aws_auth,
+            kubernetes_users_to_be_added,
+            kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            options,
+        );
+
+        // adding users, existing ones being left as written if group users sync is disabled
+        if !keep_users {
+            config_map_data.insert(
+                "mapUsers".to_string(),
+                Self::generate_users_config_map_yaml_string(aws_auth.users.clone())?,
+            );
+        }
+
+        // adding sso roles, existing ones being left as written if there are no roles to sync nor to remove
+        if !keep_roles {
+            config_map_data.insert(
+                "mapRoles".to_string(),
+                Self::generate_roles_config_map_yaml_string(aws_auth.roles.clone())?,
+            );
+        }
+
+        // content aws-iam-authenticator cannot read would lock everyone out, making sure it reads back as intended
+        Self::validate_generated_content(config_map, &aws_auth)?;
+
+        // adding accounts
+        Self::merge_config_map_aws_accounts(config_map, aws_accounts_to_be_added)?;
+
+        Ok((aws_auth, diff))
+    }
+
+    /// Parses generated `mapUsers` and `mapRoles` back, failing if they don't hold exactly `aws_auth` entries.
+    fn validate_generated_content(
+        config_map: &ConfigMap,
+        aws_auth: &AwsAuth,
+    ) -> Result<(), KubernetesError> {
+        let config_map_data = config_map.data.clone().unwrap_or_default();
+        let invalid = |key: &str, intended_content: String, raw_message: String| {
+            KubernetesError::GeneratedContentInvalid {
+                key: Arc::from(key),
+                generated_content: Arc::from(
+                    config_map_data
+                        .get(key)
+                        .map(String::as_str)
+                        .unwrap_or_default(),
+                ),
+                intended_content: Arc::from(intended_content),
+                raw_message: Arc::from(raw_message),
+            }
+        };
+        let mut intended_users: Vec<&KubernetesUser> = aws_auth.users.iter().collect();
+        intended_users.sort_by_key(|u| (u.iam_arn.to_string(), u.iam_user_name.to_string()));
+        let mut intended_roles: Vec<&KubernetesRole> = aws_auth.roles.iter().collect();
+        intended_roles.sort_by_key(|r| r.iam_role_arn.to_string());
+
+        let parsed_aws_auth = Self::parse_aws_auth(config_map).map_err(|e| match e {
+            KubernetesError::CannotDeserializeRolesMap { .. } => {
+                invalid("mapRoles", format!("{intended_roles:?}"), e.to_string())
+            }
+            e => invalid("mapUsers", format!("{intended_users:?}"), e.to_string()),
+        })?;
+        if !Self::read_back_as(&parsed_aws_auth.users, &aws_auth.users) {
+            return Err(invalid(
+                "mapUsers",
+                format!("{intended_users:?}"),
+                format!("parsed users differ: {:?}", parsed_aws_auth.users),
+            ));
+        }
+        if !Self::read_back_as(&parsed_aws_auth.roles, &aws_auth.roles) {
+            return Err(invalid(
+                "mapRoles",
+                format!("{intended_roles:?}"),
+                format!("parsed roles differ: {:?}", parsed_aws_auth.roles),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Whether `parsed` entries are `intended` ones field by field, as equality ignores markers and names case.
+    fn read_back_as<T: AwsAuthEntry>(parsed: &BTreeSet<T>, intended: &BTreeSet<T>) -> bool {
+        parsed.len() == intended.len()
+            && parsed
+                .iter()
+                .zip(intended)
+                .all(|(parsed, intended)| parsed.is_read_back_as(intended))
+    }
+
+    /// Removes entries of YAML sequence `content` for which `removed` is true, other entries being kept byte for
+    /// byte, comments included. `None` if entries cannot be told apart in the text, e.q: in a flow sequence.
+    fn remove_sequence_entries(
+        content: &str,
+        removed: impl Fn(&serde_yaml::Value) -> bool,
+    ) -> Option<String> {
+        let entries: Vec<serde_yaml::Value> = match content.trim().is_empty() {
+            true => Vec::new(),
+            false => serde_yaml::from_str(content).ok()?,
+        };
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let is_entry_start = |line: &str, indent: usize| {
+            line.len() > indent
+                && line[..indent].chars().all(|c| c == ' ')
+                && line[indent..].starts_with('-')
+                && line[indent + 1..].starts_with([' ', '\n', '\r'])
+        };
+        // entries start at the indentation of the first one, their nested content being more indented
+        let first_entry = lines.iter().position(|line| {
+            let trimmed = line.trim_start_matches(' ');
+            trimmed.starts_with('-') && is_entry_start(line, line.len() - trimmed.len())
+        });
+        let (header, chunks) = match first_entry {
+            None => (content.to_string(), Vec::new()),
+            Some(first_entry) => {
+                let indent =
+                    lines[first_entry].len() - lines[first_entry].trim_start_matches(' ').len();
+                let mut chunks: Vec<String> = Vec::new();
+                for line in &lines[first_entry..] {
+                    match (is_entry_start(line, indent), chunks.last_mut()) {
+                        (false, Some(chunk)) => chunk.push_str(line),
+                        _ => chunks.push(line.to_string()),
+                    }
+                }
+                (lines[..first_entry].concat(), chunks)
+            }
+        };
+
+        // each chunk has to be exactly one of the entries, in order
+        let mut kept = header;
+        let mut kept_entries = 0;
+        if chunks.len() != entries.len() {
+            return None;
+        }
+        for (chunk, entry) in chunks.iter().zip(&entries) {
+            let chunk_entries: Vec<serde_yaml::Value> = serde_yaml::from_str(chunk).ok()?;
+            if chunk_entries.as_slice() != std::slice::from_ref(entry) {
+                return None;
+            }
+            if !removed(entry) {
+                kept.push_str(chunk);
+                kept_entries += 1;
+            }
+        }
+
+        match kept_entries {
+            0 if !entries.is_empty() => Some("[]\n".to_string()),
+            _ => Some(kept),
+        }
+    }
+
+    /// Accounts added by the tool are tracked in an annotation since `mapAccounts` entries cannot hold
+    /// any marker, allowing to remove them later on while keeping accounts added by others.
+    fn merge_config_map_aws_accounts(
+        config_map: &mut ConfigMap,
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<(), KubernetesError> {
+        let previously_managed_accounts: BTreeSet<AwsAccountId> = config_map
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|a| a.get(MANAGED_ACCOUNTS_ANNOTATION))
+            .map(|raw| {
+                raw.split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .map(AwsAccountId::new)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // nothing is nor was managed by the tool, leaving mapAccounts untouched
+        if previously_managed_accounts.is_empty() && aws_accounts_to_be_added.is_empty() {
+            return Ok(());
+        }
+
+        let config_map_data = config_map.data.get_or_insert_with(BTreeMap::new);
+        let existing_accounts = match config_map_data.get("mapAccounts") {
+            None => BTreeSet::new(),
+            Some(raw) => Self::parse_aws_accounts(raw)?,
+        };
+
+        let accounts: Vec<String> = existing_accounts
+            .into_iter()
+            // removing accounts previously added by the tool, allowing to delete them
+            .filter(|a| !previously_managed_accounts.contains(a))
+            .chain(aws_accounts_to_be_added.iter().cloned())
+            .collect::<BTreeSet<AwsAccountId>>()
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+
+        config_map_data.insert(
+            "mapAccounts".to_string(),
+            serde_yaml::to_string(&accounts).map_err(|e| {
+                KubernetesError::CannotSerializeAccountsMap {
+                    raw_message: Arc::from(e.to_string()),
+                }
+            })?,
+        );
+
+        let annotations = config_map
+            .metadata
+            .annotations
+            .get_or_insert_with(BTreeMap::new);
+        match aws_accounts_to_be_added.is_empty() {
+            true => annotations.remove(MANAGED_ACCOUNTS_ANNOTATION),
+            false => annotations.insert(
+                MANAGED_ACCOUNTS_ANNOTATION.to_string(),
+                aws_accounts_to_be_added
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        };
+
+        Ok(())
+    }
+
+    /// Parses `mapAccounts` entries, accounts can be written either as strings or numbers.
+    fn parse_aws_accounts(raw: &str) -> Result<BTreeSet<AwsAccountId>, KubernetesError> {
+        if raw.trim().is_empty() {
+            return Ok(BTreeSet::new());
+        }
+
+        Ok(serde_yaml::from_str::<Vec<serde_yaml::Value>>(raw)
+            .map_err(|e| KubernetesError::CannotDeserializeAccountsMap {
+                raw_message: Arc::from(raw),
+                underlying_error: Arc::from(e.to_string().as_str()),
+            })?
+            .into_iter()
+            .filter_map(|a| match a {
+                serde_yaml::Value::String(a) => Some(AwsAccountId::new(&a)),
+                serde_yaml::Value::Number(a) => Some(AwsAccountId::new(&a.to_string())),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Tells whether `merged` config map content differs from the `original` one.
+    /// `mapUsers`, `mapRoles` and `mapAccounts` are compared parsed since their entries order isn't stable.
+    pub fn config_map_content_changed(original: &ConfigMap, merged: &ConfigMap) -> bool {
+        fn same_parsed<T: DeserializeOwned + PartialEq>(original: &str, merged: &str) -> bool {
+            match (
+                serde_yaml::from_str::<T>(original),
+                serde_yaml::from_str::<T>(merged),
+            ) {
+                (Ok(original), Ok(merged)) => original == merged,
+                _ => false,
+            }
+        }
+
+        if original.metadata.annotations != merged.metadata.annotations {
+            return true;
+        }
+
+        let original_data = original.data.clone().unwrap_or_default();
+        let merged_data = merged.data.clone().unwrap_or_default();
+        let keys: BTreeSet<&String> = original_data.keys().chain(merged_data.keys()).collect();
+        for key in keys {
+            let changed = match (key.as_str(), original_data.get(key), merged_data.get(key)) {
+                (_, Some(original), Some(merged)) if original == merged => false,
+                ("mapUsers", Some(original), Some(merged)) => {
+                    !same_parsed::<HashSet<MapUserConfig>>(original, merged)
+                }
+                ("mapRoles", Some(original), Some(merged)) => {
+                    !same_parsed::<HashSet<MapRoleConfig>>(original, merged)
+                }
+                ("mapAccounts", Some(original), Some(merged)) => {
+                    match (
+                        Self::parse_aws_accounts(original),
+                        Self::parse_aws_accounts(merged),
+                    ) {
+                        (Ok(original), Ok(merged)) => original != merged,
+                        _ => true,
+                    }
+                }
+                _ => true,
+            };
+            if changed {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl AuthBackend for KubernetesService {
+    async fn get_config_map_resource_version(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<String>, KubernetesError> {
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+
+        retry_on_transient_error(
+            &format!("get config map `{config_map_namespace}/{config_map_name}` metadata"),
+            || config_maps_api.get_metadata(config_map_name),
+        )
+        .await
+        .map(|m| m.metadata.resource_version)
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::ConfigMapNotFound {
+                config_map_name: Arc::from(config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })
+    }
+
+    async fn read_current(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        self.read_aws_auth(config_map_namespace, config_map_name)
+            .await
+    }
+
+    async fn update_user_and_role_config_map(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, KubernetesError> {
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace); // TODO(benjaminch): avoid clone()
+
+        retry_on_conflict(
+            config_map_namespace,
+            config_map_name,
+            CONFIG_MAP_UPDATE_MAX_ATTEMPTS,
+            |attempt| {
+                let config_maps_api = &config_maps_api;
+                let kubernetes_users_to_be_added = kubernetes_users_to_be_added.clone();
+                let kubernetes_sso_role_to_be_added = kubernetes_sso_role_to_be_added.clone();
+
+                async move {
+                    // get config map, fetched again on each attempt to get latest changes
+                    let read_span = info_span!(
+                        "config_map_read",
+                        namespace = config_map_namespace,
+                        name = config_map_name,
+                    );
+                    let mut users_config_map = match retry_on_transient_error(
+                        &format!("get config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.get(config_map_name),
+                    )
+                    .instrument(read_span)
+                    .await
+                    {
+                        Ok(config_map) => config_map,
+                        Err(RequestError::Failed(kube::Error::Api(e)))
+                            if e.code == 404 && self.options.create_if_missing =>
+                        {
+                            info!("Config map `{config_map_namespace}/{config_map_name}` doesn't exist, creating it");
+                            return self
+                                .create_user_and_role_config_map(
+                                    config_maps_api,
+                                    config_map_namespace,
+                                    config_map_name,
+                                    kubernetes_users_to_be_added,
+                                    kubernetes_sso_role_to_be_added,
+                                    karpenter_roles_to_be_added,
+                                    other_roles_to_be_added,
+                                    aws_accounts_to_be_added,
+                                )
+                                .await;
+                        }
+                        Err(RequestError::Unreachable(e)) => {
+                            return Err(UpdateAttemptError::Failed(e))
+                        }
+                        Err(RequestError::Failed(e)) => {
+                            return Err(UpdateAttemptError::Failed(
+                                KubernetesError::ConfigMapNotFound {
+                                    config_map_name: Arc::from(config_map_name),
+                                    config_map_namespace: Arc::from(config_map_namespace),
+                                    raw_message: Arc::from(e.to_string()),
+                                },
+                            ))
+                        }
+                    };
+
+                    // update config map
+                    let original_config_map = users_config_map.clone();
+                    let existing_aws_auth = Self::parse_aws_auth_with_error_snippet(&original_config_map, self.options.error_snippet_max_length)
+                        .map_err(UpdateAttemptError::Failed)?;
+                    let (aws_auth, diff) = Self::merge_config_map_with_report(
+                        &mut users_config_map,
+                        kubernetes_users_to_be_added,
+                        kubernetes_sso_role_to_be_added,
+                        karpenter_roles_to_be_added, other_roles_to_be_added,
+                        aws_accounts_to_be_added, &self.options
+                    )
+                    .map_err(UpdateAttemptError::Failed)?;
+                    let next_expiry = self.options.next_expiry([&existing_aws_auth, &aws_auth]);
+
+                    if !Self::config_map_content_changed(&original_config_map, &users_config_map) {
+                        return Ok(ConfigMapUpdate {
+                            diff,
+                            aws_auth,
+                            previous_resource_version: original_config_map.metadata.resource_version,
+                            resource_version: users_config_map.metadata.resource_version,
+                            status: ConfigMapUpdateStatus::Unchanged,
+                            next_expiry,
+                        });
+                    }
+
+                    self.options
+                        .removal_guard
+                        .check(&existing_aws_auth, &diff, &self.options.synced_by)
+                        .map_err(UpdateAttemptError::Failed)?;
+
+                    if self.options.dry_run {
+                        return Ok(ConfigMapUpdate {
+                            diff,
+                            aws_auth,
+                            previous_resource_version: original_config_map.metadata.resource_version.clone(),
+                            resource_version: original_config_map.metadata.resource_version,
+                            status: ConfigMapUpdateStatus::DryRun,
+                            next_expiry,
+                        });
+                    }
+
+                    self.ensure_leadership(config_map_namespace, config_map_name)
+                        .map_err(UpdateAttemptError::Failed)?;
+
+                    self.backup_config_map(
+                        config_maps_api,
+                        config_map_namespace,
+                        config_map_name,
+                        &original_config_map,
+                    )
+                    .await
+                    .map_err(UpdateAttemptError::Failed)?;
+
+                    self.annotate_config_map(&mut users_config_map, &aws_auth);
+
+                    let post_params = self.post_params();
+                    let write_span = info_span!(
+                        "config_map_write",
+                        namespace = config_map_namespace,
+                        name = config_map_name,
+                        users = aws_auth.users.len(),
+                        roles = aws_auth.roles.len(),
+                    );
+                    retry_on_transient_error(
+                        &format!("replace config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.replace(config_map_name, &post_params, &users_config_map),
+                    )
+                    .instrument(write_span)
+                    .await
+                        .map(|config_map| ConfigMapUpdate {
+                            diff,
+                            aws_auth,
+                            previous_resource_version: original_config_map.metadata.resource_version.clone(),
+                            resource_version: config_map.metadata.resource_version,
+                            status: self.written_status(),
+                            next_expiry,
+                        })
+                        .map_err(|e| {
+                            UpdateAttemptError::from_replace_error(
+                                e,
+                                config_map_namespace,
+                                config_map_name,
+                                attempt,
+                            )
+                        })
+                }
+            },
+        )
+        .await
+    }
+}
+
+impl KubernetesService {
+    #[allow(clippy::too_many_arguments)]
+    async fn create_user_and_role_config_map(
+        &self,
+        config_maps_api: &Api<ConfigMap>,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+        kubernetes_sso_role_to_be_added: Option<KubernetesRole>,
+        karpenter_roles_to_be_added: &[KubernetesRole],
+        other_roles_to_be_added: &[KubernetesRole],
+        aws_accounts_to_be_added: &BTreeSet<AwsAccountId>,
+    ) -> Result<ConfigMapUpdate, UpdateAttemptError> {
+        let mut config_map = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(config_map_name.to_string()),
+                namespace: Some(config_map_namespace.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (aws_auth, diff) = Self::merge_config_map_with_report(
+            &mut config_map,
+            kubernetes_users_to_be_added,
+            kubernetes_sso_role_to_be_added,
+            karpenter_roles_to_be_added,
+            other_roles_to_be_added,
+            aws_accounts_to_be_added,
+            &self.options,
+        )
+        .map_err(UpdateAttemptError::Failed)?;
+        let next_expiry = self.options.next_expiry([&aws_auth]);
+
+        if self.options.dry_run {
+            return Ok(ConfigMapUpdate {
+                diff,
+                aws_auth,
+                previous_resource_version: None,
+                resource_version: None,
+                status: ConfigMapUpdateStatus::DryRun,
+                next_expiry,
+            });
+        }
+
+        self.ensure_leadership(config_map_namespace, config_map_name)
+            .map_err(UpdateAttemptError::Failed)?;
+
+        self.annotate_config_map(&mut config_map, &aws_auth);
+
+        match config_maps_api
+            .create(&self.post_params(), &config_map)
+            .await
+        {
+            Ok(config_map) => Ok(ConfigMapUpdate {
+                diff,
+                aws_auth,
+                previous_resource_version: None,
+                resource_version: config_map.metadata.resource_version,
+                status: self.written_status(),
+                next_expiry,
+            }),
+            // someone else created it in the meantime, retrying will go through the update path
+            Err(kube::Error::Api(e)) if e.code == 409 => Err(UpdateAttemptError::Conflict {
                 raw_message: Arc::from(e.to_string()),
             }),
+            Err(e) => Err(UpdateAttemptError::Failed(
+                KubernetesError::ConfigMapCannotBeCreated {
+                    config_map_name: Arc::from(config_map_name),
+                    config_map_namespace: Arc::from(config_map_namespace),
+                    raw_message: Arc::from(e.to_string()),
+                },
+            )),
+        }
+    }
+}
+
+impl KubernetesService {
+    /// Fails if leader election is enabled and leadership is not held anymore, another replica possibly writing already.
+    fn ensure_leadership(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<(), KubernetesError> {
+        match &self.leadership {
+            Some(leadership) if !leadership.is_leader() => Err(KubernetesError::LeadershipLost {
+                config_map_name: Arc::from(config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn post_params(&self) -> PostParams {
+        PostParams {
+            dry_run: self.options.server_dry_run,
+            ..Default::default()
+        }
+    }
+
+    /// Status of a successful write, which is not persisted on server side dry runs.
+    fn written_status(&self) -> ConfigMapUpdateStatus {
+        match self.options.server_dry_run {
+            true => ConfigMapUpdateStatus::ServerDryRun,
+            false => ConfigMapUpdateStatus::Updated,
+        }
+    }
+
+    /// Annotates `config_map` with last write metadata, letting operators know when and by which version it was written.
+    fn annotate_config_map(&self, config_map: &mut ConfigMap, aws_auth: &AwsAuth) {
+        if self.options.skip_annotations {
+            return;
+        }
+
+        config_map
+            .metadata
+            .annotations
+            .get_or_insert_with(BTreeMap::new)
+            .extend([
+                (
+                    LAST_SYNC_TIME_ANNOTATION.to_string(),
+                    humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+                ),
+                (
+                    VERSION_ANNOTATION.to_string(),
+                    env!("CARGO_PKG_VERSION").to_string(),
+                ),
+                (
+                    CONTENT_HASH_ANNOTATION.to_string(),
+                    format!("{:016x}", aws_auth.content_hash()),
+                ),
+            ]);
+    }
+
+    /// Backs up `config_map` content into the backup config map before it gets modified.
+    async fn backup_config_map(
+        &self,
+        config_maps_api: &Api<ConfigMap>,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        config_map: &ConfigMap,
+    ) -> Result<(), KubernetesError> {
+        if self.options.backup_history_count == 0 {
+            return Ok(());
+        }
+
+        let backup_config_map_name = self.options.backup_config_map_name.as_str();
+        let existing_backup_config_map = retry_on_transient_error(
+            &format!("get backup config map `{config_map_namespace}/{backup_config_map_name}`"),
+            || config_maps_api.get_opt(backup_config_map_name),
+        )
+        .await
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            // missing backup config map is `Ok(None)`, remaining errors are about access rights
+            RequestError::Failed(e) => KubernetesError::BackupCannotBeRead {
+                backup_config_map_name: Arc::from(backup_config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })?;
+        let mut backup_config_map = existing_backup_config_map.clone().unwrap_or(ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(backup_config_map_name.to_string()),
+                namespace: Some(config_map_namespace.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        if !backup::add_backup(
+            backup_config_map.data.get_or_insert_with(BTreeMap::new),
+            &ConfigMapBackup::from_config_map(config_map),
+            SystemTime::now(),
+            self.options.backup_history_count,
+        )? {
+            return Ok(());
+        }
+
+        let post_params = self.post_params();
+        match existing_backup_config_map {
+            Some(_) => retry_on_transient_error(
+                &format!(
+                    "replace backup config map `{config_map_namespace}/{backup_config_map_name}`"
+                ),
+                || {
+                    config_maps_api.replace(
+                        backup_config_map_name,
+                        &post_params,
+                        &backup_config_map,
+                    )
+                },
+            )
+            .await
+            .map_err(|e| match e {
+                RequestError::Unreachable(e) => e,
+                RequestError::Failed(e) => KubernetesError::ConfigMapCannotBePatched {
+                    config_map_name: Arc::from(backup_config_map_name),
+                    config_map_namespace: Arc::from(config_map_namespace),
+                    attempts: 1,
+                    raw_message: Arc::from(e.to_string()),
+                },
+            })?,
+            None => retry_on_transient_error(
+                &format!(
+                    "create backup config map `{config_map_namespace}/{backup_config_map_name}`"
+                ),
+                || config_maps_api.create(&post_params, &backup_config_map),
+            )
+            .await
+            .map_err(|e| match e {
+                RequestError::Unreachable(e) => e,
+                RequestError::Failed(e) => KubernetesError::ConfigMapCannotBeCreated {
+                    config_map_name: Arc::from(backup_config_map_name),
+                    config_map_namespace: Arc::from(config_map_namespace),
+                    raw_message: Arc::from(e.to_string()),
+                },
+            })?,
+        };
+
+        info!("Config map `{config_map_namespace}/{config_map_name}` content backed up into `{backup_config_map_name}`");
+
+        Ok(())
+    }
+
+    /// Lists backups available for restore, from the oldest to the most recent one.
+    pub async fn list_config_map_backups(
+        &self,
+        config_map_namespace: &str,
+    ) -> Result<Vec<String>, KubernetesError> {
+        Ok(backup::backup_names(
+            &self.get_config_map_backups(config_map_namespace).await?,
+        ))
+    }
+
+    /// Writes backup `backup_name` (the most recent one if not set) back into the config map, returning the restored backup name.
+    /// Current content is backed up first, so a restore can be reverted as well.
+    pub async fn restore_config_map_backup(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        backup_name: Option<&str>,
+    ) -> Result<String, KubernetesError> {
+        let (backup_name, backup) = backup::get_backup(
+            &self.get_config_map_backups(config_map_namespace).await?,
+            backup_name,
+        )?;
+
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+        retry_on_conflict(
+            config_map_namespace,
+            config_map_name,
+            CONFIG_MAP_UPDATE_MAX_ATTEMPTS,
+            |attempt| {
+                let config_maps_api = &config_maps_api;
+                let backup = &backup;
+
+                async move {
+                    let mut config_map = retry_on_transient_error(
+                        &format!("get config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.get(config_map_name),
+                    )
+                    .await
+                    .map_err(|e| match e {
+                        RequestError::Unreachable(e) => UpdateAttemptError::Failed(e),
+                        RequestError::Failed(e) => {
+                            UpdateAttemptError::Failed(KubernetesError::ConfigMapNotFound {
+                                config_map_name: Arc::from(config_map_name),
+                                config_map_namespace: Arc::from(config_map_namespace),
+                                raw_message: Arc::from(e.to_string()),
+                            })
+                        }
+                    })?;
+                    let original_config_map = config_map.clone();
+                    backup.apply_to(&mut config_map);
+
+                    if !Self::config_map_content_changed(&original_config_map, &config_map) {
+                        return Ok(());
+                    }
+
+                    self.backup_config_map(
+                        config_maps_api,
+                        config_map_namespace,
+                        config_map_name,
+                        &original_config_map,
+                    )
+                    .await
+                    .map_err(UpdateAttemptError::Failed)?;
+
+                    let post_params = self.post_params();
+                    retry_on_transient_error(
+                        &format!("replace config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.replace(config_map_name, &post_params, &config_map),
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| {
+                        UpdateAttemptError::from_replace_error(
+                            e,
+                            config_map_namespace,
+                            config_map_name,
+                            attempt,
+                        )
+                    })
+                }
+            },
+        )
+        .await?;
+
+        Ok(backup_name)
+    }
+
+    async fn get_config_map_backups(
+        &self,
+        config_map_namespace: &str,
+    ) -> Result<BTreeMap<String, String>, KubernetesError> {
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+
+        config_maps_api
+            .get(&self.options.backup_config_map_name)
+            .await
+            .map(|config_map| config_map.data.unwrap_or_default())
+            .map_err(|e| KubernetesError::ConfigMapNotFound {
+                config_map_name: Arc::from(self.options.backup_config_map_name.as_str()),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from(e.to_string()),
+            })
+    }
+}
+
+impl ClusterPreflight for KubernetesService {
+    async fn server_version(&self) -> Result<String, KubernetesError> {
+        retry_on_transient_error("get API server version", || self.client.apiserver_version())
+            .await
+            .map(|info| info.git_version)
+            .map_err(|e| match e {
+                RequestError::Unreachable(e) => e,
+                RequestError::Failed(e) => KubernetesError::ClusterUnreachable {
+                    raw_message: Arc::from(e.to_string()),
+                },
+            })
+    }
+
+    async fn read_aws_auth(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<Option<AwsAuth>, KubernetesError> {
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+
+        retry_on_transient_error(
+            &format!("get config map `{config_map_namespace}/{config_map_name}`"),
+            || config_maps_api.get_opt(config_map_name),
+        )
+        .await
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::ConfigMapNotFound {
+                config_map_name: Arc::from(config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })?
+        .map(|config_map| {
+            Self::parse_aws_auth_with_error_snippet(
+                &config_map,
+                self.options.error_snippet_max_length,
+            )
+        })
+        .transpose()
+    }
+
+    async fn can_i(
+        &self,
+        verb: &str,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<bool, KubernetesError> {
+        let reviews_api: Api<SelfSubjectAccessReview> = Api::all(self.client.clone());
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    verb: Some(verb.to_string()),
+                    resource: Some("configmaps".to_string()),
+                    namespace: Some(config_map_namespace.to_string()),
+                    name: Some(config_map_name.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let post_params = PostParams::default();
+
+        retry_on_transient_error(
+            &format!(
+                "review `{verb}` access to config map `{config_map_namespace}/{config_map_name}`"
+            ),
+            || reviews_api.create(&post_params, &review),
+        )
+        .await
+        .map(|review| review.status.is_some_and(|status| status.allowed))
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::AccessCannotBeReviewed {
+                verb: Arc::from(verb),
+                config_map_name: Arc::from(config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })
+    }
+}
+
+impl KubernetesService {
+    /// Returns users and roles of config map `config_map_namespace/config_map_name`.
+    pub async fn get_aws_auth(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<AwsAuth, KubernetesError> {
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+
+        let config_map = retry_on_transient_error(
+            &format!("get config map `{config_map_namespace}/{config_map_name}`"),
+            || config_maps_api.get(config_map_name),
+        )
+        .await
+        .map_err(|e| match e {
+            RequestError::Unreachable(e) => e,
+            RequestError::Failed(e) => KubernetesError::ConfigMapNotFound {
+                config_map_name: Arc::from(config_map_name),
+                config_map_namespace: Arc::from(config_map_namespace),
+                raw_message: Arc::from(e.to_string()),
+            },
+        })?;
+
+        Self::parse_aws_auth_with_error_snippet(&config_map, self.options.error_snippet_max_length)
+    }
+
+    /// Removes users and roles whose ARN is one of `arns` from the config map, whoever synced them.
+    /// Previous content is backed up, returns removed entries, nothing being written in dry run.
+    pub async fn remove_aws_auth_entries(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        arns: &[IamArn],
+    ) -> Result<AwsAuthDiff, KubernetesError> {
+        let arns: HashSet<String> = arns
+            .iter()
+            .map(|arn| arn.to_string().to_lowercase())
+            .collect();
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+
+        retry_on_conflict(
+            config_map_namespace,
+            config_map_name,
+            CONFIG_MAP_UPDATE_MAX_ATTEMPTS,
+            |attempt| {
+                let config_maps_api = &config_maps_api;
+                let arns = &arns;
+
+                async move {
+                    let mut config_map = retry_on_transient_error(
+                        &format!("get config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.get(config_map_name),
+                    )
+                    .await
+                    .map_err(|e| match e {
+                        RequestError::Unreachable(e) => UpdateAttemptError::Failed(e),
+                        RequestError::Failed(e) => {
+                            UpdateAttemptError::Failed(KubernetesError::ConfigMapNotFound {
+                                config_map_name: Arc::from(config_map_name),
+                                config_map_namespace: Arc::from(config_map_namespace),
+                                raw_message: Arc::from(e.to_string()),
+                            })
+                        }
+                    })?;
+                    let original_config_map = config_map.clone();
+                    let existing_aws_auth = Self::parse_aws_auth_with_error_snippet(
+                        &config_map,
+                        self.options.error_snippet_max_length,
+                    )
+                    .map_err(UpdateAttemptError::Failed)?;
+                    let aws_auth = AwsAuth {
+                        users: existing_aws_auth
+                            .users
+                            .iter()
+                            .filter(|u| !arns.contains(&u.iam_arn.to_string().to_lowercase()))
+                            .cloned()
+                            .collect(),
+                        roles: existing_aws_auth
+                            .roles
+                            .iter()
+                            .filter(|r| !arns.contains(&r.iam_role_arn.to_string().to_lowercase()))
+                            .cloned()
+                            .collect(),
+                    };
+                    let diff = existing_aws_auth.diff(&aws_auth, &self.options.synced_by);
+                    if diff.is_empty() || self.options.dry_run {
+                        return Ok(diff);
+                    }
+
+                    let config_map_data = config_map.data.get_or_insert_with(BTreeMap::new);
+                    config_map_data.insert(
+                        "mapUsers".to_string(),
+                        Self::generate_users_config_map_yaml_string(aws_auth.users.clone())
+                            .map_err(UpdateAttemptError::Failed)?,
+                    );
+                    config_map_data.insert(
+                        "mapRoles".to_string(),
+                        Self::generate_roles_config_map_yaml_string(aws_auth.roles.clone())
+                            .map_err(UpdateAttemptError::Failed)?,
+                    );
+                    Self::validate_generated_content(&config_map, &aws_auth)
+                        .map_err(UpdateAttemptError::Failed)?;
+
+                    self.ensure_leadership(config_map_namespace, config_map_name)
+                        .map_err(UpdateAttemptError::Failed)?;
+
+                    self.backup_config_map(
+                        config_maps_api,
+                        config_map_namespace,
+                        config_map_name,
+                        &original_config_map,
+                    )
+                    .await
+                    .map_err(UpdateAttemptError::Failed)?;
+
+                    self.annotate_config_map(&mut config_map, &aws_auth);
+
+                    let post_params = self.post_params();
+                    retry_on_transient_error(
+                        &format!("replace config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.replace(config_map_name, &post_params, &config_map),
+                    )
+                    .await
+                    .map(|_| diff)
+                    .map_err(|e| {
+                        UpdateAttemptError::from_replace_error(
+                            e,
+                            config_map_namespace,
+                            config_map_name,
+                            attempt,
+                        )
+                    })
+                }
+            },
+        )
+        .await
+    }
+
+    /// Removes all users and roles synced by this instance from the config map, protected ones excepted. Other
+    /// entries are left byte for byte as they are, nothing being written if there is nothing to remove.
+    /// Previous content is backed up, returns removed entries, nothing being written in dry run.
+    pub async fn remove_synced_entries(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+    ) -> Result<AwsAuthDiff, KubernetesError> {
+        let synced_by = self.options.synced_by.to_string();
+        let protected_arns: HashSet<String> = self
+            .options
+            .protected_arns
+            .iter()
+            .map(|arn| arn.to_string().to_lowercase())
+            .collect();
+        let removed = |arn: &IamArn, entry_synced_by: &Option<SyncedBy>| {
+            entry_synced_by.as_ref() == Some(&self.options.synced_by)
+                && !protected_arns.contains(&arn.to_string().to_lowercase())
+        };
+        let removed_entry = |arn_key: &str, entry: &serde_yaml::Value| {
+            let field = |key: &str| entry.get(key).and_then(serde_yaml::Value::as_str);
+            field("syncedBy") == Some(synced_by.as_str())
+                && !protected_arns
+                    .contains(&field(arn_key).unwrap_or_default().trim().to_lowercase())
+        };
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+
+        retry_on_conflict(
+            config_map_namespace,
+            config_map_name,
+            CONFIG_MAP_UPDATE_MAX_ATTEMPTS,
+            |attempt| {
+                let config_maps_api = &config_maps_api;
+
+                async move {
+                    let mut config_map = retry_on_transient_error(
+                        &format!("get config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.get(config_map_name),
+                    )
+                    .await
+                    .map_err(|e| match e {
+                        RequestError::Unreachable(e) => UpdateAttemptError::Failed(e),
+                        RequestError::Failed(e) => {
+                            UpdateAttemptError::Failed(KubernetesError::ConfigMapNotFound {
+                                config_map_name: Arc::from(config_map_name),
+                                config_map_namespace: Arc::from(config_map_namespace),
+                                raw_message: Arc::from(e.to_string()),
+                            })
+                        }
+                    })?;
+                    let original_config_map = config_map.clone();
+                    let existing_aws_auth =
+                        Self::parse_aws_auth_with_error_snippet(&config_map, self.options.error_snippet_max_length).map_err(UpdateAttemptError::Failed)?;
+                    let aws_auth = AwsAuth {
+                        users: existing_aws_auth
+                            .users
+                            .iter()
+                            .filter(|u| !removed(&u.iam_arn, &u.synced_by))
+                            .cloned()
+                            .collect(),
+                        roles: existing_aws_auth
+                            .roles
+                            .iter()
+                            .filter(|r| !removed(&r.iam_role_arn, &r.synced_by))
+                            .cloned()
+                            .collect(),
+                    };
+                    let diff = existing_aws_auth.diff(&aws_auth, &self.options.synced_by);
+                    if diff.is_empty() || self.options.dry_run {
+                        return Ok(diff);
+                    }
+
+                    let config_map_data = config_map.data.get_or_insert_with(BTreeMap::new);
+                    for (key, arn_key) in [("mapUsers", "userarn"), ("mapRoles", "rolearn")] {
+                        let content = config_map_data.get(key).cloned().unwrap_or_default();
+                        let content = match Self::remove_sequence_entries(&content, |entry| {
+                            removed_entry(arn_key, entry)
+                        }) {
+                            Some(content) => content,
+                            None => {
+                                warn!("`{key}` entries cannot be removed as written, rewriting remaining ones");
+                                match key {
+                                    "mapUsers" => Self::generate_users_config_map_yaml_string(
+                                        aws_auth.users.clone(),
+                                    ),
+                                    _ => Self::generate_roles_config_map_yaml_string(
+                                        aws_auth.roles.clone(),
+                                    ),
+                                }
+                                .map_err(UpdateAttemptError::Failed)?
+                            }
+                        };
+                        config_map_data.insert(key.to_string(), content);
+                    }
+                    Self::validate_generated_content(&config_map, &aws_auth)
+                        .map_err(UpdateAttemptError::Failed)?;
+
+                    self.ensure_leadership(config_map_namespace, config_map_name)
+                        .map_err(UpdateAttemptError::Failed)?;
+
+                    self.backup_config_map(
+                        config_maps_api,
+                        config_map_namespace,
+                        config_map_name,
+                        &original_config_map,
+                    )
+                    .await
+                    .map_err(UpdateAttemptError::Failed)?;
+
+                    self.annotate_config_map(&mut config_map, &aws_auth);
+
+                    let post_params = self.post_params();
+                    retry_on_transient_error(
+                        &format!("replace config map `{config_map_namespace}/{config_map_name}`"),
+                        || config_maps_api.replace(config_map_name, &post_params, &config_map),
+                    )
+                    .await
+                    .map(|_| diff)
+                    .map_err(|e| {
+                        UpdateAttemptError::from_replace_error(
+                            e,
+                            config_map_namespace,
+                            config_map_name,
+                            attempt,
+                        )
+                    })
+                }
+            },
+        )
+        .await
+    }
+}
+
+enum UpdateAttemptError {
+    /// Config map has been modified since it has been read, update can be retried.
+    Conflict {
+        raw_message: Arc<str>,
+    },
+    Failed(KubernetesError),
+}
+
+impl UpdateAttemptError {
+    fn from_replace_error(
+        e: RequestError,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        attempt: u32,
+    ) -> UpdateAttemptError {
+        match e {
+            RequestError::Unreachable(e) => UpdateAttemptError::Failed(e),
+            RequestError::Failed(kube::Error::Api(e)) if e.code == 409 => {
+                UpdateAttemptError::Conflict {
+                    raw_message: Arc::from(e.to_string()),
+                }
+            }
+            RequestError::Failed(e) => {
+                UpdateAttemptError::Failed(KubernetesError::ConfigMapCannotBePatched {
+                    config_map_name: Arc::from(config_map_name),
+                    config_map_namespace: Arc::from(config_map_namespace),
+                    attempts: attempt,
+                    raw_message: Arc::from(e.to_string()),
+                })
+            }
+        }
+    }
+}
+
+/// Runs `update` until it succeeds, fails for another reason than a conflict or `max_attempts` is reached.
+async fn retry_on_conflict<T, F, Fut>(
+    config_map_namespace: &str,
+    config_map_name: &str,
+    max_attempts: u32,
+    mut update: F,
+) -> Result<T, KubernetesError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, UpdateAttemptError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match update(attempt).await {
+            Ok(res) => return Ok(res),
+            Err(UpdateAttemptError::Failed(e)) => return Err(e),
+            Err(UpdateAttemptError::Conflict { raw_message }) if attempt >= max_attempts => {
+                return Err(KubernetesError::ConfigMapCannotBePatched {
+                    config_map_name: Arc::from(config_map_name),
+                    config_map_namespace: Arc::from(config_map_namespace),
+                    attempts: attempt,
+                    raw_message,
+                })
+            }
+            Err(UpdateAttemptError::Conflict { raw_message }) => {
+                warn!("Config map `{config_map_namespace}/{config_map_name}` has been modified concurrently (attempt {attempt}/{max_attempts}), retrying with fresh content: {raw_message}");
+                attempt += 1;
+            }
+        }
+    }
+}
+
+enum RequestError {
+    /// Request kept failing with transient errors, or timed out.
+    Unreachable(KubernetesError),
+    Failed(kube::Error),
+}
+
+/// Whether `e` may not happen anymore if the request is sent again, e.q: API server restarting or overloaded.
+fn is_transient_error(e: &kube::Error) -> bool {
+    match e {
+        kube::Error::Api(e) => e.code >= 500 || e.code == 429,
+        // connection errors and timeouts
+        kube::Error::HyperError(_) | kube::Error::Service(_) => true,
+        _ => false,
+    }
+}
+
+/// Sends `request` until it succeeds, fails with a non transient error or `TRANSIENT_ERROR_MAX_ATTEMPTS` is reached.
+/// Conflicts are not transient errors, they have to be handled by callers with fresh content.
+async fn retry_on_transient_error<T, F, Fut>(
+    description: &str,
+    mut request: F,
+) -> Result<T, RequestError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, kube::Error>>,
+{
+    let started_at = Instant::now();
+    let mut backoff = TRANSIENT_ERROR_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Ok(res) => return Ok(res),
+            Err(e) if !is_transient_error(&e) => return Err(RequestError::Failed(e)),
+            Err(e) if attempt >= TRANSIENT_ERROR_MAX_ATTEMPTS => {
+                return Err(RequestError::Unreachable(
+                    KubernetesError::ClusterUnreachable {
+                        raw_message: Arc::from(format!(
+                            "cannot {description} after {attempt} attempts in {:.1?}: {e}",
+                            started_at.elapsed()
+                        )),
+                    },
+                ))
+            }
+            Err(e) => {
+                warn!("Cannot {description} (attempt {attempt}/{TRANSIENT_ERROR_MAX_ATTEMPTS}), retrying in {backoff:?}: {e}");
+                time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::config::ConfigurationError;
+    use crate::kubernetes::aws_auth::{AwsAuth, RemovalGuard};
+    use crate::kubernetes::backup::{self, ConfigMapBackup};
+    use crate::kubernetes::leadership::Leadership;
+    use crate::kubernetes::{
+        is_transient_error, retry_on_conflict, AuthBackend, AwsAccountId, ClusterAccess,
+        ConfigMapOptions, ConfigMapUpdateStatus, EntryErrorLocation, IamArn, IamUserName,
+        KubernetesError, KubernetesGroupName, KubernetesRole, KubernetesService, KubernetesUser,
+        MapRoleConfig, MapUserConfig, SyncedBy, UpdateAttemptError,
+        DEFAULT_ERROR_SNIPPET_MAX_LENGTH,
+    };
+    use http::{Method, Request, Response};
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use kube::client::Body;
+    use kube::Client;
+    use proptest::prelude::*;
+    use std::collections::{BTreeMap, BTreeSet, HashSet};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn kube_config_test() {
+        // setup:
+        let kubeconfig_path = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-kubeconfig-{}",
+            std::process::id()
+        ));
+        std::fs::write(
+            &kubeconfig_path,
+            "apiVersion: v1\n\
+             kind: Config\n\
+             current-context: dev\n\
+             clusters:\n\
+             - name: dev\n  cluster:\n    server: https://dev.example.com\n\
+             - name: prod\n  cluster:\n    server: https://prod.example.com\n\
+             contexts:\n\
+             - name: dev\n  context:\n    cluster: dev\n    user: admin\n\
+             - name: prod\n  context:\n    cluster: prod\n    user: admin\n\
+             users:\n\
+             - name: admin\n  user:\n    token: whatever\n",
+        )
+        .expect("kubeconfig should be written");
+        let cluster_access = |path: &std::path::Path, context: Option<&str>| ClusterAccess {
+            kubeconfig_path: Some(path.to_path_buf()),
+            context: context.map(|c| c.to_string()),
+            request_timeout: None,
+        };
+
+        // execute:
+        let current_context_config =
+            KubernetesService::kube_config(&cluster_access(&kubeconfig_path, None)).await;
+        let selected_context_config =
+            KubernetesService::kube_config(&cluster_access(&kubeconfig_path, Some("prod"))).await;
+        let missing_context_config =
+            KubernetesService::kube_config(&cluster_access(&kubeconfig_path, Some("staging")))
+                .await;
+        let missing_file_config = KubernetesService::kube_config(&cluster_access(
+            std::path::Path::new("/non/existing/kubeconfig"),
+            None,
+        ))
+        .await;
+        std::fs::remove_file(&kubeconfig_path).expect("kubeconfig should be removed");
+
+        // verify:
+        assert_eq!(
+            "https://dev.example.com/",
+            current_context_config
+                .expect("config should be built")
+                .cluster_url
+                .to_string()
+        );
+        assert_eq!(
+            "https://prod.example.com/",
+            selected_context_config
+                .expect("config should be built")
+                .cluster_url
+                .to_string()
+        );
+        assert_eq!(
+            Some(KubernetesError::KubeContextNotFound {
+                context: Arc::from("staging"),
+                kubeconfig_path: Arc::from(kubeconfig_path.display().to_string().as_str()),
+            }),
+            missing_context_config.err()
+        );
+        assert!(matches!(
+            missing_file_config,
+            Err(KubernetesError::KubeconfigCannotBeRead { kubeconfig_path, .. }) if kubeconfig_path.as_ref() == "/non/existing/kubeconfig"
+        ));
+    }
+
+    #[test]
+    fn kubernetes_group_name_parse_test() {
+        struct TestCase<'a> {
+            input: String,
+            expected_valid: bool,
+            expected_unknown_system_group: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "system:masters".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 1 - well-known system group",
+            },
+            TestCase {
+                input: "system:bootstrappers".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 2 - well-known system group",
+            },
+            TestCase {
+                input: "system:nodes".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 3 - well-known system group",
+            },
+            TestCase {
+                input: "system:node-proxier".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 4 - well-known system group",
+            },
+            TestCase {
+                input: "system:master".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: true,
+                _description: "case 5 - system group typo",
+            },
+            TestCase {
+                input: "system:".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: true,
+                _description: "case 6 - system prefix only",
+            },
+            TestCase {
+                input: " devops ".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 7 - spaces around trimmed",
+            },
+            TestCase {
+                input: "oidc:team@example.com".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 8 - OIDC prefixed group",
+            },
+            TestCase {
+                input: "legacy->admins".to_string(),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 9 - group containing the mapping delimiter",
+            },
+            TestCase {
+                input: "a".repeat(253),
+                expected_valid: true,
+                expected_unknown_system_group: false,
+                _description: "case 10 - longest name",
+            },
+            TestCase {
+                input: "".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 11 - empty",
+            },
+            TestCase {
+                input: "dev ops".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 12 - inner space",
+            },
+            TestCase {
+                input: "dev\tops".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 13 - tab",
+            },
+            TestCase {
+                input: "\"devops\"".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 14 - quotes",
+            },
+            TestCase {
+                input: "dev,ops".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 15 - comma",
+            },
+            TestCase {
+                input: "dev\\ops".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 16 - backslash",
+            },
+            TestCase {
+                input: "équipe".to_string(),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 17 - non ASCII",
+            },
+            TestCase {
+                input: "a".repeat(254),
+                expected_valid: false,
+                expected_unknown_system_group: false,
+                _description: "case 18 - too long",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = KubernetesGroupName::parse(&tc.input);
+
+            // verify:
+            match tc.expected_valid {
+                true => assert_eq!(
+                    Ok(KubernetesGroupName::new(tc.input.trim())),
+                    res,
+                    "{}",
+                    tc._description
+                ),
+                false => assert_eq!(
+                    Err(ConfigurationError::InvalidKubernetesGroupName {
+                        raw_group_name: Arc::from(tc.input.as_str()),
+                    }),
+                    res,
+                    "{}",
+                    tc._description
+                ),
+            }
+            if let Ok(group) = res {
+                assert_eq!(
+                    tc.expected_unknown_system_group,
+                    group.is_unknown_system_group(),
+                    "{}",
+                    tc._description
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn iam_arn_parse_test() {
+        struct TestCase<'a> {
+            input: &'a str,
+            resource_type: &'a str,
+            expected: Result<&'a str, ()>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "arn:aws:iam::123456789012:role/ci",
+                resource_type: "role",
+                expected: Ok("arn:aws:iam::123456789012:role/ci"),
+                _description: "case 1 - role in aws partition",
+            },
+            TestCase {
+                input: "arn:aws-cn:iam::123456789012:user/ops/alice@example.com",
+                resource_type: "user",
+                expected: Ok("arn:aws-cn:iam::123456789012:user/ops/alice@example.com"),
+                _description: "case 2 - user with a path in aws-cn partition",
+            },
+            TestCase {
+                input: " arn:aws-us-gov:iam::123456789012:role/KarpenterNodeRole-prod ",
+                resource_type: "role",
+                expected: Ok("arn:aws-us-gov:iam::123456789012:role/KarpenterNodeRole-prod"),
+                _description: "case 3 - aws-us-gov partition, surrounding spaces trimmed",
+            },
+            TestCase {
+                input: "arn:aws-iso-b:iam::123456789012:role/nodes",
+                resource_type: "role",
+                expected: Ok("arn:aws-iso-b:iam::123456789012:role/nodes"),
+                _description: "case 4 - aws-iso-b partition",
+            },
+            TestCase {
+                input: "banana",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 5 - not an ARN",
+            },
+            TestCase {
+                input: "arn::iam::123456789012:role/ci",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 6 - empty partition",
+            },
+            TestCase {
+                input: "arn:gcp:iam::123456789012:role/ci",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 7 - unknown partition",
+            },
+            TestCase {
+                input: "arn:aws:iam::12345:role/ci",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 8 - account ID not 12 digits",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:user/ci",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 9 - user ARN where a role one is expected",
+            },
+            TestCase {
+                input: "arn:aws:sts::123456789012:assumed-role/ci/session",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 10 - STS ARN",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:role/",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 11 - empty name",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:role/ci admin",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 12 - name with a space",
+            },
+            TestCase {
+                input: "arn:aws:iam::123456789012:role/ops/",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 13 - path without name",
+            },
+            TestCase {
+                input: "arn:aws-cn:iam::123456789012:instance-profile/eks/karpenter",
+                resource_type: "instance-profile",
+                expected: Ok("arn:aws-cn:iam::123456789012:instance-profile/eks/karpenter"),
+                _description: "case 14 - instance profile with a path in aws-cn partition",
+            },
+            TestCase {
+                input: "arn:aws:iam:us-east-1:123456789012:role/ci",
+                resource_type: "role",
+                expected: Err(()),
+                _description: "case 15 - region set",
+            },
+            TestCase {
+                input: "arn:aws:eks::aws:cluster-access-policy/AmazonEKSClusterAdminPolicy",
+                resource_type: "cluster-access-policy",
+                expected: Err(()),
+                _description: "case 16 - not an IAM ARN",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = IamArn::parse(tc.input, tc.resource_type);
+
+            // verify:
+            assert_eq!(
+                tc.expected.map(IamArn::new).map_err(|_| {
+                    ConfigurationError::InvalidArn {
+                        raw_arn: Arc::from(tc.input.trim()),
+                        resource_type: Arc::from(tc.resource_type),
+                    }
+                }),
+                res,
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn kubernetes_user_eq_test() {
+        // setup:
+        let user = |name: &str, arn: &str, groups: Vec<&str>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(arn),
+                BTreeSet::from_iter(groups.into_iter().map(KubernetesGroupName::new)),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+
+        // execute & verify:
+        // names case is ignored, as when hashing
+        assert_eq!(
+            user("Alice", "arn:aws:iam::123:user/Alice", vec!["view"]),
+            user("alice", "arn:aws:iam::123:user/alice", vec!["view"])
+        );
+        assert_eq!(
+            1,
+            HashSet::<KubernetesUser>::from_iter(vec![
+                user("Alice", "arn:aws:iam::123:user/Alice", vec!["view"]),
+                user("alice", "arn:aws:iam::123:user/Alice", vec!["view"]),
+            ])
+            .len()
+        );
+        assert_ne!(
+            user("Alice", "arn:aws:iam::123:user/Alice", vec!["view"]),
+            user("Alice", "arn:aws:iam::123:user/Alice", vec!["edit"])
+        );
+        assert_ne!(
+            user("alice", "arn:aws:iam::123:user/alice", vec!["view"]),
+            user("alice", "arn:aws:iam::123:user/bob", vec!["view"])
+        );
+    }
+
+    #[test]
+    fn synced_by_serde_test() {
+        // setup:
+        struct TestCase<'a> {
+            raw: &'a str,
+            expected: SyncedBy,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                raw: "iam-eks-user-mapper",
+                expected: SyncedBy::IamEksUserMapper,
+            },
+            TestCase {
+                raw: "iam-eks-user-mapper-ci",
+                expected: SyncedBy::Other("iam-eks-user-mapper-ci".to_string()),
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let deserialized = serde_yaml::from_str::<SyncedBy>(tc.raw);
+            let serialized = serde_yaml::to_string(&tc.expected);
+
+            // verify:
+            assert_eq!(Some(tc.expected), deserialized.ok());
+            assert_eq!(Some(format!("{}\n", tc.raw)), serialized.ok());
+        }
+    }
+
+    #[test]
+    fn generate_users_config_map_yaml_string_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: BTreeSet<KubernetesUser>,
+            expected_output: Result<String, KubernetesError>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: BTreeSet::from_iter(vec![
+                    KubernetesUser {
+                        iam_user_name: IamUserName::new("user_1"),
+                        iam_arn: IamArn::new("arn:test:user_1"),
+                        roles: BTreeSet::from_iter(vec![
+                            KubernetesGroupName::new("group_1"),
+                            KubernetesGroupName::new("group_2"),
+                        ]),
+                        synced_by: None,
+                        synced_at: None,
+                        extra: BTreeMap::new(),
+                    },
+                    KubernetesUser {
+                        iam_user_name: IamUserName::new("user_2"),
+                        iam_arn: IamArn::new("arn:test:user_2"),
+                        roles: BTreeSet::from_iter(vec![
+                            KubernetesGroupName::new("group_2"),
+                            KubernetesGroupName::new("group_3"),
+                        ]),
+                        synced_by: None,
+                        synced_at: None,
+                        extra: BTreeMap::new(),
+                    },
+                    KubernetesUser {
+                        iam_user_name: IamUserName::new("user_3"),
+                        iam_arn: IamArn::new("arn:test:user_3"),
+                        roles: BTreeSet::from_iter(vec![
+                            KubernetesGroupName::new("group_3"),
+                            KubernetesGroupName::new("group_4"),
+                        ]),
+                        synced_by: Some(SyncedBy::IamEksUserMapper),
+                        synced_at: None,
+                        extra: BTreeMap::new(),
+                    },
+                ]),
+                expected_output: Ok(r"
+- userarn: arn:test:user_1
+  username: user_1
+  groups:
+    - group_1
+    - group_2
+- userarn: arn:test:user_2
+  username: user_2
+  groups:
+    - group_2
+    - group_3
+- userarn: arn:test:user_3
+  username: user_3
+  groups:
+    - group_3
+    - group_4
+  syncedBy: iam-eks-user-mapper"
+                    .trim_start()
+                    .to_string()),
+
+                _description: "case 1 - nominal case",
+            },
+            TestCase {
+                input: BTreeSet::from_iter(vec![KubernetesUser {
+                    iam_user_name: IamUserName::new("user_1"),
+                    iam_arn: IamArn::new("arn:test:user_1"),
+                    roles: BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    synced_by: None,
+                    synced_at: None,
+                    extra: BTreeMap::new(),
+                }]),
+                expected_output: Ok(r"
+- userarn: arn:test:user_1
+  username: user_1
+  groups:
+    - group_1
+    - group_2"
+                    .trim_start()
+                    .to_string()),
+
+                _description: "case 2 - one user",
+            },
+            TestCase {
+                input: BTreeSet::from_iter(vec![KubernetesUser {
+                    iam_user_name: IamUserName::new("user_1"),
+                    iam_arn: IamArn::new("arn:test:user_1"),
+                    roles: BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_1"),
+                        KubernetesGroupName::new("group_2"),
+                    ]),
+                    synced_by: Some(SyncedBy::Other("a-tool-we-do-not-know".to_string())),
+                    synced_at: None,
+                    extra: BTreeMap::new(),
+                }]),
+                expected_output: Ok(r"
+- userarn: arn:test:user_1
+  username: user_1
+  groups:
+    - group_1
+    - group_2
+  syncedBy: a-tool-we-do-not-know"
+                    .trim_start()
+                    .to_string()),
+
+                _description: "case 3 - one user synced by unknown provider",
+            },
+            TestCase {
+                input: BTreeSet::from_iter(vec![]),
+                expected_output: Ok(r"".to_string()),
+                _description: "case 4 - no users",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = KubernetesService::generate_users_config_map_yaml_string(tc.input);
+
+            // verify:
+            match tc.expected_output {
+                Ok(expected_yaml) => {
+                    assert!(result.is_ok());
+
+                    // YAML serializer is not preserving orders
+                    let parsed_yaml_expected_result: Result<HashSet<MapUserConfig>, _> =
+                        serde_yaml::from_str(&expected_yaml);
+                    assert!(parsed_yaml_expected_result.is_ok());
+
+                    let result_yaml_string = result.unwrap_or_default();
+                    let parsed_yaml_result: Result<HashSet<MapUserConfig>, _> =
+                        serde_yaml::from_str(&result_yaml_string);
+                    assert!(parsed_yaml_result.is_ok());
+
+                    assert_eq!(
+                        parsed_yaml_expected_result.unwrap_or_default(),
+                        parsed_yaml_result.unwrap_or_default()
+                    );
+                }
+                Err(e) => {
+                    assert!(result.is_err());
+                    assert_eq!(e, result.unwrap_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generate_roles_config_map_yaml_string_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: BTreeSet<KubernetesRole>,
+            expected_output: Result<String, KubernetesError>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: BTreeSet::from_iter(vec![KubernetesRole {
+                    role_name: Some("role_1".to_string()),
+                    user_name: None,
+                    iam_role_arn: IamArn::new("arn:test:role_1"),
+                    groups: BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_3"),
+                    ]),
+                    synced_by: None,
+                    synced_at: None,
+                    extra: BTreeMap::new(),
+                }]),
+                expected_output: Ok(r"
+- rolearn: arn:test:role_1
+  rolename: role_1
+  groups:
+    - group_2
+    - group_3"
+                    .trim_start()
+                    .to_string()),
+
+                _description: "case 1 - nominal case",
+            },
+            TestCase {
+                input: BTreeSet::from_iter(vec![KubernetesRole {
+                    role_name: Some("role_1".to_string()),
+                    user_name: None,
+                    iam_role_arn: IamArn::new("arn:test:role_1"),
+                    groups: BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_3"),
+                    ]),
+                    synced_by: Some(SyncedBy::IamEksUserMapper),
+                    synced_at: None,
+                    extra: BTreeMap::new(),
+                }]),
+                expected_output: Ok(r"
+- rolearn: arn:test:role_1
+  rolename: role_1
+  groups:
+    - group_2
+    - group_3
+  syncedBy: iam-eks-user-mapper"
+                    .trim_start()
+                    .to_string()),
+
+                _description: "case 2 - role synced by iam-user-mapper",
+            },
+            TestCase {
+                input: BTreeSet::from_iter(vec![KubernetesRole {
+                    role_name: Some("role_1".to_string()),
+                    user_name: None,
+                    iam_role_arn: IamArn::new("arn:test:role_1"),
+                    groups: BTreeSet::from_iter(vec![
+                        KubernetesGroupName::new("group_2"),
+                        KubernetesGroupName::new("group_3"),
+                    ]),
+                    synced_by: Some(SyncedBy::Other("some-tool-we-do-not-know".to_string())),
+                    synced_at: None,
+                    extra: BTreeMap::new(),
+                }]),
+                expected_output: Ok(r"
+- rolearn: arn:test:role_1
+  rolename: role_1
+  groups:
+    - group_2
+    - group_3
+  syncedBy: some-tool-we-do-not-know"
+                    .trim_start()
+                    .to_string()),
+
+                _description: "case 3 - role synced by unknown",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = KubernetesService::generate_roles_config_map_yaml_string(tc.input);
+
+            // verify:
+            match tc.expected_output {
+                Ok(expected_yaml) => {
+                    assert!(result.is_ok());
+
+                    // YAML serializer is not preserving orders
+                    let parsed_yaml_expected_result: Result<HashSet<MapRoleConfig>, _> =
+                        serde_yaml::from_str(&expected_yaml);
+                    assert!(parsed_yaml_expected_result.is_ok());
+
+                    let result_yaml_string = result.unwrap_or_default();
+                    let parsed_yaml_result: Result<HashSet<MapRoleConfig>, _> =
+                        serde_yaml::from_str(&result_yaml_string);
+                    assert!(parsed_yaml_result.is_ok());
+
+                    assert_eq!(
+                        parsed_yaml_expected_result.unwrap_or_default(),
+                        parsed_yaml_result.unwrap_or_default()
+                    );
+                }
+                Err(e) => {
+                    assert!(result.is_err());
+                    assert_eq!(e, result.unwrap_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generate_config_map_yaml_string_is_stable_test() {
+        // setup:
+        let users = vec![
+            KubernetesUser::new(
+                IamUserName::new("user_2"),
+                IamArn::new("arn:test:user_2"),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("group_2"),
+                    KubernetesGroupName::new("group_1"),
+                ]),
+                None,
+            ),
+            KubernetesUser::new(
+                IamUserName::new("user_1"),
+                IamArn::new("arn:test:user_1"),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("group_3"),
+                    KubernetesGroupName::new("group_1"),
+                ]),
+                Some(SyncedBy::IamEksUserMapper),
+            ),
+        ];
+        let roles = vec![
+            KubernetesRole::new(
+                IamArn::new("arn:test:role_2"),
+                None,
+                Some("node".to_string()),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("system:nodes"),
+                    KubernetesGroupName::new("system:bootstrappers"),
+                ]),
+                None,
+            ),
+            KubernetesRole::new(
+                IamArn::new("arn:test:role_1"),
+                Some("role_1".to_string()),
+                None,
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("system:masters")]),
+                None,
+            ),
+        ];
+
+        // execute:
+        let users_yaml = KubernetesService::generate_users_config_map_yaml_string(
+            BTreeSet::from_iter(users.clone()),
+        );
+        let users_yaml_reversed = KubernetesService::generate_users_config_map_yaml_string(
+            BTreeSet::from_iter(users.into_iter().rev()),
+        );
+        let roles_yaml = KubernetesService::generate_roles_config_map_yaml_string(
+            BTreeSet::from_iter(roles.clone()),
+        );
+        let roles_yaml_reversed = KubernetesService::generate_roles_config_map_yaml_string(
+            BTreeSet::from_iter(roles.into_iter().rev()),
+        );
+
+        // verify:
+        assert_eq!(
+            r"
+- userarn: arn:test:user_1
+  username: user_1
+  groups:
+  - group_1
+  - group_3
+  syncedBy: iam-eks-user-mapper
+- userarn: arn:test:user_2
+  username: user_2
+  groups:
+  - group_1
+  - group_2
+"
+            .trim_start(),
+            users_yaml.as_deref().expect("users should be serializable")
+        );
+        assert_eq!(users_yaml, users_yaml_reversed);
+        assert_eq!(
+            r"
+- rolearn: arn:test:role_1
+  rolename: role_1
+  groups:
+  - system:masters
+- rolearn: arn:test:role_2
+  username: node
+  groups:
+  - system:bootstrappers
+  - system:nodes
+"
+            .trim_start(),
+            roles_yaml.as_deref().expect("roles should be serializable")
+        );
+        assert_eq!(roles_yaml, roles_yaml_reversed);
+    }
+
+    #[test]
+    fn generate_config_map_yaml_string_snapshot_test() {
+        // setup:
+        let synced_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        let mut user = KubernetesUser::new(
+            IamUserName::new("yes"),
+            IamArn::new("arn:aws:iam::123456789012:user/ops/yes"),
+            BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("system:masters"),
+                KubernetesGroupName::new("on"),
+                KubernetesGroupName::new("1_000"),
+                KubernetesGroupName::new("- admins"),
+                KubernetesGroupName::new("équipe"),
+            ]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        user.synced_at = Some(synced_at);
+        user.extra = BTreeMap::from([(
+            "email".to_string(),
+            serde_yaml::Value::String("yes@example.com".to_string()),
+        )]);
+        let mut role = KubernetesRole::new(
+            IamArn::new("arn:aws:iam::123456789012:role/no"),
+            Some("no".to_string()),
+            Some("2001-12-14".to_string()),
+            BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("system:bootstrappers"),
+                KubernetesGroupName::new("1:20"),
+            ]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        role.synced_at = Some(synced_at);
+
+        // execute:
+        let users_yaml =
+            KubernetesService::generate_users_config_map_yaml_string(BTreeSet::from([user]));
+        let roles_yaml =
+            KubernetesService::generate_roles_config_map_yaml_string(BTreeSet::from([role]));
+
+        // verify:
+        // shape read by aws-iam-authenticator, a YAML 1.1 parser: plain scalars it would type are quoted
+        assert_eq!(
+            r"
+- userarn: arn:aws:iam::123456789012:user/ops/yes
+  username: 'yes'
+  groups:
+  - '- admins'
+  - '1_000'
+  - 'on'
+  - system:masters
+  - équipe
+  syncedBy: iam-eks-user-mapper
+  syncedAt: '2024-01-01T00:00:00Z'
+  email: yes@example.com
+"
+            .trim_start(),
+            users_yaml.as_deref().expect("users should be serializable")
+        );
+        assert_eq!(
+            r"
+- rolearn: arn:aws:iam::123456789012:role/no
+  rolename: 'no'
+  username: '2001-12-14'
+  groups:
+  - '1:20'
+  - system:bootstrappers
+  syncedBy: iam-eks-user-mapper
+  syncedAt: '2024-01-01T00:00:00Z'
+"
+            .trim_start(),
+            roles_yaml.as_deref().expect("roles should be serializable")
+        );
+    }
+
+    /// Strings hard to serialize in YAML, e.q: YAML 1.1 booleans, indicators, quotes, unicode or line breaks.
+    fn yaml_tricky_string() -> impl Strategy<Value = String> {
+        prop_oneof![
+            prop::sample::select(vec![
+                "yes",
+                "No",
+                "ON",
+                "off",
+                "y",
+                "N",
+                "true",
+                "null",
+                "~",
+                "1_000",
+                "0x1F",
+                "017",
+                "1:20",
+                "2001-12-14",
+                "1e3",
+                ".inf",
+                "<<",
+                "=",
+                "-",
+                "- admins",
+                "system:masters",
+            ])
+            .prop_map(str::to_string),
+            "[-:#'\" a-zA-Z0-9_.@/=+,|>&*!%?éà数据]{0,16}",
+            "(- |yes|no|a|: |'|\"|\n| ){0,8}",
+            "\\PC{0,16}",
+        ]
+    }
+
+    fn synced_at() -> impl Strategy<Value = Option<SystemTime>> {
+        prop::option::of(
+            (0u64..4_102_444_800).prop_map(|s| SystemTime::UNIX_EPOCH + Duration::from_secs(s)),
+        )
+    }
+
+    fn extra() -> impl Strategy<Value = BTreeMap<String, serde_yaml::Value>> {
+        prop::collection::btree_map(
+            "x-[a-z]{1,6}",
+            yaml_tricky_string().prop_map(serde_yaml::Value::String),
+            0..2,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn map_user_config_round_trip_test(
+            users in prop::collection::vec(
+                (
+                    yaml_tricky_string(),
+                    yaml_tricky_string(),
+                    prop::collection::hash_set(yaml_tricky_string(), 0..4),
+                    any::<bool>(),
+                    synced_at(),
+                    extra(),
+                ),
+                0..4,
+            )
+        ) {
+            // setup:
+            let users: BTreeSet<KubernetesUser> = users
+                .into_iter()
+                .map(|(arn, name, groups, synced, synced_at, extra)| {
+                    let mut user = KubernetesUser::new(
+                        IamUserName::new(&name),
+                        IamArn::new(&arn),
+                        groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                        synced.then_some(SyncedBy::IamEksUserMapper),
+                    );
+                    user.synced_at = synced_at;
+                    user.extra = extra;
+                    user
+                })
+                .collect();
+            let expected: Vec<MapUserConfig> = users.iter().cloned().map(MapUserConfig::from).collect();
+
+            // execute:
+            let yaml = KubernetesService::generate_users_config_map_yaml_string(users)
+                .expect("users should be serializable");
+            let parsed: Vec<MapUserConfig> =
+                serde_yaml::from_str(&yaml).expect("generated users should be parsable");
+
+            // verify:
+            prop_assert_eq!(expected.len(), parsed.len());
+            prop_assert_eq!(
+                HashSet::<MapUserConfig>::from_iter(expected),
+                HashSet::from_iter(parsed)
+            );
+        }
+
+        #[test]
+        fn map_role_config_round_trip_test(
+            roles in prop::collection::vec(
+                (
+                    yaml_tricky_string(),
+                    prop::option::of(yaml_tricky_string()),
+                    prop::option::of(yaml_tricky_string()),
+                    prop::collection::hash_set(yaml_tricky_string(), 0..4),
+                    any::<bool>(),
+                    synced_at(),
+                    extra(),
+                ),
+                0..4,
+            )
+        ) {
+            // setup:
+            let roles: BTreeSet<KubernetesRole> = roles
+                .into_iter()
+                .map(|(arn, role_name, user_name, groups, synced, synced_at, extra)| {
+                    let mut role = KubernetesRole::new(
+                        IamArn::new(&arn),
+                        role_name,
+                        user_name,
+                        groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                        synced.then_some(SyncedBy::IamEksUserMapper),
+                    );
+                    role.synced_at = synced_at;
+                    role.extra = extra;
+                    role
+                })
+                .collect();
+            let expected: Vec<MapRoleConfig> = roles.iter().cloned().map(MapRoleConfig::from).collect();
+
+            // execute:
+            let yaml = KubernetesService::generate_roles_config_map_yaml_string(roles)
+                .expect("roles should be serializable");
+            let parsed: Vec<MapRoleConfig> =
+                serde_yaml::from_str(&yaml).expect("generated roles should be parsable");
+
+            // verify:
+            prop_assert_eq!(expected.len(), parsed.len());
+            prop_assert_eq!(
+                HashSet::<MapRoleConfig>::from_iter(expected),
+                HashSet::from_iter(parsed)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_on_conflict_test() {
+        // setup:
+        struct TestCase<'a> {
+            conflicts_before_success: u32,
+            expected_output: Result<u32, KubernetesError>,
+            expected_attempts: u32,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                conflicts_before_success: 0,
+                expected_output: Ok(1),
+                expected_attempts: 1,
+                _description: "case 1 - no conflict",
+            },
+            TestCase {
+                conflicts_before_success: 1,
+                expected_output: Ok(2),
+                expected_attempts: 2,
+                _description: "case 2 - one conflict followed by success",
+            },
+            TestCase {
+                conflicts_before_success: 5,
+                expected_output: Err(KubernetesError::ConfigMapCannotBePatched {
+                    config_map_name: Arc::from("aws-auth"),
+                    config_map_namespace: Arc::from("kube-system"),
+                    attempts: 3,
+                    raw_message: Arc::from("conflict"),
+                }),
+                expected_attempts: 3,
+                _description: "case 3 - conflicts exhausting retries",
+            },
+        ];
+
+        for tc in test_cases {
+            let attempts = AtomicU32::new(0);
+
+            // execute:
+            let result = retry_on_conflict("kube-system", "aws-auth", 3, |attempt| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match attempt > tc.conflicts_before_success {
+                        true => Ok(attempt),
+                        false => Err(UpdateAttemptError::Conflict {
+                            raw_message: Arc::from("conflict"),
+                        }),
+                    }
+                }
+            })
+            .await;
+
+            // verify:
+            assert_eq!(tc.expected_output, result);
+            assert_eq!(tc.expected_attempts, attempts.load(Ordering::SeqCst));
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_on_conflict_does_not_retry_other_errors_test() {
+        // setup:
+        let attempts = AtomicU32::new(0);
+
+        // execute:
+        let result: Result<(), KubernetesError> =
+            retry_on_conflict("kube-system", "aws-auth", 3, |_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async {
+                    Err(UpdateAttemptError::Failed(
+                        KubernetesError::ClusterUnreachable {
+                            raw_message: Arc::from("boom"),
+                        },
+                    ))
+                }
+            })
+            .await;
+
+        // verify:
+        assert_eq!(
+            Err(KubernetesError::ClusterUnreachable {
+                raw_message: Arc::from("boom"),
+            }),
+            result
+        );
+        assert_eq!(1, attempts.load(Ordering::SeqCst));
+    }
+
+    type ApiServerHandle = tower_test::mock::Handle<Request<Body>, Response<Body>>;
+
+    fn mock_kubernetes_service(options: ConfigMapOptions) -> (KubernetesService, ApiServerHandle) {
+        let (mock_service, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        (
+            KubernetesService::with_client(Client::new(mock_service, "default"), options),
+            handle,
+        )
+    }
+
+    pub(crate) fn status_response(code: u16, reason: &str) -> Response<Body> {
+        Response::builder()
+            .status(code)
+            .body(Body::from(
+                serde_json::to_vec(&serde_json::json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "metadata": {},
+                    "status": "Failure",
+                    "message": reason,
+                    "reason": reason,
+                    "code": code,
+                }))
+                .unwrap(),
+            ))
+            .unwrap()
+    }
+
+    fn config_map_response(config_map: &ConfigMap) -> Response<Body> {
+        Response::builder()
+            .status(200)
+            .body(Body::from(serde_json::to_vec(config_map).unwrap()))
+            .unwrap()
+    }
+
+    fn aws_auth_config_map(resource_version: &str, data: BTreeMap<String, String>) -> ConfigMap {
+        let mut config_map = ConfigMap {
+            data: Some(data),
+            ..Default::default()
+        };
+        config_map.metadata.name = Some("aws-auth".to_string());
+        config_map.metadata.namespace = Some("kube-system".to_string());
+        config_map.metadata.resource_version = Some(resource_version.to_string());
+        config_map
+    }
+
+    fn synced_user() -> KubernetesUser {
+        KubernetesUser::new(
+            IamUserName::new("user_1"),
+            IamArn::new("arn:test:user_1"),
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+            None,
+        )
+    }
+
+    /// IAM user `name` of account `123456789012` granted `groups`, marked as synced by the tool.
+    pub(crate) fn iam_user(name: &str, groups: &[&str]) -> KubernetesUser {
+        KubernetesUser::new(
+            IamUserName::new(name),
+            IamArn::new(&format!("arn:aws:iam::123456789012:user/{name}")),
+            groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+            Some(SyncedBy::IamEksUserMapper),
+        )
+    }
+
+    async fn request_body_config_map(request: Request<Body>) -> ConfigMap {
+        let body = request
+            .into_body()
+            .collect_bytes()
+            .await
+            .expect("cannot read request body");
+        serde_json::from_slice(&body).expect("request body should be a config map")
+    }
+
+    #[tokio::test]
+    async fn update_config_map_creates_missing_config_map_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            create_if_missing: true,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth",
+                request.uri().path()
+            );
+            send.send_response(status_response(404, "NotFound"));
+
+            let (request, send) = handle.next_request().await.expect("POST expected");
+            assert_eq!(Method::POST, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps",
+                request.uri().path()
+            );
+            let created = request_body_config_map(request).await;
+            assert_eq!(Some("aws-auth".to_string()), created.metadata.name);
+            let data = created.data.clone().unwrap_or_default();
+            assert!(data["mapUsers"].contains("arn:test:user_1"));
+            // no roles to sync
+            assert!(!data.contains_key("mapRoles"));
+            let mut response = created;
+            response.metadata.resource_version = Some("1".to_string());
+            send.send_response(config_map_response(&response));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        let update = result.unwrap();
+        assert_eq!(Some("1".to_string()), update.resource_version);
+        assert_eq!(1, update.aws_auth.users.len());
+    }
+
+    #[tokio::test]
+    async fn update_config_map_created_concurrently_falls_back_to_update_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            create_if_missing: true,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(status_response(404, "NotFound"));
+
+            // someone else created the config map in the meantime
+            let (request, send) = handle.next_request().await.expect("POST expected");
+            assert_eq!(Method::POST, request.method());
+            send.send_response(status_response(409, "AlreadyExists"));
+
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![(
+                    "mapRoles".to_string(),
+                    "- rolearn: arn:test:node\n  username: node\n  groups:\n    - system:nodes\n"
+                        .to_string(),
+                )]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth",
+                request.uri().path()
+            );
+            let mut replaced = request_body_config_map(request).await;
+            let data = replaced.data.clone().unwrap_or_default();
+            assert!(data["mapUsers"].contains("arn:test:user_1"));
+            assert!(data["mapRoles"].contains("arn:test:node"));
+            replaced.metadata.resource_version = Some("43".to_string());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        assert_eq!(Some("43".to_string()), result.unwrap().resource_version);
+    }
+
+    #[tokio::test]
+    async fn update_config_map_retries_transient_errors_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(status_response(503, "ServiceUnavailable"));
+
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_error(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "connection refused",
+            ));
+
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::new(),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            send.send_response(status_response(500, "InternalError"));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let mut replaced = request_body_config_map(request).await;
+            replaced.metadata.resource_version = Some("43".to_string());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        let update = result.unwrap();
+        assert_eq!(ConfigMapUpdateStatus::Updated, update.status);
+        assert_eq!(Some("43".to_string()), update.resource_version);
+    }
+
+    #[tokio::test]
+    async fn update_config_map_cluster_unreachable_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            for _ in 0..3 {
+                let (request, send) = handle.next_request().await.expect("GET expected");
+                assert_eq!(Method::GET, request.method());
+                send.send_response(status_response(503, "ServiceUnavailable"));
+            }
+            // no more attempts expected, mock handle being dropped would make it fail
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        match result {
+            Err(KubernetesError::ClusterUnreachable { raw_message }) => assert!(
+                raw_message.starts_with(
+                    "cannot get config map `kube-system/aws-auth` after 3 attempts in "
+                ),
+                "unexpected message: {raw_message}"
+            ),
+            res => panic!("cluster should be unreachable, got: {:?}", res.err()),
+        }
+    }
+
+    #[test]
+    fn is_transient_error_test() {
+        // setup:
+        let api_error = |code: u16| {
+            kube::Error::Api(kube::core::ErrorResponse {
+                status: "Failure".to_string(),
+                message: "whatever".to_string(),
+                reason: "whatever".to_string(),
+                code,
+            })
+        };
+
+        // execute & verify:
+        assert!(is_transient_error(&api_error(500)));
+        assert!(is_transient_error(&api_error(503)));
+        assert!(is_transient_error(&api_error(429)));
+        assert!(is_transient_error(&kube::Error::Service(Box::new(
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out")
+        ))));
+        assert!(!is_transient_error(&api_error(404)));
+        assert!(!is_transient_error(&api_error(409)));
+        assert!(!is_transient_error(&api_error(403)));
+    }
+
+    #[tokio::test]
+    async fn update_config_map_missing_without_create_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/custom-namespace/configmaps/custom-aws-auth",
+                request.uri().path()
+            );
+            send.send_response(status_response(404, "NotFound"));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "custom-namespace",
+                "custom-aws-auth",
+                None,
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(matches!(
+            result,
+            Err(KubernetesError::ConfigMapNotFound {
+                config_map_name,
+                config_map_namespace,
+                ..
+            }) if config_map_name.as_ref() == "custom-aws-auth" && config_map_namespace.as_ref() == "custom-namespace"
+        ));
+    }
+
+    #[tokio::test]
+    async fn update_config_map_disabled_features_then_cleanup_test() {
+        // setup:
+        let map_users = "# managed by hand\n- userarn: arn:test:zoe\n  username: zoe\n  groups: [ view ]\n- userarn: arn:test:synced\n  username: synced\n  groups:\n    - devops\n  syncedBy: iam-eks-user-mapper\n";
+        let map_roles = "- rolearn: arn:test:nodes\n  username: system:node:{{EC2PrivateDNSName}}\n  groups:\n    - system:nodes\n";
+        let config_map = aws_auth_config_map(
+            "42",
+            BTreeMap::from_iter(vec![
+                ("mapUsers".to_string(), map_users.to_string()),
+                ("mapRoles".to_string(), map_roles.to_string()),
+            ]),
+        );
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            // sync with features disabled: read only
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(config_map_response(&config_map));
+
+            // cleanup: synced entries removed, others left byte for byte
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(config_map_response(&config_map));
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let replaced = request_body_config_map(request).await;
+            let data = replaced.data.clone().unwrap_or_default();
+            assert_eq!(
+                "# managed by hand\n- userarn: arn:test:zoe\n  username: zoe\n  groups: [ view ]\n",
+                data["mapUsers"]
+            );
+            assert_eq!(map_roles, data["mapRoles"]);
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let update = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                None,
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+        let cleanup = service
+            .remove_synced_entries("kube-system", "aws-auth")
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        let update = update.expect("sync should succeed");
+        assert_eq!(ConfigMapUpdateStatus::Unchanged, update.status);
+        assert!(update.diff.is_empty());
+        assert_eq!(2, update.aws_auth.users.len());
+        let diff = cleanup.expect("synced entries should be removed");
+        assert_eq!(1, diff.users.removed.len());
+        assert!(diff.roles.removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_config_map_unchanged_content_is_not_written_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    (
+                        "mapUsers".to_string(),
+                        "- userarn: arn:test:user_1\n  username: user_1\n  groups:\n    - group_1\n  syncedBy: iam-eks-user-mapper\n  syncedAt: 2024-01-01T00:00:00Z\n"
+                            .to_string(),
+                    ),
+                    ("mapRoles".to_string(), "[]\n".to_string()),
+                ]),
+            )));
+            // no PUT expected, mock handle being dropped would make it fail
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        let update = result.unwrap();
+        assert_eq!(ConfigMapUpdateStatus::Unchanged, update.status);
+        assert_eq!(Some("42".to_string()), update.resource_version);
+        assert_eq!(1, update.aws_auth.users.len());
+    }
+
+    #[tokio::test]
+    async fn update_config_map_changed_content_is_written_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), "[]\n".to_string()),
+                    ("mapRoles".to_string(), "[]\n".to_string()),
+                ]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let mut replaced = request_body_config_map(request).await;
+            let annotations = replaced.metadata.annotations.clone().unwrap_or_default();
+            assert!(annotations.contains_key("iam-eks-user-mapper/last-sync-time"));
+            assert_eq!(
+                Some(&env!("CARGO_PKG_VERSION").to_string()),
+                annotations.get("iam-eks-user-mapper/version")
+            );
+            let written_aws_auth =
+                KubernetesService::parse_aws_auth(&replaced).expect("aws-auth should be valid");
+            assert_eq!(
+                BTreeSet::from_iter(vec![KubernetesUser::new_synced_from(
+                    synced_user(),
+                    SyncedBy::IamEksUserMapper
+                )]),
+                written_aws_auth.users
+            );
+            assert!(written_aws_auth.users.iter().all(|u| u.synced_at.is_some()));
+            assert_eq!(
+                Some(&format!("{:016x}", written_aws_auth.content_hash())),
+                annotations.get("iam-eks-user-mapper/content-hash")
+            );
+            replaced.metadata.resource_version = Some("43".to_string());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        let update = result.unwrap();
+        assert_eq!(ConfigMapUpdateStatus::Updated, update.status);
+        assert_eq!(Some("43".to_string()), update.resource_version);
+    }
+
+    #[tokio::test]
+    async fn update_config_map_preserves_foreign_entries_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let foreign_users =
+            "- userarn: arn:test:manual\n  username: manual\n  groups:\n    - view\n";
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth",
+                request.uri().path()
+            );
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), foreign_users.to_string()),
+                    (
+                        "mapRoles".to_string(),
+                        "- rolearn: arn:test:role/manual\n  username: manual\n  groups:\n    - view\n"
+                            .to_string(),
+                    ),
+                    ("mapAccounts".to_string(), "- \"123\"\n".to_string()),
+                    ("other".to_string(), "untouched".to_string()),
+                ]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth",
+                request.uri().path()
+            );
+            let mut replaced = request_body_config_map(request).await;
+            let data = replaced.data.clone().unwrap_or_default();
+            assert_eq!(Some(&"untouched".to_string()), data.get("other"));
+            assert_eq!(Some(&"- \"123\"\n".to_string()), data.get("mapAccounts"));
+            let written_aws_auth =
+                KubernetesService::parse_aws_auth(&replaced).expect("aws-auth should be valid");
+            assert_eq!(2, written_aws_auth.users.len());
+            assert!(written_aws_auth
+                .users
+                .iter()
+                .any(|u| u.iam_arn == IamArn::new("arn:test:manual") && u.synced_by.is_none()));
+            assert!(written_aws_auth
+                .users
+                .iter()
+                .any(|u| u.iam_arn == IamArn::new("arn:test:user_1") && u.synced_by.is_some()));
+            assert_eq!(1, written_aws_auth.roles.len());
+            assert!(written_aws_auth
+                .roles
+                .iter()
+                .all(|r| r.iam_role_arn == IamArn::new("arn:test:role/manual")));
+            replaced.metadata.resource_version = Some("43".to_string());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        assert_eq!(ConfigMapUpdateStatus::Updated, result.unwrap().status);
+    }
+
+    #[tokio::test]
+    async fn update_config_map_write_failure_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), "[]\n".to_string()),
+                    ("mapRoles".to_string(), "[]\n".to_string()),
+                ]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            send.send_response(status_response(403, "Forbidden"));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        match result {
+            Err(KubernetesError::ConfigMapCannotBePatched {
+                config_map_name,
+                config_map_namespace,
+                attempts,
+                ..
+            }) => {
+                assert_eq!("aws-auth", config_map_name.as_ref());
+                assert_eq!("kube-system", config_map_namespace.as_ref());
+                assert_eq!(1, attempts);
+            }
+            _ => panic!("write failure should be reported"),
+        }
+    }
+
+    #[tokio::test]
+    async fn update_config_map_removals_only_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            removals_only: true,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    (
+                        "mapUsers".to_string(),
+                        "- userarn: arn:test:stale\n  username: stale\n  groups:\n    - group_1\n  syncedBy: iam-eks-user-mapper\n"
+                            .to_string(),
+                    ),
+                    ("mapRoles".to_string(), "[]\n".to_string()),
+                ]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let mut replaced = request_body_config_map(request).await;
+            let written_aws_auth =
+                KubernetesService::parse_aws_auth(&replaced).expect("aws-auth should be valid");
+            // stale entry removed, new one not added
+            assert!(written_aws_auth.users.is_empty());
+            replaced.metadata.resource_version = Some("43".to_string());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        let update = result.expect("stale entry should be removed");
+        assert_eq!(ConfigMapUpdateStatus::Updated, update.status);
+        assert!(update.diff.users.added.is_empty());
+        assert_eq!(1, update.diff.users.removed.len());
+        assert_eq!(
+            vec![IamArn::new("arn:test:user_1")],
+            update
+                .diff
+                .users
+                .suppressed
+                .iter()
+                .map(|u| u.iam_arn.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn update_config_map_server_dry_run_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            server_dry_run: true,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    "- userarn: arn:test:removed\n  username: removed\n  groups: []\n  syncedBy: iam-eks-user-mapper\n"
+                        .to_string(),
+                )]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            assert!(request
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .contains("dryRun=All"));
+            let replaced = request_body_config_map(request).await;
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        let update = result.unwrap();
+        assert_eq!(ConfigMapUpdateStatus::ServerDryRun, update.status);
+        assert_eq!(
+            vec!["arn:test:user_1".to_string()],
+            update
+                .diff
+                .users
+                .added
+                .iter()
+                .map(|u| u.iam_arn.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["arn:test:removed".to_string()],
+            update
+                .diff
+                .users
+                .removed
+                .iter()
+                .map(|u| u.iam_arn.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn update_config_map_dry_run_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            dry_run: true,
+            backup_history_count: 5,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(Method::GET, request.method());
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    "- userarn: arn:test:third_party\n  username: third_party\n  groups: []\n\
+                     - userarn: arn:test:removed\n  username: removed\n  groups: []\n  syncedBy: iam-eks-user-mapper\n"
+                        .to_string(),
+                )]),
+            )));
+            // neither backup nor PUT expected, mock handle being dropped would make it fail
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        let update = result.unwrap();
+        assert_eq!(ConfigMapUpdateStatus::DryRun, update.status);
+        assert_eq!(Some("42".to_string()), update.resource_version);
+        assert_eq!(
+            "+ user `arn:test:user_1` (username: user_1, groups: [group_1])\n\
+             - user `arn:test:removed` (username: removed, groups: [])\n\
+             = user `arn:test:third_party` (username: third_party, groups: []) (not managed by the tool)\n",
+            format!("{:#}", update.diff)
+        );
+    }
+
+    #[tokio::test]
+    async fn update_config_map_leadership_lost_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(backup_options());
+        let service = service.with_leadership(Leadership::default());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::new(),
+            )));
+            // neither backup nor PUT expected, mock handle being dropped would make it fail
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(
+            Some(KubernetesError::LeadershipLost {
+                config_map_name: Arc::from("aws-auth"),
+                config_map_namespace: Arc::from("kube-system"),
+            }),
+            result.err()
+        );
+    }
+
+    #[tokio::test]
+    async fn update_config_map_too_many_removals_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            removal_guard: RemovalGuard {
+                max_removal_fraction: 0.5,
+                ..Default::default()
+            },
+            ..backup_options()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    "- userarn: arn:test:user_1\n  username: user_1\n  groups: []\n  syncedBy: iam-eks-user-mapper\n\
+                     - userarn: arn:test:user_2\n  username: user_2\n  groups: []\n  syncedBy: iam-eks-user-mapper\n"
+                        .to_string(),
+                )]),
+            )));
+            // neither backup nor PUT expected, mock handle being dropped would make it fail
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::new()),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(
+            Some(KubernetesError::TooManyRemovals {
+                removals: 2,
+                synced_entries: 2,
+                removed_entries: Arc::from(
+                    "- user `arn:test:user_1` (username: user_1, groups: [])\n\
+                     - user `arn:test:user_2` (username: user_2, groups: [])\n"
+                ),
+            }),
+            result.err()
+        );
+    }
+
+    #[tokio::test]
+    async fn update_config_map_skip_annotations_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            skip_annotations: true,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::new(),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            let replaced = request_body_config_map(request).await;
+            assert_eq!(None, replaced.metadata.annotations);
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+    }
+
+    fn backup_options() -> ConfigMapOptions {
+        ConfigMapOptions {
+            backup_config_map_name: "aws-auth-backup".to_string(),
+            backup_history_count: 5,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn update_config_map_backs_up_previous_content_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(backup_options());
+        let previous_data = BTreeMap::from_iter(vec![
+            ("mapUsers".to_string(), "[]\n".to_string()),
+            (
+                "mapRoles".to_string(),
+                "- rolearn: arn:test:node\n  username: node\n  groups:\n  - system:nodes\n"
+                    .to_string(),
+            ),
+        ]);
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                previous_data.clone(),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("GET backup expected");
+            assert_eq!(Method::GET, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth-backup",
+                request.uri().path()
+            );
+            send.send_response(status_response(404, "NotFound"));
+
+            let (request, send) = handle.next_request().await.expect("POST backup expected");
+            assert_eq!(Method::POST, request.method());
+            let backup_config_map = request_body_config_map(request).await;
+            assert_eq!(
+                Some("aws-auth-backup".to_string()),
+                backup_config_map.metadata.name
+            );
+            let backups = backup_config_map.data.clone().unwrap_or_default();
+            let (_, backup) =
+                backup::get_backup(&backups, None).expect("a backup should be written");
+            assert_eq!(previous_data.get("mapUsers"), backup.map_users.as_ref());
+            assert_eq!(previous_data.get("mapRoles"), backup.map_roles.as_ref());
+            send.send_response(config_map_response(&backup_config_map));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth",
+                request.uri().path()
+            );
+            let mut replaced = request_body_config_map(request).await;
+            replaced.metadata.resource_version = Some("43".to_string());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .update_user_and_role_config_map(
+                "kube-system",
+                "aws-auth",
+                Some(BTreeSet::from_iter(vec![synced_user()])),
+                None,
+                &[],
+                &[],
+                &BTreeSet::new(),
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.is_ok());
+        assert_eq!(ConfigMapUpdateStatus::Updated, result.unwrap().status);
+    }
+
+    #[tokio::test]
+    async fn restore_config_map_backup_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(backup_options());
+        let mut backups = BTreeMap::new();
+        backup::add_backup(
+            &mut backups,
+            &ConfigMapBackup {
+                map_users: Some(
+                    "- userarn: arn:test:old\n  username: old\n  groups: []\n".to_string(),
+                ),
+                map_roles: None,
+                map_accounts: None,
+            },
+            SystemTime::UNIX_EPOCH,
+            5,
+        )
+        .expect("backup should be added");
+        let mut backup_config_map = aws_auth_config_map("1", backups);
+        backup_config_map.metadata.name = Some("aws-auth-backup".to_string());
+        let api_server = tokio::spawn(async move {
+            let (request, send) = handle.next_request().await.expect("GET backup expected");
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth-backup",
+                request.uri().path()
+            );
+            send.send_response(config_map_response(&backup_config_map));
+
+            let (request, send) = handle.next_request().await.expect("GET expected");
+            assert_eq!(
+                "/api/v1/namespaces/kube-system/configmaps/aws-auth",
+                request.uri().path()
+            );
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), "[]\n".to_string()),
+                    ("mapRoles".to_string(), "[]\n".to_string()),
+                ]),
+            )));
+
+            // current content is backed up before being restored
+            let (_, send) = handle.next_request().await.expect("GET backup expected");
+            send.send_response(config_map_response(&backup_config_map));
+            let (request, send) = handle.next_request().await.expect("PUT backup expected");
+            assert_eq!(Method::PUT, request.method());
+            let updated_backup_config_map = request_body_config_map(request).await;
+            assert_eq!(
+                2,
+                backup::backup_names(&updated_backup_config_map.data.clone().unwrap_or_default())
+                    .len()
+            );
+            send.send_response(config_map_response(&updated_backup_config_map));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let restored = request_body_config_map(request).await;
+            let data = restored.data.clone().unwrap_or_default();
+            assert!(data["mapUsers"].contains("arn:test:old"));
+            assert!(!data.contains_key("mapRoles"));
+            send.send_response(config_map_response(&restored));
+        });
+
+        // execute:
+        let result = service
+            .restore_config_map_backup("kube-system", "aws-auth", None)
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(Ok("backup-1970-01-01T00-00-00Z".to_string()), result);
+    }
+
+    #[tokio::test]
+    async fn remove_aws_auth_entries_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    (
+                        "mapUsers".to_string(),
+                        "- userarn: arn:test:user_1\n  username: user_1\n  groups: []\n  syncedBy: iam-eks-user-mapper\n- userarn: arn:test:user_2\n  username: user_2\n  groups: []\n".to_string(),
+                    ),
+                    (
+                        "mapRoles".to_string(),
+                        "- rolearn: arn:test:role_1\n  username: role_1\n  groups: []\n".to_string(),
+                    ),
+                ]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let replaced = request_body_config_map(request).await;
+            let written_aws_auth =
+                KubernetesService::parse_aws_auth(&replaced).expect("aws-auth should be valid");
+            assert_eq!(
+                vec!["arn:test:user_2".to_string()],
+                written_aws_auth
+                    .users
+                    .iter()
+                    .map(|u| u.iam_arn.to_string())
+                    .collect::<Vec<_>>()
+            );
+            assert!(written_aws_auth.roles.is_empty());
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .remove_aws_auth_entries(
+                "kube-system",
+                "aws-auth",
+                &[
+                    IamArn::new("arn:test:USER_1"),
+                    IamArn::new("arn:test:role_1"),
+                ],
+            )
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        let diff = result.expect("entries should be removed");
+        assert_eq!(1, diff.users.removed.len());
+        assert_eq!(1, diff.roles.removed.len());
+    }
+
+    #[tokio::test]
+    async fn remove_aws_auth_entries_dry_run_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            dry_run: true,
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    "- userarn: arn:test:user_1\n  username: user_1\n  groups: []\n".to_string(),
+                )]),
+            )));
+        });
+
+        // execute:
+        let result = service
+            .remove_aws_auth_entries("kube-system", "aws-auth", &[IamArn::new("arn:test:user_1")])
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert_eq!(
+            1,
+            result
+                .expect("removal should be planned")
+                .users
+                .removed
+                .len()
+        );
+    }
+
+    #[test]
+    fn remove_sequence_entries_test() {
+        // setup:
+        struct TestCase<'a> {
+            content: &'a str,
+            expected: Option<&'a str>,
+            _description: &'a str,
+        }
+
+        let synced = |entry: &serde_yaml::Value| {
+            entry.get("syncedBy").and_then(serde_yaml::Value::as_str) == Some("iam-eks-user-mapper")
+        };
+        let test_cases = vec![
+            TestCase {
+                content: "# managed by hand\n- userarn: \"arn:test:manual\"   # break glass\n  username: manual\n  groups: [ view ]\n- userarn: arn:test:synced\n  username: synced\n  groups:\n    - devops\n  syncedBy: iam-eks-user-mapper\n- userarn: arn:test:other\n  username: other\n  groups: []\n  syncedBy: other-mapper\n",
+                expected: Some("# managed by hand\n- userarn: \"arn:test:manual\"   # break glass\n  username: manual\n  groups: [ view ]\n- userarn: arn:test:other\n  username: other\n  groups: []\n  syncedBy: other-mapper\n"),
+                _description: "case 1 - unmanaged entries kept as written",
+            },
+            TestCase {
+                content: "  - rolearn: arn:test:synced\n    groups:\n      - system:nodes\n    syncedBy: iam-eks-user-mapper\n  - rolearn: arn:test:manual\n    groups:\n      - system:nodes\n",
+                expected: Some("  - rolearn: arn:test:manual\n    groups:\n      - system:nodes\n"),
+                _description: "case 2 - indented entries",
+            },
+            TestCase {
+                content: "- userarn: arn:test:synced\n  groups: []\n  syncedBy: iam-eks-user-mapper\n",
+                expected: Some("[]\n"),
+                _description: "case 3 - all entries removed",
+            },
+            TestCase {
+                content: "- userarn: arn:test:manual\n  groups: []\n",
+                expected: Some("- userarn: arn:test:manual\n  groups: []\n"),
+                _description: "case 4 - nothing removed",
+            },
+            TestCase {
+                content: "",
+                expected: Some(""),
+                _description: "case 5 - no entries",
+            },
+            TestCase {
+                content: "[{userarn: arn:test:synced, groups: [], syncedBy: iam-eks-user-mapper}, {userarn: arn:test:manual, groups: []}]\n",
+                expected: None,
+                _description: "case 6 - flow sequence, entries cannot be told apart",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = KubernetesService::remove_sequence_entries(tc.content, synced);
+
+            // verify:
+            assert_eq!(tc.expected.map(str::to_string), res, "{}", tc._description);
+        }
+    }
+
+    #[tokio::test]
+    async fn remove_synced_entries_test() {
+        // setup:
+        let map_users = "# managed by hand\n- userarn: arn:test:manual\n  username: manual\n  groups: [ view ]\n- userarn: arn:test:synced\n  username: synced\n  groups:\n    - devops\n  syncedBy: ci-mapper\n- userarn: arn:test:default\n  username: default\n  groups: []\n  syncedBy: iam-eks-user-mapper\n- userarn: arn:test:protected\n  username: protected\n  groups: []\n  syncedBy: ci-mapper\n";
+        let map_roles = "- rolearn: arn:test:nodes\n  username: system:node:{{EC2PrivateDNSName}}\n  groups:\n    - system:nodes\n";
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions {
+            synced_by: SyncedBy::new("ci-mapper"),
+            protected_arns: vec![IamArn::new("arn:test:protected")],
+            ..Default::default()
+        });
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), map_users.to_string()),
+                    ("mapRoles".to_string(), map_roles.to_string()),
+                    ("mapAccounts".to_string(), "- \"123\"\n".to_string()),
+                ]),
+            )));
+
+            let (request, send) = handle.next_request().await.expect("PUT expected");
+            assert_eq!(Method::PUT, request.method());
+            let replaced = request_body_config_map(request).await;
+            let data = replaced.data.clone().unwrap_or_default();
+            // entries of other instances, protected and unmanaged ones are left byte for byte
+            assert_eq!(
+                "# managed by hand\n- userarn: arn:test:manual\n  username: manual\n  groups: [ view ]\n- userarn: arn:test:default\n  username: default\n  groups: []\n  syncedBy: iam-eks-user-mapper\n- userarn: arn:test:protected\n  username: protected\n  groups: []\n  syncedBy: ci-mapper\n",
+                data["mapUsers"]
+            );
+            assert_eq!(map_roles, data["mapRoles"]);
+            assert_eq!("- \"123\"\n", data["mapAccounts"]);
+            send.send_response(config_map_response(&replaced));
+        });
+
+        // execute:
+        let result = service
+            .remove_synced_entries("kube-system", "aws-auth")
+            .await;
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        let diff = result.expect("synced entries should be removed");
+        assert_eq!(1, diff.users.removed.len());
+        assert!(diff.roles.removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_synced_entries_nothing_synced_test() {
+        // setup:
+        let (service, mut handle) = mock_kubernetes_service(ConfigMapOptions::default());
+        let api_server = tokio::spawn(async move {
+            let (_, send) = handle.next_request().await.expect("GET expected");
+            send.send_response(config_map_response(&aws_auth_config_map(
+                "42",
+                BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    "- userarn: arn:test:manual\n  username: manual\n  groups: []\n".to_string(),
+                )]),
+            )));
+            // no write expected
+            assert!(handle.next_request().await.is_none());
+        });
+
+        // execute:
+        let result = service
+            .remove_synced_entries("kube-system", "aws-auth")
+            .await;
+        drop(service);
+
+        // verify:
+        api_server.await.expect("API server assertions failed");
+        assert!(result.expect("nothing should be removed").is_empty());
+    }
+
+    #[test]
+    fn config_map_content_changed_test() {
+        // setup:
+        struct TestCase<'a> {
+            original: Vec<(&'a str, &'a str)>,
+            merged: Vec<(&'a str, &'a str)>,
+            expected: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                original: vec![("mapUsers", "- userarn: arn:test:user_1\n  username: user_1\n  groups:\n    - group_1\n    - group_2\n- userarn: arn:test:user_2\n  username: user_2\n  groups: []\n")],
+                merged: vec![("mapUsers", "- userarn: arn:test:user_2\n  username: user_2\n  groups: []\n- userarn: arn:test:user_1\n  username: user_1\n  groups:\n    - group_2\n    - group_1\n")],
+                expected: false,
+                _description: "case 1 - same users written in a different order",
+            },
+            TestCase {
+                original: vec![("mapUsers", "- userarn: arn:test:user_1\n  username: user_1\n  groups:\n    - group_1\n")],
+                merged: vec![("mapUsers", "- userarn: arn:test:user_1\n  username: user_1\n  groups:\n    - group_2\n")],
+                expected: true,
+                _description: "case 2 - user groups changed",
+            },
+            TestCase {
+                original: vec![("mapRoles", "- rolearn: arn:test:role_1\n  username: node\n  groups:\n    - system:nodes\n")],
+                merged: vec![("mapRoles", "[]\n")],
+                expected: true,
+                _description: "case 3 - role removed",
+            },
+            TestCase {
+                original: vec![("mapAccounts", "- 111122223333\n- \"444455556666\"\n")],
+                merged: vec![("mapAccounts", "- '444455556666'\n- '111122223333'\n")],
+                expected: false,
+                _description: "case 4 - same accounts written differently",
+            },
+            TestCase {
+                original: vec![],
+                merged: vec![("mapUsers", "[]\n")],
+                expected: true,
+                _description: "case 5 - key added",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = KubernetesService::config_map_content_changed(
+                &aws_auth_config_map(
+                    "1",
+                    BTreeMap::from_iter(
+                        tc.original
+                            .into_iter()
+                            .map(|(k, v)| (k.to_string(), v.to_string())),
+                    ),
+                ),
+                &aws_auth_config_map(
+                    "1",
+                    BTreeMap::from_iter(
+                        tc.merged
+                            .into_iter()
+                            .map(|(k, v)| (k.to_string(), v.to_string())),
+                    ),
+                ),
+            );
+
+            // verify:
+            assert_eq!(tc.expected, res);
+        }
+    }
+
+    #[test]
+    fn merge_config_map_preserves_other_keys_test() {
+        // setup:
+        let other_data = vec![
+            (
+                "mapAccounts".to_string(),
+                "- \"111122223333\"\n- 444455556666\n".to_string(),
+            ),
+            ("someOtherKey".to_string(), "whatever: [1, 2]".to_string()),
+        ];
+        let mut config_map = aws_auth_config_map("1", BTreeMap::from_iter(other_data.clone()));
+
+        // execute:
+        let res = KubernetesService::merge_config_map(
+            &mut config_map,
+            Some(BTreeSet::from_iter(vec![synced_user()])),
+            None,
+            &[],
+            &[],
+            &BTreeSet::new(),
+            &ConfigMapOptions::default(),
+        );
+
+        // verify:
+        assert!(res.is_ok());
+        let data = config_map.data.unwrap_or_default();
+        for (key, value) in other_data {
+            assert_eq!(Some(&value), data.get(&key));
+        }
+        assert!(data["mapUsers"].contains("arn:test:user_1"));
+        assert_eq!(None, config_map.metadata.annotations);
+    }
+
+    #[test]
+    fn merge_config_map_synced_at_test() {
+        // setup:
+        let mut config_map = aws_auth_config_map(
+            "1",
+            BTreeMap::from_iter(vec![(
+                "mapUsers".to_string(),
+                r"
+- userarn: arn:test:user_1
+  username: user_1
+  groups:
+    - group_1
+  syncedBy: iam-eks-user-mapper
+  syncedAt: 2024-01-01T00:00:00Z
+- userarn: arn:test:user_2
+  username: user_2
+  groups:
+    - group_1
+  syncedBy: iam-eks-user-mapper
+"
+                .to_string(),
+            )]),
+        );
+        let user = |name: &str| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:test:{name}")),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("group_1")]),
+                None,
+            )
+        };
+        let synced_at = |config_map: &ConfigMap, name: &str| {
+            KubernetesService::parse_aws_auth(config_map)
+                .expect("aws-auth should be valid")
+                .users
+                .into_iter()
+                .find(|u| u.iam_user_name == IamUserName::new(name))
+                .and_then(|u| u.synced_at)
+        };
+
+        // execute:
+        let first_sync = KubernetesService::merge_config_map(
+            &mut config_map,
+            Some(BTreeSet::from_iter(vec![user("user_1"), user("user_2")])),
+            None,
+            &[],
+            &[],
+            &BTreeSet::new(),
+            &ConfigMapOptions::default(),
+        );
+        let user_2_synced_at = synced_at(&config_map, "user_2");
+        let second_sync = KubernetesService::merge_config_map(
+            &mut config_map,
+            Some(BTreeSet::from_iter(vec![user("user_1"), user("user_2")])),
+            None,
+            &[],
+            &[],
+            &BTreeSet::new(),
+            &ConfigMapOptions {
+                synced_entry_ttl: Some(Duration::from_secs(24 * 60 * 60)),
+                ..Default::default()
+            },
+        );
+
+        // verify:
+        assert!(first_sync.is_ok());
+        assert!(second_sync.is_ok());
+        assert!(
+            user_2_synced_at.is_some(),
+            "entry without syncedAt gets one on first sync"
+        );
+        assert_eq!(
+            user_2_synced_at,
+            synced_at(&config_map, "user_2"),
+            "syncedAt is preserved across syncs"
+        );
+        assert_eq!(
+            None,
+            synced_at(&config_map, "user_1"),
+            "entry synced more than a TTL ago is removed"
+        );
+        assert!(config_map.data.unwrap_or_default()["mapUsers"].contains("syncedAt: "));
+    }
+
+    #[test]
+    fn merge_config_map_merges_duplicated_entries_test() {
+        // setup:
+        let mut config_map = aws_auth_config_map(
+            "1",
+            BTreeMap::from_iter(vec![
+                (
+                    "mapUsers".to_string(),
+                    r"
+- userarn: arn:test:manual
+  username: manual
+  groups:
+    - view
+- userarn: arn:test:manual
+  username: manual
+  groups:
+    - edit
+    - view
+"
+                    .to_string(),
+                ),
+                (
+                    "mapRoles".to_string(),
+                    r"
+- rolearn: arn:test:node
+  username: node
+  groups:
+    - system:nodes
+- rolearn: arn:test:node
+  username: node
+  groups:
+    - system:bootstrappers
+"
+                    .to_string(),
+                ),
+            ]),
+        );
+
+        // users and roles being synced so both keys are rewritten
+        let synced_role = KubernetesRole::new(
+            IamArn::new("arn:test:synced"),
+            Some("synced".to_string()),
+            None,
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("view")]),
+            None,
+        );
+
+        // execute:
+        let res = KubernetesService::merge_config_map(
+            &mut config_map,
+            Some(BTreeSet::new()),
+            None,
+            &[],
+            &[synced_role],
+            &BTreeSet::new(),
+            &ConfigMapOptions::default(),
+        );
+
+        // verify:
+        assert!(res.is_ok());
+        let data = config_map.data.unwrap_or_default();
+        let users: Vec<MapUserConfig> =
+            serde_yaml::from_str(&data["mapUsers"]).expect("invalid mapUsers");
+        assert_eq!(1, users.len());
+        assert_eq!("arn:test:manual", users[0].user_arn);
+        assert_eq!(
+            BTreeSet::from_iter(vec!["edit".to_string(), "view".to_string()]),
+            users[0].groups
+        );
+        let roles: Vec<MapRoleConfig> =
+            serde_yaml::from_str(&data["mapRoles"]).expect("invalid mapRoles");
+        assert_eq!(2, roles.len());
+        assert_eq!("arn:test:node", roles[0].role_arn);
+        assert_eq!(
+            BTreeSet::from_iter(vec![
+                "system:bootstrappers".to_string(),
+                "system:nodes".to_string()
+            ]),
+            roles[0].groups
+        );
+    }
+
+    #[test]
+    fn merge_config_map_disabled_features_test() {
+        // setup:
+        struct TestCase<'a> {
+            kubernetes_users_to_be_added: Option<BTreeSet<KubernetesUser>>,
+            other_roles_to_be_added: Vec<KubernetesRole>,
+            map_roles: &'a str,
+            expected_users_untouched: bool,
+            expected_roles_untouched: bool,
+            expected_users: Vec<&'a str>,
+            expected_roles: Vec<&'a str>,
+            _description: &'a str,
+        }
+
+        // third-party entries, unsorted and commented, next to previously synced ones
+        let map_users = "# managed by hand\n- userarn: arn:test:zoe\n  username: zoe\n  groups: [ view ]\n- userarn: arn:test:synced\n  username: synced\n  groups:\n    - devops\n  syncedBy: iam-eks-user-mapper\n";
+        let map_roles = "- rolearn: arn:test:nodes\n  username: system:node:{{EC2PrivateDNSName}}\n  groups:\n    - system:nodes\n- rolearn: arn:test:admin\n  groups: [ system:masters ]\n";
+        let map_roles_with_synced = "- rolearn: arn:test:nodes\n  username: system:node:{{EC2PrivateDNSName}}\n  groups:\n    - system:nodes\n- rolearn: arn:test:sso\n  username: sso\n  groups:\n    - view\n  syncedBy: iam-eks-user-mapper\n";
+        let role = |name: &str| {
+            KubernetesRole::new(
+                IamArn::new(&format!("arn:test:{name}")),
+                Some(name.to_string()),
+                None,
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("view")]),
+                None,
+            )
+        };
+
+        let test_cases = vec![
+            TestCase {
+                kubernetes_users_to_be_added: None,
+                other_roles_to_be_added: vec![],
+                map_roles,
+                expected_users_untouched: true,
+                expected_roles_untouched: true,
+                expected_users: vec!["arn:test:synced", "arn:test:zoe"],
+                expected_roles: vec!["arn:test:admin", "arn:test:nodes"],
+                _description: "case 1 - group users sync disabled, no roles to sync, config map left byte for byte",
+            },
+            TestCase {
+                kubernetes_users_to_be_added: None,
+                other_roles_to_be_added: vec![role("sso")],
+                map_roles,
+                expected_users_untouched: true,
+                expected_roles_untouched: false,
+                expected_users: vec!["arn:test:synced", "arn:test:zoe"],
+                expected_roles: vec!["arn:test:admin", "arn:test:nodes", "arn:test:sso"],
+                _description: "case 2 - group users sync disabled, roles to sync",
+            },
+            TestCase {
+                kubernetes_users_to_be_added: Some(BTreeSet::new()),
+                other_roles_to_be_added: vec![],
+                map_roles,
+                expected_users_untouched: false,
+                expected_roles_untouched: true,
+                expected_users: vec!["arn:test:zoe"],
+                expected_roles: vec!["arn:test:admin", "arn:test:nodes"],
+                _description: "case 3 - group users sync enabled, no users left in groups, synced ones removed",
+            },
+            TestCase {
+                kubernetes_users_to_be_added: None,
+                other_roles_to_be_added: vec![],
+                map_roles: map_roles_with_synced,
+                expected_users_untouched: true,
+                expected_roles_untouched: false,
+                expected_users: vec!["arn:test:synced", "arn:test:zoe"],
+                expected_roles: vec!["arn:test:nodes"],
+                _description: "case 4 - no roles to sync anymore, previously synced ones removed",
+            },
+        ];
+
+        for tc in test_cases {
+            let mut config_map = aws_auth_config_map(
+                "1",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), map_users.to_string()),
+                    ("mapRoles".to_string(), tc.map_roles.to_string()),
+                ]),
+            );
+
+            // execute:
+            let res = KubernetesService::merge_config_map(
+                &mut config_map,
+                tc.kubernetes_users_to_be_added,
+                None,
+                &[],
+                &tc.other_roles_to_be_added,
+                &BTreeSet::new(),
+                &ConfigMapOptions::default(),
+            );
+
+            // verify:
+            let aws_auth = res.expect(tc._description);
+            let data = config_map.data.unwrap_or_default();
+            assert_eq!(
+                tc.expected_users_untouched,
+                data["mapUsers"] == map_users,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_roles_untouched,
+                data["mapRoles"] == tc.map_roles,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_users,
+                aws_auth
+                    .users
+                    .iter()
+                    .map(|u| u.iam_arn.to_string())
+                    .collect::<Vec<_>>(),
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_roles,
+                aws_auth
+                    .roles
+                    .iter()
+                    .map(|r| r.iam_role_arn.to_string())
+                    .collect::<Vec<_>>(),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn parse_aws_auth_deserialization_error_location_test() {
+        // setup:
+        let users = r"
+- userarn: arn:aws:iam::123456789012:user/alice
+  username: alice
+  groups:
+  - system:masters
+- userarn: arn:aws:iam::123456789012:user/bob
+  username: bob
+  groups:
+  - system:masters
+- userarn: arn:aws:iam::123456789012:user/carol
+  username: carol
+  groups: admins
+- userarn: arn:aws:iam::123456789012:user/dave
+  username: dave
+  groups:
+  - system:masters
+"
+        .trim_start();
+        let roles = r"
+    - rolearn: arn:aws:iam::123456789012:role/nodes
+      username: system:node:{{EC2PrivateDNSName}}
+      groups:
+        - system:nodes
+    - rolearn: arn:aws:iam::123456789012:role/admin
+      groups:
+        - system:masters
+    - rolename: ci
+      groups:
+        - system:masters
+"
+        .trim_start_matches('\n');
+
+        struct TestCase<'a> {
+            key: &'a str,
+            raw_yaml: &'a str,
+            error_snippet_max_length: usize,
+            expected_location: EntryErrorLocation,
+            expected_snippet: &'a str,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                key: "mapUsers",
+                raw_yaml: users,
+                error_snippet_max_length: DEFAULT_ERROR_SNIPPET_MAX_LENGTH,
+                expected_location: EntryErrorLocation {
+                    entry_index: Some(2),
+                    arn: Some(Arc::from("arn:aws:iam::123456789012:user/carol")),
+                    line: Some(11),
+                    column: Some(11),
+                },
+                expected_snippet: "- userarn: arn:aws:iam::123456789012:user/carol\n  username: carol\n  groups: admins",
+                _description: "case 1 - invalid groups of the third user",
+            },
+            TestCase {
+                key: "mapUsers",
+                raw_yaml: users,
+                error_snippet_max_length: 20,
+                expected_location: EntryErrorLocation {
+                    entry_index: Some(2),
+                    arn: Some(Arc::from("arn:aws:iam::123456789012:user/carol")),
+                    line: Some(11),
+                    column: Some(11),
+                },
+                expected_snippet: "- userarn: arn:aws:i...",
+                _description: "case 2 - truncated snippet",
+            },
+            TestCase {
+                key: "mapUsers",
+                raw_yaml: users,
+                error_snippet_max_length: 0,
+                expected_location: EntryErrorLocation {
+                    entry_index: Some(2),
+                    arn: Some(Arc::from("arn:aws:iam::123456789012:user/carol")),
+                    line: Some(11),
+                    column: Some(11),
+                },
+                expected_snippet: "",
+                _description: "case 3 - no snippet",
+            },
+            TestCase {
+                key: "mapRoles",
+                raw_yaml: roles,
+                error_snippet_max_length: DEFAULT_ERROR_SNIPPET_MAX_LENGTH,
+                expected_location: EntryErrorLocation {
+                    entry_index: Some(2),
+                    arn: None,
+                    line: Some(8),
+                    column: Some(7),
+                },
+                expected_snippet: "- rolename: ci\n      groups:\n        - system:masters",
+                _description: "case 4 - indented roles, third role without ARN",
+            },
+            TestCase {
+                key: "mapUsers",
+                raw_yaml: "userarn: arn:aws:iam::123456789012:user/alice\n",
+                error_snippet_max_length: DEFAULT_ERROR_SNIPPET_MAX_LENGTH,
+                expected_location: EntryErrorLocation {
+                    entry_index: None,
+                    arn: None,
+                    line: Some(1),
+                    column: Some(1),
+                },
+                expected_snippet: "userarn: arn:aws:iam::123456789012:user/alice",
+                _description: "case 5 - content not being a list",
+            },
+        ];
+
+        for tc in test_cases {
+            let config_map = aws_auth_config_map(
+                "1",
+                BTreeMap::from([(tc.key.to_string(), tc.raw_yaml.to_string())]),
+            );
+
+            // execute:
+            let res = KubernetesService::parse_aws_auth_with_error_snippet(
+                &config_map,
+                tc.error_snippet_max_length,
+            );
+
+            // verify:
+            let (location, snippet) = match res {
+                Err(KubernetesError::CannotDeserializeUsersMap {
+                    location, snippet, ..
+                }) if tc.key == "mapUsers" => (location, snippet),
+                Err(KubernetesError::CannotDeserializeRolesMap {
+                    location, snippet, ..
+                }) if tc.key == "mapRoles" => (location, snippet),
+                res => panic!("{}: unexpected result {res:?}", tc._description),
+            };
+            assert_eq!(tc.expected_location, location, "{}", tc._description);
+            assert_eq!(tc.expected_snippet, snippet.as_ref(), "{}", tc._description);
+        }
+
+        // other entries are never part of the error
+        let error = KubernetesService::parse_aws_auth(&aws_auth_config_map(
+            "1",
+            BTreeMap::from([("mapUsers".to_string(), users.to_string())]),
+        ))
+        .expect_err("third user should be invalid")
+        .to_string();
+        assert!(
+            error.contains("entry 2 (`arn:aws:iam::123456789012:user/carol`), line 11, column 11")
+        );
+        for other_user in ["alice", "bob", "dave"] {
+            assert!(!error.contains(other_user), "{error}");
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::kubernetes::{
-        IamArn, IamUserName, KubernetesError, KubernetesGroupName, KubernetesRole,
-        KubernetesService, KubernetesUser, MapRoleConfig, MapUserConfig, SyncedBy,
-    };
-    use std::collections::HashSet;
+    #[test]
+    fn merge_config_map_round_trips_unknown_fields_test() {
+        // setup:
+        let map_users = r"
+- userarn: arn:test:manual
+  username: manual
+  groups:
+    - edit
+    - view
+  email: manual@example.com
+  metadata:
+    team: platform
+    tags:
+      - a
+      - b
+";
+        let map_roles = r"
+- rolearn: arn:test:node
+  username: node
+  groups:
+    - system:nodes
+  custom:
+    nested:
+      deep: 1
+";
+        let mut config_map = aws_auth_config_map(
+            "1",
+            BTreeMap::from_iter(vec![
+                ("mapUsers".to_string(), map_users.to_string()),
+                ("mapRoles".to_string(), map_roles.to_string()),
+            ]),
+        );
+
+        // execute:
+        let res = KubernetesService::merge_config_map(
+            &mut config_map,
+            Some(BTreeSet::from_iter(vec![synced_user()])),
+            None,
+            &[],
+            &[],
+            &BTreeSet::new(),
+            &ConfigMapOptions::default(),
+        );
+
+        // verify:
+        assert!(res.is_ok());
+        let data = config_map.data.unwrap_or_default();
+        let users: Vec<serde_yaml::Value> =
+            serde_yaml::from_str(&data["mapUsers"]).expect("invalid mapUsers");
+        assert_eq!(2, users.len());
+        let expected_users: Vec<serde_yaml::Value> =
+            serde_yaml::from_str(map_users).expect("invalid expected mapUsers");
+        assert!(users.contains(&expected_users[0]));
+        let roles: Vec<serde_yaml::Value> =
+            serde_yaml::from_str(&data["mapRoles"]).expect("invalid mapRoles");
+        let expected_roles: Vec<serde_yaml::Value> =
+            serde_yaml::from_str(map_roles).expect("invalid expected mapRoles");
+        assert_eq!(expected_roles, roles);
+    }
 
     #[test]
-    fn generate_users_config_map_yaml_string_test() {
+    fn merge_config_map_quotes_special_group_names_test() {
         // setup:
         struct TestCase<'a> {
-            input: HashSet<KubernetesUser>,
-            expected_output: Result<String, KubernetesError>,
+            group_name: &'a str,
             _description: &'a str,
         }
 
         let test_cases = vec![
             TestCase {
-                input: HashSet::from_iter(vec![
-                    KubernetesUser {
-                        iam_user_name: IamUserName::new("user_1"),
-                        iam_arn: IamArn::new("arn:test:user_1"),
-                        roles: HashSet::from_iter(vec![
-                            KubernetesGroupName::new("group_1"),
-                            KubernetesGroupName::new("group_2"),
-                        ]),
-                        synced_by: None,
-                    },
-                    KubernetesUser {
-                        iam_user_name: IamUserName::new("user_2"),
-                        iam_arn: IamArn::new("arn:test:user_2"),
-                        roles: HashSet::from_iter(vec![
-                            KubernetesGroupName::new("group_2"),
-                            KubernetesGroupName::new("group_3"),
-                        ]),
-                        synced_by: None,
-                    },
-                    KubernetesUser {
-                        iam_user_name: IamUserName::new("user_3"),
-                        iam_arn: IamArn::new("arn:test:user_3"),
-                        roles: HashSet::from_iter(vec![
-                            KubernetesGroupName::new("group_3"),
-                            KubernetesGroupName::new("group_4"),
-                        ]),
-                        synced_by: Some(SyncedBy::IamEksUserMapper),
-                    },
-                ]),
-                expected_output: Ok(r"
-- userarn: arn:test:user_1
-  username: user_1
-  groups:
-    - group_1
-    - group_2
-- userarn: arn:test:user_2
-  username: user_2
-  groups:
-    - group_2
-    - group_3
-- userarn: arn:test:user_3
-  username: user_3
-  groups:
-    - group_3
-    - group_4
-  syncedBy: iam-eks-user-mapper"
-                    .trim_start()
-                    .to_string()),
-
-                _description: "case 1 - nominal case",
+                group_name: "system:masters",
+                _description: "colon",
             },
             TestCase {
-                input: HashSet::from_iter(vec![KubernetesUser {
-                    iam_user_name: IamUserName::new("user_1"),
-                    iam_arn: IamArn::new("arn:test:user_1"),
-                    roles: HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    synced_by: None,
-                }]),
-                expected_output: Ok(r"
-- userarn: arn:test:user_1
-  username: user_1
-  groups:
-    - group_1
-    - group_2"
-                    .trim_start()
-                    .to_string()),
-
-                _description: "case 2 - one user",
+                group_name: "team: platform",
+                _description: "colon followed by a space",
             },
             TestCase {
-                input: HashSet::from_iter(vec![KubernetesUser {
-                    iam_user_name: IamUserName::new("user_1"),
-                    iam_arn: IamArn::new("arn:test:user_1"),
-                    roles: HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_1"),
-                        KubernetesGroupName::new("group_2"),
-                    ]),
-                    synced_by: Some(SyncedBy::Unknown),
-                }]),
-                expected_output: Ok(r"
-- userarn: arn:test:user_1
-  username: user_1
-  groups:
-    - group_1
-    - group_2
-  syncedBy: a-tool-we-do-not-know"
-                    .trim_start()
-                    .to_string()),
-
-                _description: "case 3 - one user synced by unknown provider",
+                group_name: "#admins",
+                _description: "leading comment character",
             },
             TestCase {
-                input: HashSet::from_iter(vec![]),
-                expected_output: Ok(r"".to_string()),
-                _description: "case 4 - no users",
+                group_name: "admins #comment",
+                _description: "inline comment",
+            },
+            TestCase {
+                group_name: "- admins",
+                _description: "leading dash",
+            },
+            TestCase {
+                group_name: "-admins",
+                _description: "leading dash without space",
+            },
+            TestCase {
+                group_name: "équipe-données-数据",
+                _description: "unicode",
+            },
+            TestCase {
+                group_name: "true",
+                _description: "boolean looking",
+            },
+            TestCase {
+                group_name: "0123",
+                _description: "number looking",
+            },
+            TestCase {
+                group_name: "yes",
+                _description: "YAML 1.1 boolean",
+            },
+            TestCase {
+                group_name: "1_000",
+                _description: "YAML 1.1 number",
+            },
+            TestCase {
+                group_name: "2001-12-14",
+                _description: "YAML 1.1 timestamp",
+            },
+            TestCase {
+                group_name: "it's \"quoted\"",
+                _description: "quotes",
             },
         ];
 
         for tc in test_cases {
+            let user = KubernetesUser::new(
+                IamUserName::new("user_1"),
+                IamArn::new("arn:test:user_1"),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new(tc.group_name)]),
+                None,
+            );
+            let role = KubernetesRole::new(
+                IamArn::new("arn:test:role_1"),
+                None,
+                Some(tc.group_name.to_string()),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new(tc.group_name)]),
+                None,
+            );
+            let mut config_map = aws_auth_config_map("1", BTreeMap::new());
+
             // execute:
-            let result = KubernetesService::generate_users_config_map_yaml_string(tc.input);
+            let res = KubernetesService::merge_config_map(
+                &mut config_map,
+                Some(BTreeSet::from_iter(vec![user])),
+                Some(role),
+                &[],
+                &[],
+                &BTreeSet::new(),
+                &ConfigMapOptions::default(),
+            );
 
             // verify:
-            match tc.expected_output {
-                Ok(expected_yaml) => {
-                    assert!(result.is_ok());
-
-                    // YAML serializer is not preserving orders
-                    let parsed_yaml_expected_result: Result<HashSet<MapUserConfig>, _> =
-                        serde_yaml::from_str(&expected_yaml);
-                    assert!(parsed_yaml_expected_result.is_ok());
+            let aws_auth = res.expect(tc._description);
+            let parsed_aws_auth =
+                KubernetesService::parse_aws_auth(&config_map).expect(tc._description);
+            assert_eq!(aws_auth.users, parsed_aws_auth.users, "{}", tc._description);
+            assert_eq!(aws_auth.roles, parsed_aws_auth.roles, "{}", tc._description);
+            let groups: Vec<String> = parsed_aws_auth
+                .users
+                .iter()
+                .flat_map(|u| u.roles.iter().map(|g| g.to_string()))
+                .collect();
+            assert_eq!(
+                vec![tc.group_name.to_string()],
+                groups,
+                "{}",
+                tc._description
+            );
+        }
+    }
 
-                    let result_yaml_string = result.unwrap_or_default();
-                    let parsed_yaml_result: Result<HashSet<MapUserConfig>, _> =
-                        serde_yaml::from_str(&result_yaml_string);
-                    assert!(parsed_yaml_result.is_ok());
+    #[test]
+    fn validate_generated_content_test() {
+        // setup:
+        let mut user = synced_user();
+        user.synced_by = Some(SyncedBy::IamEksUserMapper);
+        user.synced_at = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200));
+        let aws_auth = AwsAuth {
+            users: BTreeSet::from_iter(vec![user]),
+            roles: BTreeSet::new(),
+        };
+        let config_map = |map_users: &str| {
+            aws_auth_config_map(
+                "1",
+                BTreeMap::from_iter(vec![
+                    ("mapUsers".to_string(), map_users.to_string()),
+                    ("mapRoles".to_string(), "[]\n".to_string()),
+                ]),
+            )
+        };
+        let valid_map_users =
+            KubernetesService::generate_users_config_map_yaml_string(aws_auth.users.clone())
+                .expect("mapUsers should be generated");
 
-                    assert_eq!(
-                        parsed_yaml_expected_result.unwrap_or_default(),
-                        parsed_yaml_result.unwrap_or_default()
-                    );
-                }
-                Err(e) => {
-                    assert!(result.is_err());
-                    assert_eq!(e, result.unwrap_err());
-                }
-            }
+        // execute & verify:
+        assert_eq!(
+            Ok(()),
+            KubernetesService::validate_generated_content(&config_map(&valid_map_users), &aws_auth)
+        );
+        for (map_users, _description) in [
+            (
+                valid_map_users.replace("group_1", "group_2"),
+                "different groups",
+            ),
+            ("[]\n".to_string(), "missing user"),
+            ("- userarn: [arn\n".to_string(), "unparsable content"),
+            (
+                valid_map_users.replace("  syncedBy: iam-eks-user-mapper\n", ""),
+                "lost marker",
+            ),
+            (
+                valid_map_users.replace("2024-01-01T00:00:00Z", "2024-01-02T00:00:00Z"),
+                "different sync time",
+            ),
+            (
+                valid_map_users.replace("arn:test:user_1", "arn:test:USER_1"),
+                "different ARN case",
+            ),
+        ] {
+            assert_ne!(valid_map_users, map_users, "{_description}");
+            let res =
+                KubernetesService::validate_generated_content(&config_map(&map_users), &aws_auth);
+            assert!(
+                matches!(
+                    &res,
+                    Err(KubernetesError::GeneratedContentInvalid { key, generated_content, .. })
+                        if key.as_ref() == "mapUsers" && generated_content.as_ref() == map_users
+                ),
+                "{_description}: {res:?}"
+            );
         }
     }
 
     #[test]
-    fn generate_roles_config_map_yaml_string_test() {
+    fn merge_config_map_aws_accounts_test() {
         // setup:
         struct TestCase<'a> {
-            input: HashSet<KubernetesRole>,
-            expected_output: Result<String, KubernetesError>,
+            existing_map_accounts: Option<&'a str>,
+            previously_managed_accounts: Option<&'a str>,
+            aws_accounts_to_be_added: Vec<&'a str>,
+            expected_map_accounts: Option<Vec<&'a str>>,
+            expected_managed_accounts: Option<&'a str>,
             _description: &'a str,
         }
 
         let test_cases = vec![
             TestCase {
-                input: HashSet::from_iter(vec![KubernetesRole {
-                    role_name: Some("role_1".to_string()),
-                    user_name: None,
-                    iam_role_arn: IamArn::new("arn:test:role_1"),
-                    groups: HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_2"),
-                        KubernetesGroupName::new("group_3"),
-                    ]),
-                    synced_by: None,
-                }]),
-                expected_output: Ok(r"
-- rolearn: arn:test:role_1
-  rolename: role_1
-  groups:
-    - group_2
-    - group_3"
-                    .trim_start()
-                    .to_string()),
-
-                _description: "case 1 - nominal case",
+                existing_map_accounts: None,
+                previously_managed_accounts: None,
+                aws_accounts_to_be_added: vec!["111122223333"],
+                expected_map_accounts: Some(vec!["111122223333"]),
+                expected_managed_accounts: Some("111122223333"),
+                _description: "case 1 - no existing accounts, adding one",
             },
             TestCase {
-                input: HashSet::from_iter(vec![KubernetesRole {
-                    role_name: Some("role_1".to_string()),
-                    user_name: None,
-                    iam_role_arn: IamArn::new("arn:test:role_1"),
-                    groups: HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_2"),
-                        KubernetesGroupName::new("group_3"),
-                    ]),
-                    synced_by: Some(SyncedBy::IamEksUserMapper),
-                }]),
-                expected_output: Ok(r"
-- rolearn: arn:test:role_1
-  rolename: role_1
-  groups:
-    - group_2
-    - group_3
-  syncedBy: iam-eks-user-mapper"
-                    .trim_start()
-                    .to_string()),
-
-                _description: "case 2 - role synced by iam-user-mapper",
+                existing_map_accounts: Some("- \"999988887777\"\n- 444455556666\n"),
+                previously_managed_accounts: None,
+                aws_accounts_to_be_added: vec!["111122223333", "444455556666"],
+                expected_map_accounts: Some(vec!["111122223333", "444455556666", "999988887777"]),
+                expected_managed_accounts: Some("111122223333,444455556666"),
+                _description: "case 2 - existing accounts are merged with new ones",
             },
             TestCase {
-                input: HashSet::from_iter(vec![KubernetesRole {
-                    role_name: Some("role_1".to_string()),
-                    user_name: None,
-                    iam_role_arn: IamArn::new("arn:test:role_1"),
-                    groups: HashSet::from_iter(vec![
-                        KubernetesGroupName::new("group_2"),
-                        KubernetesGroupName::new("group_3"),
-                    ]),
-                    synced_by: Some(SyncedBy::Unknown),
-                }]),
-                expected_output: Ok(r"
-- rolearn: arn:test:role_1
-  rolename: role_1
-  groups:
-    - group_2
-    - group_3
-  syncedBy: some-tool-we-do-not-know"
-                    .trim_start()
-                    .to_string()),
-
-                _description: "case 3 - role synced by unknown",
+                existing_map_accounts: Some("- \"999988887777\"\n- \"111122223333\"\n"),
+                previously_managed_accounts: Some("111122223333"),
+                aws_accounts_to_be_added: vec![],
+                expected_map_accounts: Some(vec!["999988887777"]),
+                expected_managed_accounts: None,
+                _description: "case 3 - previously managed account is removed, others are kept",
+            },
+            TestCase {
+                existing_map_accounts: Some("- \"999988887777\"\n- \"111122223333\"\n"),
+                previously_managed_accounts: Some("111122223333"),
+                aws_accounts_to_be_added: vec!["444455556666"],
+                expected_map_accounts: Some(vec!["444455556666", "999988887777"]),
+                expected_managed_accounts: Some("444455556666"),
+                _description: "case 4 - managed account is replaced",
+            },
+            TestCase {
+                existing_map_accounts: None,
+                previously_managed_accounts: None,
+                aws_accounts_to_be_added: vec![],
+                expected_map_accounts: None,
+                expected_managed_accounts: None,
+                _description: "case 5 - nothing managed, mapAccounts is not created",
             },
         ];
 
         for tc in test_cases {
+            let mut config_map = aws_auth_config_map(
+                "1",
+                BTreeMap::from_iter(
+                    tc.existing_map_accounts
+                        .map(|a| ("mapAccounts".to_string(), a.to_string())),
+                ),
+            );
+            config_map.metadata.annotations = tc.previously_managed_accounts.map(|a| {
+                BTreeMap::from_iter(vec![(
+                    "iam-eks-user-mapper/managed-accounts".to_string(),
+                    a.to_string(),
+                )])
+            });
+
             // execute:
-            let result = KubernetesService::generate_roles_config_map_yaml_string(tc.input);
+            let res = KubernetesService::merge_config_map(
+                &mut config_map,
+                None,
+                None,
+                &[],
+                &[],
+                &BTreeSet::from_iter(
+                    tc.aws_accounts_to_be_added
+                        .into_iter()
+                        .map(AwsAccountId::new),
+                ),
+                &ConfigMapOptions::default(),
+            );
 
             // verify:
-            match tc.expected_output {
-                Ok(expected_yaml) => {
-                    assert!(result.is_ok());
-
-                    // YAML serializer is not preserving orders
-                    let parsed_yaml_expected_result: Result<HashSet<MapRoleConfig>, _> =
-                        serde_yaml::from_str(&expected_yaml);
-                    assert!(parsed_yaml_expected_result.is_ok());
-
-                    let result_yaml_string = result.unwrap_or_default();
-                    let parsed_yaml_result: Result<HashSet<MapRoleConfig>, _> =
-                        serde_yaml::from_str(&result_yaml_string);
-                    assert!(parsed_yaml_result.is_ok());
-
-                    assert_eq!(
-                        parsed_yaml_expected_result.unwrap_or_default(),
-                        parsed_yaml_result.unwrap_or_default()
-                    );
-                }
-                Err(e) => {
-                    assert!(result.is_err());
-                    assert_eq!(e, result.unwrap_err());
-                }
-            }
+            assert!(res.is_ok());
+            let map_accounts = config_map
+                .data
+                .unwrap_or_default()
+                .get("mapAccounts")
+                .map(|a| serde_yaml::from_str::<Vec<String>>(a).expect("invalid mapAccounts"));
+            assert_eq!(
+                tc.expected_map_accounts
+                    .map(|a| a.into_iter().map(String::from).collect::<Vec<_>>()),
+                map_accounts
+            );
+            assert_eq!(
+                tc.expected_managed_accounts.map(String::from),
+                config_map
+                    .metadata
+                    .annotations
+                    .unwrap_or_default()
+                    .get("iam-eks-user-mapper/managed-accounts")
+                    .cloned()
+            );
         }
     }
 }
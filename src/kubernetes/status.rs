@@ -0,0 +1,278 @@
+use crate::kubernetes::aws_auth::{AwsAuth, AwsAuthEntry};
+use crate::kubernetes::{
+    retry_on_transient_error, KubernetesError, KubernetesService, RequestError, SyncedBy,
+};
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::api::{Patch, PatchParams};
+use kube::Api;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::debug;
+
+/// Outcome of the last sync attempt, written into a dedicated config map so it can be read without parsing logs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyncStatus {
+    pub last_attempt_time: SystemTime,
+    pub outcome: SyncStatusOutcome,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SyncStatusOutcome {
+    Succeeded {
+        /// Users and roles synced by this instance.
+        users_synced: usize,
+        roles_synced: usize,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl SyncStatus {
+    pub fn succeeded(aws_auth: &AwsAuth, synced_by: &SyncedBy, time: SystemTime) -> SyncStatus {
+        SyncStatus {
+            last_attempt_time: time,
+            outcome: SyncStatusOutcome::Succeeded {
+                users_synced: aws_auth
+                    .users
+                    .iter()
+                    .filter(|u| u.is_synced_by(synced_by))
+                    .count(),
+                roles_synced: aws_auth
+                    .roles
+                    .iter()
+                    .filter(|r| r.is_synced_by(synced_by))
+                    .count(),
+            },
+        }
+    }
+
+    pub fn failed(error: &impl Display, time: SystemTime) -> SyncStatus {
+        SyncStatus {
+            last_attempt_time: time,
+            outcome: SyncStatusOutcome::Failed {
+                error: error.to_string(),
+            },
+        }
+    }
+
+    /// Config map data keys to be written, `None` ones being removed.
+    /// Keys of the last success are left out on failures, previous values being kept.
+    pub fn data(&self) -> BTreeMap<String, Option<String>> {
+        let last_attempt_time =
+            humantime::format_rfc3339_seconds(self.last_attempt_time).to_string();
+        let mut data = BTreeMap::from_iter(vec![
+            (
+                "last_attempt_time".to_string(),
+                Some(last_attempt_time.clone()),
+            ),
+            (
+                "version".to_string(),
+                Some(env!("CARGO_PKG_VERSION").to_string()),
+            ),
+        ]);
+        match &self.outcome {
+            SyncStatusOutcome::Succeeded {
+                users_synced,
+                roles_synced,
+            } => data.extend(vec![
+                ("last_success_time".to_string(), Some(last_attempt_time)),
+                ("users_synced".to_string(), Some(users_synced.to_string())),
+                ("roles_synced".to_string(), Some(roles_synced.to_string())),
+                ("last_error".to_string(), None),
+            ]),
+            SyncStatusOutcome::Failed { error } => {
+                data.insert("last_error".to_string(), Some(error.clone()));
+            }
+        }
+
+        data
+    }
+}
+
+impl KubernetesService {
+    /// Writes `status` into config map `namespace/name`, creating it if needed. Nothing is written in dry run.
+    pub async fn write_sync_status(
+        &self,
+        namespace: &str,
+        name: &str,
+        status: &SyncStatus,
+    ) -> Result<(), KubernetesError> {
+        if self.options.dry_run {
+            debug!("Dry run, not writing sync status into config map `{namespace}/{name}`");
+            return Ok(());
+        }
+
+        let api: Api<ConfigMap> = Api::namespaced(self.client.clone(), namespace);
+        let data = status.data();
+        let (patch_params, patch) = (
+            PatchParams {
+                dry_run: self.options.server_dry_run,
+                ..Default::default()
+            },
+            // null values remove keys, missing ones are kept
+            Patch::Merge(serde_json::json!({ "data": data })),
+        );
+        let patched = retry_on_transient_error(
+            &format!("write sync status into config map `{namespace}/{name}`"),
+            || api.patch(name, &patch_params, &patch),
+        )
+        .await;
+
+        match patched {
+            Ok(_) => Ok(()),
+            Err(RequestError::Failed(kube::Error::Api(e))) if e.code == 404 => {
+                let config_map = ConfigMap {
+                    metadata: ObjectMeta {
+                        name: Some(name.to_string()),
+                        namespace: Some(namespace.to_string()),
+                        ..Default::default()
+                    },
+                    data: Some(
+                        data.into_iter()
+                            .filter_map(|(k, v)| Some((k, v?)))
+                            .collect(),
+                    ),
+                    ..Default::default()
+                };
+                api.create(&self.post_params(), &config_map)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| KubernetesError::ConfigMapCannotBeCreated {
+                        config_map_name: Arc::from(name),
+                        config_map_namespace: Arc::from(namespace),
+                        raw_message: Arc::from(e.to_string()),
+                    })
+            }
+            Err(RequestError::Unreachable(e)) => Err(e),
+            Err(RequestError::Failed(e)) => Err(KubernetesError::ConfigMapCannotBePatched {
+                config_map_name: Arc::from(name),
+                config_map_namespace: Arc::from(namespace),
+                attempts: 1,
+                raw_message: Arc::from(e.to_string()),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::AwsAuth;
+    use crate::kubernetes::status::SyncStatus;
+    use crate::kubernetes::{
+        IamArn, IamUserName, KubernetesError, KubernetesRole, KubernetesUser, SyncedBy,
+    };
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn sync_status_data_test() {
+        // setup:
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let user = |name: &str, synced_by: Option<SyncedBy>| {
+            KubernetesUser::new(
+                IamUserName::new(name),
+                IamArn::new(&format!("arn:aws:iam::123:user/{name}")),
+                BTreeSet::new(),
+                synced_by,
+            )
+        };
+        let aws_auth = AwsAuth {
+            users: BTreeSet::from_iter(vec![
+                user("alice", Some(SyncedBy::IamEksUserMapper)),
+                user("bob", Some(SyncedBy::IamEksUserMapper)),
+                user("manual", None),
+                user("other", Some(SyncedBy::new("other-instance"))),
+            ]),
+            roles: BTreeSet::from_iter(vec![KubernetesRole::new(
+                IamArn::new("arn:aws:iam::123:role/karpenter"),
+                None,
+                None,
+                BTreeSet::new(),
+                Some(SyncedBy::IamEksUserMapper),
+            )]),
+        };
+        let entry = |key: &str, value: Option<&str>| (key.to_string(), value.map(String::from));
+        let version = env!("CARGO_PKG_VERSION");
+
+        struct TestCase<'a> {
+            input: SyncStatus,
+            expected: BTreeMap<String, Option<String>>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: SyncStatus::succeeded(&aws_auth, &SyncedBy::IamEksUserMapper, time),
+                expected: BTreeMap::from_iter(vec![
+                    entry("last_attempt_time", Some("2023-11-14T22:13:20Z")),
+                    entry("last_error", None),
+                    entry("last_success_time", Some("2023-11-14T22:13:20Z")),
+                    entry("roles_synced", Some("1")),
+                    entry("users_synced", Some("2")),
+                    entry("version", Some(version)),
+                ]),
+                _description: "case 1 - success clears last error",
+            },
+            TestCase {
+                input: SyncStatus::failed(
+                    &KubernetesError::ClusterUnreachable {
+                        raw_message: Arc::from("connection refused"),
+                    },
+                    time,
+                ),
+                expected: BTreeMap::from_iter(vec![
+                    entry("last_attempt_time", Some("2023-11-14T22:13:20Z")),
+                    entry(
+                        "last_error",
+                        Some("Cluster not reachable: connection refused"),
+                    ),
+                    entry("version", Some(version)),
+                ]),
+                _description: "case 2 - failure keeps last success keys untouched",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = tc.input.data();
+
+            // verify:
+            assert_eq!(tc.expected, res, "{}", tc._description);
+        }
+    }
+
+    #[test]
+    fn sync_status_patch_test() {
+        // setup:
+        let status = SyncStatus::failed(&"IAM is down", SystemTime::UNIX_EPOCH);
+
+        // execute:
+        let res = serde_json::json!({ "data": status.data() });
+
+        // verify:
+        assert_eq!(
+            serde_json::json!({
+                "data": {
+                    "last_attempt_time": "1970-01-01T00:00:00Z",
+                    "last_error": "IAM is down",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }
+            }),
+            res
+        );
+        let success = SyncStatus::succeeded(
+            &AwsAuth::default(),
+            &SyncedBy::IamEksUserMapper,
+            SystemTime::UNIX_EPOCH,
+        );
+        assert_eq!(
+            serde_json::Value::Null,
+            serde_json::json!({ "data": success.data() })["data"]["last_error"]
+        );
+    }
+}
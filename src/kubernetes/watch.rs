@@ -0,0 +1,182 @@
+use crate::kubernetes::{KubernetesService, CONTENT_HASH_ANNOTATION};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::Api;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+impl KubernetesService {
+    /// Watches config map `config_map_namespace/config_map_name`, notifying `changes` each time it's modified by someone else than the tool.
+    /// API server disconnections are retried with backoff, returns only once `changes` receiver is dropped.
+    pub async fn watch_config_map(
+        &self,
+        config_map_namespace: &str,
+        config_map_name: &str,
+        changes: mpsc::Sender<()>,
+    ) {
+        let config_maps_api: Api<ConfigMap> =
+            Api::namespaced(self.client.clone(), config_map_namespace);
+        let mut watcher_config =
+            watcher::Config::default().fields(&format!("metadata.name={config_map_name}"));
+        if let Some(request_timeout) = self.request_timeout {
+            // idle watches would otherwise be cut by the request timeout, the server has to end them before
+            watcher_config = watcher_config.timeout(Self::watch_timeout_seconds(request_timeout));
+        }
+        let mut events = watcher(config_maps_api, watcher_config)
+            .default_backoff()
+            .applied_objects()
+            .boxed();
+
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(config_map) if Self::config_map_modified_externally(&config_map) => {
+                    info!("Config map `{config_map_namespace}/{config_map_name}` has been modified outside of the tool");
+                    // a full channel means a sync is already pending, changes are coalesced
+                    if let Err(mpsc::error::TrySendError::Closed(_)) = changes.try_send(()) {
+                        return;
+                    }
+                }
+                Ok(_) => debug!(
+                    "Config map `{config_map_namespace}/{config_map_name}` modification comes from the tool, ignoring it"
+                ),
+                Err(e) => warn!(
+                    "Error while watching config map `{config_map_namespace}/{config_map_name}`, retrying: {e}"
+                ),
+            }
+        }
+    }
+
+    /// Server side timeout of watch requests, shorter than the client side `request_timeout`.
+    pub(crate) fn watch_timeout_seconds(request_timeout: Duration) -> u32 {
+        // kube default watch timeout
+        const MAX_WATCH_TIMEOUT_SECONDS: u64 = 290;
+
+        request_timeout
+            .as_secs()
+            .saturating_sub(1)
+            .clamp(1, MAX_WATCH_TIMEOUT_SECONDS) as u32
+    }
+
+    /// Whether `config_map` content differs from the one the tool last wrote, according to its content hash annotation.
+    /// Config maps without annotation, e.q: written with annotations disabled, are considered modified externally.
+    fn config_map_modified_externally(config_map: &ConfigMap) -> bool {
+        let content_hash = config_map
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(CONTENT_HASH_ANNOTATION));
+
+        match (content_hash, Self::parse_aws_auth(config_map)) {
+            (Some(content_hash), Ok(aws_auth)) => {
+                content_hash != &format!("{:016x}", aws_auth.content_hash())
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::{KubernetesService, CONTENT_HASH_ANNOTATION};
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn watch_timeout_seconds_test() {
+        assert_eq!(
+            1,
+            KubernetesService::watch_timeout_seconds(Duration::from_secs(1))
+        );
+        assert_eq!(
+            9,
+            KubernetesService::watch_timeout_seconds(Duration::from_secs(10))
+        );
+        assert_eq!(
+            290,
+            KubernetesService::watch_timeout_seconds(Duration::from_secs(3600))
+        );
+    }
+
+    #[test]
+    fn config_map_modified_externally_test() {
+        // setup:
+        struct TestCase<'a> {
+            map_users: &'a str,
+            content_hash_annotation: Option<String>,
+            expected_output: bool,
+            _description: &'a str,
+        }
+
+        let map_users = "- userarn: arn:test:user_1\n  username: user_1\n  groups:\n    - group_1\n  syncedBy: iam-eks-user-mapper\n";
+        let content_hash = |map_users: &str| {
+            let config_map = ConfigMap {
+                data: Some(BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    map_users.to_string(),
+                )])),
+                ..Default::default()
+            };
+            format!(
+                "{:016x}",
+                KubernetesService::parse_aws_auth(&config_map)
+                    .expect("mapUsers should be valid")
+                    .content_hash()
+            )
+        };
+
+        let test_cases = vec![
+            TestCase {
+                map_users,
+                content_hash_annotation: Some(content_hash(map_users)),
+                expected_output: false,
+                _description: "content written by the tool",
+            },
+            TestCase {
+                map_users: "- userarn: arn:test:user_2\n  username: user_2\n  groups: []\n",
+                content_hash_annotation: Some(content_hash(map_users)),
+                expected_output: true,
+                _description: "content modified after the tool wrote it",
+            },
+            TestCase {
+                map_users,
+                content_hash_annotation: None,
+                expected_output: true,
+                _description: "no content hash annotation",
+            },
+            TestCase {
+                map_users: "not: [valid",
+                content_hash_annotation: Some(content_hash(map_users)),
+                expected_output: true,
+                _description: "content cannot be parsed",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let config_map = ConfigMap {
+                metadata: ObjectMeta {
+                    annotations: tc.content_hash_annotation.map(|content_hash| {
+                        BTreeMap::from_iter(vec![(
+                            CONTENT_HASH_ANNOTATION.to_string(),
+                            content_hash,
+                        )])
+                    }),
+                    ..Default::default()
+                },
+                data: Some(BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    tc.map_users.to_string(),
+                )])),
+                ..Default::default()
+            };
+            let res = KubernetesService::config_map_modified_externally(&config_map);
+
+            // verify:
+            assert_eq!(tc.expected_output, res, "{}", tc._description);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::sync::OnceLock;
+
+/// Plain scalars read as strings by YAML 1.2 but typed by YAML 1.1 parsers, e.q: `yes`, `1_000`, `1:20`, `2001-12-14`.
+fn yaml_1_1_typed_scalar_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(?:y|Y|yes|Yes|YES|n|N|no|No|NO|on|On|ON|off|Off|OFF|<<|=",
+            r"|[-+]?(?:0b[01_]+|0o?[0-7_]+|0x[0-9a-fA-F_]+|[0-9][0-9_]*|[1-9][0-9_]*(?::[0-5]?[0-9])+)",
+            r"|[-+]?(?:[0-9][0-9_]*)?\.[0-9_]*(?:[eE][-+]?[0-9]+)?|[-+]?[0-9][0-9_]*[eE][-+]?[0-9]+",
+            r"|[-+]?[0-9][0-9_]*(?::[0-5]?[0-9])+\.[0-9_]*",
+            r"|[0-9]{4}-[0-9]{1,2}-[0-9]{1,2}(?:[Tt ].*)?)$",
+        ))
+        .expect("YAML 1.1 scalar pattern should be valid")
+    })
+}
+
+/// Serializes a value as block YAML readable by YAML 1.1 parsers.
+///
+/// serde_yaml emits YAML 1.2 where e.q: `yes` or `1_000` are plain strings, while aws-iam-authenticator reads
+/// aws-auth with a YAML 1.1 parser typing them as booleans or numbers, failing to parse the whole `mapUsers` or
+/// `mapRoles`. Nodes are laid out the way serde_yaml does, each string being single quoted as it is written
+/// whenever YAML 1.1 would type it.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_yaml::Error> {
+    let lines = match node(&serde_yaml::to_value(value)?)? {
+        Node::Scalar(lines) => lines,
+        Node::Block { tag, lines, .. } => tag.into_iter().chain(lines).collect(),
+    };
+
+    let mut yaml = lines.join("\n");
+    yaml.push('\n');
+    Ok(yaml)
+}
+
+/// Lines of a serialized node, relative to the column the node starts at.
+enum Node {
+    /// Written after its key or sequence entry dash, e.q: `'yes'` or a `|-` block scalar.
+    Scalar(Vec<String>),
+    /// Written below its key or sequence entry dash, e.q: a non empty mapping.
+    Block {
+        tag: Option<String>,
+        lines: Vec<String>,
+        is_sequence: bool,
+    },
+}
+
+fn node(value: &Value) -> Result<Node, serde_yaml::Error> {
+    match value {
+        Value::String(s) => scalar(s),
+        Value::Sequence(sequence) if !sequence.is_empty() => {
+            let mut lines = Vec::new();
+            for entry in sequence {
+                match node(entry)? {
+                    Node::Block {
+                        tag: Some(tag),
+                        lines: entry_lines,
+                        ..
+                    } => {
+                        lines.push(format!("- {tag}"));
+                        lines.extend(entry_lines.iter().map(|line| indent(line)));
+                    }
+                    Node::Block {
+                        lines: entry_lines, ..
+                    } => {
+                        let mut entry_lines = entry_lines.into_iter();
+                        lines.extend(entry_lines.next().map(|line| format!("- {line}")));
+                        lines.extend(entry_lines.map(|line| indent(&line)));
+                    }
+                    // block scalar content only has to be indented deeper than the sequence
+                    Node::Scalar(entry_lines) => {
+                        let mut entry_lines = entry_lines.into_iter();
+                        lines.extend(entry_lines.next().map(|line| format!("- {line}")));
+                        lines.extend(entry_lines);
+                    }
+                }
+            }
+            Ok(Node::Block {
+                tag: None,
+                lines,
+                is_sequence: true,
+            })
+        }
+        Value::Mapping(mapping) if !mapping.is_empty() => {
+            let mut lines = Vec::new();
+            for (key, value) in mapping {
+                let key = key_scalar(key)?;
+                match node(value)? {
+                    Node::Scalar(value_lines) => {
+                        let mut value_lines = value_lines.into_iter();
+                        lines.extend(value_lines.next().map(|line| format!("{key}: {line}")));
+                        lines.extend(value_lines);
+                    }
+                    Node::Block {
+                        tag,
+                        lines: value_lines,
+                        is_sequence,
+                    } => {
+                        match &tag {
+                            Some(tag) => lines.push(format!("{key}: {tag}")),
+                            None => lines.push(format!("{key}:")),
+                        }
+                        // sequences are not indented below their key, e.q: `groups:\n- view`
+                        match is_sequence && tag.is_none() {
+                            true => lines.extend(value_lines),
+                            false => lines.extend(value_lines.iter().map(|line| indent(line))),
+                        }
+                    }
+                }
+            }
+            Ok(Node::Block {
+                tag: None,
+                lines,
+                is_sequence: false,
+            })
+        }
+        Value::Tagged(tagged) => match node(&tagged.value)? {
+            Node::Scalar(mut lines) => {
+                if let Some(first_line) = lines.first_mut() {
+                    *first_line = format!("{} {first_line}", tagged.tag);
+                }
+                Ok(Node::Scalar(lines))
+            }
+            Node::Block {
+                lines, is_sequence, ..
+            } => Ok(Node::Block {
+                tag: Some(tagged.tag.to_string()),
+                lines,
+                is_sequence,
+            }),
+        },
+        // null, booleans, numbers and empty collections, e.q: `[]`
+        value => serde_yaml_scalar(value),
+    }
+}
+
+/// Single quotes strings YAML 1.2 reads as strings but YAML 1.1 would type, other strings being left to serde_yaml.
+fn scalar(s: &str) -> Result<Node, serde_yaml::Error> {
+    if yaml_1_1_typed_scalar_pattern().is_match(s)
+        && matches!(serde_yaml::from_str(s), Ok(Value::String(parsed)) if parsed == s)
+    {
+        return Ok(Node::Scalar(vec![format!("'{s}'")]));
+    }
+
+    serde_yaml_scalar(s)
+}
+
+fn serde_yaml_scalar<T: Serialize + ?Sized>(value: &T) -> Result<Node, serde_yaml::Error> {
+    let yaml = serde_yaml::to_string(value)?;
+    let yaml = yaml.strip_suffix('\n').unwrap_or(&yaml);
+    Ok(Node::Scalar(yaml.split('\n').map(str::to_string).collect()))
+}
+
+/// Mapping keys fit on their line, multi-line ones being double quoted, e.q: `"a\nb"`.
+fn key_scalar(key: &Value) -> Result<String, serde_yaml::Error> {
+    match node(key)? {
+        Node::Scalar(lines) if lines.len() == 1 => Ok(lines.concat()),
+        _ => match key {
+            Value::String(s) => serde_json::to_string(s).map_err(serde::ser::Error::custom),
+            _ => Err(serde::ser::Error::custom(
+                "mapping keys should be scalars or strings",
+            )),
+        },
+    }
+}
+
+/// Nested lines, blank lines of block scalars being kept without trailing spaces.
+fn indent(line: &str) -> String {
+    match line.is_empty() {
+        true => String::new(),
+        false => format!("  {line}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn to_string_yaml_1_1_scalars_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: Vec<&'a str>,
+            expected_output: &'a str,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: vec!["arn:aws:iam::123456789012:user/user_1", "system:masters", "view"],
+                expected_output: "- arn:aws:iam::123456789012:user/user_1\n- system:masters\n- view\n",
+                _description: "case 1 - nothing to quote",
+            },
+            TestCase {
+                input: vec!["yes", "no", "on", "off"],
+                expected_output: "- 'yes'\n- 'no'\n- 'on'\n- 'off'\n",
+                _description: "case 2 - YAML 1.1 booleans",
+            },
+            TestCase {
+                input: vec!["y", "Y", "n", "N", "Yes", "YES", "No", "NO", "On", "ON", "Off", "OFF"],
+                expected_output: "- 'y'\n- 'Y'\n- 'n'\n- 'N'\n- 'Yes'\n- 'YES'\n- 'No'\n- 'NO'\n- 'On'\n- 'ON'\n- 'Off'\n- 'OFF'\n",
+                _description: "case 3 - YAML 1.1 booleans, single letters and capitalized",
+            },
+            TestCase {
+                input: vec!["0755", "-017", "0o17", "0b1010", "0x_1F"],
+                expected_output: "- '0755'\n- '-017'\n- '0o17'\n- '0b1010'\n- '0x_1F'\n",
+                _description: "case 4 - YAML 1.1 octal, binary and hexadecimal integers",
+            },
+            TestCase {
+                input: vec!["1:20", "190:20:30", "-1:20", "1:20.5", "190:20:30.15"],
+                expected_output: "- '1:20'\n- '190:20:30'\n- '-1:20'\n- '1:20.5'\n- '190:20:30.15'\n",
+                _description: "case 5 - YAML 1.1 sexagesimal integers and floats",
+            },
+            TestCase {
+                input: vec!["1_000", "1_0.5", "2001-12-14", "2001-12-14T21:59:43.10-05:00", "<<", "="],
+                expected_output: "- '1_000'\n- '1_0.5'\n- '2001-12-14'\n- '2001-12-14T21:59:43.10-05:00'\n- '<<'\n- '='\n",
+                _description: "case 6 - YAML 1.1 underscored numbers, timestamps, merge and value keys",
+            },
+            TestCase {
+                input: vec!["true", "1000", "1.5", "null", "~"],
+                expected_output: "- 'true'\n- '1000'\n- '1.5'\n- 'null'\n- '~'\n",
+                _description: "case 7 - strings typed by YAML 1.2 are quoted by serde_yaml",
+            },
+            TestCase {
+                input: vec!["1:60", "yess", "o", "2001-12", "arn:aws:iam::123456789012:user/on"],
+                expected_output: "- 1:60\n- yess\n- o\n- 2001-12\n- arn:aws:iam::123456789012:user/on\n",
+                _description: "case 8 - strings read as strings by YAML 1.1 are left plain",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = super::to_string(&tc.input);
+
+            // verify:
+            assert_eq!(
+                tc.expected_output,
+                result.expect("strings should be serializable"),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn to_string_layout_test() {
+        // setup:
+        struct TestCase<'a> {
+            input: &'a str,
+            expected_output: &'a str,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: "- username: 'on'\n  'yes': 'off'\n  groups:\n  - - 'yes'\n    - '1_000'\n  x-empty: []\n",
+                expected_output: "- username: 'on'\n  'yes': 'off'\n  groups:\n  - - 'yes'\n    - '1_000'\n  x-empty: []\n",
+                _description: "case 1 - mapping keys and values, nested and empty sequences",
+            },
+            TestCase {
+                input: "- description: |-\n    yes\n    - no\n    on: off\n  groups:\n  - |-\n    a\n\n    - yes\n  - 'yes'\n",
+                expected_output: "- description: |-\n    yes\n    - no\n    on: off\n  groups:\n  - |-\n    a\n\n    - yes\n  - 'yes'\n",
+                _description: "case 2 - block scalar content is left as is",
+            },
+            TestCase {
+                input: "- x-owner:\n    team: 'no'\n    count: 2\n    enabled: true\n    tags: !set\n      a: null\n",
+                expected_output: "- x-owner:\n    team: 'no'\n    count: 2\n    enabled: true\n    tags: !set\n      a: null\n",
+                _description: "case 3 - nested mappings, non string scalars and tags",
+            },
+            TestCase {
+                input: "[]\n",
+                expected_output: "[]\n",
+                _description: "case 4 - no entries",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let value: Value = serde_yaml::from_str(tc.input).expect("input should be valid YAML");
+
+            // execute:
+            let result = super::to_string(&value).expect("value should be serializable");
+
+            // verify:
+            assert_eq!(tc.expected_output, result, "{}", tc._description);
+            assert_eq!(
+                value,
+                serde_yaml::from_str::<Value>(&result).expect("output should be valid YAML"),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn to_string_multi_line_key_test() {
+        // setup:
+        let value = BTreeMap::from_iter(vec![("a\nb".to_string(), "yes".to_string())]);
+
+        // execute:
+        let result = super::to_string(&value).expect("value should be serializable");
+
+        // verify:
+        assert_eq!("\"a\\nb\": 'yes'\n", result);
+        assert_eq!(
+            value,
+            serde_yaml::from_str::<BTreeMap<String, String>>(&result)
+                .expect("output should be valid YAML")
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! Gives selected AWS IAM users and roles access to EKS clusters, syncing them into the aws-auth config map
+//! or EKS access entries.
+//!
+//! The `iam-eks-user-mapper` binary is a thin wrapper around [`cli::execute`], the sync logic being usable on its own
+//! through [`run_sync_once`] and [`run_forever`], e.q: from another operator.
+
+// traits are implemented by the crate clients and fakes only, none needing `Send` bounds on their futures
+#![allow(async_fn_in_trait)]
+
+pub mod arn;
+mod audit;
+pub mod aws;
+pub mod cli;
+pub mod config;
+mod config_file;
+pub mod errors;
+mod export;
+mod health;
+mod heartbeat;
+pub mod kubernetes;
+mod notification;
+mod preflight;
+mod sync;
+mod telemetry;
+mod trigger;
+
+pub use sync::{run_forever, run_sync_once};
+pub use trigger::SyncReport;
@@ -0,0 +1,374 @@
+use crate::audit::{AuditedChanges, AuditedEntries};
+use crate::kubernetes::aws_auth::{AwsAuthDiff, AwsAuthDiffSummary};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+use tracing::warn;
+
+pub mod slack;
+pub mod webhook;
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum NotificationError {
+    #[error("Invalid notification sink configuration: {raw_message}")]
+    InvalidConfiguration { raw_message: Arc<str> },
+    #[error("Cannot serialize notification: {raw_message}")]
+    CannotSerialize { raw_message: Arc<str> },
+    #[error("Cannot send notification to `{sink}`: {raw_message}")]
+    CannotSend {
+        sink: Arc<str>,
+        raw_message: Arc<str>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationKind {
+    /// Entries have been added, removed or modified.
+    AccessChanged,
+    /// Sync failed, cluster access may not reflect IAM anymore.
+    SyncFailed,
+}
+
+/// What is sent to notification sinks, entries being sorted by ARN.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    /// Cluster the sync targets, e.q: EKS cluster name or API server URL.
+    pub cluster: String,
+    pub timestamp: String,
+    pub added: AuditedEntries,
+    pub removed: AuditedEntries,
+    pub modified: AuditedChanges,
+    /// Number of users and roles added, removed, modified and kept as is.
+    pub summary: AwsAuthDiffSummary,
+    pub error: Option<String>,
+}
+
+impl Notification {
+    pub fn access_changed(
+        cluster: &str,
+        diff: &AwsAuthDiff,
+        timestamp: SystemTime,
+    ) -> Notification {
+        Notification {
+            kind: NotificationKind::AccessChanged,
+            cluster: cluster.to_string(),
+            timestamp: humantime::format_rfc3339_seconds(timestamp).to_string(),
+            added: AuditedEntries::added(diff),
+            removed: AuditedEntries::removed(diff),
+            modified: AuditedChanges::modified(diff),
+            summary: diff.summary(),
+            error: None,
+        }
+    }
+
+    pub fn sync_failed(cluster: &str, error: &str, timestamp: SystemTime) -> Notification {
+        Notification {
+            kind: NotificationKind::SyncFailed,
+            cluster: cluster.to_string(),
+            timestamp: humantime::format_rfc3339_seconds(timestamp).to_string(),
+            added: AuditedEntries::default(),
+            removed: AuditedEntries::default(),
+            modified: AuditedChanges::default(),
+            summary: AwsAuthDiffSummary::default(),
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, NotificationError> {
+        serde_json::to_string(self).map_err(|e| NotificationError::CannotSerialize {
+            raw_message: Arc::from(e.to_string()),
+        })
+    }
+}
+
+/// Somewhere cluster access changes are reported to, e.q: chat or incident tooling.
+pub trait NotificationSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotificationError>;
+
+    /// Minimum duration between two logged failures, e.q: not to flood logs while a chat is down.
+    fn failure_log_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Tells whether a sink failure should be logged, counting the ones skipped in between.
+#[derive(Debug, Default)]
+struct FailureLogLimiter {
+    last_logged_at: Option<Instant>,
+    skipped: u32,
+}
+
+impl FailureLogLimiter {
+    /// Number of failures skipped since the last logged one if the failure happening at `now` should be logged.
+    fn should_log(&mut self, interval: Duration, now: Instant) -> Option<u32> {
+        match self.last_logged_at {
+            Some(last_logged_at) if now.saturating_duration_since(last_logged_at) < interval => {
+                self.skipped += 1;
+                None
+            }
+            _ => {
+                self.last_logged_at = Some(now);
+                Some(std::mem::take(&mut self.skipped))
+            }
+        }
+    }
+}
+
+/// Sends notifications to every sink, failures being only logged not to fail the sync.
+pub struct Notifier<S> {
+    sinks: Vec<S>,
+    /// Failures logs of each of `sinks`, in the same order.
+    failure_logs: Vec<Mutex<FailureLogLimiter>>,
+    notify_on_failure: bool,
+}
+
+impl<S: NotificationSink> Notifier<S> {
+    pub fn new(sinks: Vec<S>, notify_on_failure: bool) -> Notifier<S> {
+        Notifier {
+            failure_logs: sinks.iter().map(|_| Mutex::default()).collect(),
+            sinks,
+            notify_on_failure,
+        }
+    }
+
+    /// Notifies `diff` if any entry changed.
+    pub async fn sync_succeeded(&self, cluster: &str, diff: &AwsAuthDiff) {
+        if diff.is_empty() {
+            return;
+        }
+        self.notify(&Notification::access_changed(
+            cluster,
+            diff,
+            SystemTime::now(),
+        ))
+        .await
+    }
+
+    /// Notifies `error` if failures are to be notified.
+    pub async fn sync_failed(&self, cluster: &str, error: &impl ToString) {
+        if !self.notify_on_failure {
+            return;
+        }
+        self.notify(&Notification::sync_failed(
+            cluster,
+            &error.to_string(),
+            SystemTime::now(),
+        ))
+        .await
+    }
+
+    async fn notify(&self, notification: &Notification) {
+        for (sink, failure_log) in self.sinks.iter().zip(&self.failure_logs) {
+            if let Err(e) = sink.notify(notification).await {
+                let skipped = failure_log
+                    .lock()
+                    .expect("cannot lock failure log")
+                    .should_log(sink.failure_log_interval(), Instant::now());
+                match skipped {
+                    Some(0) => warn!("Error while sending notification: {e}"),
+                    Some(skipped) => warn!("Error while sending notification ({skipped} similar errors not logged): {e}"),
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::{AwsAuthDiff, EntriesDiff};
+    use crate::kubernetes::tests::iam_user;
+    use crate::notification::{
+        FailureLogLimiter, Notification, NotificationError, NotificationSink, Notifier,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant, SystemTime};
+
+    /// Records received notifications as JSON, or fails every call if `failing`.
+    #[derive(Default)]
+    struct RecordingSink {
+        notifications: Mutex<Vec<serde_json::Value>>,
+        calls: AtomicUsize,
+        failing: bool,
+    }
+
+    impl NotificationSink for RecordingSink {
+        async fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing {
+                return Err(NotificationError::CannotSend {
+                    sink: "recording".into(),
+                    raw_message: "unavailable".into(),
+                });
+            }
+            self.notifications
+                .lock()
+                .expect("cannot lock notifications")
+                .push(serde_json::from_str(&notification.to_json()?).expect("invalid JSON"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn notification_json_schema_test() {
+        // setup:
+        let diff = AwsAuthDiff {
+            users: EntriesDiff {
+                added: vec![iam_user("alice", &["admin", "dev"])],
+                removed: vec![iam_user("bob", &["view"])],
+                unchanged: vec![iam_user("carol", &["view"]), iam_user("dave", &["view"])],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        // execute:
+        let changed = Notification::access_changed("my-cluster", &diff, timestamp).to_json();
+        let failed =
+            Notification::sync_failed("my-cluster", "IAM unreachable", timestamp).to_json();
+
+        // verify:
+        assert_eq!(
+            serde_json::json!({
+                "kind": "accessChanged",
+                "cluster": "my-cluster",
+                "timestamp": "2023-11-14T22:13:20Z",
+                "added": {
+                    "users": [
+                        {
+                            "username": "alice",
+                            "arn": "arn:aws:iam::123456789012:user/alice",
+                            "groups": ["admin", "dev"],
+                            "syncedBy": "iam-eks-user-mapper",
+                        },
+                    ],
+                    "roles": [],
+                },
+                "removed": {
+                    "users": [
+                        {
+                            "username": "bob",
+                            "arn": "arn:aws:iam::123456789012:user/bob",
+                            "groups": ["view"],
+                            "syncedBy": "iam-eks-user-mapper",
+                        },
+                    ],
+                    "roles": [],
+                },
+                "modified": {"users": [], "roles": []},
+                "summary": {
+                    "users": {"added": 1, "removed": 1, "modified": 0, "unchanged": 2},
+                    "roles": {"added": 0, "removed": 0, "modified": 0, "unchanged": 0},
+                },
+                "error": null,
+            }),
+            serde_json::from_str::<serde_json::Value>(&changed.unwrap()).unwrap()
+        );
+        assert_eq!(
+            serde_json::json!({
+                "kind": "syncFailed",
+                "cluster": "my-cluster",
+                "timestamp": "2023-11-14T22:13:20Z",
+                "added": {"users": [], "roles": []},
+                "removed": {"users": [], "roles": []},
+                "modified": {"users": [], "roles": []},
+                "summary": {
+                    "users": {"added": 0, "removed": 0, "modified": 0, "unchanged": 0},
+                    "roles": {"added": 0, "removed": 0, "modified": 0, "unchanged": 0},
+                },
+                "error": "IAM unreachable",
+            }),
+            serde_json::from_str::<serde_json::Value>(&failed.unwrap()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn notifier_test() {
+        // setup:
+        let diff = AwsAuthDiff {
+            users: EntriesDiff {
+                added: vec![iam_user("alice", &["dev"])],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for notify_on_failure in [false, true] {
+            let notifier = Notifier::new(
+                vec![
+                    RecordingSink {
+                        failing: true,
+                        ..Default::default()
+                    },
+                    RecordingSink::default(),
+                ],
+                notify_on_failure,
+            );
+
+            // execute:
+            notifier
+                .sync_succeeded("my-cluster", &AwsAuthDiff::default())
+                .await;
+            notifier.sync_succeeded("my-cluster", &diff).await;
+            notifier.sync_failed("my-cluster", &"IAM unreachable").await;
+
+            // verify:
+            let expected_notifications = match notify_on_failure {
+                true => 2,
+                false => 1,
+            };
+            // a failing sink doesn't prevent others from being notified
+            assert_eq!(
+                expected_notifications,
+                notifier.sinks[0].calls.load(Ordering::SeqCst)
+            );
+            let notifications = notifier.sinks[1].notifications.lock().unwrap();
+            assert_eq!(expected_notifications, notifications.len());
+            assert_eq!("accessChanged", notifications[0]["kind"]);
+            if notify_on_failure {
+                assert_eq!("syncFailed", notifications[1]["kind"]);
+            }
+        }
+    }
+
+    #[test]
+    fn failure_log_limiter_test() {
+        // setup:
+        let interval = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut limiter = FailureLogLimiter::default();
+
+        // execute & verify:
+        assert_eq!(Some(0), limiter.should_log(interval, start));
+        assert_eq!(
+            None,
+            limiter.should_log(interval, start + Duration::from_secs(10))
+        );
+        assert_eq!(
+            None,
+            limiter.should_log(interval, start + Duration::from_secs(59))
+        );
+        assert_eq!(
+            Some(2),
+            limiter.should_log(interval, start + Duration::from_secs(60))
+        );
+        assert_eq!(
+            None,
+            limiter.should_log(interval, start + Duration::from_secs(61))
+        );
+        assert_eq!(
+            Some(1),
+            limiter.should_log(interval, start + Duration::from_secs(200))
+        );
+        // no interval, every failure is logged
+        assert_eq!(
+            Some(0),
+            limiter.should_log(Duration::ZERO, start + Duration::from_secs(200))
+        );
+    }
+}
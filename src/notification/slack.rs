@@ -0,0 +1,387 @@
+use crate::notification::webhook::WebhookSink;
+use crate::notification::{Notification, NotificationError, NotificationKind, NotificationSink};
+use serde::Serialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Changes listed in a message, others being only counted, a long list being unreadable in a channel anyway.
+const SLACK_MAX_LISTED_CHANGES: usize = 20;
+/// Maximum length of a section block text, Slack rejecting longer ones.
+const SLACK_SECTION_MAX_CHARS: usize = 3000;
+/// Minimum duration between two logged failures, not to flood logs while Slack is unreachable.
+const SLACK_FAILURE_LOG_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Slack incoming webhook payload, `text` being shown in push notifications and `blocks` in the channel.
+#[derive(Debug, Serialize)]
+struct SlackMessage {
+    text: String,
+    blocks: Vec<serde_json::Value>,
+}
+
+impl SlackMessage {
+    fn new(notification: &Notification) -> SlackMessage {
+        let cluster = escape(&notification.cluster);
+        let (title, body) = match notification.kind {
+            NotificationKind::AccessChanged => (
+                format!("IAM access changed on `{cluster}`"),
+                changes_text(notification),
+            ),
+            NotificationKind::SyncFailed => (
+                format!("IAM sync failed on `{cluster}`"),
+                // room kept for code block delimiters
+                format!(
+                    "```{}```",
+                    truncate(
+                        &escape(notification.error.as_deref().unwrap_or_default()),
+                        SLACK_SECTION_MAX_CHARS - 6
+                    )
+                ),
+            ),
+        };
+        let summary = match notification.kind {
+            NotificationKind::AccessChanged => format!("{title}: {}", counts(notification)),
+            NotificationKind::SyncFailed => title.clone(),
+        };
+
+        SlackMessage {
+            text: summary,
+            blocks: vec![
+                json!({"type": "section", "text": {"type": "mrkdwn", "text": format!("*{title}*")}}),
+                json!({"type": "section", "text": {"type": "mrkdwn", "text": body}}),
+                json!({"type": "context", "elements": [{"type": "mrkdwn", "text": notification.timestamp}]}),
+            ],
+        }
+    }
+}
+
+/// Escapes characters Slack mrkdwn uses for links and mentions.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `text` cut to `max_chars` characters, ending with an ellipsis if cut.
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.chars().count() > max_chars {
+        true => format!(
+            "{}…",
+            text.chars()
+                .take(max_chars.saturating_sub(1))
+                .collect::<String>()
+        ),
+        false => text.to_string(),
+    }
+}
+
+/// `name` followed by its groups if any, e.q: `alice (admin, dev)`.
+fn entry(name: &str, groups: &[String]) -> String {
+    match groups.is_empty() {
+        true => escape(name),
+        false => escape(&format!("{name} ({})", groups.join(", "))),
+    }
+}
+
+fn counts(notification: &Notification) -> String {
+    format!(
+        "{} added, {} removed, {} modified",
+        notification.added.users.len() + notification.added.roles.len(),
+        notification.removed.users.len() + notification.removed.roles.len(),
+        notification.modified.users.len() + notification.modified.roles.len(),
+    )
+}
+
+/// One line per change, listing at most `SLACK_MAX_LISTED_CHANGES` of them within Slack section limit,
+/// remaining ones being summarized.
+fn changes_text(notification: &Notification) -> String {
+    let added = notification
+        .added
+        .users
+        .iter()
+        .map(|u| format!("➕ {}", entry(&u.username, &u.groups)))
+        .chain(
+            notification
+                .added
+                .roles
+                .iter()
+                .map(|r| format!("➕ {}", entry(&r.arn, &r.groups))),
+        );
+    let removed = notification
+        .removed
+        .users
+        .iter()
+        .map(|u| format!("➖ {}", escape(&u.username)))
+        .chain(
+            notification
+                .removed
+                .roles
+                .iter()
+                .map(|r| format!("➖ {}", escape(&r.arn))),
+        );
+    let modified = notification
+        .modified
+        .users
+        .iter()
+        .map(|c| {
+            format!(
+                "✏️ {} ({} → {})",
+                escape(&c.after.username),
+                escape(&c.before.groups.join(", ")),
+                escape(&c.after.groups.join(", "))
+            )
+        })
+        .chain(notification.modified.roles.iter().map(|c| {
+            format!(
+                "✏️ {} ({} → {})",
+                escape(&c.after.arn),
+                escape(&c.before.groups.join(", ")),
+                escape(&c.after.groups.join(", "))
+            )
+        }));
+    let lines: Vec<String> = added.chain(removed).chain(modified).collect();
+
+    // room kept for the summary line
+    let max_chars = SLACK_SECTION_MAX_CHARS - 100;
+    let mut text = String::new();
+    let mut text_chars = 0;
+    let mut listed = 0;
+    for line in lines.iter().take(SLACK_MAX_LISTED_CHANGES) {
+        let line_chars = line.chars().count() + 1;
+        if text_chars + line_chars > max_chars {
+            break;
+        }
+        text.push_str(line);
+        text.push('\n');
+        text_chars += line_chars;
+        listed += 1;
+    }
+    if listed < lines.len() {
+        text.push_str(&format!(
+            "…and {} more changes ({})",
+            lines.len() - listed,
+            counts(notification)
+        ));
+    }
+    text.trim_end().to_string()
+}
+
+/// Posts notifications to a Slack incoming webhook, formatted with Block Kit.
+pub struct SlackSink {
+    webhook: WebhookSink,
+}
+
+impl SlackSink {
+    pub fn new(webhook_url: &str, timeout: Duration) -> Result<SlackSink, NotificationError> {
+        Ok(SlackSink {
+            // webhook URL holds its secret, not to be logged
+            webhook: WebhookSink::new(webhook_url, timeout)?.with_name("Slack"),
+        })
+    }
+}
+
+impl NotificationSink for SlackSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+        let message = serde_json::to_string(&SlackMessage::new(notification)).map_err(|e| {
+            NotificationError::CannotSerialize {
+                raw_message: e.to_string().into(),
+            }
+        })?;
+        self.webhook.send(&message).await
+    }
+
+    fn failure_log_interval(&self) -> Duration {
+        SLACK_FAILURE_LOG_INTERVAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::{AwsAuthDiff, EntriesDiff};
+    use crate::kubernetes::tests::iam_user;
+    use crate::kubernetes::{
+        IamArn, KubernetesGroupName, KubernetesRole, KubernetesUser, SyncedBy,
+    };
+    use crate::notification::slack::{SlackMessage, SLACK_SECTION_MAX_CHARS};
+    use crate::notification::Notification;
+    use std::collections::BTreeSet;
+    use std::time::SystemTime;
+
+    fn section_text(message: &SlackMessage, block: usize) -> &str {
+        message.blocks[block]["text"]["text"]
+            .as_str()
+            .expect("section should have a text")
+    }
+
+    #[test]
+    fn slack_message_access_changed_test() {
+        // setup:
+        let diff = AwsAuthDiff {
+            users: EntriesDiff {
+                added: vec![iam_user("alice", &["dev", "admin"]), iam_user("eve", &[])],
+                removed: vec![iam_user("bob", &["view"])],
+                modified: vec![(
+                    iam_user("carol", &["view"]),
+                    iam_user("carol", &["edit", "view"]),
+                )],
+                ..Default::default()
+            },
+            roles: EntriesDiff {
+                added: vec![KubernetesRole::new(
+                    IamArn::new("arn:aws:iam::123456789012:role/nodes"),
+                    None,
+                    None,
+                    BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+                    Some(SyncedBy::IamEksUserMapper),
+                )],
+                ..Default::default()
+            },
+        };
+        let notification =
+            Notification::access_changed("my-cluster", &diff, SystemTime::UNIX_EPOCH);
+
+        // execute:
+        let message = SlackMessage::new(&notification);
+
+        // verify:
+        assert_eq!(
+            "IAM access changed on `my-cluster`: 3 added, 1 removed, 1 modified",
+            message.text
+        );
+        assert_eq!(
+            "*IAM access changed on `my-cluster`*",
+            section_text(&message, 0)
+        );
+        assert_eq!(
+            "➕ alice (admin, dev)\n➕ eve\n➕ arn:aws:iam::123456789012:role/nodes (system:nodes)\n➖ bob\n✏️ carol (view → edit, view)",
+            section_text(&message, 1)
+        );
+        assert_eq!(
+            "1970-01-01T00:00:00Z",
+            message.blocks[2]["elements"][0]["text"]
+        );
+    }
+
+    #[test]
+    fn slack_message_truncation_test() {
+        struct TestCase<'a> {
+            added: Vec<KubernetesUser>,
+            expected_listed: usize,
+            _description: &'a str,
+        }
+
+        let long_group = "g".repeat(500);
+        let test_cases = vec![
+            TestCase {
+                added: (0..20)
+                    .map(|i| iam_user(&format!("user-{i:02}"), &["dev"]))
+                    .collect(),
+                expected_listed: 20,
+                _description: "all changes listed up to the limit",
+            },
+            TestCase {
+                added: (0..50)
+                    .map(|i| iam_user(&format!("user-{i:02}"), &["dev"]))
+                    .collect(),
+                expected_listed: 20,
+                _description: "changes over the limit are summarized",
+            },
+            TestCase {
+                added: (0..10)
+                    .map(|i| iam_user(&format!("user-{i:02}"), &[&long_group]))
+                    .collect(),
+                expected_listed: 5,
+                _description: "changes over Slack section limit are summarized",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let total = tc.added.len();
+            let diff = AwsAuthDiff {
+                users: EntriesDiff {
+                    added: tc.added,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let notification =
+                Notification::access_changed("my-cluster", &diff, SystemTime::UNIX_EPOCH);
+
+            // execute:
+            let message = SlackMessage::new(&notification);
+
+            // verify:
+            let text = section_text(&message, 1);
+            assert!(
+                text.chars().count() <= SLACK_SECTION_MAX_CHARS,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_listed,
+                text.lines().filter(|l| l.starts_with('➕')).count(),
+                "{}",
+                tc._description
+            );
+            match tc.expected_listed < total {
+                true => assert_eq!(
+                    Some(
+                        format!(
+                            "…and {} more changes ({total} added, 0 removed, 0 modified)",
+                            total - tc.expected_listed
+                        )
+                        .as_str()
+                    ),
+                    text.lines().last(),
+                    "{}",
+                    tc._description
+                ),
+                false => assert!(!text.contains("more changes"), "{}", tc._description),
+            }
+        }
+    }
+
+    #[test]
+    fn slack_message_sync_failed_test() {
+        struct TestCase<'a> {
+            error: String,
+            expected_text: String,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                error: "IAM <unreachable> & retrying".to_string(),
+                expected_text: "```IAM &lt;unreachable&gt; &amp; retrying```".to_string(),
+                _description: "mrkdwn special characters are escaped",
+            },
+            TestCase {
+                error: "e".repeat(5000),
+                expected_text: format!("```{}…```", "e".repeat(SLACK_SECTION_MAX_CHARS - 7)),
+                _description: "long errors are truncated",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let notification =
+                Notification::sync_failed("my-cluster", &tc.error, SystemTime::UNIX_EPOCH);
+
+            // execute:
+            let message = SlackMessage::new(&notification);
+
+            // verify:
+            assert_eq!(
+                "IAM sync failed on `my-cluster`", message.text,
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.expected_text,
+                section_text(&message, 1),
+                "{}",
+                tc._description
+            );
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::notification::{Notification, NotificationError, NotificationSink};
+use bytes::Bytes;
+use http::{Method, Request, StatusCode, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::warn;
+
+/// Attempts made to deliver a notification, a receiver restarting shouldn't lose it.
+const WEBHOOK_MAX_ATTEMPTS: u32 = 3;
+/// Delay before the second attempt, doubled on each following one.
+const WEBHOOK_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Why a delivery attempt failed, only `Transient` ones being attempted again.
+enum DeliveryError {
+    Transient(String),
+    Failed(String),
+}
+
+/// POSTs notifications as JSON to an HTTP(S) endpoint.
+pub struct WebhookSink {
+    http_client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    url: Uri,
+    /// Name used in errors and logs, the URL by default.
+    name: String,
+    /// Maximum duration of a single attempt.
+    timeout: Duration,
+    backoff: Duration,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str, timeout: Duration) -> Result<WebhookSink, NotificationError> {
+        let invalid_configuration = |raw_message: String| NotificationError::InvalidConfiguration {
+            raw_message: Arc::from(raw_message),
+        };
+
+        let url = url
+            .parse::<Uri>()
+            .map_err(|e| invalid_configuration(format!("invalid webhook URL `{url}`: {e}")))?;
+        if !matches!(url.scheme_str(), Some("http" | "https")) {
+            return Err(invalid_configuration(format!(
+                "webhook URL `{url}` should be an http(s) URL"
+            )));
+        }
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .map_err(|e| invalid_configuration(e.to_string()))?
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Ok(WebhookSink {
+            http_client: Client::builder(TokioExecutor::new()).build(connector),
+            name: url.to_string(),
+            url,
+            timeout,
+            backoff: WEBHOOK_BACKOFF,
+        })
+    }
+
+    pub fn with_name(self, name: &str) -> WebhookSink {
+        WebhookSink {
+            name: name.to_string(),
+            ..self
+        }
+    }
+
+    /// POSTs `body` until it is accepted, fails with a non transient error or `WEBHOOK_MAX_ATTEMPTS` is reached.
+    pub async fn send(&self, body: &str) -> Result<(), NotificationError> {
+        let cannot_send = |raw_message: String| NotificationError::CannotSend {
+            sink: Arc::from(self.name.as_str()),
+            raw_message: Arc::from(raw_message),
+        };
+
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match self.post(body).await {
+                Ok(()) => return Ok(()),
+                Err(DeliveryError::Failed(e)) => return Err(cannot_send(e)),
+                Err(DeliveryError::Transient(e)) if attempt >= WEBHOOK_MAX_ATTEMPTS => {
+                    return Err(cannot_send(format!("{e} after {attempt} attempts")))
+                }
+                Err(DeliveryError::Transient(e)) => {
+                    warn!("Cannot send notification to `{}` (attempt {attempt}/{WEBHOOK_MAX_ATTEMPTS}), retrying in {backoff:?}: {e}", self.name);
+                    time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    async fn post(&self, body: &str) -> Result<(), DeliveryError> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&self.url)
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .map_err(|e| DeliveryError::Failed(e.to_string()))?;
+
+        let response = match time::timeout(self.timeout, self.http_client.request(request)).await {
+            Ok(Ok(response)) => response,
+            // connection errors
+            Ok(Err(e)) => return Err(DeliveryError::Transient(e.to_string())),
+            Err(_) => {
+                return Err(DeliveryError::Transient(format!(
+                    "no response after {:?}",
+                    self.timeout
+                )))
+            }
+        };
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = time::timeout(self.timeout, response.into_body().collect())
+            .await
+            .ok()
+            .and_then(|b| b.ok())
+            .map(|b| String::from_utf8_lossy(&b.to_bytes()).to_string())
+            .unwrap_or_default();
+        let raw_message = format!("{status}: {body}");
+        match status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            true => Err(DeliveryError::Transient(raw_message)),
+            false => Err(DeliveryError::Failed(raw_message)),
+        }
+    }
+}
+
+impl NotificationSink for WebhookSink {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+        self.send(&notification.to_json()?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notification::webhook::WebhookSink;
+    use crate::notification::{Notification, NotificationSink};
+    use bytes::Bytes;
+    use http::{Request, Response, StatusCode};
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+    use tokio::net::TcpListener;
+
+    /// Requests received by the local server.
+    #[derive(Default)]
+    struct Received {
+        bodies: Vec<serde_json::Value>,
+        content_types: Vec<String>,
+    }
+
+    /// Starts a local server answering with `statuses` in order, then `200 OK`, delaying answers by `delay`.
+    async fn start_server(
+        statuses: Vec<StatusCode>,
+        delay: Duration,
+    ) -> (String, Arc<Mutex<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("cannot bind local server");
+        let url = format!("http://{}/hooks/iam", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Received::default()));
+        let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+
+        let server_received = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let received = server_received.clone();
+                let statuses = statuses.clone();
+                let service = service_fn(move |request: Request<Incoming>| {
+                    let received = received.clone();
+                    let statuses = statuses.clone();
+                    async move {
+                        let content_type = request
+                            .headers()
+                            .get("content-type")
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+                        let body = request.into_body().collect().await?.to_bytes();
+                        {
+                            let mut received = received.lock().unwrap();
+                            received.content_types.push(content_type);
+                            received
+                                .bodies
+                                .push(serde_json::from_slice(&body).expect("invalid JSON"));
+                        }
+                        tokio::time::sleep(delay).await;
+                        let status = statuses.lock().unwrap().pop_front().unwrap_or_default();
+                        let mut response = Response::new(Full::new(Bytes::new()));
+                        *response.status_mut() = status;
+                        Ok::<_, hyper::Error>(response)
+                    }
+                });
+                tokio::spawn(async move {
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        (url, received)
+    }
+
+    fn sink(url: &str, timeout: Duration) -> WebhookSink {
+        let mut sink = WebhookSink::new(url, timeout).expect("valid webhook sink");
+        sink.backoff = Duration::from_millis(10);
+        sink
+    }
+
+    fn notification() -> Notification {
+        Notification::sync_failed("my-cluster", "IAM unreachable", SystemTime::UNIX_EPOCH)
+    }
+
+    #[test]
+    fn webhook_sink_invalid_url_test() {
+        struct TestCase<'a> {
+            url: &'a str,
+            expected_valid: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                url: "https://hooks.example.com/iam",
+                expected_valid: true,
+                _description: "https URL",
+            },
+            TestCase {
+                url: "http://127.0.0.1:8080/iam",
+                expected_valid: true,
+                _description: "http URL",
+            },
+            TestCase {
+                url: "ftp://hooks.example.com/iam",
+                expected_valid: false,
+                _description: "not an http URL",
+            },
+            TestCase {
+                url: "not a URL",
+                expected_valid: false,
+                _description: "invalid URL",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = WebhookSink::new(tc.url, Duration::from_secs(5));
+
+            // verify:
+            assert_eq!(tc.expected_valid, res.is_ok(), "{}", tc._description);
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_notify_test() {
+        // setup:
+        let (url, received) = start_server(vec![], Duration::ZERO).await;
+        let notification = notification();
+
+        // execute:
+        let res = sink(&url, Duration::from_secs(5))
+            .notify(&notification)
+            .await;
+
+        // verify:
+        assert!(res.is_ok());
+        let received = received.lock().unwrap();
+        assert_eq!(vec!["application/json".to_string()], received.content_types);
+        assert_eq!(
+            vec![
+                serde_json::from_str::<serde_json::Value>(&notification.to_json().unwrap())
+                    .unwrap()
+            ],
+            received.bodies
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_notify_retry_test() {
+        struct TestCase<'a> {
+            statuses: Vec<StatusCode>,
+            expected_ok: bool,
+            expected_requests: usize,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                statuses: vec![StatusCode::BAD_GATEWAY, StatusCode::TOO_MANY_REQUESTS],
+                expected_ok: true,
+                expected_requests: 3,
+                _description: "transient errors are retried",
+            },
+            TestCase {
+                statuses: vec![StatusCode::SERVICE_UNAVAILABLE; 3],
+                expected_ok: false,
+                expected_requests: 3,
+                _description: "giving up after max attempts",
+            },
+            TestCase {
+                statuses: vec![StatusCode::NOT_FOUND],
+                expected_ok: false,
+                expected_requests: 1,
+                _description: "client errors are not retried",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let (url, received) = start_server(tc.statuses, Duration::ZERO).await;
+
+            // execute:
+            let res = sink(&url, Duration::from_secs(5))
+                .notify(&notification())
+                .await;
+
+            // verify:
+            assert_eq!(tc.expected_ok, res.is_ok(), "{}", tc._description);
+            assert_eq!(
+                tc.expected_requests,
+                received.lock().unwrap().bodies.len(),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_notify_timeout_test() {
+        // setup:
+        let (url, received) = start_server(vec![], Duration::from_secs(5)).await;
+
+        // execute:
+        let res = sink(&url, Duration::from_millis(50))
+            .notify(&notification())
+            .await;
+
+        // verify:
+        assert!(res.is_err());
+        assert_eq!(3, received.lock().unwrap().bodies.len());
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_notify_unreachable_test() {
+        // setup: nothing listens on a dropped listener port
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/iam", listener.local_addr().unwrap());
+        drop(listener);
+
+        // execute:
+        let res = sink(&url, Duration::from_secs(5))
+            .notify(&notification())
+            .await;
+
+        // verify:
+        assert!(res.is_err());
+    }
+}
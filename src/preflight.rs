@@ -0,0 +1,485 @@
+use crate::aws::iam::{IamGroup, IamRoleSource, IamUserSource};
+use crate::aws::CallerIdentitySource;
+use crate::kubernetes::{ClusterPreflight, IamArn, KubernetesError};
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+/// Checks of the `validate` command, each of them can be skipped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, ValueEnum)]
+pub enum PreflightCheck {
+    /// AWS credentials resolve into an identity, through `sts:GetCallerIdentity`
+    AwsIdentity,
+    /// Each mapped IAM group can be read, through `iam:GetGroup`
+    IamGroups,
+    /// SSO and Karpenter roles exist, through `iam:GetRole`
+    IamRoles,
+    /// Kubernetes API server can be reached
+    Kubernetes,
+    /// Config map can be read and parsed
+    ConfigMapRead,
+    /// RBAC allows patching the config map, through a `SelfSubjectAccessReview`
+    ConfigMapRbac,
+}
+
+impl PreflightCheck {
+    /// Checks in the order they are run.
+    pub const ALL: [PreflightCheck; 6] = [
+        PreflightCheck::AwsIdentity,
+        PreflightCheck::IamGroups,
+        PreflightCheck::IamRoles,
+        PreflightCheck::Kubernetes,
+        PreflightCheck::ConfigMapRead,
+        PreflightCheck::ConfigMapRbac,
+    ];
+
+    /// Name of the check on the command line, e.q: in `--skip-check`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PreflightCheck::AwsIdentity => "aws-identity",
+            PreflightCheck::IamGroups => "iam-groups",
+            PreflightCheck::IamRoles => "iam-roles",
+            PreflightCheck::Kubernetes => "kubernetes",
+            PreflightCheck::ConfigMapRead => "config-map-read",
+            PreflightCheck::ConfigMapRbac => "config-map-rbac",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "SKIP",
+        })
+    }
+}
+
+/// Outcome of a check, checks about several IAM groups or roles having one per group or role.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CheckResult {
+    /// Check name, `config` for the configuration parsing done before any check.
+    pub check: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    pub fn new(check: &'static str, outcome: Result<String, String>) -> CheckResult {
+        match outcome {
+            Ok(detail) => CheckResult {
+                check,
+                status: CheckStatus::Pass,
+                detail,
+            },
+            Err(detail) => CheckResult {
+                check,
+                status: CheckStatus::Fail,
+                detail,
+            },
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        self.status == CheckStatus::Fail
+    }
+}
+
+/// One line per result, e.q: `PASS iam-groups: IAM group `Admins` read, 3 users`.
+impl Display for CheckResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}", self.status, self.check, self.detail)
+    }
+}
+
+/// What the configuration makes the sync read and write.
+pub struct PreflightTargets {
+    pub iam_groups: BTreeSet<IamGroup>,
+    /// Roles to be synced with their description, e.q: `SSO role`.
+    pub roles: Vec<(&'static str, IamArn)>,
+    pub config_map_namespace: String,
+    pub config_map_name: String,
+    /// Whether a missing config map gets created by the sync instead of failing it.
+    pub create_if_missing: bool,
+}
+
+/// Runs checks not in `skipped`, in order, all of them being run whatever the previous ones outcome.
+/// Cluster checks fail with `kubernetes` error if no client could be built.
+pub async fn run_checks<K: ClusterPreflight>(
+    aws: &impl CallerIdentitySource,
+    iam: &(impl IamUserSource + IamRoleSource),
+    kubernetes: Result<&K, &KubernetesError>,
+    targets: &PreflightTargets,
+    skipped: &BTreeSet<PreflightCheck>,
+) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for check in PreflightCheck::ALL {
+        if skipped.contains(&check) {
+            results.push(CheckResult {
+                check: check.name(),
+                status: CheckStatus::Skip,
+                detail: "skipped".to_string(),
+            });
+            continue;
+        }
+
+        let outcomes = match (check, kubernetes) {
+            (PreflightCheck::AwsIdentity, _) => vec![check_aws_identity(aws).await],
+            (PreflightCheck::IamGroups, _) => check_iam_groups(iam, &targets.iam_groups).await,
+            (PreflightCheck::IamRoles, _) => check_iam_roles(iam, &targets.roles).await,
+            (_, Err(e)) => vec![Err(format!("no Kubernetes client: {e}"))],
+            (PreflightCheck::Kubernetes, Ok(kubernetes)) => {
+                vec![check_kubernetes(kubernetes).await]
+            }
+            (PreflightCheck::ConfigMapRead, Ok(kubernetes)) => {
+                vec![check_config_map_read(kubernetes, targets).await]
+            }
+            (PreflightCheck::ConfigMapRbac, Ok(kubernetes)) => {
+                vec![check_config_map_rbac(kubernetes, targets).await]
+            }
+        };
+        results.extend(
+            outcomes
+                .into_iter()
+                .map(|outcome| CheckResult::new(check.name(), outcome)),
+        );
+    }
+
+    results
+}
+
+async fn check_aws_identity(aws: &impl CallerIdentitySource) -> Result<String, String> {
+    aws.get_caller_identity()
+        .await
+        .map(|identity| {
+            format!(
+                "credentials resolve into `{}` of account `{}`",
+                identity.arn, identity.account
+            )
+        })
+        .map_err(|e| e.to_string())
+}
+
+async fn check_iam_groups(
+    iam: &impl IamUserSource,
+    iam_groups: &BTreeSet<IamGroup>,
+) -> Vec<Result<String, String>> {
+    if iam_groups.is_empty() {
+        return vec![Ok("no IAM groups mapped".to_string())];
+    }
+
+    let mut outcomes = Vec::with_capacity(iam_groups.len());
+    for iam_group in iam_groups {
+        outcomes.push(
+            iam.get_users_from_groups(BTreeSet::from_iter(vec![iam_group.clone()]))
+                .await
+                .map(|users| format!("IAM group `{iam_group}` read, {} users", users.len()))
+                .map_err(|e| e.to_string()),
+        );
+    }
+    outcomes
+}
+
+async fn check_iam_roles(
+    iam: &impl IamRoleSource,
+    roles: &[(&'static str, IamArn)],
+) -> Vec<Result<String, String>> {
+    if roles.is_empty() {
+        return vec![Ok("no SSO nor Karpenter roles set".to_string())];
+    }
+
+    let mut outcomes = Vec::with_capacity(roles.len());
+    for (description, role_arn) in roles {
+        outcomes.push(match iam.get_role_arn(role_arn.name()).await {
+            // roles names are unique in an account, the ARN telling whether it's the configured one
+            Ok(arn) if arn.to_string().eq_ignore_ascii_case(&role_arn.to_string()) => {
+                Ok(format!("{description} `{role_arn}` exists"))
+            }
+            Ok(arn) => Err(format!(
+                "{description} `{role_arn}` not found, role `{}` of the credentials account being `{arn}`",
+                role_arn.name()
+            )),
+            Err(e) => Err(format!("{description} `{role_arn}` not found: {e}")),
+        });
+    }
+    outcomes
+}
+
+async fn check_kubernetes(kubernetes: &impl ClusterPreflight) -> Result<String, String> {
+    kubernetes
+        .server_version()
+        .await
+        .map(|version| format!("API server reached, version `{version}`"))
+        .map_err(|e| e.to_string())
+}
+
+async fn check_config_map_read(
+    kubernetes: &impl ClusterPreflight,
+    targets: &PreflightTargets,
+) -> Result<String, String> {
+    let (namespace, name) = (&targets.config_map_namespace, &targets.config_map_name);
+    match kubernetes.read_aws_auth(namespace, name).await {
+        Ok(Some(aws_auth)) => Ok(format!(
+            "config map `{namespace}/{name}` read, {} users and {} roles",
+            aws_auth.users.len(),
+            aws_auth.roles.len()
+        )),
+        Ok(None) if targets.create_if_missing => Ok(format!(
+            "config map `{namespace}/{name}` doesn't exist, to be created by the first sync"
+        )),
+        Ok(None) => Err(format!(
+            "config map `{namespace}/{name}` doesn't exist, set `create_if_missing` for the sync to create it"
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+async fn check_config_map_rbac(
+    kubernetes: &impl ClusterPreflight,
+    targets: &PreflightTargets,
+) -> Result<String, String> {
+    let (namespace, name) = (&targets.config_map_namespace, &targets.config_map_name);
+    match kubernetes.can_i("patch", namespace, name).await {
+        Ok(true) => Ok(format!(
+            "RBAC allows patching config map `{namespace}/{name}`"
+        )),
+        Ok(false) => Err(format!(
+            "RBAC doesn't allow patching config map `{namespace}/{name}`"
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aws::fake::{FakeCallerIdentity, FakeIamUserSource};
+    use crate::aws::iam::IamGroup;
+    use crate::kubernetes::fake::{FakeAuthBackend, FakeConfigMapsBackend};
+    use crate::kubernetes::{IamArn, KubernetesError};
+    use crate::preflight::{
+        run_checks, CheckResult, CheckStatus, PreflightCheck, PreflightTargets,
+    };
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Arc;
+
+    fn targets(create_if_missing: bool) -> PreflightTargets {
+        PreflightTargets {
+            iam_groups: BTreeSet::from_iter(vec![IamGroup::new("Admins"), IamGroup::new("Devops")]),
+            roles: vec![(
+                "SSO role",
+                IamArn::new("arn:aws:iam::123456789012:role/sso-admin"),
+            )],
+            config_map_namespace: "kube-system".to_string(),
+            config_map_name: "aws-auth".to_string(),
+            create_if_missing,
+        }
+    }
+
+    /// Returns (status, check) of each result.
+    fn statuses(results: &[CheckResult]) -> Vec<(CheckStatus, &'static str)> {
+        results.iter().map(|r| (r.status, r.check)).collect()
+    }
+
+    #[tokio::test]
+    async fn run_checks_test() {
+        // setup:
+        let aws = FakeCallerIdentity::new("arn:aws:sts::123456789012:assumed-role/mapper/session");
+        let iam = FakeIamUserSource::new(vec![
+            (
+                "Admins",
+                vec![("alice", "arn:aws:iam::123456789012:user/alice")],
+            ),
+            (
+                "Devops",
+                vec![("bob", "arn:aws:iam::123456789012:user/bob")],
+            ),
+        ])
+        .with_roles(&["arn:aws:iam::123456789012:role/sso-admin"]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+
+        // execute:
+        let results = run_checks(
+            &aws,
+            &iam,
+            Ok(&kubernetes),
+            &targets(false),
+            &BTreeSet::new(),
+        )
+        .await;
+
+        // verify:
+        assert_eq!(
+            vec![
+                (CheckStatus::Pass, "aws-identity"),
+                (CheckStatus::Pass, "iam-groups"),
+                (CheckStatus::Pass, "iam-groups"),
+                (CheckStatus::Pass, "iam-roles"),
+                (CheckStatus::Pass, "kubernetes"),
+                (CheckStatus::Pass, "config-map-read"),
+                (CheckStatus::Pass, "config-map-rbac"),
+            ],
+            statuses(&results)
+        );
+        assert_eq!(
+            "PASS iam-groups: IAM group `Admins` read, 1 users",
+            results[1].to_string()
+        );
+        // nothing written
+        assert_eq!(0, kubernetes.updates_count());
+    }
+
+    #[tokio::test]
+    async fn run_checks_failures_test() {
+        // setup: unresolvable credentials, missing IAM group, SSO role of another account and read only RBAC
+        let aws = FakeCallerIdentity { identity: None };
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123456789012:user/alice")],
+        )])
+        .with_roles(&["arn:aws:iam::210987654321:role/sso-admin"]);
+        let kubernetes = FakeAuthBackend {
+            read_only: true,
+            ..FakeAuthBackend::new(BTreeMap::new())
+        };
+
+        // execute:
+        let results = run_checks(
+            &aws,
+            &iam,
+            Ok(&kubernetes),
+            &targets(false),
+            &BTreeSet::new(),
+        )
+        .await;
+
+        // verify:
+        assert_eq!(
+            vec![
+                (CheckStatus::Fail, "aws-identity"),
+                (CheckStatus::Pass, "iam-groups"),
+                (CheckStatus::Fail, "iam-groups"),
+                (CheckStatus::Fail, "iam-roles"),
+                (CheckStatus::Pass, "kubernetes"),
+                (CheckStatus::Pass, "config-map-read"),
+                (CheckStatus::Fail, "config-map-rbac"),
+            ],
+            statuses(&results)
+        );
+        assert_eq!(
+            "FAIL iam-roles: SSO role `arn:aws:iam::123456789012:role/sso-admin` not found, role `sso-admin` of the credentials account being `arn:aws:iam::210987654321:role/sso-admin`",
+            results[3].to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn run_checks_skipped_test() {
+        // setup: nothing would pass
+        let aws = FakeCallerIdentity { identity: None };
+        let iam = FakeIamUserSource::new(vec![]);
+        let kubernetes = FakeAuthBackend::unreachable();
+
+        // execute:
+        let results = run_checks(
+            &aws,
+            &iam,
+            Ok(&kubernetes),
+            &targets(false),
+            &BTreeSet::from_iter(PreflightCheck::ALL),
+        )
+        .await;
+
+        // verify:
+        assert_eq!(
+            PreflightCheck::ALL
+                .iter()
+                .map(|check| (CheckStatus::Skip, check.name()))
+                .collect::<Vec<_>>(),
+            statuses(&results)
+        );
+    }
+
+    #[tokio::test]
+    async fn run_checks_cluster_test() {
+        struct TestCase<'a> {
+            kubernetes: Result<&'a FakeConfigMapsBackend, &'a KubernetesError>,
+            create_if_missing: bool,
+            expected: Vec<(CheckStatus, &'static str)>,
+            _description: &'a str,
+        }
+
+        let missing_config_map = FakeConfigMapsBackend::new(vec![]);
+        let no_client = KubernetesError::KubeconfigCannotBeRead {
+            kubeconfig_path: Arc::from("/missing/kubeconfig"),
+            raw_message: Arc::from("No such file or directory"),
+        };
+        let aws_checks = BTreeSet::from_iter(vec![
+            PreflightCheck::AwsIdentity,
+            PreflightCheck::IamGroups,
+            PreflightCheck::IamRoles,
+        ]);
+
+        let test_cases = vec![
+            TestCase {
+                kubernetes: Ok(&missing_config_map),
+                create_if_missing: true,
+                expected: vec![
+                    (CheckStatus::Pass, "kubernetes"),
+                    (CheckStatus::Pass, "config-map-read"),
+                    (CheckStatus::Pass, "config-map-rbac"),
+                ],
+                _description: "case 1 - missing config map created by the sync",
+            },
+            TestCase {
+                kubernetes: Ok(&missing_config_map),
+                create_if_missing: false,
+                expected: vec![
+                    (CheckStatus::Pass, "kubernetes"),
+                    (CheckStatus::Fail, "config-map-read"),
+                    (CheckStatus::Pass, "config-map-rbac"),
+                ],
+                _description: "case 2 - missing config map failing the sync",
+            },
+            TestCase {
+                kubernetes: Err(&no_client),
+                create_if_missing: true,
+                expected: vec![
+                    (CheckStatus::Fail, "kubernetes"),
+                    (CheckStatus::Fail, "config-map-read"),
+                    (CheckStatus::Fail, "config-map-rbac"),
+                ],
+                _description: "case 3 - no Kubernetes client",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let results = run_checks(
+                &FakeCallerIdentity { identity: None },
+                &FakeIamUserSource::new(vec![]),
+                tc.kubernetes,
+                &targets(tc.create_if_missing),
+                &aws_checks,
+            )
+            .await;
+
+            // verify:
+            assert_eq!(
+                tc.expected,
+                statuses(&results)
+                    .into_iter()
+                    .filter(|(status, _)| *status != CheckStatus::Skip)
+                    .collect::<Vec<_>>(),
+                "{}",
+                tc._description
+            );
+        }
+    }
+}
@@ -0,0 +1,3693 @@
+use crate::audit::{AuditLog, AuditRecord};
+use crate::aws::eks::{discover_node_roles, EksService};
+use crate::aws::iam::{
+    find_sso_role_arn, resolve_instance_profiles, IamGroup, IamRoleSource, IamUserSource,
+};
+use crate::config::{
+    Config, ConfigurationError, GroupUserSyncConfig, IamK8sGroup, KarpenterRoleConfig,
+    MissedTickBehavior, SSOPermissionSet, SSORoleConfig, UsernameTemplate,
+};
+use crate::errors::{self, Error};
+use crate::health::{publish, LoopAliveGuard, SharedSyncState};
+use crate::heartbeat::write_heartbeat;
+use crate::kubernetes::aws_auth::{AwsAuth, AwsAuthDiff};
+use crate::kubernetes::group_mapping::{IamGroupMapping, USERNAME_PLACEHOLDER};
+use crate::kubernetes::leadership::Leadership;
+use crate::kubernetes::{
+    AuthBackend, AwsAccountId, ConfigMapOptions, ConfigMapUpdateStatus, IamArn, IamUserName,
+    KubernetesError, KubernetesGroupName, KubernetesRole, KubernetesService, KubernetesUser,
+    SyncedBy,
+};
+use crate::trigger::{sync_trigger, SyncReport, TriggeredSyncs};
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::ResourceExt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+/// Syncs IAM users and roles of `config` once into aws-auth through `auth_backend`, Karpenter instance profiles and
+/// the SSO permission set role, if any, being looked up in IAM first.
+///
+/// ```
+/// use clap::Parser;
+/// use iam_eks_user_mapper::aws::fake::FakeIamUserSource;
+/// use iam_eks_user_mapper::cli::{config_from_args, Args};
+/// use iam_eks_user_mapper::kubernetes::fake::FakeAuthBackend;
+/// use iam_eks_user_mapper::{run_sync_once, SyncReport};
+/// use std::collections::BTreeMap;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let args = Args::try_parse_from([
+///     "iam-eks-user-mapper",
+///     "--service-account-name=iam-eks-user-mapper",
+///     "--aws-role-arn=arn:aws:iam::123456789012:role/iam-eks-user-mapper",
+///     "--aws-default-region=eu-west-3",
+///     "--enable-group-user-sync",
+///     "--iam-k8s-groups=Admins->system:masters",
+/// ])
+/// .unwrap();
+/// let config = config_from_args(&args).unwrap();
+/// let iam = FakeIamUserSource::new(vec![(
+///     "Admins",
+///     vec![("alice", "arn:aws:iam::123456789012:user/alice")],
+/// )]);
+/// let aws_auth = FakeAuthBackend::new(BTreeMap::new());
+///
+/// let report = run_sync_once(&config, &iam, &aws_auth).await.unwrap();
+///
+/// assert!(matches!(report, SyncReport::Succeeded { users_added, .. } if users_added.len() == 1));
+/// assert!(aws_auth.data()["mapUsers"].contains("arn:aws:iam::123456789012:user/alice"));
+/// # }
+/// ```
+pub async fn run_sync_once(
+    config: &Config,
+    iam_client: &(impl IamUserSource + IamRoleSource),
+    auth_backend: &impl AuthBackend,
+) -> Result<SyncReport, Error> {
+    SyncLoop::new(
+        config,
+        iam_client,
+        ConfigMapTargets::of_config(config, auth_backend),
+        (),
+    )
+    .sync_once()
+    .await
+}
+
+/// Syncs IAM users and roles of `config` into aws-auth through `auth_backend` every `config.refresh_interval`, the
+/// first time right away, aws-auth being only written when IAM or aws-auth changed since the last sync.
+///
+/// Failures are logged and retried with backoff up to `config.max_backoff`, errors retrying won't fix, e.q: a missing
+/// config map, being returned right away unless `config.fail_fast` is unset. The binary runs the same loop along
+/// health probes, leader election and config reloads, see [`crate::cli::execute`].
+///
+/// ```no_run
+/// use iam_eks_user_mapper::aws::fake::FakeIamUserSource;
+/// use iam_eks_user_mapper::config::Config;
+/// use iam_eks_user_mapper::kubernetes::fake::FakeAuthBackend;
+/// use iam_eks_user_mapper::run_forever;
+///
+/// # async fn sync(config: Config, iam: FakeIamUserSource, aws_auth: FakeAuthBackend) {
+/// let Err(e) = run_forever(&config, &iam, &aws_auth).await;
+/// eprintln!("Sync stopped: {e}");
+/// # }
+/// ```
+pub async fn run_forever(
+    config: &Config,
+    iam_client: &(impl IamUserSource + IamRoleSource),
+    auth_backend: &impl AuthBackend,
+) -> Result<Infallible, Error> {
+    SyncLoop::new(
+        config,
+        iam_client,
+        ConfigMapTargets::of_config(config, auth_backend),
+        (),
+    )
+    .run()
+    .await
+}
+
+pub(crate) struct GroupsMappings {
+    raw: HashMap<IamGroup, BTreeSet<KubernetesGroupName>>,
+    /// Username of IAM groups users, `username_template` being used for groups without template.
+    username_templates: BTreeMap<IamGroup, UsernameTemplate>,
+}
+
+impl GroupsMappings {
+    fn new(iam_k8s_groups: Vec<IamK8sGroup>) -> GroupsMappings {
+        let mut raw: HashMap<IamGroup, BTreeSet<KubernetesGroupName>> = HashMap::new();
+        let mut username_templates: BTreeMap<IamGroup, UsernameTemplate> = BTreeMap::new();
+        for m in iam_k8s_groups {
+            if let Some(username_template) = m.username_template {
+                match username_templates.get(&m.iam_group) {
+                    Some(existing) if *existing != username_template => warn!(
+                        "Several username templates set for IAM group `{}`, ignoring `{username_template}`",
+                        m.iam_group
+                    ),
+                    _ => {
+                        username_templates.insert(m.iam_group.clone(), username_template);
+                    }
+                }
+            }
+            raw.entry(m.iam_group).or_default().insert(m.k8s_group);
+        }
+
+        GroupsMappings {
+            raw,
+            username_templates,
+        }
+    }
+
+    /// Union of valid `group_mappings`, invalid ones being ignored.
+    fn from_group_mappings(
+        group_mappings: &[IamGroupMapping],
+        denied_groups: &BTreeSet<KubernetesGroupName>,
+    ) -> GroupsMappings {
+        let mut groups_mappings = GroupsMappings::new(Vec::new());
+        for group_mapping in group_mappings.iter().filter(|m| m.validate().is_ok()) {
+            if let Some(denied_group) = group_mapping
+                .spec
+                .kubernetes_groups
+                .iter()
+                .find(|g| denied_groups.contains(&KubernetesGroupName::new(g.trim())))
+            {
+                warn!(
+                    "Ignoring IAM group mapping `{}`, Kubernetes group `{}` is denied",
+                    group_mapping.name_any(),
+                    denied_group.trim()
+                );
+                continue;
+            }
+            let iam_group = IamGroup::new(group_mapping.spec.iam_group.trim());
+            groups_mappings
+                .raw
+                .entry(iam_group.clone())
+                .or_default()
+                .extend(
+                    group_mapping
+                        .spec
+                        .kubernetes_groups
+                        .iter()
+                        .map(|g| KubernetesGroupName::new(g.trim())),
+                );
+            if let Some(username_template) = &group_mapping.spec.username_template {
+                let username_template =
+                    UsernameTemplate::with_placeholder(username_template, USERNAME_PLACEHOLDER);
+                match groups_mappings.username_templates.get(&iam_group) {
+                    Some(existing) if *existing != username_template => warn!(
+                        "Several username templates set for IAM group `{iam_group}`, ignoring `{username_template}` of `{}`",
+                        group_mapping.name_any()
+                    ),
+                    _ => {
+                        groups_mappings
+                            .username_templates
+                            .insert(iam_group, username_template);
+                    }
+                }
+            }
+        }
+
+        groups_mappings
+    }
+
+    pub(crate) fn iam_groups(&self) -> BTreeSet<IamGroup> {
+        BTreeSet::from_iter(self.raw.keys().cloned())
+    }
+
+    /// Kubernetes groups `iam_user` gets from its `iam_groups`, each of them having to be mapped.
+    ///
+    /// Users are only fetched from mapped IAM groups, an unmapped one means fetched users and mappings got out of
+    /// sync: failing the sync is safer than mapping the user without the groups of that IAM group.
+    fn k8s_group_for(
+        &self,
+        iam_user: &str,
+        iam_groups: &BTreeSet<IamGroup>,
+    ) -> Result<BTreeSet<KubernetesGroupName>, Error> {
+        let mut k8s_groups = BTreeSet::new();
+
+        for iam_group in iam_groups {
+            let mapped_groups = self
+                .raw
+                .get(iam_group)
+                .ok_or_else(|| Error::UnmappedIamGroup {
+                    iam_group: Arc::from(iam_group.to_string()),
+                    iam_user: Arc::from(iam_user),
+                })?;
+            k8s_groups.extend(mapped_groups.iter().cloned());
+        }
+
+        Ok(k8s_groups)
+    }
+
+    /// Kubernetes username of `user_name`, from the template of the first of its `iam_groups` having one in
+    /// alphabetical order, `None` if none of them has.
+    fn username_for(
+        &self,
+        user_name: &str,
+        arn: &IamArn,
+        iam_groups: &BTreeSet<IamGroup>,
+    ) -> Option<String> {
+        let mut templates = self
+            .username_templates
+            .iter()
+            .filter(|(iam_group, _)| iam_groups.contains(iam_group));
+        let (iam_group, template) = templates.next()?;
+        if let Some((other_iam_group, _)) = templates.find(|(_, other)| *other != template) {
+            warn!(
+                "IAM user `{user_name}` is in IAM groups `{iam_group}` and `{other_iam_group}` having different username templates, using `{template}` of `{iam_group}`"
+            );
+        }
+
+        Some(template.render(user_name, arn))
+    }
+}
+
+/// What has to be synced into the config map, derived from the configuration.
+pub(crate) struct SyncSettings {
+    pub(crate) groups_mappings: Option<GroupsMappings>,
+    pub(crate) sso_role: Option<KubernetesRole>,
+    /// Permission set the SSO role is looked up from before each sync.
+    pub(crate) sso_permission_set: Option<SSOPermissionSet>,
+    pub(crate) karpenter_roles: Vec<KubernetesRole>,
+    /// Roles of the cluster managed nodegroups, discovered before each sync.
+    pub(crate) node_roles: Vec<KubernetesRole>,
+    /// Roles set through `node_role_arns`.
+    static_node_roles: Vec<KubernetesRole>,
+    static_roles: Vec<KubernetesRole>,
+    static_users: Vec<KubernetesUser>,
+    admins_users: BTreeSet<IamArn>,
+    aws_accounts: BTreeSet<AwsAccountId>,
+    /// Groups IAM groups mappings read from the cluster cannot map into.
+    denied_groups: BTreeSet<KubernetesGroupName>,
+    username_template: UsernameTemplate,
+    lowercase_usernames: bool,
+    pub(crate) config_map_namespace: String,
+    pub(crate) config_map_name: String,
+    /// Where aws-auth changes are recorded, set at startup.
+    audit_log: Option<AuditLog>,
+}
+
+impl SyncSettings {
+    pub(crate) fn new(config: &Config) -> SyncSettings {
+        SyncSettings {
+            groups_mappings: match &config.group_user_sync_config {
+                GroupUserSyncConfig::Disabled => None,
+                GroupUserSyncConfig::Enabled { iam_k8s_groups } => {
+                    Some(GroupsMappings::new(iam_k8s_groups.clone()))
+                }
+            },
+            sso_role: match &config.sso_role_config {
+                SSORoleConfig::Disabled => None,
+                SSORoleConfig::Enabled { sso_role } => Some(sso_role.clone()),
+                SSORoleConfig::PermissionSet { .. } => None,
+            },
+            sso_permission_set: match &config.sso_role_config {
+                SSORoleConfig::PermissionSet { permission_set } => Some(permission_set.clone()),
+                _ => None,
+            },
+            karpenter_roles: match &config.karpenter_config {
+                KarpenterRoleConfig::Disabled => Vec::new(),
+                KarpenterRoleConfig::Enabled { karpenter_roles } => karpenter_roles.clone(),
+            },
+            node_roles: Vec::new(),
+            static_node_roles: config.node_roles.clone(),
+            static_roles: config.static_roles.clone(),
+            static_users: config.static_users.clone(),
+            admins_users: config.admins_users.clone(),
+            aws_accounts: config.aws_accounts.clone(),
+            denied_groups: config.denied_groups.clone(),
+            username_template: config.username_template.clone(),
+            lowercase_usernames: config.lowercase_usernames,
+            config_map_namespace: config.config_map_namespace.clone(),
+            config_map_name: config.config_map_name.clone(),
+            audit_log: None,
+        }
+    }
+
+    /// Replaces settings read from `config` by the reloaded ones.
+    /// Discovered ones and the config map, watched since startup, are kept.
+    fn reload(&mut self, config: &Config) {
+        let reloaded = SyncSettings::new(config);
+        if (&reloaded.config_map_namespace, &reloaded.config_map_name)
+            != (&self.config_map_namespace, &self.config_map_name)
+        {
+            warn!("Config map cannot be changed by a config reload, restart to sync another one");
+        }
+        *self = SyncSettings {
+            node_roles: std::mem::take(&mut self.node_roles),
+            config_map_namespace: std::mem::take(&mut self.config_map_namespace),
+            config_map_name: std::mem::take(&mut self.config_map_name),
+            audit_log: self.audit_log.take(),
+            ..reloaded
+        };
+    }
+
+    /// Synced roles other than SSO and Karpenter ones.
+    fn other_roles(&self) -> Vec<KubernetesRole> {
+        self.node_roles
+            .iter()
+            .chain(self.static_node_roles.iter())
+            .chain(self.static_roles.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// SSO role of `permission_set`, its role being looked up in IAM.
+pub(crate) async fn permission_set_sso_role(
+    iam: &impl IamRoleSource,
+    permission_set: &SSOPermissionSet,
+) -> Result<KubernetesRole, Error> {
+    let role_arn = find_sso_role_arn(iam, &permission_set.name)
+        .await
+        .map_err(|e| Error::Aws {
+            underlying_error: e.into(),
+        })?;
+    permission_set
+        .sso_role(&role_arn.to_string())
+        .map_err(|e| Error::Configuration {
+            underlying_error: e,
+        })
+}
+
+/// Looks up in IAM the roles synced ones depend on, before each sync of both the single and multiple clusters loops:
+/// Karpenter instance profiles are replaced by the roles they hold, and the SSO role of the permission set, if any,
+/// is looked up.
+async fn resolve_iam_roles(
+    iam_client: &impl IamRoleSource,
+    settings: &mut SyncSettings,
+    deadline: &SyncDeadline,
+) -> Result<(), Error> {
+    // instance profiles are left at startup and after config reloads only
+    if settings
+        .karpenter_roles
+        .iter()
+        .any(|r| r.iam_role_arn.is_instance_profile())
+    {
+        settings.karpenter_roles = deadline
+            .run("resolving Karpenter instance profiles", async {
+                resolve_instance_profiles(iam_client, &settings.karpenter_roles)
+                    .await
+                    .map_err(|e| Error::Aws {
+                        underlying_error: e.into(),
+                    })
+            })
+            .await?;
+    }
+    // the role suffix changes when the permission set is provisioned again
+    if let Some(permission_set) = &settings.sso_permission_set {
+        let sso_role = deadline
+            .run(
+                "looking up the SSO permission set role",
+                permission_set_sso_role(iam_client, permission_set),
+            )
+            .await?;
+        if settings.sso_role.as_ref().map(|r| &r.iam_role_arn) != Some(&sso_role.iam_role_arn) {
+            info!(
+                "SSO permission set `{}` role is `{}`",
+                permission_set.name, sso_role.iam_role_arn
+            );
+        }
+        settings.sso_role = Some(sso_role);
+    }
+
+    Ok(())
+}
+
+/// Waits for the next change notification, e.q: config map modification, at most once per `debounce`.
+/// Changes notified while waiting for debounce are coalesced into a single one.
+async fn next_change(
+    changes: &mut mpsc::Receiver<()>,
+    debounce: Duration,
+    last_change_sync: &mut Option<Instant>,
+) -> Option<()> {
+    changes.recv().await?;
+    if let Some(last_change_sync) = last_change_sync {
+        time::sleep_until(*last_change_sync + debounce).await;
+    }
+    while changes.try_recv().is_ok() {}
+    *last_change_sync = Some(Instant::now());
+
+    Some(())
+}
+
+/// Consecutive sync failures, delaying the next attempt exponentially not to hammer IAM or the API server while down.
+#[derive(Debug)]
+struct SyncBackoff {
+    /// Longest delay between two attempts, the refresh interval if shorter.
+    max_backoff: Duration,
+    /// Consecutive failures after which syncing stops, never if `None`.
+    max_consecutive_failures: Option<u32>,
+    /// Whether syncing stops on the first error retrying won't fix, instead of backing off.
+    fail_fast: bool,
+    consecutive_failures: u32,
+}
+
+impl SyncBackoff {
+    fn new(
+        max_backoff: Duration,
+        max_consecutive_failures: Option<u32>,
+        fail_fast: bool,
+    ) -> SyncBackoff {
+        SyncBackoff {
+            max_backoff,
+            max_consecutive_failures,
+            fail_fast,
+            consecutive_failures: 0,
+        }
+    }
+
+    /// Whether `max_consecutive_failures` syncs failed in a row.
+    fn exhausted(&self) -> bool {
+        self.max_consecutive_failures
+            .is_some_and(|max_consecutive_failures| {
+                self.consecutive_failures >= max_consecutive_failures
+            })
+    }
+
+    /// Records a failed sync, returning the delay before the next attempt.
+    fn failed(&mut self, refresh_interval: Duration) -> Duration {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.delay(refresh_interval)
+    }
+
+    /// Records a successful sync, returning the number of consecutive failures it ends.
+    fn succeeded(&mut self) -> u32 {
+        std::mem::take(&mut self.consecutive_failures)
+    }
+
+    /// `refresh_interval * 2^consecutive_failures`, capped at `max_backoff`.
+    fn delay(&self, refresh_interval: Duration) -> Duration {
+        let max_backoff = self.max_backoff.max(refresh_interval);
+        2u32.checked_pow(self.consecutive_failures)
+            .and_then(|factor| refresh_interval.checked_mul(factor))
+            .map(|delay| delay.min(max_backoff))
+            .unwrap_or(max_backoff)
+    }
+}
+
+/// When periodic syncs happen, each one `refresh_interval` ± a random `jitter` after the previous one, not for
+/// several mappers started together to keep on hitting IAM at the same time.
+struct RefreshSchedule {
+    refresh_interval: Duration,
+    /// Maximum random offset of each delay between two ticks, at most `refresh_interval`.
+    jitter: Duration,
+    rng: StdRng,
+    next_tick: Instant,
+    /// Ticks missed while a sync overran the refresh interval, skipped by default.
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+impl RefreshSchedule {
+    /// Schedule ticking right away, random offsets being reproducible with `seed`.
+    fn new(refresh_interval: Duration, jitter: Duration, seed: Option<u64>) -> RefreshSchedule {
+        RefreshSchedule {
+            refresh_interval,
+            jitter,
+            rng: seed
+                .map(StdRng::seed_from_u64)
+                .unwrap_or_else(StdRng::from_entropy),
+            next_tick: Instant::now(),
+            missed_tick_behavior: MissedTickBehavior::Skip,
+        }
+    }
+
+    fn with_missed_tick_behavior(
+        self,
+        missed_tick_behavior: MissedTickBehavior,
+    ) -> RefreshSchedule {
+        RefreshSchedule {
+            missed_tick_behavior,
+            ..self
+        }
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+    }
+
+    /// `refresh_interval` moved by a random offset within ± `jitter`, exactly `refresh_interval` without jitter.
+    fn next_delay(&mut self) -> Duration {
+        let jitter = self.jitter.min(self.refresh_interval).as_secs_f64();
+        if jitter == 0.0 {
+            return self.refresh_interval;
+        }
+        let offset = self.rng.gen_range(-jitter..=jitter);
+        Duration::from_secs_f64((self.refresh_interval.as_secs_f64() + offset).max(0.0))
+    }
+
+    /// Waits for the next tick, the following one being scheduled from this one like `time::interval` does.
+    async fn tick(&mut self) {
+        time::sleep_until(self.next_tick).await;
+        let skipped = self.schedule_next_tick(Instant::now());
+        if skipped > 0 {
+            warn!("Last sync overran the refresh interval, skipping {skipped} missed refresh(es)");
+        }
+    }
+
+    /// Schedules the tick following the one due at `next_tick` when ticking at `now`, returning the number of
+    /// ticks skipped, the ones which would have been already due.
+    fn schedule_next_tick(&mut self, now: Instant) -> u32 {
+        let delay = self.next_delay();
+        let next_tick = self.next_tick + delay;
+        if next_tick >= now || delay.is_zero() {
+            self.next_tick = next_tick;
+            return 0;
+        }
+
+        match self.missed_tick_behavior {
+            MissedTickBehavior::Burst => {
+                self.next_tick = next_tick;
+                0
+            }
+            MissedTickBehavior::Delay => {
+                self.next_tick = now + delay;
+                0
+            }
+            MissedTickBehavior::Skip => {
+                let skipped = (now - next_tick).as_nanos() / delay.as_nanos() + 1;
+                self.next_tick = next_tick + delay * skipped as u32;
+                skipped as u32
+            }
+        }
+    }
+
+    /// Moves the next tick `delay` from now, e.q: to back off after a failure.
+    fn delay_next_tick(&mut self, delay: Duration) {
+        self.next_tick = Instant::now() + delay;
+    }
+
+    fn set_refresh_interval(&mut self, refresh_interval: Duration) {
+        self.refresh_interval = refresh_interval;
+        let delay = self.next_delay();
+        self.delay_next_tick(delay);
+    }
+}
+
+/// Delays the next tick of `schedule` after a sync failed with `error`, according to `sync_backoff`.
+/// `error` is returned right away if retrying cannot fix it and failing fast, or once too many syncs failed in a
+/// row, for the pod to be restarted.
+fn back_off(
+    sync_backoff: &mut SyncBackoff,
+    schedule: &mut RefreshSchedule,
+    error: Error,
+) -> Result<(), Error> {
+    if sync_backoff.fail_fast && !error.is_retryable() {
+        error!("Sync failed with an error retrying won't fix, exiting: {error}");
+        return Err(error);
+    }
+    let delay = sync_backoff.failed(schedule.refresh_interval());
+    if sync_backoff.exhausted() {
+        error!(
+            "Giving up after {} consecutive failures, `max_consecutive_failures` reached, exiting: {error}",
+            sync_backoff.consecutive_failures
+        );
+        return Err(error);
+    }
+    warn!(
+        "Next attempt in {}s after {} failures",
+        delay.as_secs(),
+        sync_backoff.consecutive_failures
+    );
+    schedule.delay_next_tick(delay);
+    Ok(())
+}
+
+/// Resets `sync_backoff` after a successful sync, `schedule` ticking at its refresh interval again if it was backing off.
+fn reset_backoff(sync_backoff: &mut SyncBackoff, schedule: &mut RefreshSchedule) {
+    let failures = sync_backoff.succeeded();
+    if failures > 0 {
+        let delay = schedule.next_delay();
+        info!(
+            "Sync succeeded after {failures} failures, next attempt in {}s",
+            delay.as_secs()
+        );
+        schedule.delay_next_tick(delay);
+    }
+}
+
+/// Instant by which a sync iteration should be done, only bounding phases before any write for a timeout not to
+/// leave the config map half updated. Writes are bounded by Kubernetes requests timeout instead.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SyncDeadline {
+    timeout: Duration,
+    at: Instant,
+}
+
+impl SyncDeadline {
+    pub(crate) fn after(timeout: Duration) -> SyncDeadline {
+        SyncDeadline {
+            timeout,
+            at: Instant::now() + timeout,
+        }
+    }
+
+    /// Runs `future`, failing if `phase` is still in progress at the deadline.
+    async fn run<T>(
+        &self,
+        phase: &str,
+        future: impl std::future::Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        time::timeout_at(self.at, future)
+            .await
+            .map_err(|_| Error::SyncTimeout {
+                phase: Arc::from(phase),
+                timeout_seconds: self.timeout.as_secs(),
+            })?
+    }
+}
+
+/// Result of a successful sync.
+pub(crate) struct SyncOutcome {
+    pub(crate) aws_auth: AwsAuth,
+    /// Users and roles changes made, empty if the update was skipped.
+    pub(crate) diff: AwsAuthDiff,
+    /// Number of users found in each synced IAM group.
+    pub(crate) users_per_iam_group: HashMap<IamGroup, usize>,
+}
+
+/// Adds `static_users` to IAM groups users, groups of a user coming from both being merged.
+/// Static mapping username wins over the IAM user name.
+fn merge_static_users(
+    iam_groups_users: BTreeSet<KubernetesUser>,
+    static_users: &[KubernetesUser],
+) -> BTreeSet<KubernetesUser> {
+    let mut users: BTreeMap<String, KubernetesUser> = iam_groups_users
+        .into_iter()
+        .map(|u| (u.iam_arn.to_string().to_lowercase(), u))
+        .collect();
+    for static_user in static_users {
+        let arn = static_user.iam_arn.to_string().to_lowercase();
+        match users.get_mut(&arn) {
+            Some(user) => {
+                user.iam_user_name = static_user.iam_user_name.clone();
+                user.roles.extend(static_user.roles.iter().cloned());
+            }
+            None => {
+                users.insert(arn, static_user.clone());
+            }
+        }
+    }
+
+    users.into_values().collect()
+}
+
+/// Usernames shared by several of `users`, along their ARNs, e.q: `iam:alice` rendered for IAM users of different
+/// accounts or paths.
+fn username_collisions<'a>(
+    users: impl IntoIterator<Item = &'a KubernetesUser>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut arns_per_username: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for user in users {
+        arns_per_username
+            .entry(user.iam_user_name.to_string())
+            .or_default()
+            .push(user.iam_arn.to_string());
+    }
+    arns_per_username.retain(|_, arns| arns.len() > 1);
+    for arns in arns_per_username.values_mut() {
+        arns.sort();
+    }
+    arns_per_username
+}
+
+/// State of the last successful sync, allowing to skip aws-auth updates when nothing changed.
+pub(crate) struct LastSync {
+    desired_state_hash: u64,
+    config_map_resource_version: String,
+    aws_auth: AwsAuth,
+    /// Earliest time a written synced entry expires, the update being applied again by then.
+    next_expiry: Option<SystemTime>,
+}
+
+/// Users and roles to be synced, computed once from IAM and applied to each synced cluster or config map.
+struct DesiredState {
+    kubernetes_users: Option<BTreeSet<KubernetesUser>>,
+    /// Number of users found in each synced IAM group.
+    users_per_iam_group: HashMap<IamGroup, usize>,
+    hash: u64,
+}
+
+/// Outcome of each synced cluster or config map, by context or `<namespace>/<name>`.
+pub(crate) type TargetsOutcomes = Vec<(String, Result<SyncOutcome, errors::Error>)>;
+
+/// Config map synced along others in the same cluster, tracking its own last sync.
+pub(crate) struct ConfigMapTarget {
+    pub(crate) namespace: String,
+    pub(crate) name: String,
+    pub(crate) last_sync: Option<LastSync>,
+}
+
+/// Cluster synced along others, tracking its own last sync.
+pub(crate) struct ClusterTarget<B> {
+    pub(crate) context: String,
+    pub(crate) backend: B,
+    pub(crate) last_sync: Option<LastSync>,
+}
+
+/// Syncs the same users and roles into each of `config_maps` through `kubernetes_client`, returning each
+/// config map outcome, in `config_maps` order. Each config map keeps its own entries not synced by the tool,
+/// one failing not preventing others from being synced.
+pub(crate) async fn sync_iam_eks_users_and_roles(
+    iam_client: &impl IamUserSource,
+    kubernetes_client: &impl AuthBackend,
+    config_maps: &mut [ConfigMapTarget],
+    settings: &SyncSettings,
+    deadline: &SyncDeadline,
+) -> Result<TargetsOutcomes, errors::Error> {
+    let desired_state = deadline
+        .run("fetching IAM users", desired_state(iam_client, settings))
+        .await?;
+    let desired_state = &desired_state;
+
+    Ok(
+        futures::future::join_all(config_maps.iter_mut().map(|config_map| async move {
+            let target = format!("{}/{}", config_map.namespace, config_map.name);
+            let outcome = apply_desired_state(
+                kubernetes_client,
+                settings,
+                &config_map.namespace,
+                &config_map.name,
+                desired_state,
+                &mut config_map.last_sync,
+                deadline,
+            )
+            .instrument(info_span!("config_map", target = %target))
+            .await;
+            (target, outcome)
+        }))
+        .await,
+    )
+}
+
+/// Changes made into all config maps of `config_maps_outcomes`, any config map failing failing the sync, each one
+/// being logged.
+pub(crate) fn config_maps_diff(
+    config_maps_outcomes: TargetsOutcomes,
+) -> Result<AwsAuthDiff, errors::Error> {
+    let mut diff = AwsAuthDiff::default();
+    let mut first_error = None;
+    for (target, outcome) in config_maps_outcomes {
+        match outcome {
+            Ok(outcome) => {
+                info!("Config map `{target}` synced");
+                diff = diff + outcome.diff;
+            }
+            Err(e) => {
+                error!("Error while syncing IAM EKS users into config map `{target}`: {e}");
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(diff),
+    }
+}
+
+/// Syncs the same users and roles into each of `clusters`, returning each cluster outcome.
+/// Clusters are synced concurrently, one failing not preventing others from being synced.
+async fn sync_iam_eks_users_and_roles_to_clusters<B: AuthBackend>(
+    iam_client: &impl IamUserSource,
+    clusters: &mut [ClusterTarget<B>],
+    settings: &SyncSettings,
+    deadline: &SyncDeadline,
+) -> Result<TargetsOutcomes, errors::Error> {
+    let desired_state = deadline
+        .run("fetching IAM users", desired_state(iam_client, settings))
+        .await?;
+    let desired_state = &desired_state;
+
+    Ok(
+        futures::future::join_all(clusters.iter_mut().map(|cluster| async move {
+            let outcome = apply_desired_state(
+                &cluster.backend,
+                settings,
+                &settings.config_map_namespace,
+                &settings.config_map_name,
+                desired_state,
+                &mut cluster.last_sync,
+                deadline,
+            )
+            .instrument(info_span!("cluster", context = %cluster.context))
+            .await;
+            (cluster.context.clone(), outcome)
+        }))
+        .await,
+    )
+}
+
+async fn desired_state(
+    iam_client: &impl IamUserSource,
+    settings: &SyncSettings,
+) -> Result<DesiredState, errors::Error> {
+    // create kubernetes users to be added
+    let mut users_per_iam_group = HashMap::new();
+    let kubernetes_users = match &settings.groups_mappings {
+        Some(gm) => {
+            // get users from AWS groups
+            let iam_users = iam_client
+                .get_users_from_groups(gm.iam_groups())
+                .await
+                .map_err(|e| Error::Aws {
+                    underlying_error: e.into(),
+                })?;
+
+            info!("Found {} users in IAM groups", iam_users.len());
+            for iam_group in iam_users.iter().flat_map(|u| u.groups.iter()) {
+                *users_per_iam_group.entry(iam_group.clone()).or_default() += 1;
+            }
+
+            Some(
+                iam_users
+                    .iter()
+                    .map(|u| {
+                        let user_name = u.user_name.to_string();
+                        let arn = IamArn::new(&u.arn.to_string());
+                        Ok(KubernetesUser::new(
+                            IamUserName::new(
+                                &gm.username_for(&user_name, &arn, &u.groups).unwrap_or_else(
+                                    || settings.username_template.render(&user_name, &arn),
+                                ),
+                            ),
+                            arn,
+                            gm.k8s_group_for(&u.user_name.to_string(), &u.groups)?,
+                            Some(SyncedBy::IamEksUserMapper), // <- those users are managed by the tool
+                        ))
+                    })
+                    .collect::<Result<BTreeSet<_>, Error>>()?,
+            )
+        }
+        None => None,
+    };
+    let static_users: Vec<KubernetesUser> = settings
+        .admins_users
+        .iter()
+        .map(|arn| {
+            KubernetesUser::new(
+                // user name without its path, e.q: `alice` for `arn:aws:iam::123456789012:user/ops/alice`
+                IamUserName::new(&settings.username_template.render(arn.name(), arn)),
+                arn.clone(),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("system:masters")]),
+                Some(SyncedBy::IamEksUserMapper), // <- managed by the tool
+            )
+        })
+        .chain(settings.static_users.iter().cloned())
+        .collect();
+    let kubernetes_users = match static_users.is_empty() {
+        true => kubernetes_users,
+        false => Some(merge_static_users(
+            kubernetes_users.unwrap_or_default(),
+            &static_users,
+        )),
+    };
+    let kubernetes_users = match settings.lowercase_usernames {
+        true => kubernetes_users.map(|users| {
+            users
+                .into_iter()
+                .map(|mut u| {
+                    u.iam_user_name = IamUserName::new(&u.iam_user_name.to_string().to_lowercase());
+                    u
+                })
+                .collect()
+        }),
+        false => kubernetes_users,
+    };
+    for (username, arns) in username_collisions(kubernetes_users.iter().flatten()) {
+        warn!(
+            "Username `{username}` is shared by several IAM users, check `username_template`: {}",
+            arns.join(", ")
+        );
+    }
+
+    // skip the update if neither IAM state nor config map changed since last sync
+    let desired_state_hash = {
+        let mut hasher = DefaultHasher::new();
+        AwsAuth {
+            users: kubernetes_users.iter().flatten().cloned().collect(),
+            roles: BTreeSet::from_iter(
+                settings
+                    .sso_role
+                    .iter()
+                    .chain(settings.karpenter_roles.iter())
+                    .cloned()
+                    .chain(settings.other_roles()),
+            ),
+        }
+        .content_hash()
+        .hash(&mut hasher);
+        settings.aws_accounts.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    Ok(DesiredState {
+        kubernetes_users,
+        users_per_iam_group,
+        hash: desired_state_hash,
+    })
+}
+
+async fn apply_desired_state(
+    kubernetes_client: &impl AuthBackend,
+    settings: &SyncSettings,
+    config_map_namespace: &str,
+    config_map_name: &str,
+    desired_state: &DesiredState,
+    last_sync: &mut Option<LastSync>,
+    deadline: &SyncDeadline,
+) -> Result<SyncOutcome, errors::Error> {
+    let desired_state_hash = desired_state.hash;
+    let users_per_iam_group = desired_state.users_per_iam_group.clone();
+    if let Some(last) = last_sync.as_ref() {
+        if last.desired_state_hash == desired_state_hash {
+            let resource_version = deadline
+                .run("reading config map resource version", async {
+                    kubernetes_client
+                        .get_config_map_resource_version(config_map_namespace, config_map_name)
+                        .await
+                        .map_err(|e| Error::Kubernetes {
+                            underlying_error: e,
+                        })
+                })
+                .await?;
+            debug!(
+                "IAM state hash: {desired_state_hash} (unchanged), config map resource version: {} (last written: {})",
+                resource_version.as_deref().unwrap_or_default(),
+                last.config_map_resource_version
+            );
+            let expired = last
+                .next_expiry
+                .is_some_and(|next_expiry| next_expiry <= SystemTime::now());
+            if expired {
+                debug!("Synced entries expired since last sync, going through aws-auth update");
+            }
+            if !expired
+                && resource_version.as_deref() == Some(last.config_map_resource_version.as_str())
+            {
+                info!("No changes detected, skipping aws-auth update");
+                return Ok(SyncOutcome {
+                    aws_auth: last.aws_auth.clone(),
+                    diff: AwsAuthDiff::default(),
+                    users_per_iam_group,
+                });
+            }
+        }
+    }
+
+    // create new users & roles config map
+    let update = kubernetes_client
+        .update_user_and_role_config_map(
+            config_map_namespace,
+            config_map_name,
+            desired_state.kubernetes_users.clone(),
+            settings.sso_role.clone(),
+            &settings.karpenter_roles,
+            &settings.other_roles(),
+            &settings.aws_accounts,
+        )
+        .await
+        .map_err(|e| Error::Kubernetes {
+            underlying_error: e,
+        })?;
+
+    let summary = update.diff.summary();
+    let suppressed_additions =
+        update.diff.users.suppressed.len() + update.diff.roles.suppressed.len();
+    if suppressed_additions > 0 {
+        info!(
+            "{suppressed_additions} entries not added to config map `{}/{}`, only removals being synced",
+            config_map_namespace, config_map_name
+        );
+    }
+    // dry runs list changes at info level below
+    if !update.diff.is_empty() && update.status != ConfigMapUpdateStatus::DryRun {
+        debug!(
+            "Config map `{}/{}` users and roles changes:\n{}",
+            config_map_namespace, config_map_name, update.diff
+        );
+    }
+    match update.status {
+        ConfigMapUpdateStatus::Updated => info!(
+            "Config map `{}/{}` updated, {summary}",
+            config_map_namespace, config_map_name
+        ),
+        ConfigMapUpdateStatus::Unchanged => info!(
+            "Config map `{}/{}` content is already up to date, no update needed, {summary}",
+            config_map_namespace, config_map_name
+        ),
+        ConfigMapUpdateStatus::DryRun => {
+            info!(
+                "Config map `{}/{}` would be updated (dry run, nothing written), {summary}, users and roles changes, followed by untouched entries:\n{:#}",
+                config_map_namespace, config_map_name, update.diff
+            );
+            // nothing has been written, next sync has to go through the whole path again
+            return Ok(SyncOutcome {
+                aws_auth: update.aws_auth,
+                diff: update.diff,
+                users_per_iam_group,
+            });
+        }
+        ConfigMapUpdateStatus::ServerDryRun => {
+            info!(
+                "Config map `{}/{}` would be updated (server side dry run, nothing persisted), {summary}",
+                config_map_namespace, config_map_name
+            );
+            // nothing has been written, next sync has to go through the whole path again
+            return Ok(SyncOutcome {
+                aws_auth: update.aws_auth,
+                diff: update.diff,
+                users_per_iam_group,
+            });
+        }
+    }
+
+    // unchanged config maps have nothing to be recorded, other statuses returned early
+    if let (Some(audit_log), ConfigMapUpdateStatus::Updated) = (&settings.audit_log, update.status)
+    {
+        let record = AuditRecord::new(
+            &format!("{config_map_namespace}/{config_map_name}"),
+            &update.diff,
+            update.previous_resource_version.clone(),
+            update.resource_version.clone(),
+            SystemTime::now(),
+        );
+        if let Err(e) = audit_log.append(&record) {
+            match audit_log.required {
+                true => {
+                    return Err(Error::Audit {
+                        underlying_error: e,
+                    })
+                }
+                false => error!("Error while writing audit log record: {e}"),
+            }
+        }
+    }
+
+    *last_sync = update.resource_version.map(|resource_version| LastSync {
+        desired_state_hash,
+        config_map_resource_version: resource_version,
+        aws_auth: update.aws_auth.clone(),
+        next_expiry: update.next_expiry,
+    });
+
+    Ok(SyncOutcome {
+        aws_auth: update.aws_auth,
+        diff: update.diff,
+        users_per_iam_group,
+    })
+}
+
+/// YAML manifest of the config map the sync would write, merging entries to be synced into `existing_config_map`
+/// or into an empty one, without any cluster access.
+pub(crate) async fn render_config_map(
+    iam_client: &impl IamUserSource,
+    settings: &SyncSettings,
+    config_map_options: &ConfigMapOptions,
+    existing_config_map: Option<ConfigMap>,
+) -> Result<String, errors::Error> {
+    let desired_state = desired_state(iam_client, settings).await?;
+
+    let existing_config_map = existing_config_map.unwrap_or_default();
+    let mut config_map = ConfigMap {
+        // server side fields, e.q: resource version or managed fields, are not part of what is written
+        metadata: ObjectMeta {
+            name: Some(settings.config_map_name.clone()),
+            namespace: Some(settings.config_map_namespace.clone()),
+            labels: existing_config_map.metadata.labels,
+            annotations: existing_config_map.metadata.annotations,
+            ..Default::default()
+        },
+        ..existing_config_map
+    };
+    KubernetesService::merge_config_map(
+        &mut config_map,
+        desired_state.kubernetes_users,
+        settings.sso_role.clone(),
+        &settings.karpenter_roles,
+        &settings.other_roles(),
+        &settings.aws_accounts,
+        config_map_options,
+    )
+    .map_err(|e| Error::Kubernetes {
+        underlying_error: e,
+    })?;
+
+    serde_yaml::to_string(&config_map).map_err(|e| Error::Kubernetes {
+        underlying_error: KubernetesError::CannotSerializeConfigMap {
+            raw_message: Arc::from(e.to_string()),
+        },
+    })
+}
+
+/// Writes the time of a successful sync into `heartbeat_file` if any, failures being only logged not to fail the sync.
+fn record_heartbeat(heartbeat_file: Option<&Path>) {
+    if let Some(heartbeat_file) = heartbeat_file {
+        if let Err(e) = write_heartbeat(heartbeat_file, SystemTime::now()) {
+            error!("Error while writing heartbeat: {e}");
+        }
+    }
+}
+
+/// Truncates `duration` to the millisecond, sync durations being logged with this precision.
+pub(crate) fn millis_precision(duration: Duration) -> Duration {
+    Duration::from_millis(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// Where a sync writes IAM users and roles, each target keeping track of its own last sync.
+pub(crate) trait SyncTargets {
+    /// Syncs the same users and roles into each target, returning each target outcome in targets order.
+    async fn sync(
+        &mut self,
+        iam_client: &impl IamUserSource,
+        settings: &SyncSettings,
+        deadline: &SyncDeadline,
+    ) -> Result<TargetsOutcomes, Error>;
+
+    /// Changes made by a sync out of each target outcome, logged, failing if the sync is to be retried.
+    fn diff(&self, outcomes: TargetsOutcomes) -> Result<AwsAuthDiff, Error>;
+}
+
+/// Config maps of the same cluster synced through `backend`, the first one being the primary one, watched.
+pub(crate) struct ConfigMapTargets<'a, B> {
+    pub(crate) backend: &'a B,
+    pub(crate) config_maps: Vec<ConfigMapTarget>,
+}
+
+impl<'a, B> ConfigMapTargets<'a, B> {
+    /// `config_maps`, at least one being required.
+    pub(crate) fn new(
+        backend: &'a B,
+        config_maps: Vec<ConfigMapTarget>,
+    ) -> Result<ConfigMapTargets<'a, B>, ConfigurationError> {
+        match config_maps.is_empty() {
+            true => Err(ConfigurationError::NoConfigMapTargets),
+            false => Ok(ConfigMapTargets {
+                backend,
+                config_maps,
+            }),
+        }
+    }
+
+    /// The config map of `config` only.
+    pub(crate) fn of_config(config: &Config, backend: &'a B) -> ConfigMapTargets<'a, B> {
+        ConfigMapTargets {
+            backend,
+            config_maps: vec![ConfigMapTarget {
+                namespace: config.config_map_namespace.clone(),
+                name: config.config_map_name.clone(),
+                last_sync: None,
+            }],
+        }
+    }
+}
+
+impl<B: AuthBackend> SyncTargets for ConfigMapTargets<'_, B> {
+    async fn sync(
+        &mut self,
+        iam_client: &impl IamUserSource,
+        settings: &SyncSettings,
+        deadline: &SyncDeadline,
+    ) -> Result<TargetsOutcomes, Error> {
+        info!("Syncing IAM EKS users & roles");
+        sync_iam_eks_users_and_roles(
+            iam_client,
+            self.backend,
+            &mut self.config_maps,
+            settings,
+            deadline,
+        )
+        .await
+    }
+
+    fn diff(&self, outcomes: TargetsOutcomes) -> Result<AwsAuthDiff, Error> {
+        config_maps_diff(outcomes)
+    }
+}
+
+impl<B: AuthBackend> SyncTargets for Vec<ClusterTarget<B>> {
+    async fn sync(
+        &mut self,
+        iam_client: &impl IamUserSource,
+        settings: &SyncSettings,
+        deadline: &SyncDeadline,
+    ) -> Result<TargetsOutcomes, Error> {
+        info!("Syncing IAM EKS users & roles into {} clusters", self.len());
+        sync_iam_eks_users_and_roles_to_clusters(iam_client, self, settings, deadline).await
+    }
+
+    /// Changes of all synced clusters, failing only when no cluster could be synced, one failing shouldn't delay
+    /// others by backing off.
+    fn diff(&self, outcomes: TargetsOutcomes) -> Result<AwsAuthDiff, Error> {
+        let mut diff = AwsAuthDiff::default();
+        let mut first_error = None;
+        let mut synced = false;
+        for (context, outcome) in outcomes {
+            match outcome {
+                Ok(outcome) => {
+                    info!("Cluster `{context}` synced");
+                    diff = diff + outcome.diff;
+                    synced = true;
+                }
+                Err(e) => {
+                    error!("Error while syncing IAM EKS users into cluster `{context}`: {e}");
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match (first_error, synced) {
+            (Some(e), false) => Err(e),
+            _ => Ok(diff),
+        }
+    }
+}
+
+/// Reports each sync outcome outside of logs, e.q: into a status config map or as notifications, failing to
+/// report being only logged.
+pub(crate) trait SyncReporter {
+    async fn report(
+        &mut self,
+        outcomes: &Result<TargetsOutcomes, Error>,
+        settings: &SyncSettings,
+        group_mappings: &[IamGroupMapping],
+    );
+}
+
+/// Nothing reported but logs.
+impl SyncReporter for () {
+    async fn report(
+        &mut self,
+        _outcomes: &Result<TargetsOutcomes, Error>,
+        _settings: &SyncSettings,
+        _group_mappings: &[IamGroupMapping],
+    ) {
+    }
+}
+
+/// What the binary adds to the sync loop, defaults triggering no sync and publishing to nobody.
+pub(crate) struct SyncHooks {
+    /// Liveness and readiness served by health probes.
+    pub(crate) sync_state: SharedSyncState,
+    pub(crate) triggered_syncs: TriggeredSyncs,
+    /// Config map modified outside of the tool, syncing right away at most once per `watch_debounce`.
+    pub(crate) config_map_changes: mpsc::Receiver<()>,
+    /// IAM group mappings resources modified, debounced as config map changes.
+    pub(crate) group_mappings_changes: mpsc::Receiver<()>,
+    pub(crate) watch_debounce: Duration,
+    /// Configuration read again after a config file change.
+    pub(crate) config_reloads: mpsc::Receiver<Config>,
+    /// Syncs are skipped while not the leader.
+    pub(crate) leadership: Option<Leadership>,
+    /// Client nodegroups roles are discovered with before each sync.
+    pub(crate) nodegroups_client: Option<EksService>,
+    /// Client and namespace IAM group mappings resources are listed from before each sync, in CRD mode.
+    pub(crate) group_mappings_source: Option<(KubernetesService, String)>,
+    pub(crate) heartbeat_file: Option<PathBuf>,
+}
+
+impl Default for SyncHooks {
+    fn default() -> SyncHooks {
+        // senders are dropped right away, never triggering any sync
+        SyncHooks {
+            sync_state: SharedSyncState::default(),
+            triggered_syncs: sync_trigger().1,
+            config_map_changes: mpsc::channel(1).1,
+            group_mappings_changes: mpsc::channel(1).1,
+            watch_debounce: Duration::ZERO,
+            config_reloads: mpsc::channel(1).1,
+            leadership: None,
+            nodegroups_client: None,
+            group_mappings_source: None,
+            heartbeat_file: None,
+        }
+    }
+}
+
+/// Syncs IAM users and roles into `targets` on each refresh, change or sync request, backing off after failures.
+/// Shared by [`run_forever`], [`run_sync_once`] and the binary, the latter adding hooks and a reporter.
+pub(crate) struct SyncLoop<'a, I, T, R> {
+    iam_client: &'a I,
+    targets: T,
+    reporter: R,
+    settings: SyncSettings,
+    schedule: RefreshSchedule,
+    sync_backoff: SyncBackoff,
+    sync_timeout: Duration,
+    hooks: SyncHooks,
+}
+
+impl<'a, I, T, R> SyncLoop<'a, I, T, R>
+where
+    I: IamUserSource + IamRoleSource,
+    T: SyncTargets,
+    R: SyncReporter,
+{
+    pub(crate) fn new(config: &Config, iam_client: &'a I, targets: T, reporter: R) -> Self {
+        SyncLoop {
+            iam_client,
+            targets,
+            reporter,
+            settings: SyncSettings::new(config),
+            schedule: RefreshSchedule::new(
+                config.refresh_interval,
+                config.refresh_interval_jitter,
+                config.refresh_interval_jitter_seed,
+            )
+            .with_missed_tick_behavior(config.missed_tick_behavior),
+            sync_backoff: SyncBackoff::new(
+                config.max_backoff,
+                config.max_consecutive_failures,
+                config.fail_fast,
+            ),
+            sync_timeout: config.sync_timeout,
+            hooks: SyncHooks::default(),
+        }
+    }
+
+    pub(crate) fn with_hooks(self, hooks: SyncHooks) -> Self {
+        SyncLoop { hooks, ..self }
+    }
+
+    pub(crate) fn with_audit_log(mut self, audit_log: Option<AuditLog>) -> Self {
+        self.settings.audit_log = audit_log;
+        self
+    }
+
+    /// Syncs right away, unless not the leader, what synced entries depend on being looked up first.
+    pub(crate) async fn sync_once(&mut self) -> Result<SyncReport, Error> {
+        let sync_start = Instant::now();
+        if self
+            .hooks
+            .leadership
+            .as_ref()
+            .is_some_and(|l| !l.is_leader())
+        {
+            debug!("Not the leader, skipping sync");
+            return Ok(SyncReport::Skipped {
+                reason: "not the leader".to_string(),
+            });
+        }
+        let deadline = SyncDeadline::after(self.sync_timeout);
+        let group_mappings = self.prepare(&deadline).await?;
+        let outcomes = self
+            .targets
+            .sync(self.iam_client, &self.settings, &deadline)
+            // a trace per iteration, not a never ending one under the main span
+            .instrument(info_span!(parent: None, "sync_iteration"))
+            .await;
+        self.reporter
+            .report(&outcomes, &self.settings, &group_mappings)
+            .await;
+        let duration = sync_start.elapsed();
+        match outcomes.and_then(|outcomes| self.targets.diff(outcomes)) {
+            Ok(diff) => {
+                info!(
+                    "Syncing of IAM EKS users is done in {}, {}",
+                    humantime::format_duration(millis_precision(duration)),
+                    diff.summary()
+                );
+                Ok(SyncReport::succeeded(diff, duration))
+            }
+            Err(e) => {
+                error!("Error while syncing IAM EKS users: {e}");
+                Err(e)
+            }
+        }
+    }
+
+    /// Looks up what synced entries depend on, returning IAM group mappings resources in CRD mode. Failing skips the
+    /// sync, syncing without them removing entries, e.q: preventing nodes from joining or all IAM groups users.
+    async fn prepare(&mut self, deadline: &SyncDeadline) -> Result<Vec<IamGroupMapping>, Error> {
+        if let Some(nodegroups_client) = &self.hooks.nodegroups_client {
+            let node_roles = deadline
+                .run("discovering nodegroups roles", async {
+                    discover_node_roles(nodegroups_client)
+                        .await
+                        .map_err(|e| Error::Aws {
+                            underlying_error: e.into(),
+                        })
+                })
+                .await;
+            match node_roles {
+                Ok(node_roles) => self.settings.node_roles = node_roles,
+                Err(e) => {
+                    error!("Error while discovering nodegroups roles, skipping sync: {e}");
+                    return Err(e);
+                }
+            }
+        }
+        if let Err(e) = resolve_iam_roles(self.iam_client, &mut self.settings, deadline).await {
+            error!("Error while looking up synced roles in IAM, skipping sync: {e}");
+            return Err(e);
+        }
+        let Some((kubernetes_client, namespace)) = &self.hooks.group_mappings_source else {
+            return Ok(Vec::new());
+        };
+        let group_mappings = deadline
+            .run("listing IAM group mappings", async {
+                kubernetes_client
+                    .list_group_mappings(namespace)
+                    .await
+                    .map_err(|e| Error::Kubernetes {
+                        underlying_error: e,
+                    })
+            })
+            .await;
+        match group_mappings {
+            Ok(group_mappings) => {
+                self.settings.groups_mappings = Some(GroupsMappings::from_group_mappings(
+                    &group_mappings,
+                    &self.settings.denied_groups,
+                ));
+                Ok(group_mappings)
+            }
+            Err(e) => {
+                error!("Error while listing IAM group mappings, skipping sync: {e}");
+                Err(e)
+            }
+        }
+    }
+
+    /// Syncs on each tick of the refresh schedule, the first one right away, and on each hook change, until
+    /// syncing fails with an error retrying won't fix or too many times in a row.
+    pub(crate) async fn run(mut self) -> Result<Infallible, Error> {
+        let _loop_alive = LoopAliveGuard::new(self.hooks.sync_state.clone());
+        let mut last_change_sync = None;
+        let mut last_group_mappings_change_sync = None;
+
+        loop {
+            tokio::select! {
+                _ = self.schedule.tick() => {}
+                // a change pending on debounce when ticking is handled by the regular sync
+                Some(()) = next_change(&mut self.hooks.config_map_changes, self.hooks.watch_debounce, &mut last_change_sync) => {
+                    info!("Config map modified outside of the tool, syncing right away");
+                }
+                Some(()) = next_change(&mut self.hooks.group_mappings_changes, self.hooks.watch_debounce, &mut last_group_mappings_change_sync) => {
+                    info!("IAM group mappings modified, syncing right away");
+                }
+                Some(()) = self.hooks.triggered_syncs.requested() => {
+                    info!("Sync requested, syncing right away");
+                }
+                Some(reloaded_config) = self.hooks.config_reloads.recv() => {
+                    self.settings.reload(&reloaded_config);
+                    if reloaded_config.refresh_interval != self.schedule.refresh_interval() {
+                        self.schedule.set_refresh_interval(reloaded_config.refresh_interval);
+                    }
+                    info!("Config reloaded, syncing right away");
+                }
+            }
+            let sync_number = self.hooks.triggered_syncs.started();
+            let res = self.sync_once().await;
+            self.hooks.triggered_syncs.finished(
+                sync_number,
+                match &res {
+                    Ok(report) => report.clone(),
+                    Err(e) => SyncReport::Failed {
+                        error: e.to_string(),
+                    },
+                },
+            );
+            match res {
+                Ok(SyncReport::Skipped { .. }) => {
+                    publish(&self.hooks.sync_state, |state| state.standby = true)
+                }
+                Ok(_) => {
+                    publish(&self.hooks.sync_state, |state| {
+                        state.succeeded(Instant::now())
+                    });
+                    record_heartbeat(self.hooks.heartbeat_file.as_deref());
+                    reset_backoff(&mut self.sync_backoff, &mut self.schedule)
+                }
+                Err(e) => {
+                    publish(&self.hooks.sync_state, |state| state.failed(Instant::now()));
+                    back_off(&mut self.sync_backoff, &mut self.schedule, e)?
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::AuditLog;
+    use crate::aws::fake::FakeIamUserSource;
+    use crate::aws::iam::{Arn, AwsUser, IamError, IamGroup, IamUserSource, User};
+    use crate::cli::{config_from_args, Args};
+    use crate::config::{ConfigurationError, IamK8sGroup, MissedTickBehavior, UsernameTemplate};
+    use crate::errors::Error;
+    use crate::kubernetes::fake::{FakeAuthBackend, FakeConfigMapsBackend};
+    use crate::kubernetes::group_mapping::{IamGroupMapping, IamGroupMappingSpec};
+    use crate::kubernetes::leadership::Leadership;
+    use crate::kubernetes::AuthBackend;
+    use crate::kubernetes::{
+        ConfigMapOptions, IamArn, IamUserName, KubernetesGroupName, KubernetesRole,
+        KubernetesService, KubernetesUser, SyncedBy,
+    };
+    use crate::sync::{
+        back_off, config_maps_diff, next_change, render_config_map, resolve_iam_roles,
+        sync_iam_eks_users_and_roles, sync_iam_eks_users_and_roles_to_clusters,
+        username_collisions, ClusterTarget, ConfigMapTarget, ConfigMapTargets, GroupsMappings,
+        LastSync, RefreshSchedule, SyncBackoff, SyncDeadline, SyncHooks, SyncLoop, SyncOutcome,
+        SyncSettings,
+    };
+    use crate::trigger::SyncReport;
+    use clap::Parser;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use std::collections::{BTreeMap, BTreeSet, HashSet};
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::Instant;
+    use tracing_subscriber::prelude::*;
+
+    /// Returns (userarn, username, sorted groups, syncedBy) of each mapUsers entry, sorted by ARN.
+    fn map_users(
+        data: &BTreeMap<String, String>,
+    ) -> Vec<(String, String, Vec<String>, Option<String>)> {
+        let entries: Vec<BTreeMap<String, serde_yaml::Value>> =
+            serde_yaml::from_str(data.get("mapUsers").expect("mapUsers should be set"))
+                .expect("mapUsers should be valid YAML");
+        let mut users: Vec<_> = entries
+            .into_iter()
+            .map(|e| {
+                let mut groups: Vec<String> = e["groups"]
+                    .as_sequence()
+                    .expect("groups should be a list")
+                    .iter()
+                    .map(|g| g.as_str().unwrap_or_default().to_string())
+                    .collect();
+                groups.sort();
+                (
+                    e["userarn"].as_str().unwrap_or_default().to_string(),
+                    e["username"].as_str().unwrap_or_default().to_string(),
+                    groups,
+                    e.get("syncedBy")
+                        .and_then(|s| s.as_str())
+                        .map(|s| s.to_string()),
+                )
+            })
+            .collect();
+        users.sort();
+        users
+    }
+
+    /// Returns (rolearn, syncedBy) of each mapRoles entry, sorted by ARN.
+    fn map_roles(data: &BTreeMap<String, String>) -> Vec<(String, Option<String>)> {
+        let entries: Vec<BTreeMap<String, serde_yaml::Value>> =
+            serde_yaml::from_str(data.get("mapRoles").expect("mapRoles should be set"))
+                .expect("mapRoles should be valid YAML");
+        let mut roles: Vec<_> = entries
+            .into_iter()
+            .map(|e| {
+                (
+                    e["rolearn"].as_str().unwrap_or_default().to_string(),
+                    e.get("syncedBy")
+                        .and_then(|s| s.as_str())
+                        .map(|s| s.to_string()),
+                )
+            })
+            .collect();
+        roles.sort();
+        roles
+    }
+
+    /// Syncs the config map of `settings` only, the way it's done without `config_map_targets`.
+    async fn sync_config_map(
+        iam: &impl IamUserSource,
+        kubernetes: &impl AuthBackend,
+        settings: &SyncSettings,
+        last_sync: &mut Option<LastSync>,
+    ) -> Result<SyncOutcome, Error> {
+        let mut config_maps = [ConfigMapTarget {
+            namespace: settings.config_map_namespace.clone(),
+            name: settings.config_map_name.clone(),
+            last_sync: last_sync.take(),
+        }];
+        let outcomes =
+            sync_iam_eks_users_and_roles(iam, kubernetes, &mut config_maps, settings, &deadline())
+                .await;
+        *last_sync = config_maps[0].last_sync.take();
+
+        outcomes?
+            .pop()
+            .map(|(_, outcome)| outcome)
+            .expect("a single config map should be synced")
+    }
+
+    fn deadline() -> SyncDeadline {
+        SyncDeadline::after(Duration::from_secs(60))
+    }
+
+    fn groups_mappings(raw: &[&str]) -> GroupsMappings {
+        GroupsMappings::new(
+            raw.iter()
+                .map(|m| IamK8sGroup::from_str(m).expect("invalid mapping"))
+                .collect(),
+        )
+    }
+
+    fn sync_settings(
+        groups_mappings: GroupsMappings,
+        karpenter_roles: Vec<KubernetesRole>,
+    ) -> SyncSettings {
+        SyncSettings {
+            groups_mappings: Some(groups_mappings),
+            sso_role: None,
+            sso_permission_set: None,
+            karpenter_roles,
+            node_roles: Vec::new(),
+            static_node_roles: Vec::new(),
+            static_roles: Vec::new(),
+            static_users: Vec::new(),
+            admins_users: BTreeSet::new(),
+            aws_accounts: BTreeSet::new(),
+            denied_groups: BTreeSet::new(),
+            username_template: UsernameTemplate::default(),
+            lowercase_usernames: false,
+            config_map_namespace: "kube-system".to_string(),
+            config_map_name: "aws-auth".to_string(),
+            audit_log: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn render_config_map_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![
+            ("Admins", vec![("alice", "arn:aws:iam::123:user/alice")]),
+            ("Devops", vec![("bob", "arn:aws:iam::123:user/bob")]),
+        ]);
+        let karpenter_role = KubernetesRole::new(
+            IamArn::new("arn:aws:iam::123:role/karpenter"),
+            None,
+            Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        let settings = sync_settings(
+            groups_mappings(&["Admins->system:masters", "Devops->devops"]),
+            vec![karpenter_role.clone()],
+        );
+
+        // execute:
+        let res = render_config_map(&iam, &settings, &ConfigMapOptions::default(), None).await;
+
+        // verify:
+        assert!(res.is_ok());
+        let config_map: ConfigMap =
+            serde_yaml::from_str(&res.unwrap()).expect("rendered config map should be valid YAML");
+        assert_eq!(Some("aws-auth"), config_map.metadata.name.as_deref());
+        assert_eq!(
+            Some("kube-system"),
+            config_map.metadata.namespace.as_deref()
+        );
+        let aws_auth = KubernetesService::parse_aws_auth(&config_map)
+            .expect("rendered aws-auth should be parsable");
+        assert_eq!(
+            BTreeSet::from_iter(vec![
+                KubernetesUser::new(
+                    IamUserName::new("alice"),
+                    IamArn::new("arn:aws:iam::123:user/alice"),
+                    BTreeSet::from_iter(vec![KubernetesGroupName::new("system:masters")]),
+                    Some(SyncedBy::IamEksUserMapper),
+                ),
+                KubernetesUser::new(
+                    IamUserName::new("bob"),
+                    IamArn::new("arn:aws:iam::123:user/bob"),
+                    BTreeSet::from_iter(vec![KubernetesGroupName::new("devops")]),
+                    Some(SyncedBy::IamEksUserMapper),
+                ),
+            ]),
+            aws_auth.users
+        );
+        assert_eq!(BTreeSet::from_iter(vec![karpenter_role]), aws_auth.roles);
+    }
+
+    #[tokio::test]
+    async fn desired_state_unmapped_iam_group_test() {
+        // setup:
+        /// Returns users of IAM groups not asked for, as if mappings changed while fetching users.
+        struct InconsistentIamUserSource;
+        impl IamUserSource for InconsistentIamUserSource {
+            async fn get_users_from_groups(
+                &self,
+                _iam_groups: BTreeSet<IamGroup>,
+            ) -> Result<HashSet<AwsUser>, IamError> {
+                Ok(HashSet::from_iter(vec![AwsUser {
+                    arn: Arn::new("arn:aws:iam::123:user/alice"),
+                    user_name: User::new("alice"),
+                    groups: BTreeSet::from_iter(vec![
+                        IamGroup::new("Admins"),
+                        IamGroup::new("Removed"),
+                    ]),
+                }]))
+            }
+        }
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+
+        // execute:
+        let res = render_config_map(
+            &InconsistentIamUserSource,
+            &settings,
+            &ConfigMapOptions::default(),
+            None,
+        )
+        .await;
+
+        // verify:
+        match res {
+            Err(Error::UnmappedIamGroup {
+                iam_group,
+                iam_user,
+            }) => {
+                assert_eq!("Removed", iam_group.as_ref());
+                assert_eq!("alice", iam_user.as_ref());
+            }
+            res => panic!("expected an unmapped IAM group error, got {res:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn render_config_map_existing_aws_auth_test() {
+        // setup:
+        let existing_aws_auth: ConfigMap = serde_yaml::from_str(
+            r#"apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: aws-auth
+  namespace: kube-system
+  resourceVersion: "42"
+  uid: 0b6f7c1e-4b1a-4d3c-9a39-5a1f6c1d2e3f
+data:
+  mapUsers: |
+    - userarn: arn:aws:iam::123:user/manual
+      username: manual
+      groups:
+        - view
+    - userarn: arn:aws:iam::123:user/former
+      username: former
+      groups:
+        - devops
+      syncedBy: iam-eks-user-mapper
+  mapRoles: |
+    - rolearn: arn:aws:iam::123:role/nodes
+      username: system:node:{{EC2PrivateDNSName}}
+      groups:
+        - system:nodes
+"#,
+        )
+        .expect("existing aws-auth should be valid");
+        let iam =
+            FakeIamUserSource::new(vec![("Devops", vec![("bob", "arn:aws:iam::123:user/bob")])]);
+        let settings = sync_settings(groups_mappings(&["Devops->devops"]), Vec::new());
+
+        // execute:
+        let res = render_config_map(
+            &iam,
+            &settings,
+            &ConfigMapOptions::default(),
+            Some(existing_aws_auth),
+        )
+        .await;
+
+        // verify:
+        assert!(res.is_ok());
+        let config_map: ConfigMap =
+            serde_yaml::from_str(&res.unwrap()).expect("rendered config map should be valid YAML");
+        assert_eq!(None, config_map.metadata.resource_version);
+        assert_eq!(None, config_map.metadata.uid);
+        let data = config_map.data.clone().unwrap_or_default();
+        // entries not synced by the tool are kept, the ones it doesn't sync anymore are removed
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:user/bob".to_string(),
+                    "bob".to_string(),
+                    vec!["devops".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/manual".to_string(),
+                    "manual".to_string(),
+                    vec!["view".to_string()],
+                    None,
+                ),
+            ],
+            map_users(&data)
+        );
+        assert_eq!(
+            vec![("arn:aws:iam::123:role/nodes".to_string(), None)],
+            map_roles(&data)
+        );
+    }
+
+    #[tokio::test]
+    async fn render_config_map_iam_error_test() {
+        // setup: IAM group doesn't exist
+        let iam = FakeIamUserSource::new(vec![]);
+        let settings = sync_settings(groups_mappings(&["Devops->devops"]), Vec::new());
+
+        // execute:
+        let res = render_config_map(&iam, &settings, &ConfigMapOptions::default(), None).await;
+
+        // verify:
+        assert!(matches!(res, Err(Error::Aws { .. })));
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![
+            ("Admins", vec![("alice", "arn:aws:iam::123:user/alice")]),
+            (
+                "Devops",
+                vec![
+                    ("alice", "arn:aws:iam::123:user/alice"),
+                    ("bob", "arn:aws:iam::123:user/bob"),
+                ],
+            ),
+        ]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::from_iter(vec![
+            (
+                "mapUsers".to_string(),
+                "- userarn: arn:aws:iam::123:user/manual\n  username: manual\n  groups:\n    - view\n"
+                    .to_string(),
+            ),
+            ("mapAccounts".to_string(), "- \"123\"\n".to_string()),
+        ]));
+        let karpenter_role = KubernetesRole::new(
+            IamArn::new("arn:aws:iam::123:role/karpenter"),
+            None,
+            Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+            Some(SyncedBy::IamEksUserMapper),
+        );
+        let settings = sync_settings(
+            groups_mappings(&["Admins->system:masters", "Devops->devops"]),
+            vec![karpenter_role],
+        );
+        let mut last_sync = None;
+
+        // execute:
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        assert!(res.is_ok());
+        let aws_auth = res.unwrap().aws_auth;
+        assert_eq!(3, aws_auth.users.len());
+        assert_eq!(1, aws_auth.roles.len());
+
+        let data = kubernetes.data();
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:user/alice".to_string(),
+                    "alice".to_string(),
+                    vec!["devops".to_string(), "system:masters".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/bob".to_string(),
+                    "bob".to_string(),
+                    vec!["devops".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/manual".to_string(),
+                    "manual".to_string(),
+                    vec!["view".to_string()],
+                    None,
+                ),
+            ],
+            map_users(&data)
+        );
+        assert!(data["mapRoles"].contains("arn:aws:iam::123:role/karpenter"));
+        assert_eq!("- \"123\"\n", data["mapAccounts"]);
+    }
+
+    #[tokio::test]
+    async fn sync_karpenter_roles_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let karpenter_role = |arn: &str| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let mut settings = sync_settings(
+            groups_mappings(&["Admins->system:masters"]),
+            vec![
+                karpenter_role("arn:aws:iam::123:role/karpenter"),
+                karpenter_role("arn:aws:iam::123:role/nodes"),
+            ],
+        );
+        let mut last_sync = None;
+
+        // execute:
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        assert!(res.is_ok());
+        let synced_by = Some("iam-eks-user-mapper".to_string());
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:role/karpenter".to_string(),
+                    synced_by.clone()
+                ),
+                ("arn:aws:iam::123:role/nodes".to_string(), synced_by.clone()),
+            ],
+            map_roles(&kubernetes.data())
+        );
+
+        // execute:
+        settings.karpenter_roles = vec![karpenter_role("arn:aws:iam::123:role/nodes")];
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        assert!(res.is_ok());
+        assert_eq!(
+            vec![("arn:aws:iam::123:role/nodes".to_string(), synced_by)],
+            map_roles(&kubernetes.data()),
+            "removed Karpenter role is unmapped"
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_with_backend_options_test() {
+        // setup:
+        struct TestCase {
+            options: ConfigMapOptions,
+            expected_users: Vec<&'static str>,
+            _description: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                options: ConfigMapOptions::default(),
+                expected_users: vec!["arn:aws:iam::123:user/alice"],
+                _description: "case 1 - default options, user is added",
+            },
+            TestCase {
+                options: ConfigMapOptions {
+                    removals_only: true,
+                    ..Default::default()
+                },
+                expected_users: vec![],
+                _description: "case 2 - removals only, addition is suppressed",
+            },
+        ];
+
+        for tc in test_cases {
+            let iam = FakeIamUserSource::new(vec![(
+                "Admins",
+                vec![("alice", "arn:aws:iam::123:user/alice")],
+            )]);
+            let kubernetes = FakeAuthBackend::new(BTreeMap::new()).with_options(tc.options);
+            let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+
+            // execute:
+            let res = sync_config_map(&iam, &kubernetes, &settings, &mut None).await;
+
+            // verify:
+            assert!(res.is_ok(), "{}", tc._description);
+            let current = kubernetes
+                .read_current("kube-system", "aws-auth")
+                .await
+                .expect("fake config map should be readable")
+                .expect("fake config map should exist");
+            assert_eq!(
+                tc.expected_users,
+                current
+                    .users
+                    .iter()
+                    .map(|u| u.iam_arn.to_string())
+                    .collect::<Vec<_>>(),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    fn group_mapping(
+        name: &str,
+        iam_group: &str,
+        kubernetes_groups: &[&str],
+        username_template: Option<&str>,
+    ) -> IamGroupMapping {
+        IamGroupMapping::new(
+            name,
+            IamGroupMappingSpec {
+                iam_group: iam_group.to_string(),
+                kubernetes_groups: kubernetes_groups.iter().map(|g| g.to_string()).collect(),
+                username_template: username_template.map(|t| t.to_string()),
+            },
+        )
+    }
+
+    #[test]
+    fn sync_settings_reload_test() {
+        // setup:
+        let mut settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        settings.node_roles = vec![KubernetesRole::new(
+            IamArn::new("arn:aws:iam::123:role/node"),
+            None,
+            None,
+            BTreeSet::new(),
+            Some(SyncedBy::IamEksUserMapper),
+        )];
+        let args = Args::try_parse_from([
+            "iam-eks-user-mapper",
+            "--service-account-name",
+            "iam-eks-user-mapper",
+            "--aws-role-arn",
+            "arn:aws:iam::123456789012:role/mapper",
+            "--aws-default-region",
+            "eu-west-3",
+            "--enable-group-user-sync",
+            "--iam-k8s-groups",
+            "Devops->system:devops",
+            "--karpenter-role-arn",
+            "arn:aws:iam::123456789012:role/karpenter",
+            "--config-map-name",
+            "other-aws-auth",
+        ])
+        .unwrap();
+        let config = config_from_args(&args).unwrap();
+
+        // execute:
+        settings.reload(&config);
+
+        // verify:
+        assert_eq!(
+            BTreeSet::from_iter(vec![IamGroup::new("Devops")]),
+            settings
+                .groups_mappings
+                .as_ref()
+                .map(|gm| gm.iam_groups())
+                .unwrap_or_default()
+        );
+        assert_eq!(1, settings.karpenter_roles.len());
+        assert_eq!(1, settings.node_roles.len(), "discovered roles are kept");
+        assert_eq!("aws-auth", settings.config_map_name, "config map is kept");
+    }
+
+    #[test]
+    fn groups_mappings_from_group_mappings_test() {
+        // setup:
+        let group_mappings = vec![
+            group_mapping("admins", "Admins", &["system:masters"], None),
+            group_mapping(
+                "admins-view",
+                "Admins",
+                &["view"],
+                Some("admin:{{username}}"),
+            ),
+            group_mapping(
+                "admins-other",
+                "Admins",
+                &["view"],
+                Some("other:{{username}}"),
+            ),
+            group_mapping("devops", "Devops", &["devops"], Some("other:{{username}}")),
+            group_mapping("invalid", "Invalid", &[], None),
+        ];
+
+        // execute:
+        let groups_mappings =
+            GroupsMappings::from_group_mappings(&group_mappings, &BTreeSet::new());
+
+        // verify:
+        assert_eq!(
+            BTreeSet::from_iter(vec![IamGroup::new("Admins"), IamGroup::new("Devops")]),
+            groups_mappings.iam_groups()
+        );
+        assert_eq!(
+            BTreeSet::from_iter(vec![
+                KubernetesGroupName::new("system:masters"),
+                KubernetesGroupName::new("view"),
+            ]),
+            groups_mappings
+                .k8s_group_for("alice", &BTreeSet::from_iter(vec![IamGroup::new("Admins")]))
+                .unwrap()
+        );
+        // first template wins on conflicts
+        assert_eq!(
+            Some("admin:alice".to_string()),
+            groups_mappings.username_for(
+                "alice",
+                &IamArn::new("arn:aws:iam::123:user/alice"),
+                &BTreeSet::from_iter(vec![IamGroup::new("Admins"), IamGroup::new("Devops")])
+            )
+        );
+        assert_eq!(
+            Some("other:bob".to_string()),
+            groups_mappings.username_for(
+                "bob",
+                &IamArn::new("arn:aws:iam::123:user/bob"),
+                &BTreeSet::from_iter(vec![IamGroup::new("Devops")])
+            )
+        );
+        // no template, `username_template` being used
+        assert_eq!(
+            None,
+            groups_mappings.username_for(
+                "carol",
+                &IamArn::new("arn:aws:iam::123:user/carol"),
+                &BTreeSet::from_iter(vec![IamGroup::new("Ops")])
+            )
+        );
+    }
+
+    /// Logs written so far, shared with the writer of the subscriber under test.
+    #[derive(Clone, Default)]
+    struct LogsBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogsBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn groups_mappings_username_templates_test() {
+        // setup:
+        let groups_mappings = groups_mappings(&[
+            "Platform->platform",
+            "TenantB-Devs->tenant-b-group|username=tenant-b:{name}",
+            "TenantA-Devs->tenant-a-group|username=tenant-a:{account}:{name}",
+            "TenantA-Devs->tenant-a-viewers",
+            "TenantA-Devs->tenant-a-group|username=ignored:{name}",
+        ]);
+        let arn = IamArn::new("arn:aws:iam::123456789012:user/alice");
+        let username_for = |iam_groups: &[&str]| {
+            groups_mappings.username_for(
+                "alice",
+                &arn,
+                &iam_groups.iter().map(|g| IamGroup::new(g)).collect(),
+            )
+        };
+        let logs = LogsBuffer::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+
+        // execute & verify:
+        assert_eq!(
+            Some("tenant-a:123456789012:alice".to_string()),
+            username_for(&["TenantA-Devs"]),
+            "first template set for an IAM group is kept"
+        );
+        assert_eq!(
+            Some("tenant-b:alice".to_string()),
+            username_for(&["Platform", "TenantB-Devs"])
+        );
+        // no template, `username_template` being used
+        assert_eq!(None, username_for(&["Platform"]));
+        // first IAM group in alphabetical order wins on conflicts, whatever the mappings order
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(
+                Some("tenant-a:123456789012:alice".to_string()),
+                username_for(&["TenantB-Devs", "TenantA-Devs", "Platform"])
+            );
+        });
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            logs.contains("IAM user `alice` is in IAM groups `TenantA-Devs` and `TenantB-Devs` having different username templates, using `tenant-a:{account}:{name}` of `TenantA-Devs`"),
+            "{logs}"
+        );
+    }
+
+    #[test]
+    fn groups_mappings_from_group_mappings_denied_groups_test() {
+        // setup:
+        let group_mappings = vec![
+            group_mapping("admins", "Admins", &["system:masters", "view"], None),
+            group_mapping("devops", "Devops", &["devops"], None),
+        ];
+
+        // execute:
+        let groups_mappings = GroupsMappings::from_group_mappings(
+            &group_mappings,
+            &BTreeSet::from_iter(vec![KubernetesGroupName::new("system:masters")]),
+        );
+
+        // verify:
+        // mappings into a denied group are ignored as a whole
+        assert_eq!(
+            BTreeSet::from_iter(vec![IamGroup::new("Devops")]),
+            groups_mappings.iam_groups()
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_with_username_template_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![
+            ("Admins", vec![("alice", "arn:aws:iam::123:user/alice")]),
+            ("Devops", vec![("bob", "arn:aws:iam::123:user/bob")]),
+        ]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let settings = sync_settings(
+            GroupsMappings::from_group_mappings(
+                &[
+                    group_mapping(
+                        "admins",
+                        "Admins",
+                        &["system:masters"],
+                        Some("sso:{{username}}"),
+                    ),
+                    group_mapping("devops", "Devops", &["devops"], None),
+                ],
+                &BTreeSet::new(),
+            ),
+            Vec::new(),
+        );
+        let mut last_sync = None;
+
+        // execute:
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        let outcome = res.expect("sync should succeed");
+        assert_eq!(
+            Some(&1),
+            outcome.users_per_iam_group.get(&IamGroup::new("Admins"))
+        );
+        assert_eq!(
+            Some(&1),
+            outcome.users_per_iam_group.get(&IamGroup::new("Devops"))
+        );
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:user/alice".to_string(),
+                    "sso:alice".to_string(),
+                    vec!["system:masters".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/bob".to_string(),
+                    "bob".to_string(),
+                    vec!["devops".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+            ],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_with_global_username_template_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![
+            ("Admins", vec![("alice", "arn:aws:iam::123:user/alice")]),
+            ("Devops", vec![("bob", "arn:aws:iam::123:user/ops/bob")]),
+        ]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let mut settings = sync_settings(
+            GroupsMappings::from_group_mappings(
+                &[
+                    group_mapping(
+                        "admins",
+                        "Admins",
+                        &["system:masters"],
+                        Some("sso:{{username}}"),
+                    ),
+                    group_mapping("devops", "Devops", &["devops"], None),
+                ],
+                &BTreeSet::new(),
+            ),
+            Vec::new(),
+        );
+        settings.username_template = UsernameTemplate::from_str("iam:{path}:{name}").unwrap();
+        settings.admins_users =
+            BTreeSet::from_iter(vec![IamArn::new("arn:aws:iam::123:user/carol")]);
+        let mut last_sync = None;
+
+        // execute:
+        let first_res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+        // not skipping the update, for the existing entries to be diffed again
+        last_sync = None;
+        let second_res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        assert!(first_res.is_ok());
+        // template is applied to IAM user names, not again to already templated entries
+        assert!(second_res.expect("sync should succeed").diff.is_empty());
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:user/alice".to_string(),
+                    "sso:alice".to_string(),
+                    vec!["system:masters".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/carol".to_string(),
+                    "iam::carol".to_string(),
+                    vec!["system:masters".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/ops/bob".to_string(),
+                    "iam:ops:bob".to_string(),
+                    vec!["devops".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+            ],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_with_mapping_username_template_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![
+            (
+                "TenantA-Devs",
+                vec![("alice", "arn:aws:iam::123:user/alice")],
+            ),
+            ("Platform", vec![("bob", "arn:aws:iam::123:user/bob")]),
+        ]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let mut settings = sync_settings(
+            groups_mappings(&[
+                "TenantA-Devs->tenant-a-group|username=tenant-a:{name}",
+                "Platform->platform",
+            ]),
+            Vec::new(),
+        );
+        settings.username_template = UsernameTemplate::from_str("iam:{name}").unwrap();
+        let mut last_sync = None;
+
+        // execute:
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        assert!(res.is_ok());
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:user/alice".to_string(),
+                    "tenant-a:alice".to_string(),
+                    vec!["tenant-a-group".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+                (
+                    "arn:aws:iam::123:user/bob".to_string(),
+                    "iam:bob".to_string(),
+                    vec!["platform".to_string()],
+                    Some("iam-eks-user-mapper".to_string()),
+                ),
+            ],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_lowercase_usernames_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("Alice", "arn:aws:iam::123:user/Alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::from_iter(vec![(
+            "mapUsers".to_string(),
+            "- userarn: arn:aws:iam::123:user/Alice\n  username: Alice\n  groups:\n    - system:masters\n  syncedBy: iam-eks-user-mapper\n"
+                .to_string(),
+        )]));
+        let mut settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        settings.lowercase_usernames = true;
+        let mut last_sync = None;
+
+        // execute:
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        let outcome = res.expect("sync should succeed");
+        // same user, updated rather than duplicated
+        assert!(outcome.diff.users.added.is_empty());
+        assert!(outcome.diff.users.removed.is_empty());
+        assert_eq!(1, outcome.diff.users.modified.len());
+        assert_eq!(
+            vec![(
+                "arn:aws:iam::123:user/Alice".to_string(),
+                "alice".to_string(),
+                vec!["system:masters".to_string()],
+                Some("iam-eks-user-mapper".to_string()),
+            )],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[test]
+    fn username_collisions_test() {
+        // setup:
+        let user = |username: &str, arn: &str| {
+            KubernetesUser::new(
+                IamUserName::new(username),
+                IamArn::new(arn),
+                BTreeSet::new(),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let users = vec![
+            user("iam:alice", "arn:aws:iam::123:user/ops/alice"),
+            user("iam:alice", "arn:aws:iam::123:user/alice"),
+            user("iam:bob", "arn:aws:iam::123:user/bob"),
+        ];
+
+        // execute:
+        let collisions = username_collisions(&users);
+
+        // verify:
+        assert_eq!(
+            BTreeMap::from_iter(vec![(
+                "iam:alice".to_string(),
+                vec![
+                    "arn:aws:iam::123:user/alice".to_string(),
+                    "arn:aws:iam::123:user/ops/alice".to_string(),
+                ],
+            )]),
+            collisions
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_to_clusters_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let cluster = |context: &str, backend: FakeAuthBackend| ClusterTarget {
+            context: context.to_string(),
+            backend,
+            last_sync: None,
+        };
+        let mut clusters = vec![
+            cluster("cluster-a", FakeAuthBackend::new(BTreeMap::new())),
+            cluster("cluster-down", FakeAuthBackend::unreachable()),
+            cluster(
+                "cluster-b",
+                FakeAuthBackend::new(BTreeMap::from_iter(vec![(
+                    "mapUsers".to_string(),
+                    "- userarn: arn:aws:iam::123:user/manual\n  username: manual\n  groups:\n    - view\n"
+                        .to_string(),
+                )])),
+            ),
+        ];
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+
+        // execute:
+        let res =
+            sync_iam_eks_users_and_roles_to_clusters(&iam, &mut clusters, &settings, &deadline())
+                .await;
+
+        // verify:
+        let outcomes = res.expect("IAM users should be fetched");
+        assert_eq!(
+            vec![
+                ("cluster-a".to_string(), true),
+                ("cluster-down".to_string(), false),
+                ("cluster-b".to_string(), true),
+            ],
+            outcomes
+                .iter()
+                .map(|(context, outcome)| (context.clone(), outcome.is_ok()))
+                .collect::<Vec<_>>()
+        );
+        let alice = (
+            "arn:aws:iam::123:user/alice".to_string(),
+            "alice".to_string(),
+            vec!["system:masters".to_string()],
+            Some("iam-eks-user-mapper".to_string()),
+        );
+        assert_eq!(vec![alice.clone()], map_users(&clusters[0].backend.data()));
+        assert_eq!(
+            vec![
+                alice,
+                (
+                    "arn:aws:iam::123:user/manual".to_string(),
+                    "manual".to_string(),
+                    vec!["view".to_string()],
+                    None,
+                ),
+            ],
+            map_users(&clusters[2].backend.data())
+        );
+        // failed cluster is synced again on next sync, others being skipped as unchanged
+        assert!(clusters[0].last_sync.is_some());
+        assert!(clusters[1].last_sync.is_none());
+        assert!(clusters[2].last_sync.is_some());
+    }
+
+    #[test]
+    fn config_map_targets_new_test() {
+        // setup:
+        let backend = FakeAuthBackend::new(BTreeMap::new());
+        let config_map = ConfigMapTarget {
+            namespace: "kube-system".to_string(),
+            name: "aws-auth".to_string(),
+            last_sync: None,
+        };
+
+        // execute & verify:
+        assert!(ConfigMapTargets::new(&backend, vec![config_map]).is_ok());
+        assert!(matches!(
+            ConfigMapTargets::new(&backend, Vec::new()),
+            Err(ConfigurationError::NoConfigMapTargets)
+        ));
+    }
+
+    #[tokio::test]
+    async fn sync_loop_sync_once_test() {
+        struct TestCase<'a> {
+            clusters: Vec<(&'a str, FakeAuthBackend)>,
+            leadership: Option<Leadership>,
+            expected: Result<&'a str, &'a str>,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                clusters: vec![
+                    ("cluster-a", FakeAuthBackend::new(BTreeMap::new())),
+                    ("cluster-down", FakeAuthBackend::unreachable()),
+                ],
+                leadership: None,
+                expected: Ok("succeeded"),
+                _description: "case 1 - a cluster down not failing the sync",
+            },
+            TestCase {
+                clusters: vec![
+                    ("cluster-down", FakeAuthBackend::unreachable()),
+                    ("other-cluster-down", FakeAuthBackend::unreachable()),
+                ],
+                leadership: None,
+                expected: Err("Kubernetes error"),
+                _description: "case 2 - no cluster synced",
+            },
+            TestCase {
+                clusters: vec![("cluster-down", FakeAuthBackend::unreachable())],
+                leadership: Some(Leadership::default()),
+                expected: Ok("skipped"),
+                _description: "case 3 - not the leader",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let iam = FakeIamUserSource::new(vec![(
+                "Admins",
+                vec![("alice", "arn:aws:iam::123:user/alice")],
+            )]);
+            let args = Args::try_parse_from([
+                "iam-eks-user-mapper",
+                "--service-account-name=iam-eks-user-mapper",
+                "--aws-role-arn=arn:aws:iam::123456789012:role/iam-eks-user-mapper",
+                "--aws-default-region=eu-west-3",
+                "--enable-group-user-sync",
+                "--iam-k8s-groups=Admins->system:masters",
+            ])
+            .unwrap();
+            let config = config_from_args(&args).unwrap();
+            let clusters = tc
+                .clusters
+                .into_iter()
+                .map(|(context, backend)| ClusterTarget {
+                    context: context.to_string(),
+                    backend,
+                    last_sync: None,
+                })
+                .collect::<Vec<_>>();
+            let mut sync_loop = SyncLoop::new(&config, &iam, clusters, ()).with_hooks(SyncHooks {
+                leadership: tc.leadership,
+                ..SyncHooks::default()
+            });
+
+            // execute:
+            let res = sync_loop.sync_once().await;
+
+            // verify:
+            match (tc.expected, res) {
+                (
+                    Ok("succeeded"),
+                    Ok(SyncReport::Succeeded {
+                        summary,
+                        users_added,
+                        ..
+                    }),
+                ) => {
+                    assert_eq!(1, summary.users.added, "{}", tc._description);
+                    assert_eq!(
+                        vec!["arn:aws:iam::123:user/alice".to_string()],
+                        users_added
+                            .iter()
+                            .map(|u| u.iam_arn.to_string())
+                            .collect::<Vec<_>>(),
+                        "{}",
+                        tc._description
+                    );
+                }
+                (Ok("skipped"), Ok(SyncReport::Skipped { .. })) => {}
+                (Err(expected), Err(e)) => {
+                    assert!(e.to_string().contains(expected), "{}: {e}", tc._description)
+                }
+                (expected, res) => {
+                    panic!("{}: expected {expected:?}, got {res:?}", tc._description)
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_iam_roles_test() {
+        // setup:
+        let sso_role_arn = "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-3/AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac";
+        let iam = FakeIamUserSource::new(vec![])
+            .with_roles(&[sso_role_arn])
+            .with_instance_profiles(&[(
+                "karpenter",
+                "arn:aws:iam::123456789012:role/karpenter-node",
+            )]);
+        let args = Args::try_parse_from([
+            "iam-eks-user-mapper",
+            "--service-account-name=iam-eks-user-mapper",
+            "--aws-role-arn=arn:aws:iam::123456789012:role/mapper",
+            "--aws-default-region=eu-west-3",
+            "--enable-sso",
+            "--iam-sso-permission-set=AdministratorAccess",
+            "--karpenter-role-arn=arn:aws:iam::123456789012:instance-profile/karpenter",
+            "--resolve-instance-profile",
+        ])
+        .unwrap();
+        let mut settings = SyncSettings::new(&config_from_args(&args).unwrap());
+
+        // execute:
+        let res = resolve_iam_roles(&iam, &mut settings, &deadline()).await;
+
+        // verify:
+        assert!(res.is_ok());
+        assert_eq!(
+            vec!["arn:aws:iam::123456789012:role/karpenter-node"],
+            settings
+                .karpenter_roles
+                .iter()
+                .map(|r| r.iam_role_arn.to_string())
+                .collect::<Vec<_>>()
+        );
+        // path is dropped, aws-auth not matching role ARNs with one
+        assert_eq!(
+            Some("arn:aws:iam::123456789012:role/AWSReservedSSO_AdministratorAccess_53b82e109c5e2cac".to_string()),
+            settings.sso_role.map(|r| r.iam_role_arn.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_to_config_maps_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let manual_user = |name: &str| {
+            FakeAuthBackend::new(BTreeMap::from_iter(vec![(
+                "mapUsers".to_string(),
+                format!("- userarn: arn:aws:iam::123:user/{name}\n  username: {name}\n  groups:\n    - view\n"),
+            )]))
+        };
+        let kubernetes = FakeConfigMapsBackend::new(vec![
+            ("kube-system/aws-auth", manual_user("manual")),
+            ("preview/aws-auth-shadow", manual_user("previewer")),
+        ]);
+        let config_map = |namespace: &str, name: &str| ConfigMapTarget {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            last_sync: None,
+        };
+        let mut config_maps = vec![
+            config_map("kube-system", "aws-auth"),
+            config_map("preview", "missing"),
+            config_map("preview", "aws-auth-shadow"),
+        ];
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+
+        // execute:
+        let res = sync_iam_eks_users_and_roles(
+            &iam,
+            &kubernetes,
+            &mut config_maps,
+            &settings,
+            &deadline(),
+        )
+        .await;
+
+        // verify:
+        let outcomes = res.expect("IAM users should be fetched");
+        assert_eq!(
+            vec![
+                ("kube-system/aws-auth".to_string(), true),
+                ("preview/missing".to_string(), false),
+                ("preview/aws-auth-shadow".to_string(), true),
+            ],
+            outcomes
+                .iter()
+                .map(|(target, outcome)| (target.clone(), outcome.is_ok()))
+                .collect::<Vec<_>>()
+        );
+        let alice = (
+            "arn:aws:iam::123:user/alice".to_string(),
+            "alice".to_string(),
+            vec!["system:masters".to_string()],
+            Some("iam-eks-user-mapper".to_string()),
+        );
+        let manual = |name: &str| {
+            (
+                format!("arn:aws:iam::123:user/{name}"),
+                name.to_string(),
+                vec!["view".to_string()],
+                None,
+            )
+        };
+        assert_eq!(
+            vec![alice.clone(), manual("manual")],
+            map_users(&kubernetes.config_map("kube-system/aws-auth").data())
+        );
+        assert_eq!(
+            vec![alice, manual("previewer")],
+            map_users(&kubernetes.config_map("preview/aws-auth-shadow").data())
+        );
+        assert!(config_maps[0].last_sync.is_some());
+        assert!(config_maps[1].last_sync.is_none());
+        assert!(config_maps[2].last_sync.is_some());
+        // any config map failure fails the sync, for it to be retried with backoff and reported
+        assert!(matches!(
+            config_maps_diff(outcomes),
+            Err(Error::Kubernetes { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_skips_unchanged_state_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::default();
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        let mut last_sync = None;
+
+        // execute:
+        for _ in 0..3 {
+            sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+                .await
+                .expect("sync should succeed");
+        }
+
+        // verify:
+        assert_eq!(1, kubernetes.updates_count());
+
+        // someone else modifies the config map, it should be rewritten
+        kubernetes.set_data(BTreeMap::default());
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(2, kubernetes.updates_count());
+        assert_eq!(1, map_users(&kubernetes.data()).len());
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_synced_entry_ttl_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::from_iter(vec![(
+            "mapUsers".to_string(),
+            "- userarn: arn:aws:iam::123:user/alice\n  username: alice\n  groups:\n  - system:masters\n  syncedBy: iam-eks-user-mapper\n  syncedAt: '2020-01-01T00:00:00Z'\n".to_string(),
+        )]))
+        .with_options(ConfigMapOptions {
+            synced_entry_ttl: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            ..Default::default()
+        });
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        let mut last_sync = None;
+        let synced_at = |kubernetes: &FakeAuthBackend| {
+            serde_yaml::from_str::<Vec<BTreeMap<String, serde_yaml::Value>>>(
+                kubernetes
+                    .data()
+                    .get("mapUsers")
+                    .expect("mapUsers should be set"),
+            )
+            .expect("mapUsers should be valid YAML")
+            .iter()
+            .map(|e| e["syncedAt"].as_str().unwrap_or_default().to_string())
+            .collect::<Vec<_>>()
+        };
+
+        // execute & verify:
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(1, kubernetes.updates_count());
+        assert!(
+            map_users(&kubernetes.data()).is_empty(),
+            "expired entry is removed even if still granted access"
+        );
+
+        // IAM state and config map are unchanged since last sync, expired entry should be added back
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(2, kubernetes.updates_count());
+        let re_added_synced_at = synced_at(&kubernetes);
+        assert_eq!(1, re_added_synced_at.len());
+        assert_ne!("2020-01-01T00:00:00Z", re_added_synced_at[0]);
+
+        // re-added entry has not expired yet, nothing changed
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(2, kubernetes.updates_count());
+        assert_eq!(re_added_synced_at, synced_at(&kubernetes));
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_audit_log_test() {
+        // setup:
+        let dir = std::env::temp_dir().join(format!(
+            "iam-eks-user-mapper-sync-audit-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).expect("cannot create temp dir");
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::default();
+        let mut settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        settings.audit_log = Some(AuditLog::new(dir.join("audit.log"), true));
+        let mut last_sync = None;
+
+        // execute:
+        for _ in 0..2 {
+            sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+                .await
+                .expect("sync should succeed");
+        }
+
+        // verify:
+        let written =
+            std::fs::read_to_string(dir.join("audit.log")).expect("audit log should exist");
+        let records: Vec<serde_json::Value> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("record should be valid JSON"))
+            .collect();
+        assert_eq!(1, records.len(), "unchanged config map is not recorded");
+        assert_eq!("0", records[0]["resourceVersionBefore"]);
+        assert_eq!("1", records[0]["resourceVersionAfter"]);
+        assert_eq!(
+            "arn:aws:iam::123:user/alice",
+            records[0]["added"]["users"][0]["arn"]
+        );
+
+        std::fs::remove_dir_all(&dir).expect("cannot clean temp dir");
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_audit_log_required_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let missing_dir_path = std::env::temp_dir()
+            .join("iam-eks-user-mapper-sync-audit-missing-dir")
+            .join("audit.log");
+
+        for (required, expected_ok) in [(false, true), (true, false)] {
+            let kubernetes = FakeAuthBackend::default();
+            let mut settings =
+                sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+            settings.audit_log = Some(AuditLog::new(missing_dir_path.clone(), required));
+            let mut last_sync = None;
+
+            // execute:
+            let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+            // verify:
+            assert_eq!(expected_ok, res.is_ok(), "required: {required}");
+            assert!(!required || matches!(res, Err(Error::Audit { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_removes_users_leaving_groups_test() {
+        // setup:
+        let kubernetes = FakeAuthBackend::default();
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        let mut last_sync = None;
+        sync_config_map(
+            &FakeIamUserSource::new(vec![(
+                "Admins",
+                vec![
+                    ("alice", "arn:aws:iam::123:user/alice"),
+                    ("bob", "arn:aws:iam::123:user/bob"),
+                ],
+            )]),
+            &kubernetes,
+            &settings,
+            &mut last_sync,
+        )
+        .await
+        .expect("sync should succeed");
+        assert_eq!(2, map_users(&kubernetes.data()).len());
+
+        // execute:
+        let res = sync_config_map(
+            &FakeIamUserSource::new(vec![("Admins", vec![("bob", "arn:aws:iam::123:user/bob")])]),
+            &kubernetes,
+            &settings,
+            &mut last_sync,
+        )
+        .await;
+
+        // verify:
+        assert!(res.is_ok());
+        let users = map_users(&kubernetes.data());
+        assert_eq!(1, users.len());
+        assert_eq!("bob", users[0].1);
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_full_cycles_test() {
+        // setup:
+        let kubernetes = FakeAuthBackend::new(BTreeMap::from_iter(vec![(
+            "mapUsers".to_string(),
+            "- userarn: arn:aws:iam::123:user/manual\n  username: manual\n  groups:\n    - view\n"
+                .to_string(),
+        )]));
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        let mut last_sync = None;
+        let alice = ("alice", "arn:aws:iam::123:user/alice");
+        let bob = ("bob", "arn:aws:iam::123:user/bob");
+        let manual = (
+            "arn:aws:iam::123:user/manual".to_string(),
+            "manual".to_string(),
+            vec!["view".to_string()],
+            None,
+        );
+        let synced = |name: &str, arn: &str| {
+            (
+                arn.to_string(),
+                name.to_string(),
+                vec!["system:masters".to_string()],
+                Some("iam-eks-user-mapper".to_string()),
+            )
+        };
+
+        // execute & verify: alice is added
+        sync_config_map(
+            &FakeIamUserSource::new(vec![("Admins", vec![alice])]),
+            &kubernetes,
+            &settings,
+            &mut last_sync,
+        )
+        .await
+        .expect("sync should succeed");
+        assert_eq!(
+            vec![synced(alice.0, alice.1), manual.clone()],
+            map_users(&kubernetes.data())
+        );
+
+        // execute & verify: bob is added
+        sync_config_map(
+            &FakeIamUserSource::new(vec![("Admins", vec![alice, bob])]),
+            &kubernetes,
+            &settings,
+            &mut last_sync,
+        )
+        .await
+        .expect("sync should succeed");
+        assert_eq!(
+            vec![
+                synced(alice.0, alice.1),
+                synced(bob.0, bob.1),
+                manual.clone()
+            ],
+            map_users(&kubernetes.data())
+        );
+
+        // execute & verify: alice is removed
+        sync_config_map(
+            &FakeIamUserSource::new(vec![("Admins", vec![bob])]),
+            &kubernetes,
+            &settings,
+            &mut last_sync,
+        )
+        .await
+        .expect("sync should succeed");
+        assert_eq!(
+            vec![synced(bob.0, bob.1), manual.clone()],
+            map_users(&kubernetes.data())
+        );
+
+        // execute & verify: nothing changed, aws-auth isn't written
+        let updates_count = kubernetes.updates_count();
+        sync_config_map(
+            &FakeIamUserSource::new(vec![("Admins", vec![bob])]),
+            &kubernetes,
+            &settings,
+            &mut last_sync,
+        )
+        .await
+        .expect("sync should succeed");
+        assert_eq!(updates_count, kubernetes.updates_count());
+        assert_eq!(
+            vec![synced(bob.0, bob.1), manual],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_node_roles_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::from_iter(vec![(
+            "mapRoles".to_string(),
+            "- rolearn: arn:aws:iam::123:role/nodes\n  username: system:node:{{EC2PrivateDNSName}}\n  groups:\n    - system:bootstrappers\n    - system:nodes\n\
+             - rolearn: arn:aws:iam::123:role/ops\n  username: ops\n  groups:\n    - view\n"
+                .to_string(),
+        )]));
+        let node_role = |arn: &str| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some("system:node:{{EC2PrivateDNSName}}".to_string()),
+                BTreeSet::from_iter(vec![
+                    KubernetesGroupName::new("system:bootstrappers"),
+                    KubernetesGroupName::new("system:nodes"),
+                ]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let mut settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        let mut last_sync = None;
+
+        // execute & verify:
+        // manual entry of a discovered role is merged into a single synced one
+        settings.node_roles = vec![node_role("arn:aws:iam::123:role/nodes")];
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:role/nodes".to_string(),
+                    Some("iam-eks-user-mapper".to_string())
+                ),
+                ("arn:aws:iam::123:role/ops".to_string(), None),
+            ],
+            map_roles(&kubernetes.data())
+        );
+
+        // nodegroup role changed, synced entry follows
+        settings.node_roles = vec![node_role("arn:aws:iam::123:role/nodes-v2")];
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(
+            vec![
+                (
+                    "arn:aws:iam::123:role/nodes-v2".to_string(),
+                    Some("iam-eks-user-mapper".to_string())
+                ),
+                ("arn:aws:iam::123:role/ops".to_string(), None),
+            ],
+            map_roles(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_static_roles_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let static_role = |arn: &str, username: &str| {
+            KubernetesRole::new(
+                IamArn::new(arn),
+                None,
+                Some(username.to_string()),
+                BTreeSet::from_iter(vec![KubernetesGroupName::new("ci")]),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let mut settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        settings.static_roles = vec![
+            static_role("arn:aws:iam::123456789012:role/ci", "ci-deployer"),
+            static_role("arn:aws:iam::123456789012:role/backup", "backup-operator"),
+        ];
+        let mut last_sync = None;
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(2, map_roles(&kubernetes.data()).len());
+
+        // execute:
+        settings.static_roles.pop();
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut last_sync).await;
+
+        // verify:
+        assert!(res.is_ok());
+        assert_eq!(
+            vec![(
+                "arn:aws:iam::123456789012:role/ci".to_string(),
+                Some("iam-eks-user-mapper".to_string())
+            )],
+            map_roles(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_static_users_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Admins",
+            vec![
+                ("alice", "arn:aws:iam::123:user/alice"),
+                ("bob", "arn:aws:iam::123:user/bob"),
+            ],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let static_user = |arn: &str, username: &str, groups: &[&str]| {
+            KubernetesUser::new(
+                IamUserName::new(username),
+                IamArn::new(arn),
+                groups.iter().map(|g| KubernetesGroupName::new(g)).collect(),
+                Some(SyncedBy::IamEksUserMapper),
+            )
+        };
+        let mut settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+        settings.static_users = vec![
+            static_user("arn:aws:iam::123:user/contractor", "contractor", &["view"]),
+            static_user("arn:aws:iam::123:user/bob", "bob-ci", &["ci"]),
+        ];
+        let mut last_sync = None;
+        let synced_user = |arn: &str, username: &str, groups: &[&str]| {
+            (
+                arn.to_string(),
+                username.to_string(),
+                groups.iter().map(|g| g.to_string()).collect::<Vec<_>>(),
+                Some("iam-eks-user-mapper".to_string()),
+            )
+        };
+
+        // execute & verify:
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(
+            vec![
+                synced_user("arn:aws:iam::123:user/alice", "alice", &["system:masters"]),
+                synced_user(
+                    "arn:aws:iam::123:user/bob",
+                    "bob-ci",
+                    &["ci", "system:masters"]
+                ),
+                synced_user("arn:aws:iam::123:user/contractor", "contractor", &["view"]),
+            ],
+            map_users(&kubernetes.data())
+        );
+
+        // removed static users go back to IAM groups users
+        settings.static_users.clear();
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(
+            vec![
+                synced_user("arn:aws:iam::123:user/alice", "alice", &["system:masters"]),
+                synced_user("arn:aws:iam::123:user/bob", "bob", &["system:masters"]),
+            ],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_admins_users_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![(
+            "Devops",
+            vec![("alice", "arn:aws:iam::123:user/alice")],
+        )]);
+        let kubernetes = FakeAuthBackend::new(BTreeMap::new());
+        let mut settings = sync_settings(groups_mappings(&["Devops->devops"]), Vec::new());
+        settings.admins_users = BTreeSet::from_iter(vec![
+            IamArn::new("arn:aws:iam::123:user/alice"),
+            IamArn::new("arn:aws:iam::123:user/ops/bob"),
+        ]);
+        let mut last_sync = None;
+        let synced_user = |arn: &str, username: &str, groups: &[&str]| {
+            (
+                arn.to_string(),
+                username.to_string(),
+                groups.iter().map(|g| g.to_string()).collect::<Vec<_>>(),
+                Some("iam-eks-user-mapper".to_string()),
+            )
+        };
+
+        // execute & verify:
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(
+            vec![
+                synced_user(
+                    "arn:aws:iam::123:user/alice",
+                    "alice",
+                    &["devops", "system:masters"]
+                ),
+                synced_user("arn:aws:iam::123:user/ops/bob", "bob", &["system:masters"]),
+            ],
+            map_users(&kubernetes.data())
+        );
+
+        // dropping the flag removes admins granted through it only
+        settings.admins_users.clear();
+        sync_config_map(&iam, &kubernetes, &settings, &mut last_sync)
+            .await
+            .expect("sync should succeed");
+        assert_eq!(
+            vec![synced_user(
+                "arn:aws:iam::123:user/alice",
+                "alice",
+                &["devops"]
+            )],
+            map_users(&kubernetes.data())
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_iam_eks_users_and_roles_iam_error_test() {
+        // setup:
+        let iam = FakeIamUserSource::new(vec![("Admins", vec![])]);
+        let kubernetes = FakeAuthBackend::default();
+        let settings = sync_settings(groups_mappings(&["Admins->system:masters"]), Vec::new());
+
+        // execute:
+        let res = sync_config_map(&iam, &kubernetes, &settings, &mut None).await;
+
+        // verify:
+        assert!(res.is_err());
+        assert_eq!(0, kubernetes.updates_count());
+    }
+
+    #[tokio::test]
+    async fn next_change_is_debounced_test() {
+        // setup:
+        let debounce = Duration::from_millis(100);
+        let (sender, mut changes) = mpsc::channel(1);
+        let mut last_change_sync = None;
+
+        // execute & verify:
+        sender.try_send(()).expect("change should be sent");
+        let start = Instant::now();
+        assert_eq!(
+            Some(()),
+            next_change(&mut changes, debounce, &mut last_change_sync).await
+        );
+        assert!(start.elapsed() < debounce, "first change is not debounced");
+
+        sender.try_send(()).expect("change should be sent");
+        // channel is full, change is coalesced with the pending one
+        assert!(sender.try_send(()).is_err());
+        assert_eq!(
+            Some(()),
+            next_change(&mut changes, debounce, &mut last_change_sync).await
+        );
+        assert!(start.elapsed() >= debounce, "second change is debounced");
+        assert!(changes.try_recv().is_err());
+
+        drop(sender);
+        assert_eq!(
+            None,
+            next_change(&mut changes, debounce, &mut last_change_sync).await
+        );
+    }
+
+    #[test]
+    fn sync_backoff_test() {
+        struct TestCase<'a> {
+            max_backoff: Duration,
+            failures: u32,
+            expected_delay: Duration,
+            _description: &'a str,
+        }
+
+        let refresh_interval = Duration::from_secs(30);
+        let test_cases = vec![
+            TestCase {
+                max_backoff: Duration::from_secs(900),
+                failures: 1,
+                expected_delay: Duration::from_secs(60),
+                _description: "interval doubled after a failure",
+            },
+            TestCase {
+                max_backoff: Duration::from_secs(900),
+                failures: 4,
+                expected_delay: Duration::from_secs(480),
+                _description: "interval doubled on each failure",
+            },
+            TestCase {
+                max_backoff: Duration::from_secs(900),
+                failures: 5,
+                expected_delay: Duration::from_secs(900),
+                _description: "delay capped at max backoff",
+            },
+            TestCase {
+                max_backoff: Duration::from_secs(900),
+                failures: 200,
+                expected_delay: Duration::from_secs(900),
+                _description: "delay capped at max backoff without overflowing",
+            },
+            TestCase {
+                max_backoff: Duration::from_secs(10),
+                failures: 3,
+                expected_delay: Duration::from_secs(30),
+                _description: "no backoff with max backoff shorter than interval",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let mut backoff = SyncBackoff::new(tc.max_backoff, None, true);
+
+            // execute:
+            let delays: Vec<Duration> = (0..tc.failures)
+                .map(|_| backoff.failed(refresh_interval))
+                .collect();
+
+            // verify:
+            assert_eq!(
+                Some(&tc.expected_delay),
+                delays.last(),
+                "{}",
+                tc._description
+            );
+            assert!(
+                delays.windows(2).all(|w| w[0] <= w[1]),
+                "{}",
+                tc._description
+            );
+            assert_eq!(tc.failures, backoff.succeeded(), "{}", tc._description);
+            assert_eq!(
+                refresh_interval,
+                backoff.delay(refresh_interval),
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[test]
+    fn sync_backoff_exhausted_test() {
+        // setup:
+        let refresh_interval = Duration::from_secs(30);
+        let mut unlimited = SyncBackoff::new(Duration::from_secs(900), None, true);
+        let mut limited = SyncBackoff::new(Duration::from_secs(900), Some(3), true);
+
+        // execute & verify:
+        for _ in 0..100 {
+            unlimited.failed(refresh_interval);
+        }
+        assert!(!unlimited.exhausted());
+
+        limited.failed(refresh_interval);
+        limited.failed(refresh_interval);
+        assert!(!limited.exhausted());
+        // counter is reset on success
+        limited.succeeded();
+        limited.failed(refresh_interval);
+        limited.failed(refresh_interval);
+        assert!(!limited.exhausted());
+        limited.failed(refresh_interval);
+        assert!(limited.exhausted());
+    }
+
+    #[tokio::test]
+    async fn back_off_fatal_error_test() {
+        struct TestCase<'a> {
+            groups_mappings: Vec<&'a str>,
+            config_map_name: &'a str,
+            fail_fast: bool,
+            expected_exit: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                groups_mappings: vec!["Admins->system:masters"],
+                config_map_name: "wrong-name",
+                fail_fast: true,
+                expected_exit: true,
+                _description: "case 1 - wrong config map name, failing fast",
+            },
+            TestCase {
+                groups_mappings: vec!["Admins->system:masters"],
+                config_map_name: "wrong-name",
+                fail_fast: false,
+                expected_exit: false,
+                _description: "case 2 - wrong config map name, not failing fast",
+            },
+            TestCase {
+                groups_mappings: vec!["Admins->system:masters", "Developers->developers"],
+                config_map_name: "aws-auth",
+                fail_fast: true,
+                expected_exit: false,
+                _description: "case 3 - IAM group without users yet, failing fast",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let iam = FakeIamUserSource::new(vec![(
+                "Admins",
+                vec![("alice", "arn:aws:iam::123:user/alice")],
+            )]);
+            let kubernetes = FakeConfigMapsBackend::new(vec![(
+                "kube-system/aws-auth",
+                FakeAuthBackend::default(),
+            )]);
+            let mut settings = sync_settings(groups_mappings(&tc.groups_mappings), Vec::new());
+            settings.config_map_name = tc.config_map_name.to_string();
+            let mut schedule =
+                RefreshSchedule::new(Duration::from_secs(30), Duration::ZERO, Some(42));
+            let mut sync_backoff = SyncBackoff::new(Duration::from_secs(900), None, tc.fail_fast);
+
+            // execute:
+            let res = match sync_config_map(&iam, &kubernetes, &settings, &mut None).await {
+                Ok(_) => panic!("{}: first sync should fail", tc._description),
+                Err(e) => back_off(&mut sync_backoff, &mut schedule, e),
+            };
+
+            // verify:
+            assert_eq!(tc.expected_exit, res.is_err(), "{}", tc._description);
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_deadline_test() {
+        // setup:
+        let deadline = SyncDeadline::after(Duration::from_millis(50));
+
+        // execute:
+        let fast = deadline.run("fetching IAM users", async { Ok(42) }).await;
+        let stuck = deadline
+            .run("fetching IAM users", async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(42)
+            })
+            .await;
+
+        // verify:
+        assert_eq!(42, fast.expect("fast phase should not time out"));
+        match stuck {
+            Err(Error::SyncTimeout { phase, .. }) => {
+                assert_eq!("fetching IAM users", phase.as_ref())
+            }
+            res => panic!("stuck phase should time out, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn refresh_schedule_missed_ticks_test() {
+        struct TestCase<'a> {
+            missed_tick_behavior: MissedTickBehavior,
+            sync_duration: Duration,
+            expected_skipped: u32,
+            expected_next_tick: Duration,
+            _description: &'a str,
+        }
+
+        let refresh_interval = Duration::from_secs(60);
+        let test_cases = vec![
+            TestCase {
+                missed_tick_behavior: MissedTickBehavior::Skip,
+                sync_duration: Duration::from_secs(10),
+                expected_skipped: 0,
+                expected_next_tick: Duration::from_secs(120),
+                _description: "case 1 - sync within the refresh interval",
+            },
+            TestCase {
+                missed_tick_behavior: MissedTickBehavior::Skip,
+                sync_duration: Duration::from_secs(290),
+                expected_skipped: 4,
+                expected_next_tick: Duration::from_secs(360),
+                _description: "case 2 - overrun, missed ticks skipped",
+            },
+            TestCase {
+                missed_tick_behavior: MissedTickBehavior::Skip,
+                sync_duration: Duration::from_secs(60),
+                expected_skipped: 0,
+                expected_next_tick: Duration::from_secs(120),
+                _description:
+                    "case 3 - sync as long as the refresh interval, next tick due right away",
+            },
+            TestCase {
+                missed_tick_behavior: MissedTickBehavior::Delay,
+                sync_duration: Duration::from_secs(290),
+                expected_skipped: 0,
+                expected_next_tick: Duration::from_secs(410),
+                _description: "case 4 - overrun, schedule delayed",
+            },
+            TestCase {
+                missed_tick_behavior: MissedTickBehavior::Burst,
+                sync_duration: Duration::from_secs(290),
+                expected_skipped: 0,
+                expected_next_tick: Duration::from_secs(120),
+                _description: "case 5 - overrun, missed ticks bursting",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let mut schedule = RefreshSchedule::new(refresh_interval, Duration::ZERO, Some(42))
+                .with_missed_tick_behavior(tc.missed_tick_behavior);
+            let started_at = Instant::now();
+            // first tick of the schedule is due after a refresh interval, synced for `sync_duration`
+            schedule.next_tick = started_at + refresh_interval;
+
+            // execute:
+            let skipped =
+                schedule.schedule_next_tick(started_at + refresh_interval + tc.sync_duration);
+
+            // verify:
+            assert_eq!(tc.expected_skipped, skipped, "{}", tc._description);
+            assert_eq!(
+                started_at + tc.expected_next_tick,
+                schedule.next_tick,
+                "{}",
+                tc._description
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_schedule_slow_sync_test() {
+        // setup:
+        let refresh_interval = Duration::from_millis(100);
+        let mut schedule = RefreshSchedule::new(refresh_interval, Duration::ZERO, Some(42));
+        // a fake sync overrunning 3 refresh intervals
+        let slow_sync = || tokio::time::sleep(Duration::from_millis(350));
+
+        // execute:
+        schedule.tick().await;
+        slow_sync().await;
+        // tick missed while syncing, due right away
+        schedule.tick().await;
+        let ticked_at = Instant::now();
+        schedule.tick().await;
+
+        // verify:
+        // back on schedule at 400ms instead of syncing back to back for the missed ticks
+        assert!(ticked_at.elapsed() >= Duration::from_millis(25));
+    }
+
+    #[test]
+    fn refresh_schedule_jitter_test() {
+        struct TestCase<'a> {
+            jitter: Duration,
+            expected_min: Duration,
+            expected_max: Duration,
+            _description: &'a str,
+        }
+
+        let refresh_interval = Duration::from_secs(60);
+        let test_cases = vec![
+            TestCase {
+                jitter: Duration::ZERO,
+                expected_min: refresh_interval,
+                expected_max: refresh_interval,
+                _description: "no jitter keeps the exact refresh interval",
+            },
+            TestCase {
+                jitter: Duration::from_secs(10),
+                expected_min: Duration::from_secs(50),
+                expected_max: Duration::from_secs(70),
+                _description: "delays within jitter around the refresh interval",
+            },
+            TestCase {
+                jitter: Duration::from_secs(600),
+                expected_min: Duration::ZERO,
+                expected_max: Duration::from_secs(120),
+                _description: "jitter capped at the refresh interval",
+            },
+        ];
+
+        for tc in test_cases {
+            // setup:
+            let mut schedule = RefreshSchedule::new(refresh_interval, tc.jitter, Some(42));
+            let mut same_seed_schedule =
+                RefreshSchedule::new(refresh_interval, tc.jitter, Some(42));
+
+            // execute:
+            let delays: Vec<Duration> = (0..100).map(|_| schedule.next_delay()).collect();
+            let same_seed_delays: Vec<Duration> =
+                (0..100).map(|_| same_seed_schedule.next_delay()).collect();
+
+            // verify:
+            assert!(
+                delays
+                    .iter()
+                    .all(|d| (tc.expected_min..=tc.expected_max).contains(d)),
+                "{}",
+                tc._description
+            );
+            assert_eq!(
+                tc.jitter.is_zero(),
+                delays.iter().all(|d| *d == delays[0]),
+                "{}",
+                tc._description
+            );
+            assert_eq!(delays, same_seed_delays, "{}", tc._description);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{Key, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Service name traces are exported under, unless set through `OTEL_SERVICE_NAME` or `OTEL_RESOURCE_ATTRIBUTES`.
+const SERVICE_NAME: &str = "iam-eks-user-mapper";
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum TelemetryError {
+    #[error("Cannot setup OTLP traces exporter to `{endpoint}`: {raw_message}")]
+    CannotSetupExporter {
+        endpoint: Arc<str>,
+        raw_message: Arc<str>,
+    },
+    #[error("Cannot flush exported traces: {raw_message}")]
+    CannotFlush { raw_message: Arc<str> },
+}
+
+/// Exports spans to an OTLP gRPC endpoint, e.q: an OpenTelemetry collector, Tempo or Jaeger.
+pub struct OtlpTracing {
+    provider: TracerProvider,
+}
+
+impl OtlpTracing {
+    /// Spans are exported in batches in the background, `OTEL_*` env vars tuning the exporter and the
+    /// resource the way OpenTelemetry SDKs do.
+    pub fn new(endpoint: &str) -> Result<OtlpTracing, TelemetryError> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| TelemetryError::CannotSetupExporter {
+                endpoint: Arc::from(endpoint),
+                raw_message: Arc::from(e.to_string()),
+            })?;
+
+        Ok(OtlpTracing {
+            provider: TracerProvider::builder()
+                .with_batch_exporter(exporter, runtime::Tokio)
+                .with_resource(resource())
+                .build(),
+        })
+    }
+
+    pub fn tracer(&self) -> Tracer {
+        self.provider.tracer(SERVICE_NAME)
+    }
+
+    /// Exports spans not exported yet, blocking until done.
+    pub fn shutdown(&self) -> Result<(), TelemetryError> {
+        self.provider
+            .shutdown()
+            .map_err(|e| TelemetryError::CannotFlush {
+                raw_message: Arc::from(e.to_string()),
+            })
+    }
+}
+
+/// Resource detected from `OTEL_*` env vars, named after the tool if they don't name it.
+fn resource() -> Resource {
+    let resource = Resource::default();
+    // SDK fallback when no env var sets the service name
+    let unnamed = resource
+        .get(Key::new("service.name"))
+        .map(|name| name.as_str().starts_with("unknown_service"))
+        .unwrap_or(true);
+
+    match unnamed {
+        true => resource.merge(&Resource::new([KeyValue::new(
+            "service.name",
+            SERVICE_NAME,
+        )])),
+        false => resource,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telemetry::{OtlpTracing, TelemetryError};
+
+    #[tokio::test]
+    async fn otlp_tracing_test() {
+        struct TestCase<'a> {
+            endpoint: &'a str,
+            expected_ok: bool,
+            _description: &'a str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                endpoint: "http://localhost:4317",
+                expected_ok: true,
+                _description: "case 1 - valid endpoint, connected lazily",
+            },
+            TestCase {
+                endpoint: "not an endpoint",
+                expected_ok: false,
+                _description: "case 2 - invalid endpoint",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let res = OtlpTracing::new(tc.endpoint);
+
+            // verify:
+            match res {
+                Ok(otlp_tracing) => {
+                    assert!(tc.expected_ok, "{}", tc._description);
+                    // nothing to export, not reaching the endpoint
+                    let shutdown = tokio::task::spawn_blocking(move || otlp_tracing.shutdown())
+                        .await
+                        .unwrap();
+                    assert_eq!(Ok(()), shutdown, "{}", tc._description);
+                }
+                Err(e) => {
+                    assert!(!tc.expected_ok, "{}: {e}", tc._description);
+                    assert!(
+                        matches!(e, TelemetryError::CannotSetupExporter { .. }),
+                        "{}",
+                        tc._description
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use crate::kubernetes::aws_auth::{AwsAuthDiff, AwsAuthDiffSummary, AwsAuthEntry};
+use crate::kubernetes::{KubernetesRole, KubernetesUser};
+use serde::{Serialize, Serializer};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// Outcome of a sync, answered as JSON to on-demand sync requests.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SyncReport {
+    Succeeded {
+        summary: AwsAuthDiffSummary,
+        #[serde(serialize_with = "serialize_arns")]
+        users_added: Vec<KubernetesUser>,
+        #[serde(serialize_with = "serialize_arns")]
+        users_removed: Vec<KubernetesUser>,
+        /// Users left in place, modified ones with their new groups.
+        #[serde(serialize_with = "serialize_arns")]
+        users_kept: Vec<KubernetesUser>,
+        #[serde(serialize_with = "serialize_arns")]
+        roles_added: Vec<KubernetesRole>,
+        #[serde(serialize_with = "serialize_arns")]
+        roles_removed: Vec<KubernetesRole>,
+        /// Roles left in place, modified ones with their new groups.
+        #[serde(serialize_with = "serialize_arns")]
+        roles_kept: Vec<KubernetesRole>,
+        /// Time the sync took, IAM lookups included.
+        #[serde(rename = "duration_seconds", serialize_with = "serialize_seconds")]
+        duration: Duration,
+    },
+    Failed {
+        error: String,
+    },
+    /// Nothing synced by this instance, e.q: not being the leader.
+    Skipped {
+        reason: String,
+    },
+}
+
+impl SyncReport {
+    /// Report of a sync having made `diff` changes, entries of all synced targets put together.
+    pub fn succeeded(diff: AwsAuthDiff, duration: Duration) -> SyncReport {
+        SyncReport::Succeeded {
+            summary: diff.summary(),
+            users_kept: diff.users.kept(),
+            roles_kept: diff.roles.kept(),
+            users_added: diff.users.added,
+            users_removed: diff.users.removed,
+            roles_added: diff.roles.added,
+            roles_removed: diff.roles.removed,
+            duration,
+        }
+    }
+}
+
+/// Entries as their ARN, groups being left out of answers.
+fn serialize_arns<T: AwsAuthEntry, S: Serializer>(
+    entries: &[T],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(entries.iter().map(|e| e.arn().to_string()))
+}
+
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Requests syncs on demand, e.q: on `POST /sync` or `SIGUSR1`, outside of the refresh schedule.
+#[derive(Clone)]
+pub struct SyncTrigger {
+    requests: mpsc::Sender<()>,
+    /// Number of the last sync started by the loop.
+    started: Arc<AtomicU64>,
+    /// Number and report of the last sync finished by the loop.
+    reports: watch::Receiver<(u64, Option<SyncReport>)>,
+}
+
+/// Sync loop side of a `SyncTrigger`, waiting for requests and publishing reports.
+pub struct TriggeredSyncs {
+    requests: mpsc::Receiver<()>,
+    started: Arc<AtomicU64>,
+    reports: watch::Sender<(u64, Option<SyncReport>)>,
+}
+
+/// Trigger and its sync loop side, a single request being kept pending at most for concurrent requests made
+/// while a sync is running to end up in a single follow-up sync.
+pub fn sync_trigger() -> (SyncTrigger, TriggeredSyncs) {
+    let (requests_sender, requests) = mpsc::channel(1);
+    let (reports_sender, reports) = watch::channel((0, None));
+    let started = Arc::new(AtomicU64::new(0));
+
+    (
+        SyncTrigger {
+            requests: requests_sender,
+            started: started.clone(),
+            reports,
+        },
+        TriggeredSyncs {
+            requests,
+            started,
+            reports: reports_sender,
+        },
+    )
+}
+
+impl SyncTrigger {
+    /// Requests a sync, coalesced with any other request not served yet.
+    pub fn request(&self) {
+        let _ = self.requests.try_send(());
+    }
+
+    /// Requests a sync and waits for its report, `None` if the sync loop stopped.
+    pub async fn sync(&self) -> Option<SyncReport> {
+        let mut reports = self.reports.clone();
+        // a sync already running may have read IAM before the change the request is made for
+        let running = self.started.load(Ordering::SeqCst);
+        self.request();
+        loop {
+            if let (number, Some(report)) = &*reports.borrow_and_update() {
+                if *number > running {
+                    return Some(report.clone());
+                }
+            }
+            reports.changed().await.ok()?;
+        }
+    }
+}
+
+impl TriggeredSyncs {
+    /// Resolves on the next request, `None` once no trigger is left.
+    pub async fn requested(&mut self) -> Option<()> {
+        self.requests.recv().await
+    }
+
+    /// Records a sync starting, serving requests made so far, returning its number to report it.
+    pub fn started(&mut self) -> u64 {
+        while self.requests.try_recv().is_ok() {}
+        self.started.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn finished(&self, number: u64, report: SyncReport) {
+        self.reports.send_replace((number, Some(report)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kubernetes::aws_auth::{AwsAuthDiff, EntriesDiff};
+    use crate::kubernetes::tests::iam_user;
+    use crate::kubernetes::{
+        IamArn, KubernetesGroupName, KubernetesRole, KubernetesUser, SyncedBy,
+    };
+    use crate::trigger::{sync_trigger, SyncReport};
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+
+    fn role(name: &str) -> KubernetesRole {
+        KubernetesRole::new(
+            IamArn::new(&format!("arn:aws:iam::123456789012:role/{name}")),
+            None,
+            Some(name.to_string()),
+            BTreeSet::from_iter(vec![KubernetesGroupName::new("system:nodes")]),
+            Some(SyncedBy::IamEksUserMapper),
+        )
+    }
+
+    #[tokio::test]
+    async fn sync_trigger_coalesces_requests_test() {
+        // setup:
+        let (sync_trigger, mut triggered_syncs) = sync_trigger();
+
+        // execute:
+        for _ in 0..5 {
+            sync_trigger.request();
+        }
+
+        // verify:
+        assert_eq!(Some(()), triggered_syncs.requested().await);
+        let number = triggered_syncs.started();
+        // requests made while a sync is running end up in a single follow-up sync
+        sync_trigger.request();
+        sync_trigger.request();
+        triggered_syncs.finished(
+            number,
+            SyncReport::succeeded(AwsAuthDiff::default(), Duration::from_millis(1500)),
+        );
+        assert_eq!(Some(()), triggered_syncs.requested().await);
+        triggered_syncs.started();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), triggered_syncs.requested())
+                .await
+                .is_err(),
+            "no request should be left pending"
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_trigger_sync_test() {
+        // setup:
+        let (sync_trigger, mut triggered_syncs) = sync_trigger();
+        // a sync already running when the request is made
+        let running = triggered_syncs.started();
+        let sync_loop = tokio::spawn(async move {
+            triggered_syncs.finished(
+                running,
+                SyncReport::Failed {
+                    error: "stale".to_string(),
+                },
+            );
+            triggered_syncs.requested().await;
+            let number = triggered_syncs.started();
+            triggered_syncs.finished(
+                number,
+                SyncReport::succeeded(AwsAuthDiff::default(), Duration::from_millis(1500)),
+            );
+            triggered_syncs
+        });
+
+        // execute:
+        let report = sync_trigger.sync().await;
+
+        // verify:
+        assert_eq!(
+            Some(SyncReport::succeeded(
+                AwsAuthDiff::default(),
+                Duration::from_millis(1500)
+            )),
+            report
+        );
+        // no report once the sync loop stopped
+        drop(sync_loop.await.unwrap());
+        assert_eq!(None, sync_trigger.sync().await);
+    }
+
+    #[test]
+    fn sync_report_json_test() {
+        // execute & verify:
+        assert_eq!(
+            r#"{"status":"failed","error":"access denied"}"#,
+            serde_json::to_string(&SyncReport::Failed {
+                error: "access denied".to_string(),
+            })
+            .unwrap()
+        );
+        assert_eq!(
+            r#"{"status":"succeeded","summary":{"users":{"added":0,"removed":0,"modified":0,"unchanged":0},"roles":{"added":0,"removed":0,"modified":0,"unchanged":0}},"users_added":[],"users_removed":[],"users_kept":[],"roles_added":[],"roles_removed":[],"roles_kept":[],"duration_seconds":1.5}"#,
+            serde_json::to_string(&SyncReport::succeeded(
+                AwsAuthDiff::default(),
+                Duration::from_millis(1500)
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            r#"{"status":"succeeded","summary":{"users":{"added":1,"removed":0,"modified":0,"unchanged":1},"roles":{"added":0,"removed":1,"modified":0,"unchanged":0}},"users_added":["arn:aws:iam::123456789012:user/alice"],"users_removed":[],"users_kept":["arn:aws:iam::123456789012:user/bob"],"roles_added":[],"roles_removed":["arn:aws:iam::123456789012:role/nodes"],"roles_kept":[],"duration_seconds":1.5}"#,
+            serde_json::to_string(&SyncReport::succeeded(
+                AwsAuthDiff {
+                    users: EntriesDiff {
+                        added: vec![iam_user("alice", &["system:masters"])],
+                        unchanged: vec![iam_user("bob", &["view"])],
+                        ..Default::default()
+                    },
+                    roles: EntriesDiff {
+                        removed: vec![role("nodes")],
+                        ..Default::default()
+                    },
+                },
+                Duration::from_millis(1500)
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn sync_report_succeeded_test() {
+        // setup:
+        struct TestCase {
+            input: AwsAuthDiff,
+            expected_users: [Vec<KubernetesUser>; 3],
+            expected_roles: [Vec<KubernetesRole>; 3],
+            _description: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: AwsAuthDiff::default(),
+                expected_users: [vec![], vec![], vec![]],
+                expected_roles: [vec![], vec![], vec![]],
+                _description: "case 1 - nothing synced",
+            },
+            TestCase {
+                input: AwsAuthDiff {
+                    users: EntriesDiff {
+                        added: vec![iam_user("alice", &["system:masters"])],
+                        removed: vec![iam_user("bob", &["view"])],
+                        unchanged: vec![iam_user("carol", &["view"])],
+                        ..Default::default()
+                    },
+                    roles: EntriesDiff {
+                        added: vec![role("karpenter")],
+                        removed: vec![role("nodes")],
+                        ..Default::default()
+                    },
+                },
+                expected_users: [
+                    vec![iam_user("alice", &["system:masters"])],
+                    vec![iam_user("bob", &["view"])],
+                    vec![iam_user("carol", &["view"])],
+                ],
+                expected_roles: [vec![role("karpenter")], vec![role("nodes")], vec![]],
+                _description: "case 2 - entries added, removed and unchanged",
+            },
+            TestCase {
+                input: AwsAuthDiff {
+                    users: EntriesDiff {
+                        modified: vec![(iam_user("dave", &["view"]), iam_user("dave", &["edit", "view"]))],
+                        untouched: vec![iam_user("erin", &["view"])],
+                        unchanged: vec![iam_user("carol", &["view"])],
+                        ..Default::default()
+                    },
+                    roles: EntriesDiff::default(),
+                },
+                expected_users: [
+                    vec![],
+                    vec![],
+                    vec![
+                        iam_user("carol", &["view"]),
+                        iam_user("dave", &["edit", "view"]),
+                        iam_user("erin", &["view"]),
+                    ],
+                ],
+                expected_roles: [vec![], vec![], vec![]],
+                _description: "case 3 - modified and not synced entries are kept, modified ones with their new groups",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let report = SyncReport::succeeded(tc.input, Duration::from_secs(1));
+
+            // verify:
+            match report {
+                SyncReport::Succeeded {
+                    users_added,
+                    users_removed,
+                    users_kept,
+                    roles_added,
+                    roles_removed,
+                    roles_kept,
+                    ..
+                } => {
+                    assert_eq!(
+                        tc.expected_users,
+                        [users_added, users_removed, users_kept],
+                        "{}",
+                        tc._description
+                    );
+                    assert_eq!(
+                        tc.expected_roles,
+                        [roles_added, roles_removed, roles_kept],
+                        "{}",
+                        tc._description
+                    );
+                }
+                report => panic!("{}: unexpected report {report:?}", tc._description),
+            }
+        }
+    }
+}